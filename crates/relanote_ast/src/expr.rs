@@ -1,8 +1,8 @@
-use relanote_core::{InternedStr, Spanned};
+use relanote_core::{InternedStr, Span, Spanned};
 
 use crate::music::{
-    AbsolutePitchLit, Articulation, Block, EnvelopeLit, IntervalLit, LayerExpr, PartExpr,
-    SectionExpr, Tuplet,
+    AbsolutePitchLit, Articulation, Block, ContextExpr, DurationLit, EnvelopeLit, IntervalLit,
+    KitExpr, LayerExpr, PartExpr, SectionExpr, Tuplet,
 };
 use crate::pattern::Pattern;
 use crate::types::TypeAnnotation;
@@ -73,6 +73,9 @@ pub enum Expr {
     /// Envelope env(from, to, duration)
     Envelope(EnvelopeLit),
 
+    /// Duration literal (1/8, 2 beats, 1 bar)
+    Duration(DurationLit),
+
     // ===== Higher-level Music Structures =====
     /// Part expression
     Part(PartExpr),
@@ -83,6 +86,12 @@ pub enum Expr {
     /// Layer expression
     Layer(LayerExpr),
 
+    /// Drum kit for percussion blocks: kit { x: Kick, o: Snare }
+    Kit(KitExpr),
+
+    /// Render settings scoped to a sub-expression: context { tempo = 140 } in body
+    Context(Box<ContextExpr>),
+
     // ===== Functions =====
     /// Lambda expression: \x -> body
     Lambda(Lambda),
@@ -153,6 +162,17 @@ pub struct Lambda {
 pub struct Application {
     pub func: Box<Spanned<Expr>>,
     pub args: Vec<Spanned<Expr>>,
+    /// Arguments passed by name: `delay(time: 250, mix: 0.3)`
+    pub named: Vec<NamedArg>,
+}
+
+/// Argument passed by name in a call
+#[derive(Clone, Debug)]
+pub struct NamedArg {
+    pub name: Ident,
+    /// Span of the name
+    pub name_span: Span,
+    pub value: Spanned<Expr>,
 }
 
 /// Pipe expression: left |> right
@@ -179,6 +199,7 @@ pub enum BinaryOp {
     Mul,
     Div,
     Mod,
+    Pow, // ^
 
     // Comparison
     Eq,
@@ -195,6 +216,8 @@ pub enum BinaryOp {
     // Music-specific
     Concat,  // ++
     Compose, // >>
+    Range,   // ..
+    Overlay, // &
 }
 
 /// Unary operation
@@ -23,9 +23,18 @@ pub enum Item {
     /// Set binding: set key = C4 (for built-in configuration variables)
     SetBinding(SetBinding),
 
+    /// Song metadata: meta { title: "Etude", composer: "..." }
+    Meta(MetaDecl),
+
     /// Function definition: let f x y = expr (sugar for let f = \x -> \y -> expr)
     FunctionDef(FunctionDef),
 
+    /// Test run by `relanote test`: test "chorus" { chorus }
+    Test(TestDecl),
+
+    /// Output of the program: render song, or render "demo" = song
+    Render(RenderDecl),
+
     /// Import declaration (JavaScript-style)
     Import(ImportDecl),
 
@@ -42,12 +51,58 @@ pub enum Item {
     ExprStmt(Spanned<Expr>),
 }
 
+impl Item {
+    /// The name a definition binds, if it binds a single name
+    pub fn defined_name(&self) -> Option<&Ident> {
+        match self {
+            Item::ScaleDef(def) => Some(&def.name),
+            Item::ChordDef(def) => Some(&def.name),
+            Item::SynthDef(def) => Some(&def.name),
+            Item::LetBinding(binding) => match &binding.pattern.node {
+                Pattern::Ident(ident) => Some(ident),
+                _ => None,
+            },
+            Item::FunctionDef(def) => Some(&def.name),
+            Item::Export(ExportDecl::Definition(item)) => item.defined_name(),
+            _ => None,
+        }
+    }
+
+    /// The doc comment of a definition
+    pub fn doc(&self) -> Option<&str> {
+        match self {
+            Item::ScaleDef(def) => def.doc.as_deref(),
+            Item::ChordDef(def) => def.doc.as_deref(),
+            Item::SynthDef(def) => def.doc.as_deref(),
+            Item::LetBinding(binding) => binding.doc.as_deref(),
+            Item::FunctionDef(def) => def.doc.as_deref(),
+            Item::Export(ExportDecl::Definition(item)) => item.doc(),
+            _ => None,
+        }
+    }
+
+    /// Attach a doc comment to a definition; other items have none
+    pub fn set_doc(&mut self, doc: Option<String>) {
+        match self {
+            Item::ScaleDef(def) => def.doc = doc,
+            Item::ChordDef(def) => def.doc = doc,
+            Item::SynthDef(def) => def.doc = doc,
+            Item::LetBinding(binding) => binding.doc = doc,
+            Item::FunctionDef(def) => def.doc = doc,
+            Item::Export(ExportDecl::Definition(item)) => item.set_doc(doc),
+            _ => {}
+        }
+    }
+}
+
 /// Let binding at the top level
 #[derive(Clone, Debug)]
 pub struct LetBinding {
     pub pattern: Spanned<Pattern>,
     pub type_ann: Option<TypeAnnotation>,
     pub value: Spanned<Expr>,
+    /// Doc comment above the binding
+    pub doc: Option<String>,
 }
 
 /// Set binding for built-in configuration variables (key, tempo)
@@ -55,6 +110,59 @@ pub struct LetBinding {
 pub struct SetBinding {
     pub name: Ident,
     pub value: Spanned<Expr>,
+    /// Mode following a key's tonic (`set key = D minor`)
+    pub mode: Option<Ident>,
+}
+
+impl SetBinding {
+    /// The prelude scale a key mode stands for
+    pub fn mode_scale(mode: &str) -> Option<&'static str> {
+        match mode {
+            "major" | "ionian" => Some("Major"),
+            "minor" | "aeolian" => Some("Minor"),
+            "dorian" => Some("Dorian"),
+            "phrygian" => Some("Phrygian"),
+            "lydian" => Some("Lydian"),
+            "mixolydian" => Some("Mixolydian"),
+            "locrian" => Some("Locrian"),
+            _ => None,
+        }
+    }
+}
+
+/// Song metadata written to the rendered files
+#[derive(Clone, Debug)]
+pub struct MetaDecl {
+    pub fields: Vec<Spanned<MetaField>>,
+}
+
+/// A field of a meta block: `title: "Etude"`
+#[derive(Clone, Debug)]
+pub struct MetaField {
+    pub name: Ident,
+    pub value: Spanned<Expr>,
+}
+
+impl MetaDecl {
+    /// Fields a meta block can have
+    pub const FIELDS: [&'static str; 3] = ["title", "composer", "copyright"];
+}
+
+/// A test: the body passes when it evaluates to `true`, or to music that
+/// matches its snapshot
+#[derive(Clone, Debug)]
+pub struct TestDecl {
+    pub name: String,
+    pub body: Spanned<Expr>,
+}
+
+/// A song the program renders; with render items, these are the program's
+/// outputs instead of its last expression
+#[derive(Clone, Debug)]
+pub struct RenderDecl {
+    /// Name the output file takes: render "verse_demo" = verse
+    pub name: Option<String>,
+    pub target: Spanned<Expr>,
 }
 
 /// Function definition (desugared to LetBinding with Lambda)
@@ -64,6 +172,24 @@ pub struct FunctionDef {
     pub params: Vec<Spanned<Pattern>>,
     pub return_type: Option<TypeAnnotation>,
     pub body: Spanned<Expr>,
+    /// Doc comment above the definition
+    pub doc: Option<String>,
+}
+
+impl FunctionDef {
+    /// The function's declared type, when every parameter and the result
+    /// are annotated
+    pub fn signature(&self) -> Option<TypeAnnotation> {
+        let params = self
+            .params
+            .iter()
+            .map(|param| match &param.node {
+                Pattern::Annotated(_, ann) => Some(ann.clone()),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()?;
+        Some(TypeAnnotation::function(params, self.return_type.clone()?))
+    }
 }
 
 /// Import declaration
@@ -124,6 +250,18 @@ pub struct UsePath {
     pub kind: UseKind,
 }
 
+impl UsePath {
+    /// Path of the module being imported from, e.g. `synths::bass` for both
+    /// `use synths::bass::AcidBass` and `use synths::bass::*`
+    pub fn module_name(&self) -> String {
+        let segments: Vec<String> = self.segments.iter().map(|s| s.name.to_string()).collect();
+        match self.kind {
+            UseKind::Simple if segments.len() >= 2 => segments[..segments.len() - 1].join("::"),
+            UseKind::Simple | UseKind::Glob | UseKind::Group(_) => segments.join("::"),
+        }
+    }
+}
+
 /// What kind of import to perform at the end of a use path
 #[derive(Clone, Debug)]
 pub enum UseKind {
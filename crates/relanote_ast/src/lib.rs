@@ -37,6 +37,19 @@ impl NodeId {
 pub struct Comment {
     pub text: String,
     pub span: relanote_core::Span,
+    /// Whether the comment follows code on the same line
+    pub trailing: bool,
+}
+
+impl Comment {
+    /// Text of a doc comment (`--- ...` or `--| ...`) without its marker
+    pub fn doc(&self) -> Option<&str> {
+        let text = self
+            .text
+            .strip_prefix("---")
+            .or_else(|| self.text.strip_prefix("--|"))?;
+        Some(text.strip_prefix(' ').unwrap_or(text).trim_end())
+    }
 }
 
 /// A complete relanote program
@@ -64,4 +77,19 @@ impl Program {
             comments: Vec::new(),
         }
     }
+
+    /// Names made public by `export` items; everything else is private to
+    /// the module
+    pub fn exported_names(&self) -> Vec<&Ident> {
+        let mut names = Vec::new();
+        for item in &self.items {
+            match &item.node {
+                Item::Export(ExportDecl::Named(idents))
+                | Item::Export(ExportDecl::ReExport { items: idents, .. }) => names.extend(idents),
+                Item::Export(ExportDecl::Definition(def)) => names.extend(def.defined_name()),
+                _ => {}
+            }
+        }
+        names
+    }
 }
@@ -1,7 +1,7 @@
 use std::str::FromStr;
 
-use relanote_core::Spanned;
-use relanote_lexer::token::{AbsolutePitchData, Accidental, IntervalQuality};
+use relanote_core::{Span, Spanned};
+use relanote_lexer::token::{AbsolutePitchData, Accidental, IntervalData, IntervalQuality};
 
 use crate::expr::{Expr, Ident};
 
@@ -48,12 +48,16 @@ impl FromStr for Dynamic {
     }
 }
 
-/// Interval literal (parsed from M3, P5+, m7-, etc.)
-#[derive(Clone, Debug, PartialEq, Eq)]
+/// Interval literal (parsed from M3, P5+, m7-, 386c, etc.)
+#[derive(Clone, Debug, PartialEq)]
 pub struct IntervalLit {
     pub quality: IntervalQuality,
     pub degree: u8,
     pub accidentals: Vec<Accidental>,
+    /// Exact size in cents (386c), for intervals outside 12-tone equal temperament
+    pub cents: Option<f64>,
+    /// Size in semitones, as written with `st` (12st)
+    pub steps: Option<i32>,
 }
 
 impl IntervalLit {
@@ -62,6 +66,24 @@ impl IntervalLit {
             quality,
             degree,
             accidentals: Vec::new(),
+            cents: None,
+            steps: None,
+        }
+    }
+
+    /// An interval of an exact size in cents
+    pub fn from_cents(cents: f64) -> Self {
+        Self {
+            cents: Some(cents),
+            ..Self::new(IntervalQuality::Perfect, 1)
+        }
+    }
+
+    /// An interval of a number of semitones
+    pub fn from_semitones(steps: i32) -> Self {
+        Self {
+            steps: Some(steps),
+            ..Self::new(IntervalQuality::Perfect, 1)
         }
     }
 
@@ -72,28 +94,15 @@ impl IntervalLit {
 
     /// Calculate the semitone offset from the root
     pub fn semitones(&self) -> i32 {
+        if let Some(steps) = self.steps {
+            return steps;
+        }
+        if let Some(cents) = self.cents {
+            return (cents / 100.0).round() as i32;
+        }
+
         // Base semitones for each degree (assuming major scale)
         let base = match (self.quality, self.degree) {
-            // Unison
-            (_, 1) => 0,
-            // Second
-            (IntervalQuality::Major, 2) => 2,
-            (IntervalQuality::Minor, 2) => 1,
-            // Third
-            (IntervalQuality::Major, 3) => 4,
-            (IntervalQuality::Minor, 3) => 3,
-            // Fourth
-            (IntervalQuality::Perfect, 4) => 5,
-            (IntervalQuality::Augmented, 4) => 6,
-            // Fifth
-            (IntervalQuality::Perfect, 5) => 7,
-            (IntervalQuality::Diminished, 5) => 6,
-            // Sixth
-            (IntervalQuality::Major, 6) => 9,
-            (IntervalQuality::Minor, 6) => 8,
-            // Seventh
-            (IntervalQuality::Major, 7) => 11,
-            (IntervalQuality::Minor, 7) => 10,
             // Octave and beyond
             (_, n) if n > 7 => {
                 let octaves = (n - 1) / 7;
@@ -101,6 +110,26 @@ impl IntervalLit {
                 let base_interval = IntervalLit::new(self.quality, remainder);
                 base_interval.semitones() + (octaves as i32 * 12)
             }
+            // Perfect degrees: unison, fourth and fifth
+            (quality, degree @ (1 | 4 | 5)) => {
+                let perfect = [0, 0, 0, 5, 7][degree as usize - 1];
+                match quality {
+                    IntervalQuality::Augmented => perfect + 1,
+                    IntervalQuality::Diminished if degree != 1 => perfect - 1,
+                    _ => perfect,
+                }
+            }
+            // Major degrees: second, third, sixth and seventh
+            (quality, degree @ (2 | 3 | 6 | 7)) => {
+                let major = [0, 2, 4, 0, 0, 9, 11][degree as usize - 1];
+                match quality {
+                    IntervalQuality::Major => major,
+                    IntervalQuality::Minor => major - 1,
+                    IntervalQuality::Augmented => major + 1,
+                    IntervalQuality::Diminished => major - 2,
+                    IntervalQuality::Perfect => 0,
+                }
+            }
             // Default case
             _ => 0,
         };
@@ -120,7 +149,20 @@ impl IntervalLit {
 
     /// Calculate the cent offset from the root (100 cents = 1 semitone)
     pub fn cents(&self) -> f64 {
-        self.semitones() as f64 * 100.0
+        self.cents
+            .unwrap_or_else(|| self.semitones() as f64 * 100.0)
+    }
+}
+
+impl From<IntervalData> for IntervalLit {
+    fn from(data: IntervalData) -> Self {
+        Self {
+            quality: data.quality,
+            degree: data.degree,
+            accidentals: data.accidentals,
+            cents: None,
+            steps: None,
+        }
     }
 }
 
@@ -173,10 +215,13 @@ impl From<AbsolutePitchData> for AbsolutePitchLit {
 
 /// Articulation type
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Articulation {
     Staccato,   // *
     Accent,     // ^
     Portamento, // ~
+    Tie,        // ~ after the duration: held into the next note
+    Tied,       // ~ before the pitch: continues the previous note
 }
 
 /// A pitch in a block (can be interval or scale index)
@@ -186,8 +231,9 @@ pub enum Pitch {
     Interval(IntervalLit),
     /// Scale index (<1>, <3>, etc.)
     ScaleIndex(u8),
-    /// Scale index with accidentals (<4+>)
-    ScaleIndexMod(u8, Vec<Accidental>),
+    /// Scale index with accidentals (<4+>) and octave marks (<3'>, <5,>):
+    /// the octave is the number of `'` less the number of `,`
+    ScaleIndexMod(u8, Vec<Accidental>, i8),
     /// Root reference (R)
     Root,
 }
@@ -199,23 +245,29 @@ pub enum Slot {
     Note {
         pitch: Spanned<Pitch>,
         articulations: Vec<Articulation>,
-        /// Explicit duration in slots (e.g., :2 means this note takes 2 slot positions)
-        duration: Option<u32>,
+        /// Explicit duration (e.g., :2 for two beats, :1/8 for an eighth note)
+        duration: Option<DurationLit>,
     },
     /// Rest (-) with optional duration
     Rest {
-        /// Explicit duration in slots (e.g., :2 means this rest takes 2 slot positions)
-        duration: Option<u32>,
+        /// Explicit duration (e.g., :2 for two beats, :1/8 for an eighth rest)
+        duration: Option<DurationLit>,
     },
     /// Chord (multiple simultaneous pitches)
     Chord {
         pitches: Vec<Spanned<Pitch>>,
         articulations: Vec<Articulation>,
-        /// Explicit duration in slots
-        duration: Option<u32>,
+        /// Explicit duration
+        duration: Option<DurationLit>,
     },
     /// Nested tuplet
     Tuplet(Tuplet),
+    /// Drum hit in a percussion block (`x` in `| x - o - |`), played by a kit
+    Hit {
+        name: Ident,
+        articulations: Vec<Articulation>,
+        duration: Option<DurationLit>,
+    },
 }
 
 /// Block: | slot slot slot | or | slot slot slot |:n
@@ -255,13 +307,59 @@ impl Block {
     pub fn duration_beats(&self) -> f64 {
         self.beats.unwrap_or(1.0)
     }
+
+    /// Length of the block in beats as written, given the length of a bar:
+    /// each slot's explicit duration, or an equal share of the block's
+    /// duration
+    ///
+    /// `None` when a tuplet's length is an expression rather than a literal,
+    /// and only known once evaluated.
+    pub fn written_beats(&self, beats_per_bar: f64) -> Option<f64> {
+        let share = self.duration_beats() / self.slots.len().max(1) as f64;
+        self.slots
+            .iter()
+            .map(|slot| slot_beats(&slot.node, beats_per_bar, share))
+            .sum()
+    }
 }
 
-/// Tuplet: { contents }:n
+/// Length of a slot in beats as written, `share` when it has no duration
+fn slot_beats(slot: &Slot, beats_per_bar: f64, share: f64) -> Option<f64> {
+    match slot {
+        Slot::Note { duration, .. }
+        | Slot::Rest { duration }
+        | Slot::Chord { duration, .. }
+        | Slot::Hit { duration, .. } => Some(duration.map_or(share, |d| d.to_beats(beats_per_bar))),
+        Slot::Tuplet(tuplet) => {
+            let in_time_of = tuplet.in_time_of.as_ref().map(|m| &m.node);
+            match (&tuplet.target_beats.node, in_time_of) {
+                (Expr::Integer(n), None) => Some(*n as f64),
+                (Expr::Float(beats), None) => Some(*beats),
+                (Expr::Duration(duration), None) => Some(duration.to_beats(beats_per_bar)),
+                // n notes in the time of m: the slots, a beat each unless
+                // they say otherwise, scaled by m/n
+                (Expr::Integer(n @ 1..), Some(Expr::Integer(m @ 1..))) => {
+                    let nominal: f64 = tuplet
+                        .contents
+                        .iter()
+                        .map(|slot| slot_beats(&slot.node, beats_per_bar, 1.0))
+                        .sum::<Option<f64>>()?;
+                    Some(nominal * *m as f64 / *n as f64)
+                }
+                _ => None,
+            }
+        }
+    }
+}
+
+/// Tuplet: { contents }:n, or { contents }:n:m for n notes in the time of m
 #[derive(Clone, Debug)]
 pub struct Tuplet {
     pub contents: Vec<Spanned<Slot>>,
+    /// Length of the tuplet, or the `n` of an `n:m` ratio
     pub target_beats: Box<Spanned<Expr>>,
+    /// The `m` of an `n:m` ratio
+    pub in_time_of: Option<Box<Spanned<Expr>>>,
 }
 
 /// Envelope literal: env(from, to, duration)
@@ -276,6 +374,10 @@ pub struct EnvelopeLit {
 #[derive(Clone, Debug)]
 pub struct ScaleDef {
     pub name: Ident,
+    /// Doc comment above the definition
+    pub doc: Option<String>,
+    /// The scale expression a derived scale is built from, such as
+    /// `Major with { P4+ }`; its intervals are then empty
     pub base: Option<Spanned<Expr>>,
     pub intervals: Vec<Spanned<IntervalLit>>,
 }
@@ -284,6 +386,8 @@ pub struct ScaleDef {
 #[derive(Clone, Debug)]
 pub struct ChordDef {
     pub name: Ident,
+    /// Doc comment above the definition
+    pub doc: Option<String>,
     pub intervals: Vec<Spanned<IntervalLit>>,
 }
 
@@ -295,6 +399,8 @@ pub struct ChordDef {
 #[derive(Clone, Debug)]
 pub struct SynthDef {
     pub name: Ident,
+    /// Doc comment above the definition
+    pub doc: Option<String>,
     pub properties: Vec<Spanned<SynthProperty>>,
 }
 
@@ -312,6 +418,8 @@ pub enum SynthProperty {
     /// pitch_env: (start_hz, end_hz, time_seconds)
     /// Used for drum sounds like kicks where pitch sweeps down
     PitchEnvelope(Spanned<Expr>),
+    /// lfo: PitchLfo 5.0 0.2 + AmpLfo 4.0 0.3
+    Lfo(Spanned<Expr>),
 }
 
 /// Part expression: part "instrument" { body }
@@ -337,25 +445,65 @@ pub struct SectionContext {
     pub tempo: Option<Spanned<Expr>>,
 }
 
+/// Drum kit: kit { x: Kick, o: Snare }
+#[derive(Clone, Debug)]
+pub struct KitExpr {
+    pub hits: Vec<KitHit>,
+}
+
+/// A kit entry: the letter used in percussion blocks and the drum it plays
+/// (a drum synth or a GM percussion key)
+#[derive(Clone, Debug)]
+pub struct KitHit {
+    pub name: Ident,
+    /// Span of the name
+    pub name_span: Span,
+    pub drum: Spanned<Expr>,
+}
+
+/// Context expression: context { tempo = 140, key = A3, swing = 0.6 } in body
+///
+/// Scopes render settings to the sections its body produces.
+#[derive(Clone, Debug)]
+pub struct ContextExpr {
+    pub tempo: Option<Spanned<Expr>>,
+    pub key: Option<Spanned<Expr>>,
+    pub swing: Option<Spanned<Expr>>,
+    pub body: Spanned<Expr>,
+}
+
 /// Layer expression: layer [ part1, part2, ... ]
 #[derive(Clone, Debug)]
 pub struct LayerExpr {
     pub parts: Vec<Spanned<Expr>>,
 }
 
-/// Duration unit
+/// A written length of time
 #[derive(Clone, Copy, Debug, PartialEq)]
-pub enum DurationUnit {
-    Bars(u32),
-    Beats(u32),
+pub enum DurationLit {
+    /// Beats: `:2` after a slot, `2 beats`
+    Beats(f64),
+    /// Bars of the song's time signature: `1 bar`
+    Bars(f64),
+    /// Fraction of a whole note, a quarter note being one beat: `1/8`,
+    /// dotted `1/4.`
+    NoteValue { num: u32, den: u32, dotted: bool },
 }
 
-impl DurationUnit {
-    /// Convert to beats given a time signature numerator
-    pub fn to_beats(&self, beats_per_bar: u32) -> f64 {
-        match self {
-            DurationUnit::Bars(n) => (*n as f64) * (beats_per_bar as f64),
-            DurationUnit::Beats(n) => *n as f64,
+impl DurationLit {
+    /// Length in beats, given the length of a bar in beats
+    pub fn to_beats(&self, beats_per_bar: f64) -> f64 {
+        match *self {
+            DurationLit::Beats(beats) => beats,
+            DurationLit::Bars(bars) => bars * beats_per_bar,
+            DurationLit::NoteValue { num, den, dotted } => {
+                let beats = 4.0 * num as f64 / den as f64;
+                if dotted {
+                    beats * 1.5
+                } else {
+                    beats
+                }
+            }
         }
     }
 }
@@ -387,6 +535,16 @@ mod tests {
             6
         );
 
+        // Augmented fifth = 8 semitones, diminished seventh = 9 semitones
+        assert_eq!(
+            IntervalLit::new(IntervalQuality::Augmented, 5).semitones(),
+            8
+        );
+        assert_eq!(
+            IntervalLit::new(IntervalQuality::Diminished, 7).semitones(),
+            9
+        );
+
         // Perfect fifth with sharp = 8 semitones
         assert_eq!(
             IntervalLit::new(IntervalQuality::Perfect, 5)
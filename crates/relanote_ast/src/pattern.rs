@@ -63,6 +63,14 @@ impl Pattern {
         }
     }
 
+    /// The pattern without its type annotation, if it has one
+    pub fn unannotated(&self) -> &Pattern {
+        match self {
+            Pattern::Annotated(p, _) => p.node.unannotated(),
+            pattern => pattern,
+        }
+    }
+
     /// Get all bound identifiers in this pattern
     pub fn bindings(&self) -> Vec<&Ident> {
         match self {
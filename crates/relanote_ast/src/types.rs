@@ -1,3 +1,5 @@
+use std::fmt;
+
 use crate::expr::Ident;
 
 /// Type annotation in source code
@@ -46,3 +48,37 @@ impl TypeAnnotation {
         })
     }
 }
+
+impl fmt::Display for TypeAnnotation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TypeAnnotation::Named(name) | TypeAnnotation::Var(name) => write!(f, "{}", name.name),
+            TypeAnnotation::Generic(name, args) => {
+                write!(f, "{}<", name.name)?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", arg)?;
+                }
+                write!(f, ">")
+            }
+            TypeAnnotation::Function(param, ret) => match param.as_ref() {
+                TypeAnnotation::Function(..) => write!(f, "({}) -> {}", param, ret),
+                _ => write!(f, "{} -> {}", param, ret),
+            },
+            TypeAnnotation::Tuple(elems) => {
+                write!(f, "(")?;
+                for (i, elem) in elems.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", elem)?;
+                }
+                write!(f, ")")
+            }
+            TypeAnnotation::Array(elem) => write!(f, "[{}]", elem),
+            TypeAnnotation::Unit => write!(f, "()"),
+        }
+    }
+}
@@ -51,7 +51,11 @@ pub fn walk_expr<V: Visitor>(visitor: &mut V, expr: &Spanned<Expr>) {
 
         Expr::Ident(ident) => visitor.visit_ident(ident),
 
-        Expr::Interval(_) | Expr::AbsolutePitch(_) | Expr::Root | Expr::Articulation(_) => {}
+        Expr::Interval(_)
+        | Expr::AbsolutePitch(_)
+        | Expr::Root
+        | Expr::Articulation(_)
+        | Expr::Duration(_) => {}
 
         Expr::Block(block) => visitor.visit_block(block),
 
@@ -60,6 +64,9 @@ pub fn walk_expr<V: Visitor>(visitor: &mut V, expr: &Spanned<Expr>) {
                 visitor.visit_slot(slot);
             }
             visitor.visit_expr(&tuplet.target_beats);
+            if let Some(in_time_of) = &tuplet.in_time_of {
+                visitor.visit_expr(in_time_of);
+            }
         }
 
         Expr::Envelope(env) => {
@@ -97,6 +104,22 @@ pub fn walk_expr<V: Visitor>(visitor: &mut V, expr: &Spanned<Expr>) {
             }
         }
 
+        Expr::Kit(kit) => {
+            for hit in &kit.hits {
+                visitor.visit_expr(&hit.drum);
+            }
+        }
+
+        Expr::Context(context) => {
+            for setting in [&context.tempo, &context.key, &context.swing]
+                .into_iter()
+                .flatten()
+            {
+                visitor.visit_expr(setting);
+            }
+            visitor.visit_expr(&context.body);
+        }
+
         Expr::Lambda(lambda) => {
             for param in &lambda.params {
                 visitor.visit_pattern(param);
@@ -109,6 +132,9 @@ pub fn walk_expr<V: Visitor>(visitor: &mut V, expr: &Spanned<Expr>) {
             for arg in &app.args {
                 visitor.visit_expr(arg);
             }
+            for arg in &app.named {
+                visitor.visit_expr(&arg.value);
+            }
         }
 
         Expr::Pipe(pipe) => {
@@ -207,7 +233,8 @@ pub fn walk_item<V: Visitor>(visitor: &mut V, item: &Spanned<Item>) {
                     | crate::music::SynthProperty::Envelope(expr)
                     | crate::music::SynthProperty::Filter(expr)
                     | crate::music::SynthProperty::Detune(expr)
-                    | crate::music::SynthProperty::PitchEnvelope(expr) => {
+                    | crate::music::SynthProperty::PitchEnvelope(expr)
+                    | crate::music::SynthProperty::Lfo(expr) => {
                         visitor.visit_expr(expr);
                     }
                 }
@@ -224,6 +251,16 @@ pub fn walk_item<V: Visitor>(visitor: &mut V, item: &Spanned<Item>) {
             visitor.visit_expr(&binding.value);
         }
 
+        Item::Meta(meta) => {
+            for field in &meta.fields {
+                visitor.visit_expr(&field.node.value);
+            }
+        }
+
+        Item::Test(test) => visitor.visit_expr(&test.body),
+
+        Item::Render(render) => visitor.visit_expr(&render.target),
+
         Item::FunctionDef(func_def) => {
             visitor.visit_ident(&func_def.name);
             for param in &func_def.params {
@@ -317,12 +354,15 @@ pub fn walk_block<V: Visitor>(visitor: &mut V, block: &Block) {
 /// Walk through slot contents
 pub fn walk_slot<V: Visitor>(visitor: &mut V, slot: &Spanned<Slot>) {
     match &slot.node {
-        Slot::Note { .. } | Slot::Rest { .. } | Slot::Chord { .. } => {}
+        Slot::Note { .. } | Slot::Rest { .. } | Slot::Chord { .. } | Slot::Hit { .. } => {}
         Slot::Tuplet(tuplet) => {
             for s in &tuplet.contents {
                 visitor.visit_slot(s);
             }
             visitor.visit_expr(&tuplet.target_beats);
+            if let Some(in_time_of) = &tuplet.in_time_of {
+                visitor.visit_expr(in_time_of);
+            }
         }
     }
 }
@@ -361,6 +401,9 @@ pub fn walk_expr_mut<V: MutVisitor>(visitor: &mut V, expr: &mut Spanned<Expr>) {
             for arg in &mut app.args {
                 visitor.visit_expr_mut(arg);
             }
+            for arg in &mut app.named {
+                visitor.visit_expr_mut(&mut arg.value);
+            }
         }
         Expr::Pipe(pipe) => {
             visitor.visit_expr_mut(&mut pipe.left);
@@ -0,0 +1,152 @@
+//! Reference pages for the exported bindings of a module, written by
+//! `relanote doc`
+
+use relanote_ast::{ExportDecl, Item, Program};
+use relanote_types::TypeChecker;
+
+/// What a page is written as
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum DocFormat {
+    /// Markdown, e.g. for a docs site or a repository README
+    Markdown,
+    /// Standalone HTML pages
+    Html,
+}
+
+impl DocFormat {
+    /// Extension of a page file
+    pub fn extension(self) -> &'static str {
+        match self {
+            DocFormat::Markdown => "md",
+            DocFormat::Html => "html",
+        }
+    }
+}
+
+/// An exported binding of a module
+pub struct DocEntry {
+    pub name: String,
+    /// How it reads in code: the definition of a scale or chord, the type
+    /// of anything else
+    pub signature: String,
+    pub doc: Option<String>,
+}
+
+/// The exported bindings of `program`, parsed from `source` and checked by
+/// `checker`, in the order the module defines them
+pub fn module_entries(source: &str, program: &Program, checker: &TypeChecker) -> Vec<DocEntry> {
+    let exported = program.exported_names();
+    program
+        .items
+        .iter()
+        .filter_map(|item| {
+            let node = match &item.node {
+                Item::Export(ExportDecl::Definition(def)) => def.as_ref(),
+                node => node,
+            };
+            let name = node.defined_name()?;
+            if !exported.iter().any(|export| export.name == name.name) {
+                return None;
+            }
+            let signature = match node {
+                Item::ScaleDef(_) | Item::ChordDef(_) => {
+                    let text = &source[item.span.start..item.span.end];
+                    text.strip_prefix("export ").unwrap_or(text).to_string()
+                }
+                _ => {
+                    let ty = match checker.declared_type(&name.name) {
+                        Some(ann) => ann.to_string(),
+                        None => checker.lookup_type(&name.name)?.lettered().to_string(),
+                    };
+                    format!("{} : {}", name.name, ty)
+                }
+            };
+            Some(DocEntry {
+                name: name.name.to_string(),
+                signature,
+                doc: node.doc().map(str::to_string),
+            })
+        })
+        .collect()
+}
+
+/// A page documenting module `module`
+pub fn page(module: &str, entries: &[DocEntry], format: DocFormat) -> String {
+    match format {
+        DocFormat::Markdown => {
+            let mut out = format!("# Module `{}`\n", module);
+            for entry in entries {
+                out.push_str(&format!(
+                    "\n## {}\n\n```rela\n{}\n```\n",
+                    entry.name, entry.signature
+                ));
+                if let Some(doc) = &entry.doc {
+                    out.push_str(&format!("\n{}\n", doc));
+                }
+            }
+            out
+        }
+        DocFormat::Html => {
+            let mut body = format!("<h1>Module <code>{}</code></h1>\n", escape(module));
+            for entry in entries {
+                body.push_str(&format!(
+                    "<section id=\"{0}\">\n<h2>{0}</h2>\n<pre><code>{1}</code></pre>\n",
+                    escape(&entry.name),
+                    escape(&entry.signature)
+                ));
+                for paragraph in entry.doc.iter().flat_map(|doc| doc.split("\n\n")) {
+                    body.push_str(&format!("<p>{}</p>\n", escape(paragraph)));
+                }
+                body.push_str("</section>\n");
+            }
+            html_page(module, &body)
+        }
+    }
+}
+
+/// A page linking the pages of `modules`, each in the file `file_name`
+/// gives it
+pub fn index(title: &str, modules: &[&str], format: DocFormat) -> String {
+    match format {
+        DocFormat::Markdown => {
+            let mut out = format!("# {}\n\n", title);
+            for module in modules {
+                out.push_str(&format!("- [{}]({})\n", module, file_name(module, format)));
+            }
+            out
+        }
+        DocFormat::Html => {
+            let mut body = format!("<h1>{}</h1>\n<ul>\n", escape(title));
+            for module in modules {
+                body.push_str(&format!(
+                    "<li><a href=\"{}\"><code>{}</code></a></li>\n",
+                    file_name(module, format),
+                    escape(module)
+                ));
+            }
+            body.push_str("</ul>\n");
+            html_page(title, &body)
+        }
+    }
+}
+
+/// File the page of `module` is written to: `synths::bass` -> `synths-bass.md`
+pub fn file_name(module: &str, format: DocFormat) -> String {
+    format!("{}.{}", module.replace("::", "-"), format.extension())
+}
+
+fn html_page(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>{}</title>\n</head>\n<body>\n{}</body>\n</html>\n",
+        escape(title),
+        body
+    )
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,36 @@
+//! Ableton Link for `relanote play`
+//!
+//! Needs the `link` feature.
+
+use relanote_render::Clock;
+
+/// Run `play` with a clock following the Link session on the network,
+/// founding one at `tempo` BPM when there is none
+#[cfg(feature = "link")]
+pub fn join<R>(
+    tempo: f64,
+    quantum: f64,
+    play: impl FnOnce(&mut dyn Clock) -> R,
+) -> Result<R, String> {
+    use relanote_render::link::LinkSession;
+
+    let session =
+        LinkSession::join(tempo).map_err(|e| format!("could not join a Link session: {}", e))?;
+    // Give the peers on the network time to answer, and to be measured
+    std::thread::sleep(std::time::Duration::from_secs(1));
+    println!(
+        "Joined a Link session with {} peers at {:.1} BPM",
+        session.peers(),
+        session.tempo()
+    );
+    Ok(play(&mut session.clock(quantum)))
+}
+
+#[cfg(not(feature = "link"))]
+pub fn join<R>(
+    _tempo: f64,
+    _quantum: f64,
+    _play: impl FnOnce(&mut dyn Clock) -> R,
+) -> Result<R, String> {
+    Err("this relanote was built without Ableton Link; build it with `--features link`".to_string())
+}
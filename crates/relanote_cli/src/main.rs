@@ -1,15 +1,33 @@
+mod doc;
+mod link;
+mod midi_out;
+
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 
 use ariadne::{Color, Label, Report, ReportKind, Source};
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 
-use relanote_core::Source as RelaSource;
-use relanote_eval::{AbsolutePitchValue, Evaluator, Value};
-use relanote_format::{format, FormatConfig};
+use relanote_ast::{Item, Program, TestDecl};
+use relanote_core::{DiagnosticKind, Source as RelaSource};
+use relanote_eval::{pitch_name, BlockValue, EvalError, Evaluator, PartValue, SongValue, Value};
+use relanote_format::{format_with_source, FormatConfig};
 use relanote_parser::parse_source;
-use relanote_render::{MidiConfig, MidiRenderer};
-use relanote_types::TypeChecker;
+use relanote_render::osc::{OscConfig, OscSender};
+use relanote_render::{
+    abc_to_rela, diff_layouts, AbcRenderer, Clock, MidiConfig, MidiRenderer, NoteChange,
+    NoteLayout, PartDiffKind, Performance, RenderContext, SongLayout, WallClock,
+};
+use relanote_resolver::{
+    resolve_packages, Lockfile, Manifest, ModuleChecker, ModuleResolver, ResolvedPackage,
+    ENTRY_MODULE, LOCK_FILE, MANIFEST_FILE,
+};
+use serde::Serialize;
+use similar::TextDiff;
+
+use doc::DocFormat;
 
 #[derive(Parser)]
 #[command(name = "relanote")]
@@ -19,18 +37,74 @@ struct Cli {
     command: Commands,
 }
 
+/// MIDI settings for `render`, overriding the program's `set` bindings
+#[derive(Args, Default)]
+struct MidiOptions {
+    /// Tempo in BPM (default: `set tempo`, or 120)
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    tempo: Option<u32>,
+    /// Ticks per quarter note
+    #[arg(long, value_parser = clap::value_parser!(u16).range(1..=0x7FFF))]
+    ticks_per_beat: Option<u16>,
+    /// MIDI note of the root (default: `set key`, or 60 = C4)
+    #[arg(long, value_parser = clap::value_parser!(u8).range(0..=127))]
+    base_note: Option<u8>,
+    /// Pitch bend range in semitones
+    #[arg(long, value_parser = parse_bend_range)]
+    bend_range: Option<f64>,
+    /// Give every note its own MPE member channel so chords can bend independently
+    #[arg(long)]
+    mpe: bool,
+}
+
+/// Which part of a program's output `render` writes
+#[derive(clap::Args)]
+struct SongSelection {
+    /// Only write this render target: its name, or its number among the
+    /// program's `render` items
+    #[arg(long)]
+    target: Option<String>,
+    /// Only write the sections with this name
+    #[arg(long)]
+    section: Option<String>,
+}
+
+/// A pitch bend range a synth can be set to (RPN 0 goes up to 127 semitones)
+fn parse_bend_range(arg: &str) -> Result<f64, String> {
+    match arg.parse::<f64>() {
+        Ok(semitones) if semitones > 0.0 && semitones < 128.0 => Ok(semitones),
+        Ok(_) => Err("must be more than 0 and less than 128 semitones".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// How diagnostics are reported
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    /// Annotated source snippets
+    Human,
+    /// One JSON document with every diagnostic (for CI and editor plugins)
+    Json,
+}
+
 #[derive(Subcommand)]
 enum Commands {
     /// Parse a relanote file and display the AST
     Parse {
         /// Input file
         file: PathBuf,
+        /// Output format (json prints diagnostics instead of the AST)
+        #[arg(long, value_enum, default_value_t = OutputFormat::Human)]
+        format: OutputFormat,
     },
 
     /// Type check a relanote file
     Check {
         /// Input file
         file: PathBuf,
+        /// Output format for diagnostics
+        #[arg(long, value_enum, default_value_t = OutputFormat::Human)]
+        format: OutputFormat,
     },
 
     /// Run/evaluate a relanote file
@@ -39,22 +113,146 @@ enum Commands {
         file: PathBuf,
     },
 
-    /// Format a relanote file
+    /// Format relanote files
+    #[command(alias = "fmt")]
     Format {
-        /// Input file
-        file: PathBuf,
+        /// Input files (only one unless --check is given)
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
         /// Write output to file (in-place if same as input)
         #[arg(short, long)]
         output: Option<PathBuf>,
+        /// Formatter config file (default: nearest .relafmt or relanote.toml)
+        #[arg(long)]
+        config: Option<PathBuf>,
+        /// Don't write anything; print a diff and exit with 1 if a file isn't formatted
+        #[arg(long, conflicts_with = "output")]
+        check: bool,
     },
 
-    /// Render a relanote file to MIDI
+    /// Render a relanote file to MIDI (or ABC notation for a `.abc` output)
     Render {
         /// Input file
         file: PathBuf,
-        /// Output file
+        /// Output file, or a directory to write each render target into
+        /// (default: a `.mid` named after the song's `meta` title, or the
+        /// input file, next to the input)
         #[arg(short, long)]
-        output: PathBuf,
+        output: Option<PathBuf>,
+        #[command(flatten)]
+        selection: SongSelection,
+        #[command(flatten)]
+        midi: MidiOptions,
+    },
+
+    /// Play a relanote file live, sending its notes as Open Sound Control
+    /// messages (or to a MIDI output port) as they sound
+    Play {
+        /// Input file
+        file: PathBuf,
+        /// Host and port to send OSC messages to (default: 127.0.0.1:57120)
+        #[arg(long, value_name = "HOST:PORT")]
+        osc: Option<String>,
+        /// Stream MIDI to this output port instead of sending OSC: its
+        /// number, or part of its name
+        #[arg(long, value_name = "DEVICE", conflicts_with = "osc")]
+        midi_out: Option<String>,
+        /// Also send MIDI clock to the --midi-out port, so a DAW can follow
+        #[arg(long, requires = "midi_out")]
+        midi_clock: bool,
+        /// Follow the MIDI clock of this input port (its number, or part of
+        /// its name): play on its Start, at its tempo, until its Stop
+        #[arg(long, value_name = "DEVICE", conflicts_with = "midi_clock")]
+        follow_clock: Option<String>,
+        /// Play in time with the Ableton Link session on the network
+        #[arg(long, conflicts_with = "follow_clock")]
+        link: bool,
+        /// Start on the Link session's next multiple of this many beats
+        #[arg(long, value_name = "BEATS", default_value_t = 4.0, requires = "link")]
+        link_quantum: f64,
+        /// Prefix of the OSC addresses
+        #[arg(long, default_value = "/relanote")]
+        osc_prefix: String,
+        /// Don't send a `/beat` message on every beat
+        #[arg(long)]
+        no_clock: bool,
+        /// Play the song over and over until interrupted
+        #[arg(long = "loop")]
+        looping: bool,
+        #[command(flatten)]
+        midi: MidiOptions,
+    },
+
+    /// Evaluate a relanote file and print where its sections and parts land:
+    /// bars, note counts, MIDI channels and effect settings
+    Inspect {
+        /// Input file
+        file: PathBuf,
+        #[command(flatten)]
+        midi: MidiOptions,
+    },
+
+    /// Evaluate two relanote files and print how their music differs:
+    /// notes added, removed or changed in each part, by bar
+    Diff {
+        /// The earlier version
+        old: PathBuf,
+        /// The later version
+        new: PathBuf,
+        #[command(flatten)]
+        midi: MidiOptions,
+    },
+
+    /// Run the `test` items of relanote files, comparing the music they make
+    /// with snapshots saved by earlier runs
+    Test {
+        /// Files or directories to test (default: the current directory)
+        paths: Vec<PathBuf>,
+        /// Save the music of failing snapshot tests as their new snapshots
+        #[arg(long)]
+        update: bool,
+    },
+
+    /// Build the project described by a relanote.toml: check every module and render its outputs
+    Build {
+        /// Project manifest (default: nearest relanote.toml above the current directory)
+        #[arg(long)]
+        manifest_path: Option<PathBuf>,
+        /// Fail instead of updating relanote.lock when the resolved packages changed
+        #[arg(long)]
+        locked: bool,
+    },
+
+    /// Evaluate a relanote file and write the resulting music as JSON
+    ExportJson {
+        /// Input file
+        file: PathBuf,
+        /// Write output to file (stdout if omitted)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Convert an ABC notation tune to relanote source
+    Import {
+        /// Input ABC file
+        file: PathBuf,
+        /// Write output to file (stdout if omitted)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Write reference pages for the exported bindings of a module and their doc comments
+    Doc {
+        /// A .rela file, or a stdlib module such as `drums` or `synths::bass`
+        /// (every stdlib module if omitted)
+        module: Option<String>,
+        /// Page format
+        #[arg(long, value_enum, default_value_t = DocFormat::Markdown)]
+        format: DocFormat,
+        /// Write output to file (stdout if omitted); a directory for the
+        /// pages of every stdlib module
+        #[arg(short, long)]
+        output: Option<PathBuf>,
     },
 
     /// Start the LSP server
@@ -65,16 +263,82 @@ fn main() {
     let cli = Cli::parse();
 
     match cli.command {
-        Commands::Parse { file } => cmd_parse(&file),
-        Commands::Check { file } => cmd_check(&file),
+        Commands::Parse { file, format } => cmd_parse(&file, format),
+        Commands::Check { file, format } => cmd_check(&file, format),
         Commands::Run { file } => cmd_run(&file),
-        Commands::Format { file, output } => cmd_format(&file, output),
-        Commands::Render { file, output } => cmd_render(&file, &output),
+        Commands::Format {
+            files,
+            output,
+            config,
+            check,
+        } => {
+            if check {
+                cmd_format_check(&files, config)
+            } else if let [file] = files.as_slice() {
+                cmd_format(file, output, config)
+            } else {
+                eprintln!("Error: formatting several files at once needs --check");
+                std::process::exit(1);
+            }
+        }
+        Commands::Render {
+            file,
+            output,
+            selection,
+            midi,
+        } => cmd_render(&file, output, &selection, &midi),
+        Commands::Play {
+            file,
+            osc,
+            midi_out,
+            midi_clock,
+            follow_clock,
+            link,
+            link_quantum,
+            osc_prefix,
+            no_clock,
+            looping,
+            midi,
+        } => {
+            let output = match midi_out {
+                Some(device) => PlayOutput::Midi {
+                    device,
+                    clock: midi_clock,
+                },
+                None => PlayOutput::Osc {
+                    target: osc.unwrap_or_else(|| "127.0.0.1:57120".to_string()),
+                    prefix: osc_prefix,
+                    clock: !no_clock,
+                },
+            };
+            let sync = match follow_clock {
+                Some(device) => PlaySync::MidiClock(device),
+                None if link => PlaySync::Link {
+                    quantum: link_quantum,
+                },
+                None => PlaySync::Tempo,
+            };
+            cmd_play(&file, output, sync, looping, &midi)
+        }
+        Commands::Inspect { file, midi } => cmd_inspect(&file, &midi),
+        Commands::Diff { old, new, midi } => cmd_diff(&old, &new, &midi),
+        Commands::Test { paths, update } => cmd_test(paths, update),
+        Commands::Build {
+            manifest_path,
+            locked,
+        } => cmd_build(manifest_path, locked),
+        Commands::ExportJson { file, output } => cmd_export_json(&file, output),
+        Commands::Import { file, output } => cmd_import(&file, output),
+        Commands::Doc {
+            module,
+            format,
+            output,
+        } => cmd_doc(module, format, output),
         Commands::Lsp => cmd_lsp(),
     }
 }
 
-fn cmd_parse(file: &PathBuf) {
+fn cmd_parse(file: &PathBuf, format: OutputFormat) {
     let content = match fs::read_to_string(file) {
         Ok(c) => c,
         Err(e) => {
@@ -86,6 +350,14 @@ fn cmd_parse(file: &PathBuf) {
     let source = RelaSource::from_string(file.display().to_string(), content.clone());
     let (program, diagnostics) = parse_source(&source);
 
+    if format == OutputFormat::Json {
+        print_json_diagnostics(file, &source, &diagnostics);
+        if diagnostics.has_errors() {
+            std::process::exit(1);
+        }
+        return;
+    }
+
     if diagnostics.has_errors() {
         print_diagnostics(file, &content, &diagnostics);
         std::process::exit(1);
@@ -94,7 +366,7 @@ fn cmd_parse(file: &PathBuf) {
     println!("{:#?}", program);
 }
 
-fn cmd_check(file: &PathBuf) {
+fn cmd_check(file: &PathBuf, format: OutputFormat) {
     let content = match fs::read_to_string(file) {
         Ok(c) => c,
         Err(e) => {
@@ -104,24 +376,83 @@ fn cmd_check(file: &PathBuf) {
     };
 
     let source = RelaSource::from_string(file.display().to_string(), content.clone());
-    let (program, parse_diagnostics) = parse_source(&source);
+    let (program, mut diagnostics) = parse_source(&source);
 
-    if parse_diagnostics.has_errors() {
-        print_diagnostics(file, &content, &parse_diagnostics);
-        std::process::exit(1);
+    // Type checking a program with syntax errors only adds noise
+    if !diagnostics.has_errors() {
+        diagnostics.merge(type_check(file, &content, &program));
     }
 
-    let mut type_checker = TypeChecker::new();
-    let type_diagnostics = type_checker.check_program(&program);
+    if format == OutputFormat::Json {
+        print_json_diagnostics(file, &source, &diagnostics);
+        if diagnostics.has_errors() {
+            std::process::exit(1);
+        }
+        return;
+    }
 
-    if type_diagnostics.has_errors() {
-        print_diagnostics(file, &content, &type_diagnostics);
+    print_diagnostics(file, &content, &diagnostics);
+    if diagnostics.has_errors() {
         std::process::exit(1);
     }
 
     println!("No errors found.");
 }
 
+/// Type check `program`, parsed from `file`, against the modules it uses
+fn type_check(file: &Path, content: &str, program: &Program) -> relanote_core::Diagnostics {
+    let mut resolver = ModuleResolver::for_file(file);
+    if let Err(e) = resolver.resolve_source(ENTRY_MODULE, file, content.to_string()) {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+    ModuleChecker::new().check_entry(&resolver, ENTRY_MODULE, program)
+}
+
+/// An evaluator that finds the modules `file` uses the way `check` does:
+/// next to it, in the module paths of the relanote.toml above it and in the
+/// packages that manifest depends on
+fn evaluator_for(file: &Path) -> Evaluator {
+    let dir = file
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let project = Manifest::discover(dir).and_then(|manifest_path| {
+        let manifest = Manifest::load(&manifest_path).ok()?;
+        Some((manifest, manifest_path.parent()?.to_path_buf()))
+    });
+    let (module_paths, packages) = match project {
+        Some((manifest, root)) => (
+            manifest
+                .package
+                .module_paths
+                .iter()
+                .map(|path| root.join(path))
+                .collect(),
+            resolve_packages(&manifest, &root).unwrap_or_default(),
+        ),
+        None => (Vec::new(), Vec::new()),
+    };
+    project_evaluator(dir.to_path_buf(), module_paths, packages)
+}
+
+/// An evaluator that finds modules in `base_dir`, then `module_paths`, and
+/// `packages` by name
+fn project_evaluator(
+    base_dir: PathBuf,
+    module_paths: Vec<PathBuf>,
+    packages: Vec<ResolvedPackage>,
+) -> Evaluator {
+    let mut evaluator = Evaluator::with_base_dir(Some(base_dir));
+    for path in module_paths {
+        evaluator.add_search_path(path);
+    }
+    for package in packages {
+        evaluator.add_package(package);
+    }
+    evaluator
+}
+
 fn cmd_run(file: &PathBuf) {
     let content = match fs::read_to_string(file) {
         Ok(c) => c,
@@ -139,27 +470,28 @@ fn cmd_run(file: &PathBuf) {
         std::process::exit(1);
     }
 
-    let mut type_checker = TypeChecker::new();
-    let type_diagnostics = type_checker.check_program(&program);
+    let type_diagnostics = type_check(file, &content, &program);
 
+    print_diagnostics(file, &content, &type_diagnostics);
     if type_diagnostics.has_errors() {
-        print_diagnostics(file, &content, &type_diagnostics);
         std::process::exit(1);
     }
 
-    let mut evaluator = Evaluator::new();
-    match evaluator.eval_program(&program) {
+    let mut evaluator = evaluator_for(file);
+    let result = evaluator.eval_program(&program);
+    print_eval_warnings(file, &content, &evaluator);
+    match result {
         Ok(value) => {
             println!("{:?}", value);
         }
         Err(e) => {
-            eprintln!("Runtime error: {}", e);
+            print_runtime_error(file, &content, &e);
             std::process::exit(1);
         }
     }
 }
 
-fn cmd_format(file: &PathBuf, output: Option<PathBuf>) {
+fn cmd_format(file: &PathBuf, output: Option<PathBuf>, config: Option<PathBuf>) {
     let content = match fs::read_to_string(file) {
         Ok(c) => c,
         Err(e) => {
@@ -168,16 +500,10 @@ fn cmd_format(file: &PathBuf, output: Option<PathBuf>) {
         }
     };
 
-    let source = RelaSource::from_string(file.display().to_string(), content.clone());
-    let (program, diagnostics) = parse_source(&source);
-
-    if diagnostics.has_errors() {
-        print_diagnostics(file, &content, &diagnostics);
-        std::process::exit(1);
-    }
-
-    let config = FormatConfig::default();
-    let formatted = format(&program, &config);
+    let formatted = match format_source(file, &content, config.as_deref()) {
+        Some(formatted) => formatted,
+        None => std::process::exit(1),
+    };
 
     match output {
         Some(output_path) => {
@@ -193,7 +519,71 @@ fn cmd_format(file: &PathBuf, output: Option<PathBuf>) {
     }
 }
 
-fn cmd_render(file: &PathBuf, output: &PathBuf) {
+/// Check that every file is already formatted, printing a unified diff for
+/// each one that isn't
+fn cmd_format_check(files: &[PathBuf], config: Option<PathBuf>) {
+    let mut ok = true;
+
+    for file in files {
+        let content = match fs::read_to_string(file) {
+            Ok(c) => c,
+            Err(e) => {
+                eprintln!("Error reading {}: {}", file.display(), e);
+                ok = false;
+                continue;
+            }
+        };
+        let Some(formatted) = format_source(file, &content, config.as_deref()) else {
+            ok = false;
+            continue;
+        };
+
+        if formatted != content {
+            ok = false;
+            let name = file.display().to_string();
+            print!(
+                "{}",
+                TextDiff::from_lines(&content, &formatted)
+                    .unified_diff()
+                    .header(&name, &name)
+            );
+        }
+    }
+
+    if !ok {
+        std::process::exit(1);
+    }
+}
+
+/// Format the contents of `file`, reporting parse and config errors
+fn format_source(file: &Path, content: &str, config: Option<&Path>) -> Option<String> {
+    let source = RelaSource::from_string(file.display().to_string(), content.to_string());
+    let (program, diagnostics) = parse_source(&source);
+
+    if diagnostics.has_errors() {
+        print_diagnostics(file, content, &diagnostics);
+        return None;
+    }
+
+    let config = match config {
+        Some(path) => FormatConfig::load(path),
+        None => FormatConfig::resolve(file),
+    };
+    match config {
+        Ok(config) => Some(format_with_source(&program, content, &config)),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            None
+        }
+    }
+}
+
+fn cmd_render(
+    file: &PathBuf,
+    output: Option<PathBuf>,
+    selection: &SongSelection,
+    options: &MidiOptions,
+) {
     let content = match fs::read_to_string(file) {
         Ok(c) => c,
         Err(e) => {
@@ -210,52 +600,1119 @@ fn cmd_render(file: &PathBuf, output: &PathBuf) {
         std::process::exit(1);
     }
 
-    let mut evaluator = Evaluator::new();
-    match evaluator.eval_program(&program) {
-        Ok(Value::Song(song)) => {
-            // Get key from environment if available
-            let mut config = MidiConfig::default();
-            if let Some(Value::AbsolutePitch(AbsolutePitchValue { midi_note })) =
-                evaluator.get_binding("key")
-            {
-                config.base_note = midi_note;
-            }
+    let mut evaluator = evaluator_for(file);
+    let result = evaluator.eval_program(&program);
+    print_eval_warnings(file, &content, &evaluator);
+    let songs = match result {
+        Ok(value) => program_songs(&evaluator, value),
+        Err(e) => {
+            print_runtime_error(file, &content, &e);
+            std::process::exit(1);
+        }
+    };
+    if songs.is_empty() {
+        eprintln!("Error: Program did not produce a Song value");
+        std::process::exit(1);
+    }
+    let songs = match select_songs(songs, selection) {
+        Ok(songs) => songs,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
 
-            let renderer = MidiRenderer::new(config);
-            let midi_data = renderer.render(&song);
-            if let Err(e) = fs::write(output, &midi_data) {
-                eprintln!("Error writing MIDI file: {}", e);
+    let title = file
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let name = match &evaluator.metadata().title {
+        Some(title) => file_name_for(title),
+        None => title.clone(),
+    };
+    // A directory (an existing one, or a path ending in a slash) gets a file
+    // for each target
+    let directory = output.as_ref().filter(|output| {
+        output.is_dir() || output.as_os_str().to_string_lossy().ends_with(['/', '\\'])
+    });
+    if let Some(directory) = directory {
+        if let Err(e) = fs::create_dir_all(directory) {
+            eprintln!("Error creating {}: {}", directory.display(), e);
+            std::process::exit(1);
+        }
+    }
+    // Target names become file names, so they can't lead out of the
+    // output's directory
+    if let Some(target) = songs
+        .iter()
+        .filter_map(|(target, _)| target.as_deref())
+        .find(|target| !is_plain_file_name(target))
+    {
+        eprintln!(
+            "Error: render target `{}` can't be used as a file name",
+            target
+        );
+        std::process::exit(1);
+    }
+    for (index, (target, song)) in songs.iter().enumerate() {
+        // A named target takes its name; with several targets, the others
+        // are numbered: song-1.mid, song-2.mid, ...
+        let label = match target {
+            Some(target) => Some(target.clone()),
+            None if songs.len() > 1 => Some((index + 1).to_string()),
+            None => None,
+        };
+        let output = match (directory, &output) {
+            // In a directory, a named target is a file of that name
+            (Some(directory), _) => directory.join(match target {
+                Some(target) => format!("{}.mid", target),
+                None => labelled_name(&name, label, "mid"),
+            }),
+            (None, Some(output)) => match label {
+                Some(label) => labelled_path(output, &label),
+                None => output.clone(),
+            },
+            (None, None) => file.with_file_name(labelled_name(&name, label, "mid")),
+        };
+        match write_song(song, &evaluator, &title, &output, options) {
+            Ok(kind) => println!("{} file written to {}", kind, output.display()),
+            Err(e) => {
+                eprintln!("Error writing {}: {}", output.display(), e);
                 std::process::exit(1);
             }
-            println!("MIDI file written to {}", output.display());
         }
-        Ok(_) => {
+    }
+}
+
+/// The songs a program outputs, with the names they were given: those of
+/// its `render` items, or else its last expression when that is a song
+fn program_songs(evaluator: &Evaluator, value: Value) -> Vec<(Option<String>, SongValue)> {
+    if !evaluator.render_targets().is_empty() {
+        return evaluator
+            .render_targets()
+            .iter()
+            .map(|target| (target.name.clone(), target.song.clone()))
+            .collect();
+    }
+    match value {
+        Value::Song(song) => vec![(None, song)],
+        _ => Vec::new(),
+    }
+}
+
+/// The songs, or parts of songs, `selection` picks out
+///
+/// A selected target is written like a program's only output, so `-o` names
+/// its file exactly.
+fn select_songs(
+    songs: Vec<(Option<String>, SongValue)>,
+    selection: &SongSelection,
+) -> Result<Vec<(Option<String>, SongValue)>, String> {
+    let mut songs = match &selection.target {
+        Some(target) => {
+            let number = target.parse::<usize>().ok();
+            let (_, song) = songs
+                .into_iter()
+                .enumerate()
+                .find(|(index, (name, _))| {
+                    name.as_deref() == Some(target.as_str()) || number == Some(index + 1)
+                })
+                .map(|(_, song)| song)
+                .ok_or_else(|| format!("no render target named `{}`", target))?;
+            vec![(None, song)]
+        }
+        None => songs,
+    };
+    if let Some(section) = &selection.section {
+        for (_, song) in &mut songs {
+            song.sections.retain(|s| &s.name == section);
+        }
+        songs.retain(|(_, song)| !song.sections.is_empty());
+        if songs.is_empty() {
+            return Err(format!("no section named `{}`", section));
+        }
+    }
+    Ok(songs)
+}
+
+/// A file name from a stem, with `-<label>` added when there is one
+fn labelled_name(stem: &str, label: Option<String>, ext: &str) -> String {
+    match label {
+        Some(label) => format!("{}-{}.{}", stem, label, ext),
+        None => format!("{}.{}", stem, ext),
+    }
+}
+
+/// Whether `name` is a file name on its own, without separators or parent
+/// directory references
+fn is_plain_file_name(name: &str) -> bool {
+    !name.is_empty() && !name.contains(['/', '\\']) && !name.contains("..")
+}
+
+/// `path` with `-<label>` added to its file stem: out.mid -> out-2.mid
+fn labelled_path(path: &Path, label: &str) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let name = match path.extension() {
+        Some(ext) => labelled_name(&stem, Some(label.to_string()), &ext.to_string_lossy()),
+        None => format!("{}-{}", stem, label),
+    };
+    path.with_file_name(name)
+}
+
+/// Where `relanote play` sends a song
+enum PlayOutput {
+    Osc {
+        /// `HOST:PORT`
+        target: String,
+        prefix: String,
+        clock: bool,
+    },
+    /// A MIDI output port, sending MIDI clock with `clock`
+    Midi { device: String, clock: bool },
+}
+
+/// What `relanote play` keeps time with
+enum PlaySync {
+    /// The song's own tempo
+    Tempo,
+    /// The MIDI clock of an input port
+    MidiClock(String),
+    /// An Ableton Link session, starting on a multiple of `quantum` beats
+    Link { quantum: f64 },
+}
+
+fn cmd_play(
+    file: &PathBuf,
+    output: PlayOutput,
+    sync: PlaySync,
+    looping: bool,
+    options: &MidiOptions,
+) {
+    let (song, evaluator) = eval_song(file);
+    let context = options.context(&evaluator);
+    let performance = match Performance::new(&song, &options.midi_config(&context)) {
+        Ok(performance) => performance,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    // Plays with `clock`: the song's own tempo, or the one it syncs to
+    let play = |clock: &mut dyn Clock| -> Result<(), String> {
+        let (target, prefix, send_clock) = match &output {
+            PlayOutput::Osc {
+                target,
+                prefix,
+                clock,
+            } => (target, prefix, *clock),
+            PlayOutput::Midi {
+                device,
+                clock: send_clock,
+            } => {
+                return midi_out::play(device, &performance, clock, *send_clock, looping);
+            }
+        };
+        let config = target
+            .rsplit_once(':')
+            .and_then(|(host, port)| {
+                Some(OscConfig {
+                    host: host
+                        .trim_start_matches('[')
+                        .trim_end_matches(']')
+                        .to_string(),
+                    port: port.parse().ok()?,
+                    prefix: prefix.clone(),
+                    clock: send_clock,
+                })
+            })
+            .ok_or("--osc must be HOST:PORT, such as 127.0.0.1:57120")?;
+        let sender = OscSender::connect(&config)
+            .map_err(|e| format!("could not send to {}: {}", target, e))?;
+        println!(
+            "Playing {} to {} ({} beats at {} BPM)",
+            file.display(),
+            target,
+            performance.total_beats(),
+            context.tempo
+        );
+        sender
+            .play(&performance, &config, clock, looping)
+            .map(|_| ())
+            .map_err(|e| format!("could not send to {}: {}", target, e))
+    };
+
+    let result = match sync {
+        PlaySync::MidiClock(device) => {
+            // Each Stop of the followed clock ends a pass; looping waits
+            // for its next Start
+            midi_out::follow_clock(&device, |clock| loop {
+                match play(clock) {
+                    Ok(()) if looping => continue,
+                    result => break result,
+                }
+            })
+            .and_then(|result| result)
+        }
+        PlaySync::Link { quantum } => {
+            link::join(context.tempo as f64, quantum, play).and_then(|result| result)
+        }
+        PlaySync::Tempo => play(&mut WallClock::new(&performance)),
+    };
+    if let Err(e) = result {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn cmd_inspect(file: &PathBuf, options: &MidiOptions) {
+    let (song, evaluator) = eval_song(file);
+    let context = options.context(&evaluator);
+    let layout = MidiRenderer::new(options.midi_config(&context)).inspect(&song);
+    print!("{}", format_layout(&layout, &context, false));
+}
+
+/// Evaluate a file to the song it produces (the first, with several render
+/// targets), exiting with its diagnostics if it has errors or doesn't
+/// produce a song
+fn eval_song(file: &PathBuf) -> (SongValue, Evaluator) {
+    let content = match fs::read_to_string(file) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("Error reading file: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let source = RelaSource::from_string(file.display().to_string(), content.clone());
+    let (program, parse_diagnostics) = parse_source(&source);
+
+    if parse_diagnostics.has_errors() {
+        print_diagnostics(file, &content, &parse_diagnostics);
+        std::process::exit(1);
+    }
+
+    let mut evaluator = evaluator_for(file);
+    let result = evaluator.eval_program(&program);
+    print_eval_warnings(file, &content, &evaluator);
+    let song = match result {
+        Ok(value) => program_songs(&evaluator, value)
+            .into_iter()
+            .next()
+            .map(|(_, song)| song),
+        Err(e) => {
+            print_runtime_error(file, &content, &e);
+            std::process::exit(1);
+        }
+    };
+    match song {
+        Some(song) => (song, evaluator),
+        None => {
             eprintln!("Error: Program did not produce a Song value");
             std::process::exit(1);
         }
+    }
+}
+
+/// Human-readable timeline of a song layout
+///
+/// With `notes` set, each part also lists its notes.
+fn format_layout(layout: &SongLayout, context: &RenderContext, notes: bool) -> String {
+    let bars = |beats: f64| beats / layout.beats_per_bar.max(1) as f64;
+    let (beats, unit) = context.time_signature;
+    let mut out = format!(
+        "song: {} BPM, key {}, {}/{}, {} beats ({} bars)\n",
+        context.tempo,
+        pitch_name(context.base_note.into()),
+        beats,
+        unit,
+        layout.total_beats,
+        bars(layout.total_beats),
+    );
+    for bus in &layout.buses {
+        out.push_str(&format!(
+            "bus \"{}\": {}\n",
+            bus.name,
+            bus.effects.join(", ")
+        ));
+    }
+    for section in &layout.sections {
+        let tempo = section
+            .tempo
+            .map(|tempo| format!(", {} BPM", tempo))
+            .unwrap_or_default();
+        out.push_str(&format!(
+            "\nsection \"{}\": bar {}, {} beats, key {}{}\n",
+            section.name,
+            bars(section.start_beat) + 1.0,
+            section.beats,
+            pitch_name(section.base_note.into()),
+            tempo,
+        ));
+        for part in &section.parts {
+            let channel = match part.channel {
+                Some(channel) if part.drums => format!("ch {} (drums)", channel + 1),
+                Some(channel) => format!("ch {}", channel + 1),
+                None => "no channel".to_string(),
+            };
+            out.push_str(&format!(
+                "  part \"{}\": {}, {} bars, {} beats, {} notes\n",
+                part.instrument,
+                channel,
+                bars(part.beats),
+                part.beats,
+                part.notes.len(),
+            ));
+            if !part.effects.is_empty() {
+                out.push_str(&format!("    effects: {}\n", part.effects.join(", ")));
+            }
+            if let Some(reason) = part.silence() {
+                out.push_str(&format!("    silent: {}\n", reason));
+            }
+            if notes {
+                for note in &part.notes {
+                    out.push_str(&format!(
+                        "    {}: {}\n",
+                        note_position(note, layout),
+                        describe_note(note)
+                    ));
+                }
+            }
+        }
+    }
+    out
+}
+
+fn cmd_diff(old_file: &PathBuf, new_file: &PathBuf, options: &MidiOptions) {
+    let (old_song, old_evaluator) = eval_song(old_file);
+    let (new_song, new_evaluator) = eval_song(new_file);
+    let old_context = options.context(&old_evaluator);
+    let new_context = options.context(&new_evaluator);
+    let old_layout = MidiRenderer::new(options.midi_config(&old_context)).inspect(&old_song);
+    let new_layout = MidiRenderer::new(options.midi_config(&new_context)).inspect(&new_song);
+
+    let mut settings = Vec::new();
+    if old_context.tempo != new_context.tempo {
+        settings.push(format!(
+            "tempo: {} -> {} BPM",
+            old_context.tempo, new_context.tempo
+        ));
+    }
+    if old_context.base_note != new_context.base_note {
+        settings.push(format!(
+            "key: {} -> {}",
+            pitch_name(old_context.base_note.into()),
+            pitch_name(new_context.base_note.into())
+        ));
+    }
+    if old_context.time_signature != new_context.time_signature {
+        let (old_beats, old_unit) = old_context.time_signature;
+        let (new_beats, new_unit) = new_context.time_signature;
+        settings.push(format!(
+            "time signature: {}/{} -> {}/{}",
+            old_beats, old_unit, new_beats, new_unit
+        ));
+    }
+    let diffs = diff_layouts(&old_layout, &new_layout);
+
+    if settings.is_empty() && diffs.is_empty() {
+        println!("No musical differences");
+        return;
+    }
+    for setting in &settings {
+        println!("{}", setting);
+    }
+    for diff in &diffs {
+        let kind = match diff.kind {
+            PartDiffKind::Added => " (added)",
+            PartDiffKind::Removed => " (removed)",
+            PartDiffKind::Changed => "",
+        };
+        println!(
+            "\nsection \"{}\", part \"{}\"{}:",
+            diff.section, diff.instrument, kind
+        );
+        for change in &diff.changes {
+            // Removed notes are placed in the old song, the others in the new one
+            let layout = match change {
+                NoteChange::Removed(_) => &old_layout,
+                _ => &new_layout,
+            };
+            let position = note_position(change.note(), layout);
+            let description = match change {
+                NoteChange::Added(note) => format!("+ {}", describe_note(note)),
+                NoteChange::Removed(note) => format!("- {}", describe_note(note)),
+                NoteChange::Changed { old, new } => {
+                    format!("~ {} -> {}", describe_note(old), describe_note(new))
+                }
+            };
+            println!("  {}: {}", position, description);
+        }
+    }
+    // Like diff(1), differences are reported with exit code 1
+    std::process::exit(1);
+}
+
+/// Where a note starts, such as `bar 2, beat 3.5`
+fn note_position(note: &NoteLayout, layout: &SongLayout) -> String {
+    let bar_beats = layout.beats_per_bar.max(1) as f64;
+    format!(
+        "bar {}, beat {}",
+        (note.start_beat / bar_beats).floor() + 1.0,
+        note.start_beat % bar_beats + 1.0
+    )
+}
+
+/// A note's pitch and length, such as `E4 (0.5 beats)`
+fn describe_note(note: &NoteLayout) -> String {
+    let nearest = note.pitch.round();
+    let cents = ((note.pitch - nearest) * 100.0).round();
+    let pitch = pitch_name(nearest.clamp(0.0, 127.0) as i32);
+    let pitch = if cents == 0.0 {
+        pitch
+    } else {
+        format!("{}{:+}c", pitch, cents)
+    };
+    let unit = if note.beats == 1.0 { "beat" } else { "beats" };
+    format!("{} ({} {})", pitch, note.beats, unit)
+}
+
+fn cmd_test(paths: Vec<PathBuf>, update: bool) {
+    let paths = if paths.is_empty() {
+        vec![PathBuf::from(".")]
+    } else {
+        paths
+    };
+    let mut files = Vec::new();
+    for path in &paths {
+        collect_rela_files(path, &mut files);
+    }
+    files.sort();
+
+    let (mut passed, mut failed) = (0, 0);
+    for file in &files {
+        let (file_passed, file_failed) = run_tests(file, update);
+        passed += file_passed;
+        failed += file_failed;
+    }
+
+    println!("\ntest result: {} passed, {} failed", passed, failed);
+    if failed > 0 {
+        std::process::exit(1);
+    }
+}
+
+/// `.rela` files at `path`, searching directories (except hidden ones and
+/// build output) recursively
+fn collect_rela_files(path: &Path, files: &mut Vec<PathBuf>) {
+    if !path.is_dir() {
+        files.push(path.to_path_buf());
+        return;
+    }
+    let Ok(entries) = fs::read_dir(path) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if path.is_dir() {
+            if !name.starts_with('.') && name != "target" && name != "node_modules" {
+                collect_rela_files(&path, files);
+            }
+        } else if path.extension().is_some_and(|ext| ext == "rela") {
+            files.push(path);
+        }
+    }
+}
+
+/// Run the tests of a file, returning how many passed and failed
+fn run_tests(file: &Path, update: bool) -> (usize, usize) {
+    let content = match fs::read_to_string(file) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("Error reading {}: {}", file.display(), e);
+            return (0, 1);
+        }
+    };
+
+    let source = RelaSource::from_string(file.display().to_string(), content.clone());
+    let (program, parse_diagnostics) = parse_source(&source);
+    if parse_diagnostics.has_errors() {
+        print_diagnostics(file, &content, &parse_diagnostics);
+        return (0, 1);
+    }
+
+    let tests: Vec<&TestDecl> = program
+        .items
+        .iter()
+        .filter_map(|item| match &item.node {
+            Item::Test(test) => Some(test),
+            _ => None,
+        })
+        .collect();
+    if tests.is_empty() {
+        return (0, 0);
+    }
+
+    let mut evaluator = evaluator_for(file);
+    if let Err(e) = evaluator.eval_program(&program) {
+        print_runtime_error(file, &content, &e);
+        return (0, tests.len());
+    }
+    let context = RenderContext::from_evaluator(&evaluator);
+    let renderer = MidiRenderer::new(context.midi_config());
+
+    let (mut passed, mut failed) = (0, 0);
+    // Test names by snapshot file, since names like "Verse 1" and "verse-1"
+    // map to the same one
+    let mut snapshots: HashMap<PathBuf, &str> = HashMap::new();
+    for test in tests {
+        let outcome = match evaluator.eval_expr(&test.body) {
+            Ok(Value::Bool(true)) => Ok("ok"),
+            Ok(Value::Bool(false)) => Err("the test evaluated to false".to_string()),
+            Ok(value) => match value.into_song() {
+                Some(song) => match snapshots.entry(snapshot_path(file, &test.name)) {
+                    Entry::Occupied(entry) => Err(format!(
+                        "the test's snapshot {} is already test \"{}\"'s; rename one of them",
+                        entry.key().display(),
+                        entry.get()
+                    )),
+                    Entry::Vacant(entry) => {
+                        let path = entry.key().clone();
+                        entry.insert(&test.name);
+                        let layout = renderer.inspect(&song);
+                        let rendered = format_layout(&layout, &context, true);
+                        check_snapshot(&path, &rendered, update)
+                    }
+                },
+                None => Err("a test has to produce a Bool or music".to_string()),
+            },
+            Err(e) => {
+                print_runtime_error(file, &content, &e);
+                Err("the test failed to evaluate".to_string())
+            }
+        };
+        match outcome {
+            Ok(status) => {
+                passed += 1;
+                println!("test {} \"{}\" ... {}", file.display(), test.name, status);
+            }
+            Err(message) => {
+                failed += 1;
+                println!("test {} \"{}\" ... FAILED", file.display(), test.name);
+                println!("{}", message);
+            }
+        }
+    }
+    (passed, failed)
+}
+
+/// Where a test's snapshot is saved: `__snapshots__/<file>/<test>.snap`
+/// next to the file
+fn snapshot_path(file: &Path, name: &str) -> PathBuf {
+    let stem = file
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    file.with_file_name("__snapshots__")
+        .join(stem)
+        .join(format!("{}.snap", file_name_for(name)))
+}
+
+/// Compare rendered music with its snapshot, saving it if there's none yet
+/// (or if `update` is set)
+fn check_snapshot(path: &Path, rendered: &str, update: bool) -> Result<&'static str, String> {
+    let saved = fs::read_to_string(path).ok();
+    if saved.as_deref() == Some(rendered) {
+        return Ok("ok");
+    }
+    if saved.is_some() && !update {
+        return Err(format!(
+            "{}(run with --update to accept the new music)",
+            TextDiff::from_lines(saved.as_deref().unwrap_or_default(), rendered)
+                .unified_diff()
+                .header(&path.display().to_string(), "rendered")
+        ));
+    }
+    let write = || -> std::io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, rendered)
+    };
+    match write() {
+        Ok(()) if saved.is_some() => Ok("ok (snapshot updated)"),
+        Ok(()) => Ok("ok (snapshot saved)"),
+        Err(e) => Err(format!("Error writing {}: {}", path.display(), e)),
+    }
+}
+
+/// A file name for a song title: lowercase words joined by dashes
+fn file_name_for(title: &str) -> String {
+    let name = title
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join("-");
+    if name.is_empty() {
+        "untitled".to_string()
+    } else {
+        name
+    }
+}
+
+impl MidiOptions {
+    /// Render context of the program's `set` bindings, with the flags applied
+    fn context(&self, evaluator: &Evaluator) -> RenderContext {
+        let mut context = RenderContext::from_evaluator(evaluator);
+        if let Some(tempo) = self.tempo {
+            context.tempo = tempo;
+        }
+        if let Some(base_note) = self.base_note {
+            context.base_note = base_note;
+        }
+        context
+    }
+
+    /// MIDI settings for `context`, with the MIDI-only flags applied
+    fn midi_config(&self, context: &RenderContext) -> MidiConfig {
+        let mut config = context.midi_config();
+        if let Some(ticks_per_beat) = self.ticks_per_beat {
+            config.ticks_per_beat = ticks_per_beat;
+        }
+        if let Some(bend_range) = self.bend_range {
+            config.pitch_bend_range = bend_range;
+        }
+        config.mpe = self.mpe;
+        config
+    }
+}
+
+/// Write `song` to `output`: ABC notation for a `.abc` file, JSON for `.json`,
+/// MIDI otherwise. Returns the name of the format written.
+fn write_song(
+    song: &SongValue,
+    evaluator: &Evaluator,
+    title: &str,
+    output: &Path,
+    options: &MidiOptions,
+) -> std::io::Result<&'static str> {
+    let context = options.context(evaluator);
+
+    match output.extension().and_then(|ext| ext.to_str()) {
+        Some("abc") => {
+            let renderer = AbcRenderer::new(context.abc_config(title));
+            fs::write(output, renderer.render(song))?;
+            Ok("ABC")
+        }
+        Some("json") => {
+            let json = serde_json::to_string_pretty(&ExportedMusic::Song(song))?;
+            fs::write(output, json + "\n")?;
+            Ok("JSON")
+        }
+        _ => {
+            let renderer = MidiRenderer::new(options.midi_config(&context));
+            let (midi_data, render_diagnostics) = renderer
+                .render_with_diagnostics(song)
+                .map_err(std::io::Error::other)?;
+            for diag in render_diagnostics.iter() {
+                match diag.code {
+                    Some(code) => eprintln!("{}[{}]: {}", diag.kind, code, diag.message),
+                    None => eprintln!("{}: {}", diag.kind, diag.message),
+                }
+            }
+            fs::write(output, &midi_data)?;
+            Ok("MIDI")
+        }
+    }
+}
+
+fn cmd_build(manifest_path: Option<PathBuf>, locked: bool) {
+    let manifest_path = match manifest_path.or_else(|| {
+        std::env::current_dir()
+            .ok()
+            .and_then(|dir| Manifest::discover(&dir))
+    }) {
+        Some(path) => path,
+        None => {
+            eprintln!(
+                "Error: could not find {} in the current directory or any parent",
+                MANIFEST_FILE
+            );
+            std::process::exit(1);
+        }
+    };
+    let manifest = match Manifest::load(&manifest_path) {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    if let Err(e) = manifest.check_stdlib() {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+
+    let root = manifest_path
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+    let entry = root.join(&manifest.package.entry);
+    let entry_dir = entry
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| root.clone());
+    let module_paths: Vec<PathBuf> = manifest
+        .package
+        .module_paths
+        .iter()
+        .map(|path| root.join(path))
+        .collect();
+
+    let packages = match resolve_packages(&manifest, &root) {
+        Ok(packages) => packages,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    update_lockfile(&root, &packages, locked);
+
+    // Resolve every module reachable from the entry point
+    let mut resolver = ModuleResolver::new(entry_dir.clone());
+    for path in &module_paths {
+        resolver.add_search_path(path.clone());
+    }
+    for package in &packages {
+        resolver.add_package(package.clone());
+    }
+    if let Err(e) = resolver.resolve_file(&manifest.package.name, &entry) {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+
+    // Type check the whole project before rendering anything
+    let mut has_errors = false;
+    for (module, diagnostics) in ModuleChecker::new().check_resolved(&resolver) {
+        if let Some(source) = resolver.source_db().get(module.source_id) {
+            print_diagnostics(&module.path, &source.content, &diagnostics);
+        }
+        has_errors |= diagnostics.has_errors();
+    }
+    if has_errors {
+        std::process::exit(1);
+    }
+
+    let Some(entry_module) = resolver.modules().last() else {
+        return;
+    };
+    let mut evaluator = project_evaluator(entry_dir, module_paths, packages);
+    let song = match evaluator.eval_program(&entry_module.program) {
+        Ok(value) => match program_songs(&evaluator, value).into_iter().next() {
+            Some((_, song)) => song,
+            None if manifest.outputs.is_empty() => return,
+            None => {
+                eprintln!("Error: Program did not produce a Song value");
+                std::process::exit(1);
+            }
+        },
+        Err(e) => {
+            match resolver.source_db().get(entry_module.source_id) {
+                Some(source) => print_runtime_error(&entry_module.path, &source.content, &e),
+                None => eprintln!("Runtime error: {}", e),
+            }
+            std::process::exit(1);
+        }
+    };
+
+    for target in &manifest.outputs {
+        let output = root.join(&target.path);
+        let written = output
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|()| {
+                write_song(
+                    &song,
+                    &evaluator,
+                    &manifest.package.name,
+                    &output,
+                    &MidiOptions::default(),
+                )
+            });
+        match written {
+            Ok(kind) => println!("{} file written to {}", kind, output.display()),
+            Err(e) => {
+                eprintln!("Error writing {}: {}", output.display(), e);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+/// Write relanote.lock next to the manifest when the resolved packages changed
+fn update_lockfile(root: &Path, packages: &[ResolvedPackage], locked: bool) {
+    let path = root.join(LOCK_FILE);
+    let existing = match Lockfile::load(&path) {
+        Ok(existing) => existing,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let lockfile = Lockfile::from_packages(packages, root);
+    if existing.as_ref() == Some(&lockfile) || (existing.is_none() && packages.is_empty()) {
+        return;
+    }
+    if locked {
+        eprintln!(
+            "Error: {} needs to be updated but --locked was given",
+            path.display()
+        );
+        std::process::exit(1);
+    }
+    if let Err(e) = fs::write(&path, lockfile.to_toml()) {
+        eprintln!("Error writing {}: {}", path.display(), e);
+        std::process::exit(1);
+    }
+}
+
+/// Music value written by `export-json`, tagged with its kind
+#[derive(Serialize)]
+enum ExportedMusic<'a> {
+    Song(&'a SongValue),
+    Part(&'a PartValue),
+    Block(&'a BlockValue),
+}
+
+fn cmd_export_json(file: &PathBuf, output: Option<PathBuf>) {
+    let content = match fs::read_to_string(file) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("Error reading file: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let source = RelaSource::from_string(file.display().to_string(), content.clone());
+    let (program, parse_diagnostics) = parse_source(&source);
+
+    if parse_diagnostics.has_errors() {
+        print_diagnostics(file, &content, &parse_diagnostics);
+        std::process::exit(1);
+    }
+
+    let mut evaluator = evaluator_for(file);
+    let value = match evaluator.eval_program(&program) {
+        // A render target is exported as the song it renders
+        Ok(value) => match evaluator.render_targets().first() {
+            Some(target) => Value::Song(target.song.clone()),
+            None => value,
+        },
         Err(e) => {
-            eprintln!("Runtime error: {}", e);
+            print_runtime_error(file, &content, &e);
+            std::process::exit(1);
+        }
+    };
+
+    let music = match &value {
+        Value::Song(song) => ExportedMusic::Song(song),
+        Value::Part(part) => ExportedMusic::Part(part),
+        Value::Block(block) => ExportedMusic::Block(block),
+        _ => {
+            eprintln!("Error: Program did not produce a Song, Part or Block value");
             std::process::exit(1);
         }
+    };
+
+    let json = match serde_json::to_string_pretty(&music) {
+        Ok(json) => json,
+        Err(e) => {
+            eprintln!("Error serializing to JSON: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    match output {
+        Some(output_path) => {
+            if let Err(e) = fs::write(&output_path, json + "\n") {
+                eprintln!("Error writing file: {}", e);
+                std::process::exit(1);
+            }
+            println!("JSON written to {}", output_path.display());
+        }
+        None => {
+            println!("{}", json);
+        }
+    }
+}
+
+fn cmd_import(file: &PathBuf, output: Option<PathBuf>) {
+    let content = match fs::read_to_string(file) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("Error reading file: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let source = match abc_to_rela(&content) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("Error converting {}: {}", file.display(), e);
+            std::process::exit(1);
+        }
+    };
+
+    match output {
+        Some(output_path) => {
+            if let Err(e) = fs::write(&output_path, &source) {
+                eprintln!("Error writing file: {}", e);
+                std::process::exit(1);
+            }
+            println!("Relanote source written to {}", output_path.display());
+        }
+        None => {
+            print!("{}", source);
+        }
     }
 }
 
+fn cmd_doc(module: Option<String>, format: DocFormat, output: Option<PathBuf>) {
+    let Some(module) = module else {
+        let Some(dir) = output else {
+            eprintln!("Error: documenting every stdlib module needs --output DIR");
+            std::process::exit(1);
+        };
+        cmd_doc_stdlib(format, &dir);
+        return;
+    };
+
+    let page = if module.ends_with(".rela") || Path::new(&module).is_file() {
+        file_doc_page(Path::new(&module), format)
+    } else {
+        match stdlib_doc_page(&module, format) {
+            Some(page) => page,
+            None => {
+                eprintln!("Error: no file or stdlib module named `{}`", module);
+                std::process::exit(1);
+            }
+        }
+    };
+
+    match output {
+        Some(output_path) => {
+            if let Err(e) = fs::write(&output_path, &page) {
+                eprintln!("Error writing file: {}", e);
+                std::process::exit(1);
+            }
+            println!("Reference written to {}", output_path.display());
+        }
+        None => {
+            print!("{}", page);
+        }
+    }
+}
+
+/// Write a page per stdlib module to `dir`, with an index linking them
+fn cmd_doc_stdlib(format: DocFormat, dir: &Path) {
+    if let Err(e) = fs::create_dir_all(dir) {
+        eprintln!("Error creating {}: {}", dir.display(), e);
+        std::process::exit(1);
+    }
+    let pages = relanote_stdlib::MODULES.iter().map(|module| {
+        let page = stdlib_doc_page(module, format).expect("stdlib module has a source");
+        (doc::file_name(module, format), page)
+    });
+    let index = doc::index("Standard library", relanote_stdlib::MODULES, format);
+    let index_file = format!("index.{}", format.extension());
+    for (name, page) in pages.chain([(index_file, index)]) {
+        if let Err(e) = fs::write(dir.join(&name), page) {
+            eprintln!("Error writing file: {}", e);
+            std::process::exit(1);
+        }
+    }
+    println!(
+        "Reference for {} modules written to {}",
+        relanote_stdlib::MODULES.len(),
+        dir.display()
+    );
+}
+
+/// Reference page of a module file, checked against the modules it uses
+fn file_doc_page(file: &Path, format: DocFormat) -> String {
+    let content = match fs::read_to_string(file) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("Error reading file: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let source = RelaSource::from_string(file.display().to_string(), content.clone());
+    let (program, diagnostics) = parse_source(&source);
+    if diagnostics.has_errors() {
+        print_diagnostics(file, &content, &diagnostics);
+        std::process::exit(1);
+    }
+
+    let mut resolver = ModuleResolver::for_file(file);
+    if let Err(e) = resolver.resolve_source(ENTRY_MODULE, file, content.clone()) {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+    let mut checker = ModuleChecker::new();
+    checker.check_dependencies(&resolver, ENTRY_MODULE);
+    let (types, _) = checker.check(&program);
+
+    let name = file.file_stem().unwrap_or_default().to_string_lossy();
+    let entries = doc::module_entries(&content, &program, &types);
+    doc::page(&name, &entries, format)
+}
+
+/// Reference page of a stdlib module, if there is one called `module`
+fn stdlib_doc_page(module: &str, format: DocFormat) -> Option<String> {
+    let content = relanote_stdlib::module_source(&format!("std::{}", module))?;
+    let (program, _) = relanote_parser::parse(&content);
+    let (types, _) = ModuleChecker::new().check(&program);
+    let entries = doc::module_entries(&content, &program, &types);
+    Some(doc::page(module, &entries, format))
+}
+
 fn cmd_lsp() {
     let rt = tokio::runtime::Runtime::new().unwrap();
     rt.block_on(relanote_lsp::run_server());
 }
 
+/// Print the warnings the evaluator reported while running `file`
+fn print_eval_warnings(file: &Path, content: &str, evaluator: &Evaluator) {
+    let mut warnings = relanote_core::Diagnostics::new();
+    warnings.extend(evaluator.warnings().iter().cloned());
+    print_diagnostics(file, content, &warnings);
+}
+
 fn print_diagnostics(file: &Path, content: &str, diagnostics: &relanote_core::Diagnostics) {
     let filename = file.display().to_string();
 
     for diag in diagnostics.iter() {
-        let report = Report::build(ReportKind::Error, &filename, diag.span.start)
+        let (kind, color) = match diag.kind {
+            DiagnosticKind::Error => (ReportKind::Error, Color::Red),
+            DiagnosticKind::Warning => (ReportKind::Warning, Color::Yellow),
+            DiagnosticKind::Info | DiagnosticKind::Hint => (ReportKind::Advice, Color::Cyan),
+        };
+        let mut report = Report::build(kind, &filename, diag.span.start)
             .with_message(&diag.message)
             .with_label(
                 Label::new((&filename, diag.span.start..diag.span.end))
                     .with_message(&diag.message)
-                    .with_color(Color::Red),
+                    .with_color(color),
             );
+        if let Some(code) = diag.code {
+            report = report.with_code(code);
+        }
 
         let report = diag.notes.iter().fold(report, |r, note| r.with_note(note));
 
@@ -265,3 +1722,61 @@ fn print_diagnostics(file: &Path, content: &str, diagnostics: &relanote_core::Di
             .unwrap();
     }
 }
+
+/// Print a runtime error, underlining the expression that raised it
+fn print_runtime_error(file: &Path, content: &str, error: &EvalError) {
+    if error.span() == relanote_core::Span::dummy() {
+        eprintln!("Runtime error: {}", error);
+        return;
+    }
+    let mut diagnostics = relanote_core::Diagnostics::new();
+    diagnostics.add(error.to_diagnostic());
+    print_diagnostics(file, content, &diagnostics);
+}
+
+/// Print diagnostics as a JSON document:
+/// `{ "file": ..., "diagnostics": [{ "severity", "code", "message", "span", "start", "end", "labels", "notes" }] }`
+///
+/// Spans are byte offsets; `start`/`end` are 1-based line and column.
+fn print_json_diagnostics(
+    file: &Path,
+    source: &RelaSource,
+    diagnostics: &relanote_core::Diagnostics,
+) {
+    let range = |span: relanote_core::Span| {
+        let start = source.location(span.start);
+        let end = source.location(span.end);
+        serde_json::json!({
+            "span": { "start": span.start, "end": span.end },
+            "start": { "line": start.line, "column": start.column },
+            "end": { "line": end.line, "column": end.column },
+        })
+    };
+
+    let diagnostics: Vec<serde_json::Value> = diagnostics
+        .iter()
+        .map(|diag| {
+            let mut value = range(diag.span);
+            value["severity"] = diag.kind.to_string().into();
+            value["code"] = diag.code.map(|code| code.to_string()).into();
+            value["message"] = diag.message.clone().into();
+            value["labels"] = diag
+                .labels
+                .iter()
+                .map(|label| {
+                    let mut value = range(label.span);
+                    value["message"] = label.message.clone().into();
+                    value
+                })
+                .collect();
+            value["notes"] = diag.notes.clone().into();
+            value
+        })
+        .collect();
+
+    let report = serde_json::json!({
+        "file": file.display().to_string(),
+        "diagnostics": diagnostics,
+    });
+    println!("{}", report);
+}
@@ -0,0 +1,152 @@
+//! MIDI ports for `relanote play`: streaming a performance to an output
+//! port, and following the MIDI clock of an input port
+//!
+//! Needs the `midi-out` feature, which links the platform's MIDI libraries.
+
+use relanote_render::{Clock, Performance};
+
+/// Play `performance` on the output port `device` as `clock` reaches its
+/// beats, over and over with `looping`; with `send_clock`, the port also
+/// gets MIDI clock so other programs can follow
+#[cfg(feature = "midi-out")]
+pub fn play(
+    device: &str,
+    performance: &Performance,
+    clock: &mut dyn Clock,
+    send_clock: bool,
+    looping: bool,
+) -> Result<(), String> {
+    use relanote_render::{schedule, ClockMessage};
+
+    let output = midir::MidiOutput::new("relanote").map_err(|e| e.to_string())?;
+    let ports = output.ports();
+    let names: Vec<String> = ports
+        .iter()
+        .map(|port| output.port_name(port).unwrap_or_default())
+        .collect();
+    let index = find_port(device, &names)?;
+    let mut connection = output
+        .connect(&ports[index], "relanote")
+        .map_err(|e| e.to_string())?;
+    println!("Playing to MIDI port {}", names[index]);
+
+    let events = if send_clock {
+        connection
+            .send(&[ClockMessage::Start.to_midi()])
+            .map_err(|e| e.to_string())?;
+        performance.events_with_clock()
+    } else {
+        performance.events().to_vec()
+    };
+    let finished = schedule(
+        clock,
+        &events,
+        performance.total_beats(),
+        looping,
+        |event| connection.send(&event.bytes),
+    )
+    .map_err(|e| e.to_string())?;
+
+    if send_clock {
+        connection
+            .send(&[ClockMessage::Stop.to_midi()])
+            .map_err(|e| e.to_string())?;
+    }
+    if !finished {
+        // Stopped partway: silence the notes still sounding
+        for channel in 0..16u8 {
+            connection
+                .send(&[0xB0 | channel, 123, 0])
+                .map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
+}
+
+/// Run `play` with a clock following the MIDI clock of the input port
+/// `device`
+#[cfg(feature = "midi-out")]
+pub fn follow_clock<R>(device: &str, play: impl FnOnce(&mut dyn Clock) -> R) -> Result<R, String> {
+    use relanote_render::{ClockMessage, FollowedClock};
+
+    let mut input = midir::MidiInput::new("relanote").map_err(|e| e.to_string())?;
+    // Clock messages are real-time messages, which are ignored by default
+    input.ignore(midir::Ignore::SysexAndActiveSense);
+    let ports = input.ports();
+    let names: Vec<String> = ports
+        .iter()
+        .map(|port| input.port_name(port).unwrap_or_default())
+        .collect();
+    let index = find_port(device, &names)?;
+
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let _connection = input
+        .connect(
+            &ports[index],
+            "relanote-clock",
+            move |_, bytes, _| {
+                if let Some(message) = ClockMessage::from_midi(bytes) {
+                    let _ = sender.send(message);
+                }
+            },
+            (),
+        )
+        .map_err(|e| e.to_string())?;
+    println!("Following the MIDI clock of {}", names[index]);
+    Ok(play(&mut FollowedClock::new(receiver)))
+}
+
+/// The port `device` names: a number from the list of ports, or part of a
+/// port's name
+#[cfg(feature = "midi-out")]
+fn find_port(device: &str, names: &[String]) -> Result<usize, String> {
+    let lowercase = device.to_lowercase();
+    let index = device
+        .parse::<usize>()
+        .ok()
+        .filter(|&index| index < names.len())
+        .or_else(|| {
+            names
+                .iter()
+                .position(|name| name.to_lowercase().contains(&lowercase))
+        });
+    match index {
+        Some(index) => Ok(index),
+        None if names.is_empty() => Err("there are no MIDI ports".to_string()),
+        None => {
+            let list: Vec<String> = names
+                .iter()
+                .enumerate()
+                .map(|(index, name)| format!("  {}: {}", index, name))
+                .collect();
+            Err(format!(
+                "no MIDI port matches \"{}\"; the ports are:\n{}",
+                device,
+                list.join("\n")
+            ))
+        }
+    }
+}
+
+#[cfg(not(feature = "midi-out"))]
+pub fn play(
+    _device: &str,
+    _performance: &Performance,
+    _clock: &mut dyn Clock,
+    _send_clock: bool,
+    _looping: bool,
+) -> Result<(), String> {
+    Err(NO_MIDI.to_string())
+}
+
+#[cfg(not(feature = "midi-out"))]
+pub fn follow_clock<R>(
+    _device: &str,
+    _play: impl FnOnce(&mut dyn Clock) -> R,
+) -> Result<R, String> {
+    Err(NO_MIDI.to_string())
+}
+
+#[cfg(not(feature = "midi-out"))]
+const NO_MIDI: &str =
+    "this relanote was built without MIDI ports; build it with `--features midi-out`";
@@ -237,11 +237,7 @@ add 3 4
     assert!(stdout.contains("Int(7)"));
 }
 
-// NOTE: map, filter tests are ignored because the functional builtins
-// need access to the evaluator to apply closures. See builtins/functional.rs
-
 #[test]
-#[ignore = "functional builtins need evaluator context for closure application"]
 fn test_run_map() {
     // Use pipe syntax since f (x) is parsed same as f(x)
     let file = create_temp_file(
@@ -264,7 +260,6 @@ fn test_run_map() {
 }
 
 #[test]
-#[ignore = "functional builtins need evaluator context for closure application"]
 fn test_run_filter() {
     // Use pipe syntax
     let file = create_temp_file(
@@ -411,6 +406,115 @@ x
     assert!(output.status.success());
 }
 
+#[test]
+fn test_check_sees_module_bindings() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(
+        dir.path().join("melody.rela"),
+        "export let lead = | R M3 P5 |\n",
+    )
+    .unwrap();
+    let main = dir.path().join("main.rela");
+    fs::write(&main, "use melody::lead\nlet song = lead ++ lead\n").unwrap();
+
+    let output = relanote_cmd()
+        .arg("check")
+        .arg(&main)
+        .output()
+        .expect("Failed to execute command");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+
+    // Imported bindings keep their types, and unknown modules are reported
+    fs::write(
+        &main,
+        "use melody::lead\nuse drums::kick\nlet n = lead + 1\n",
+    )
+    .unwrap();
+    let output = relanote_cmd()
+        .args(["check", "--format", "json"])
+        .arg(&main)
+        .output()
+        .expect("Failed to execute command");
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("\"E0208\""), "{stdout}");
+    assert!(stdout.contains("\"E0201\""), "{stdout}");
+}
+
+#[test]
+fn test_local_module_named_like_a_std_only_module() {
+    // `drums` is the project's own module; the stdlib one is `std::drums`
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("drums.rela"), "export let kick = | R R |\n").unwrap();
+    let main = dir.path().join("main.rela");
+    fs::write(
+        &main,
+        "use drums::kick\nlet song = layer [kick ++ kick]\nrender song\n",
+    )
+    .unwrap();
+
+    for command in ["check", "render"] {
+        let output = relanote_cmd()
+            .current_dir(dir.path())
+            .args([command, "main.rela"])
+            .output()
+            .expect("Failed to execute command");
+        assert!(
+            output.status.success(),
+            "{command}: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+}
+
+#[test]
+fn test_check_rejects_private_import() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(
+        dir.path().join("melody.rela"),
+        "export let lead = | R M3 P5 |\nlet helper = | R |\n",
+    )
+    .unwrap();
+    let main = dir.path().join("main.rela");
+    fs::write(&main, "use melody::helper\n").unwrap();
+
+    let output = relanote_cmd()
+        .args(["check", "--format", "json"])
+        .arg(&main)
+        .output()
+        .expect("Failed to execute command");
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("\"E0210\""), "{stdout}");
+    assert!(stdout.contains("melody.rela:2:5"), "{stdout}");
+}
+
+#[test]
+fn test_check_reports_use_of_broken_module() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(
+        dir.path().join("melody.rela"),
+        "export let lead = | R M3 P5 |\nlet oops = lead + \"x\"\n",
+    )
+    .unwrap();
+    let main = dir.path().join("main.rela");
+    fs::write(&main, "use melody::lead\nlet song = lead ++ lead\n").unwrap();
+
+    let output = relanote_cmd()
+        .args(["check", "--format", "json"])
+        .arg(&main)
+        .output()
+        .expect("Failed to execute command");
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("\"E0213\""), "{stdout}");
+    assert!(stdout.contains("melody.rela:2:"), "{stdout}");
+}
+
 // ===== Format Command Tests =====
 
 #[test]
@@ -426,6 +530,173 @@ fn test_format_command() {
     assert!(stdout.contains("let x = 42"));
 }
 
+#[test]
+fn test_format_check() {
+    let formatted = create_temp_file("let x = 42\n");
+    let unformatted = create_temp_file("let   x=42\n");
+
+    let output = relanote_cmd()
+        .args(["fmt", "--check", formatted.path().to_str().unwrap()])
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+
+    let output = relanote_cmd()
+        .args([
+            "fmt",
+            "--check",
+            formatted.path().to_str().unwrap(),
+            unformatted.path().to_str().unwrap(),
+        ])
+        .output()
+        .expect("Failed to execute command");
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("-let   x=42"));
+    assert!(stdout.contains("+let x = 42"));
+    // Check mode never rewrites the file
+    assert_eq!(
+        fs::read_to_string(unformatted.path()).unwrap(),
+        "let   x=42\n"
+    );
+}
+
+// ===== Build Command Tests =====
+
+#[test]
+fn test_build_project() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::create_dir(dir.path().join("lib")).unwrap();
+    fs::write(
+        dir.path().join("lib/melody.rela"),
+        "export let lead = | R M3 P5 |\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.path().join("main.rela"),
+        "use melody::lead\nlayer [ lead ]\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.path().join("relanote.toml"),
+        "[package]\nname = \"demo\"\nmodule_paths = [\"lib\"]\n\n\
+         [[output]]\npath = \"dist/demo.mid\"\n\n[[output]]\npath = \"dist/demo.abc\"\n",
+    )
+    .unwrap();
+
+    let output = relanote_cmd()
+        .arg("build")
+        .current_dir(dir.path())
+        .output()
+        .expect("Failed to execute command");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(fs::read(dir.path().join("dist/demo.mid"))
+        .unwrap()
+        .starts_with(b"MThd"));
+    assert!(fs::read_to_string(dir.path().join("dist/demo.abc"))
+        .unwrap()
+        .contains("T:demo"));
+
+    // A type error in any module fails the build
+    fs::write(dir.path().join("lib/melody.rela"), "let lead = 1 + \"a\"\n").unwrap();
+    let output = relanote_cmd()
+        .args(["build", "--manifest-path"])
+        .arg(dir.path().join("relanote.toml"))
+        .output()
+        .expect("Failed to execute command");
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_build_with_packages_writes_lockfile() {
+    let dir = tempfile::tempdir().unwrap();
+    let package = dir.path().join("packages/melody_lib");
+    fs::create_dir_all(&package).unwrap();
+    fs::write(
+        package.join("relanote.toml"),
+        "[package]\nname = \"melody_lib\"\nversion = \"0.2.1\"\n",
+    )
+    .unwrap();
+    fs::write(package.join("main.rela"), "export let hook = | R M3 P5 |\n").unwrap();
+    fs::write(
+        package.join("riffs.rela"),
+        "export let riff = | P5 P4 M3 |\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.path().join("main.rela"),
+        "use melody_lib::hook\nuse melody_lib::riffs::*\nlayer [ hook ++ riff ]\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.path().join("relanote.toml"),
+        "[package]\nname = \"song\"\n\n[dependencies]\nmelody_lib = \"0.2\"\n\n\
+         [[output]]\npath = \"song.mid\"\n",
+    )
+    .unwrap();
+
+    let build = |locked: bool| {
+        let mut cmd = relanote_cmd();
+        cmd.arg("build").current_dir(dir.path());
+        if locked {
+            cmd.arg("--locked");
+        }
+        cmd.output().expect("Failed to execute command")
+    };
+
+    let output = build(false);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let lockfile = fs::read_to_string(dir.path().join("relanote.lock")).unwrap();
+    assert!(lockfile.contains("name = \"melody_lib\"\nversion = \"0.2.1\""));
+    assert!(build(true).status.success());
+
+    // Every command finds the package from the manifest above the file, not
+    // just `build`
+    let main = dir.path().join("main.rela");
+    let midi = dir.path().join("out.mid");
+    for args in [
+        vec!["check".as_ref(), main.as_os_str()],
+        vec!["run".as_ref(), main.as_os_str()],
+        vec![
+            "render".as_ref(),
+            main.as_os_str(),
+            "-o".as_ref(),
+            midi.as_os_str(),
+        ],
+        vec!["inspect".as_ref(), main.as_os_str()],
+    ] {
+        let output = relanote_cmd()
+            .args(&args)
+            .output()
+            .expect("Failed to execute command");
+        assert!(
+            output.status.success(),
+            "{:?}: {}",
+            args,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    // A package whose version no longer matches the requirement is rejected
+    fs::write(
+        package.join("relanote.toml"),
+        "[package]\nname = \"melody_lib\"\nversion = \"0.3.0\"\n",
+    )
+    .unwrap();
+    let output = build(false);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("requires version 0.2"));
+}
+
 // ===== Render Command Tests =====
 
 #[test]
@@ -463,6 +734,387 @@ layer [melody]
     assert_eq!(&midi_content[0..4], b"MThd");
 }
 
+#[test]
+fn test_render_midi_options() {
+    // Tempo meta event: FF 51 03 followed by microseconds per quarter note
+    let render = |source: &str, flags: &[&str]| {
+        let file = create_temp_file(source);
+        let output_midi = tempfile::NamedTempFile::with_suffix(".mid").unwrap();
+        let output = relanote_cmd()
+            .args([
+                "render",
+                file.path().to_str().unwrap(),
+                "-o",
+                output_midi.path().to_str().unwrap(),
+            ])
+            .args(flags)
+            .output()
+            .expect("Failed to execute command");
+        assert!(
+            output.status.success(),
+            "stderr: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        let midi = fs::read(output_midi.path()).unwrap();
+        let tempo_at = midi
+            .windows(3)
+            .position(|w| w == [0xFF, 0x51, 0x03])
+            .unwrap()
+            + 3;
+        let tempo = u32::from_be_bytes([0, midi[tempo_at], midi[tempo_at + 1], midi[tempo_at + 2]]);
+        let ticks_per_beat = u16::from_be_bytes([midi[12], midi[13]]);
+        (60_000_000 / tempo, ticks_per_beat)
+    };
+
+    let song = "layer [| R M3 P5 |]";
+    assert_eq!(render(song, &[]), (120, 480));
+    assert_eq!(
+        render(&format!("set tempo = 140\n{}", song), &[]),
+        (140, 480)
+    );
+    // Flags win over the program's bindings
+    assert_eq!(
+        render(
+            &format!("set tempo = 140\n{}", song),
+            &["--tempo", "90", "--ticks-per-beat", "96"]
+        ),
+        (90, 96)
+    );
+
+    let file = create_temp_file(song);
+    let output = relanote_cmd()
+        .args(["render", file.path().to_str().unwrap(), "-o", "out.mid"])
+        .args(["--bend-range", "0"])
+        .output()
+        .expect("Failed to execute command");
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_render_names_output_after_meta_title() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("song.rela");
+    fs::write(
+        &file,
+        "meta { title: \"Night Drive\", copyright: \"(c) 2026\" }\nlayer [| R M3 P5 |]",
+    )
+    .unwrap();
+
+    let output = relanote_cmd()
+        .args(["render", file.to_str().unwrap()])
+        .output()
+        .expect("Failed to execute command");
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let midi = fs::read(dir.path().join("night-drive.mid")).unwrap();
+    assert!(midi.windows(8).any(|w| w == b"(c) 2026"));
+}
+
+#[test]
+fn test_render_writes_every_render_target() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("song.rela");
+    fs::write(
+        &file,
+        "let song = layer [| R M3 P5 |]\nrender song\nrender layer [| P5 M7 P8 |]\n| R |",
+    )
+    .unwrap();
+
+    let output = relanote_cmd()
+        .args(["render", file.to_str().unwrap(), "-o"])
+        .arg(dir.path().join("out.mid"))
+        .output()
+        .expect("Failed to execute command");
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // The targets are written, not the last expression
+    assert!(!dir.path().join("out.mid").exists());
+    for name in ["out-1.mid", "out-2.mid"] {
+        let midi = fs::read(dir.path().join(name)).unwrap();
+        assert_eq!(&midi[0..4], b"MThd");
+    }
+}
+
+#[test]
+fn test_render_named_targets_into_a_directory() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("song.rela");
+    fs::write(
+        &file,
+        "let verse = layer [| R M3 P5 |]\nrender \"verse_demo\" = verse\nrender verse",
+    )
+    .unwrap();
+
+    let out = dir.path().join("out");
+    let output = relanote_cmd()
+        .args(["render", file.to_str().unwrap(), "-o"])
+        .arg(format!("{}/", out.display()))
+        .output()
+        .expect("Failed to execute command");
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(out.join("verse_demo.mid").exists());
+    // An unnamed target is named after the file, numbered among the targets
+    assert!(out.join("song-2.mid").exists());
+}
+
+#[test]
+fn test_render_target_names_stay_in_the_output_directory() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("song.rela");
+    fs::write(
+        &file,
+        "let verse = layer [| R M3 P5 |]\nrender \"../escaped\" = verse\nrender verse",
+    )
+    .unwrap();
+
+    let out = dir.path().join("out");
+    let output = relanote_cmd()
+        .args(["render", file.to_str().unwrap(), "-o"])
+        .arg(format!("{}/", out.display()))
+        .output()
+        .expect("Failed to execute command");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("../escaped"));
+    assert!(!dir.path().join("escaped.mid").exists());
+}
+
+#[test]
+fn test_render_selected_target_and_section() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("song.rela");
+    fs::write(
+        &file,
+        "let intro = section \"Intro\" { layer [| R M3 |] }\n\
+         let verse = section \"Verse\" { layer [| P5 M7 P8 |] }\n\
+         render \"demo\" = intro ++ verse\nrender verse",
+    )
+    .unwrap();
+    let render = |args: &[&str], output: &str| {
+        relanote_cmd()
+            .args(["render", file.to_str().unwrap(), "-o"])
+            .arg(dir.path().join(output))
+            .args(args)
+            .output()
+            .expect("Failed to execute command")
+    };
+
+    // A selected target is written to the output path as it is
+    let output = render(&["--target", "demo", "--section", "Verse"], "verse.json");
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let json = fs::read_to_string(dir.path().join("verse.json")).unwrap();
+    assert!(json.contains("\"Verse\"") && !json.contains("\"Intro\""));
+
+    // Unnamed targets are selected by number
+    let output = render(&["--target", "2"], "second.mid");
+    assert!(output.status.success());
+    assert!(dir.path().join("second.mid").exists());
+
+    let output = render(&["--section", "Chorus"], "none.mid");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("no section named `Chorus`"));
+}
+
+// ===== Inspect Command Tests =====
+
+#[test]
+fn test_inspect_explains_silent_parts() {
+    let file = create_temp_file(
+        r#"
+set tempo = 90
+let melody = | R M3 P5 M3 |:4
+let intro = section "Intro" { layer [melody |> reverb 0.4, | - - |:8, | [R M3 P5] |:4 |> volume 0.0] }
+let verse = section "Verse" with key: G4 { melody }
+intro ++ verse
+"#,
+    );
+    let output = relanote_cmd()
+        .args(["inspect", file.path().to_str().unwrap()])
+        .output()
+        .expect("Failed to execute command");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(stdout.contains("song: 90 BPM, key C4, 4/4, 12 beats (3 bars)"));
+    assert!(stdout.contains("section \"Intro\": bar 1, 8 beats, key C4"));
+    assert!(stdout.contains("ch 1, 1 bars, 4 beats, 4 notes\n    effects: reverb 0.40"));
+    assert!(stdout.contains("2 bars, 8 beats, 0 notes\n    silent: no notes"));
+    assert!(stdout.contains("3 notes\n    effects: volume 0.00\n    silent: volume is 0"));
+    assert!(stdout.contains("section \"Verse\": bar 3, 4 beats, key G4"));
+}
+
+#[test]
+fn test_diff_reports_changed_notes_by_bar() {
+    let old = create_temp_file(
+        r#"
+set tempo = 90
+let intro = section "Intro" { | R M3 P5 M3 |:4 }
+let verse = section "Verse" { | R M3 P5 M3 |:4 }
+intro ++ verse
+"#,
+    );
+    let new = create_temp_file(
+        r#"
+set tempo = 100
+let intro = section "Intro" { | R M3 P4 M3 |:4 }
+let verse = section "Verse" { | R M3 P5 M3 R |:5 }
+intro ++ verse
+"#,
+    );
+    let diff = |old: &tempfile::NamedTempFile, new: &tempfile::NamedTempFile| {
+        relanote_cmd()
+            .args([
+                "diff",
+                old.path().to_str().unwrap(),
+                new.path().to_str().unwrap(),
+            ])
+            .output()
+            .expect("Failed to execute command")
+    };
+
+    let output = diff(&old, &new);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(output.status.code(), Some(1), "stdout: {}", stdout);
+    assert!(stdout.contains("tempo: 90 -> 100 BPM"));
+    assert!(stdout.contains("bar 1, beat 3: ~ G4 (1 beat) -> F4 (1 beat)"));
+    assert!(stdout.contains("bar 3, beat 1: + C4 (1 beat)"));
+    assert!(!stdout.contains("bar 2"));
+
+    let output = diff(&old, &old);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("No musical differences"));
+}
+
+// ===== Test Command Tests =====
+
+#[test]
+fn test_test_command_snapshots_music() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("song.rela");
+    let write = |source: &str| fs::write(&file, source).unwrap();
+    let run = |flags: &[&str]| {
+        relanote_cmd()
+            .arg("test")
+            .arg(dir.path())
+            .args(flags)
+            .output()
+            .expect("Failed to execute command")
+    };
+
+    write("let chorus = | R M3 P5 |:3\ntest \"chorus\" { chorus }\ntest \"sums\" { 1 + 1 == 2 }\nchorus\n");
+    let output = run(&[]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "stdout: {}", stdout);
+    assert!(stdout.contains("\"chorus\" ... ok (snapshot saved)"));
+    assert!(stdout.contains("2 passed, 0 failed"));
+    let snapshot = dir.path().join("__snapshots__/song/chorus.snap");
+    assert!(fs::read_to_string(&snapshot)
+        .unwrap()
+        .contains("bar 1, beat 3: G4 (1 beat)"));
+
+    // Changing the music fails the test until the snapshot is updated
+    write("let chorus = | R M3 P4 |:3\ntest \"chorus\" { chorus }\nchorus\n");
+    let output = run(&[]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(output.status.code(), Some(1), "stdout: {}", stdout);
+    assert!(stdout.contains("\"chorus\" ... FAILED"));
+    assert!(stdout.contains("+    bar 1, beat 3: F4 (1 beat)"));
+
+    assert!(run(&["--update"]).status.success());
+    assert!(run(&[]).status.success());
+
+    write("test \"false\" { 1 == 2 }\n");
+    assert_eq!(run(&[]).status.code(), Some(1));
+
+    // Names that would share a snapshot file fail instead of overwriting it
+    write("test \"Verse 1\" { | R |:1 }\ntest \"verse-1\" { | P5 |:1 }\n");
+    let output = run(&["--update"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(output.status.code(), Some(1), "stdout: {}", stdout);
+    assert!(stdout.contains("\"Verse 1\" ... ok"));
+    assert!(stdout.contains("\"verse-1\" ... FAILED"));
+    assert!(stdout.contains("already test \"Verse 1\"'s"));
+}
+
+// ===== Doc Command Tests =====
+
+#[test]
+fn test_doc_command_module_file() {
+    let file = create_temp_file(
+        "--- Walking bass in quarters\nexport let walk = | R M3 P5 M6 |\nlet twice b = b ++ b\nlet private = | R |\nexport twice\n",
+    );
+    let doc = |flags: &[&str]| {
+        let output = relanote_cmd()
+            .arg("doc")
+            .arg(file.path())
+            .args(flags)
+            .output()
+            .expect("Failed to execute command");
+        assert!(output.status.success());
+        String::from_utf8_lossy(&output.stdout).to_string()
+    };
+
+    let markdown = doc(&[]);
+    assert!(markdown.contains("## walk\n\n```rela\nwalk : Block\n```\n\nWalking bass in quarters"));
+    assert!(markdown.contains("twice : a -> a"));
+    assert!(!markdown.contains("private"));
+
+    let html = doc(&["--format", "html"]);
+    assert!(html.contains("<pre><code>twice : a -&gt; a</code></pre>"));
+    assert!(html.contains("<p>Walking bass in quarters</p>"));
+}
+
+#[test]
+fn test_doc_command_stdlib() {
+    let output = relanote_cmd()
+        .args(["doc", "patterns"])
+        .output()
+        .expect("Failed to execute command");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success());
+    assert!(stdout.starts_with("# Module `patterns`"));
+    assert!(stdout.contains("alberti : a -> Int -> Block\n```\n\nLow, high, middle, high"));
+
+    let output = relanote_cmd()
+        .args(["doc", "no_such_module"])
+        .output()
+        .expect("Failed to execute command");
+    assert_eq!(output.status.code(), Some(1));
+
+    let dir = tempfile::tempdir().unwrap();
+    let output = relanote_cmd()
+        .arg("doc")
+        .arg("-o")
+        .arg(dir.path())
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+    let index = fs::read_to_string(dir.path().join("index.md")).unwrap();
+    assert!(index.contains("- [synths::bass](synths-bass.md)"));
+    assert!(fs::read_to_string(dir.path().join("synths-bass.md"))
+        .unwrap()
+        .contains("## WoodBass"));
+}
+
 // ===== Audio Generation Tests from Example Files =====
 // These tests ensure that .rela files can be rendered to MIDI without errors
 
@@ -570,7 +1222,33 @@ fn test_render_showcase_organ() {
     test_render_example_file("showcases/showcase_organ.rela");
 }
 
-// Note: showcase_symphony.rela outputs Block, not Song (no layer/voice)
+#[test]
+fn test_render_showcase_symphony() {
+    test_render_example_file("showcases/showcase_symphony.rela");
+}
+
+#[test]
+fn test_examples_check_without_warnings() {
+    for dir in ["showcases", "tutorials"] {
+        for entry in fs::read_dir(get_examples_dir().join(dir)).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_none_or(|ext| ext != "rela") {
+                continue;
+            }
+            let output = relanote_cmd()
+                .args(["check", "--format", "json"])
+                .arg(&path)
+                .output()
+                .expect("Failed to execute command");
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            assert!(
+                output.status.success() && stdout.contains("\"diagnostics\":[]"),
+                "{}: {stdout}",
+                path.display()
+            );
+        }
+    }
+}
 
 // ===== Newline Handling Tests (regression) =====
 
@@ -28,6 +28,92 @@ impl fmt::Display for DiagnosticKind {
     }
 }
 
+/// Stable identifier for a kind of diagnostic, shown as e.g. `E0201`
+///
+/// `E01xx` are syntax errors, `E02xx` type errors, `W01xx` checker
+/// warnings and `W02xx` rendering warnings.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DiagnosticCode {
+    // Syntax
+    UnexpectedToken,
+    UnexpectedEof,
+    InvalidInterval,
+    InvalidScaleIndex,
+    UnclosedDelimiter,
+    InvalidExpression,
+    InvalidSyntax,
+
+    // Types
+    TypeMismatch,
+    UndefinedVariable,
+    UndefinedType,
+    InfiniteType,
+    NotAFunction,
+    NotAScale,
+    TimeAlignmentMismatch,
+    UnresolvedModule,
+    UnknownModuleItem,
+    PrivateModuleItem,
+    NonExhaustiveMatch,
+    InvalidArgument,
+    ModuleHasErrors,
+
+    // Checker warnings
+    ShadowedBuiltin,
+    UnreachablePattern,
+    UnusedBinding,
+    UnusedImport,
+    ShadowedPrelude,
+    UnplayedBinding,
+
+    // Rendering warnings
+    PartDropped,
+    OutOfScale,
+    SharedMpeZone,
+}
+
+impl DiagnosticCode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DiagnosticCode::UnexpectedToken => "E0101",
+            DiagnosticCode::UnexpectedEof => "E0102",
+            DiagnosticCode::InvalidInterval => "E0103",
+            DiagnosticCode::InvalidScaleIndex => "E0104",
+            DiagnosticCode::UnclosedDelimiter => "E0105",
+            DiagnosticCode::InvalidExpression => "E0106",
+            DiagnosticCode::InvalidSyntax => "E0107",
+            DiagnosticCode::TypeMismatch => "E0201",
+            DiagnosticCode::UndefinedVariable => "E0202",
+            DiagnosticCode::UndefinedType => "E0203",
+            DiagnosticCode::InfiniteType => "E0204",
+            DiagnosticCode::NotAFunction => "E0205",
+            DiagnosticCode::NotAScale => "E0206",
+            DiagnosticCode::TimeAlignmentMismatch => "E0207",
+            DiagnosticCode::UnresolvedModule => "E0208",
+            DiagnosticCode::UnknownModuleItem => "E0209",
+            DiagnosticCode::PrivateModuleItem => "E0210",
+            DiagnosticCode::NonExhaustiveMatch => "E0211",
+            DiagnosticCode::InvalidArgument => "E0212",
+            DiagnosticCode::ModuleHasErrors => "E0213",
+            DiagnosticCode::ShadowedBuiltin => "W0101",
+            DiagnosticCode::UnreachablePattern => "W0102",
+            DiagnosticCode::UnusedBinding => "W0103",
+            DiagnosticCode::UnusedImport => "W0104",
+            DiagnosticCode::ShadowedPrelude => "W0105",
+            DiagnosticCode::UnplayedBinding => "W0106",
+            DiagnosticCode::PartDropped => "W0201",
+            DiagnosticCode::OutOfScale => "W0202",
+            DiagnosticCode::SharedMpeZone => "W0203",
+        }
+    }
+}
+
+impl fmt::Display for DiagnosticCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
 /// A secondary label for a diagnostic
 #[derive(Clone, Debug)]
 pub struct Label {
@@ -48,6 +134,7 @@ impl Label {
 #[derive(Clone, Debug)]
 pub struct Diagnostic {
     pub kind: DiagnosticKind,
+    pub code: Option<DiagnosticCode>,
     pub message: String,
     pub span: Span,
     pub labels: Vec<Label>,
@@ -55,9 +142,10 @@ pub struct Diagnostic {
 }
 
 impl Diagnostic {
-    pub fn error(message: impl Into<String>, span: Span) -> Self {
+    pub fn new(kind: DiagnosticKind, message: impl Into<String>, span: Span) -> Self {
         Self {
-            kind: DiagnosticKind::Error,
+            kind,
+            code: None,
             message: message.into(),
             span,
             labels: Vec::new(),
@@ -65,14 +153,21 @@ impl Diagnostic {
         }
     }
 
+    pub fn error(message: impl Into<String>, span: Span) -> Self {
+        Self::new(DiagnosticKind::Error, message, span)
+    }
+
     pub fn warning(message: impl Into<String>, span: Span) -> Self {
-        Self {
-            kind: DiagnosticKind::Warning,
-            message: message.into(),
-            span,
-            labels: Vec::new(),
-            notes: Vec::new(),
-        }
+        Self::new(DiagnosticKind::Warning, message, span)
+    }
+
+    pub fn info(message: impl Into<String>, span: Span) -> Self {
+        Self::new(DiagnosticKind::Info, message, span)
+    }
+
+    pub fn with_code(mut self, code: DiagnosticCode) -> Self {
+        self.code = Some(code);
+        self
     }
 
     pub fn with_label(mut self, span: Span, message: impl Into<String>) -> Self {
@@ -113,6 +208,10 @@ impl Diagnostics {
         self.add(Diagnostic::warning(message, span));
     }
 
+    pub fn info(&mut self, message: impl Into<String>, span: Span) {
+        self.add(Diagnostic::info(message, span));
+    }
+
     pub fn has_errors(&self) -> bool {
         self.diagnostics.iter().any(|d| d.is_error())
     }
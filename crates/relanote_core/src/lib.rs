@@ -3,7 +3,7 @@ pub mod intern;
 pub mod source;
 pub mod span;
 
-pub use diagnostics::{Diagnostic, DiagnosticKind, Diagnostics};
+pub use diagnostics::{Diagnostic, DiagnosticCode, DiagnosticKind, Diagnostics};
 pub use intern::{intern, InternedStr};
 pub use source::{Source, SourceDb, SourceId};
 pub use span::{Location, Span, Spanned};
@@ -0,0 +1,101 @@
+//! Assertion builtins: checks that stop evaluation when music isn't as expected
+
+use crate::error::EvalError;
+use crate::value::{values_equal, BlockValue, CallSite, ScaleValue, Value};
+
+/// How far a block's length may be from the expected one, in beats
+const BEATS_TOLERANCE: f64 = 1e-6;
+
+/// Check that two values are equal
+/// Usage: assert_eq(expected, actual) -> true
+pub fn builtin_assert_eq(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
+    if args.len() != 2 {
+        return Err(EvalError::Custom {
+            message: "assert_eq expects 2 arguments (expected, actual)".to_string(),
+            span: call.span,
+        });
+    }
+
+    if !values_equal(&args[0], &args[1]) {
+        return Err(EvalError::Custom {
+            message: format!(
+                "assertion failed: values differ\n  expected: {:?}\n    actual: {:?}",
+                args[0], args[1]
+            ),
+            span: call.span,
+        });
+    }
+    Ok(Value::Bool(true))
+}
+
+/// Check that a block lasts a number of beats, passing the block on
+/// Usage: block |> assert_beats 4, or assert_beats(block, 4)
+pub fn builtin_assert_beats(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
+    if args.len() != 2 {
+        return Err(EvalError::Custom {
+            message: "assert_beats expects 2 arguments (block, beats)".to_string(),
+            span: call.span,
+        });
+    }
+
+    let (block, beats) = match (&args[0], &args[1]) {
+        (Value::Block(block), beats) | (beats, Value::Block(block)) => match beats {
+            Value::Int(beats) => (block, *beats as f64),
+            Value::Float(beats) => (block, *beats),
+            Value::Duration(duration) => (block, duration.beats),
+            _ => return Err(block_and(&args, "Int")),
+        },
+        _ => return Err(block_and(&args, "Int")),
+    };
+
+    let actual = block.total_beats();
+    if (actual - beats).abs() > BEATS_TOLERANCE {
+        return Err(EvalError::Custom {
+            message: format!(
+                "assertion failed: expected {} beats, the block has {}",
+                beats, actual
+            ),
+            span: call.span,
+        });
+    }
+    Ok(Value::Block(block.clone()))
+}
+
+/// Check that every note of a block is in a scale, passing the block on
+/// Usage: block |> assert_in_scale Major, or assert_in_scale(block, Major)
+pub fn builtin_assert_in_scale(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
+    if args.len() != 2 {
+        return Err(EvalError::Custom {
+            message: "assert_in_scale expects 2 arguments (block, scale)".to_string(),
+            span: call.span,
+        });
+    }
+
+    let (block, scale): (&BlockValue, &ScaleValue) = match (&args[0], &args[1]) {
+        (Value::Block(block), Value::Scale(scale)) | (Value::Scale(scale), Value::Block(block)) => {
+            (block, scale)
+        }
+        _ => return Err(block_and(&args, "Scale")),
+    };
+
+    let outside = scale.notes_outside(std::slice::from_ref(block));
+    if !outside.is_empty() {
+        return Err(EvalError::Custom {
+            message: format!(
+                "assertion failed: notes outside {}: {}",
+                scale.name,
+                outside.join(", ")
+            ),
+            span: call.span,
+        });
+    }
+    Ok(Value::Block(block.clone()))
+}
+
+fn block_and(args: &[Value], other: &str) -> EvalError {
+    EvalError::TypeError {
+        expected: format!("Block and {}", other),
+        found: format!("{:?}, {:?}", args[0], args[1]),
+        span: relanote_core::Span::dummy(),
+    }
+}
@@ -1,14 +1,19 @@
 //! Block transformation builtins
 
+use relanote_ast::Articulation;
+
 use crate::error::EvalError;
-use crate::value::{BlockValue, IntervalValue, PartValue, SlotValue, Value};
+use crate::value::{
+    drum_key, BlockValue, CallSite, IntervalValue, NoteMeta, PartValue, ScaleDegree, SectionValue,
+    SlotValue, Swing, Value,
+};
 
 /// Reverse a block
-pub fn builtin_reverse(args: Vec<Value>) -> Result<Value, EvalError> {
+pub fn builtin_reverse(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
     if args.len() != 1 {
         return Err(EvalError::Custom {
             message: "reverse expects 1 argument".to_string(),
-            span: relanote_core::Span::dummy(),
+            span: call.span,
         });
     }
 
@@ -24,7 +29,7 @@ pub fn builtin_reverse(args: Vec<Value>) -> Result<Value, EvalError> {
         _ => Err(EvalError::TypeError {
             expected: "Block".to_string(),
             found: format!("{:?}", args[0]),
-            span: relanote_core::Span::dummy(),
+            span: call.span,
         }),
     }
 }
@@ -33,11 +38,11 @@ pub fn builtin_reverse(args: Vec<Value>) -> Result<Value, EvalError> {
 /// Usage: block |> rotate(n) or rotate(n, block)
 /// Positive n rotates left (first elements move to end)
 /// Negative n rotates right (last elements move to start)
-pub fn builtin_rotate(args: Vec<Value>) -> Result<Value, EvalError> {
+pub fn builtin_rotate(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
     if args.len() != 2 {
         return Err(EvalError::Custom {
             message: "rotate expects 2 arguments".to_string(),
-            span: relanote_core::Span::dummy(),
+            span: call.span,
         });
     }
 
@@ -49,7 +54,7 @@ pub fn builtin_rotate(args: Vec<Value>) -> Result<Value, EvalError> {
             return Err(EvalError::TypeError {
                 expected: "Block and Int".to_string(),
                 found: format!("{:?}, {:?}", args[0], args[1]),
-                span: relanote_core::Span::dummy(),
+                span: call.span,
             })
         }
     };
@@ -73,11 +78,11 @@ pub fn builtin_rotate(args: Vec<Value>) -> Result<Value, EvalError> {
 
 /// Repeat a block n times
 /// Usage: block |> repeat(n) or repeat(n, block)
-pub fn builtin_repeat(args: Vec<Value>) -> Result<Value, EvalError> {
+pub fn builtin_repeat(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
     if args.len() != 2 {
         return Err(EvalError::Custom {
             message: "repeat expects 2 arguments".to_string(),
-            span: relanote_core::Span::dummy(),
+            span: call.span,
         });
     }
 
@@ -89,7 +94,7 @@ pub fn builtin_repeat(args: Vec<Value>) -> Result<Value, EvalError> {
             return Err(EvalError::TypeError {
                 expected: "Block and Int".to_string(),
                 found: format!("{:?}, {:?}", args[0], args[1]),
-                span: relanote_core::Span::dummy(),
+                span: call.span,
             })
         }
     };
@@ -107,11 +112,11 @@ pub fn builtin_repeat(args: Vec<Value>) -> Result<Value, EvalError> {
 
 /// Transpose a block up by one octave (12 semitones / 1200 cents)
 /// Usage: block |> octaveUp or octaveUp(block)
-pub fn builtin_octave_up(args: Vec<Value>) -> Result<Value, EvalError> {
+pub fn builtin_octave_up(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
     if args.len() != 1 {
         return Err(EvalError::Custom {
             message: "octaveUp expects 1 argument".to_string(),
-            span: relanote_core::Span::dummy(),
+            span: call.span,
         });
     }
 
@@ -131,18 +136,18 @@ pub fn builtin_octave_up(args: Vec<Value>) -> Result<Value, EvalError> {
         _ => Err(EvalError::TypeError {
             expected: "Block".to_string(),
             found: format!("{:?}", args[0]),
-            span: relanote_core::Span::dummy(),
+            span: call.span,
         }),
     }
 }
 
 /// Transpose a block down by one octave (12 semitones / 1200 cents)
 /// Usage: block |> octaveDown or octaveDown(block)
-pub fn builtin_octave_down(args: Vec<Value>) -> Result<Value, EvalError> {
+pub fn builtin_octave_down(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
     if args.len() != 1 {
         return Err(EvalError::Custom {
             message: "octaveDown expects 1 argument".to_string(),
-            span: relanote_core::Span::dummy(),
+            span: call.span,
         });
     }
 
@@ -162,18 +167,18 @@ pub fn builtin_octave_down(args: Vec<Value>) -> Result<Value, EvalError> {
         _ => Err(EvalError::TypeError {
             expected: "Block".to_string(),
             found: format!("{:?}", args[0]),
-            span: relanote_core::Span::dummy(),
+            span: call.span,
         }),
     }
 }
 
 /// Transpose a block by an interval
 /// Usage: block |> transpose(interval) or transpose(interval, block)
-pub fn builtin_transpose(args: Vec<Value>) -> Result<Value, EvalError> {
+pub fn builtin_transpose(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
     if args.len() != 2 {
         return Err(EvalError::Custom {
             message: "transpose expects 2 arguments".to_string(),
-            span: relanote_core::Span::dummy(),
+            span: call.span,
         });
     }
 
@@ -185,7 +190,7 @@ pub fn builtin_transpose(args: Vec<Value>) -> Result<Value, EvalError> {
             return Err(EvalError::TypeError {
                 expected: "Block and Interval".to_string(),
                 found: format!("{:?}, {:?}", args[0], args[1]),
-                span: relanote_core::Span::dummy(),
+                span: call.span,
             })
         }
     };
@@ -207,12 +212,14 @@ fn transpose_slot(slot: &SlotValue, cents: f64) -> SlotValue {
             interval,
             articulations,
             duration_beats,
+            meta,
         } => SlotValue::Note {
             interval: IntervalValue {
                 cents: interval.cents + cents,
             },
             articulations: articulations.clone(),
             duration_beats: *duration_beats,
+            meta: meta.clone(),
         },
         SlotValue::Rest { duration_beats } => SlotValue::Rest {
             duration_beats: *duration_beats,
@@ -221,6 +228,7 @@ fn transpose_slot(slot: &SlotValue, cents: f64) -> SlotValue {
             intervals,
             articulations,
             duration_beats,
+            meta,
         } => SlotValue::Chord {
             intervals: intervals
                 .iter()
@@ -230,6 +238,7 @@ fn transpose_slot(slot: &SlotValue, cents: f64) -> SlotValue {
                 .collect(),
             articulations: articulations.clone(),
             duration_beats: *duration_beats,
+            meta: meta.clone(),
         },
         SlotValue::Tuplet {
             slots,
@@ -238,75 +247,217 @@ fn transpose_slot(slot: &SlotValue, cents: f64) -> SlotValue {
             slots: slots.iter().map(|s| transpose_slot(s, cents)).collect(),
             target_beats: *target_beats,
         },
+        SlotValue::Overlay { voices } => SlotValue::Overlay {
+            voices: voices
+                .iter()
+                .map(|voice| voice.map_slots(|s| transpose_slot(s, cents)))
+                .collect(),
+        },
     }
 }
 
-/// Apply swing feel to a block
-/// Converts pairs of notes to 5-slot swing pattern: | n1 n2 | -> | n1~ - - n2 - |
-/// Ratio is 3:2 (light swing), not 2:1 (shuffle)
-/// Usage: block |> swing or swing(block)
-pub fn builtin_swing(args: Vec<Value>) -> Result<Value, EvalError> {
-    if args.len() != 1 {
+/// Change key by an interval
+/// A section (or every section of a song) keeps its intervals and moves the
+/// base note they are relative to; a block or part is transposed.
+/// Usage: modulate(P5, chorus) or chorus |> modulate P5
+pub fn builtin_modulate(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
+    if args.len() != 2 {
         return Err(EvalError::Custom {
-            message: "swing expects 1 argument".to_string(),
-            span: relanote_core::Span::dummy(),
+            message: "modulate expects 2 arguments (interval, block/part/section/song)".to_string(),
+            span: call.span,
         });
     }
 
-    let block = match &args[0] {
-        Value::Block(block) => block,
+    // Support both argument orders, as transpose does
+    let (interval, target) = match (&args[0], &args[1]) {
+        (Value::Interval(interval), target) | (target, Value::Interval(interval)) => {
+            (interval, target)
+        }
         _ => {
             return Err(EvalError::TypeError {
-                expected: "Block".to_string(),
-                found: format!("{:?}", args[0]),
-                span: relanote_core::Span::dummy(),
+                expected: "Interval".to_string(),
+                found: format!("{:?}, {:?}", args[0], args[1]),
+                span: call.span,
             })
         }
     };
-
-    // Process pairs of notes into 5-slot swing pattern (3:2 ratio)
-    let mut swing_slots = Vec::new();
-    let mut iter = block.slots.iter().peekable();
-
-    while iter.peek().is_some() {
-        let first = iter.next();
-        let second = iter.next();
-
-        // Position 1: first note with slur
-        if let Some(slot) = first {
-            swing_slots.push(add_slur(slot.clone()));
-        } else {
-            swing_slots.push(SlotValue::Rest {
-                duration_beats: None,
+    let cents = interval.cents;
+    let semitones = interval.semitones();
+    let modulate_section = |section: &SectionValue| {
+        if semitones.fract() != 0.0 {
+            return Err(EvalError::Custom {
+                message: format!(
+                    "modulate moves a section's key by whole semitones, not {} cents",
+                    cents
+                ),
+                span: call.span,
             });
         }
+        Ok(SectionValue {
+            modulation: section.modulation + semitones as i32,
+            ..section.clone()
+        })
+    };
 
-        // Positions 2-3: rest
-        for _ in 0..2 {
-            swing_slots.push(SlotValue::Rest {
-                duration_beats: None,
-            });
+    match target {
+        Value::Block(block) => Ok(Value::Block(BlockValue {
+            slots: block
+                .slots
+                .iter()
+                .map(|slot| transpose_slot(slot, cents))
+                .collect(),
+            beats: block.beats,
+        })),
+        Value::Part(part) => Ok(Value::Part(PartValue {
+            blocks: part
+                .blocks
+                .iter()
+                .map(|block| BlockValue {
+                    slots: block
+                        .slots
+                        .iter()
+                        .map(|slot| transpose_slot(slot, cents))
+                        .collect(),
+                    beats: block.beats,
+                })
+                .collect(),
+            ..part.clone()
+        })),
+        Value::Section(section) => Ok(Value::Section(modulate_section(section)?)),
+        Value::Song(song) => {
+            let mut song = song.clone();
+            song.sections = song
+                .sections
+                .iter()
+                .map(modulate_section)
+                .collect::<Result<_, _>>()?;
+            Ok(Value::Song(song))
         }
+        other => Err(EvalError::TypeError {
+            expected: "Block, Part, Section or Song".to_string(),
+            found: format!("{:?}", other),
+            span: call.span,
+        }),
+    }
+}
 
-        // Position 4: second note
-        if let Some(slot) = second {
-            swing_slots.push(slot.clone());
-        } else {
-            swing_slots.push(SlotValue::Rest {
-                duration_beats: None,
-            });
-        }
+/// Default swing ratio (3:2, light swing)
+const SWING_DEFAULT_RATIO: f64 = 0.6;
 
-        // Position 5: rest
-        swing_slots.push(SlotValue::Rest {
-            duration_beats: None,
+/// Apply swing feel to a block or part
+/// Delays the off-beat of each pair of the block's steps; the timing is applied
+/// by the renderer so explicit durations, tuplets and chords swing alike.
+/// Ratio is 0.5 (straight) to 0.75 (hard shuffle), 0.6 (3:2) by default.
+/// Usage: block |> swing or block |> swing(ratio)
+pub fn builtin_swing(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
+    if args.is_empty() || args.len() > 2 {
+        return Err(EvalError::Custom {
+            message: "swing expects 1 or 2 arguments (ratio, block)".to_string(),
+            span: call.span,
         });
     }
 
-    Ok(Value::Block(BlockValue {
-        slots: swing_slots,
+    let mut target = None;
+    let mut ratio = SWING_DEFAULT_RATIO;
+    for arg in &args {
+        match arg {
+            Value::Block(_) | Value::Part(_) => target = Some(arg),
+            Value::Float(r) => ratio = *r,
+            _ => {
+                return Err(EvalError::TypeError {
+                    expected: "Block or Float".to_string(),
+                    found: format!("{:?}", arg),
+                    span: call.span,
+                })
+            }
+        }
+    }
+
+    match target {
+        Some(Value::Block(block)) => Ok(Value::Block(swing_block(block, ratio, true))),
+        Some(Value::Part(part)) => Ok(Value::Part(PartValue {
+            blocks: part
+                .blocks
+                .iter()
+                .map(|block| swing_block(block, ratio, true))
+                .collect(),
+            ..part.clone()
+        })),
+        _ => Err(EvalError::TypeError {
+            expected: "Block or Part".to_string(),
+            found: format!("{:?}", args),
+            span: call.span,
+        }),
+    }
+}
+
+/// Swing a block's notes at `ratio` (clamped to 0.5-0.75), on a grid of pairs of the block's
+/// (relative rhythm) steps; with `replace` false, notes that already swing
+/// keep their own feel
+pub(crate) fn swing_block(block: &BlockValue, ratio: f64, replace: bool) -> BlockValue {
+    let swing = Swing {
+        ratio: ratio.clamp(0.5, 0.75),
+        grid_beats: 2.0 * block.beats / block.slots.len().max(1) as f64,
+    };
+    BlockValue {
+        slots: block
+            .slots
+            .iter()
+            .map(|s| swing_slot(s, swing, replace))
+            .collect(),
         beats: block.beats,
-    }))
+    }
+}
+
+fn swing_slot(slot: &SlotValue, swing: Swing, replace: bool) -> SlotValue {
+    let swung = |meta: &NoteMeta| NoteMeta {
+        swing: match meta.swing {
+            Some(own) if !replace => Some(own),
+            _ => Some(swing),
+        },
+        ..meta.clone()
+    };
+    match slot {
+        SlotValue::Note {
+            interval,
+            articulations,
+            duration_beats,
+            meta,
+        } => SlotValue::Note {
+            interval: interval.clone(),
+            articulations: articulations.clone(),
+            duration_beats: *duration_beats,
+            meta: swung(meta),
+        },
+        SlotValue::Chord {
+            intervals,
+            articulations,
+            duration_beats,
+            meta,
+        } => SlotValue::Chord {
+            intervals: intervals.clone(),
+            articulations: articulations.clone(),
+            duration_beats: *duration_beats,
+            meta: swung(meta),
+        },
+        SlotValue::Tuplet {
+            slots,
+            target_beats,
+        } => SlotValue::Tuplet {
+            slots: slots
+                .iter()
+                .map(|s| swing_slot(s, swing, replace))
+                .collect(),
+            target_beats: *target_beats,
+        },
+        SlotValue::Overlay { voices } => SlotValue::Overlay {
+            voices: voices
+                .iter()
+                .map(|voice| voice.map_slots(|s| swing_slot(s, swing, replace)))
+                .collect(),
+        },
+        SlotValue::Rest { .. } => slot.clone(),
+    }
 }
 
 /// Add slur (portamento) articulation to a slot
@@ -316,103 +467,711 @@ fn add_slur(slot: SlotValue) -> SlotValue {
             interval,
             mut articulations,
             duration_beats,
+            meta,
         } => {
-            if !articulations.contains(&relanote_ast::Articulation::Portamento) {
-                articulations.push(relanote_ast::Articulation::Portamento);
+            if !articulations.contains(&Articulation::Portamento) {
+                articulations.push(Articulation::Portamento);
             }
             SlotValue::Note {
                 interval,
                 articulations,
                 duration_beats,
+                meta,
             }
         }
         SlotValue::Chord {
             intervals,
             mut articulations,
             duration_beats,
+            meta,
         } => {
-            if !articulations.contains(&relanote_ast::Articulation::Portamento) {
-                articulations.push(relanote_ast::Articulation::Portamento);
+            if !articulations.contains(&Articulation::Portamento) {
+                articulations.push(Articulation::Portamento);
             }
             SlotValue::Chord {
                 intervals,
                 articulations,
                 duration_beats,
+                meta,
             }
         }
         other => other,
     }
 }
 
-/// Double the tempo of a block (halve all durations)
-/// Usage: block |> double_time
-pub fn builtin_double_time(args: Vec<Value>) -> Result<Value, EvalError> {
-    if args.len() != 1 {
+/// Glide (portamento) between every note of a block
+/// Marks notes with portamento and sets the glide time used by the renderer.
+/// Usage: block |> glide(ms) or glide(ms, block)
+pub fn builtin_glide(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
+    if args.len() != 2 {
         return Err(EvalError::Custom {
-            message: "double_time expects 1 argument".to_string(),
-            span: relanote_core::Span::dummy(),
+            message: "glide expects 2 arguments (ms, block)".to_string(),
+            span: call.span,
         });
     }
 
-    let block = match &args[0] {
-        Value::Block(block) => block,
+    // Support both argument orders: (target, ms) or (ms, target)
+    let (target, ms) = match (&args[0], &args[1]) {
+        (Value::Int(ms), target) | (target, Value::Int(ms)) => (target, *ms as f64),
+        (Value::Float(ms), target) | (target, Value::Float(ms)) => (target, *ms),
         _ => {
+            return Err(EvalError::TypeError {
+                expected: "Block and Int".to_string(),
+                found: format!("{:?}, {:?}", args[0], args[1]),
+                span: call.span,
+            })
+        }
+    };
+    let ms = ms.max(0.0);
+
+    let glide_block = |block: &BlockValue| BlockValue {
+        slots: block.slots.iter().map(|s| glide_slot(s, ms)).collect(),
+        beats: block.beats,
+    };
+
+    match target {
+        Value::Block(block) => Ok(Value::Block(glide_block(block))),
+        Value::Part(part) => Ok(Value::Part(PartValue {
+            blocks: part.blocks.iter().map(glide_block).collect(),
+            ..part.clone()
+        })),
+        _ => Err(EvalError::TypeError {
+            expected: "Block or Part".to_string(),
+            found: format!("{:?}", target),
+            span: call.span,
+        }),
+    }
+}
+
+fn glide_slot(slot: &SlotValue, ms: f64) -> SlotValue {
+    match add_slur(slot.clone()) {
+        SlotValue::Note {
+            interval,
+            articulations,
+            duration_beats,
+            meta,
+        } => SlotValue::Note {
+            interval,
+            articulations,
+            duration_beats,
+            meta: NoteMeta {
+                glide_ms: Some(ms),
+                ..meta
+            },
+        },
+        SlotValue::Tuplet {
+            slots,
+            target_beats,
+        } => SlotValue::Tuplet {
+            slots: slots.iter().map(|s| glide_slot(s, ms)).collect(),
+            target_beats,
+        },
+        SlotValue::Overlay { voices } => SlotValue::Overlay {
+            voices: voices
+                .iter()
+                .map(|voice| voice.map_slots(|s| glide_slot(s, ms)))
+                .collect(),
+        },
+        other => other,
+    }
+}
+
+/// A block argument and the remaining arguments with their indices
+pub(crate) type BlockAndArgs<'a> = (&'a BlockValue, Vec<(usize, &'a Value)>);
+
+/// Split off the block a transform is applied to: the first argument when
+/// it is piped in, the last otherwise. The other arguments keep their order
+/// and their indices, for error spans.
+pub(crate) fn split_block<'a>(
+    args: &'a [Value],
+    call: &CallSite,
+) -> Result<BlockAndArgs<'a>, EvalError> {
+    let last = args.len() - 1;
+    let index = match (&args[0], &args[last]) {
+        (_, Value::Block(_)) => last,
+        (Value::Block(_), _) => 0,
+        (_, other) => {
             return Err(EvalError::TypeError {
                 expected: "Block".to_string(),
-                found: format!("{:?}", args[0]),
-                span: relanote_core::Span::dummy(),
+                found: format!("{:?}", other),
+                span: call.arg(last),
             })
         }
     };
+    let Value::Block(block) = &args[index] else {
+        unreachable!("the block was just found at {}", index);
+    };
+    let rest = args
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| *i != index)
+        .collect();
+    Ok((block, rest))
+}
 
-    let slots: Vec<SlotValue> = block.slots.iter().map(halve_slot_duration).collect();
+/// The block argument of a transform with one other argument, in either order
+fn block_and_arg<'a>(
+    name: &str,
+    args: &'a [Value],
+    call: &CallSite,
+) -> Result<(&'a BlockValue, (usize, &'a Value)), EvalError> {
+    match (&args[0], &args[1]) {
+        (Value::Block(block), _) => Ok((block, (1, &args[1]))),
+        (_, Value::Block(block)) => Ok((block, (0, &args[0]))),
+        _ => Err(EvalError::TypeError {
+            expected: format!("a Block for {}", name),
+            found: format!("{:?}, {:?}", args[0], args[1]),
+            span: call.span,
+        }),
+    }
+}
 
-    Ok(Value::Block(BlockValue {
-        slots,
-        beats: block.beats / 2.0,
-    }))
+/// The only argument of a transform, which must be a block
+fn single_block<'a>(
+    name: &str,
+    args: &'a [Value],
+    call: &CallSite,
+) -> Result<&'a BlockValue, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::Custom {
+            message: format!("{} expects 1 argument", name),
+            span: call.span,
+        });
+    }
+    match &args[0] {
+        Value::Block(block) => Ok(block),
+        other => Err(EvalError::TypeError {
+            expected: "Block".to_string(),
+            found: format!("{:?}", other),
+            span: call.arg(0),
+        }),
+    }
 }
 
-fn halve_slot_duration(slot: &SlotValue) -> SlotValue {
+/// Double the tempo of a block (halve all durations)
+/// Usage: block |> double_time
+pub fn builtin_double_time(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
+    let block = single_block("double_time", &args, call)?;
+    Ok(Value::Block(scale_block_time(block, 0.5)))
+}
+
+/// Halve the tempo of a block (double all durations)
+/// Usage: block |> half_time
+pub fn builtin_half_time(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
+    let block = single_block("half_time", &args, call)?;
+    Ok(Value::Block(scale_block_time(block, 2.0)))
+}
+
+/// Stretch a block in time: every duration is multiplied by the factor
+/// Usage: block |> stretch 1.5 or stretch(2, block)
+pub fn builtin_stretch(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
+    time_factor_transform("stretch", args, call, |factor| factor)
+}
+
+/// Compress a block in time: every duration is divided by the factor
+/// Usage: block |> compress 1.5 or compress(2, block)
+pub fn builtin_compress(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
+    time_factor_transform("compress", args, call, |factor| 1.0 / factor)
+}
+
+fn time_factor_transform(
+    name: &str,
+    args: Vec<Value>,
+    call: &CallSite,
+    scale: impl Fn(f64) -> f64,
+) -> Result<Value, EvalError> {
+    if args.len() != 2 {
+        return Err(EvalError::Custom {
+            message: format!("{} expects 2 arguments (factor, block)", name),
+            span: call.span,
+        });
+    }
+    let (block, (index, factor)) = block_and_arg(name, &args, call)?;
+    let factor = match factor {
+        Value::Int(n) => *n as f64,
+        Value::Float(f) => *f,
+        other => {
+            return Err(EvalError::TypeError {
+                expected: "Float".to_string(),
+                found: format!("{:?}", other),
+                span: call.arg(index),
+            })
+        }
+    };
+    if factor <= 0.0 {
+        return Err(EvalError::Custom {
+            message: format!("{} factor must be positive", name),
+            span: call.arg(index),
+        });
+    }
+    Ok(Value::Block(scale_block_time(block, scale(factor))))
+}
+
+fn scale_block_time(block: &BlockValue, factor: f64) -> BlockValue {
+    BlockValue {
+        slots: block
+            .slots
+            .iter()
+            .map(|slot| scale_slot_duration(slot, factor))
+            .collect(),
+        beats: block.beats * factor,
+    }
+}
+
+/// Scale a slot's explicit duration; a tuplet's slots are shares of its
+/// length, so only the length is scaled
+fn scale_slot_duration(slot: &SlotValue, factor: f64) -> SlotValue {
     match slot {
         SlotValue::Note {
             interval,
             articulations,
             duration_beats,
+            meta,
         } => SlotValue::Note {
             interval: interval.clone(),
             articulations: articulations.clone(),
-            duration_beats: duration_beats.map(|d| d / 2.0),
+            duration_beats: duration_beats.map(|d| d * factor),
+            meta: meta.clone(),
         },
         SlotValue::Rest { duration_beats } => SlotValue::Rest {
-            duration_beats: duration_beats.map(|d| d / 2.0),
+            duration_beats: duration_beats.map(|d| d * factor),
         },
         SlotValue::Chord {
             intervals,
             articulations,
             duration_beats,
+            meta,
         } => SlotValue::Chord {
             intervals: intervals.clone(),
             articulations: articulations.clone(),
-            duration_beats: duration_beats.map(|d| d / 2.0),
+            duration_beats: duration_beats.map(|d| d * factor),
+            meta: meta.clone(),
         },
         SlotValue::Tuplet {
             slots,
             target_beats,
         } => SlotValue::Tuplet {
-            slots: slots.iter().map(halve_slot_duration).collect(),
-            target_beats: *target_beats,
+            slots: slots.clone(),
+            target_beats: target_beats * factor,
+        },
+        SlotValue::Overlay { voices } => SlotValue::Overlay {
+            voices: voices
+                .iter()
+                .map(|voice| scale_block_time(voice, factor))
+                .collect(),
+        },
+    }
+}
+
+/// Snap a block's rhythm to a grid
+/// Each slot ends on the grid line nearest its written end, so every slot
+/// starts on the grid; a slot that would vanish keeps one grid step.
+/// Usage: block |> quantize 1/8 or quantize(1/16, block)
+pub fn builtin_quantize(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
+    if args.len() != 2 {
+        return Err(EvalError::Custom {
+            message: "quantize expects 2 arguments (grid, block)".to_string(),
+            span: call.span,
+        });
+    }
+    let (block, (index, grid)) = block_and_arg("quantize", &args, call)?;
+    let grid = match grid {
+        Value::Duration(duration) if duration.beats > 0.0 => duration.beats,
+        Value::Duration(_) => {
+            return Err(EvalError::Custom {
+                message: "quantize grid must be positive".to_string(),
+                span: call.arg(index),
+            })
+        }
+        other => {
+            return Err(EvalError::TypeError {
+                expected: "Duration".to_string(),
+                found: format!("{:?}", other),
+                span: call.arg(index),
+            })
+        }
+    };
+
+    let mut written_end = 0.0;
+    let mut end = 0.0;
+    let mut slots = Vec::with_capacity(block.slots.len());
+    for (slot, length) in block.slots.iter().zip(block.slot_lengths()) {
+        written_end += length;
+        let start = end;
+        end = ((written_end / grid).round() * grid).max(start + grid);
+        let beats = end - start;
+        slots.push(match slot {
+            SlotValue::Tuplet { slots, .. } => SlotValue::Tuplet {
+                slots: slots.clone(),
+                target_beats: beats,
+            },
+            SlotValue::Overlay { .. } if length > 0.0 => scale_slot_duration(slot, beats / length),
+            other => set_duration(other, beats),
+        });
+    }
+    Ok(Value::Block(BlockValue::with_beats(slots, end)))
+}
+
+/// A note, chord or rest with its duration replaced
+fn set_duration(slot: &SlotValue, beats: f64) -> SlotValue {
+    let mut slot = slot.clone();
+    match &mut slot {
+        SlotValue::Note { duration_beats, .. }
+        | SlotValue::Chord { duration_beats, .. }
+        | SlotValue::Rest { duration_beats } => *duration_beats = Some(beats),
+        SlotValue::Tuplet { .. } | SlotValue::Overlay { .. } => {}
+    }
+    slot
+}
+
+/// Mirror a block's melody upside down
+/// Each pitch is reflected around the axis, the first note's pitch unless
+/// one is given, so rising intervals fall by the same amount.
+/// Usage: block |> invert or invert(P5, block)
+pub fn builtin_invert(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
+    let (block, axis) = match args.len() {
+        1 => {
+            let block = single_block("invert", &args, call)?;
+            (block, first_pitch(&block.slots).unwrap_or(0.0))
+        }
+        2 => match block_and_arg("invert", &args, call)? {
+            (block, (_, Value::Interval(axis))) => (block, axis.cents),
+            (_, (index, other)) => {
+                return Err(EvalError::TypeError {
+                    expected: "Interval".to_string(),
+                    found: format!("{:?}", other),
+                    span: call.arg(index),
+                })
+            }
         },
+        _ => {
+            return Err(EvalError::Custom {
+                message: "invert expects 1 or 2 arguments ([axis], block)".to_string(),
+                span: call.span,
+            })
+        }
+    };
+    Ok(Value::Block(BlockValue::with_beats(
+        block
+            .slots
+            .iter()
+            .map(|slot| slot.map_cents(&|cents| 2.0 * axis - cents))
+            .collect(),
+        block.beats,
+    )))
+}
+
+/// Pitch of the first note (or the first chord's lowest listed note)
+fn first_pitch(slots: &[SlotValue]) -> Option<f64> {
+    slots.iter().find_map(|slot| match slot {
+        SlotValue::Note { interval, .. } => Some(interval.cents),
+        SlotValue::Chord { intervals, .. } => intervals.first().map(|i| i.cents),
+        SlotValue::Tuplet { slots, .. } => first_pitch(slots),
+        SlotValue::Overlay { voices } => voices.iter().find_map(|voice| first_pitch(&voice.slots)),
+        SlotValue::Rest { .. } => None,
+    })
+}
+
+/// Play a block's pitches in reverse order over its original rhythm
+/// Unlike `reverse`, the durations, rests and articulations stay where
+/// they were; only what is played on them is reversed.
+/// Usage: block |> retrograde
+pub fn builtin_retrograde(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
+    let block = single_block("retrograde", &args, call)?;
+    Ok(Value::Block(permute_pitches(block, |pitches| {
+        pitches.reverse()
+    })))
+}
+
+/// Default seed used by shuffle when none is given, so renders are reproducible
+const SHUFFLE_DEFAULT_SEED: u64 = 0x5EED;
+
+/// Play a block's pitches in a random order over its original rhythm
+/// The order is fixed by the seed, so a render always sounds the same.
+/// Usage: block |> shuffle or shuffle(seed, block)
+pub fn builtin_shuffle(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
+    let (block, seed) = match args.len() {
+        1 => (single_block("shuffle", &args, call)?, SHUFFLE_DEFAULT_SEED),
+        2 => match block_and_arg("shuffle", &args, call)? {
+            (block, (_, Value::Int(seed))) => (block, *seed as u64),
+            (_, (index, other)) => {
+                return Err(EvalError::TypeError {
+                    expected: "Int".to_string(),
+                    found: format!("{:?}", other),
+                    span: call.arg(index),
+                })
+            }
+        },
+        _ => {
+            return Err(EvalError::Custom {
+                message: "shuffle expects 1 or 2 arguments ([seed], block)".to_string(),
+                span: call.span,
+            })
+        }
+    };
+    let mut rng = SplitMix64::new(seed);
+    Ok(Value::Block(permute_pitches(block, |pitches| {
+        // Fisher-Yates
+        for i in (1..pitches.len()).rev() {
+            let j = (rng.next_u64() % (i as u64 + 1)) as usize;
+            pitches.swap(i, j);
+        }
+    })))
+}
+
+/// What a note or chord plays, moved together when pitches are reordered
+#[derive(Clone)]
+struct Sounding {
+    intervals: Vec<IntervalValue>,
+    degree: Option<ScaleDegree>,
+    hit: Option<String>,
+    drum_key: Option<u8>,
+}
+
+/// Reorder the pitches of a block's notes and chords (tuplets included),
+/// leaving its rhythm in place
+fn permute_pitches(block: &BlockValue, permute: impl FnOnce(&mut Vec<Sounding>)) -> BlockValue {
+    fn collect(slots: &[SlotValue], out: &mut Vec<Sounding>) {
+        for slot in slots {
+            match slot {
+                SlotValue::Note { interval, meta, .. } => out.push(Sounding {
+                    intervals: vec![interval.clone()],
+                    degree: meta.degree,
+                    hit: meta.hit.clone(),
+                    drum_key: meta.drum_key,
+                }),
+                SlotValue::Chord {
+                    intervals, meta, ..
+                } => out.push(Sounding {
+                    intervals: intervals.clone(),
+                    degree: meta.degree,
+                    hit: meta.hit.clone(),
+                    drum_key: meta.drum_key,
+                }),
+                SlotValue::Tuplet { slots, .. } => collect(slots, out),
+                SlotValue::Overlay { voices } => {
+                    for voice in voices {
+                        collect(&voice.slots, out);
+                    }
+                }
+                SlotValue::Rest { .. } => {}
+            }
+        }
     }
+
+    fn place(slot: &SlotValue, next: &mut impl Iterator<Item = Sounding>) -> SlotValue {
+        let (articulations, duration_beats, meta) = match slot {
+            SlotValue::Note {
+                articulations,
+                duration_beats,
+                meta,
+                ..
+            }
+            | SlotValue::Chord {
+                articulations,
+                duration_beats,
+                meta,
+                ..
+            } => (articulations.clone(), *duration_beats, meta),
+            SlotValue::Tuplet {
+                slots,
+                target_beats,
+            } => {
+                return SlotValue::Tuplet {
+                    slots: slots.iter().map(|s| place(s, next)).collect(),
+                    target_beats: *target_beats,
+                }
+            }
+            SlotValue::Overlay { voices } => {
+                return SlotValue::Overlay {
+                    voices: voices
+                        .iter()
+                        .map(|voice| voice.map_slots(|s| place(s, next)))
+                        .collect(),
+                }
+            }
+            SlotValue::Rest { .. } => return slot.clone(),
+        };
+        let sounding = next.next().expect("one sounding per note and chord");
+        let meta = NoteMeta {
+            degree: sounding.degree,
+            hit: sounding.hit,
+            drum_key: sounding.drum_key,
+            ..meta.clone()
+        };
+        let mut intervals = sounding.intervals;
+        if intervals.len() == 1 {
+            SlotValue::Note {
+                interval: intervals.remove(0),
+                articulations,
+                duration_beats,
+                meta,
+            }
+        } else {
+            SlotValue::Chord {
+                intervals,
+                articulations,
+                duration_beats,
+                meta,
+            }
+        }
+    }
+
+    let mut pitches = Vec::new();
+    collect(&block.slots, &mut pitches);
+    permute(&mut pitches);
+    let mut next = pitches.into_iter();
+    BlockValue {
+        slots: block.slots.iter().map(|s| place(s, &mut next)).collect(),
+        beats: block.beats,
+    }
+}
+
+/// Cut out the part of a block between two points, in beats from its start
+/// Notes that cross either point are shortened to fit; a tuplet can't be cut.
+/// Usage: slice(1, 3, block) or block |> slice 1 3
+pub fn builtin_slice(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
+    if args.len() != 3 {
+        return Err(EvalError::Custom {
+            message: "slice expects 3 arguments (start_beat, end_beat, block)".to_string(),
+            span: call.span,
+        });
+    }
+    let (block, rest) = split_block(&args, call)?;
+    let beats = |(index, value): (usize, &Value)| match value {
+        Value::Int(n) => Ok(*n as f64),
+        Value::Float(f) => Ok(*f),
+        Value::Duration(duration) => Ok(duration.beats),
+        other => Err(EvalError::TypeError {
+            expected: "Float or Duration".to_string(),
+            found: format!("{:?}", other),
+            span: call.arg(index),
+        }),
+    };
+    let (start, end) = (beats(rest[0])?, beats(rest[1])?);
+    if start < 0.0 || end < start {
+        return Err(EvalError::Custom {
+            message: format!("slice needs 0 <= start <= end, got {} to {}", start, end),
+            span: call.span,
+        });
+    }
+    Ok(Value::Block(slice_block(block, start, end, call)?))
+}
+
+/// Take whole bars of a block, counted from 0 under the time signature
+/// A range takes the bars in it (`0..2` is the first two bars); an array of
+/// bar numbers joins the bars it lists, in its order.
+/// Usage: bars(0..2, block) or block |> bars [3, 1]
+pub fn builtin_bars(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
+    if args.len() != 2 {
+        return Err(EvalError::Custom {
+            message: "bars expects 2 arguments (bars, block)".to_string(),
+            span: call.span,
+        });
+    }
+    let (block, (index, selection)) = block_and_arg("bars", &args, call)?;
+    let numbers = match selection {
+        Value::Int(n) => vec![*n],
+        Value::Array(items) => items
+            .iter()
+            .map(|item| match item {
+                Value::Int(n) => Ok(*n),
+                other => Err(EvalError::TypeError {
+                    expected: "Int".to_string(),
+                    found: format!("{:?}", other),
+                    span: call.arg(index),
+                }),
+            })
+            .collect::<Result<_, _>>()?,
+        other => {
+            return Err(EvalError::TypeError {
+                expected: "[Int] or Int".to_string(),
+                found: format!("{:?}", other),
+                span: call.arg(index),
+            })
+        }
+    };
+
+    let bar = call.beats_per_bar();
+    let mut slots = Vec::new();
+    let mut beats = 0.0;
+    for n in numbers {
+        if n < 0 {
+            return Err(EvalError::Custom {
+                message: format!("bar numbers start at 0, got {}", n),
+                span: call.arg(index),
+            });
+        }
+        let Some(next) = n.checked_add(1) else {
+            return Err(EvalError::Custom {
+                message: format!("bar number {} is too large", n),
+                span: call.arg(index),
+            });
+        };
+        let part = slice_block(block, n as f64 * bar, next as f64 * bar, call)?;
+        beats += part.beats;
+        slots.extend(part.slots);
+    }
+    Ok(Value::Block(BlockValue::with_beats(slots, beats)))
+}
+
+/// The slots of a block between `start` and `end` beats, each with an
+/// explicit duration so the result can be joined to other blocks
+fn slice_block(
+    block: &BlockValue,
+    start: f64,
+    end: f64,
+    call: &CallSite,
+) -> Result<BlockValue, EvalError> {
+    const EPSILON: f64 = 1e-9;
+    let mut slots = Vec::new();
+    let mut beats = 0.0;
+    let mut position = 0.0;
+    for (slot, length) in block.slots.iter().zip(block.slot_lengths()) {
+        let (from, to) = (position, position + length);
+        position = to;
+        let kept = to.min(end) - from.max(start);
+        if kept <= EPSILON {
+            continue;
+        }
+        let slot = match slot {
+            SlotValue::Tuplet { .. } if kept < length - EPSILON => {
+                return Err(EvalError::Custom {
+                    message: format!(
+                        "can't cut through the tuplet from beat {} to beat {}",
+                        from, to
+                    ),
+                    span: call.span,
+                });
+            }
+            SlotValue::Overlay { .. } if kept < length - EPSILON => {
+                return Err(EvalError::Custom {
+                    message: format!(
+                        "can't cut through the overlaid blocks from beat {} to beat {}",
+                        from, to
+                    ),
+                    span: call.span,
+                });
+            }
+            SlotValue::Tuplet { slots, .. } => SlotValue::Tuplet {
+                slots: slots.clone(),
+                target_beats: kept,
+            },
+            slot => set_duration(slot, kept),
+        };
+        slots.push(slot);
+        beats += kept;
+    }
+    Ok(BlockValue::with_beats(slots, beats))
 }
 
 /// Generate a metronome click track
 /// Usage: metronome(bars, beats_per_bar)
-pub fn builtin_metronome(args: Vec<Value>) -> Result<Value, EvalError> {
+pub fn builtin_metronome(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
     if args.len() != 2 {
         return Err(EvalError::Custom {
             message: "metronome expects 2 arguments (bars, beats_per_bar)".to_string(),
-            span: relanote_core::Span::dummy(),
+            span: call.span,
         });
     }
 
@@ -422,7 +1181,7 @@ pub fn builtin_metronome(args: Vec<Value>) -> Result<Value, EvalError> {
             return Err(EvalError::TypeError {
                 expected: "Int".to_string(),
                 found: format!("{:?}", args[0]),
-                span: relanote_core::Span::dummy(),
+                span: call.span,
             })
         }
     };
@@ -433,7 +1192,7 @@ pub fn builtin_metronome(args: Vec<Value>) -> Result<Value, EvalError> {
             return Err(EvalError::TypeError {
                 expected: "Int".to_string(),
                 found: format!("{:?}", args[1]),
-                span: relanote_core::Span::dummy(),
+                span: call.span,
             })
         }
     };
@@ -445,11 +1204,13 @@ pub fn builtin_metronome(args: Vec<Value>) -> Result<Value, EvalError> {
         interval: IntervalValue { cents: 3600.0 }, // C7
         articulations: vec![],
         duration_beats: None,
+        meta: NoteMeta::default(),
     };
     let click = SlotValue::Note {
         interval: IntervalValue { cents: 3100.0 }, // G6
         articulations: vec![],
         duration_beats: None,
+        meta: NoteMeta::default(),
     };
     let rest = SlotValue::Rest {
         duration_beats: None,
@@ -468,18 +1229,275 @@ pub fn builtin_metronome(args: Vec<Value>) -> Result<Value, EvalError> {
         }
     }
 
-    Ok(Value::Part(PartValue {
-        instrument: "Metronome".to_string(),
-        blocks: vec![BlockValue {
+    Ok(Value::Part(PartValue::new(
+        "Metronome",
+        vec![BlockValue {
             slots,
             beats: total_beats as f64,
         }],
-        envelope: None,
-        reverb_level: None,
-        volume_level: None,
-        delay: None,
-        phaser: None,
-        distortion: None,
-        synth: None,
-    }))
+    )))
+}
+
+/// Step length of `steps` when none is given: a sixteenth note
+const DEFAULT_STEP_BEATS: f64 = 0.25;
+
+/// Build a drum block from a step pattern
+/// `x` is a hit, `X` an accented hit and `.` or `-` a rest; spaces and `|`
+/// only group the steps. Each step is a sixteenth unless a step length is given.
+/// Usage: steps("x..x..x.", Kick8bit) or steps("x.x.", 42, 1/8)
+pub fn builtin_steps(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
+    if args.len() != 2 && args.len() != 3 {
+        return Err(EvalError::Custom {
+            message: "steps expects 2 or 3 arguments (pattern, drum, [step])".to_string(),
+            span: call.span,
+        });
+    }
+
+    let pattern = match &args[0] {
+        Value::String(pattern) => pattern,
+        other => {
+            return Err(EvalError::TypeError {
+                expected: "String".to_string(),
+                found: format!("{:?}", other),
+                span: call.arg(0),
+            })
+        }
+    };
+    let Some(key) = drum_key(&args[1]) else {
+        return Err(EvalError::Custom {
+            message: "steps expects a drum synth or a GM key from 0 to 127".to_string(),
+            span: call.arg(1),
+        });
+    };
+    let step = match args.get(2) {
+        None => DEFAULT_STEP_BEATS,
+        Some(Value::Duration(duration)) if duration.beats > 0.0 => duration.beats,
+        Some(Value::Duration(_)) => {
+            return Err(EvalError::Custom {
+                message: "step length must be positive".to_string(),
+                span: call.arg(2),
+            })
+        }
+        Some(other) => {
+            return Err(EvalError::TypeError {
+                expected: "Duration".to_string(),
+                found: format!("{:?}", other),
+                span: call.arg(2),
+            })
+        }
+    };
+
+    let hit = |articulations| SlotValue::Note {
+        interval: IntervalValue::from_semitones(0),
+        articulations,
+        duration_beats: Some(step),
+        meta: NoteMeta {
+            hit: Some("x".to_string()),
+            drum_key: Some(key),
+            ..NoteMeta::default()
+        },
+    };
+    let mut slots = Vec::new();
+    for c in pattern.chars() {
+        match c {
+            'x' => slots.push(hit(vec![])),
+            'X' => slots.push(hit(vec![Articulation::Accent])),
+            '.' | '-' => slots.push(SlotValue::Rest {
+                duration_beats: Some(step),
+            }),
+            '|' => {}
+            c if c.is_whitespace() => {}
+            c => {
+                return Err(EvalError::Custom {
+                    message: format!(
+                        "unknown step `{}`; use `x` for a hit, `X` for an accent and `.` \
+                         for a rest",
+                        c
+                    ),
+                    span: call.arg(0),
+                })
+            }
+        }
+    }
+
+    let beats = slots.len() as f64 * step;
+    Ok(Value::Block(BlockValue::with_beats(slots, beats)))
+}
+
+/// Default seed used by humanize when none is given, so renders are reproducible
+const HUMANIZE_DEFAULT_SEED: u64 = 0x5EED;
+
+/// Apply small random offsets to note start times and velocities
+/// Offsets are stored as note metadata and applied by the renderer,
+/// so the written rhythm of the block is unchanged.
+/// Usage: block |> humanize(timing_ms, velocity_range) or humanize(timing_ms, velocity_range, block)
+/// An optional trailing seed makes different takes reproducible: humanize(10, 8, block, 42)
+pub fn builtin_humanize(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
+    if args.len() != 3 && args.len() != 4 {
+        return Err(EvalError::Custom {
+            message: "humanize expects 3 or 4 arguments (timing_ms, velocity_range, block, [seed])"
+                .to_string(),
+            span: call.span,
+        });
+    }
+
+    let mut nums: Vec<f64> = Vec::new();
+    let mut target: Option<&Value> = None;
+    for arg in &args {
+        match arg {
+            Value::Float(f) => nums.push(*f),
+            Value::Int(i) => nums.push(*i as f64),
+            Value::Block(_) | Value::Part(_) if target.is_none() => target = Some(arg),
+            _ => {
+                return Err(EvalError::TypeError {
+                    expected: "Int, Float, and one Block or Part".to_string(),
+                    found: format!("{:?}", arg),
+                    span: call.span,
+                })
+            }
+        }
+    }
+
+    let target = match target {
+        Some(target) if nums.len() == args.len() - 1 => target,
+        _ => {
+            return Err(EvalError::Custom {
+                message: "humanize expects timing_ms, velocity_range and a Block or Part"
+                    .to_string(),
+                span: call.span,
+            })
+        }
+    };
+
+    let timing_ms = nums[0].abs();
+    let velocity_range = nums[1].abs().round() as i32;
+    let seed = nums
+        .get(2)
+        .map(|s| *s as u64)
+        .unwrap_or(HUMANIZE_DEFAULT_SEED);
+    let mut rng = SplitMix64::new(seed);
+
+    match target {
+        Value::Block(block) => Ok(Value::Block(humanize_block(
+            block,
+            timing_ms,
+            velocity_range,
+            &mut rng,
+        ))),
+        Value::Part(part) => {
+            let blocks = part
+                .blocks
+                .iter()
+                .map(|b| humanize_block(b, timing_ms, velocity_range, &mut rng))
+                .collect();
+            Ok(Value::Part(PartValue {
+                blocks,
+                ..part.clone()
+            }))
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn humanize_block(
+    block: &BlockValue,
+    timing_ms: f64,
+    velocity_range: i32,
+    rng: &mut SplitMix64,
+) -> BlockValue {
+    BlockValue {
+        slots: block
+            .slots
+            .iter()
+            .map(|slot| humanize_slot(slot, timing_ms, velocity_range, rng))
+            .collect(),
+        beats: block.beats,
+    }
+}
+
+fn humanize_slot(
+    slot: &SlotValue,
+    timing_ms: f64,
+    velocity_range: i32,
+    rng: &mut SplitMix64,
+) -> SlotValue {
+    match slot {
+        SlotValue::Note {
+            interval,
+            articulations,
+            duration_beats,
+            meta,
+        } => SlotValue::Note {
+            interval: interval.clone(),
+            articulations: articulations.clone(),
+            duration_beats: *duration_beats,
+            meta: humanize_meta(meta, timing_ms, velocity_range, rng),
+        },
+        SlotValue::Chord {
+            intervals,
+            articulations,
+            duration_beats,
+            meta,
+        } => SlotValue::Chord {
+            intervals: intervals.clone(),
+            articulations: articulations.clone(),
+            duration_beats: *duration_beats,
+            meta: humanize_meta(meta, timing_ms, velocity_range, rng),
+        },
+        SlotValue::Rest { .. } => slot.clone(),
+        SlotValue::Tuplet {
+            slots,
+            target_beats,
+        } => SlotValue::Tuplet {
+            slots: slots
+                .iter()
+                .map(|s| humanize_slot(s, timing_ms, velocity_range, rng))
+                .collect(),
+            target_beats: *target_beats,
+        },
+        SlotValue::Overlay { voices } => SlotValue::Overlay {
+            voices: voices
+                .iter()
+                .map(|voice| voice.map_slots(|s| humanize_slot(s, timing_ms, velocity_range, rng)))
+                .collect(),
+        },
+    }
+}
+
+fn humanize_meta(
+    meta: &NoteMeta,
+    timing_ms: f64,
+    velocity_range: i32,
+    rng: &mut SplitMix64,
+) -> NoteMeta {
+    NoteMeta {
+        timing_offset_ms: meta.timing_offset_ms + rng.next_signed() * timing_ms,
+        velocity_offset: meta.velocity_offset
+            + (rng.next_signed() * velocity_range as f64).round() as i32,
+        ..meta.clone()
+    }
+}
+
+/// Small deterministic PRNG (SplitMix64) so humanized output is reproducible
+struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform value in [-1.0, 1.0)
+    fn next_signed(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64 * 2.0 - 1.0
+    }
 }
@@ -1,364 +1,335 @@
 //! Audio effects builtins
 
 use crate::error::EvalError;
-use crate::value::{DelayParams, DistortionParams, DistortionType, PartValue, PhaserParams, Value};
+use crate::value::{
+    set_effect, AutomationValue, BlockValue, BusValue, CallSite, ChorusParams, CompressorParams,
+    DelayParams, DistortionParams, DistortionType, DuckValue, EffectValue, EqParams, PartValue,
+    PhaserParams, SendValue, Value,
+};
+
+/// Add `effect` to a block's or part's chain, a bus's, or a song's master
+/// bus; a block becomes a part named `instrument`
+fn apply_effect(
+    target: &Value,
+    effect: EffectValue,
+    instrument: &str,
+    call: &CallSite,
+) -> Result<Value, EvalError> {
+    match target {
+        Value::Block(block) => Ok(Value::Part(
+            PartValue::new(instrument, vec![block.clone()]).with_effect(effect),
+        )),
+        Value::Part(part) => Ok(Value::Part(part.clone().with_effect(effect))),
+        Value::Bus(bus) => {
+            let mut bus = bus.clone();
+            set_effect(&mut bus.effects, effect);
+            Ok(Value::Bus(bus))
+        }
+        Value::Song(song) => {
+            let mut song = song.clone();
+            set_effect(&mut song.master, effect);
+            Ok(Value::Song(song))
+        }
+        other => Err(EvalError::TypeError {
+            expected: "Block, Part, Bus or Song".to_string(),
+            found: format!("{:?}", other),
+            span: call.span,
+        }),
+    }
+}
+
+/// A level from 0.0 to 1.0, or 0 to 100
+fn level_arg(level: &Value, call: &CallSite) -> Result<f64, EvalError> {
+    match level {
+        Value::Float(level) => Ok(level.clamp(0.0, 1.0)),
+        Value::Int(level) => Ok((*level as f64 / 100.0).clamp(0.0, 1.0)),
+        _ => Err(EvalError::TypeError {
+            expected: "Float (or Int)".to_string(),
+            found: format!("{:?}", level),
+            span: call.span,
+        }),
+    }
+}
 
 /// Apply reverb to a block or part with specified level
 /// Usage: reverb(level, block) or block |> reverb(level)
-pub fn builtin_reverb(args: Vec<Value>) -> Result<Value, EvalError> {
+pub fn builtin_reverb(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
     if args.len() != 2 {
         return Err(EvalError::Custom {
             message: "reverb expects 2 arguments (level, block/part)".to_string(),
-            span: relanote_core::Span::dummy(),
+            span: call.span,
+        });
+    }
+
+    // Support both argument orders: (level, target) or (target, level)
+    let (target, level) = match (&args[0], &args[1]) {
+        (level @ (Value::Float(_) | Value::Int(_)), target) => (target, level),
+        (target, level) => (target, level),
+    };
+    let level = level_arg(level, call)?;
+    apply_effect(target, EffectValue::Reverb(level), "Reverb", call)
+}
+
+/// A reverb preset at `level`, applied to the only argument
+fn reverb_preset(
+    name: &str,
+    level: f64,
+    instrument: &str,
+    args: &[Value],
+    call: &CallSite,
+) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::Custom {
+            message: format!("{} expects 1 argument", name),
+            span: call.span,
         });
     }
+    apply_effect(&args[0], EffectValue::Reverb(level), instrument, call)
+}
 
-    // Handle Part input to allow chaining (e.g., |> voice ... |> reverb ...)
+/// Hall reverb preset (high reverb level for large spaces)
+/// Usage: block |> hall_reverb
+pub fn builtin_hall_reverb(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
+    reverb_preset("hall_reverb", 0.7, "Hall", &args, call)
+}
+
+/// Room reverb preset (medium reverb level for smaller spaces)
+/// Usage: block |> room_reverb
+pub fn builtin_room_reverb(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
+    reverb_preset("room_reverb", 0.4, "Room", &args, call)
+}
+
+/// Plate reverb preset (crisp, bright reverb)
+/// Usage: block |> plate_reverb
+pub fn builtin_plate_reverb(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
+    reverb_preset("plate_reverb", 0.5, "Plate", &args, call)
+}
+
+/// Dry signal (no reverb)
+/// Usage: block |> dry
+pub fn builtin_dry(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
+    reverb_preset("dry", 0.0, "Dry", &args, call)
+}
+/// Set volume level for a block
+/// Usage: block |> volume(level) where level is 0.0-1.0 or 0-100
+pub fn builtin_volume(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
+    if args.len() != 2 {
+        return Err(EvalError::Custom {
+            message: "volume expects 2 arguments (level, block)".to_string(),
+            span: call.span,
+        });
+    }
+
+    // Support both argument orders: (level, block) or (block, level)
+    let volume = |block: &BlockValue| PartValue::new("Volume", vec![block.clone()]);
     let (part, level) = match (&args[0], &args[1]) {
-        (Value::Part(part), Value::Float(level)) => (part.clone(), *level),
-        (Value::Float(level), Value::Part(part)) => (part.clone(), *level),
-        (Value::Part(part), Value::Int(level)) => (part.clone(), *level as f64 / 100.0),
-        (Value::Int(level), Value::Part(part)) => (part.clone(), *level as f64 / 100.0),
-        // Also handle Block input directly
-        (Value::Block(block), Value::Float(level)) => {
-            let level = level.clamp(0.0, 1.0);
-            return Ok(Value::Part(PartValue {
-                instrument: "Reverb".to_string(),
-                blocks: vec![block.clone()],
-                envelope: None,
-                reverb_level: Some(level),
-                volume_level: None,
-                delay: None,
-                phaser: None,
-                distortion: None,
-                synth: None,
-            }));
+        (Value::Block(block), Value::Float(level)) | (Value::Float(level), Value::Block(block)) => {
+            (volume(block), *level)
         }
-        (Value::Float(level), Value::Block(block)) => {
-            let level = level.clamp(0.0, 1.0);
-            return Ok(Value::Part(PartValue {
-                instrument: "Reverb".to_string(),
-                blocks: vec![block.clone()],
-                envelope: None,
-                reverb_level: Some(level),
-                volume_level: None,
-                delay: None,
-                phaser: None,
-                distortion: None,
-                synth: None,
-            }));
+        (Value::Block(block), Value::Int(level)) | (Value::Int(level), Value::Block(block)) => {
+            (volume(block), *level as f64 / 100.0)
         }
-        (Value::Block(block), Value::Int(level)) => {
-            let level = (*level as f64 / 100.0).clamp(0.0, 1.0);
-            return Ok(Value::Part(PartValue {
-                instrument: "Reverb".to_string(),
-                blocks: vec![block.clone()],
-                envelope: None,
-                reverb_level: Some(level),
-                volume_level: None,
-                delay: None,
-                phaser: None,
-                distortion: None,
-                synth: None,
-            }));
+        // Handle Part input to allow chaining
+        (Value::Part(part), Value::Float(level)) | (Value::Float(level), Value::Part(part)) => {
+            (part.clone(), *level)
         }
-        (Value::Int(level), Value::Block(block)) => {
-            let level = (*level as f64 / 100.0).clamp(0.0, 1.0);
-            return Ok(Value::Part(PartValue {
-                instrument: "Reverb".to_string(),
-                blocks: vec![block.clone()],
-                envelope: None,
-                reverb_level: Some(level),
-                volume_level: None,
-                delay: None,
-                phaser: None,
-                distortion: None,
-                synth: None,
-            }));
+        (Value::Part(part), Value::Int(level)) | (Value::Int(level), Value::Part(part)) => {
+            (part.clone(), *level as f64 / 100.0)
         }
         _ => {
             return Err(EvalError::TypeError {
                 expected: "Block/Part and Float (or Int)".to_string(),
                 found: format!("{:?}, {:?}", args[0], args[1]),
-                span: relanote_core::Span::dummy(),
+                span: call.span,
             })
         }
     };
 
-    let level = level.clamp(0.0, 1.0);
-
     Ok(Value::Part(PartValue {
-        instrument: part.instrument,
-        blocks: part.blocks,
-        envelope: part.envelope,
-        reverb_level: Some(level),
-        volume_level: part.volume_level,
-        delay: part.delay,
-        phaser: part.phaser,
-        distortion: part.distortion,
-        synth: part.synth,
+        volume_level: Some(level.clamp(0.0, 1.0)),
+        ..part
     }))
 }
 
-/// Hall reverb preset (high reverb level for large spaces)
-/// Usage: block |> hall_reverb
-pub fn builtin_hall_reverb(args: Vec<Value>) -> Result<Value, EvalError> {
-    if args.len() != 1 {
+/// Set stereo pan for a block or part
+/// Usage: block |> pan(position) where position is -1.0 (left) to 1.0 (right) or -100 to 100
+pub fn builtin_pan(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
+    if args.len() != 2 {
         return Err(EvalError::Custom {
-            message: "hall_reverb expects 1 argument".to_string(),
-            span: relanote_core::Span::dummy(),
+            message: "pan expects 2 arguments (position, block/part)".to_string(),
+            span: call.span,
         });
     }
 
-    match &args[0] {
+    // Support both argument orders: (position, block) or (block, position)
+    let (target, position) = match (&args[0], &args[1]) {
+        (target @ (Value::Block(_) | Value::Part(_)), position) => (target, position),
+        (position, target) => (target, position),
+    };
+
+    let position = match position {
+        Value::Float(position) => *position,
+        Value::Int(position) => *position as f64 / 100.0,
+        _ => {
+            return Err(EvalError::TypeError {
+                expected: "Float (or Int)".to_string(),
+                found: format!("{:?}", position),
+                span: call.span,
+            })
+        }
+    };
+    let position = position.clamp(-1.0, 1.0);
+
+    match target {
         Value::Block(block) => Ok(Value::Part(PartValue {
-            instrument: "Hall".to_string(),
-            blocks: vec![block.clone()],
-            envelope: None,
-            reverb_level: Some(0.7),
-            volume_level: None,
-            delay: None,
-            phaser: None,
-            distortion: None,
-            synth: None,
+            pan_level: Some(position),
+            ..PartValue::new("Pan", vec![block.clone()])
         })),
         Value::Part(part) => Ok(Value::Part(PartValue {
-            instrument: part.instrument.clone(),
-            blocks: part.blocks.clone(),
-            envelope: part.envelope.clone(),
-            reverb_level: Some(0.7),
-            volume_level: part.volume_level,
-            delay: part.delay.clone(),
-            phaser: part.phaser.clone(),
-            distortion: part.distortion.clone(),
-            synth: part.synth.clone(),
+            pan_level: Some(position),
+            ..part.clone()
         })),
         _ => Err(EvalError::TypeError {
             expected: "Block or Part".to_string(),
-            found: format!("{:?}", args[0]),
-            span: relanote_core::Span::dummy(),
+            found: format!("{:?}", target),
+            span: call.span,
         }),
     }
 }
 
-/// Room reverb preset (medium reverb level for smaller spaces)
-/// Usage: block |> room_reverb
-pub fn builtin_room_reverb(args: Vec<Value>) -> Result<Value, EvalError> {
-    if args.len() != 1 {
+/// Automate a MIDI controller over a block or part with a curve of levels
+/// Usage: automate(cc, curve, block) or block |> automate(cc, curve)
+/// where curve is an array of levels (0.0-1.0 or 0-100) spread evenly over the part
+pub fn builtin_automate(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
+    if args.len() != 3 {
         return Err(EvalError::Custom {
-            message: "room_reverb expects 1 argument".to_string(),
-            span: relanote_core::Span::dummy(),
+            message: "automate expects 3 arguments (cc, curve, block/part)".to_string(),
+            span: call.span,
         });
     }
 
-    match &args[0] {
-        Value::Block(block) => Ok(Value::Part(PartValue {
-            instrument: "Room".to_string(),
-            blocks: vec![block.clone()],
-            envelope: None,
-            reverb_level: Some(0.4),
-            volume_level: None,
-            delay: None,
-            phaser: None,
-            distortion: None,
-            synth: None,
-        })),
-        Value::Part(part) => Ok(Value::Part(PartValue {
-            instrument: part.instrument.clone(),
-            blocks: part.blocks.clone(),
-            envelope: part.envelope.clone(),
-            reverb_level: Some(0.4),
-            volume_level: part.volume_level,
-            delay: part.delay.clone(),
-            phaser: part.phaser.clone(),
-            distortion: part.distortion.clone(),
-            synth: part.synth.clone(),
-        })),
-        _ => Err(EvalError::TypeError {
-            expected: "Block or Part".to_string(),
-            found: format!("{:?}", args[0]),
-            span: relanote_core::Span::dummy(),
-        }),
+    // Support any argument order: find the target, the controller and the curve
+    let mut target = None;
+    let mut cc = None;
+    let mut curve = None;
+    for arg in &args {
+        match arg {
+            Value::Block(_) | Value::Part(_) => target = Some(arg),
+            Value::Int(n) => cc = Some(*n),
+            Value::Array(points) => curve = Some(points),
+            _ => {
+                return Err(EvalError::TypeError {
+                    expected: "Int, Array or Block/Part".to_string(),
+                    found: format!("{:?}", arg),
+                    span: call.span,
+                })
+            }
+        }
     }
-}
 
-/// Plate reverb preset (crisp, bright reverb)
-/// Usage: block |> plate_reverb
-pub fn builtin_plate_reverb(args: Vec<Value>) -> Result<Value, EvalError> {
-    if args.len() != 1 {
+    let (Some(target), Some(cc), Some(curve)) = (target, cc, curve) else {
         return Err(EvalError::Custom {
-            message: "plate_reverb expects 1 argument".to_string(),
-            span: relanote_core::Span::dummy(),
+            message: "automate expects a controller number, a curve and a block or part"
+                .to_string(),
+            span: call.span,
         });
-    }
+    };
 
-    match &args[0] {
-        Value::Block(block) => Ok(Value::Part(PartValue {
-            instrument: "Plate".to_string(),
-            blocks: vec![block.clone()],
-            envelope: None,
-            reverb_level: Some(0.5),
-            volume_level: None,
-            delay: None,
-            phaser: None,
-            distortion: None,
-            synth: None,
-        })),
-        Value::Part(part) => Ok(Value::Part(PartValue {
-            instrument: part.instrument.clone(),
-            blocks: part.blocks.clone(),
-            envelope: part.envelope.clone(),
-            reverb_level: Some(0.5),
-            volume_level: part.volume_level,
-            delay: part.delay.clone(),
-            phaser: part.phaser.clone(),
-            distortion: part.distortion.clone(),
-            synth: part.synth.clone(),
-        })),
-        _ => Err(EvalError::TypeError {
-            expected: "Block or Part".to_string(),
-            found: format!("{:?}", args[0]),
-            span: relanote_core::Span::dummy(),
-        }),
+    if !(0..=127).contains(&cc) {
+        return Err(EvalError::Custom {
+            message: format!("controller number {} is out of range (0-127)", cc),
+            span: call.span,
+        });
     }
-}
 
-/// Dry signal (no reverb)
-/// Usage: block |> dry
-pub fn builtin_dry(args: Vec<Value>) -> Result<Value, EvalError> {
-    if args.len() != 1 {
+    let points = curve
+        .iter()
+        .map(|point| match point {
+            Value::Float(level) => Ok(level.clamp(0.0, 1.0)),
+            Value::Int(level) => Ok((*level as f64 / 100.0).clamp(0.0, 1.0)),
+            _ => Err(EvalError::TypeError {
+                expected: "Float or Int".to_string(),
+                found: format!("{:?}", point),
+                span: call.span,
+            }),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if points.is_empty() {
         return Err(EvalError::Custom {
-            message: "dry expects 1 argument".to_string(),
-            span: relanote_core::Span::dummy(),
+            message: "automate expects a curve with at least one point".to_string(),
+            span: call.span,
         });
     }
 
-    match &args[0] {
+    let automation = AutomationValue {
+        cc: cc as u8,
+        points,
+    };
+
+    match target {
         Value::Block(block) => Ok(Value::Part(PartValue {
-            instrument: "Dry".to_string(),
-            blocks: vec![block.clone()],
-            envelope: None,
-            reverb_level: Some(0.0),
-            volume_level: None,
-            delay: None,
-            phaser: None,
-            distortion: None,
-            synth: None,
+            automation: vec![automation],
+            ..PartValue::new("Automation", vec![block.clone()])
         })),
-        Value::Part(part) => Ok(Value::Part(PartValue {
-            instrument: part.instrument.clone(),
-            blocks: part.blocks.clone(),
-            envelope: part.envelope.clone(),
-            reverb_level: Some(0.0),
-            volume_level: part.volume_level,
-            delay: part.delay.clone(),
-            phaser: part.phaser.clone(),
-            distortion: part.distortion.clone(),
-            synth: part.synth.clone(),
-        })),
-        _ => Err(EvalError::TypeError {
-            expected: "Block or Part".to_string(),
-            found: format!("{:?}", args[0]),
-            span: relanote_core::Span::dummy(),
-        }),
+        Value::Part(part) => {
+            let mut part = part.clone();
+            part.automation.push(automation);
+            Ok(Value::Part(part))
+        }
+        _ => unreachable!(),
     }
 }
 
-/// Set volume level for a block
-/// Usage: block |> volume(level) where level is 0.0-1.0 or 0-100
-pub fn builtin_volume(args: Vec<Value>) -> Result<Value, EvalError> {
-    if args.len() != 2 {
+/// Dip a block's or part's volume each time a trigger part plays a note
+/// Usage: duck(trigger, amount, target) or pad |> duck(kick, amount)
+/// where amount is 0.0-1.0 or 0-100
+pub fn builtin_duck(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
+    if args.len() != 3 {
         return Err(EvalError::Custom {
-            message: "volume expects 2 arguments (level, block)".to_string(),
-            span: relanote_core::Span::dummy(),
+            message: "duck expects 3 arguments (trigger, amount, block/part)".to_string(),
+            span: call.span,
         });
     }
 
-    // Support both argument orders: (level, block) or (block, level)
-    let (part_or_block, level) = match (&args[0], &args[1]) {
-        (Value::Block(block), Value::Float(level)) => {
-            let part = PartValue {
-                instrument: "Volume".to_string(),
-                blocks: vec![block.clone()],
-                envelope: None,
-                reverb_level: None,
-                volume_level: Some(*level),
-                delay: None,
-                phaser: None,
-                distortion: None,
-                synth: None,
-            };
-            return Ok(Value::Part(part));
-        }
-        (Value::Float(level), Value::Block(block)) => {
-            let part = PartValue {
-                instrument: "Volume".to_string(),
-                blocks: vec![block.clone()],
-                envelope: None,
-                reverb_level: None,
-                volume_level: Some(*level),
-                delay: None,
-                phaser: None,
-                distortion: None,
-                synth: None,
-            };
-            return Ok(Value::Part(part));
-        }
-        (Value::Block(block), Value::Int(level)) => {
-            let part = PartValue {
-                instrument: "Volume".to_string(),
-                blocks: vec![block.clone()],
-                envelope: None,
-                reverb_level: None,
-                volume_level: Some(*level as f64 / 100.0),
-                delay: None,
-                phaser: None,
-                distortion: None,
-                synth: None,
-            };
-            return Ok(Value::Part(part));
-        }
-        (Value::Int(level), Value::Block(block)) => {
-            let part = PartValue {
-                instrument: "Volume".to_string(),
-                blocks: vec![block.clone()],
-                envelope: None,
-                reverb_level: None,
-                volume_level: Some(*level as f64 / 100.0),
-                delay: None,
-                phaser: None,
-                distortion: None,
-                synth: None,
-            };
-            return Ok(Value::Part(part));
-        }
-        // Handle Part input to allow chaining
-        (Value::Part(part), Value::Float(level)) => (part.clone(), *level),
-        (Value::Float(level), Value::Part(part)) => (part.clone(), *level),
-        (Value::Part(part), Value::Int(level)) => (part.clone(), *level as f64 / 100.0),
-        (Value::Int(level), Value::Part(part)) => (part.clone(), *level as f64 / 100.0),
-        _ => {
+    // Piped in, the target comes first: (target, trigger, amount)
+    let (trigger, amount, target) = match &args[2] {
+        Value::Float(_) | Value::Int(_) => (1, 2, 0),
+        _ => (0, 1, 2),
+    };
+    let trigger = match &args[trigger] {
+        Value::Block(block) => PartValue::new("Trigger", vec![block.clone()]),
+        Value::Part(part) => part.clone(),
+        other => {
             return Err(EvalError::TypeError {
-                expected: "Block/Part and Float (or Int)".to_string(),
-                found: format!("{:?}, {:?}", args[0], args[1]),
-                span: relanote_core::Span::dummy(),
+                expected: "Block or Part".to_string(),
+                found: format!("{:?}", other),
+                span: call.arg(trigger),
             })
         }
     };
+    let duck = DuckValue {
+        trigger,
+        amount: level_arg(&args[amount], call)?,
+    };
 
-    let level = level.clamp(0.0, 1.0);
-
-    Ok(Value::Part(PartValue {
-        instrument: part_or_block.instrument,
-        blocks: part_or_block.blocks,
-        envelope: part_or_block.envelope,
-        reverb_level: part_or_block.reverb_level,
-        volume_level: Some(level),
-        delay: part_or_block.delay,
-        phaser: part_or_block.phaser,
-        distortion: part_or_block.distortion,
-        synth: part_or_block.synth,
-    }))
+    match &args[target] {
+        Value::Block(block) => {
+            let mut part = PartValue::new("Duck", vec![block.clone()]);
+            part.ducks.push(duck);
+            Ok(Value::Part(part))
+        }
+        Value::Part(part) => {
+            let mut part = part.clone();
+            part.ducks.push(duck);
+            Ok(Value::Part(part))
+        }
+        other => Err(EvalError::TypeError {
+            expected: "Block or Part".to_string(),
+            found: format!("{:?}", other),
+            span: call.arg(target),
+        }),
+    }
 }
 
 // ============================================================================
@@ -367,11 +338,11 @@ pub fn builtin_volume(args: Vec<Value>) -> Result<Value, EvalError> {
 
 /// Apply delay effect to a block or part
 /// Usage: delay(time_ms, feedback, mix, block) or block |> delay(time_ms, feedback, mix)
-pub fn builtin_delay(args: Vec<Value>) -> Result<Value, EvalError> {
+pub fn builtin_delay(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
     if args.len() != 4 {
         return Err(EvalError::Custom {
             message: "delay expects 4 arguments (time_ms, feedback, mix, block/part)".to_string(),
-            span: relanote_core::Span::dummy(),
+            span: call.span,
         });
     }
 
@@ -380,28 +351,7 @@ pub fn builtin_delay(args: Vec<Value>) -> Result<Value, EvalError> {
 
     let params = DelayParams::new(time_ms, feedback, mix);
 
-    match target {
-        Value::Block(block) => Ok(Value::Part(PartValue {
-            instrument: "Delay".to_string(),
-            blocks: vec![block],
-            envelope: None,
-            reverb_level: None,
-            volume_level: None,
-            delay: Some(params),
-            phaser: None,
-            distortion: None,
-            synth: None,
-        })),
-        Value::Part(part) => Ok(Value::Part(PartValue {
-            delay: Some(params),
-            ..part
-        })),
-        _ => Err(EvalError::TypeError {
-            expected: "Block or Part".to_string(),
-            found: format!("{:?}", target),
-            span: relanote_core::Span::dummy(),
-        }),
-    }
+    apply_effect(&target, EffectValue::Delay(params), "Delay", call)
 }
 
 fn extract_delay_args(args: &[Value]) -> Result<(Value, f64, f64, f64), EvalError> {
@@ -412,10 +362,10 @@ fn extract_delay_args(args: &[Value]) -> Result<(Value, f64, f64, f64), EvalErro
         match arg {
             Value::Float(f) => nums.push(*f),
             Value::Int(i) => nums.push(*i as f64),
-            Value::Block(_) | Value::Part(_) => {
+            Value::Block(_) | Value::Part(_) | Value::Bus(_) | Value::Song(_) => {
                 if target.is_some() {
                     return Err(EvalError::TypeError {
-                        expected: "only one Block or Part".to_string(),
+                        expected: "only one Block, Part, Bus or Song".to_string(),
                         found: "multiple".to_string(),
                         span: relanote_core::Span::dummy(),
                     });
@@ -434,7 +384,7 @@ fn extract_delay_args(args: &[Value]) -> Result<(Value, f64, f64, f64), EvalErro
     }
 
     let target = target.ok_or_else(|| EvalError::TypeError {
-        expected: "Block or Part".to_string(),
+        expected: "Block, Part, Bus or Song".to_string(),
         found: "none".to_string(),
         span: relanote_core::Span::dummy(),
     })?;
@@ -444,11 +394,11 @@ fn extract_delay_args(args: &[Value]) -> Result<(Value, f64, f64, f64), EvalErro
 
 /// Apply phaser effect to a block or part
 /// Usage: phaser(rate, depth, mix, block) or block |> phaser(rate, depth, mix)
-pub fn builtin_phaser(args: Vec<Value>) -> Result<Value, EvalError> {
+pub fn builtin_phaser(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
     if args.len() != 4 {
         return Err(EvalError::Custom {
             message: "phaser expects 4 arguments (rate, depth, mix, block/part)".to_string(),
-            span: relanote_core::Span::dummy(),
+            span: call.span,
         });
     }
 
@@ -456,28 +406,7 @@ pub fn builtin_phaser(args: Vec<Value>) -> Result<Value, EvalError> {
 
     let params = PhaserParams::new(rate, depth, mix);
 
-    match target {
-        Value::Block(block) => Ok(Value::Part(PartValue {
-            instrument: "Phaser".to_string(),
-            blocks: vec![block],
-            envelope: None,
-            reverb_level: None,
-            volume_level: None,
-            delay: None,
-            phaser: Some(params),
-            distortion: None,
-            synth: None,
-        })),
-        Value::Part(part) => Ok(Value::Part(PartValue {
-            phaser: Some(params),
-            ..part
-        })),
-        _ => Err(EvalError::TypeError {
-            expected: "Block or Part".to_string(),
-            found: format!("{:?}", target),
-            span: relanote_core::Span::dummy(),
-        }),
-    }
+    apply_effect(&target, EffectValue::Phaser(params), "Phaser", call)
 }
 
 fn extract_phaser_args(args: &[Value]) -> Result<(Value, f64, f64, f64), EvalError> {
@@ -488,10 +417,10 @@ fn extract_phaser_args(args: &[Value]) -> Result<(Value, f64, f64, f64), EvalErr
         match arg {
             Value::Float(f) => nums.push(*f),
             Value::Int(i) => nums.push(*i as f64),
-            Value::Block(_) | Value::Part(_) => {
+            Value::Block(_) | Value::Part(_) | Value::Bus(_) | Value::Song(_) => {
                 if target.is_some() {
                     return Err(EvalError::TypeError {
-                        expected: "only one Block or Part".to_string(),
+                        expected: "only one Block, Part, Bus or Song".to_string(),
                         found: "multiple".to_string(),
                         span: relanote_core::Span::dummy(),
                     });
@@ -510,7 +439,7 @@ fn extract_phaser_args(args: &[Value]) -> Result<(Value, f64, f64, f64), EvalErr
     }
 
     let target = target.ok_or_else(|| EvalError::TypeError {
-        expected: "Block or Part".to_string(),
+        expected: "Block, Part, Bus or Song".to_string(),
         found: "none".to_string(),
         span: relanote_core::Span::dummy(),
     })?;
@@ -520,11 +449,11 @@ fn extract_phaser_args(args: &[Value]) -> Result<(Value, f64, f64, f64), EvalErr
 
 /// Apply distortion effect to a block or part
 /// Usage: distortion(amount, type, mix, block) or block |> distortion(amount, type, mix)
-pub fn builtin_distortion(args: Vec<Value>) -> Result<Value, EvalError> {
+pub fn builtin_distortion(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
     if args.len() != 4 {
         return Err(EvalError::Custom {
             message: "distortion expects 4 arguments (amount, type, mix, block/part)".to_string(),
-            span: relanote_core::Span::dummy(),
+            span: call.span,
         });
     }
 
@@ -532,28 +461,7 @@ pub fn builtin_distortion(args: Vec<Value>) -> Result<Value, EvalError> {
 
     let params = DistortionParams::new(amount, dist_type, mix);
 
-    match target {
-        Value::Block(block) => Ok(Value::Part(PartValue {
-            instrument: "Distortion".to_string(),
-            blocks: vec![block],
-            envelope: None,
-            reverb_level: None,
-            volume_level: None,
-            delay: None,
-            phaser: None,
-            distortion: Some(params),
-            synth: None,
-        })),
-        Value::Part(part) => Ok(Value::Part(PartValue {
-            distortion: Some(params),
-            ..part
-        })),
-        _ => Err(EvalError::TypeError {
-            expected: "Block or Part".to_string(),
-            found: format!("{:?}", target),
-            span: relanote_core::Span::dummy(),
-        }),
-    }
+    apply_effect(&target, EffectValue::Distortion(params), "Distortion", call)
 }
 
 fn extract_distortion_args(args: &[Value]) -> Result<(Value, f64, DistortionType, f64), EvalError> {
@@ -565,10 +473,10 @@ fn extract_distortion_args(args: &[Value]) -> Result<(Value, f64, DistortionType
         match arg {
             Value::Float(f) => nums.push(*f),
             Value::Int(i) => nums.push(*i as f64),
-            Value::Block(_) | Value::Part(_) => {
+            Value::Block(_) | Value::Part(_) | Value::Bus(_) | Value::Song(_) => {
                 if target.is_some() {
                     return Err(EvalError::TypeError {
-                        expected: "only one Block or Part".to_string(),
+                        expected: "only one Block, Part, Bus or Song".to_string(),
                         found: "multiple".to_string(),
                         span: relanote_core::Span::dummy(),
                     });
@@ -590,7 +498,7 @@ fn extract_distortion_args(args: &[Value]) -> Result<(Value, f64, DistortionType
     }
 
     let target = target.ok_or_else(|| EvalError::TypeError {
-        expected: "Block or Part".to_string(),
+        expected: "Block, Part, Bus or Song".to_string(),
         found: "none".to_string(),
         span: relanote_core::Span::dummy(),
     })?;
@@ -600,26 +508,189 @@ fn extract_distortion_args(args: &[Value]) -> Result<(Value, f64, DistortionType
     Ok((target, nums[0], dist_type, nums[1]))
 }
 
+/// The target of an effect and its `count` numeric settings, in order
+fn effect_args<'a>(
+    name: &str,
+    settings: &str,
+    count: usize,
+    args: &'a [Value],
+    call: &CallSite,
+) -> Result<(&'a Value, Vec<f64>), EvalError> {
+    if args.len() != count + 1 {
+        return Err(EvalError::Custom {
+            message: format!(
+                "{} expects {} arguments ({}, block/part)",
+                name,
+                count + 1,
+                settings
+            ),
+            span: call.span,
+        });
+    }
+
+    let mut nums = Vec::with_capacity(count);
+    let mut target = None;
+    for (index, arg) in args.iter().enumerate() {
+        match arg {
+            Value::Float(f) => nums.push(*f),
+            Value::Int(i) => nums.push(*i as f64),
+            Value::Block(_) | Value::Part(_) | Value::Bus(_) | Value::Song(_)
+                if target.is_none() =>
+            {
+                target = Some(arg)
+            }
+            other => {
+                return Err(EvalError::TypeError {
+                    expected: "Float or Int".to_string(),
+                    found: format!("{:?}", other),
+                    span: call.arg(index),
+                })
+            }
+        }
+    }
+
+    let target = target.ok_or_else(|| EvalError::TypeError {
+        expected: "Block, Part, Bus or Song".to_string(),
+        found: "none".to_string(),
+        span: call.span,
+    })?;
+    Ok((target, nums))
+}
+
+/// Apply a compressor to a block or part (`compress` is taken by the
+/// rhythm transform)
+/// Usage: compressor(threshold, ratio, attack_ms, release_ms, block)
+/// or block |> compressor(threshold, ratio, attack_ms, release_ms)
+pub fn builtin_compressor(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
+    let (target, nums) = effect_args(
+        "compressor",
+        "threshold, ratio, attack, release",
+        4,
+        &args,
+        call,
+    )?;
+    let params = CompressorParams::new(nums[0], nums[1], nums[2], nums[3]);
+    apply_effect(target, EffectValue::Compressor(params), "Compressor", call)
+}
+
+/// Apply a three-band EQ to a block or part, with gains in dB
+/// Usage: eq(low, mid, high, block) or block |> eq(low, mid, high)
+pub fn builtin_eq(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
+    let (target, nums) = effect_args("eq", "low, mid, high", 3, &args, call)?;
+    let params = EqParams::new(nums[0], nums[1], nums[2]);
+    apply_effect(target, EffectValue::Eq(params), "EQ", call)
+}
+
+/// Apply a chorus to a block or part (`chorus` is left free for song
+/// sections)
+/// Usage: chorus_fx(rate, depth, mix, block) or block |> chorus_fx(rate, depth, mix)
+pub fn builtin_chorus_fx(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
+    let (target, nums) = effect_args("chorus_fx", "rate, depth, mix", 3, &args, call)?;
+    let params = ChorusParams::new(nums[0], nums[1], nums[2]);
+    apply_effect(target, EffectValue::Chorus(params), "Chorus", call)
+}
+
+/// Narrow or widen the stereo image of a block or part
+/// Usage: width(amount, block) or block |> width(amount)
+/// where amount is 0.0 (mono) to 2.0, 1.0 leaving it as is
+pub fn builtin_width(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
+    let (target, nums) = effect_args("width", "amount", 1, &args, call)?;
+    let amount = nums[0].clamp(0.0, 2.0);
+    apply_effect(target, EffectValue::Width(amount), "Width", call)
+}
+
+// ============================================================================
+// Buses
+// ============================================================================
+
+/// A bus without effects, for effects to be added to and parts to send to
+/// Usage: bus "room" |> hall_reverb
+pub fn builtin_bus(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
+    match args.as_slice() {
+        [Value::String(name)] => Ok(Value::Bus(BusValue {
+            name: name.clone(),
+            effects: vec![],
+        })),
+        [other] => Err(EvalError::TypeError {
+            expected: "String".to_string(),
+            found: format!("{:?}", other),
+            span: call.span,
+        }),
+        _ => Err(EvalError::Custom {
+            message: "bus expects 1 argument (name)".to_string(),
+            span: call.span,
+        }),
+    }
+}
+
+/// Send some of a block's or part's sound to a bus
+/// Usage: send(bus, level, block) or block |> send(bus, level)
+pub fn builtin_send(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
+    if args.len() != 3 {
+        return Err(EvalError::Custom {
+            message: "send expects 3 arguments (bus, level, block/part)".to_string(),
+            span: call.span,
+        });
+    }
+
+    let mut bus = None;
+    let mut level = None;
+    let mut target = None;
+    for arg in &args {
+        match arg {
+            Value::Bus(b) => bus = Some(b),
+            Value::Float(_) | Value::Int(_) => level = Some(level_arg(arg, call)?),
+            Value::Block(_) | Value::Part(_) => target = Some(arg),
+            other => {
+                return Err(EvalError::TypeError {
+                    expected: "Bus, Float or Block/Part".to_string(),
+                    found: format!("{:?}", other),
+                    span: call.span,
+                })
+            }
+        }
+    }
+    let (Some(bus), Some(level), Some(target)) = (bus, level, target) else {
+        return Err(EvalError::Custom {
+            message: "send expects a bus, a level and a block or part".to_string(),
+            span: call.span,
+        });
+    };
+
+    let mut part = match target {
+        Value::Block(block) => PartValue::new("Send", vec![block.clone()]),
+        Value::Part(part) => part.clone(),
+        _ => unreachable!(),
+    };
+    // Sending to a bus again changes the level
+    part.sends.retain(|send| send.bus.name != bus.name);
+    part.sends.push(SendValue {
+        bus: bus.clone(),
+        level,
+    });
+    Ok(Value::Part(part))
+}
+
 // ============================================================================
 // Distortion Type Constructors
 // ============================================================================
 
 /// Soft clipping distortion type (tube-like warmth)
-pub fn builtin_soft_clip(_args: Vec<Value>) -> Result<Value, EvalError> {
+pub fn builtin_soft_clip(_args: Vec<Value>, _call: &CallSite) -> Result<Value, EvalError> {
     Ok(Value::DistortionType(DistortionType::Soft))
 }
 
 /// Hard clipping distortion type (aggressive)
-pub fn builtin_hard_clip(_args: Vec<Value>) -> Result<Value, EvalError> {
+pub fn builtin_hard_clip(_args: Vec<Value>, _call: &CallSite) -> Result<Value, EvalError> {
     Ok(Value::DistortionType(DistortionType::Hard))
 }
 
 /// Fuzz distortion type (asymmetric clipping)
-pub fn builtin_fuzz(_args: Vec<Value>) -> Result<Value, EvalError> {
+pub fn builtin_fuzz(_args: Vec<Value>, _call: &CallSite) -> Result<Value, EvalError> {
     Ok(Value::DistortionType(DistortionType::Fuzz))
 }
 
 /// Bit crush distortion type (lo-fi bit reduction)
-pub fn builtin_bitcrush(_args: Vec<Value>) -> Result<Value, EvalError> {
+pub fn builtin_bitcrush(_args: Vec<Value>, _call: &CallSite) -> Result<Value, EvalError> {
     Ok(Value::DistortionType(DistortionType::BitCrush))
 }
@@ -1,21 +1,21 @@
 //! Functional programming builtins
 //!
-//! This module provides common FP utilities for working with arrays and lists.
+//! This module provides common FP utilities for working with arrays and lists,
+//! and for mapping functions over the notes of a block.
 
-use std::cell::RefCell;
-use std::rc::Rc;
-
-use crate::env::Env;
 use crate::error::EvalError;
-use crate::value::{Closure, Value};
+use crate::eval::apply_function;
+use crate::value::{
+    tuplet_shares, BlockValue, CallSite, Closure, DurationValue, IntervalValue, SlotValue, Value,
+};
 
 /// Map a function over an array
 /// Usage: map(fn, array) or array |> map(fn)
-pub fn builtin_map(args: Vec<Value>) -> Result<Value, EvalError> {
+pub fn builtin_map(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
     if args.len() != 2 {
         return Err(EvalError::Custom {
             message: "map expects 2 arguments (fn, array)".to_string(),
-            span: relanote_core::Span::dummy(),
+            span: call.span,
         });
     }
 
@@ -24,36 +24,38 @@ pub fn builtin_map(args: Vec<Value>) -> Result<Value, EvalError> {
         (Value::Closure(f), Value::Array(arr)) => (arr.clone(), f.clone()),
         (Value::Array(arr), Value::Builtin(f)) => {
             // Apply builtin to each element
-            let results: Result<Vec<_>, _> = arr.iter().map(|v| f(vec![v.clone()])).collect();
+            let results: Result<Vec<_>, _> =
+                arr.iter().map(|v| f.call(vec![v.clone()], call)).collect();
             return Ok(Value::Array(results?));
         }
         (Value::Builtin(f), Value::Array(arr)) => {
-            let results: Result<Vec<_>, _> = arr.iter().map(|v| f(vec![v.clone()])).collect();
+            let results: Result<Vec<_>, _> =
+                arr.iter().map(|v| f.call(vec![v.clone()], call)).collect();
             return Ok(Value::Array(results?));
         }
         _ => {
             return Err(EvalError::TypeError {
                 expected: "Array and Function".to_string(),
                 found: format!("{:?}, {:?}", args[0], args[1]),
-                span: relanote_core::Span::dummy(),
+                span: call.span,
             })
         }
     };
 
     let results: Result<Vec<_>, _> = arr
         .iter()
-        .map(|v| apply_closure(&func, vec![v.clone()]))
+        .map(|v| apply_closure(&func, vec![v.clone()], call))
         .collect();
     Ok(Value::Array(results?))
 }
 
 /// Filter an array by a predicate function
 /// Usage: filter(fn, array) or array |> filter(fn)
-pub fn builtin_filter(args: Vec<Value>) -> Result<Value, EvalError> {
+pub fn builtin_filter(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
     if args.len() != 2 {
         return Err(EvalError::Custom {
             message: "filter expects 2 arguments (fn, array)".to_string(),
-            span: relanote_core::Span::dummy(),
+            span: call.span,
         });
     }
 
@@ -64,14 +66,14 @@ pub fn builtin_filter(args: Vec<Value>) -> Result<Value, EvalError> {
             return Err(EvalError::TypeError {
                 expected: "Array and Function".to_string(),
                 found: format!("{:?}, {:?}", args[0], args[1]),
-                span: relanote_core::Span::dummy(),
+                span: call.span,
             })
         }
     };
 
     let mut results = Vec::new();
     for v in arr {
-        let result = apply_closure(&func, vec![v.clone()])?;
+        let result = apply_closure(&func, vec![v.clone()], call)?;
         if let Value::Bool(true) = result {
             results.push(v);
         }
@@ -81,11 +83,11 @@ pub fn builtin_filter(args: Vec<Value>) -> Result<Value, EvalError> {
 
 /// Left fold: foldl fn init array
 /// Accumulates from left to right: foldl f z [a,b,c] = f (f (f z a) b) c
-pub fn builtin_foldl(args: Vec<Value>) -> Result<Value, EvalError> {
+pub fn builtin_foldl(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
     if args.len() != 3 {
         return Err(EvalError::Custom {
             message: "foldl expects 3 arguments: foldl fn init array".to_string(),
-            span: relanote_core::Span::dummy(),
+            span: call.span,
         });
     }
 
@@ -108,34 +110,34 @@ pub fn builtin_foldl(args: Vec<Value>) -> Result<Value, EvalError> {
     let arr = arr.ok_or_else(|| EvalError::TypeError {
         expected: "Array".to_string(),
         found: "no array argument".to_string(),
-        span: relanote_core::Span::dummy(),
+        span: call.span,
     })?;
 
     let func = func.ok_or_else(|| EvalError::TypeError {
         expected: "Function".to_string(),
         found: "no function argument".to_string(),
-        span: relanote_core::Span::dummy(),
+        span: call.span,
     })?;
 
     let init = init.ok_or_else(|| EvalError::Custom {
         message: "foldl requires an initial value".to_string(),
-        span: relanote_core::Span::dummy(),
+        span: call.span,
     })?;
 
     let mut acc = init;
     for v in arr {
-        acc = apply_closure(&func, vec![acc, v])?;
+        acc = apply_closure(&func, vec![acc, v], call)?;
     }
     Ok(acc)
 }
 
 /// Right fold: foldr fn init array
 /// Accumulates from right to left: foldr f z [a,b,c] = f a (f b (f c z))
-pub fn builtin_foldr(args: Vec<Value>) -> Result<Value, EvalError> {
+pub fn builtin_foldr(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
     if args.len() != 3 {
         return Err(EvalError::Custom {
             message: "foldr expects 3 arguments: foldr fn init array".to_string(),
-            span: relanote_core::Span::dummy(),
+            span: call.span,
         });
     }
 
@@ -158,35 +160,35 @@ pub fn builtin_foldr(args: Vec<Value>) -> Result<Value, EvalError> {
     let arr = arr.ok_or_else(|| EvalError::TypeError {
         expected: "Array".to_string(),
         found: "no array argument".to_string(),
-        span: relanote_core::Span::dummy(),
+        span: call.span,
     })?;
 
     let func = func.ok_or_else(|| EvalError::TypeError {
         expected: "Function".to_string(),
         found: "no function argument".to_string(),
-        span: relanote_core::Span::dummy(),
+        span: call.span,
     })?;
 
     let init = init.ok_or_else(|| EvalError::Custom {
         message: "foldr requires an initial value".to_string(),
-        span: relanote_core::Span::dummy(),
+        span: call.span,
     })?;
 
     // Fold from right to left
     let mut acc = init;
     for v in arr.into_iter().rev() {
-        acc = apply_closure(&func, vec![v, acc])?;
+        acc = apply_closure(&func, vec![v, acc], call)?;
     }
     Ok(acc)
 }
 
 /// Find the first element matching a predicate
 /// Usage: find(fn, array) or array |> find(fn)
-pub fn builtin_find(args: Vec<Value>) -> Result<Value, EvalError> {
+pub fn builtin_find(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
     if args.len() != 2 {
         return Err(EvalError::Custom {
             message: "find expects 2 arguments (fn, array)".to_string(),
-            span: relanote_core::Span::dummy(),
+            span: call.span,
         });
     }
 
@@ -197,13 +199,13 @@ pub fn builtin_find(args: Vec<Value>) -> Result<Value, EvalError> {
             return Err(EvalError::TypeError {
                 expected: "Array and Function".to_string(),
                 found: format!("{:?}, {:?}", args[0], args[1]),
-                span: relanote_core::Span::dummy(),
+                span: call.span,
             })
         }
     };
 
     for v in arr {
-        let result = apply_closure(&func, vec![v.clone()])?;
+        let result = apply_closure(&func, vec![v.clone()], call)?;
         if let Value::Bool(true) = result {
             return Ok(v);
         }
@@ -213,11 +215,11 @@ pub fn builtin_find(args: Vec<Value>) -> Result<Value, EvalError> {
 
 /// Check if any element matches a predicate
 /// Usage: any(fn, array) or array |> any(fn)
-pub fn builtin_any(args: Vec<Value>) -> Result<Value, EvalError> {
+pub fn builtin_any(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
     if args.len() != 2 {
         return Err(EvalError::Custom {
             message: "any expects 2 arguments (fn, array)".to_string(),
-            span: relanote_core::Span::dummy(),
+            span: call.span,
         });
     }
 
@@ -228,13 +230,13 @@ pub fn builtin_any(args: Vec<Value>) -> Result<Value, EvalError> {
             return Err(EvalError::TypeError {
                 expected: "Array and Function".to_string(),
                 found: format!("{:?}, {:?}", args[0], args[1]),
-                span: relanote_core::Span::dummy(),
+                span: call.span,
             })
         }
     };
 
     for v in arr {
-        let result = apply_closure(&func, vec![v])?;
+        let result = apply_closure(&func, vec![v], call)?;
         if let Value::Bool(true) = result {
             return Ok(Value::Bool(true));
         }
@@ -244,11 +246,11 @@ pub fn builtin_any(args: Vec<Value>) -> Result<Value, EvalError> {
 
 /// Check if all elements match a predicate
 /// Usage: all(fn, array) or array |> all(fn)
-pub fn builtin_all(args: Vec<Value>) -> Result<Value, EvalError> {
+pub fn builtin_all(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
     if args.len() != 2 {
         return Err(EvalError::Custom {
             message: "all expects 2 arguments (fn, array)".to_string(),
-            span: relanote_core::Span::dummy(),
+            span: call.span,
         });
     }
 
@@ -259,13 +261,13 @@ pub fn builtin_all(args: Vec<Value>) -> Result<Value, EvalError> {
             return Err(EvalError::TypeError {
                 expected: "Array and Function".to_string(),
                 found: format!("{:?}, {:?}", args[0], args[1]),
-                span: relanote_core::Span::dummy(),
+                span: call.span,
             })
         }
     };
 
     for v in arr {
-        let result = apply_closure(&func, vec![v])?;
+        let result = apply_closure(&func, vec![v], call)?;
         if let Value::Bool(false) = result {
             return Ok(Value::Bool(false));
         }
@@ -275,11 +277,11 @@ pub fn builtin_all(args: Vec<Value>) -> Result<Value, EvalError> {
 
 /// Take the first n elements
 /// Usage: take(n, array) or array |> take(n)
-pub fn builtin_take(args: Vec<Value>) -> Result<Value, EvalError> {
+pub fn builtin_take(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
     if args.len() != 2 {
         return Err(EvalError::Custom {
             message: "take expects 2 arguments (n, array)".to_string(),
-            span: relanote_core::Span::dummy(),
+            span: call.span,
         });
     }
 
@@ -290,7 +292,7 @@ pub fn builtin_take(args: Vec<Value>) -> Result<Value, EvalError> {
             return Err(EvalError::TypeError {
                 expected: "Array and Int".to_string(),
                 found: format!("{:?}, {:?}", args[0], args[1]),
-                span: relanote_core::Span::dummy(),
+                span: call.span,
             })
         }
     };
@@ -300,11 +302,11 @@ pub fn builtin_take(args: Vec<Value>) -> Result<Value, EvalError> {
 
 /// Drop the first n elements
 /// Usage: drop(n, array) or array |> drop(n)
-pub fn builtin_drop(args: Vec<Value>) -> Result<Value, EvalError> {
+pub fn builtin_drop(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
     if args.len() != 2 {
         return Err(EvalError::Custom {
             message: "drop expects 2 arguments (n, array)".to_string(),
-            span: relanote_core::Span::dummy(),
+            span: call.span,
         });
     }
 
@@ -315,7 +317,7 @@ pub fn builtin_drop(args: Vec<Value>) -> Result<Value, EvalError> {
             return Err(EvalError::TypeError {
                 expected: "Array and Int".to_string(),
                 found: format!("{:?}, {:?}", args[0], args[1]),
-                span: relanote_core::Span::dummy(),
+                span: call.span,
             })
         }
     };
@@ -325,11 +327,11 @@ pub fn builtin_drop(args: Vec<Value>) -> Result<Value, EvalError> {
 
 /// Zip two arrays together
 /// Usage: zip(array1, array2)
-pub fn builtin_zip(args: Vec<Value>) -> Result<Value, EvalError> {
+pub fn builtin_zip(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
     if args.len() != 2 {
         return Err(EvalError::Custom {
             message: "zip expects 2 arguments".to_string(),
-            span: relanote_core::Span::dummy(),
+            span: call.span,
         });
     }
 
@@ -339,7 +341,7 @@ pub fn builtin_zip(args: Vec<Value>) -> Result<Value, EvalError> {
             return Err(EvalError::TypeError {
                 expected: "Two Arrays".to_string(),
                 found: format!("{:?}, {:?}", args[0], args[1]),
-                span: relanote_core::Span::dummy(),
+                span: call.span,
             })
         }
     };
@@ -354,11 +356,11 @@ pub fn builtin_zip(args: Vec<Value>) -> Result<Value, EvalError> {
 
 /// Concatenate two arrays
 /// Usage: concat(array1, array2)
-pub fn builtin_concat(args: Vec<Value>) -> Result<Value, EvalError> {
+pub fn builtin_concat(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
     if args.len() != 2 {
         return Err(EvalError::Custom {
             message: "concat expects 2 arguments".to_string(),
-            span: relanote_core::Span::dummy(),
+            span: call.span,
         });
     }
 
@@ -368,7 +370,7 @@ pub fn builtin_concat(args: Vec<Value>) -> Result<Value, EvalError> {
             return Err(EvalError::TypeError {
                 expected: "Two Arrays".to_string(),
                 found: format!("{:?}, {:?}", args[0], args[1]),
-                span: relanote_core::Span::dummy(),
+                span: call.span,
             })
         }
     };
@@ -380,11 +382,11 @@ pub fn builtin_concat(args: Vec<Value>) -> Result<Value, EvalError> {
 
 /// Get the length of an array
 /// Usage: len(array)
-pub fn builtin_len(args: Vec<Value>) -> Result<Value, EvalError> {
+pub fn builtin_len(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
     if args.len() != 1 {
         return Err(EvalError::Custom {
             message: "len expects 1 argument".to_string(),
-            span: relanote_core::Span::dummy(),
+            span: call.span,
         });
     }
 
@@ -394,18 +396,18 @@ pub fn builtin_len(args: Vec<Value>) -> Result<Value, EvalError> {
         _ => Err(EvalError::TypeError {
             expected: "Array or String".to_string(),
             found: format!("{:?}", args[0]),
-            span: relanote_core::Span::dummy(),
+            span: call.span,
         }),
     }
 }
 
 /// FlatMap: map then flatten
 /// Usage: flatMap(fn, array) or array |> flatMap(fn)
-pub fn builtin_flat_map(args: Vec<Value>) -> Result<Value, EvalError> {
+pub fn builtin_flat_map(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
     if args.len() != 2 {
         return Err(EvalError::Custom {
             message: "flatMap expects 2 arguments (fn, array)".to_string(),
-            span: relanote_core::Span::dummy(),
+            span: call.span,
         });
     }
 
@@ -416,14 +418,14 @@ pub fn builtin_flat_map(args: Vec<Value>) -> Result<Value, EvalError> {
             return Err(EvalError::TypeError {
                 expected: "Array and Function".to_string(),
                 found: format!("{:?}, {:?}", args[0], args[1]),
-                span: relanote_core::Span::dummy(),
+                span: call.span,
             })
         }
     };
 
     let mut results = Vec::new();
     for v in arr {
-        let result = apply_closure(&func, vec![v])?;
+        let result = apply_closure(&func, vec![v], call)?;
         match result {
             Value::Array(inner) => results.extend(inner),
             other => results.push(other),
@@ -433,33 +435,259 @@ pub fn builtin_flat_map(args: Vec<Value>) -> Result<Value, EvalError> {
 }
 
 /// Helper function to apply a closure to arguments
-fn apply_closure(closure: &Closure, args: Vec<Value>) -> Result<Value, EvalError> {
+fn apply_closure(closure: &Closure, args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
     if closure.params.len() != args.len() {
         return Err(EvalError::WrongArity {
             expected: closure.params.len(),
             got: args.len(),
-            span: relanote_core::Span::dummy(),
+            span: call.span,
         });
     }
+    apply_function(Value::Closure(closure.clone()), args, call)
+}
+
+/// Transform each note of a block with a function
+/// The function gets the note's interval, its length and its index among the
+/// block's slots (as many of them as it takes) and returns the new interval,
+/// or an (interval, duration) pair to change the length too. Each note of a
+/// chord is passed on its own.
+/// Usage: map_notes(fn, block) or block |> map_notes(fn)
+pub fn builtin_map_notes(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
+    let (block, func) = block_and_function("map_notes", &args, call)?;
+
+    let slots = map_sounding(block, |slot, beats, index, in_tuplet| {
+        let mut length = None;
+        let mut map_interval = |interval: &IntervalValue| -> Result<IntervalValue, EvalError> {
+            match apply_function(func.clone(), note_args(func, interval, beats, index), call)? {
+                Value::Interval(interval) => Ok(interval),
+                Value::Tuple(items) => match items.as_slice() {
+                    [Value::Interval(interval), Value::Duration(duration)] => {
+                        length.get_or_insert(duration.beats);
+                        Ok(interval.clone())
+                    }
+                    _ => Err(map_notes_result_error(&Value::Tuple(items.clone()), call)),
+                },
+                other => Err(map_notes_result_error(&other, call)),
+            }
+        };
+        let mut slot = match slot {
+            SlotValue::Note {
+                interval,
+                articulations,
+                duration_beats,
+                meta,
+            } => SlotValue::Note {
+                interval: map_interval(interval)?,
+                articulations: articulations.clone(),
+                duration_beats: *duration_beats,
+                meta: meta.clone(),
+            },
+            SlotValue::Chord {
+                intervals,
+                articulations,
+                duration_beats,
+                meta,
+            } => SlotValue::Chord {
+                intervals: intervals
+                    .iter()
+                    .map(&mut map_interval)
+                    .collect::<Result<_, _>>()?,
+                articulations: articulations.clone(),
+                duration_beats: *duration_beats,
+                meta: meta.clone(),
+            },
+            other => other.clone(),
+        };
+        if let Some(beats) = length {
+            if in_tuplet {
+                return Err(EvalError::Custom {
+                    message: "map_notes can't change the length of a note in a tuplet".to_string(),
+                    span: call.span,
+                });
+            }
+            if let SlotValue::Note { duration_beats, .. }
+            | SlotValue::Chord { duration_beats, .. } = &mut slot
+            {
+                *duration_beats = Some(beats);
+            }
+        }
+        Ok(slot)
+    })?;
+    Ok(Value::Block(BlockValue::with_beats(slots, block.beats)))
+}
+
+/// Keep the notes of a block a predicate accepts
+/// The predicate gets the same arguments as the function of `map_notes`; notes
+/// it rejects become rests, so the rhythm of the block is kept. A chord keeps
+/// the notes that pass.
+/// Usage: filter_notes(fn, block) or block |> filter_notes(fn)
+pub fn builtin_filter_notes(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
+    let (block, func) = block_and_function("filter_notes", &args, call)?;
+
+    let slots = map_sounding(block, |slot, beats, index, _| {
+        let keep = |interval: &IntervalValue| -> Result<bool, EvalError> {
+            match apply_function(func.clone(), note_args(func, interval, beats, index), call)? {
+                Value::Bool(keep) => Ok(keep),
+                other => Err(EvalError::TypeError {
+                    expected: "Bool".to_string(),
+                    found: format!("{:?}", other),
+                    span: call.span,
+                }),
+            }
+        };
+        let rest = SlotValue::Rest {
+            duration_beats: slot.duration_beats(),
+        };
+        match slot {
+            SlotValue::Note { interval, .. } => {
+                Ok(if keep(interval)? { slot.clone() } else { rest })
+            }
+            SlotValue::Chord {
+                intervals,
+                articulations,
+                duration_beats,
+                meta,
+            } => {
+                let mut kept = Vec::new();
+                for interval in intervals {
+                    if keep(interval)? {
+                        kept.push(interval.clone());
+                    }
+                }
+                Ok(if kept.is_empty() {
+                    rest
+                } else {
+                    SlotValue::Chord {
+                        intervals: kept,
+                        articulations: articulations.clone(),
+                        duration_beats: *duration_beats,
+                        meta: meta.clone(),
+                    }
+                })
+            }
+            other => Ok(other.clone()),
+        }
+    })?;
+    Ok(Value::Block(BlockValue::with_beats(slots, block.beats)))
+}
 
-    // Create new environment with closure's captured environment as parent
-    let new_env = Rc::new(RefCell::new(Env::with_parent(closure.env.clone())));
+/// The block and the function of a higher-order block builtin, in either order
+fn block_and_function<'a>(
+    name: &str,
+    args: &'a [Value],
+    call: &CallSite,
+) -> Result<(&'a BlockValue, &'a Value), EvalError> {
+    if args.len() != 2 {
+        return Err(EvalError::Custom {
+            message: format!("{} expects 2 arguments (fn, block)", name),
+            span: call.span,
+        });
+    }
+    let is_function = |value: &Value| {
+        matches!(
+            value,
+            Value::Closure(_) | Value::Builtin(_) | Value::Partial(_) | Value::Composed(..)
+        )
+    };
+    match (&args[0], &args[1]) {
+        (Value::Block(block), f) | (f, Value::Block(block)) if is_function(f) => Ok((block, f)),
+        _ => Err(EvalError::TypeError {
+            expected: "Block and Function".to_string(),
+            found: format!("{:?}, {:?}", args[0], args[1]),
+            span: call.span,
+        }),
+    }
+}
 
-    // Bind parameters
-    for (param, arg) in closure.params.iter().zip(args) {
-        new_env.borrow_mut().bind(*param, arg);
+/// Arguments for a note function: the note's interval, length and index,
+/// cut down to the number of parameters a closure takes
+fn note_args(func: &Value, interval: &IntervalValue, beats: f64, index: usize) -> Vec<Value> {
+    let mut args = vec![
+        Value::Interval(interval.clone()),
+        Value::Duration(DurationValue { beats }),
+        Value::Int(index as i64),
+    ];
+    if let Value::Closure(closure) = func {
+        args.truncate(closure.params.len().max(1));
     }
+    args
+}
 
-    // Evaluate body - we need to use a simple evaluator here
-    // For now, we'll need to import Evaluator to do this properly
-    // This is a limitation - we may need to restructure
+fn map_notes_result_error(found: &Value, call: &CallSite) -> EvalError {
+    EvalError::TypeError {
+        expected: "Interval or (Interval, Duration)".to_string(),
+        found: format!("{:?}", found),
+        span: call.span,
+    }
+}
 
-    // For closures that return simple values, we can evaluate expressions directly
-    // But for complex expressions, we'd need the full evaluator
+/// Rebuild a block's slots, passing each note and chord to `f` with its
+/// length in beats, its index among the slots (those in tuplets and overlaid
+/// voices counted one by one) and whether it is in a tuplet
+fn map_sounding(
+    block: &BlockValue,
+    mut f: impl FnMut(&SlotValue, f64, usize, bool) -> Result<SlotValue, EvalError>,
+) -> Result<Vec<SlotValue>, EvalError> {
+    type Visit<'f> = dyn FnMut(&SlotValue, f64, usize, bool) -> Result<SlotValue, EvalError> + 'f;
+
+    fn walk(
+        slots: &[SlotValue],
+        lengths: Vec<f64>,
+        in_tuplet: bool,
+        index: &mut usize,
+        f: &mut Visit,
+    ) -> Result<Vec<SlotValue>, EvalError> {
+        slots
+            .iter()
+            .zip(lengths)
+            .map(|(slot, beats)| match slot {
+                SlotValue::Tuplet {
+                    slots: inner,
+                    target_beats,
+                } => {
+                    let lengths = tuplet_shares(inner)
+                        .map(|(_, _, share)| share * beats)
+                        .collect();
+                    Ok(SlotValue::Tuplet {
+                        slots: walk(inner, lengths, true, index, f)?,
+                        target_beats: *target_beats,
+                    })
+                }
+                SlotValue::Overlay { voices } => Ok(SlotValue::Overlay {
+                    voices: voices
+                        .iter()
+                        .map(|voice| {
+                            Ok(BlockValue::with_beats(
+                                walk(
+                                    &voice.slots,
+                                    voice.slot_lengths().collect(),
+                                    in_tuplet,
+                                    index,
+                                    f,
+                                )?,
+                                voice.beats,
+                            ))
+                        })
+                        .collect::<Result<_, EvalError>>()?,
+                }),
+                SlotValue::Rest { .. } => {
+                    *index += 1;
+                    Ok(slot.clone())
+                }
+                _ => {
+                    let mapped = f(slot, beats, *index, in_tuplet)?;
+                    *index += 1;
+                    Ok(mapped)
+                }
+            })
+            .collect()
+    }
 
-    // For now, return an error if we can't evaluate
-    Err(EvalError::Custom {
-        message: "Closure evaluation in functional builtins requires evaluator context".to_string(),
-        span: relanote_core::Span::dummy(),
-    })
+    walk(
+        &block.slots,
+        block.slot_lengths().collect(),
+        false,
+        &mut 0,
+        &mut f,
+    )
 }
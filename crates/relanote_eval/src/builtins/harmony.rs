@@ -0,0 +1,577 @@
+//! Harmony builtins: chords from chord symbols and Roman numerals, voice
+//! leading, and transforms that remap pitches harmonically
+
+use relanote_core::Span;
+
+use super::block::split_block;
+use crate::error::EvalError;
+use crate::value::{BlockValue, CallSite, IntervalValue, NoteMeta, ScaleValue, SlotValue, Value};
+
+/// Chord symbol qualities and the prelude chords they stand for
+pub(crate) const CHORD_QUALITIES: &[(&str, &str)] = &[
+    ("", "MajorTriad"),
+    ("maj", "MajorTriad"),
+    ("M", "MajorTriad"),
+    ("m", "MinorTriad"),
+    ("min", "MinorTriad"),
+    ("-", "MinorTriad"),
+    ("dim", "Diminished"),
+    ("°", "Diminished"),
+    ("o", "Diminished"),
+    ("aug", "Augmented"),
+    ("+", "Augmented"),
+    ("maj7", "Major7"),
+    ("M7", "Major7"),
+    ("Δ7", "Major7"),
+    ("Δ", "Major7"),
+    ("m7", "Minor7"),
+    ("min7", "Minor7"),
+    ("-7", "Minor7"),
+    ("7", "Dominant7"),
+    ("mMaj7", "MinorMajor7"),
+    ("mM7", "MinorMajor7"),
+    ("m(maj7)", "MinorMajor7"),
+    ("m7b5", "HalfDiminished7"),
+    ("ø", "HalfDiminished7"),
+    ("ø7", "HalfDiminished7"),
+    ("dim7", "Diminished7"),
+    ("°7", "Diminished7"),
+    ("o7", "Diminished7"),
+    ("sus2", "Sus2"),
+    ("sus4", "Sus4"),
+    ("sus", "Sus4"),
+    ("add9", "Add9"),
+];
+
+/// Intervals of the prelude chord a quality stands for, as visible at the call
+pub(crate) fn quality_intervals(
+    quality: &str,
+    call: &CallSite,
+) -> Result<Option<Vec<IntervalValue>>, EvalError> {
+    let Some((_, name)) = CHORD_QUALITIES.iter().find(|(q, _)| *q == quality) else {
+        return Ok(None);
+    };
+    match call.lookup(name) {
+        Some(Value::Chord(chord)) => Ok(Some(chord.intervals)),
+        _ => Err(EvalError::Custom {
+            message: format!("chord symbols need the prelude chord `{}`", name),
+            span: call.span,
+        }),
+    }
+}
+
+/// A `with { ... }` modification: set the interval of a scale degree,
+/// replacing the base's or adding one, or remove the degree
+pub(crate) enum IntervalChange {
+    Set(u32, IntervalValue),
+    Remove(u32),
+}
+
+/// Typical size of a scale degree in cents: perfect degrees at their perfect
+/// size, the others halfway between their minor and major sizes
+fn degree_center(degree: u32) -> f64 {
+    const CENTERS: [f64; 7] = [0.0, 150.0, 350.0, 500.0, 700.0, 850.0, 1050.0];
+    let index = degree.max(1) - 1;
+    CENTERS[(index % 7) as usize] + 1200.0 * (index / 7) as f64
+}
+
+/// The scale degree an interval of this size most likely spells
+pub(crate) fn nearest_degree(cents: f64) -> u32 {
+    let highest = ((cents.max(0.0) / 1200.0).floor() as u32 + 2) * 7;
+    (1..=highest)
+        .min_by(|a, b| {
+            let distance = |degree| (cents - degree_center(degree)).abs();
+            distance(*a).total_cmp(&distance(*b))
+        })
+        .unwrap_or(1)
+}
+
+/// Spell each interval as a scale degree, rising with pitch. The spelling
+/// closest to the degrees' typical sizes wins, so Lydian's A4 stays a fourth
+/// next to its P5, while a lone tritone reads as a diminished fifth.
+fn interval_degrees(intervals: &[IntervalValue]) -> Vec<u32> {
+    let mut order: Vec<usize> = (0..intervals.len()).collect();
+    order.sort_by(|&a, &b| intervals[a].cents.total_cmp(&intervals[b].cents));
+
+    let highest = order
+        .last()
+        .map_or(1, |&i| nearest_degree(intervals[i].cents))
+        + intervals.len() as u32;
+    let degrees = highest as usize + 1;
+
+    // cost[i][d]: the best spelling of the lowest i + 1 intervals with the
+    // highest on degree d; ties go to the higher degree
+    let mut cost = vec![vec![f64::INFINITY; degrees]; order.len()];
+    let mut previous = vec![vec![0; degrees]; order.len()];
+    for (i, &index) in order.iter().enumerate() {
+        for degree in 1..degrees {
+            let here = (intervals[index].cents - degree_center(degree as u32)).abs();
+            if i == 0 {
+                cost[i][degree] = here;
+                continue;
+            }
+            for below in 1..degree {
+                if cost[i - 1][below] + here <= cost[i][degree] {
+                    cost[i][degree] = cost[i - 1][below] + here;
+                    previous[i][degree] = below;
+                }
+            }
+        }
+    }
+
+    let mut spelled = vec![1; intervals.len()];
+    let Some(last) = cost.last() else {
+        return spelled;
+    };
+    let mut degree = (1..degrees)
+        .rev()
+        .min_by(|&a, &b| last[a].total_cmp(&last[b]))
+        .unwrap_or(1);
+    for i in (0..order.len()).rev() {
+        spelled[order[i]] = degree as u32;
+        degree = previous[i][degree];
+    }
+    spelled
+}
+
+/// Apply `with { ... }` modifications to the intervals of a scale or chord
+pub(crate) fn modify_intervals(
+    intervals: &[IntervalValue],
+    changes: &[(IntervalChange, Span)],
+) -> Result<Vec<IntervalValue>, EvalError> {
+    let mut spelled: Vec<(u32, IntervalValue)> = interval_degrees(intervals)
+        .into_iter()
+        .zip(intervals.iter().cloned())
+        .collect();
+
+    for (change, span) in changes {
+        match change {
+            IntervalChange::Set(degree, interval) => {
+                match spelled.iter_mut().find(|(d, _)| d == degree) {
+                    Some((_, existing)) => *existing = interval.clone(),
+                    None => spelled.push((*degree, interval.clone())),
+                }
+            }
+            IntervalChange::Remove(degree) => {
+                let Some(index) = spelled.iter().position(|(d, _)| d == degree) else {
+                    return Err(EvalError::Custom {
+                        message: format!("there is no degree {} to remove", degree),
+                        span: *span,
+                    });
+                };
+                spelled.remove(index);
+            }
+        }
+    }
+
+    spelled.sort_by(|(_, a), (_, b)| a.cents.total_cmp(&b.cents));
+    Ok(spelled.into_iter().map(|(_, interval)| interval).collect())
+}
+
+/// Split a note name (`C`, `F#`, `Bb`) off the front of a symbol, giving its
+/// pitch class and the rest
+fn note_name(symbol: &str) -> Option<(i32, &str)> {
+    let mut chars = symbol.chars();
+    let mut pitch_class = match chars.next()? {
+        'C' => 0,
+        'D' => 2,
+        'E' => 4,
+        'F' => 5,
+        'G' => 7,
+        'A' => 9,
+        'B' => 11,
+        _ => return None,
+    };
+    let mut rest = chars.as_str();
+    loop {
+        let mut chars = rest.chars();
+        match chars.next() {
+            Some('#' | '♯') => pitch_class += 1,
+            Some('b' | '♭') => pitch_class -= 1,
+            _ => break,
+        }
+        rest = chars.as_str();
+    }
+    Some((pitch_class, rest))
+}
+
+/// Intervals of a chord symbol like `F#m7b5` or `C/E`, from the key's tonic
+fn chord_symbol(
+    symbol: &str,
+    key_pitch_class: i32,
+    call: &CallSite,
+) -> Result<Vec<IntervalValue>, EvalError> {
+    let unknown = || EvalError::Custom {
+        message: format!("unknown chord symbol `{}`", symbol),
+        span: call.arg(0),
+    };
+    let (chord, bass) = match symbol.split_once('/') {
+        Some((chord, bass)) => (chord, Some(bass)),
+        None => (symbol, None),
+    };
+    let (root, quality) = note_name(chord).ok_or_else(unknown)?;
+    let mut intervals = quality_intervals(quality, call)?.ok_or_else(unknown)?;
+
+    // A slash bass sounds below the chord's root
+    if let Some(bass) = bass {
+        match note_name(bass) {
+            Some((bass, "")) => {
+                let below = (bass - root).rem_euclid(12) - 12;
+                intervals.insert(0, IntervalValue::from_semitones(below));
+            }
+            _ => return Err(unknown()),
+        }
+    }
+
+    let root = (root - key_pitch_class).rem_euclid(12);
+    Ok(intervals
+        .into_iter()
+        .map(|interval| IntervalValue {
+            cents: interval.cents + root as f64 * 100.0,
+        })
+        .collect())
+}
+
+/// Lay out a chart of chords in bars: bars are separated by `|` and last a
+/// bar each, and the chords written in one bar share it
+fn chord_bars(
+    chart: &str,
+    call: &CallSite,
+    mut chord: impl FnMut(&str) -> Result<Vec<IntervalValue>, EvalError>,
+) -> Result<Value, EvalError> {
+    let bar_beats = call.beats_per_bar();
+    let mut slots = Vec::new();
+    let mut bars = 0;
+    for bar in chart.split('|') {
+        let bar: Vec<&str> = bar.split_whitespace().collect();
+        if bar.is_empty() {
+            continue;
+        }
+        bars += 1;
+        for name in &bar {
+            slots.push(SlotValue::Chord {
+                intervals: chord(name)?,
+                articulations: vec![],
+                duration_beats: Some(bar_beats / bar.len() as f64),
+                meta: NoteMeta::default(),
+            });
+        }
+    }
+
+    Ok(Value::Block(BlockValue::with_beats(
+        slots,
+        bars as f64 * bar_beats,
+    )))
+}
+
+/// The chart a chord builtin is given
+fn chart_arg<'a>(name: &str, args: &'a [Value], call: &CallSite) -> Result<&'a str, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::Custom {
+            message: format!("{} expects 1 argument (a chart of chords)", name),
+            span: call.span,
+        });
+    }
+    match &args[0] {
+        Value::String(chart) => Ok(chart),
+        other => Err(EvalError::TypeError {
+            expected: "String".to_string(),
+            found: format!("{:?}", other),
+            span: call.arg(0),
+        }),
+    }
+}
+
+/// Build a block of chords from jazz chord symbols, relative to the key
+/// Bars are separated by `|` and last a bar each; the chords of a bar share it.
+/// Usage: chords("Cmaj7 | F#m7b5 B7 | Em")
+pub fn builtin_chords(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
+    let symbols = chart_arg("chords", &args, call)?;
+    let key_pitch_class = call.key() as i32 % 12;
+    chord_bars(symbols, call, |symbol| {
+        chord_symbol(symbol, key_pitch_class, call)
+    })
+}
+
+/// Intervals of a Roman numeral chord like `ii`, `V7` or `bVII`
+///
+/// Numerals count degrees of the major scale from the key's tonic, and
+/// flats or sharps in front move them. Upper case is a major chord and
+/// lower case a minor one; a suffix picks another quality (`ii°`, `V7`,
+/// `IVmaj7`, `viiø7`).
+fn roman_numeral(numeral: &str, call: &CallSite) -> Result<Vec<IntervalValue>, EvalError> {
+    const DEGREES: [(&str, i32); 7] = [
+        ("VII", 11),
+        ("VI", 9),
+        ("V", 7),
+        ("IV", 5),
+        ("III", 4),
+        ("II", 2),
+        ("I", 0),
+    ];
+    let unknown = || EvalError::Custom {
+        message: format!("unknown Roman numeral chord `{}`", numeral),
+        span: call.arg(0),
+    };
+
+    let mut root = 0;
+    let mut rest = numeral;
+    loop {
+        let mut chars = rest.chars();
+        match chars.next() {
+            Some('b' | '♭') => root -= 1,
+            Some('#' | '♯') => root += 1,
+            _ => break,
+        }
+        rest = chars.as_str();
+    }
+    let (degree, minor, suffix) = DEGREES
+        .iter()
+        .find_map(|(upper, semitones)| {
+            if let Some(suffix) = rest.strip_prefix(upper) {
+                Some((semitones, false, suffix))
+            } else {
+                rest.strip_prefix(upper.to_lowercase().as_str())
+                    .map(|suffix| (semitones, true, suffix))
+            }
+        })
+        .ok_or_else(unknown)?;
+    root += degree;
+
+    // Lower case numerals make the chord minor unless the suffix is diminished
+    let quality = match (minor, suffix) {
+        (false, suffix) => suffix.to_string(),
+        (true, suffix) if suffix.starts_with(['°', 'o', 'ø']) => suffix.to_string(),
+        (true, "maj7") => "mMaj7".to_string(),
+        (true, suffix) => format!("m{}", suffix),
+    };
+    let intervals = quality_intervals(&quality, call)?.ok_or_else(unknown)?;
+    Ok(intervals
+        .into_iter()
+        .map(|interval| IntervalValue {
+            cents: interval.cents + root as f64 * 100.0,
+        })
+        .collect())
+}
+
+/// Build a block of chords from Roman numerals in the key
+/// Bars are separated by `|` and last a bar each; the chords of a bar share it.
+/// Usage: progression("ii7 | V7 | Imaj7") or progression("I | bVII | IV | I")
+pub fn builtin_progression(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
+    let numerals = chart_arg("progression", &args, call)?;
+    chord_bars(numerals, call, |numeral| roman_numeral(numeral, call))
+}
+
+/// How far the voices move from one chord to the next, in cents
+///
+/// Voices are paired from the bottom up when both chords have as many
+/// notes; otherwise each note is paired with the nearest note of the other
+/// chord.
+fn voice_movement(from: &[f64], to: &[f64]) -> f64 {
+    if from.len() == to.len() {
+        return from.iter().zip(to).map(|(a, b)| (a - b).abs()).sum();
+    }
+    let nearest = |cents: f64, chord: &[f64]| {
+        chord
+            .iter()
+            .map(|other| (cents - other).abs())
+            .fold(f64::INFINITY, f64::min)
+    };
+    from.iter().map(|&a| nearest(a, to)).sum::<f64>()
+        + to.iter().map(|&b| nearest(b, from)).sum::<f64>()
+}
+
+/// Voice a chord in the inversion and octave that moves least from the
+/// previous chord
+fn nearest_voicing(previous: &[f64], chord: &[f64]) -> Vec<f64> {
+    let mut voicing: Vec<f64> = chord.to_vec();
+    voicing.sort_by(f64::total_cmp);
+    let center = |chord: &[f64]| chord.iter().sum::<f64>() / chord.len() as f64;
+
+    let mut best: Option<(f64, f64, Vec<f64>)> = None;
+    for _ in 0..voicing.len() {
+        // Try the inversion in each octave around the previous chord
+        let shift = ((center(previous) - center(&voicing)) / 1200.0).round() * 1200.0;
+        for octave in [-1200.0, 0.0, 1200.0] {
+            let candidate: Vec<f64> = voicing.iter().map(|c| c + shift + octave).collect();
+            let movement = voice_movement(previous, &candidate);
+            let drift = (center(previous) - center(&candidate)).abs();
+            if best
+                .as_ref()
+                .is_none_or(|(m, d, _)| movement < m - 1e-9 || (movement < m + 1e-9 && drift < *d))
+            {
+                best = Some((movement, drift, candidate));
+            }
+        }
+        // Next inversion: the lowest note goes up an octave
+        let lowest = voicing.remove(0);
+        voicing.push(lowest + 1200.0);
+    }
+    best.map_or_else(Vec::new, |(_, _, voicing)| voicing)
+}
+
+/// Revoice a progression so each chord moves as little as possible from the
+/// one before, keeping the first chord as written
+/// Chords of a block keep their rhythm; an array of chords lasts a bar each.
+/// Usage: smooth_voicing(progression "ii7 | V7 | Imaj7")
+pub fn builtin_smooth_voicing(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::Custom {
+            message: "smooth_voicing expects 1 argument (chords)".to_string(),
+            span: call.span,
+        });
+    }
+
+    let mut block = match &args[0] {
+        Value::Block(block) => block.clone(),
+        Value::Array(chords) => {
+            let bar_beats = call.beats_per_bar();
+            let slots = chords
+                .iter()
+                .map(|chord| match chord {
+                    Value::Chord(chord) => Ok(SlotValue::Chord {
+                        intervals: chord.intervals.clone(),
+                        articulations: vec![],
+                        duration_beats: Some(bar_beats),
+                        meta: NoteMeta::default(),
+                    }),
+                    other => Err(EvalError::TypeError {
+                        expected: "Chord".to_string(),
+                        found: format!("{:?}", other),
+                        span: call.arg(0),
+                    }),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            let beats = slots.len() as f64 * bar_beats;
+            BlockValue::with_beats(slots, beats)
+        }
+        other => {
+            return Err(EvalError::TypeError {
+                expected: "Block or [Chord]".to_string(),
+                found: format!("{:?}", other),
+                span: call.arg(0),
+            })
+        }
+    };
+
+    let mut previous: Option<Vec<f64>> = None;
+    for slot in &mut block.slots {
+        if let SlotValue::Chord { intervals, .. } = slot {
+            if intervals.is_empty() {
+                continue;
+            }
+            let cents: Vec<f64> = intervals.iter().map(|i| i.cents).collect();
+            let voicing = match &previous {
+                Some(previous) => nearest_voicing(previous, &cents),
+                None => cents,
+            };
+            *intervals = voicing
+                .iter()
+                .map(|&cents| IntervalValue { cents })
+                .collect();
+            previous = Some(voicing);
+        }
+    }
+    Ok(Value::Block(block))
+}
+
+/// A scale argument, with its index for error spans
+fn scale_arg<'a>(
+    (index, value): (usize, &'a Value),
+    call: &CallSite,
+) -> Result<&'a ScaleValue, EvalError> {
+    match value {
+        Value::Scale(scale) => Ok(scale),
+        other => Err(EvalError::TypeError {
+            expected: "Scale".to_string(),
+            found: format!("{:?}", other),
+            span: call.arg(index),
+        }),
+    }
+}
+
+/// Mirror a block's pitches for negative harmony
+/// Pitches are reflected around the point halfway between the axis and the
+/// fifth above it, so with the axis on the tonic the root and fifth swap,
+/// and a major third becomes a minor third.
+/// Usage: negative_harmony(R, block) or block |> negative_harmony R
+pub fn builtin_negative_harmony(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
+    if args.len() != 2 {
+        return Err(EvalError::Custom {
+            message: "negative_harmony expects 2 arguments (axis, block)".to_string(),
+            span: call.span,
+        });
+    }
+    let (block, rest) = split_block(&args, call)?;
+    let axis = match rest[0] {
+        (_, Value::Interval(axis)) => axis.cents,
+        (index, other) => {
+            return Err(EvalError::TypeError {
+                expected: "Interval".to_string(),
+                found: format!("{:?}", other),
+                span: call.arg(index),
+            })
+        }
+    };
+
+    // Halfway between the axis and its perfect fifth
+    let mirror = axis + 350.0;
+    Ok(Value::Block(BlockValue::with_beats(
+        block
+            .slots
+            .iter()
+            .map(|slot| slot.map_cents(&|cents| 2.0 * mirror - cents))
+            .collect(),
+        block.beats,
+    )))
+}
+
+/// Move a block from one mode to another, degree by degree
+/// Each note takes the step of the target scale at the degree it has in the
+/// source scale; notes between degrees keep their distance from the degree
+/// below. Both scales need the same number of steps.
+/// Usage: map_mode(Major, Minor, block) or block |> map_mode Major Dorian
+pub fn builtin_map_mode(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
+    if args.len() != 3 {
+        return Err(EvalError::Custom {
+            message: "map_mode expects 3 arguments (from, to, block)".to_string(),
+            span: call.span,
+        });
+    }
+    let (block, rest) = split_block(&args, call)?;
+    let from = scale_arg(rest[0], call)?;
+    let to = scale_arg(rest[1], call)?;
+    if from.intervals.is_empty() || from.intervals.len() != to.intervals.len() {
+        return Err(EvalError::Custom {
+            message: format!(
+                "map_mode needs scales with the same number of steps, but {} has {} and {} has {}",
+                from.name,
+                from.intervals.len(),
+                to.name,
+                to.intervals.len()
+            ),
+            span: call.span,
+        });
+    }
+
+    let remap = |cents: f64| {
+        let octave = (cents / 1200.0).floor();
+        let in_octave = cents - octave * 1200.0;
+        // The highest degree of the source scale at or below the note
+        let degree = from
+            .intervals
+            .iter()
+            .rposition(|step| step.cents <= in_octave + 0.01)
+            .unwrap_or(0);
+        let offset = in_octave - from.intervals[degree].cents;
+        to.intervals[degree].cents + offset + octave * 1200.0
+    };
+    Ok(Value::Block(BlockValue::with_beats(
+        block
+            .slots
+            .iter()
+            .map(|slot| slot.map_cents(&remap))
+            .collect(),
+        block.beats,
+    )))
+}
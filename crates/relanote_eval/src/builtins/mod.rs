@@ -3,18 +3,167 @@
 //! This module provides native functions that are available in every relanote program.
 //! Functions are organized into categories:
 //!
+//! - `assert`: Assertions (assert_eq, assert_beats, assert_in_scale)
 //! - `block`: Block transformations (reverse, repeat, transpose, swing, etc.)
 //! - `effects`: Audio effects (reverb, volume, etc.)
 //! - `synth`: Synthesizer modifiers (voice, cutoff, resonance, etc.)
 //! - `functional`: Functional programming utilities (map, filter, fold, etc.)
+//! - `harmony`: Chord progressions and harmonic transforms (chords, progression, negative_harmony, etc.)
+//! - `text`: Text annotations (markers, lyrics)
+//! - `tuning`: Microtonal tunings (edo)
 
+pub mod assert;
 pub mod block;
 pub mod effects;
 pub mod functional;
+pub mod harmony;
 pub mod synth;
+pub mod text;
+pub mod tuning;
 
 // Re-export all builtins for convenient access
+pub use assert::*;
 pub use block::*;
 pub use effects::*;
 pub use functional::*;
+pub use harmony::*;
 pub use synth::*;
+pub use text::*;
+pub use tuning::*;
+
+use crate::value::BuiltinFn;
+
+/// Implementation of the builtin `name` of the registry
+/// ([`relanote_meta::BUILTINS`]), `None` for builtins the evaluator doesn't
+/// provide
+pub fn implementation(name: &str) -> Option<BuiltinFn> {
+    let func: BuiltinFn = match name {
+        // Block transformations
+        "reverse" => builtin_reverse,
+        "repeat" => builtin_repeat,
+        "rotate" => builtin_rotate,
+        "transpose" => builtin_transpose,
+        "modulate" => builtin_modulate,
+        "octaveUp" => builtin_octave_up,
+        "octaveDown" => builtin_octave_down,
+        "metronome" => builtin_metronome,
+        "steps" => builtin_steps,
+        "swing" => builtin_swing,
+        "double_time" => builtin_double_time,
+        "half_time" => builtin_half_time,
+        "stretch" => builtin_stretch,
+        "compress" => builtin_compress,
+        "quantize" => builtin_quantize,
+        "invert" => builtin_invert,
+        "retrograde" => builtin_retrograde,
+        "shuffle" => builtin_shuffle,
+        "slice" => builtin_slice,
+        "bars" => builtin_bars,
+        "humanize" => builtin_humanize,
+        "glide" => builtin_glide,
+
+        // Harmony
+        "chords" => builtin_chords,
+        "progression" => builtin_progression,
+        "smooth_voicing" => builtin_smooth_voicing,
+        "negative_harmony" => builtin_negative_harmony,
+        "map_mode" => builtin_map_mode,
+
+        // Text annotations
+        "mark" => builtin_mark,
+        "lyrics" => builtin_lyrics,
+
+        // Tunings
+        "edo" => builtin_edo,
+
+        // Assertions
+        "assert_eq" => builtin_assert_eq,
+        "assert_beats" => builtin_assert_beats,
+        "assert_in_scale" => builtin_assert_in_scale,
+
+        // Effects
+        "reverb" => builtin_reverb,
+        "hall_reverb" => builtin_hall_reverb,
+        "room_reverb" => builtin_room_reverb,
+        "plate_reverb" => builtin_plate_reverb,
+        "dry" => builtin_dry,
+        "volume" => builtin_volume,
+        "pan" => builtin_pan,
+        "automate" => builtin_automate,
+        "duck" => builtin_duck,
+        "delay" => builtin_delay,
+        "phaser" => builtin_phaser,
+        "distortion" => builtin_distortion,
+        "compressor" => builtin_compressor,
+        "eq" => builtin_eq,
+        "chorus_fx" => builtin_chorus_fx,
+        "width" => builtin_width,
+        "bus" => builtin_bus,
+        "send" => builtin_send,
+
+        // Distortion type constructors
+        "SoftClip" => builtin_soft_clip,
+        "HardClip" => builtin_hard_clip,
+        "Fuzz" => builtin_fuzz,
+        "BitCrush" => builtin_bitcrush,
+
+        // Synth functions
+        "voice" => builtin_voice,
+        "cutoff" => builtin_cutoff,
+        "resonance" => builtin_resonance,
+        "detune" => builtin_detune,
+        "adsr" => builtin_adsr,
+        "drum_map" => builtin_drum_map,
+        "envelope" => builtin_env,
+        "apply_env" => builtin_apply_env,
+
+        // Filter constructors
+        "LowPass" => builtin_lowpass,
+        "HighPass" => builtin_highpass,
+        "BandPass" => builtin_bandpass,
+
+        // Oscillator constructors
+        "Pulse" => builtin_pulse,
+        "Square" => builtin_square,
+        "Saw" => builtin_saw,
+        "Triangle" => builtin_triangle,
+        "Sine" => builtin_sine,
+        "Noise" => builtin_noise,
+        "PinkNoise" => builtin_pink_noise,
+        "BrownNoise" => builtin_brown_noise,
+        "sample" => builtin_sample,
+
+        // Oscillator modifiers (for multi-oscillator synths)
+        "mix" => builtin_osc_mix,
+        "octave" => builtin_osc_octave,
+        "osc_detune" => builtin_osc_detune,
+        "sample_root" => builtin_sample_root,
+        "sample_loop" => builtin_sample_loop,
+
+        // LFO constructors and modifiers
+        "PitchLfo" => builtin_pitch_lfo,
+        "CutoffLfo" => builtin_cutoff_lfo,
+        "AmpLfo" => builtin_amp_lfo,
+        "lfo_shape" => builtin_lfo_shape,
+
+        // Functional programming utilities
+        "take" => builtin_take,
+        "drop" => builtin_drop,
+        "zip" => builtin_zip,
+        "concat" => builtin_concat,
+        "len" => builtin_len,
+        "map" => builtin_map,
+        "filter" => builtin_filter,
+        "foldl" => builtin_foldl,
+        "foldr" => builtin_foldr,
+        "find" => builtin_find,
+        "any" => builtin_any,
+        "all" => builtin_all,
+        "flat_map" => builtin_flat_map,
+        "map_notes" => builtin_map_notes,
+        "filter_notes" => builtin_filter_notes,
+
+        _ => return None,
+    };
+    Some(func)
+}
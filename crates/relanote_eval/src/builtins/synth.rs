@@ -2,16 +2,17 @@
 
 use crate::error::EvalError;
 use crate::value::{
-    ADSREnvelope, FilterType, FilterValue, OscillatorValue, PartValue, SynthValue, Value, Waveform,
+    ADSREnvelope, CallSite, DrumMapValue, FilterType, FilterValue, LfoShape, LfoTarget, LfoValue,
+    OscillatorValue, PartValue, SampleValue, SynthValue, Value, Waveform,
 };
 
 /// Create an ADSR envelope value
 /// Usage: env(attack, decay, sustain, release)
-pub fn builtin_env(args: Vec<Value>) -> Result<Value, EvalError> {
+pub fn builtin_env(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
     if args.len() != 4 {
         return Err(EvalError::Custom {
             message: "env expects 4 arguments (attack, decay, sustain, release)".to_string(),
-            span: relanote_core::Span::dummy(),
+            span: call.span,
         });
     }
 
@@ -28,7 +29,7 @@ pub fn builtin_env(args: Vec<Value>) -> Result<Value, EvalError> {
     if values.len() != 4 {
         return Err(EvalError::Custom {
             message: "env requires 4 numeric values (attack, decay, sustain, release)".to_string(),
-            span: relanote_core::Span::dummy(),
+            span: call.span,
         });
     }
 
@@ -39,11 +40,11 @@ pub fn builtin_env(args: Vec<Value>) -> Result<Value, EvalError> {
 
 /// Apply a synth to a block
 /// Usage: block |> voice(synth) or voice(synth, block)
-pub fn builtin_voice(args: Vec<Value>) -> Result<Value, EvalError> {
+pub fn builtin_voice(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
     if args.len() != 2 {
         return Err(EvalError::Custom {
             message: "voice expects 2 arguments (synth, block)".to_string(),
-            span: relanote_core::Span::dummy(),
+            span: call.span,
         });
     }
 
@@ -55,58 +56,124 @@ pub fn builtin_voice(args: Vec<Value>) -> Result<Value, EvalError> {
         (Value::Part(part), Value::Synth(synth)) => {
             return Ok(Value::Part(PartValue {
                 instrument: synth.name.clone(),
-                blocks: part.blocks.clone(),
-                envelope: part.envelope.clone(),
-                reverb_level: part.reverb_level,
-                volume_level: part.volume_level,
-                delay: part.delay.clone(),
-                phaser: part.phaser.clone(),
-                distortion: part.distortion.clone(),
                 synth: Some(synth.clone()),
+                ..part.clone()
             }));
         }
         (Value::Synth(synth), Value::Part(part)) => {
             return Ok(Value::Part(PartValue {
                 instrument: synth.name.clone(),
-                blocks: part.blocks.clone(),
-                envelope: part.envelope.clone(),
-                reverb_level: part.reverb_level,
-                volume_level: part.volume_level,
-                delay: part.delay.clone(),
-                phaser: part.phaser.clone(),
-                distortion: part.distortion.clone(),
                 synth: Some(synth.clone()),
+                ..part.clone()
             }));
         }
         _ => {
             return Err(EvalError::TypeError {
                 expected: "Block/Part and Synth".to_string(),
                 found: format!("{:?}, {:?}", args[0], args[1]),
-                span: relanote_core::Span::dummy(),
+                span: call.span,
             })
         }
     };
 
+    let part = PartValue::new(synth.name.clone(), vec![block]);
     Ok(Value::Part(PartValue {
-        instrument: synth.name.clone(),
-        blocks: vec![block],
-        envelope: None,
-        reverb_level: None,
-        volume_level: None,
-        delay: None,
-        phaser: None,
-        distortion: None,
         synth: Some(synth),
+        ..part
     }))
 }
 
+/// Render a part on the percussion channel with GM drum keys
+/// Usage: part |> drum_map(key) maps every note to one key,
+///        part |> drum_map([(R, 36), (M2, 38), (M3, 42)]) maps pitches to keys
+pub fn builtin_drum_map(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
+    if args.len() != 2 {
+        return Err(EvalError::Custom {
+            message: "drum_map expects 2 arguments (key or mapping, part)".to_string(),
+            span: call.span,
+        });
+    }
+
+    // Support both argument orders
+    let (mapping, target) = match (&args[0], &args[1]) {
+        (target @ (Value::Block(_) | Value::Part(_)), mapping) => (mapping, target),
+        (mapping, target) => (mapping, target),
+    };
+
+    let drum_map = match mapping {
+        Value::Int(key) => DrumMapValue {
+            default_key: Some(drum_key(*key)?),
+            keys: vec![],
+        },
+        Value::Array(entries) => {
+            let mut keys = Vec::new();
+            for entry in entries {
+                match entry {
+                    Value::Tuple(pair) if pair.len() == 2 => match (&pair[0], &pair[1]) {
+                        (Value::Interval(interval), Value::Int(key)) => {
+                            keys.push((interval.cents, drum_key(*key)?))
+                        }
+                        _ => return Err(drum_map_entry_error(entry)),
+                    },
+                    _ => return Err(drum_map_entry_error(entry)),
+                }
+            }
+            DrumMapValue {
+                default_key: None,
+                keys,
+            }
+        }
+        _ => {
+            return Err(EvalError::TypeError {
+                expected: "Int or [(Interval, Int)]".to_string(),
+                found: format!("{:?}", mapping),
+                span: call.span,
+            })
+        }
+    };
+
+    match target {
+        Value::Block(block) => Ok(Value::Part(PartValue {
+            drum_map: Some(drum_map),
+            ..PartValue::new("Drums", vec![block.clone()])
+        })),
+        Value::Part(part) => Ok(Value::Part(PartValue {
+            drum_map: Some(drum_map),
+            ..part.clone()
+        })),
+        _ => Err(EvalError::TypeError {
+            expected: "Block or Part".to_string(),
+            found: format!("{:?}", target),
+            span: call.span,
+        }),
+    }
+}
+
+fn drum_key(key: i64) -> Result<u8, EvalError> {
+    u8::try_from(key)
+        .ok()
+        .filter(|k| *k <= 127)
+        .ok_or_else(|| EvalError::Custom {
+            message: format!("drum key must be between 0 and 127, got {}", key),
+            span: relanote_core::Span::dummy(),
+        })
+}
+
+fn drum_map_entry_error(entry: &Value) -> EvalError {
+    EvalError::TypeError {
+        expected: "(Interval, Int)".to_string(),
+        found: format!("{:?}", entry),
+        span: relanote_core::Span::dummy(),
+    }
+}
+
 /// Set filter cutoff frequency on a part
 /// Usage: part |> cutoff(freq)
-pub fn builtin_cutoff(args: Vec<Value>) -> Result<Value, EvalError> {
+pub fn builtin_cutoff(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
     if args.len() != 2 {
         return Err(EvalError::Custom {
             message: "cutoff expects 2 arguments (freq, part)".to_string(),
-            span: relanote_core::Span::dummy(),
+            span: call.span,
         });
     }
 
@@ -119,7 +186,7 @@ pub fn builtin_cutoff(args: Vec<Value>) -> Result<Value, EvalError> {
             return Err(EvalError::TypeError {
                 expected: "Part and Float/Int".to_string(),
                 found: format!("{:?}, {:?}", args[0], args[1]),
-                span: relanote_core::Span::dummy(),
+                span: call.span,
             })
         }
     };
@@ -132,25 +199,18 @@ pub fn builtin_cutoff(args: Vec<Value>) -> Result<Value, EvalError> {
     }
 
     Ok(Value::Part(PartValue {
-        instrument: part.instrument,
-        blocks: part.blocks,
-        envelope: part.envelope,
-        reverb_level: part.reverb_level,
-        volume_level: part.volume_level,
-        delay: part.delay,
-        phaser: part.phaser,
-        distortion: part.distortion,
         synth: Some(synth),
+        ..part
     }))
 }
 
 /// Set filter resonance on a part
 /// Usage: part |> resonance(q)
-pub fn builtin_resonance(args: Vec<Value>) -> Result<Value, EvalError> {
+pub fn builtin_resonance(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
     if args.len() != 2 {
         return Err(EvalError::Custom {
             message: "resonance expects 2 arguments (q, part)".to_string(),
-            span: relanote_core::Span::dummy(),
+            span: call.span,
         });
     }
 
@@ -161,7 +221,7 @@ pub fn builtin_resonance(args: Vec<Value>) -> Result<Value, EvalError> {
             return Err(EvalError::TypeError {
                 expected: "Part and Float".to_string(),
                 found: format!("{:?}, {:?}", args[0], args[1]),
-                span: relanote_core::Span::dummy(),
+                span: call.span,
             })
         }
     };
@@ -175,25 +235,18 @@ pub fn builtin_resonance(args: Vec<Value>) -> Result<Value, EvalError> {
     }
 
     Ok(Value::Part(PartValue {
-        instrument: part.instrument,
-        blocks: part.blocks,
-        envelope: part.envelope,
-        reverb_level: part.reverb_level,
-        volume_level: part.volume_level,
-        delay: part.delay,
-        phaser: part.phaser,
-        distortion: part.distortion,
         synth: Some(synth),
+        ..part
     }))
 }
 
 /// Set detune on a part
 /// Usage: part |> detune(cents)
-pub fn builtin_detune(args: Vec<Value>) -> Result<Value, EvalError> {
+pub fn builtin_detune(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
     if args.len() != 2 {
         return Err(EvalError::Custom {
             message: "detune expects 2 arguments (cents, part)".to_string(),
-            span: relanote_core::Span::dummy(),
+            span: call.span,
         });
     }
 
@@ -206,7 +259,7 @@ pub fn builtin_detune(args: Vec<Value>) -> Result<Value, EvalError> {
             return Err(EvalError::TypeError {
                 expected: "Part and Float/Int".to_string(),
                 found: format!("{:?}, {:?}", args[0], args[1]),
-                span: relanote_core::Span::dummy(),
+                span: call.span,
             })
         }
     };
@@ -215,25 +268,61 @@ pub fn builtin_detune(args: Vec<Value>) -> Result<Value, EvalError> {
     synth.detune_cents = cents;
 
     Ok(Value::Part(PartValue {
-        instrument: part.instrument,
-        blocks: part.blocks,
-        envelope: part.envelope,
-        reverb_level: part.reverb_level,
-        volume_level: part.volume_level,
-        delay: part.delay,
-        phaser: part.phaser,
-        distortion: part.distortion,
         synth: Some(synth),
+        ..part
+    }))
+}
+
+/// Apply an envelope to a block or part: a dynamics envelope fades the part
+/// in or out from its start, an ADSR envelope replaces its instrument's
+/// Usage: part |> apply_env(env(pp, ff, 4 beats)) or part |> apply_env(envelope 0.1 0.2 0.7 0.3)
+pub fn builtin_apply_env(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
+    let (envelope, part) = match args.as_slice() {
+        [envelope, Value::Part(part)] | [Value::Part(part), envelope] => (envelope, part.clone()),
+        [envelope, Value::Block(block)] | [Value::Block(block), envelope] => {
+            (envelope, PartValue::new("Envelope", vec![block.clone()]))
+        }
+        [_, _] => {
+            return Err(EvalError::TypeError {
+                expected: "Block or Part".to_string(),
+                found: format!("{:?}", args),
+                span: call.span,
+            })
+        }
+        _ => {
+            return Err(EvalError::Custom {
+                message: "apply_env expects 2 arguments (envelope, part)".to_string(),
+                span: call.span,
+            })
+        }
+    };
+
+    let mut applied = part.envelope.clone().unwrap_or_default();
+    match envelope {
+        Value::Envelope(dynamics) => applied.dynamics = Some(dynamics.clone()),
+        Value::ADSR(adsr) => applied.adsr = Some(adsr.clone()),
+        other => {
+            return Err(EvalError::TypeError {
+                expected: "Envelope".to_string(),
+                found: format!("{:?}", other),
+                span: call.span,
+            })
+        }
+    }
+
+    Ok(Value::Part(PartValue {
+        envelope: Some(applied),
+        ..part
     }))
 }
 
 /// Set ADSR envelope on a part
 /// Usage: part |> adsr(a, d, s, r)
-pub fn builtin_adsr(args: Vec<Value>) -> Result<Value, EvalError> {
+pub fn builtin_adsr(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
     if args.len() != 5 {
         return Err(EvalError::Custom {
             message: "adsr expects 5 arguments (attack, decay, sustain, release, part)".to_string(),
-            span: relanote_core::Span::dummy(),
+            span: call.span,
         });
     }
 
@@ -257,7 +346,7 @@ pub fn builtin_adsr(args: Vec<Value>) -> Result<Value, EvalError> {
     let part_idx = part_idx.ok_or_else(|| EvalError::TypeError {
         expected: "Part".to_string(),
         found: "no Part argument".to_string(),
-        span: relanote_core::Span::dummy(),
+        span: call.span,
     })?;
 
     let part = match &args[part_idx] {
@@ -276,7 +365,7 @@ pub fn builtin_adsr(args: Vec<Value>) -> Result<Value, EvalError> {
     if values.len() != 4 {
         return Err(EvalError::Custom {
             message: "adsr requires 4 numeric values (attack, decay, sustain, release)".to_string(),
-            span: relanote_core::Span::dummy(),
+            span: call.span,
         });
     }
 
@@ -284,15 +373,8 @@ pub fn builtin_adsr(args: Vec<Value>) -> Result<Value, EvalError> {
     synth.envelope = ADSREnvelope::new(values[0], values[1], values[2], values[3]);
 
     Ok(Value::Part(PartValue {
-        instrument: part.instrument,
-        blocks: part.blocks,
-        envelope: part.envelope,
-        reverb_level: part.reverb_level,
-        volume_level: part.volume_level,
-        delay: part.delay,
-        phaser: part.phaser,
-        distortion: part.distortion,
         synth: Some(synth),
+        ..part
     }))
 }
 
@@ -310,24 +392,24 @@ fn extract_number(v: &Value) -> Option<f64> {
 
 /// Create a LowPass filter value
 /// Usage: LowPass cutoff resonance
-pub fn builtin_lowpass(args: Vec<Value>) -> Result<Value, EvalError> {
+pub fn builtin_lowpass(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
     if args.len() != 2 {
         return Err(EvalError::Custom {
             message: "LowPass expects 2 arguments (cutoff, resonance)".to_string(),
-            span: relanote_core::Span::dummy(),
+            span: call.span,
         });
     }
 
     let cutoff = extract_number(&args[0]).ok_or_else(|| EvalError::TypeError {
         expected: "number".to_string(),
         found: format!("{:?}", args[0]),
-        span: relanote_core::Span::dummy(),
+        span: call.span,
     })?;
 
     let resonance = extract_number(&args[1]).ok_or_else(|| EvalError::TypeError {
         expected: "number".to_string(),
         found: format!("{:?}", args[1]),
-        span: relanote_core::Span::dummy(),
+        span: call.span,
     })?;
 
     Ok(Value::Filter(FilterValue {
@@ -339,24 +421,24 @@ pub fn builtin_lowpass(args: Vec<Value>) -> Result<Value, EvalError> {
 
 /// Create a HighPass filter value
 /// Usage: HighPass cutoff resonance
-pub fn builtin_highpass(args: Vec<Value>) -> Result<Value, EvalError> {
+pub fn builtin_highpass(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
     if args.len() != 2 {
         return Err(EvalError::Custom {
             message: "HighPass expects 2 arguments (cutoff, resonance)".to_string(),
-            span: relanote_core::Span::dummy(),
+            span: call.span,
         });
     }
 
     let cutoff = extract_number(&args[0]).ok_or_else(|| EvalError::TypeError {
         expected: "number".to_string(),
         found: format!("{:?}", args[0]),
-        span: relanote_core::Span::dummy(),
+        span: call.span,
     })?;
 
     let resonance = extract_number(&args[1]).ok_or_else(|| EvalError::TypeError {
         expected: "number".to_string(),
         found: format!("{:?}", args[1]),
-        span: relanote_core::Span::dummy(),
+        span: call.span,
     })?;
 
     Ok(Value::Filter(FilterValue {
@@ -368,24 +450,24 @@ pub fn builtin_highpass(args: Vec<Value>) -> Result<Value, EvalError> {
 
 /// Create a BandPass filter value
 /// Usage: BandPass cutoff resonance
-pub fn builtin_bandpass(args: Vec<Value>) -> Result<Value, EvalError> {
+pub fn builtin_bandpass(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
     if args.len() != 2 {
         return Err(EvalError::Custom {
             message: "BandPass expects 2 arguments (cutoff, resonance)".to_string(),
-            span: relanote_core::Span::dummy(),
+            span: call.span,
         });
     }
 
     let cutoff = extract_number(&args[0]).ok_or_else(|| EvalError::TypeError {
         expected: "number".to_string(),
         found: format!("{:?}", args[0]),
-        span: relanote_core::Span::dummy(),
+        span: call.span,
     })?;
 
     let resonance = extract_number(&args[1]).ok_or_else(|| EvalError::TypeError {
         expected: "number".to_string(),
         found: format!("{:?}", args[1]),
-        span: relanote_core::Span::dummy(),
+        span: call.span,
     })?;
 
     Ok(Value::Filter(FilterValue {
@@ -401,18 +483,18 @@ pub fn builtin_bandpass(args: Vec<Value>) -> Result<Value, EvalError> {
 
 /// Create a Pulse oscillator value with duty cycle
 /// Usage: Pulse duty
-pub fn builtin_pulse(args: Vec<Value>) -> Result<Value, EvalError> {
+pub fn builtin_pulse(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
     if args.len() != 1 {
         return Err(EvalError::Custom {
             message: "Pulse expects 1 argument (duty cycle 0.0-1.0)".to_string(),
-            span: relanote_core::Span::dummy(),
+            span: call.span,
         });
     }
 
     let duty = extract_number(&args[0]).ok_or_else(|| EvalError::TypeError {
         expected: "number".to_string(),
         found: format!("{:?}", args[0]),
-        span: relanote_core::Span::dummy(),
+        span: call.span,
     })?;
 
     Ok(Value::Oscillator(OscillatorValue {
@@ -425,7 +507,7 @@ pub fn builtin_pulse(args: Vec<Value>) -> Result<Value, EvalError> {
 
 /// Create a Square oscillator value (Pulse with 0.5 duty)
 /// Usage: Square
-pub fn builtin_square(_args: Vec<Value>) -> Result<Value, EvalError> {
+pub fn builtin_square(_args: Vec<Value>, _call: &CallSite) -> Result<Value, EvalError> {
     Ok(Value::Oscillator(OscillatorValue {
         waveform: Waveform::Square,
         mix: 1.0,
@@ -436,7 +518,7 @@ pub fn builtin_square(_args: Vec<Value>) -> Result<Value, EvalError> {
 
 /// Create a Saw oscillator value
 /// Usage: Saw
-pub fn builtin_saw(_args: Vec<Value>) -> Result<Value, EvalError> {
+pub fn builtin_saw(_args: Vec<Value>, _call: &CallSite) -> Result<Value, EvalError> {
     Ok(Value::Oscillator(OscillatorValue {
         waveform: Waveform::Saw,
         mix: 1.0,
@@ -447,7 +529,7 @@ pub fn builtin_saw(_args: Vec<Value>) -> Result<Value, EvalError> {
 
 /// Create a Triangle oscillator value
 /// Usage: Triangle
-pub fn builtin_triangle(_args: Vec<Value>) -> Result<Value, EvalError> {
+pub fn builtin_triangle(_args: Vec<Value>, _call: &CallSite) -> Result<Value, EvalError> {
     Ok(Value::Oscillator(OscillatorValue {
         waveform: Waveform::Triangle,
         mix: 1.0,
@@ -458,7 +540,7 @@ pub fn builtin_triangle(_args: Vec<Value>) -> Result<Value, EvalError> {
 
 /// Create a Sine oscillator value
 /// Usage: Sine
-pub fn builtin_sine(_args: Vec<Value>) -> Result<Value, EvalError> {
+pub fn builtin_sine(_args: Vec<Value>, _call: &CallSite) -> Result<Value, EvalError> {
     Ok(Value::Oscillator(OscillatorValue {
         waveform: Waveform::Sine,
         mix: 1.0,
@@ -469,7 +551,7 @@ pub fn builtin_sine(_args: Vec<Value>) -> Result<Value, EvalError> {
 
 /// Create a Noise oscillator value
 /// Usage: Noise
-pub fn builtin_noise(_args: Vec<Value>) -> Result<Value, EvalError> {
+pub fn builtin_noise(_args: Vec<Value>, _call: &CallSite) -> Result<Value, EvalError> {
     Ok(Value::Oscillator(OscillatorValue {
         waveform: Waveform::Noise,
         mix: 1.0,
@@ -478,17 +560,53 @@ pub fn builtin_noise(_args: Vec<Value>) -> Result<Value, EvalError> {
     }))
 }
 
+/// Create a PinkNoise oscillator value
+/// Usage: PinkNoise
+pub fn builtin_pink_noise(_args: Vec<Value>, _call: &CallSite) -> Result<Value, EvalError> {
+    Ok(Value::Oscillator(OscillatorValue::new(Waveform::PinkNoise)))
+}
+
+/// Create a BrownNoise oscillator value
+/// Usage: BrownNoise
+pub fn builtin_brown_noise(_args: Vec<Value>, _call: &CallSite) -> Result<Value, EvalError> {
+    Ok(Value::Oscillator(OscillatorValue::new(
+        Waveform::BrownNoise,
+    )))
+}
+
+/// Create an oscillator playing a WAV file
+/// Usage: sample "kick.wav"
+pub fn builtin_sample(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::Custom {
+            message: "sample expects 1 argument (WAV file path)".to_string(),
+            span: call.span,
+        });
+    }
+
+    match &args[0] {
+        Value::String(path) => Ok(Value::Oscillator(OscillatorValue::new(Waveform::Sample(
+            SampleValue::new(path.clone()),
+        )))),
+        other => Err(EvalError::TypeError {
+            expected: "String".to_string(),
+            found: format!("{:?}", other),
+            span: call.span,
+        }),
+    }
+}
+
 // ============================================
 // Oscillator modifier functions
 // ============================================
 
 /// Helper to extract an Oscillator from a Value, auto-calling Builtins if needed
-fn extract_oscillator(v: &Value) -> Option<OscillatorValue> {
+fn extract_oscillator(v: &Value, call: &CallSite) -> Option<OscillatorValue> {
     match v {
         Value::Oscillator(osc) => Some(osc.clone()),
         Value::Builtin(f) => {
             // Auto-call zero-arg builtins like Saw, Square, etc.
-            if let Ok(Value::Oscillator(osc)) = f(vec![]) {
+            if let Ok(Value::Oscillator(osc)) = f.call(vec![], call) {
                 Some(osc)
             } else {
                 None
@@ -500,16 +618,16 @@ fn extract_oscillator(v: &Value) -> Option<OscillatorValue> {
 
 /// Set the mix level for an oscillator
 /// Usage: Saw |> mix 0.5
-pub fn builtin_osc_mix(args: Vec<Value>) -> Result<Value, EvalError> {
+pub fn builtin_osc_mix(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
     if args.len() != 2 {
         return Err(EvalError::Custom {
             message: "mix expects 2 arguments (oscillator, level)".to_string(),
-            span: relanote_core::Span::dummy(),
+            span: call.span,
         });
     }
 
     // Try to extract oscillator from first arg, number from second
-    if let Some(osc) = extract_oscillator(&args[0]) {
+    if let Some(osc) = extract_oscillator(&args[0], call) {
         if let Some(level) = extract_number(&args[1]) {
             return Ok(Value::Oscillator(OscillatorValue {
                 mix: level.clamp(0.0, 1.0),
@@ -519,7 +637,7 @@ pub fn builtin_osc_mix(args: Vec<Value>) -> Result<Value, EvalError> {
     }
 
     // Try to extract oscillator from second arg, number from first
-    if let Some(osc) = extract_oscillator(&args[1]) {
+    if let Some(osc) = extract_oscillator(&args[1], call) {
         if let Some(level) = extract_number(&args[0]) {
             return Ok(Value::Oscillator(OscillatorValue {
                 mix: level.clamp(0.0, 1.0),
@@ -531,22 +649,22 @@ pub fn builtin_osc_mix(args: Vec<Value>) -> Result<Value, EvalError> {
     Err(EvalError::TypeError {
         expected: "Oscillator and number".to_string(),
         found: format!("{:?}, {:?}", args[0], args[1]),
-        span: relanote_core::Span::dummy(),
+        span: call.span,
     })
 }
 
 /// Set the octave offset for an oscillator
 /// Usage: Saw |> octave 1  (or octave -1 for one octave down)
-pub fn builtin_osc_octave(args: Vec<Value>) -> Result<Value, EvalError> {
+pub fn builtin_osc_octave(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
     if args.len() != 2 {
         return Err(EvalError::Custom {
             message: "octave expects 2 arguments (oscillator, offset)".to_string(),
-            span: relanote_core::Span::dummy(),
+            span: call.span,
         });
     }
 
     // Try first arg as oscillator, second as offset
-    if let Some(osc) = extract_oscillator(&args[0]) {
+    if let Some(osc) = extract_oscillator(&args[0], call) {
         if let Some(offset) = extract_number(&args[1]) {
             return Ok(Value::Oscillator(OscillatorValue {
                 octave_offset: (offset as i8).clamp(-4, 4),
@@ -556,7 +674,7 @@ pub fn builtin_osc_octave(args: Vec<Value>) -> Result<Value, EvalError> {
     }
 
     // Try second arg as oscillator, first as offset
-    if let Some(osc) = extract_oscillator(&args[1]) {
+    if let Some(osc) = extract_oscillator(&args[1], call) {
         if let Some(offset) = extract_number(&args[0]) {
             return Ok(Value::Oscillator(OscillatorValue {
                 octave_offset: (offset as i8).clamp(-4, 4),
@@ -568,22 +686,22 @@ pub fn builtin_osc_octave(args: Vec<Value>) -> Result<Value, EvalError> {
     Err(EvalError::TypeError {
         expected: "Oscillator and number".to_string(),
         found: format!("{:?}, {:?}", args[0], args[1]),
-        span: relanote_core::Span::dummy(),
+        span: call.span,
     })
 }
 
 /// Set the detune in cents for an oscillator
 /// Usage: Saw |> osc_detune 5  (5 cents sharp)
-pub fn builtin_osc_detune(args: Vec<Value>) -> Result<Value, EvalError> {
+pub fn builtin_osc_detune(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
     if args.len() != 2 {
         return Err(EvalError::Custom {
             message: "osc_detune expects 2 arguments (oscillator, cents)".to_string(),
-            span: relanote_core::Span::dummy(),
+            span: call.span,
         });
     }
 
     // Try first arg as oscillator, second as cents
-    if let Some(osc) = extract_oscillator(&args[0]) {
+    if let Some(osc) = extract_oscillator(&args[0], call) {
         if let Some(cents) = extract_number(&args[1]) {
             return Ok(Value::Oscillator(OscillatorValue {
                 detune_cents: cents.clamp(-100.0, 100.0),
@@ -593,7 +711,7 @@ pub fn builtin_osc_detune(args: Vec<Value>) -> Result<Value, EvalError> {
     }
 
     // Try second arg as oscillator, first as cents
-    if let Some(osc) = extract_oscillator(&args[1]) {
+    if let Some(osc) = extract_oscillator(&args[1], call) {
         if let Some(cents) = extract_number(&args[0]) {
             return Ok(Value::Oscillator(OscillatorValue {
                 detune_cents: cents.clamp(-100.0, 100.0),
@@ -605,6 +723,173 @@ pub fn builtin_osc_detune(args: Vec<Value>) -> Result<Value, EvalError> {
     Err(EvalError::TypeError {
         expected: "Oscillator and number".to_string(),
         found: format!("{:?}, {:?}", args[0], args[1]),
-        span: relanote_core::Span::dummy(),
+        span: call.span,
     })
 }
+
+/// Split a sample oscillator from the numbers that modify it, whichever
+/// side of them it is on
+fn extract_sample_args(
+    name: &str,
+    args: &[Value],
+    call: &CallSite,
+) -> Result<(OscillatorValue, SampleValue, Vec<f64>), EvalError> {
+    let (osc, numbers) = match extract_oscillator(&args[0], call) {
+        Some(osc) => (osc, &args[1..]),
+        None => match args.last().and_then(|v| extract_oscillator(v, call)) {
+            Some(osc) => (osc, &args[..args.len() - 1]),
+            None => {
+                return Err(EvalError::TypeError {
+                    expected: "Oscillator".to_string(),
+                    found: format!("{:?}", args),
+                    span: call.span,
+                })
+            }
+        },
+    };
+    let Waveform::Sample(sample) = &osc.waveform else {
+        return Err(EvalError::Custom {
+            message: format!("{} expects a sample oscillator", name),
+            span: call.span,
+        });
+    };
+    let sample = sample.clone();
+    let numbers = numbers
+        .iter()
+        .map(|v| {
+            extract_number(v).ok_or_else(|| EvalError::TypeError {
+                expected: "number".to_string(),
+                found: format!("{:?}", v),
+                span: call.span,
+            })
+        })
+        .collect::<Result<_, _>>()?;
+    Ok((osc, sample, numbers))
+}
+
+/// Set the MIDI note a sample sounds at when played as is
+/// Usage: sample "bass.wav" |> sample_root 36
+pub fn builtin_sample_root(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
+    if args.len() != 2 {
+        return Err(EvalError::Custom {
+            message: "sample_root expects 2 arguments (oscillator, MIDI note)".to_string(),
+            span: call.span,
+        });
+    }
+
+    let (osc, sample, numbers) = extract_sample_args("sample_root", &args, call)?;
+    Ok(Value::Oscillator(OscillatorValue {
+        waveform: Waveform::Sample(SampleValue {
+            root: numbers[0].clamp(0.0, 127.0),
+            ..sample
+        }),
+        ..osc
+    }))
+}
+
+/// Loop a sample between two points, in seconds into the file
+/// Usage: sample "pad.wav" |> sample_loop 0.5 2.0
+pub fn builtin_sample_loop(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
+    if args.len() != 3 {
+        return Err(EvalError::Custom {
+            message: "sample_loop expects 3 arguments (start, end, oscillator)".to_string(),
+            span: call.span,
+        });
+    }
+
+    let (osc, sample, numbers) = extract_sample_args("sample_loop", &args, call)?;
+    let (start, end) = (numbers[0].max(0.0), numbers[1].max(0.0));
+    if end <= start {
+        return Err(EvalError::Custom {
+            message: "sample_loop's end must come after its start".to_string(),
+            span: call.span,
+        });
+    }
+    Ok(Value::Oscillator(OscillatorValue {
+        waveform: Waveform::Sample(SampleValue {
+            loop_points: Some((start, end)),
+            ..sample
+        }),
+        ..osc
+    }))
+}
+
+// ============================================
+// LFO constructors and modifiers
+// ============================================
+
+/// Create an LFO value from its rate and depth
+fn lfo(name: &str, target: LfoTarget, args: &[Value], call: &CallSite) -> Result<Value, EvalError> {
+    if args.len() != 2 {
+        return Err(EvalError::Custom {
+            message: format!("{} expects 2 arguments (rate in Hz, depth)", name),
+            span: call.span,
+        });
+    }
+
+    match (extract_number(&args[0]), extract_number(&args[1])) {
+        (Some(rate), Some(depth)) => Ok(Value::Lfo(LfoValue::new(target, rate, depth))),
+        _ => Err(EvalError::TypeError {
+            expected: "numbers".to_string(),
+            found: format!("{:?}, {:?}", args[0], args[1]),
+            span: call.span,
+        }),
+    }
+}
+
+/// Create an LFO bending the pitch, its depth in semitones
+/// Usage: PitchLfo rate depth
+pub fn builtin_pitch_lfo(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
+    lfo("PitchLfo", LfoTarget::Pitch, &args, call)
+}
+
+/// Create an LFO sweeping the filter cutoff, its depth in Hz
+/// Usage: CutoffLfo rate depth
+pub fn builtin_cutoff_lfo(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
+    lfo("CutoffLfo", LfoTarget::Cutoff, &args, call)
+}
+
+/// Create an LFO swelling the amplitude, its depth from 0.0 to 1.0
+/// Usage: AmpLfo rate depth
+pub fn builtin_amp_lfo(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
+    lfo("AmpLfo", LfoTarget::Amplitude, &args, call)
+}
+
+/// Set the shape of an LFO's wave from an oscillator (Sine, Triangle,
+/// Square or Saw)
+/// Usage: PitchLfo 5 0.2 |> lfo_shape Triangle
+pub fn builtin_lfo_shape(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
+    if args.len() != 2 {
+        return Err(EvalError::Custom {
+            message: "lfo_shape expects 2 arguments (shape, LFO)".to_string(),
+            span: call.span,
+        });
+    }
+
+    let (shape, lfo) = match (&args[0], &args[1]) {
+        (shape, Value::Lfo(lfo)) | (Value::Lfo(lfo), shape) => (shape, lfo),
+        _ => {
+            return Err(EvalError::TypeError {
+                expected: "shape and LFO".to_string(),
+                found: format!("{:?}, {:?}", args[0], args[1]),
+                span: call.span,
+            })
+        }
+    };
+    let shape = match extract_oscillator(shape, call).map(|osc| osc.waveform) {
+        Some(Waveform::Sine) => LfoShape::Sine,
+        Some(Waveform::Triangle) => LfoShape::Triangle,
+        Some(Waveform::Square) => LfoShape::Square,
+        Some(Waveform::Saw) => LfoShape::Saw,
+        _ => {
+            return Err(EvalError::Custom {
+                message: "lfo_shape expects Sine, Triangle, Square or Saw".to_string(),
+                span: call.span,
+            })
+        }
+    };
+    Ok(Value::Lfo(LfoValue {
+        shape,
+        ..lfo.clone()
+    }))
+}
@@ -0,0 +1,126 @@
+//! Text annotation builtins (markers and lyrics)
+
+use crate::error::EvalError;
+use crate::value::{BlockValue, CallSite, NoteMeta, PartValue, SlotValue, Value};
+
+/// Place a marker at the start of a block or part (shown by DAWs as song structure)
+/// The marker is attached to the first note or chord.
+/// Usage: mark("Chorus", block) or block |> mark("Chorus")
+pub fn builtin_mark(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
+    if args.len() != 2 {
+        return Err(EvalError::Custom {
+            message: "mark expects 2 arguments (text, block/part)".to_string(),
+            span: call.span,
+        });
+    }
+
+    let (text, target) = match (&args[0], &args[1]) {
+        (Value::String(text), target) | (target, Value::String(text)) => (text, target),
+        _ => {
+            return Err(EvalError::TypeError {
+                expected: "String and Block/Part".to_string(),
+                found: format!("{:?}, {:?}", args[0], args[1]),
+                span: call.span,
+            })
+        }
+    };
+
+    let mut text = Some(text.clone());
+    annotate(target, &mut |meta| {
+        if let Some(text) = text.take() {
+            meta.marker = Some(text);
+        }
+    })
+}
+
+/// Attach lyric syllables to successive notes and chords
+/// Syllables are given as an array of strings or a single whitespace-separated string.
+/// Usage: lyrics("Hap- py birth- day", block) or block |> lyrics(["Hap-", "py"])
+pub fn builtin_lyrics(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
+    if args.len() != 2 {
+        return Err(EvalError::Custom {
+            message: "lyrics expects 2 arguments (syllables, block/part)".to_string(),
+            span: call.span,
+        });
+    }
+
+    // Support both argument orders
+    let (syllables, target, syllables_arg) = match (&args[0], &args[1]) {
+        (target @ (Value::Block(_) | Value::Part(_)), syllables) => (syllables, target, 1),
+        (syllables, target) => (syllables, target, 0),
+    };
+
+    let syllables: Vec<String> = match syllables {
+        Value::String(text) => text.split_whitespace().map(str::to_string).collect(),
+        Value::Array(items) => items
+            .iter()
+            .map(|item| match item {
+                Value::String(syllable) => Ok(syllable.clone()),
+                _ => Err(EvalError::TypeError {
+                    expected: "String".to_string(),
+                    found: format!("{:?}", item),
+                    span: call.arg(syllables_arg),
+                }),
+            })
+            .collect::<Result<_, _>>()?,
+        _ => {
+            return Err(EvalError::TypeError {
+                expected: "String or [String]".to_string(),
+                found: format!("{:?}", syllables),
+                span: call.arg(syllables_arg),
+            })
+        }
+    };
+
+    let mut syllables = syllables.into_iter();
+    annotate(target, &mut |meta| {
+        if let Some(syllable) = syllables.next() {
+            meta.lyric = Some(syllable);
+        }
+    })
+}
+
+/// Update the metadata of every note and chord of a block or part, in order
+fn annotate(target: &Value, f: &mut dyn FnMut(&mut NoteMeta)) -> Result<Value, EvalError> {
+    match target {
+        Value::Block(block) => Ok(Value::Block(annotate_block(block, f))),
+        Value::Part(part) => Ok(Value::Part(PartValue {
+            blocks: part
+                .blocks
+                .iter()
+                .map(|block| annotate_block(block, f))
+                .collect(),
+            ..part.clone()
+        })),
+        _ => Err(EvalError::TypeError {
+            expected: "Block or Part".to_string(),
+            found: format!("{:?}", target),
+            span: relanote_core::Span::dummy(),
+        }),
+    }
+}
+
+fn annotate_block(block: &BlockValue, f: &mut dyn FnMut(&mut NoteMeta)) -> BlockValue {
+    let mut block = block.clone();
+    for slot in &mut block.slots {
+        annotate_slot(slot, f);
+    }
+    block
+}
+
+fn annotate_slot(slot: &mut SlotValue, f: &mut dyn FnMut(&mut NoteMeta)) {
+    match slot {
+        SlotValue::Note { meta, .. } | SlotValue::Chord { meta, .. } => f(meta),
+        SlotValue::Tuplet { slots, .. } => {
+            for slot in slots {
+                annotate_slot(slot, f);
+            }
+        }
+        SlotValue::Overlay { voices } => {
+            for slot in voices.iter_mut().flat_map(|voice| &mut voice.slots) {
+                annotate_slot(slot, f);
+            }
+        }
+        SlotValue::Rest { .. } => {}
+    }
+}
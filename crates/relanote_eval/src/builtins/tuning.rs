@@ -0,0 +1,47 @@
+//! Tuning builtins (equal divisions of the octave)
+
+use crate::error::EvalError;
+use crate::value::{CallSite, IntervalValue, ScaleValue, Value};
+
+/// Largest number of steps `edo` accepts
+const MAX_EDO_STEPS: i64 = 1200;
+
+/// Build a scale that divides the octave into equal steps
+/// Usage: edo(19) -> a scale of 19 steps of 1200/19 cents each
+pub fn builtin_edo(args: Vec<Value>, call: &CallSite) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::Custom {
+            message: "edo expects 1 argument (number of steps)".to_string(),
+            span: call.span,
+        });
+    }
+
+    let steps = match &args[0] {
+        Value::Int(steps) => *steps,
+        other => {
+            return Err(EvalError::TypeError {
+                expected: "Int".to_string(),
+                found: format!("{:?}", other),
+                span: call.arg(0),
+            })
+        }
+    };
+
+    if !(1..=MAX_EDO_STEPS).contains(&steps) {
+        return Err(EvalError::Custom {
+            message: format!(
+                "edo needs between 1 and {} steps, got {}",
+                MAX_EDO_STEPS, steps
+            ),
+            span: call.arg(0),
+        });
+    }
+
+    let step = 1200.0 / steps as f64;
+    Ok(Value::Scale(ScaleValue {
+        name: format!("{}-EDO", steps),
+        intervals: (0..steps)
+            .map(|i| IntervalValue::from_cents(i as f64 * step))
+            .collect(),
+    }))
+}
@@ -4,9 +4,9 @@ use std::cell::RefCell;
 use std::collections::HashMap;
 use std::rc::Rc;
 
-use relanote_core::InternedStr;
+use relanote_core::{intern, InternedStr};
 
-use crate::value::Value;
+use crate::value::{Builtin, BuiltinFn, Value};
 
 /// Evaluation environment
 #[derive(Clone, Debug)]
@@ -41,6 +41,16 @@ impl Env {
         self.bindings.insert(name, value);
     }
 
+    /// Remove a binding of this environment
+    pub fn unbind(&mut self, name: &InternedStr) {
+        self.bindings.remove(name);
+    }
+
+    /// Bind a builtin function under its name
+    pub fn bind_builtin(&mut self, name: &'static str, func: BuiltinFn) {
+        self.bind(intern(name), Value::Builtin(Builtin::new(name, func)));
+    }
+
     /// Get all bindings in this environment (not including parent)
     pub fn all_bindings(&self) -> Vec<(InternedStr, Value)> {
         self.bindings.iter().map(|(k, v)| (*k, v.clone())).collect()
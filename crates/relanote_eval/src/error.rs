@@ -1,6 +1,6 @@
 //! Evaluation errors
 
-use relanote_core::Span;
+use relanote_core::{Diagnostic, Span};
 use thiserror::Error;
 
 #[derive(Debug, Error, Clone)]
@@ -18,8 +18,15 @@ pub enum EvalError {
     #[error("division by zero")]
     DivisionByZero { span: Span },
 
-    #[error("index out of bounds: {index} in array of length {len}")]
-    IndexOutOfBounds { index: i64, len: usize, span: Span },
+    /// `index` as written, where the first element is at `first` (1 for
+    /// scale degrees, 0 otherwise)
+    #[error("index {index} out of range {}", index_range(*first, *len))]
+    IndexOutOfBounds {
+        index: i64,
+        first: i64,
+        len: usize,
+        span: Span,
+    },
 
     #[error("not a function")]
     NotAFunction { span: Span },
@@ -36,27 +43,81 @@ pub enum EvalError {
         module: String,
         path: String,
         reason: String,
+        span: Span,
+    },
+
+    #[error("`{name}` is private to module `{module}`")]
+    PrivateBinding {
+        module: String,
+        name: String,
+        span: Span,
     },
 
     #[error("circular module dependency: {module}")]
-    CircularModuleDependency { module: String },
+    CircularModuleDependency { module: String, span: Span },
 
     #[error("{message}")]
     Custom { message: String, span: Span },
 }
 
+/// The indices of `len` elements starting at `first`, e.g. "1..=7"
+fn index_range(first: i64, len: usize) -> String {
+    match len {
+        0 => "(there are no elements)".to_string(),
+        len => format!("{}..={}", first, first + len as i64 - 1),
+    }
+}
+
 impl EvalError {
-    pub fn span(&self) -> Option<Span> {
+    pub fn span(&self) -> Span {
         match self {
-            EvalError::UndefinedVariable { span, .. } => Some(*span),
-            EvalError::TypeError { span, .. } => Some(*span),
-            EvalError::DivisionByZero { span } => Some(*span),
-            EvalError::IndexOutOfBounds { span, .. } => Some(*span),
-            EvalError::NotAFunction { span } => Some(*span),
-            EvalError::WrongArity { span, .. } => Some(*span),
-            EvalError::ModuleNotFound { .. } => None,
-            EvalError::CircularModuleDependency { .. } => None,
-            EvalError::Custom { span, .. } => Some(*span),
+            EvalError::UndefinedVariable { span, .. }
+            | EvalError::TypeError { span, .. }
+            | EvalError::DivisionByZero { span }
+            | EvalError::IndexOutOfBounds { span, .. }
+            | EvalError::NotAFunction { span }
+            | EvalError::WrongArity { span, .. }
+            | EvalError::ModuleNotFound { span, .. }
+            | EvalError::PrivateBinding { span, .. }
+            | EvalError::CircularModuleDependency { span, .. }
+            | EvalError::Custom { span, .. } => *span,
         }
     }
+
+    fn span_mut(&mut self) -> &mut Span {
+        match self {
+            EvalError::UndefinedVariable { span, .. }
+            | EvalError::TypeError { span, .. }
+            | EvalError::DivisionByZero { span }
+            | EvalError::IndexOutOfBounds { span, .. }
+            | EvalError::NotAFunction { span }
+            | EvalError::WrongArity { span, .. }
+            | EvalError::ModuleNotFound { span, .. }
+            | EvalError::PrivateBinding { span, .. }
+            | EvalError::CircularModuleDependency { span, .. }
+            | EvalError::Custom { span, .. } => span,
+        }
+    }
+
+    /// Locate an error raised without a location (such as one from a builtin
+    /// function) at `span`
+    pub fn or_span(mut self, span: Span) -> Self {
+        let own = self.span_mut();
+        if *own == Span::dummy() {
+            *own = span;
+        }
+        self
+    }
+
+    /// Move the error to `span`
+    pub fn at(mut self, span: Span) -> Self {
+        *self.span_mut() = span;
+        self
+    }
+
+    /// The error as a diagnostic, for reporting alongside parse and type
+    /// errors
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        Diagnostic::error(self.to_string(), self.span())
+    }
 }
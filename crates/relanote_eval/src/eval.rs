@@ -1,18 +1,24 @@
 //! Main evaluation logic
 
 use std::cell::RefCell;
-use std::collections::HashMap;
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 
 use relanote_ast::*;
-use relanote_core::{intern, Spanned};
+use relanote_core::{intern, Diagnostic, DiagnosticCode, InternedStr, Span, Spanned};
+use relanote_resolver::{FileProvider, ModuleLoader, ResolveError, ResolvedPackage};
+use relanote_types::{place_arguments, Argument};
 
 use crate::builtins::*;
 use crate::env::Env;
 use crate::error::EvalError;
+use crate::patch::{Plan, ProgramDiff};
 use crate::value::*;
 
+/// Largest number of elements a range makes
+const MAX_RANGE_LEN: i64 = 1_000_000;
+
 /// Source of a module (file or virtual/embedded)
 enum ModuleSource {
     /// File-based module
@@ -21,42 +27,13 @@ enum ModuleSource {
     Virtual(String),
 }
 
-/// Helper: combine all synth modules into one string
-fn all_synths() -> String {
-    use relanote_stdlib::prelude::*;
-    format!(
-        "{}\n{}\n{}\n{}\n{}\n{}\n{}\n{}\n{}\n{}\n{}",
-        SYNTHS_BASIC,
-        SYNTHS_PIANO,
-        SYNTHS_BASS,
-        SYNTHS_BRASS,
-        SYNTHS_LEADS,
-        SYNTHS_PADS,
-        SYNTHS_PLUCK,
-        SYNTHS_DRUMS,
-        SYNTHS_PERCUSSION,
-        SYNTHS_RETRO,
-        SYNTHS_CLAP
-    )
-}
-
-/// Helper: combine all effect modules into one string
-fn all_effects() -> String {
-    use relanote_stdlib::prelude::*;
-    format!(
-        "{}\n{}\n{}\n{}",
-        EFFECTS_REVERB,
-        EFFECTS_DELAY,
-        EFFECTS_PHASER,
-        EFFECTS_DISTORTION
-    )
-}
-
 /// Module registry to track loaded modules
 #[derive(Default)]
 pub struct ModuleRegistry {
     /// Loaded modules: module path -> module environment
     modules: HashMap<String, Rc<RefCell<Env>>>,
+    /// Names each loaded module exports
+    exports: HashMap<String, HashSet<InternedStr>>,
     /// Currently loading modules (for circular dependency detection)
     loading: Vec<String>,
 }
@@ -81,9 +58,17 @@ impl ModuleRegistry {
         self.loading.retain(|p| p != path);
     }
 
-    /// Register a loaded module
-    pub fn register(&mut self, path: &str, env: Rc<RefCell<Env>>) {
+    /// Register a loaded module and the names it exports
+    pub fn register(&mut self, path: &str, env: Rc<RefCell<Env>>, exports: HashSet<InternedStr>) {
         self.modules.insert(path.to_string(), env);
+        self.exports.insert(path.to_string(), exports);
+    }
+
+    /// Check if a loaded module exports a name
+    pub fn is_exported(&self, path: &str, name: &InternedStr) -> bool {
+        self.exports
+            .get(path)
+            .is_some_and(|exports| exports.contains(name))
     }
 
     /// Get a loaded module
@@ -97,8 +82,20 @@ pub struct Evaluator {
     env: Rc<RefCell<Env>>,
     /// Module registry for tracking loaded modules
     modules: ModuleRegistry,
-    /// Base directory for module resolution
+    /// Base directory for module resolution and sample paths
     base_dir: Option<PathBuf>,
+    /// Finds and reads module files, the way the resolver does
+    loader: ModuleLoader,
+    /// Problems found while evaluating that don't stop it
+    warnings: Vec<Diagnostic>,
+    /// Scale of the key's mode, for `in key` after `set key = D minor`
+    key_scale: Option<ScaleValue>,
+    /// Title, composer and copyright from `meta { ... }`
+    metadata: SongMetadata,
+    /// Songs declared with `render`, in order
+    render_targets: Vec<RenderTarget>,
+    /// Names the builtins and the prelude bind, which a patch can't unbind
+    prelude_names: HashSet<InternedStr>,
 }
 
 impl Evaluator {
@@ -107,98 +104,84 @@ impl Evaluator {
     }
 
     pub fn with_base_dir(base_dir: Option<PathBuf>) -> Self {
-        let env = Rc::new(RefCell::new(Env::new()));
-
-        // Add primitive builtins
-        {
-            let mut e = env.borrow_mut();
-            // Block transformations
-            e.bind(intern("reverse"), Value::Builtin(builtin_reverse));
-            e.bind(intern("repeat"), Value::Builtin(builtin_repeat));
-            e.bind(intern("rotate"), Value::Builtin(builtin_rotate));
-            e.bind(intern("transpose"), Value::Builtin(builtin_transpose));
-            e.bind(intern("octaveUp"), Value::Builtin(builtin_octave_up));
-            e.bind(intern("octaveDown"), Value::Builtin(builtin_octave_down));
-            e.bind(intern("metronome"), Value::Builtin(builtin_metronome));
-            e.bind(intern("swing"), Value::Builtin(builtin_swing));
-            e.bind(intern("double_time"), Value::Builtin(builtin_double_time));
-
-            // Effects
-            e.bind(intern("reverb"), Value::Builtin(builtin_reverb));
-            e.bind(intern("hall_reverb"), Value::Builtin(builtin_hall_reverb));
-            e.bind(intern("room_reverb"), Value::Builtin(builtin_room_reverb));
-            e.bind(intern("plate_reverb"), Value::Builtin(builtin_plate_reverb));
-            e.bind(intern("dry"), Value::Builtin(builtin_dry));
-            e.bind(intern("volume"), Value::Builtin(builtin_volume));
-            e.bind(intern("delay"), Value::Builtin(builtin_delay));
-            e.bind(intern("phaser"), Value::Builtin(builtin_phaser));
-            e.bind(intern("distortion"), Value::Builtin(builtin_distortion));
-
-            // Distortion type constructors
-            e.bind(intern("SoftClip"), Value::Builtin(builtin_soft_clip));
-            e.bind(intern("HardClip"), Value::Builtin(builtin_hard_clip));
-            e.bind(intern("Fuzz"), Value::Builtin(builtin_fuzz));
-            e.bind(intern("BitCrush"), Value::Builtin(builtin_bitcrush));
-
-            // Synth functions
-            e.bind(intern("voice"), Value::Builtin(builtin_voice));
-            e.bind(intern("cutoff"), Value::Builtin(builtin_cutoff));
-            e.bind(intern("resonance"), Value::Builtin(builtin_resonance));
-            e.bind(intern("detune"), Value::Builtin(builtin_detune));
-            e.bind(intern("adsr"), Value::Builtin(builtin_adsr));
-            e.bind(intern("envelope"), Value::Builtin(builtin_env));
-
-            // Filter constructors
-            e.bind(intern("LowPass"), Value::Builtin(builtin_lowpass));
-            e.bind(intern("HighPass"), Value::Builtin(builtin_highpass));
-            e.bind(intern("BandPass"), Value::Builtin(builtin_bandpass));
-
-            // Oscillator constructors
-            e.bind(intern("Pulse"), Value::Builtin(builtin_pulse));
-            e.bind(intern("Square"), Value::Builtin(builtin_square));
-            e.bind(intern("Saw"), Value::Builtin(builtin_saw));
-            e.bind(intern("Triangle"), Value::Builtin(builtin_triangle));
-            e.bind(intern("Sine"), Value::Builtin(builtin_sine));
-            e.bind(intern("Noise"), Value::Builtin(builtin_noise));
-
-            // Oscillator modifiers (for multi-oscillator synths)
-            e.bind(intern("mix"), Value::Builtin(builtin_osc_mix));
-            e.bind(intern("octave"), Value::Builtin(builtin_osc_octave));
-            e.bind(intern("osc_detune"), Value::Builtin(builtin_osc_detune));
-
-            // Functional programming utilities
-            e.bind(intern("take"), Value::Builtin(builtin_take));
-            e.bind(intern("drop"), Value::Builtin(builtin_drop));
-            e.bind(intern("zip"), Value::Builtin(builtin_zip));
-            e.bind(intern("concat"), Value::Builtin(builtin_concat));
-            e.bind(intern("len"), Value::Builtin(builtin_len));
-            e.bind(intern("map"), Value::Builtin(builtin_map));
-            e.bind(intern("filter"), Value::Builtin(builtin_filter));
-            e.bind(intern("foldl"), Value::Builtin(builtin_foldl));
-            e.bind(intern("foldr"), Value::Builtin(builtin_foldr));
-            e.bind(intern("find"), Value::Builtin(builtin_find));
-            e.bind(intern("any"), Value::Builtin(builtin_any));
-            e.bind(intern("all"), Value::Builtin(builtin_all));
-            e.bind(intern("flat_map"), Value::Builtin(builtin_flat_map));
-        }
-
         let mut evaluator = Self {
-            env,
+            env: Rc::new(RefCell::new(Env::new())),
             modules: ModuleRegistry::new(),
+            loader: ModuleLoader::new(base_dir.clone().unwrap_or_else(|| PathBuf::from("."))),
             base_dir,
+            warnings: Vec::new(),
+            key_scale: None,
+            metadata: SongMetadata::default(),
+            render_targets: Vec::new(),
+            prelude_names: HashSet::new(),
         };
+        evaluator.reset();
+        evaluator
+    }
+
+    /// Start over from an environment with only the builtins and the prelude
+    fn reset(&mut self) {
+        // Bind the registry's builtins to their implementations
+        let mut env = Env::new();
+        for builtin in relanote_meta::BUILTINS {
+            if let Some(func) = implementation(builtin.name) {
+                env.bind_builtin(builtin.name, func);
+            }
+        }
+        self.env = Rc::new(RefCell::new(env));
+        self.warnings.clear();
+        self.key_scale = None;
+        self.metadata = SongMetadata::default();
+        self.render_targets.clear();
 
         // Load stdlib prelude (scales, chords, synth presets)
-        evaluator.load_prelude();
+        self.load_prelude();
+        self.prelude_names = self
+            .env
+            .borrow()
+            .all_bindings()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+    }
 
-        evaluator
+    /// Read module files from `files` instead of the disk
+    pub fn set_file_provider(&mut self, files: Rc<dyn FileProvider>) {
+        self.loader.set_file_provider(files);
     }
 
     /// Set the base directory for module resolution
     pub fn set_base_dir(&mut self, dir: PathBuf) {
+        self.loader.set_root(dir.clone());
         self.base_dir = Some(dir);
     }
 
+    /// Make sample paths relative to the song's directory rather than the
+    /// working directory
+    fn resolve_samples(&self, oscillators: &mut [OscillatorValue]) {
+        let Some(base_dir) = &self.base_dir else {
+            return;
+        };
+        for osc in oscillators {
+            if let Waveform::Sample(sample) = &mut osc.waveform {
+                if Path::new(&sample.path).is_relative() {
+                    sample.path = base_dir.join(&sample.path).to_string_lossy().into_owned();
+                }
+            }
+        }
+    }
+
+    /// Add a directory to search for modules not found in the base directory
+    pub fn add_search_path(&mut self, dir: PathBuf) {
+        self.loader.add_search_path(dir);
+    }
+
+    /// Make a package's modules available: `use name::item` reads its entry
+    /// point, `use name::module::item` a file in its directory
+    pub fn add_package(&mut self, package: ResolvedPackage) {
+        self.loader.add_package(package);
+    }
+
     /// Load the standard library prelude
     fn load_prelude(&mut self) {
         use relanote_stdlib::prelude::PRELUDE;
@@ -209,7 +192,10 @@ impl Evaluator {
     }
 
     /// Load a module by name
-    fn load_module(&mut self, name: &str) -> Result<(), EvalError> {
+    ///
+    /// Errors are reported at `span`, the declaration that loads the module,
+    /// since spans inside the module refer to another file.
+    fn load_module(&mut self, name: &str, span: Span) -> Result<(), EvalError> {
         // Check if already loaded
         if self.modules.get(name).is_some() {
             return Ok(());
@@ -219,20 +205,34 @@ impl Evaluator {
         if self.modules.is_loading(name) {
             return Err(EvalError::CircularModuleDependency {
                 module: name.to_string(),
+                span,
             });
         }
 
         // Resolve module source (file or virtual)
-        let module_source = self.resolve_module_source(name)?;
+        let module_source = self
+            .resolve_module_source(name)
+            .map_err(|e| e.or_span(span))?;
 
         // Get source code
         let source = match &module_source {
             ModuleSource::File(path) => {
-                std::fs::read_to_string(path).map_err(|e| EvalError::ModuleNotFound {
-                    module: name.to_string(),
-                    path: path.display().to_string(),
-                    reason: e.to_string(),
-                })?
+                let source_id = self.loader.load(path).map_err(|e| {
+                    let reason = match e {
+                        ResolveError::IoError { source, .. } => source.to_string(),
+                        e => e.to_string(),
+                    };
+                    EvalError::ModuleNotFound {
+                        module: name.to_string(),
+                        path: path.display().to_string(),
+                        reason,
+                        span,
+                    }
+                })?;
+                self.loader
+                    .source_db()
+                    .get(source_id)
+                    .map_or_else(String::new, |source| source.content.clone())
             }
             ModuleSource::Virtual(content) => content.clone(),
         };
@@ -245,19 +245,27 @@ impl Evaluator {
         let old_env = self.env.clone();
         self.env = module_env.clone();
 
-        // Parse and evaluate the module
+        // Parse and evaluate the module; only the program renders, so the
+        // module's render items are left out
         let (program, _diagnostics) = relanote_parser::parse(&source);
-        let result = self.eval_program(&program);
+        let render_targets = std::mem::take(&mut self.render_targets);
+        let result = self.eval_program(&program).map_err(|e| e.at(span));
 
         // Restore environment
         self.env = old_env;
+        self.render_targets = render_targets;
 
         // Mark as finished loading
         self.modules.finish_loading(name);
 
         // Register module if successful
         if result.is_ok() {
-            self.modules.register(name, module_env);
+            let exports = program
+                .exported_names()
+                .into_iter()
+                .map(|ident| ident.name)
+                .collect();
+            self.modules.register(name, module_env, exports);
         }
 
         result.map(|_| ())
@@ -266,67 +274,24 @@ impl Evaluator {
     /// Resolve module source (virtual stdlib or file-based)
     fn resolve_module_source(&self, name: &str) -> Result<ModuleSource, EvalError> {
         // First check for virtual stdlib modules
-        if let Some(source) = self.resolve_stdlib_module(name) {
+        if let Some(source) = relanote_stdlib::module_source(name) {
             return Ok(ModuleSource::Virtual(source));
         }
 
-        // Fall back to file-based resolution
-        let base_dir = self.base_dir.clone().unwrap_or_else(|| PathBuf::from("."));
-        let module_file = format!("{}.rela", name.replace("::", "/"));
-        let path = base_dir.join(&module_file);
-
-        if path.exists() {
-            Ok(ModuleSource::File(path))
-        } else {
-            Err(EvalError::ModuleNotFound {
+        // Then packages and files, found the way the resolver finds them
+        self.loader
+            .resolve_path(name)
+            .map(ModuleSource::File)
+            .ok_or_else(|| EvalError::ModuleNotFound {
                 module: name.to_string(),
-                path: path.display().to_string(),
-                reason: "file does not exist".to_string(),
+                path: format!("{}.rela", name.replace("::", "/")),
+                reason: "no such file in its package or the module search paths".to_string(),
+                span: Span::dummy(),
             })
-        }
-    }
-
-    /// Resolve stdlib virtual module by path
-    fn resolve_stdlib_module(&self, path: &str) -> Option<String> {
-        use relanote_stdlib::prelude::*;
-
-        // Strip optional "std::" prefix
-        let path = path.strip_prefix("std::").unwrap_or(path);
-
-        match path {
-            // Core
-            "scales" => Some(SCALES.to_string()),
-            "chords" => Some(CHORDS.to_string()),
-
-            // Synths - hierarchical paths
-            "synths::basic" => Some(SYNTHS_BASIC.to_string()),
-            "synths::bass" => Some(SYNTHS_BASS.to_string()),
-            "synths::brass" => Some(SYNTHS_BRASS.to_string()),
-            "synths::leads" => Some(SYNTHS_LEADS.to_string()),
-            "synths::pads" => Some(SYNTHS_PADS.to_string()),
-            "synths::piano" => Some(SYNTHS_PIANO.to_string()),
-            "synths::pluck" => Some(SYNTHS_PLUCK.to_string()),
-            "synths::drums" => Some(SYNTHS_DRUMS.to_string()),
-            "synths::percussion" => Some(SYNTHS_PERCUSSION.to_string()),
-            "synths::retro" => Some(SYNTHS_RETRO.to_string()),
-            "synths::clap" => Some(SYNTHS_CLAP.to_string()),
-
-            // Effects - hierarchical paths
-            "effects::reverb" => Some(EFFECTS_REVERB.to_string()),
-            "effects::delay" => Some(EFFECTS_DELAY.to_string()),
-            "effects::phaser" => Some(EFFECTS_PHASER.to_string()),
-            "effects::distortion" => Some(EFFECTS_DISTORTION.to_string()),
-
-            // Parent modules (all synths/effects combined)
-            "synths" => Some(all_synths()),
-            "effects" => Some(all_effects()),
-
-            _ => None,
-        }
     }
 
     /// Evaluate a use declaration
-    fn eval_use(&mut self, use_decl: &UseDecl) -> Result<(), EvalError> {
+    fn eval_use(&mut self, use_decl: &UseDecl, span: Span) -> Result<(), EvalError> {
         let segments: Vec<String> = use_decl
             .path
             .segments
@@ -338,26 +303,10 @@ impl Evaluator {
             return Ok(());
         }
 
-        // Build module path from segments
-        // For `use synths::bass::*`, module_name = "synths::bass"
-        // For `use synths::bass::AcidBass`, module_name = "synths::bass"
-        let module_name = match &use_decl.path.kind {
-            UseKind::Glob | UseKind::Group(_) => {
-                // All segments form the module path
-                segments.join("::")
-            }
-            UseKind::Simple => {
-                // All but the last segment form the module path
-                if segments.len() >= 2 {
-                    segments[..segments.len() - 1].join("::")
-                } else {
-                    segments[0].clone()
-                }
-            }
-        };
+        let module_name = use_decl.path.module_name();
 
         // Load module if not already loaded
-        self.load_module(&module_name)?;
+        self.load_module(&module_name, span)?;
 
         // Get module environment
         let module_env =
@@ -367,6 +316,7 @@ impl Evaluator {
                     module: module_name.clone(),
                     path: format!("{}.rela", module_name),
                     reason: "module not registered".to_string(),
+                    span,
                 })?;
 
         // Import bindings based on use kind
@@ -376,26 +326,24 @@ impl Evaluator {
                 if segments.len() >= 2 {
                     let name = &segments[segments.len() - 1];
                     let symbol = intern(name);
-                    if let Some(value) = module_env.borrow().lookup(&symbol) {
-                        self.env.borrow_mut().bind(symbol, value);
-                    }
+                    self.import_binding(&module_name, &module_env, symbol, symbol, span)?;
                 }
             }
             UseKind::Glob => {
-                // use foo::* - import all public bindings
+                // use foo::* - import all exported bindings
                 let bindings = module_env.borrow().all_bindings();
                 for (symbol, value) in bindings {
-                    self.env.borrow_mut().bind(symbol, value);
+                    if self.modules.is_exported(&module_name, &symbol) {
+                        self.env.borrow_mut().bind(symbol, value);
+                    }
                 }
             }
             UseKind::Group(items) => {
                 // use foo::{a, b as c} - import specific items
                 for item in items {
                     let symbol = item.name.name;
-                    if let Some(value) = module_env.borrow().lookup(&symbol) {
-                        let target_name = item.alias.as_ref().map(|a| a.name).unwrap_or(symbol);
-                        self.env.borrow_mut().bind(target_name, value);
-                    }
+                    let target_name = item.alias.as_ref().map(|a| a.name).unwrap_or(symbol);
+                    self.import_binding(&module_name, &module_env, symbol, target_name, span)?;
                 }
             }
         }
@@ -403,26 +351,117 @@ impl Evaluator {
         Ok(())
     }
 
+    /// Bind an exported module binding under `target_name`
+    fn import_binding(
+        &mut self,
+        module_name: &str,
+        module_env: &Rc<RefCell<Env>>,
+        symbol: InternedStr,
+        target_name: InternedStr,
+        span: Span,
+    ) -> Result<(), EvalError> {
+        if !self.modules.is_exported(module_name, &symbol) {
+            let defined_here = module_env
+                .borrow()
+                .all_bindings()
+                .iter()
+                .any(|(name, _)| *name == symbol);
+            if defined_here {
+                return Err(EvalError::PrivateBinding {
+                    module: module_name.to_string(),
+                    name: symbol.to_string(),
+                    span,
+                });
+            }
+            return Ok(());
+        }
+        if let Some(value) = module_env.borrow().lookup(&symbol) {
+            self.env.borrow_mut().bind(target_name, value);
+        }
+        Ok(())
+    }
+
     /// Evaluate a program
     pub fn eval_program(&mut self, program: &Program) -> Result<Value, EvalError> {
         let mut result = Value::Unit;
 
         for item in &program.items {
-            result = self.eval_item(item)?;
+            let value = self.eval_item(item)?;
+            // Tests only run under `relanote test`, so they don't end a program
+            if !matches!(item.node, Item::Test(_)) {
+                result = value;
+            }
         }
 
         Ok(result)
     }
 
+    /// Bring this evaluator's evaluation of a program up to date after an
+    /// edit, returning the value of the new program like `eval_program`
+    ///
+    /// Only the items the edit affects are evaluated again and the other
+    /// bindings are kept, so a live-coding loop can swap in a changed riff
+    /// without restarting the song. The evaluator must have evaluated the
+    /// old version of `diff` without errors. Edits that can't be patched
+    /// (see [`ProgramDiff`]) evaluate the whole program again, as does a
+    /// program with warnings, since they point into the old source.
+    ///
+    /// Values kept from unchanged items keep the spans of the old source.
+    pub fn patch(&mut self, diff: &ProgramDiff) -> Result<Value, EvalError> {
+        let stale = match diff.plan() {
+            Plan::Patch { stale, unbound }
+                if self.warnings.is_empty()
+                    && !unbound.iter().any(|name| self.prelude_names.contains(name)) =>
+            {
+                let mut env = self.env.borrow_mut();
+                for name in &unbound {
+                    env.unbind(name);
+                }
+                stale
+            }
+            _ => {
+                self.reset();
+                return self.eval_program(diff.program());
+            }
+        };
+
+        self.render_targets.clear();
+        let items = &diff.program().items;
+        let mut result = Value::Unit;
+        for index in stale {
+            let value = self.eval_item(&items[index])?;
+            if !matches!(items[index].node, Item::Test(_)) {
+                result = value;
+            }
+        }
+        Ok(result)
+    }
+
     /// Evaluate an item
     fn eval_item(&mut self, item: &Spanned<Item>) -> Result<Value, EvalError> {
+        self.eval_item_node(item).map_err(|e| e.or_span(item.span))
+    }
+
+    fn eval_item_node(&mut self, item: &Spanned<Item>) -> Result<Value, EvalError> {
         match &item.node {
             Item::ScaleDef(scale_def) => {
-                let intervals: Vec<IntervalValue> = scale_def
-                    .intervals
-                    .iter()
-                    .map(|i| IntervalValue::from(&i.node))
-                    .collect();
+                let intervals: Vec<IntervalValue> = match &scale_def.base {
+                    Some(base) => match self.eval_expr(base)? {
+                        Value::Scale(scale) => scale.intervals,
+                        other => {
+                            return Err(EvalError::TypeError {
+                                expected: "Scale".to_string(),
+                                found: format!("{:?}", other),
+                                span: base.span,
+                            })
+                        }
+                    },
+                    None => scale_def
+                        .intervals
+                        .iter()
+                        .map(|i| IntervalValue::from(&i.node))
+                        .collect(),
+                };
 
                 let scale = Value::Scale(ScaleValue {
                     name: scale_def.name.name.to_string(),
@@ -477,12 +516,39 @@ impl Evaluator {
                                 }
                                 Value::Builtin(f) => {
                                     // Call the builtin with empty args (for parameterless oscillators)
-                                    if let Ok(Value::Oscillator(osc)) = f(vec![]) {
+                                    if let Ok(Value::Oscillator(osc)) =
+                                        f.call(vec![], &CallSite::new(expr.span))
+                                    {
                                         synth.oscillators = vec![osc];
                                     }
                                 }
                                 _ => {}
                             }
+                            self.resolve_samples(&mut synth.oscillators);
+                        }
+                        relanote_ast::music::SynthProperty::Lfo(expr) => {
+                            // One LFO, or several added together: PitchLfo 5 0.2 + AmpLfo 4 0.3
+                            synth.lfos = match self.eval_expr(expr)? {
+                                Value::Lfo(lfo) => vec![lfo],
+                                Value::Array(values) => values
+                                    .into_iter()
+                                    .map(|v| match v {
+                                        Value::Lfo(lfo) => Ok(lfo),
+                                        other => Err(EvalError::TypeError {
+                                            expected: "LFO".to_string(),
+                                            found: format!("{:?}", other),
+                                            span: expr.span,
+                                        }),
+                                    })
+                                    .collect::<Result<_, _>>()?,
+                                other => {
+                                    return Err(EvalError::TypeError {
+                                        expected: "LFO".to_string(),
+                                        found: format!("{:?}", other),
+                                        span: expr.span,
+                                    })
+                                }
+                            };
                         }
                         relanote_ast::music::SynthProperty::Envelope(expr) => {
                             if let Ok(Value::ADSR(adsr)) = self.eval_expr(expr) {
@@ -498,7 +564,9 @@ impl Evaluator {
                                 }
                                 Value::Builtin(f) => {
                                     // Call the builtin with empty args (though filters need args)
-                                    if let Ok(Value::Filter(filter)) = f(vec![]) {
+                                    if let Ok(Value::Filter(filter)) =
+                                        f.call(vec![], &CallSite::new(expr.span))
+                                    {
                                         synth.filter = Some(filter);
                                     }
                                 }
@@ -551,16 +619,90 @@ impl Evaluator {
 
             Item::SetBinding(binding) => {
                 let value = self.eval_expr(&binding.value)?;
+                if binding.name.name.as_str() == "key" {
+                    self.key_scale = match &binding.mode {
+                        Some(mode) => Some(self.mode_scale(mode, binding.value.span)?),
+                        None => None,
+                    };
+                }
                 self.env.borrow_mut().bind(binding.name.name, value);
                 Ok(Value::Unit)
             }
 
+            Item::Meta(meta) => {
+                for field in &meta.fields {
+                    let MetaField { name, value } = &field.node;
+                    let text = match self.eval_expr(value)? {
+                        Value::String(text) => text,
+                        other => {
+                            return Err(EvalError::TypeError {
+                                expected: "String".to_string(),
+                                found: format!("{:?}", other),
+                                span: value.span,
+                            })
+                        }
+                    };
+                    let slot = match name.name.as_str() {
+                        "title" => &mut self.metadata.title,
+                        "composer" => &mut self.metadata.composer,
+                        "copyright" => &mut self.metadata.copyright,
+                        other => {
+                            return Err(EvalError::Custom {
+                                message: format!(
+                                    "unknown meta field `{}`; expected one of {}",
+                                    other,
+                                    MetaDecl::FIELDS.join(", ")
+                                ),
+                                span: field.span,
+                            })
+                        }
+                    };
+                    *slot = Some(text);
+                }
+                Ok(Value::Unit)
+            }
+
+            Item::Test(_) => Ok(Value::Unit),
+
+            Item::Render(render) => {
+                let song = match self.eval_expr(&render.target)? {
+                    Value::Song(song) => song,
+                    // A section renders as a song of its own
+                    Value::Section(section) => SongValue::new(vec![section]),
+                    other => {
+                        return Err(EvalError::TypeError {
+                            expected: "Song or Section".to_string(),
+                            found: format!("{:?}", other),
+                            span: render.target.span,
+                        })
+                    }
+                };
+                if let Some(name) = &render.name {
+                    if self
+                        .render_targets
+                        .iter()
+                        .any(|target| target.name.as_ref() == Some(name))
+                    {
+                        return Err(EvalError::Custom {
+                            message: format!("there is already a render target named \"{}\"", name),
+                            span: item.span,
+                        });
+                    }
+                }
+                self.render_targets.push(RenderTarget {
+                    name: render.name.clone(),
+                    song: song.clone(),
+                    span: render.target.span,
+                });
+                Ok(Value::Song(song))
+            }
+
             Item::FunctionDef(func_def) => {
                 let params: Vec<_> = func_def
                     .params
                     .iter()
                     .filter_map(|p| {
-                        if let Pattern::Ident(ident) = &p.node {
+                        if let Pattern::Ident(ident) = p.node.unannotated() {
                             Some(ident.name)
                         } else {
                             None
@@ -578,17 +720,21 @@ impl Evaluator {
                 Ok(Value::Unit)
             }
 
+            Item::Export(ExportDecl::Definition(def)) => {
+                self.eval_item(&Spanned::new((**def).clone(), item.span))
+            }
+
             Item::Import(_) | Item::Export(_) => Ok(Value::Unit),
 
             Item::Mod(mod_decl) => {
                 // Module declaration - load module from file
-                self.load_module(&mod_decl.name.name.to_string())?;
+                self.load_module(&mod_decl.name.name.to_string(), item.span)?;
                 Ok(Value::Unit)
             }
 
             Item::Use(use_decl) => {
                 // Use declaration - import bindings from module
-                self.eval_use(use_decl)?;
+                self.eval_use(use_decl, item.span)?;
                 Ok(Value::Unit)
             }
 
@@ -598,9 +744,18 @@ impl Evaluator {
 
     /// Evaluate an expression
     pub fn eval_expr(&mut self, expr: &Spanned<Expr>) -> Result<Value, EvalError> {
+        // Errors from builtins carry no location; blame the innermost
+        // expression that raised them
+        self.eval_expr_node(expr).map_err(|e| e.or_span(expr.span))
+    }
+
+    fn eval_expr_node(&mut self, expr: &Spanned<Expr>) -> Result<Value, EvalError> {
         match &expr.node {
             Expr::Integer(n) => Ok(Value::Int(*n)),
             Expr::Float(n) => Ok(Value::Float(*n)),
+            Expr::Duration(duration) => Ok(Value::Duration(DurationValue {
+                beats: duration.to_beats(self.beats_per_bar()),
+            })),
             Expr::String(s) => Ok(Value::String(s.clone())),
             Expr::Bool(b) => Ok(Value::Bool(*b)),
             Expr::Unit => Ok(Value::Unit),
@@ -640,7 +795,7 @@ impl Evaluator {
                     .params
                     .iter()
                     .filter_map(|p| {
-                        if let Pattern::Ident(ident) = &p.node {
+                        if let Pattern::Ident(ident) = p.node.unannotated() {
                             Some(ident.name)
                         } else {
                             None
@@ -660,22 +815,63 @@ impl Evaluator {
                 let args: Result<Vec<_>, _> = app.args.iter().map(|a| self.eval_expr(a)).collect();
                 let args = args?;
 
-                self.apply(func, args, expr.span)
+                let call = CallSite {
+                    span: expr.span,
+                    args: app.args.iter().map(|a| a.span).collect(),
+                    scope: Some(self.env.clone()),
+                };
+                if app.named.is_empty() {
+                    self.apply(func, args, &call)
+                } else {
+                    self.apply_named(func, args, &app.named, None, &call)
+                }
             }
 
             Expr::Pipe(pipe) => {
                 let arg = self.eval_expr(&pipe.left)?;
+                // Errors point at the pipe stage, not the whole pipeline
+                let mut call = CallSite {
+                    span: pipe.right.span,
+                    args: vec![pipe.left.span],
+                    scope: Some(self.env.clone()),
+                };
                 // If right side is an application, add left arg to its args
                 if let Expr::Application(app) = &pipe.right.node {
                     let func = self.eval_expr(&app.func)?;
-                    let mut args = vec![arg];
+                    if !app.named.is_empty() {
+                        // Named calls take the piped value last, as builtins do
+                        let mut args = Vec::new();
+                        call.args.clear();
+                        for a in &app.args {
+                            args.push(self.eval_expr(a)?);
+                            call.args.push(a.span);
+                        }
+                        let piped = (arg, pipe.left.span);
+                        return self.apply_named(func, args, &app.named, Some(piped), &call);
+                    }
+                    // Builtins and their partials take the piped value last,
+                    // matching inference; other callees take it first
+                    let last = matches!(func, Value::Builtin(_) | Value::Partial(_));
+                    let mut args = Vec::new();
+                    let piped = if last {
+                        call.args.clear();
+                        Some(arg)
+                    } else {
+                        args.push(arg);
+                        None
+                    };
                     for a in &app.args {
                         args.push(self.eval_expr(a)?);
+                        call.args.push(a.span);
                     }
-                    self.apply(func, args, expr.span)
+                    if let Some(arg) = piped {
+                        args.push(arg);
+                        call.args.push(pipe.left.span);
+                    }
+                    self.apply(func, args, &call)
                 } else {
                     let func = self.eval_expr(&pipe.right)?;
-                    self.apply(func, vec![arg], expr.span)
+                    self.apply(func, vec![arg], &call)
                 }
             }
 
@@ -707,28 +903,39 @@ impl Evaluator {
                 let idx = self.eval_expr(&index.index)?;
 
                 match (base, idx) {
-                    (Value::Array(arr), Value::Int(i)) => {
-                        let i = i as usize;
-                        arr.get(i).cloned().ok_or(EvalError::IndexOutOfBounds {
-                            index: i as i64,
+                    (Value::Array(arr), Value::Int(i)) => usize::try_from(i)
+                        .ok()
+                        .and_then(|i| arr.get(i).cloned())
+                        .ok_or(EvalError::IndexOutOfBounds {
+                            index: i,
+                            first: 0,
                             len: arr.len(),
                             span: expr.span,
-                        })
-                    }
-                    (Value::Scale(scale), Value::Int(i)) => {
-                        let i = (i - 1) as usize; // 1-based indexing for scales
-                        scale
-                            .intervals
-                            .get(i)
-                            .map(|interval| Value::Interval(interval.clone()))
-                            .ok_or(EvalError::IndexOutOfBounds {
-                                index: i as i64,
-                                len: scale.intervals.len(),
-                                span: expr.span,
-                            })
-                    }
+                        }),
+                    (Value::Block(block), Value::Int(i)) => usize::try_from(i)
+                        .ok()
+                        .and_then(|i| block.slot_block(i))
+                        .map(Value::Block)
+                        .ok_or(EvalError::IndexOutOfBounds {
+                            index: i,
+                            first: 0,
+                            len: block.slots.len(),
+                            span: expr.span,
+                        }),
+                    // 1-based indexing for scales
+                    (Value::Scale(scale), Value::Int(i)) => i
+                        .checked_sub(1)
+                        .and_then(|i| usize::try_from(i).ok())
+                        .and_then(|i| scale.intervals.get(i))
+                        .map(|interval| Value::Interval(interval.clone()))
+                        .ok_or(EvalError::IndexOutOfBounds {
+                            index: i,
+                            first: 1,
+                            len: scale.intervals.len(),
+                            span: expr.span,
+                        }),
                     _ => Err(EvalError::TypeError {
-                        expected: "Array or Scale".to_string(),
+                        expected: "Array, Block or Scale".to_string(),
                         found: "other".to_string(),
                         span: expr.span,
                     }),
@@ -809,6 +1016,136 @@ impl Evaluator {
             Expr::Paren(inner) => self.eval_expr(inner),
             Expr::Annotated(inner, _) => self.eval_expr(inner),
 
+            Expr::Section(section) => {
+                let name = match self.eval_expr(&section.name)? {
+                    Value::String(name) => name,
+                    other => {
+                        return Err(EvalError::TypeError {
+                            expected: "String".to_string(),
+                            found: format!("{:?}", other),
+                            span: section.name.span,
+                        })
+                    }
+                };
+                let mut parts = match self.eval_expr(&section.body)? {
+                    Value::Block(block) => vec![PartValue::new(name.clone(), vec![block])],
+                    Value::Part(part) => vec![part],
+                    // A layer's master effects become a bus of the section's
+                    Value::Song(mut song) => {
+                        song.master_to_bus(&name);
+                        song.sections
+                            .into_iter()
+                            .flat_map(|section| section.parts)
+                            .collect()
+                    }
+                    other => {
+                        return Err(EvalError::TypeError {
+                            expected: "Block, Part or Song".to_string(),
+                            found: format!("{:?}", other),
+                            span: section.body.span,
+                        })
+                    }
+                };
+
+                let mut key = None;
+                let mut tempo = None;
+                if let Some(context) = &section.context {
+                    if let Some(key_expr) = &context.key {
+                        key = Some(self.eval_key(key_expr)?);
+                    }
+                    if let Some(tempo_expr) = &context.tempo {
+                        tempo = Some(self.eval_tempo(tempo_expr)?);
+                    }
+                    // `scale: Dorian` applies the scale to every part, like `in Dorian`
+                    if let Some(scale_expr) = &context.scale {
+                        let scale = match self.eval_expr(scale_expr)? {
+                            Value::Scale(scale) => scale,
+                            other => {
+                                return Err(EvalError::TypeError {
+                                    expected: "Scale".to_string(),
+                                    found: format!("{:?}", other),
+                                    span: scale_expr.span,
+                                })
+                            }
+                        };
+                        for part in &mut parts {
+                            part.blocks = part
+                                .blocks
+                                .iter()
+                                .map(|b| self.apply_scale_to_block(&scale, b))
+                                .collect();
+                            self.check_in_scale(&scale, &part.blocks, scale_expr.span);
+                        }
+                    }
+                }
+
+                Ok(Value::Section(SectionValue {
+                    key,
+                    tempo,
+                    ..SectionValue::new(name, parts)
+                }))
+            }
+
+            Expr::Context(context) => {
+                let mut song = match self.eval_expr(&context.body)? {
+                    Value::Song(song) => song,
+                    Value::Section(section) => SongValue::new(vec![section]),
+                    Value::Part(part) => {
+                        SongValue::new(vec![SectionValue::new("Context", vec![part])])
+                    }
+                    Value::Block(block) => SongValue::new(vec![SectionValue::new(
+                        "Context",
+                        vec![PartValue::new("Context", vec![block])],
+                    )]),
+                    other => {
+                        return Err(EvalError::TypeError {
+                            expected: "Block, Part, Section or Song".to_string(),
+                            found: format!("{:?}", other),
+                            span: context.body.span,
+                        })
+                    }
+                };
+
+                let tempo = match &context.tempo {
+                    Some(tempo) => Some(self.eval_tempo(tempo)?),
+                    None => None,
+                };
+                let key = match &context.key {
+                    Some(key) => Some(self.eval_key(key)?),
+                    None => None,
+                };
+                let swing = match &context.swing {
+                    Some(swing) => match self.eval_expr(swing)? {
+                        Value::Float(ratio) => Some(ratio),
+                        other => {
+                            return Err(EvalError::TypeError {
+                                expected: "Float".to_string(),
+                                found: format!("{:?}", other),
+                                span: swing.span,
+                            })
+                        }
+                    },
+                    None => None,
+                };
+
+                // Settings made closer to the music (an inner context, a
+                // section's `with`, a `swing` call) take precedence
+                for section in &mut song.sections {
+                    section.tempo = section.tempo.or(tempo);
+                    section.key = section.key.or(key);
+                    if let Some(ratio) = swing {
+                        for part in &mut section.parts {
+                            part.blocks = part
+                                .blocks
+                                .iter()
+                                .map(|block| swing_block(block, ratio, false))
+                                .collect();
+                        }
+                    }
+                }
+                Ok(Value::Song(song))
+            }
+
             Expr::Layer(layer) => {
                 // Evaluate each part and create a Song with multiple parts
                 let mut parts = Vec::new();
@@ -816,17 +1153,7 @@ impl Evaluator {
                     let value = self.eval_expr(part_expr)?;
                     match value {
                         Value::Block(block) => {
-                            parts.push(PartValue {
-                                instrument: format!("Layer {}", i + 1),
-                                blocks: vec![block],
-                                envelope: None,
-                                reverb_level: None,
-                                volume_level: None,
-                                delay: None,
-                                phaser: None,
-                                distortion: None,
-                                synth: None,
-                            });
+                            parts.push(PartValue::new(format!("Layer {}", i + 1), vec![block]));
                         }
                         Value::Part(part) => {
                             parts.push(part);
@@ -837,12 +1164,27 @@ impl Evaluator {
                     }
                 }
 
-                Ok(Value::Song(SongValue {
-                    sections: vec![SectionValue {
-                        name: "Layer".to_string(),
-                        parts,
-                    }],
-                }))
+                Ok(Value::Song(SongValue::new(vec![SectionValue::new(
+                    "Layer", parts,
+                )])))
+            }
+
+            Expr::Kit(kit) => {
+                // Each hit plays a drum synth's GM key, or a key given as a number
+                let mut hits = Vec::new();
+                for hit in &kit.hits {
+                    let Some(key) = drum_key(&self.eval_expr(&hit.drum)?) else {
+                        return Err(EvalError::Custom {
+                            message: format!(
+                                "`{}` must be a drum synth or a GM key from 0 to 127",
+                                hit.name.name.as_str()
+                            ),
+                            span: hit.drum.span,
+                        });
+                    };
+                    hits.push((hit.name.name.as_str().to_string(), key));
+                }
+                Ok(Value::Kit(KitValue { hits }))
             }
 
             Expr::InScale(in_scale) => {
@@ -850,8 +1192,26 @@ impl Evaluator {
                 let scale_value = self.eval_expr(&in_scale.scale)?;
                 match scale_value {
                     Value::Scale(scale) => Ok(Value::InScaleApplicator(scale)),
+                    // `in` a chord reads degrees as chord tones: <1> the root,
+                    // <2> the next tone up, wrapping an octave past the top
+                    Value::Chord(chord) => Ok(Value::InScaleApplicator(ScaleValue {
+                        name: chord.name,
+                        intervals: chord.intervals,
+                    })),
+                    // `in key` uses the scale of the key's mode
+                    Value::AbsolutePitch(_)
+                        if matches!(&in_scale.scale.node, Expr::Ident(ident) if ident.name.as_str() == "key") =>
+                    {
+                        match &self.key_scale {
+                            Some(scale) => Ok(Value::InScaleApplicator(scale.clone())),
+                            None => Err(EvalError::Custom {
+                                message: "`key` has no mode to take a scale from; set one with `set key = D minor`".to_string(),
+                                span: in_scale.scale.span,
+                            }),
+                        }
+                    }
                     _ => Err(EvalError::TypeError {
-                        expected: "Scale".to_string(),
+                        expected: "Scale or Chord".to_string(),
                         found: format!("{:?}", scale_value),
                         span: in_scale.scale.span,
                     }),
@@ -861,14 +1221,48 @@ impl Evaluator {
             Expr::With(with_expr) => {
                 // Evaluate base and modifications
                 let base = self.eval_expr(&with_expr.base)?;
-                let _modifications: Result<Vec<_>, _> = with_expr
+                let changes = with_expr
                     .modifications
                     .iter()
-                    .map(|m| self.eval_expr(m))
-                    .collect();
-                // For now, just return the base value
-                // Full modification semantics can be added later
-                Ok(base)
+                    .map(|m| Ok((self.eval_interval_change(m)?, m.span)))
+                    .collect::<Result<Vec<_>, EvalError>>()?;
+                match base {
+                    Value::Scale(scale) => Ok(Value::Scale(ScaleValue {
+                        intervals: modify_intervals(&scale.intervals, &changes)?,
+                        ..scale
+                    })),
+                    Value::Chord(chord) => Ok(Value::Chord(ChordValue {
+                        intervals: modify_intervals(&chord.intervals, &changes)?,
+                        ..chord
+                    })),
+                    other => Err(EvalError::TypeError {
+                        expected: "Scale or Chord".to_string(),
+                        found: format!("{:?}", other),
+                        span: with_expr.base.span,
+                    }),
+                }
+            }
+
+            Expr::Envelope(env) => {
+                let from = self.eval_dynamic(&env.from)?;
+                let to = self.eval_dynamic(&env.to)?;
+                let duration_beats = match self.eval_expr(&env.duration)? {
+                    Value::Duration(duration) => duration.beats,
+                    Value::Int(beats) => beats as f64,
+                    Value::Float(beats) => beats,
+                    other => {
+                        return Err(EvalError::TypeError {
+                            expected: "Duration".to_string(),
+                            found: format!("{:?}", other),
+                            span: env.duration.span,
+                        })
+                    }
+                };
+                Ok(Value::Envelope(EnvelopeValue {
+                    from,
+                    to,
+                    duration_beats,
+                }))
             }
 
             // Placeholder for complex expressions
@@ -878,6 +1272,7 @@ impl Evaluator {
 
     /// Evaluate a slot in a block
     fn eval_slot(&mut self, slot: &Spanned<Slot>) -> Result<SlotValue, EvalError> {
+        let beats_per_bar = self.beats_per_bar();
         match &slot.node {
             Slot::Note {
                 pitch,
@@ -888,11 +1283,15 @@ impl Evaluator {
                 Ok(SlotValue::Note {
                     interval,
                     articulations: articulations.clone(),
-                    duration_beats: duration.map(|d| d as f64),
+                    duration_beats: duration.map(|d| d.to_beats(beats_per_bar)),
+                    meta: NoteMeta {
+                        degree: Self::scale_degree(&pitch.node),
+                        ..NoteMeta::default()
+                    },
                 })
             }
             Slot::Rest { duration } => Ok(SlotValue::Rest {
-                duration_beats: duration.map(|d| d as f64),
+                duration_beats: duration.map(|d| d.to_beats(beats_per_bar)),
             }),
             Slot::Chord {
                 pitches,
@@ -904,40 +1303,106 @@ impl Evaluator {
                 Ok(SlotValue::Chord {
                     intervals: intervals?,
                     articulations: articulations.clone(),
-                    duration_beats: duration.map(|d| d as f64),
-                })
-            }
-            Slot::Tuplet(tuplet) => {
-                let slots: Result<Vec<_>, _> =
-                    tuplet.contents.iter().map(|s| self.eval_slot(s)).collect();
-                let target = self.eval_expr(&tuplet.target_beats)?;
-                let target_beats = match target {
-                    Value::Int(n) => n,
-                    _ => 2, // Default
-                };
-                Ok(SlotValue::Tuplet {
-                    slots: slots?,
-                    target_beats,
+                    duration_beats: duration.map(|d| d.to_beats(beats_per_bar)),
+                    meta: NoteMeta::default(),
                 })
             }
+            Slot::Tuplet(tuplet) => self.eval_tuplet(tuplet),
+            // Hits are rooted notes until a kit gives them their drums
+            Slot::Hit {
+                name,
+                articulations,
+                duration,
+            } => Ok(SlotValue::Note {
+                interval: IntervalValue::from_semitones(0),
+                articulations: articulations.clone(),
+                duration_beats: duration.map(|d| d.to_beats(beats_per_bar)),
+                meta: NoteMeta {
+                    hit: Some(name.name.as_str().to_string()),
+                    ..NoteMeta::default()
+                },
+            }),
         }
     }
 
-    /// Convert scale index to semitones with octave support
-    /// idx: 1-based scale index (1-7 for first octave, 8-14 for second, etc.)
-    fn scale_index_to_semitones(idx: i64) -> i32 {
-        // Major scale intervals in semitones
-        const MAJOR_SCALE: [i32; 7] = [0, 2, 4, 5, 7, 9, 11];
-
-        if idx <= 0 {
-            return 0;
+    /// Evaluate a tuplet: `{ ... }:n` lasts n beats (or a duration), and
+    /// `{ ... }:n:m` plays n notes in the time of m
+    fn eval_tuplet(&mut self, tuplet: &Tuplet) -> Result<SlotValue, EvalError> {
+        let slots = tuplet
+            .contents
+            .iter()
+            .map(|s| self.eval_slot(s))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let target = self.eval_expr(&tuplet.target_beats)?;
+        let target_beats = match (&tuplet.in_time_of, target) {
+            (None, Value::Int(n)) => n as f64,
+            (None, Value::Float(f)) => f,
+            (None, Value::Duration(duration)) => duration.beats,
+            (Some(in_time_of), Value::Int(n)) if n > 0 => {
+                let m = match self.eval_expr(in_time_of)? {
+                    Value::Int(m) if m > 0 => m,
+                    other => {
+                        return Err(EvalError::TypeError {
+                            expected: "positive Int".to_string(),
+                            found: format!("{:?}", other),
+                            span: in_time_of.span,
+                        })
+                    }
+                };
+                tuplet_nominal_beats(&slots) * m as f64 / n as f64
+            }
+            (in_time_of, other) => {
+                return Err(EvalError::TypeError {
+                    expected: if in_time_of.is_some() {
+                        "positive Int"
+                    } else {
+                        "Int or Duration"
+                    }
+                    .to_string(),
+                    found: format!("{:?}", other),
+                    span: tuplet.target_beats.span,
+                })
+            }
+        };
+        if target_beats <= 0.0 {
+            return Err(EvalError::Custom {
+                message: "a tuplet must last longer than zero beats".to_string(),
+                span: tuplet.target_beats.span,
+            });
         }
 
-        let idx = idx as i32;
-        let octave = (idx - 1) / 7;
-        let degree = ((idx - 1) % 7) as usize;
+        Ok(SlotValue::Tuplet {
+            slots,
+            target_beats,
+        })
+    }
 
-        MAJOR_SCALE[degree] + (octave * 12)
+    /// Scale degree a pitch is written as, read in the major scale
+    /// (degrees 8-14 are the second octave, and so on)
+    fn scale_degree(pitch: &Pitch) -> Option<ScaleDegree> {
+        let (degree, accidentals, octave) = match pitch {
+            Pitch::ScaleIndex(degree) => (*degree, &[][..], 0),
+            Pitch::ScaleIndexMod(degree, accidentals, octave) => {
+                (*degree, &accidentals[..], *octave)
+            }
+            Pitch::Interval(_) | Pitch::Root => return None,
+        };
+        let alter_cents = accidentals
+            .iter()
+            .map(|a| match a {
+                relanote_lexer::token::Accidental::Sharp => 100.0,
+                relanote_lexer::token::Accidental::Flat => -100.0,
+            })
+            .sum();
+        let mut degree = ScaleDegree {
+            degree,
+            octave,
+            alter_cents,
+            cents: 0.0,
+        };
+        degree.cents = degree.resolve(&ScaleDegree::MAJOR);
+        Some(degree)
     }
 
     /// Evaluate a pitch
@@ -945,21 +1410,9 @@ impl Evaluator {
         match pitch {
             Pitch::Interval(interval) => Ok(IntervalValue::from(interval)),
             Pitch::Root => Ok(IntervalValue::from_cents(0.0)),
-            Pitch::ScaleIndex(idx) => {
-                let semitones = Self::scale_index_to_semitones(*idx as i64);
-                Ok(IntervalValue::from_semitones(semitones))
-            }
-            Pitch::ScaleIndexMod(idx, accidentals) => {
-                let base = Self::scale_index_to_semitones(*idx as i64);
-                let offset: i32 = accidentals
-                    .iter()
-                    .map(|a| match a {
-                        relanote_lexer::token::Accidental::Sharp => 1,
-                        relanote_lexer::token::Accidental::Flat => -1,
-                    })
-                    .sum();
-                Ok(IntervalValue::from_semitones(base + offset))
-            }
+            Pitch::ScaleIndex(_) | Pitch::ScaleIndexMod(..) => Ok(IntervalValue::from_cents(
+                Self::scale_degree(pitch).map_or(0.0, |degree| degree.cents),
+            )),
         }
     }
 
@@ -968,10 +1421,41 @@ impl Evaluator {
         &mut self,
         func: Value,
         args: Vec<Value>,
-        span: relanote_core::Span,
+        call: &CallSite,
     ) -> Result<Value, EvalError> {
+        let span = call.span;
         match func {
             Value::Closure(closure) => {
+                if args.is_empty() && !closure.params.is_empty() {
+                    return Err(EvalError::WrongArity {
+                        expected: closure.params.len(),
+                        got: args.len(),
+                        span,
+                    });
+                }
+
+                // Too few arguments: a closure over the ones given, taking
+                // the rest
+                if args.len() < closure.params.len() {
+                    let mut env = Env::with_parent(closure.env);
+                    for (param, arg) in closure.params.iter().zip(args.iter().cloned()) {
+                        env.bind(*param, arg);
+                    }
+                    return Ok(Value::Closure(Closure {
+                        params: closure.params[args.len()..].to_vec(),
+                        body: closure.body,
+                        env: Rc::new(RefCell::new(env)),
+                    }));
+                }
+
+                // Too many: the result takes the rest
+                if args.len() > closure.params.len() && !closure.params.is_empty() {
+                    let mut args = args;
+                    let rest = args.split_off(closure.params.len());
+                    let result = self.apply(Value::Closure(closure), args, call)?;
+                    return self.apply(result, rest, &CallSite::new(span));
+                }
+
                 if closure.params.len() != args.len() {
                     return Err(EvalError::WrongArity {
                         expected: closure.params.len(),
@@ -991,12 +1475,22 @@ impl Evaluator {
                 self.env = old_env;
                 Ok(result)
             }
-            Value::Builtin(f) => f(args),
+            Value::Builtin(builtin) => match builtin.params.as_ref().map(|params| params.len()) {
+                Some(arity) if args.len() < arity => {
+                    let partial = PartialCall {
+                        builtin,
+                        args: vec![None; arity],
+                    };
+                    self.apply_partial(partial, args, call)
+                }
+                _ => builtin.call(args, call).map_err(|e| e.or_span(span)),
+            },
+            Value::Partial(partial) => self.apply_partial(partial, args, call),
             Value::Composed(f, g) => {
                 // f >> g means apply f first, then g
                 // composed(x) = g(f(x))
-                let intermediate = self.apply(*f, args, span)?;
-                self.apply(*g, vec![intermediate], span)
+                let intermediate = self.apply(*f, args, call)?;
+                self.apply(*g, vec![intermediate], &CallSite::new(span))
             }
             Value::InScaleApplicator(scale) => {
                 // Apply scale to a block, transforming <n> references
@@ -1010,6 +1504,7 @@ impl Evaluator {
                 match &args[0] {
                     Value::Block(block) => {
                         let transformed = self.apply_scale_to_block(&scale, block);
+                        self.check_in_scale(&scale, std::slice::from_ref(&transformed), span);
                         Ok(Value::Block(transformed))
                     }
                     Value::Part(part) => {
@@ -1019,16 +1514,38 @@ impl Evaluator {
                             .iter()
                             .map(|b| self.apply_scale_to_block(&scale, b))
                             .collect();
+                        self.check_in_scale(&scale, &transformed_blocks, span);
                         Ok(Value::Part(PartValue {
-                            instrument: part.instrument.clone(),
                             blocks: transformed_blocks,
-                            envelope: part.envelope.clone(),
-                            reverb_level: part.reverb_level,
-                            volume_level: part.volume_level,
-                            delay: part.delay.clone(),
-                            phaser: part.phaser.clone(),
-                            distortion: part.distortion.clone(),
-                            synth: part.synth.clone(),
+                            ..part.clone()
+                        }))
+                    }
+                    _ => Err(EvalError::TypeError {
+                        expected: "Block or Part".to_string(),
+                        found: format!("{:?}", args[0]),
+                        span,
+                    }),
+                }
+            }
+            Value::Kit(kit) => {
+                if args.len() != 1 {
+                    return Err(EvalError::WrongArity {
+                        expected: 1,
+                        got: args.len(),
+                        span,
+                    });
+                }
+                match &args[0] {
+                    Value::Block(block) => Ok(Value::Block(apply_kit(&kit, block, span)?)),
+                    Value::Part(part) => {
+                        let blocks = part
+                            .blocks
+                            .iter()
+                            .map(|block| apply_kit(&kit, block, span))
+                            .collect::<Result<_, _>>()?;
+                        Ok(Value::Part(PartValue {
+                            blocks,
+                            ..part.clone()
                         }))
                     }
                     _ => Err(EvalError::TypeError {
@@ -1042,6 +1559,247 @@ impl Evaluator {
         }
     }
 
+    /// Pass more arguments to a partially applied builtin, calling it once
+    /// it has them all
+    fn apply_partial(
+        &mut self,
+        mut partial: PartialCall,
+        args: Vec<Value>,
+        call: &CallSite,
+    ) -> Result<Value, EvalError> {
+        let missing = partial.missing();
+        if args.len() > missing {
+            return Err(EvalError::WrongArity {
+                expected: missing,
+                got: args.len(),
+                span: call.span,
+            });
+        }
+
+        let mut args = args.into_iter().enumerate();
+        for slot in partial.args.iter_mut().filter(|slot| slot.is_none()) {
+            match args.next() {
+                Some((i, value)) => *slot = Some((value, call.arg(i))),
+                None => break,
+            }
+        }
+        if partial.missing() > 0 {
+            return Ok(Value::Partial(partial));
+        }
+
+        let (args, spans) = partial.args.into_iter().flatten().unzip();
+        let call = CallSite {
+            span: call.span,
+            args: spans,
+            scope: call.scope.clone(),
+        };
+        partial
+            .builtin
+            .call(args, &call)
+            .map_err(|e| e.or_span(call.span))
+    }
+
+    /// Apply a builtin to arguments some of which are named, placing them
+    /// the way the type checker does
+    fn apply_named(
+        &mut self,
+        func: Value,
+        args: Vec<Value>,
+        named: &[NamedArg],
+        piped: Option<(Value, Span)>,
+        call: &CallSite,
+    ) -> Result<Value, EvalError> {
+        let (builtin, params) = match func {
+            Value::Builtin(builtin) => match builtin.params.clone() {
+                Some(params) => (builtin, params),
+                None => {
+                    return Err(EvalError::Custom {
+                        message: format!(
+                            "`{}` takes different arguments in different signatures, so they \
+                             can't be named",
+                            builtin.name
+                        ),
+                        span: named[0].name_span,
+                    })
+                }
+            },
+            _ => {
+                return Err(EvalError::Custom {
+                    message: "only builtins take named arguments".to_string(),
+                    span: named[0].name_span,
+                })
+            }
+        };
+
+        let positional: Vec<_> = args.into_iter().zip(call.args.iter().copied()).collect();
+        let mut named_values = Vec::new();
+        for arg in named {
+            let value = self.eval_expr(&arg.value)?;
+            named_values.push((
+                arg.name.name.as_str(),
+                arg.name_span,
+                (value, arg.value.span),
+            ));
+        }
+        let placed = place_arguments(
+            builtin.name,
+            &params,
+            positional,
+            named_values,
+            piped,
+            call.span,
+        )
+        .map_err(|err| EvalError::Custom {
+            message: err.to_string(),
+            span: err.span(),
+        })?;
+
+        let args = placed
+            .into_iter()
+            .map(|arg| match arg {
+                Argument::Given(arg) => Some(arg),
+                Argument::Default(default) => Some((default_value(default), call.span)),
+                Argument::Missing => None,
+            })
+            .collect();
+        self.apply_partial(PartialCall { builtin, args }, Vec::new(), call)
+    }
+
+    /// Look up the prelude scale a key's mode stands for
+    fn mode_scale(&self, mode: &Ident, span: Span) -> Result<ScaleValue, EvalError> {
+        let name = SetBinding::mode_scale(mode.name.as_str()).unwrap_or_default();
+        match self.env.borrow().lookup(&intern(name)) {
+            Some(Value::Scale(scale)) => Ok(scale),
+            _ => Err(EvalError::Custom {
+                message: format!("no `{}` scale for the key's mode `{}`", name, mode.name),
+                span,
+            }),
+        }
+    }
+
+    /// With `set strict_scale = true`, warn about notes of `blocks` that
+    /// fall outside `scale` after it was applied to them
+    fn check_in_scale(&mut self, scale: &ScaleValue, blocks: &[BlockValue], span: Span) {
+        if !matches!(self.get_binding("strict_scale"), Some(Value::Bool(true))) {
+            return;
+        }
+
+        let outside = scale.notes_outside(blocks);
+        if outside.is_empty() {
+            return;
+        }
+        let names: Vec<String> = outside.iter().map(|name| format!("`{}`", name)).collect();
+        self.warnings.push(
+            Diagnostic::warning(
+                format!("notes outside {}: {}", scale.name, names.join(", ")),
+                span,
+            )
+            .with_code(DiagnosticCode::OutOfScale)
+            .with_note("`set strict_scale = true` reports notes that aren't in the applied scale"),
+        );
+    }
+
+    /// Song metadata from the program's `meta { ... }` blocks
+    pub fn metadata(&self) -> &SongMetadata {
+        &self.metadata
+    }
+
+    /// Songs the program declares with `render`, in order; when there are
+    /// none, the program's output is its last expression
+    pub fn render_targets(&self) -> &[RenderTarget] {
+        &self.render_targets
+    }
+
+    /// Warnings from evaluating, such as notes outside a scale under
+    /// `set strict_scale = true`
+    pub fn warnings(&self) -> &[Diagnostic] {
+        &self.warnings
+    }
+
+    /// Evaluate a dynamic marking; `pp`, `mf` and the like are dynamics
+    /// unless a binding shadows them
+    fn eval_dynamic(&mut self, expr: &Spanned<Expr>) -> Result<DynamicValue, EvalError> {
+        if let Expr::Ident(ident) = &expr.node {
+            if self.env.borrow().lookup(&ident.name).is_none() {
+                if let Ok(dynamic) = ident.name.parse::<Dynamic>() {
+                    return Ok(dynamic.into());
+                }
+            }
+        }
+        match self.eval_expr(expr)? {
+            Value::Dynamic(dynamic) => Ok(dynamic),
+            other => Err(EvalError::TypeError {
+                expected: "Dynamic".to_string(),
+                found: format!("{:?}", other),
+                span: expr.span,
+            }),
+        }
+    }
+
+    /// Evaluate a key setting (`key: G4`, `key = A3`) to its MIDI note
+    fn eval_key(&mut self, expr: &Spanned<Expr>) -> Result<u8, EvalError> {
+        match self.eval_expr(expr)? {
+            Value::AbsolutePitch(pitch) => Ok(pitch.midi_note),
+            other => Err(EvalError::TypeError {
+                expected: "AbsolutePitch".to_string(),
+                found: format!("{:?}", other),
+                span: expr.span,
+            }),
+        }
+    }
+
+    /// Evaluate a tempo setting (`tempo: 90`, `tempo = 92.5`) to BPM
+    fn eval_tempo(&mut self, expr: &Spanned<Expr>) -> Result<f64, EvalError> {
+        let bpm = match self.eval_expr(expr)? {
+            Value::Int(bpm) => bpm as f64,
+            Value::Float(bpm) => bpm,
+            other => {
+                return Err(EvalError::TypeError {
+                    expected: "Int or Float".to_string(),
+                    found: format!("{:?}", other),
+                    span: expr.span,
+                })
+            }
+        };
+        if bpm <= 0.0 {
+            return Err(EvalError::Custom {
+                message: format!("tempo must be positive, got {}", bpm),
+                span: expr.span,
+            });
+        }
+        Ok(bpm)
+    }
+
+    /// Evaluate a `with { ... }` modification: an interval sets its degree,
+    /// `-interval` removes it
+    fn eval_interval_change(&mut self, expr: &Spanned<Expr>) -> Result<IntervalChange, EvalError> {
+        let (target, remove) = match &expr.node {
+            Expr::Unary(unary) if unary.op == UnaryOp::Neg => (unary.operand.as_ref(), true),
+            _ => (expr, false),
+        };
+        let interval = match self.eval_expr(target)? {
+            Value::Interval(interval) => interval,
+            other => {
+                return Err(EvalError::TypeError {
+                    expected: "Interval".to_string(),
+                    found: format!("{:?}", other),
+                    span: target.span,
+                })
+            }
+        };
+        // A spelled literal names its degree; anything else goes by its size
+        let degree = match &target.node {
+            Expr::Interval(lit) if lit.cents.is_none() => lit.degree as u32,
+            Expr::Root => 1,
+            _ => nearest_degree(interval.cents),
+        };
+        Ok(if remove {
+            IntervalChange::Remove(degree)
+        } else {
+            IntervalChange::Set(degree, interval)
+        })
+    }
+
     /// Apply a scale to a block, transforming scale index references
     fn apply_scale_to_block(&self, scale: &ScaleValue, block: &BlockValue) -> BlockValue {
         let transformed_slots: Vec<_> = block
@@ -1062,13 +1820,28 @@ impl Evaluator {
                 interval,
                 articulations,
                 duration_beats,
+                meta,
             } => {
-                // Transform by looking up the interval's semitone in the scale
-                let transformed_interval = self.transform_interval_with_scale(scale, interval);
+                let mut meta = meta.clone();
+                let transformed_interval = match &mut meta.degree {
+                    // Degrees take the scale's own step, as long as the note
+                    // hasn't been moved off the degree it was written as
+                    Some(degree) if (degree.cents - interval.cents).abs() < 0.01 => {
+                        let steps: Vec<f64> = scale.intervals.iter().map(|i| i.cents).collect();
+                        degree.cents = degree.resolve(&steps);
+                        IntervalValue::from_cents(degree.cents)
+                    }
+                    // Other notes are mapped from the major scale degree they sit on
+                    _ => {
+                        meta.degree = None;
+                        self.transform_interval_with_scale(scale, interval)
+                    }
+                };
                 SlotValue::Note {
                     interval: transformed_interval,
                     articulations: articulations.clone(),
                     duration_beats: *duration_beats,
+                    meta,
                 }
             }
             SlotValue::Rest { duration_beats } => SlotValue::Rest {
@@ -1078,6 +1851,7 @@ impl Evaluator {
                 intervals,
                 articulations,
                 duration_beats,
+                meta,
             } => {
                 let transformed: Vec<_> = intervals
                     .iter()
@@ -1087,6 +1861,7 @@ impl Evaluator {
                     intervals: transformed,
                     articulations: articulations.clone(),
                     duration_beats: *duration_beats,
+                    meta: meta.clone(),
                 }
             }
             SlotValue::Tuplet {
@@ -1102,6 +1877,12 @@ impl Evaluator {
                     target_beats: *target_beats,
                 }
             }
+            SlotValue::Overlay { voices } => SlotValue::Overlay {
+                voices: voices
+                    .iter()
+                    .map(|voice| self.apply_scale_to_block(scale, voice))
+                    .collect(),
+            },
         }
     }
 
@@ -1121,9 +1902,9 @@ impl Evaluator {
 
         if degree > 0 && degree <= scale.intervals.len() {
             // Get the interval from the target scale
+            // Keep the scale's cents so microtonal scales stay in tune
             let scale_interval = &scale.intervals[degree - 1];
-            let new_semitones = scale_interval.semitones() as i32 + (octave * 12);
-            IntervalValue::from_semitones(new_semitones)
+            IntervalValue::from_cents(scale_interval.cents + octave as f64 * 1200.0)
         } else {
             // Keep original if can't map
             interval.clone()
@@ -1171,6 +1952,50 @@ impl Evaluator {
                     cents: a.cents - b.cents,
                 }))
             }
+            // Stacking: `P5 * 2` is two fifths, a ninth
+            (BinaryOp::Mul, Value::Interval(i), Value::Int(n))
+            | (BinaryOp::Mul, Value::Int(n), Value::Interval(i)) => {
+                Ok(Value::Interval(IntervalValue {
+                    cents: i.cents * n as f64,
+                }))
+            }
+
+            // Duration arithmetic
+            (BinaryOp::Add, Value::Duration(a), Value::Duration(b)) => {
+                Ok(Value::Duration(DurationValue {
+                    beats: a.beats + b.beats,
+                }))
+            }
+            (BinaryOp::Sub, Value::Duration(a), Value::Duration(b)) => {
+                Ok(Value::Duration(DurationValue {
+                    beats: a.beats - b.beats,
+                }))
+            }
+            (BinaryOp::Mul, Value::Duration(d), Value::Int(n))
+            | (BinaryOp::Mul, Value::Int(n), Value::Duration(d)) => {
+                Ok(Value::Duration(DurationValue {
+                    beats: d.beats * n as f64,
+                }))
+            }
+            (BinaryOp::Mul, Value::Duration(d), Value::Float(x))
+            | (BinaryOp::Mul, Value::Float(x), Value::Duration(d)) => {
+                Ok(Value::Duration(DurationValue { beats: d.beats * x }))
+            }
+            (BinaryOp::Div, Value::Duration(d), divisor @ (Value::Int(_) | Value::Float(_))) => {
+                let divisor = match divisor {
+                    Value::Int(n) => n as f64,
+                    Value::Float(x) => x,
+                    _ => unreachable!(),
+                };
+                if divisor == 0.0 {
+                    Err(EvalError::DivisionByZero { span })
+                } else {
+                    Ok(Value::Duration(DurationValue {
+                        beats: d.beats / divisor,
+                    }))
+                }
+            }
+
             (BinaryOp::Mul, Value::Int(a), Value::Int(b)) => Ok(Value::Int(a * b)),
             (BinaryOp::Div, Value::Int(a), Value::Int(b)) => {
                 if b == 0 {
@@ -1179,12 +2004,82 @@ impl Evaluator {
                     Ok(Value::Int(a / b))
                 }
             }
+            // Modulo wraps into 0..b, so `-1 % 7` is 6
+            (BinaryOp::Mod, Value::Int(a), Value::Int(b)) => {
+                if b == 0 {
+                    Err(EvalError::DivisionByZero { span })
+                } else {
+                    Ok(Value::Int(a.rem_euclid(b)))
+                }
+            }
+            (BinaryOp::Pow, Value::Int(a), Value::Int(b)) => u32::try_from(b)
+                .ok()
+                .and_then(|b| a.checked_pow(b))
+                .map(Value::Int)
+                .ok_or_else(|| EvalError::Custom {
+                    message: format!(
+                        "{} ^ {} is not an Int; use a Float base for negative or large powers",
+                        a, b
+                    ),
+                    span,
+                }),
+
+            // Float arithmetic, with Ints widened when mixed in
+            (
+                op @ (BinaryOp::Add
+                | BinaryOp::Sub
+                | BinaryOp::Mul
+                | BinaryOp::Div
+                | BinaryOp::Mod
+                | BinaryOp::Pow),
+                left @ (Value::Int(_) | Value::Float(_)),
+                right @ (Value::Int(_) | Value::Float(_)),
+            ) => {
+                let (a, b) = (number(&left), number(&right));
+                if matches!(op, BinaryOp::Div | BinaryOp::Mod) && b == 0.0 {
+                    return Err(EvalError::DivisionByZero { span });
+                }
+                Ok(Value::Float(match op {
+                    BinaryOp::Add => a + b,
+                    BinaryOp::Sub => a - b,
+                    BinaryOp::Mul => a * b,
+                    BinaryOp::Div => a / b,
+                    BinaryOp::Mod => a.rem_euclid(b),
+                    _ => a.powf(b),
+                }))
+            }
+
             (BinaryOp::Eq, a, b) => Ok(Value::Bool(values_equal(&a, &b))),
             (BinaryOp::Ne, a, b) => Ok(Value::Bool(!values_equal(&a, &b))),
             (BinaryOp::Lt, Value::Int(a), Value::Int(b)) => Ok(Value::Bool(a < b)),
             (BinaryOp::Le, Value::Int(a), Value::Int(b)) => Ok(Value::Bool(a <= b)),
             (BinaryOp::Gt, Value::Int(a), Value::Int(b)) => Ok(Value::Bool(a > b)),
             (BinaryOp::Ge, Value::Int(a), Value::Int(b)) => Ok(Value::Bool(a >= b)),
+            (
+                op @ (BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge),
+                left @ (Value::Int(_) | Value::Float(_)),
+                right @ (Value::Int(_) | Value::Float(_)),
+            ) => {
+                let (a, b) = (number(&left), number(&right));
+                Ok(Value::Bool(match op {
+                    BinaryOp::Lt => a < b,
+                    BinaryOp::Le => a <= b,
+                    BinaryOp::Gt => a > b,
+                    _ => a >= b,
+                }))
+            }
+            (BinaryOp::Lt, Value::Duration(a), Value::Duration(b)) => {
+                Ok(Value::Bool(a.beats < b.beats))
+            }
+            (BinaryOp::Le, Value::Duration(a), Value::Duration(b)) => {
+                Ok(Value::Bool(a.beats <= b.beats))
+            }
+            (BinaryOp::Gt, Value::Duration(a), Value::Duration(b)) => {
+                Ok(Value::Bool(a.beats > b.beats))
+            }
+            (BinaryOp::Ge, Value::Duration(a), Value::Duration(b)) => {
+                Ok(Value::Bool(a.beats >= b.beats))
+            }
             (BinaryOp::And, Value::Bool(a), Value::Bool(b)) => Ok(Value::Bool(a && b)),
             (BinaryOp::Or, Value::Bool(a), Value::Bool(b)) => Ok(Value::Bool(a || b)),
 
@@ -1210,6 +2105,40 @@ impl Evaluator {
                 }))
             }
 
+            // Block overlay: both blocks play at once, as one slot of the part
+            (BinaryOp::Overlay, Value::Block(a), Value::Block(b)) => {
+                let mut voices = match a.slots.as_slice() {
+                    [SlotValue::Overlay { voices }] => voices.clone(),
+                    _ => vec![a],
+                };
+                voices.push(b);
+                let overlay = SlotValue::Overlay { voices };
+                let beats = overlay.duration_beats().unwrap_or(0.0);
+                Ok(Value::Block(BlockValue::with_beats(vec![overlay], beats)))
+            }
+
+            // Sections join into a song, played one after another
+            (BinaryOp::Concat, Value::Section(a), Value::Section(b)) => {
+                Ok(Value::Song(SongValue::new(vec![a, b])))
+            }
+            // A song's master effects stay on its own sections, as a bus
+            (BinaryOp::Concat, Value::Song(mut a), Value::Section(b)) => {
+                own_master(&mut a);
+                a.sections.push(b);
+                Ok(Value::Song(a))
+            }
+            (BinaryOp::Concat, Value::Section(a), Value::Song(mut b)) => {
+                own_master(&mut b);
+                b.sections.insert(0, a);
+                Ok(Value::Song(b))
+            }
+            (BinaryOp::Concat, Value::Song(mut a), Value::Song(mut b)) => {
+                own_master(&mut a);
+                own_master(&mut b);
+                a.sections.extend(b.sections);
+                Ok(Value::Song(a))
+            }
+
             // Array concatenation
             (BinaryOp::Concat, Value::Array(a), Value::Array(b)) => {
                 let mut arr = a;
@@ -1220,6 +2149,20 @@ impl Evaluator {
             // String concatenation
             (BinaryOp::Concat, Value::String(a), Value::String(b)) => Ok(Value::String(a + &b)),
 
+            // Ranges of whole numbers, up to but not including the end
+            (BinaryOp::Range, Value::Int(a), Value::Int(b)) => match b.checked_sub(a) {
+                Some(len) if len <= MAX_RANGE_LEN => {
+                    Ok(Value::Array((a..b).map(Value::Int).collect()))
+                }
+                _ => Err(EvalError::Custom {
+                    message: format!(
+                        "a range can have at most {} numbers, got {}..{}",
+                        MAX_RANGE_LEN, a, b
+                    ),
+                    span,
+                }),
+            },
+
             // Function composition: f >> g means apply f first, then g
             (BinaryOp::Compose, f, g) => {
                 // Both operands should be callable (Closure, Builtin, or Composed)
@@ -1241,10 +2184,21 @@ impl Evaluator {
                 new_arr.extend(arr);
                 Ok(Value::Array(new_arr))
             }
+            // LFO addition: PitchLfo 5 0.2 + AmpLfo 4 0.3 => Array of LFOs
+            (BinaryOp::Add, Value::Lfo(a), Value::Lfo(b)) => {
+                Ok(Value::Array(vec![Value::Lfo(a), Value::Lfo(b)]))
+            }
+            (BinaryOp::Add, Value::Array(arr), Value::Lfo(lfo)) => {
+                let mut new_arr = arr;
+                new_arr.push(Value::Lfo(lfo));
+                Ok(Value::Array(new_arr))
+            }
             // Handle Builtin oscillators (auto-call them)
             (BinaryOp::Add, Value::Builtin(f), Value::Builtin(g)) => {
-                if let (Ok(Value::Oscillator(a)), Ok(Value::Oscillator(b))) = (f(vec![]), g(vec![]))
-                {
+                if let (Ok(Value::Oscillator(a)), Ok(Value::Oscillator(b))) = (
+                    f.call(vec![], &CallSite::new(span)),
+                    g.call(vec![], &CallSite::new(span)),
+                ) {
                     Ok(Value::Array(vec![
                         Value::Oscillator(a),
                         Value::Oscillator(b),
@@ -1258,7 +2212,7 @@ impl Evaluator {
                 }
             }
             (BinaryOp::Add, Value::Builtin(f), Value::Oscillator(b)) => {
-                if let Ok(Value::Oscillator(a)) = f(vec![]) {
+                if let Ok(Value::Oscillator(a)) = f.call(vec![], &CallSite::new(span)) {
                     Ok(Value::Array(vec![
                         Value::Oscillator(a),
                         Value::Oscillator(b),
@@ -1272,7 +2226,7 @@ impl Evaluator {
                 }
             }
             (BinaryOp::Add, Value::Oscillator(a), Value::Builtin(g)) => {
-                if let Ok(Value::Oscillator(b)) = g(vec![]) {
+                if let Ok(Value::Oscillator(b)) = g.call(vec![], &CallSite::new(span)) {
                     Ok(Value::Array(vec![
                         Value::Oscillator(a),
                         Value::Oscillator(b),
@@ -1286,7 +2240,7 @@ impl Evaluator {
                 }
             }
             (BinaryOp::Add, Value::Array(arr), Value::Builtin(f)) => {
-                if let Ok(Value::Oscillator(osc)) = f(vec![]) {
+                if let Ok(Value::Oscillator(osc)) = f.call(vec![], &CallSite::new(span)) {
                     let mut new_arr = arr;
                     new_arr.push(Value::Oscillator(osc));
                     Ok(Value::Array(new_arr))
@@ -1299,7 +2253,7 @@ impl Evaluator {
                 }
             }
             (BinaryOp::Add, Value::Builtin(f), Value::Array(arr)) => {
-                if let Ok(Value::Oscillator(osc)) = f(vec![]) {
+                if let Ok(Value::Oscillator(osc)) = f.call(vec![], &CallSite::new(span)) {
                     let mut new_arr = vec![Value::Oscillator(osc)];
                     new_arr.extend(arr);
                     Ok(Value::Array(new_arr))
@@ -1330,6 +2284,9 @@ impl Evaluator {
         match (op, operand) {
             (UnaryOp::Neg, Value::Int(n)) => Ok(Value::Int(-n)),
             (UnaryOp::Neg, Value::Float(n)) => Ok(Value::Float(-n)),
+            (UnaryOp::Neg, Value::Interval(i)) => {
+                Ok(Value::Interval(IntervalValue { cents: -i.cents }))
+            }
             (UnaryOp::Not, Value::Bool(b)) => Ok(Value::Bool(!b)),
             _ => Err(EvalError::TypeError {
                 expected: "numeric or boolean".to_string(),
@@ -1421,22 +2378,16 @@ impl Evaluator {
     }
 }
 
-fn values_equal(a: &Value, b: &Value) -> bool {
-    match (a, b) {
-        (Value::Int(a), Value::Int(b)) => a == b,
-        (Value::Float(a), Value::Float(b)) => (a - b).abs() < f64::EPSILON,
-        (Value::String(a), Value::String(b)) => a == b,
-        (Value::Bool(a), Value::Bool(b)) => a == b,
-        (Value::Unit, Value::Unit) => true,
-        _ => false,
-    }
-}
-
 impl Evaluator {
     /// Get a binding from the environment by name
     pub fn get_binding(&self, name: &str) -> Option<Value> {
         self.env.borrow().lookup(&intern(name))
     }
+
+    /// Length of a bar in beats under `set time_signature`, 4 without one
+    fn beats_per_bar(&self) -> f64 {
+        bar_beats(self.get_binding("time_signature"))
+    }
 }
 
 impl Default for Evaluator {
@@ -1445,6 +2396,95 @@ impl Default for Evaluator {
     }
 }
 
+/// Apply a function value from inside a builtin, such as the closure given
+/// to `map`
+///
+/// Builtins don't hold the evaluator that called them, so the function is
+/// applied by a fresh one that sees the bindings in scope at the call.
+pub(crate) fn apply_function(
+    func: Value,
+    args: Vec<Value>,
+    call: &CallSite,
+) -> Result<Value, EvalError> {
+    let mut evaluator = Evaluator {
+        env: call
+            .scope
+            .clone()
+            .unwrap_or_else(|| Rc::new(RefCell::new(Env::new()))),
+        modules: ModuleRegistry::new(),
+        base_dir: None,
+        loader: ModuleLoader::new(PathBuf::from(".")),
+        warnings: Vec::new(),
+        key_scale: None,
+        metadata: SongMetadata::default(),
+        render_targets: Vec::new(),
+        prelude_names: HashSet::new(),
+    };
+    evaluator.apply(func, args, call)
+}
+
+/// Give the hits of a percussion block the keys of a kit
+fn apply_kit(kit: &KitValue, block: &BlockValue, span: Span) -> Result<BlockValue, EvalError> {
+    fn apply(kit: &KitValue, slot: &mut SlotValue, span: Span) -> Result<(), EvalError> {
+        match slot {
+            SlotValue::Note { meta, .. } => {
+                if let Some(hit) = &meta.hit {
+                    meta.drum_key = Some(kit.key_for(hit).ok_or_else(|| EvalError::Custom {
+                        message: format!("the kit has no hit `{}`", hit),
+                        span,
+                    })?);
+                }
+            }
+            SlotValue::Tuplet { slots, .. } => {
+                for slot in slots {
+                    apply(kit, slot, span)?;
+                }
+            }
+            SlotValue::Overlay { voices } => {
+                for slot in voices.iter_mut().flat_map(|voice| &mut voice.slots) {
+                    apply(kit, slot, span)?;
+                }
+            }
+            SlotValue::Rest { .. } | SlotValue::Chord { .. } => {}
+        }
+        Ok(())
+    }
+
+    let mut block = block.clone();
+    for slot in &mut block.slots {
+        apply(kit, slot, span)?;
+    }
+    Ok(block)
+}
+
+/// Move a song's master effects onto a bus named after its first section,
+/// before it joins other sections
+fn own_master(song: &mut SongValue) {
+    let name = song
+        .sections
+        .first()
+        .map(|section| section.name.clone())
+        .unwrap_or_default();
+    song.master_to_bus(&name);
+}
+
+/// An Int or Float as a float, for mixed arithmetic
+fn number(value: &Value) -> f64 {
+    match value {
+        Value::Int(n) => *n as f64,
+        Value::Float(x) => *x,
+        _ => f64::NAN,
+    }
+}
+
+/// Value of a parameter default written in a builtin signature
+fn default_value(default: &str) -> Value {
+    match default.parse() {
+        Ok(int) => Value::Int(int),
+        Err(_) => default.parse().map_or(Value::Unit, Value::Float),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use relanote_parser::parse;
@@ -1459,6 +2499,17 @@ mod tests {
         assert!(matches!(result, Value::Int(42)));
     }
 
+    #[test]
+    fn test_registry_builtins_are_bound() {
+        let unbound: Vec<_> = relanote_meta::BUILTINS
+            .iter()
+            .map(|builtin| builtin.name)
+            .filter(|name| implementation(name).is_none())
+            .collect();
+        // Typed so programs can mention them, but not evaluated yet
+        assert_eq!(unbound, ["compose", "play"]);
+    }
+
     #[test]
     fn test_eval_let() {
         let (program, diagnostics) = parse("let x = 42 in x");
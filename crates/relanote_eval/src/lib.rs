@@ -4,12 +4,16 @@ mod builtins;
 mod env;
 mod error;
 mod eval;
+mod patch;
 pub mod value;
 
 pub use env::Env;
 pub use error::EvalError;
 pub use eval::Evaluator;
+pub use patch::ProgramDiff;
 pub use value::{
-    AbsolutePitchValue, BlockValue, DynamicValue, PartValue, SectionValue, SlotValue, SongValue,
-    Value,
+    drum_key, gm_drum_key, pitch_name, tuplet_shares, AbsolutePitchValue, AutomationValue,
+    BlockValue, Builtin, BusValue, CallSite, DrumMapValue, DuckValue, DynamicValue, EffectValue,
+    EnvelopeValue, KitValue, NoteMeta, PartEnvelope, PartValue, PartialCall, RenderTarget,
+    ScaleDegree, SectionValue, SendValue, SlotValue, SongMetadata, SongValue, Swing, Value,
 };
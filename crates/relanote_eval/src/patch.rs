@@ -0,0 +1,309 @@
+//! What an edit changes in a program, so an evaluator can bring its
+//! evaluation of the old version up to date without starting over
+
+use std::collections::HashSet;
+
+use relanote_ast::*;
+use relanote_core::{InternedStr, Spanned};
+
+/// The top-level items that differ between two versions of a program
+///
+/// Items are compared by their source text, so an item that only moved
+/// (because lines were added above it) is unchanged.
+pub struct ProgramDiff<'a> {
+    program: &'a Program,
+    /// For each item of the new program, whether the old one has no item
+    /// with the same text
+    changed: Vec<bool>,
+    /// Items of the old program the new one has no item for
+    removed: Vec<&'a Spanned<Item>>,
+}
+
+/// How to update an evaluation of the old program for the new one
+pub(crate) enum Plan {
+    /// Unbind the names only removed items defined, then evaluate the
+    /// `stale` items of the new program again, in order
+    Patch {
+        stale: Vec<usize>,
+        unbound: Vec<InternedStr>,
+    },
+    /// Evaluate the whole program again
+    Full,
+}
+
+impl<'a> ProgramDiff<'a> {
+    /// The changes from `old`, parsed from `old_source`, to `new`, parsed
+    /// from `new_source`
+    pub fn between(old_source: &str, old: &'a Program, new_source: &str, new: &'a Program) -> Self {
+        let old_texts: Vec<Option<&str>> = old.items.iter().map(|i| text(old_source, i)).collect();
+        let new_texts: Vec<Option<&str>> = new.items.iter().map(|i| text(new_source, i)).collect();
+        let same = |o: usize, n: usize| old_texts[o].is_some() && old_texts[o] == new_texts[n];
+
+        // Longest common subsequence of the items' texts
+        let (m, n) = (old_texts.len(), new_texts.len());
+        let mut lengths = vec![vec![0usize; n + 1]; m + 1];
+        for o in (0..m).rev() {
+            for k in (0..n).rev() {
+                lengths[o][k] = if same(o, k) {
+                    lengths[o + 1][k + 1] + 1
+                } else {
+                    lengths[o + 1][k].max(lengths[o][k + 1])
+                };
+            }
+        }
+
+        let mut changed = vec![true; n];
+        let mut removed = Vec::new();
+        let (mut o, mut k) = (0, 0);
+        while o < m && k < n {
+            if same(o, k) {
+                changed[k] = false;
+                o += 1;
+                k += 1;
+            } else if lengths[o + 1][k] >= lengths[o][k + 1] {
+                removed.push(&old.items[o]);
+                o += 1;
+            } else {
+                k += 1;
+            }
+        }
+        removed.extend(&old.items[o..]);
+
+        Self {
+            program: new,
+            changed,
+            removed,
+        }
+    }
+
+    /// The new program
+    pub fn program(&self) -> &'a Program {
+        self.program
+    }
+
+    /// Whether the two versions have the same items
+    pub fn is_empty(&self) -> bool {
+        self.removed.is_empty() && !self.changed.contains(&true)
+    }
+
+    /// Which items to evaluate again
+    ///
+    /// An item is stale when it changed, when it defines or uses a name a
+    /// stale or removed item defined, or when it is a `render` or the last
+    /// item, whose value is the program's. Items that set up everything
+    /// after them (`set`, `use`, `mod`, `meta`) can't be patched: changing
+    /// one, or anything before one, evaluates the whole program again.
+    pub(crate) fn plan(&self) -> Plan {
+        let items = &self.program.items;
+        if self.removed.iter().any(|item| sets_up(&item.node))
+            || items
+                .iter()
+                .zip(&self.changed)
+                .any(|(item, changed)| *changed && sets_up(&item.node))
+        {
+            return Plan::Full;
+        }
+
+        let defs: Vec<Vec<InternedStr>> = items.iter().map(|i| defined(&i.node)).collect();
+        let uses: Vec<HashSet<InternedStr>> = items.iter().map(used).collect();
+        let defines = |k: usize, name: &InternedStr| defs[k].contains(name);
+        let last = items
+            .iter()
+            .rposition(|item| !matches!(item.node, Item::Test(_)));
+
+        let mut stale: Vec<bool> = self.changed.clone();
+        for (k, item) in items.iter().enumerate() {
+            if matches!(item.node, Item::Render(_)) || Some(k) == last {
+                stale[k] = true;
+            }
+        }
+        let mut dirty: HashSet<InternedStr> = HashSet::new();
+        for item in &self.removed {
+            dirty.extend(defined(&item.node));
+        }
+
+        loop {
+            let mut grew = false;
+            for k in 0..items.len() {
+                if !stale[k]
+                    && (uses[k].iter().any(|name| dirty.contains(name))
+                        || defs[k].iter().any(|name| dirty.contains(name)))
+                {
+                    stale[k] = true;
+                    grew = true;
+                }
+                if !stale[k] {
+                    continue;
+                }
+                dirty.extend(defs[k].iter().copied());
+
+                // The environment holds the last binding of each name; when
+                // a stale item uses a name defined again at or after it, the
+                // binding it saw has to be evaluated again first
+                for name in &uses[k] {
+                    if !(k..items.len()).any(|later| defines(later, name)) {
+                        continue;
+                    }
+                    match (0..k).rev().find(|&earlier| defines(earlier, name)) {
+                        Some(earlier) if !stale[earlier] => {
+                            stale[earlier] = true;
+                            grew = true;
+                        }
+                        Some(_) => {}
+                        // The program shadows a builtin or prelude binding
+                        None => return Plan::Full,
+                    }
+                }
+            }
+            if !grew {
+                break;
+            }
+        }
+
+        let stale: Vec<usize> = (0..items.len()).filter(|&k| stale[k]).collect();
+        let set_up_last = items.iter().rposition(|item| sets_up(&item.node));
+        if stale.first().zip(set_up_last).is_some_and(|(s, u)| *s < u) {
+            return Plan::Full;
+        }
+
+        let mut unbound: Vec<InternedStr> = dirty
+            .into_iter()
+            .filter(|name| !(0..items.len()).any(|k| defines(k, name)))
+            .collect();
+        unbound.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+        Plan::Patch { stale, unbound }
+    }
+}
+
+/// The source text of an item
+fn text<'s>(source: &'s str, item: &Spanned<Item>) -> Option<&'s str> {
+    source.get(item.span.start..item.span.end)
+}
+
+/// Whether an item changes how everything after it evaluates
+fn sets_up(item: &Item) -> bool {
+    matches!(
+        item,
+        Item::SetBinding(_) | Item::Use(_) | Item::Mod(_) | Item::Meta(_)
+    )
+}
+
+/// The names an item binds; a glob `use` binds names only its module knows
+fn defined(item: &Item) -> Vec<InternedStr> {
+    match item {
+        Item::LetBinding(binding) => {
+            let mut names = PatternNames::default();
+            names.visit_pattern(&binding.pattern);
+            names.names
+        }
+        Item::Export(ExportDecl::Definition(def)) => defined(def),
+        Item::SetBinding(binding) => vec![binding.name.name],
+        Item::Use(use_decl) => match &use_decl.path.kind {
+            UseKind::Simple => use_decl
+                .path
+                .segments
+                .last()
+                .map(|s| s.name)
+                .into_iter()
+                .collect(),
+            UseKind::Group(items) => items
+                .iter()
+                .map(|item| item.alias.as_ref().unwrap_or(&item.name).name)
+                .collect(),
+            UseKind::Glob => Vec::new(),
+        },
+        Item::Mod(mod_decl) => vec![mod_decl.name.name],
+        _ => item
+            .defined_name()
+            .map(|ident| ident.name)
+            .into_iter()
+            .collect(),
+    }
+}
+
+/// The names an item refers to
+fn used(item: &Spanned<Item>) -> HashSet<InternedStr> {
+    let mut names = Names::default();
+    names.visit_item(item);
+    names.names
+}
+
+#[derive(Default)]
+struct PatternNames {
+    names: Vec<InternedStr>,
+}
+
+impl Visitor for PatternNames {
+    fn visit_pattern(&mut self, pattern: &Spanned<Pattern>) {
+        if let Pattern::Ident(ident) = &pattern.node {
+            self.names.push(ident.name);
+        }
+        walk_pattern(self, pattern);
+    }
+}
+
+#[derive(Default)]
+struct Names {
+    names: HashSet<InternedStr>,
+}
+
+impl Visitor for Names {
+    fn visit_expr(&mut self, expr: &Spanned<Expr>) {
+        if let Expr::Ident(ident) = &expr.node {
+            self.names.insert(ident.name);
+        }
+        walk_expr(self, expr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The items of `new` to evaluate again after editing `old`, or `None`
+    /// for the whole program
+    fn stale(old: &str, new: &str) -> Option<Vec<usize>> {
+        let (old_program, _) = relanote_parser::parse(old);
+        let (new_program, _) = relanote_parser::parse(new);
+        match ProgramDiff::between(old, &old_program, new, &new_program).plan() {
+            Plan::Patch { stale, .. } => Some(stale),
+            Plan::Full => None,
+        }
+    }
+
+    #[test]
+    fn test_edit_reevaluates_what_depends_on_it() {
+        let old = "set tempo = 120\nlet bass = | R R |\nlet riff = | R M3 |\nlet song = layer [riff, bass]\nsong";
+        // Lines added above only move the items
+        let moved = format!("\n\n{}", old);
+        assert_eq!(stale(old, &moved), Some(vec![4]));
+
+        let new = old.replace("| R M3 |", "| R m3 P5 |");
+        assert_eq!(stale(old, &new), Some(vec![2, 3, 4]));
+        let new = old.replace("| R R |", "| R |");
+        assert_eq!(stale(old, &new), Some(vec![1, 3, 4]));
+    }
+
+    #[test]
+    fn test_shadowed_names() {
+        let old = "let riff = | R |\nlet riff = riff ++ riff\nlet x = | P5 |\nriff";
+        // The second `riff` reads the first, which the environment no
+        // longer holds
+        let new = old.replace("riff ++ riff", "riff ++ riff ++ riff");
+        assert_eq!(stale(old, &new), Some(vec![0, 1, 3]));
+        // Shadowing the prelude can't be patched
+        let old = "let twice = \\b -> b ++ b\nlet reverse = twice\n| R M3 | |> reverse";
+        let new = old.replace("= twice", "= \\b -> twice (reverse b)");
+        assert_eq!(stale(old, &new), None);
+    }
+
+    #[test]
+    fn test_set_up_items_evaluate_everything() {
+        let old = "set tempo = 120\nlet riff = | R M3 |\nriff";
+        assert_eq!(stale(old, &old.replace("120", "90")), None);
+        assert_eq!(stale(old, &old.replace("set tempo = 120\n", "")), None);
+        // Items before a `set` saw the settings before it
+        let old = "let riff = | R M3 |\nset tempo = 120\nriff";
+        assert_eq!(stale(old, &old.replace("M3", "P5")), None);
+    }
+}
@@ -3,12 +3,16 @@
 use std::cell::RefCell;
 use std::rc::Rc;
 
-use relanote_ast::{AbsolutePitchLit, Articulation, Expr, IntervalLit};
-use relanote_core::{InternedStr, Spanned};
+use relanote_ast::{AbsolutePitchLit, Articulation, Dynamic, Expr, IntervalLit};
+use relanote_core::{intern, InternedStr, Span, Spanned};
+use relanote_types::BuiltinParam;
 
 use crate::env::Env;
 
 /// Runtime value
+// Parts carry all of their render settings inline; they are cloned far less
+// often than they are read, so boxing the variant is not worth the indirection.
+#[allow(clippy::large_enum_variant)]
 #[derive(Clone, Debug)]
 pub enum Value {
     Unit,
@@ -28,16 +32,19 @@ pub enum Value {
     Song(SongValue),
     Articulation(Articulation),
     Envelope(EnvelopeValue),
+    Duration(DurationValue),
     Dynamic(DynamicValue),
 
     // Synth values
     Synth(SynthValue),
     Oscillator(OscillatorValue),
     Filter(FilterValue),
+    Lfo(LfoValue),
     ADSR(ADSREnvelope),
 
     // Effect values
     DistortionType(DistortionType),
+    Bus(BusValue),
 
     // Collections
     Array(Vec<Value>),
@@ -45,13 +52,35 @@ pub enum Value {
 
     // Functions
     Closure(Closure),
-    Builtin(BuiltinFn),
+    Builtin(Builtin),
+    /// A builtin given only some of its arguments
+    Partial(PartialCall),
     /// Composed functions: f >> g means apply f first, then g
     Composed(Box<Value>, Box<Value>),
 
     // Scale applicator: created by `in Scale` expression
     // When applied to a block, transforms <n> references using the scale
     InScaleApplicator(ScaleValue),
+
+    // Drum kit: created by `kit { ... }` expression
+    // When applied to a block, gives its hits their percussion keys
+    Kit(KitValue),
+}
+
+impl Value {
+    /// The song that plays this value: a block plays as a piano part, and a
+    /// part as the only part of a section. None for values that aren't music
+    pub fn into_song(self) -> Option<SongValue> {
+        let section = |part| SectionValue::new("Main", vec![part]);
+        let section = match self {
+            Value::Song(song) => return Some(song),
+            Value::Section(section) => section,
+            Value::Part(part) => section(part),
+            Value::Block(block) => section(PartValue::new("Piano", vec![block])),
+            _ => return None,
+        };
+        Some(SongValue::new(vec![section]))
+    }
 }
 
 /// Closure (lambda with captured environment)
@@ -68,11 +97,157 @@ impl std::fmt::Debug for Closure {
     }
 }
 
-/// Builtin function
-pub type BuiltinFn = fn(Vec<Value>) -> Result<Value, crate::error::EvalError>;
+/// Whether two values are equal, for `==` and `assert_eq`
+///
+/// Intervals a fraction of a cent apart are equal, so scale steps computed
+/// in different ways compare equal. Functions are never equal.
+pub(crate) fn values_equal(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Int(a), Value::Int(b)) => a == b,
+        (Value::Float(a), Value::Float(b)) => (a - b).abs() < f64::EPSILON,
+        (Value::String(a), Value::String(b)) => a == b,
+        (Value::Bool(a), Value::Bool(b)) => a == b,
+        (Value::Unit, Value::Unit) => true,
+        (Value::Interval(a), Value::Interval(b)) => (a.cents - b.cents).abs() < 0.01,
+        (Value::Duration(a), Value::Duration(b)) => (a.beats - b.beats).abs() < 1e-9,
+        (Value::AbsolutePitch(a), Value::AbsolutePitch(b)) => a.midi_note == b.midi_note,
+        (Value::Block(a), Value::Block(b)) => a == b,
+        (Value::Array(a), Value::Array(b)) | (Value::Tuple(a), Value::Tuple(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| values_equal(a, b))
+        }
+        _ => false,
+    }
+}
+
+/// Builtin function, given its arguments and where it was called
+pub type BuiltinFn = fn(Vec<Value>, &CallSite) -> Result<Value, crate::error::EvalError>;
 
-/// Interval value (resolved to cents, 100 cents = 1 semitone)
+/// A builtin function with the parameters of its signature
+#[derive(Clone)]
+pub struct Builtin {
+    pub name: &'static str,
+    pub func: BuiltinFn,
+    /// Parameters, when every signature of the builtin takes the same
+    /// number; only then can it be applied partially or by name
+    pub params: Option<Rc<[BuiltinParam]>>,
+}
+
+impl Builtin {
+    pub fn new(name: &'static str, func: BuiltinFn) -> Self {
+        Self {
+            name,
+            func,
+            params: relanote_types::builtin_params(name).map(Rc::from),
+        }
+    }
+
+    /// Call the builtin with all of its arguments
+    pub fn call(
+        &self,
+        args: Vec<Value>,
+        call: &CallSite,
+    ) -> Result<Value, crate::error::EvalError> {
+        (self.func)(args, call)
+    }
+}
+
+impl std::fmt::Debug for Builtin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<builtin {}>", self.name)
+    }
+}
+
+/// A builtin waiting for the rest of its arguments
+///
+/// Created by calling a builtin with fewer arguments than it takes, or by
+/// leaving out named arguments without defaults.
+#[derive(Clone, Debug)]
+pub struct PartialCall {
+    pub builtin: Builtin,
+    /// Arguments in parameter order, with where they were written; `None`
+    /// for those still to come
+    pub args: Vec<Option<(Value, Span)>>,
+}
+
+impl PartialCall {
+    /// Number of arguments still to come
+    pub fn missing(&self) -> usize {
+        self.args.iter().filter(|arg| arg.is_none()).count()
+    }
+}
+
+/// Where a function is called: the call itself (the pipe stage, for
+/// `x |> f a`), each argument, in the order the function receives them,
+/// and the scope the call is made in
 #[derive(Clone, Debug)]
+pub struct CallSite {
+    pub span: Span,
+    pub args: Vec<Span>,
+    /// Bindings visible at the call, for builtins that read the key or the
+    /// prelude's chords
+    pub scope: Option<Rc<RefCell<Env>>>,
+}
+
+impl CallSite {
+    /// A call whose arguments have no spans, such as one made by the
+    /// evaluator itself
+    pub fn new(span: Span) -> Self {
+        Self {
+            span,
+            args: Vec::new(),
+            scope: None,
+        }
+    }
+
+    /// Span of argument `index`, or of the whole call when it isn't known
+    pub fn arg(&self, index: usize) -> Span {
+        self.args.get(index).copied().unwrap_or(self.span)
+    }
+
+    /// Value bound to `name` where the call is made
+    pub fn lookup(&self, name: &str) -> Option<Value> {
+        self.scope
+            .as_ref()
+            .and_then(|scope| scope.borrow().lookup(&intern(name)))
+    }
+
+    /// MIDI note of `set key` at the call, middle C without one
+    pub fn key(&self) -> u8 {
+        match self.lookup("key") {
+            Some(Value::AbsolutePitch(pitch)) => pitch.midi_note,
+            _ => 60,
+        }
+    }
+
+    /// Length of a bar in beats under `set time_signature` at the call
+    pub fn beats_per_bar(&self) -> f64 {
+        bar_beats(self.lookup("time_signature"))
+    }
+}
+
+/// Length of a bar in beats for a `set time_signature` value, 4 without one
+pub(crate) fn bar_beats(time_signature: Option<Value>) -> f64 {
+    match time_signature {
+        Some(Value::Tuple(items)) => match items.as_slice() {
+            [Value::Int(beats @ 1..), Value::Int(unit @ 1..)] => *beats as f64 * 4.0 / *unit as f64,
+            _ => 4.0,
+        },
+        _ => 4.0,
+    }
+}
+
+/// Length of time, resolved to beats (a quarter note is one beat)
+///
+/// Bars are resolved with the time signature set when they are evaluated.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DurationValue {
+    pub beats: f64,
+}
+
+/// Interval value (resolved to cents, 100 cents = 1 semitone)
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IntervalValue {
     pub cents: f64,
 }
@@ -115,6 +290,18 @@ impl AbsolutePitchValue {
     }
 }
 
+/// Note name of a MIDI pitch, such as `C#4`
+pub fn pitch_name(pitch: i32) -> String {
+    const NAMES: [&str; 12] = [
+        "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+    ];
+    format!(
+        "{}{}",
+        NAMES[pitch.rem_euclid(12) as usize],
+        pitch.div_euclid(12) - 1
+    )
+}
+
 impl From<&AbsolutePitchLit> for AbsolutePitchValue {
     fn from(lit: &AbsolutePitchLit) -> Self {
         Self {
@@ -130,6 +317,53 @@ pub struct ScaleValue {
     pub intervals: Vec<IntervalValue>,
 }
 
+impl ScaleValue {
+    /// Names of the pitch classes (`m3`, `A4`, ...) of `blocks` that aren't
+    /// in the scale, lowest first
+    pub fn notes_outside(&self, blocks: &[BlockValue]) -> Vec<&'static str> {
+        const NAMES: [&str; 12] = [
+            "R", "m2", "M2", "m3", "M3", "P4", "A4", "P5", "m6", "M6", "m7", "M7",
+        ];
+        fn collect(slots: &[SlotValue], out: &mut Vec<i32>) {
+            for slot in slots {
+                match slot {
+                    SlotValue::Note { interval, .. } => {
+                        out.push(interval.semitones().round() as i32)
+                    }
+                    SlotValue::Chord { intervals, .. } => {
+                        out.extend(intervals.iter().map(|i| i.semitones().round() as i32))
+                    }
+                    SlotValue::Tuplet { slots, .. } => collect(slots, out),
+                    SlotValue::Overlay { voices } => {
+                        for voice in voices {
+                            collect(&voice.slots, out);
+                        }
+                    }
+                    SlotValue::Rest { .. } => {}
+                }
+            }
+        }
+        let mut semitones = Vec::new();
+        for block in blocks {
+            collect(&block.slots, &mut semitones);
+        }
+
+        let in_scale: Vec<i32> = self
+            .intervals
+            .iter()
+            .map(|i| (i.semitones().round() as i32).rem_euclid(12))
+            .collect();
+        let mut outside: Vec<i32> = semitones
+            .into_iter()
+            .map(|s| s.rem_euclid(12))
+            .filter(|s| !in_scale.contains(s))
+            .collect();
+        outside.sort_unstable();
+        outside.dedup();
+        outside.iter().map(|s| NAMES[*s as usize]).collect()
+    }
+}
+
 /// Chord value
 #[derive(Clone, Debug)]
 pub struct ChordValue {
@@ -139,7 +373,8 @@ pub struct ChordValue {
 
 /// Block value (sequence of slots)
 /// Rhythm is relative: slots are equally divided within the block's duration.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BlockValue {
     pub slots: Vec<SlotValue>,
     /// Duration in beats (default: 1.0)
@@ -154,16 +389,155 @@ impl BlockValue {
     pub fn with_beats(slots: Vec<SlotValue>, beats: f64) -> Self {
         Self { slots, beats }
     }
+
+    /// Length of the block in beats: each slot's explicit duration, or an
+    /// equal share of the block's duration
+    pub fn total_beats(&self) -> f64 {
+        self.slot_lengths().sum()
+    }
+
+    /// Length of each slot in beats: its explicit duration, or an equal share
+    /// of the block's duration
+    pub fn slot_lengths(&self) -> impl Iterator<Item = f64> + '_ {
+        let share = self.beats / self.slots.len().max(1) as f64;
+        self.slots
+            .iter()
+            .map(move |slot| slot.duration_beats().unwrap_or(share))
+    }
+
+    /// Slot `index` as a block of its own, as long as the slot was
+    pub fn slot_block(&self, index: usize) -> Option<BlockValue> {
+        let beats = self.slot_lengths().nth(index)?;
+        let slot = match &self.slots[index] {
+            SlotValue::Tuplet { slots, .. } => SlotValue::Tuplet {
+                slots: slots.clone(),
+                target_beats: beats,
+            },
+            slot => slot.clone().with_duration(beats),
+        };
+        Some(BlockValue::with_beats(vec![slot], beats))
+    }
+
+    /// The block with each of its slots passed through `f`
+    pub fn map_slots(&self, f: impl FnMut(&SlotValue) -> SlotValue) -> BlockValue {
+        BlockValue {
+            slots: self.slots.iter().map(f).collect(),
+            beats: self.beats,
+        }
+    }
+
+    /// Whether any note plays a drum from a kit
+    pub fn has_drum_hits(&self) -> bool {
+        fn any_hit(slots: &[SlotValue]) -> bool {
+            slots.iter().any(|slot| match slot {
+                SlotValue::Tuplet { slots, .. } => any_hit(slots),
+                SlotValue::Overlay { voices } => voices.iter().any(|voice| any_hit(&voice.slots)),
+                _ => slot.meta().is_some_and(|meta| meta.drum_key.is_some()),
+            })
+        }
+        any_hit(&self.slots)
+    }
+}
+
+/// Per-note performance metadata consumed by renderers
+/// (does not affect the written pitch or rhythm)
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NoteMeta {
+    /// Start time offset in milliseconds (negative = early)
+    pub timing_offset_ms: f64,
+    /// Velocity offset added to the rendered velocity
+    pub velocity_offset: i32,
+    /// Portamento glide time in milliseconds (None = renderer default)
+    pub glide_ms: Option<f64>,
+    /// Swing feel applied to the note's timing when rendered
+    pub swing: Option<Swing>,
+    /// Marker text placed where the note starts (e.g. a song part name)
+    pub marker: Option<String>,
+    /// Lyric syllable sung on the note
+    pub lyric: Option<String>,
+    /// Drum hit written in a percussion block (`x` in `| x - o - |`)
+    pub hit: Option<String>,
+    /// GM percussion key the hit plays, once a kit is applied
+    pub drum_key: Option<u8>,
+    /// Scale degree the note was written as (`<3>`), for `in` to resolve
+    pub degree: Option<ScaleDegree>,
+}
+
+/// A note written as a scale degree (`<3>`, `<4+>`, `<5'>`)
+///
+/// Kept with the note so a scale applied with `in` takes the degree from its
+/// own steps rather than guessing it from the pitch.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ScaleDegree {
+    /// 1-based degree; degrees past the end of the scale go on into the next
+    /// octave
+    pub degree: u8,
+    /// Octave marks: `'` up, `,` down
+    pub octave: i8,
+    /// Accidentals, in cents
+    pub alter_cents: f64,
+    /// Pitch the degree was last resolved to, so a note that has been moved
+    /// since (e.g. transposed) can be told apart
+    pub cents: f64,
+}
+
+impl ScaleDegree {
+    /// Steps of the major scale, which degrees are read in until a scale is
+    /// applied
+    pub const MAJOR: [f64; 7] = [0.0, 200.0, 400.0, 500.0, 700.0, 900.0, 1100.0];
+
+    /// Pitch of the degree in a scale whose steps are given in cents
+    pub fn resolve(&self, steps: &[f64]) -> f64 {
+        let octave_marks = self.octave as f64 * 1200.0 + self.alter_cents;
+        if self.degree == 0 || steps.is_empty() {
+            return octave_marks;
+        }
+        let index = self.degree as usize - 1;
+        let octave = (index / steps.len()) as f64 * 1200.0;
+        steps[index % steps.len()] + octave + octave_marks
+    }
+}
+
+/// Swing feel: the off-beat of each grid pair is delayed
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Swing {
+    /// Where the off-beat falls within a grid pair (0.5 = straight, 0.67 = triplet)
+    pub ratio: f64,
+    /// Length of an on-beat/off-beat pair in beats
+    pub grid_beats: f64,
+}
+
+impl Swing {
+    /// Map a straight position (in beats) to its swung position
+    pub fn warp(&self, beats: f64) -> f64 {
+        if self.grid_beats <= 0.0 {
+            return beats;
+        }
+        let pair_start = (beats / self.grid_beats).floor() * self.grid_beats;
+        let position = (beats - pair_start) / self.grid_beats;
+        let swung = if position < 0.5 {
+            position / 0.5 * self.ratio
+        } else {
+            self.ratio + (position - 0.5) / 0.5 * (1.0 - self.ratio)
+        };
+        pair_start + swung * self.grid_beats
+    }
 }
 
 /// Slot value in a block
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SlotValue {
     Note {
         interval: IntervalValue,
         articulations: Vec<Articulation>,
         /// Explicit duration in beats (used when blocks are concatenated)
         duration_beats: Option<f64>,
+        /// Performance metadata (humanize offsets, etc.)
+        meta: NoteMeta,
     },
     Rest {
         /// Explicit duration in beats (used when blocks are concatenated)
@@ -174,11 +548,17 @@ pub enum SlotValue {
         articulations: Vec<Articulation>,
         /// Explicit duration in beats (used when blocks are concatenated)
         duration_beats: Option<f64>,
+        /// Performance metadata (humanize offsets, etc.)
+        meta: NoteMeta,
     },
+    /// Slots squeezed or stretched to fill `target_beats` together
     Tuplet {
         slots: Vec<SlotValue>,
-        target_beats: i64,
+        target_beats: f64,
     },
+    /// Blocks played at the same time (`a & b`), lasting as long as the
+    /// longest of them
+    Overlay { voices: Vec<BlockValue> },
 }
 
 impl SlotValue {
@@ -190,10 +570,12 @@ impl SlotValue {
                 interval,
                 articulations,
                 duration_beats,
+                meta,
             } => SlotValue::Note {
                 interval,
                 articulations,
                 duration_beats: duration_beats.or(Some(beats)),
+                meta,
             },
             SlotValue::Rest { duration_beats } => SlotValue::Rest {
                 duration_beats: duration_beats.or(Some(beats)),
@@ -202,70 +584,597 @@ impl SlotValue {
                 intervals,
                 articulations,
                 duration_beats,
+                meta,
             } => SlotValue::Chord {
                 intervals,
                 articulations,
                 duration_beats: duration_beats.or(Some(beats)),
+                meta,
             },
-            // Tuplets keep their own duration semantics
-            tuplet @ SlotValue::Tuplet { .. } => tuplet,
+            // Tuplets and overlays keep their own duration semantics
+            slot @ (SlotValue::Tuplet { .. } | SlotValue::Overlay { .. }) => slot,
         }
     }
 
+    /// Performance metadata of a note or chord
+    pub fn meta(&self) -> Option<&NoteMeta> {
+        match self {
+            SlotValue::Note { meta, .. } | SlotValue::Chord { meta, .. } => Some(meta),
+            SlotValue::Rest { .. } | SlotValue::Tuplet { .. } | SlotValue::Overlay { .. } => None,
+        }
+    }
+
+    /// Whether this slot is tied into `next`: one of them carries a tie
+    /// (`R:4~` or `~R:2`) and both sound the same pitches
+    pub fn ties_into(&self, next: &SlotValue) -> bool {
+        let (pitches, articulations, next_pitches, next_articulations) = match (self, next) {
+            (
+                SlotValue::Note {
+                    interval,
+                    articulations,
+                    ..
+                },
+                SlotValue::Note {
+                    interval: next_interval,
+                    articulations: next_articulations,
+                    ..
+                },
+            ) => (
+                std::slice::from_ref(interval),
+                articulations,
+                std::slice::from_ref(next_interval),
+                next_articulations,
+            ),
+            (
+                SlotValue::Chord {
+                    intervals,
+                    articulations,
+                    ..
+                },
+                SlotValue::Chord {
+                    intervals: next_intervals,
+                    articulations: next_articulations,
+                    ..
+                },
+            ) => (
+                intervals.as_slice(),
+                articulations,
+                next_intervals.as_slice(),
+                next_articulations,
+            ),
+            _ => return false,
+        };
+
+        (articulations.contains(&Articulation::Tie)
+            || next_articulations.contains(&Articulation::Tied))
+            && pitches.len() == next_pitches.len()
+            && pitches
+                .iter()
+                .zip(next_pitches)
+                .all(|(a, b)| a.cents == b.cents)
+    }
+
     /// Get explicit duration if set
     pub fn duration_beats(&self) -> Option<f64> {
         match self {
             SlotValue::Note { duration_beats, .. } => *duration_beats,
             SlotValue::Rest { duration_beats } => *duration_beats,
             SlotValue::Chord { duration_beats, .. } => *duration_beats,
-            SlotValue::Tuplet { target_beats, .. } => Some(*target_beats as f64),
+            SlotValue::Tuplet { target_beats, .. } => Some(*target_beats),
+            SlotValue::Overlay { voices } => Some(
+                voices
+                    .iter()
+                    .map(BlockValue::total_beats)
+                    .fold(0.0, f64::max),
+            ),
+        }
+    }
+
+    /// The slot with every pitch it sounds (in cents) passed through `f`
+    pub fn map_cents(&self, f: &impl Fn(f64) -> f64) -> SlotValue {
+        let mut slot = self.clone();
+        match &mut slot {
+            SlotValue::Note { interval, .. } => interval.cents = f(interval.cents),
+            SlotValue::Chord { intervals, .. } => {
+                for interval in intervals {
+                    interval.cents = f(interval.cents);
+                }
+            }
+            SlotValue::Rest { .. } => {}
+            SlotValue::Tuplet { slots, .. } => {
+                for inner in slots {
+                    *inner = inner.map_cents(f);
+                }
+            }
+            SlotValue::Overlay { voices } => {
+                for voice in voices {
+                    *voice = voice.map_slots(|inner| inner.map_cents(f));
+                }
+            }
         }
+        slot
     }
 }
 
+/// Where each slot of a tuplet starts and how long it lasts, as fractions
+/// of the tuplet's length
+///
+/// Slots last one beat unless they have a duration of their own (a nested
+/// tuplet lasts its target), and are scaled together to fill the tuplet.
+pub fn tuplet_shares(slots: &[SlotValue]) -> impl Iterator<Item = (&SlotValue, f64, f64)> {
+    let total = tuplet_nominal_beats(slots);
+    let mut start = 0.0;
+    slots.iter().map(move |slot| {
+        let share = if total > 0.0 {
+            slot.duration_beats().unwrap_or(1.0) / total
+        } else {
+            0.0
+        };
+        let placed = (slot, start, share);
+        start += share;
+        placed
+    })
+}
+
+/// Unscaled length of a tuplet's slots in beats
+pub fn tuplet_nominal_beats(slots: &[SlotValue]) -> f64 {
+    slots
+        .iter()
+        .map(|slot| slot.duration_beats().unwrap_or(1.0))
+        .sum()
+}
+
 /// Part value
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PartValue {
     pub instrument: String,
     pub blocks: Vec<BlockValue>,
-    pub envelope: Option<EnvelopeValue>,
-    /// Reverb send level (0.0 to 1.0, maps to MIDI CC#91 0-127)
-    pub reverb_level: Option<f64>,
+    /// Envelope applied with `apply_env`
+    pub envelope: Option<PartEnvelope>,
     /// Volume level (0.0 to 1.0, maps to MIDI CC#7 0-127)
     pub volume_level: Option<f64>,
-    /// Delay effect parameters
-    pub delay: Option<DelayParams>,
-    /// Phaser effect parameters
-    pub phaser: Option<PhaserParams>,
-    /// Distortion effect parameters
-    pub distortion: Option<DistortionParams>,
+    /// Stereo pan (-1.0 left to 1.0 right, maps to MIDI CC#10 0-127)
+    pub pan_level: Option<f64>,
+    /// Effects the part's sound goes through, in order
+    pub effects: Vec<EffectValue>,
+    /// Buses the part sends to, after its own effects
+    pub sends: Vec<SendValue>,
     /// Synthesizer configuration (for WebAudio output)
     pub synth: Option<SynthValue>,
+    /// Drum mapping (renders on the percussion channel)
+    pub drum_map: Option<DrumMapValue>,
+    /// Controller automation curves (interpolated over the part)
+    pub automation: Vec<AutomationValue>,
+    /// Parts whose notes dip the part's volume (sidechain ducking)
+    pub ducks: Vec<DuckValue>,
+}
+
+impl PartValue {
+    /// A part playing `blocks` with no effects or settings
+    pub fn new(instrument: impl Into<String>, blocks: Vec<BlockValue>) -> Self {
+        Self {
+            instrument: instrument.into(),
+            blocks,
+            envelope: None,
+            volume_level: None,
+            pan_level: None,
+            effects: vec![],
+            sends: vec![],
+            synth: None,
+            drum_map: None,
+            automation: vec![],
+            ducks: vec![],
+        }
+    }
+
+    /// The part with `effect` added to the end of its chain, or in place of
+    /// an effect of the same kind already in it
+    pub fn with_effect(mut self, effect: EffectValue) -> Self {
+        set_effect(&mut self.effects, effect);
+        self
+    }
+
+    /// Reverb level of the part's own reverb
+    pub fn reverb_level(&self) -> Option<f64> {
+        reverb_level(&self.effects)
+    }
+}
+
+/// An effect in a part's or bus's chain
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EffectValue {
+    /// Reverb level (0.0 to 1.0, maps to MIDI CC#91 0-127)
+    Reverb(f64),
+    Delay(DelayParams),
+    Phaser(PhaserParams),
+    Distortion(DistortionParams),
+    Compressor(CompressorParams),
+    Eq(EqParams),
+    Chorus(ChorusParams),
+    /// Stereo width (0.0 mono, 1.0 as is, up to 2.0 wider)
+    Width(f64),
+}
+
+impl EffectValue {
+    pub fn name(&self) -> &'static str {
+        match self {
+            EffectValue::Reverb(_) => "reverb",
+            EffectValue::Delay(_) => "delay",
+            EffectValue::Phaser(_) => "phaser",
+            EffectValue::Distortion(_) => "distortion",
+            EffectValue::Compressor(_) => "compressor",
+            EffectValue::Eq(_) => "eq",
+            EffectValue::Chorus(_) => "chorus",
+            EffectValue::Width(_) => "width",
+        }
+    }
+}
+
+/// Add `effect` to the end of `chain`, or put it in place of the effect of
+/// the same kind, so that applying an effect again changes its settings
+pub fn set_effect(chain: &mut Vec<EffectValue>, effect: EffectValue) {
+    match chain.iter_mut().find(|e| e.name() == effect.name()) {
+        Some(existing) => *existing = effect,
+        None => chain.push(effect),
+    }
+}
+
+/// Level of the reverb in `chain`
+pub fn reverb_level(chain: &[EffectValue]) -> Option<f64> {
+    chain.iter().find_map(|effect| match effect {
+        EffectValue::Reverb(level) => Some(*level),
+        _ => None,
+    })
+}
+
+/// Mix of the chorus in `chain`
+pub fn chorus_level(chain: &[EffectValue]) -> Option<f64> {
+    chain.iter().find_map(|effect| match effect {
+        EffectValue::Chorus(chorus) => Some(chorus.mix),
+        _ => None,
+    })
+}
+
+/// A bus: parts send to it and share its effects, as layered parts share
+/// one reverb
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BusValue {
+    pub name: String,
+    pub effects: Vec<EffectValue>,
+}
+
+/// How much of a part's sound goes to a bus
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SendValue {
+    pub bus: BusValue,
+    /// Send level (0.0 to 1.0)
+    pub level: f64,
+}
+
+/// GM percussion key for a drum synth preset, if the preset is a drum
+pub fn gm_drum_key(preset: &str) -> Option<u8> {
+    let key = match preset {
+        // Kicks
+        "Kick" | "Kick8bit" | "DeepKick" | "PunchyKick" | "SubKick" | "HardKick" | "SoftKick"
+        | "Kick808" | "Kick909" => 36,
+        // Snares
+        "Snare" | "Snare8bit" | "TightSnare" | "FatSnare" | "CrispSnare" | "Snare808"
+        | "Snare909" => 38,
+        "RimShot" | "SideStick" => 37,
+        // Hi-hats
+        "HiHat" | "HiHat8bit" | "ClosedHat" | "TightHat" | "Hat808" | "Hat909" => 42,
+        "PedalHat" => 44,
+        "OpenHat" | "OpenHiHat" | "OpenHat808" | "OpenHat909" => 46,
+        // Toms
+        "FloorTom" => 41,
+        "LowTom" => 45,
+        "Tom" | "MidTom" => 47,
+        "HighTom" => 50,
+        // Cymbals
+        "CrashCymbal" => 49,
+        "RideCymbal" => 51,
+        "ChinaCymbal" => 52,
+        "RideBell" => 53,
+        "SplashCymbal" => 55,
+        // Hand percussion
+        "Tambourine" => 54,
+        "Cowbell" => 56,
+        "Bongo" => 60,
+        "Conga" => 63,
+        "Timbale" => 65,
+        "Shaker" => 70,
+        "Claves" => 75,
+        "WoodBlock" => 76,
+        "Clap808" | "Clap909" => 39,
+        name if name.ends_with("Clap") => 39,
+        _ => return None,
+    };
+    Some(key)
+}
+
+/// GM percussion key a value plays as a drum: a drum synth preset, or a key
+/// given as a number from 0 to 127
+pub fn drum_key(value: &Value) -> Option<u8> {
+    match value {
+        Value::Int(key) if (0..=127).contains(key) => Some(*key as u8),
+        Value::Synth(synth) => synth.drum_key(),
+        _ => None,
+    }
+}
+
+/// Drum kit: the hits of percussion blocks and the GM keys they play
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct KitValue {
+    pub hits: Vec<(String, u8)>,
+}
+
+impl KitValue {
+    /// Percussion key of a hit
+    pub fn key_for(&self, hit: &str) -> Option<u8> {
+        self.hits
+            .iter()
+            .find(|(name, _)| name == hit)
+            .map(|(_, key)| *key)
+    }
+}
+
+/// Drum mapping for a part: notes become GM percussion keys
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DrumMapValue {
+    /// Key used for notes without a pitch-specific mapping
+    pub default_key: Option<u8>,
+    /// Pitch (in cents) to percussion key mappings
+    pub keys: Vec<(f64, u8)>,
+}
+
+impl DrumMapValue {
+    /// Look up the percussion key for a pitch
+    pub fn key_for(&self, cents: f64) -> Option<u8> {
+        self.keys
+            .iter()
+            .find(|(c, _)| (c - cents).abs() < 0.5)
+            .map(|(_, key)| *key)
+            .or(self.default_key)
+    }
+}
+
+/// Controller automation: a curve of levels spread evenly over a part
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AutomationValue {
+    /// MIDI controller number (0-127)
+    pub cc: u8,
+    /// Curve points (0.0 to 1.0); the first is at the start, the last at the end
+    pub points: Vec<f64>,
+}
+
+/// Sidechain ducking: the part's volume dips each time a trigger part starts
+/// a note, then comes back over `RELEASE_BEATS`
+///
+/// The trigger is timed from the start of the part it ducks, as when both
+/// play in the same layer.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DuckValue {
+    pub trigger: PartValue,
+    /// How far the volume dips (0.0 to 1.0)
+    pub amount: f64,
+}
+
+impl DuckValue {
+    /// Beats the volume takes to come back after a trigger note
+    pub const RELEASE_BEATS: f64 = 0.5;
+
+    /// Gain (0.0 to 1.0) at `beat`, given the beats the trigger's notes
+    /// start on, in order
+    pub fn gain_at(&self, onsets: &[f64], beat: f64) -> f64 {
+        let latest = onsets.partition_point(|&onset| onset <= beat);
+        match latest.checked_sub(1) {
+            Some(index) => {
+                let recovered = ((beat - onsets[index]) / Self::RELEASE_BEATS).min(1.0);
+                1.0 - self.amount * (1.0 - recovered)
+            }
+            None => 1.0,
+        }
+    }
+}
+
+impl AutomationValue {
+    /// Curve level at a position (0.0 = start, 1.0 = end), linearly interpolated
+    pub fn level_at(&self, position: f64) -> f64 {
+        match self.points.as_slice() {
+            [] => 0.0,
+            [only] => *only,
+            points => {
+                let scaled = position.clamp(0.0, 1.0) * (points.len() - 1) as f64;
+                let index = (scaled.floor() as usize).min(points.len() - 2);
+                let frac = scaled - index as f64;
+                points[index] + (points[index + 1] - points[index]) * frac
+            }
+        }
+    }
 }
 
 /// Section value
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SectionValue {
     pub name: String,
     pub parts: Vec<PartValue>,
+    /// MIDI note the section's intervals are relative to (`with key: G4`),
+    /// or None for the song's key
+    pub key: Option<u8>,
+    /// Semitones the section's key is moved by (`modulate`)
+    pub modulation: i32,
+    /// Tempo in BPM while the section plays (`with tempo: 90`), or None for
+    /// the song's tempo
+    pub tempo: Option<f64>,
+}
+
+impl SectionValue {
+    pub fn new(name: impl Into<String>, parts: Vec<PartValue>) -> Self {
+        Self {
+            name: name.into(),
+            parts,
+            key: None,
+            modulation: 0,
+            tempo: None,
+        }
+    }
+
+    /// MIDI note the section's intervals are relative to, in a song whose
+    /// key is `song_key`
+    pub fn base_note(&self, song_key: u8) -> u8 {
+        (self.key.unwrap_or(song_key) as i32 + self.modulation).clamp(0, 127) as u8
+    }
 }
 
 /// Song value (final output)
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SongValue {
     pub sections: Vec<SectionValue>,
+    /// Effects on the master bus, which every part and bus ends in
+    pub master: Vec<EffectValue>,
 }
 
-/// Envelope value
+impl SongValue {
+    pub fn new(sections: Vec<SectionValue>) -> Self {
+        Self {
+            sections,
+            master: vec![],
+        }
+    }
+
+    /// Buses the song's parts send to, each once: the first part sending to
+    /// a bus name defines its effects
+    pub fn buses(&self) -> Vec<&BusValue> {
+        let mut buses: Vec<&BusValue> = Vec::new();
+        let sends = self
+            .sections
+            .iter()
+            .flat_map(|section| &section.parts)
+            .flat_map(|part| &part.sends);
+        for send in sends {
+            if !buses.iter().any(|bus| bus.name == send.bus.name) {
+                buses.push(&send.bus);
+            }
+        }
+        buses
+    }
+
+    /// How much reverb `part` is heard with: its own, what it sends to
+    /// buses with reverb, and the master bus's, together; None if it has
+    /// none of them
+    pub fn reverb_level(&self, part: &PartValue) -> Option<f64> {
+        self.heard_level(part, reverb_level)
+    }
+
+    /// How much chorus `part` is heard with, added up as for reverb
+    pub fn chorus_level(&self, part: &PartValue) -> Option<f64> {
+        self.heard_level(part, chorus_level)
+    }
+
+    /// The level `level` finds in `part`'s chain, in the buses it sends to
+    /// (scaled by the send) and on the master bus, added up
+    fn heard_level(
+        &self,
+        part: &PartValue,
+        level: fn(&[EffectValue]) -> Option<f64>,
+    ) -> Option<f64> {
+        let sends = part
+            .sends
+            .iter()
+            .filter_map(|send| Some(send.level * level(&send.bus.effects)?));
+        let levels: Vec<f64> = level(&part.effects)
+            .into_iter()
+            .chain(sends)
+            .chain(level(&self.master))
+            .collect();
+        (!levels.is_empty()).then(|| levels.iter().sum::<f64>().min(1.0))
+    }
+
+    /// Move the master effects onto a bus named `name` that every part
+    /// sends all of its sound to, so they keep applying to just these parts
+    /// once they join other music
+    pub fn master_to_bus(&mut self, name: &str) {
+        if self.master.is_empty() {
+            return;
+        }
+        let bus = BusValue {
+            name: name.to_string(),
+            effects: std::mem::take(&mut self.master),
+        };
+        for part in self.sections.iter_mut().flat_map(|s| &mut s.parts) {
+            part.sends.push(SendValue {
+                bus: bus.clone(),
+                level: 1.0,
+            });
+        }
+    }
+}
+
+/// Song metadata from a `meta { ... }` block
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SongMetadata {
+    pub title: Option<String>,
+    pub composer: Option<String>,
+    pub copyright: Option<String>,
+}
+
+/// A song declared with `render`, one of the program's outputs
 #[derive(Clone, Debug)]
+pub struct RenderTarget {
+    /// Name given to the output: render "verse_demo" = verse
+    pub name: Option<String>,
+    pub song: SongValue,
+    /// The rendered expression, for errors about the output
+    pub span: Span,
+}
+
+/// Envelope value: a dynamics ramp, env(from, to, duration)
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EnvelopeValue {
     pub from: DynamicValue,
     pub to: DynamicValue,
     pub duration_beats: f64,
 }
 
+impl EnvelopeValue {
+    /// Level (0.0 to 1.0) `beats` after the ramp starts, holding the final
+    /// dynamic once the ramp is over
+    pub fn level_at(&self, beats: f64) -> f64 {
+        let from = self.from.to_velocity() as f64 / 127.0;
+        let to = self.to.to_velocity() as f64 / 127.0;
+        if self.duration_beats <= 0.0 {
+            return to;
+        }
+        let position = (beats / self.duration_beats).clamp(0.0, 1.0);
+        from + (to - from) * position
+    }
+}
+
+/// Envelopes applied to a part with `apply_env`
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PartEnvelope {
+    /// Dynamics ramp from the part's start (a fade in or out)
+    pub dynamics: Option<EnvelopeValue>,
+    /// Amplitude envelope replacing the instrument's
+    pub adsr: Option<ADSREnvelope>,
+}
+
 /// Dynamic value
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DynamicValue {
     PPP,
     PP,
@@ -283,14 +1192,60 @@ pub enum DynamicValue {
 
 /// Waveform type for oscillators
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Waveform {
     Sine,
     Square,
     Saw,
     Triangle,
     Noise,
+    /// Noise with less treble, falling 3 dB an octave (rain, wind)
+    PinkNoise,
+    /// Noise falling 6 dB an octave, deep and rumbling
+    BrownNoise,
     /// Pulse wave with duty cycle (0.0 to 1.0, 0.5 = square)
     Pulse(f64),
+    /// WAV file played in place of a wave
+    Sample(SampleValue),
+}
+
+/// A WAV file an oscillator plays, pitched from its root note
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SampleValue {
+    /// Path of the file, relative to the song's directory
+    pub path: String,
+    /// MIDI note the file sounds at when played as is
+    pub root: f64,
+    /// Start and end of the loop in seconds; without one the file plays once
+    pub loop_points: Option<(f64, f64)>,
+}
+
+impl SampleValue {
+    pub fn new(path: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            root: 60.0,
+            loop_points: None,
+        }
+    }
+
+    /// GM percussion key for the file's name, if it names a drum
+    /// ("kick.wav", "open_hat_02.wav")
+    pub fn drum_key(&self) -> Option<u8> {
+        let stem = std::path::Path::new(&self.path).file_stem()?.to_str()?;
+        let preset: String = stem
+            .split(|c: char| !c.is_ascii_alphanumeric())
+            .filter(|word| !word.is_empty() && !word.chars().all(|c| c.is_ascii_digit()))
+            .map(|word| {
+                let mut chars = word.chars();
+                chars.next().map_or_else(String::new, |first| {
+                    first.to_ascii_uppercase().to_string() + chars.as_str()
+                })
+            })
+            .collect();
+        gm_drum_key(&preset)
+    }
 }
 
 impl Waveform {
@@ -301,14 +1256,16 @@ impl Waveform {
             Waveform::Square => "square",
             Waveform::Saw => "sawtooth",
             Waveform::Triangle => "triangle",
-            Waveform::Noise => "custom", // Noise requires custom implementation
+            Waveform::Noise | Waveform::PinkNoise | Waveform::BrownNoise => "custom", // Noise requires custom implementation
             Waveform::Pulse(_) => "custom", // Pulse requires custom implementation
+            Waveform::Sample(_) => "custom", // Samples play from audio buffers
         }
     }
 }
 
 /// Filter type
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum FilterType {
     LowPass,
     HighPass,
@@ -321,6 +1278,7 @@ pub enum FilterType {
 
 /// Distortion type
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DistortionType {
     /// Soft clipping (tube-like warmth)
     Soft,
@@ -346,6 +1304,7 @@ impl DistortionType {
 
 /// Delay effect parameters
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DelayParams {
     /// Delay time in milliseconds (0-2000)
     pub time_ms: f64,
@@ -367,6 +1326,7 @@ impl DelayParams {
 
 /// Phaser effect parameters
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PhaserParams {
     /// LFO rate in Hz (0.1-10)
     pub rate: f64,
@@ -388,6 +1348,7 @@ impl PhaserParams {
 
 /// Distortion effect parameters
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DistortionParams {
     /// Drive amount (0.0-1.0)
     pub amount: f64,
@@ -407,6 +1368,79 @@ impl DistortionParams {
     }
 }
 
+/// Chorus effect parameters
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChorusParams {
+    /// LFO rate in Hz (0.1-10)
+    pub rate: f64,
+    /// Modulation depth (0.0-1.0)
+    pub depth: f64,
+    /// Wet/dry mix (0.0-1.0, maps to MIDI CC#93 0-127)
+    pub mix: f64,
+}
+
+impl ChorusParams {
+    pub fn new(rate: f64, depth: f64, mix: f64) -> Self {
+        Self {
+            rate: rate.clamp(0.1, 10.0),
+            depth: depth.clamp(0.0, 1.0),
+            mix: mix.clamp(0.0, 1.0),
+        }
+    }
+}
+
+/// Compressor effect parameters
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CompressorParams {
+    /// Level above which the sound is turned down, in dB (-100-0)
+    pub threshold: f64,
+    /// dB in for each dB out above the threshold (1-20)
+    pub ratio: f64,
+    /// Time to turn down in milliseconds (0-1000)
+    pub attack_ms: f64,
+    /// Time to come back in milliseconds (0-1000)
+    pub release_ms: f64,
+}
+
+impl CompressorParams {
+    /// A compressor from `threshold` dB below full scale, given either way
+    /// (24 and -24 are both -24 dB)
+    pub fn new(threshold: f64, ratio: f64, attack_ms: f64, release_ms: f64) -> Self {
+        Self {
+            threshold: -threshold.abs().min(100.0),
+            ratio: ratio.clamp(1.0, 20.0),
+            attack_ms: attack_ms.clamp(0.0, 1000.0),
+            release_ms: release_ms.clamp(0.0, 1000.0),
+        }
+    }
+}
+
+/// Three-band EQ parameters: gains in dB (-24-24) of the lows (a shelf
+/// below 250 Hz), mids (a peak at 1 kHz) and highs (a shelf above 4 kHz)
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EqParams {
+    pub low: f64,
+    pub mid: f64,
+    pub high: f64,
+}
+
+impl EqParams {
+    /// Gains can be cut or boosted by this many dB
+    pub const MAX_GAIN: f64 = 24.0;
+
+    pub fn new(low: f64, mid: f64, high: f64) -> Self {
+        let gain = |db: f64| db.clamp(-Self::MAX_GAIN, Self::MAX_GAIN);
+        Self {
+            low: gain(low),
+            mid: gain(mid),
+            high: gain(high),
+        }
+    }
+}
+
 impl FilterType {
     /// Get the WebAudio BiquadFilter type name
     pub fn to_web_audio_type(&self) -> &'static str {
@@ -418,8 +1452,79 @@ impl FilterType {
     }
 }
 
+/// What an LFO modulates
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LfoTarget {
+    Pitch,
+    Cutoff,
+    Amplitude,
+}
+
+impl LfoTarget {
+    pub fn name(&self) -> &'static str {
+        match self {
+            LfoTarget::Pitch => "pitch",
+            LfoTarget::Cutoff => "cutoff",
+            LfoTarget::Amplitude => "amplitude",
+        }
+    }
+}
+
+/// Shape of an LFO's wave
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LfoShape {
+    Sine,
+    Triangle,
+    Square,
+    Saw,
+}
+
+impl LfoShape {
+    /// Get the WebAudio oscillator type name
+    pub fn to_web_audio_type(&self) -> &'static str {
+        match self {
+            LfoShape::Sine => "sine",
+            LfoShape::Triangle => "triangle",
+            LfoShape::Square => "square",
+            LfoShape::Saw => "sawtooth",
+        }
+    }
+}
+
+/// Low-frequency oscillator modulating a synth's pitch, filter cutoff or
+/// amplitude
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LfoValue {
+    pub target: LfoTarget,
+    pub shape: LfoShape,
+    /// Rate in Hz (0.01 to 50)
+    pub rate: f64,
+    /// Depth: semitones for pitch, Hz for cutoff, 0.0 to 1.0 for amplitude
+    pub depth: f64,
+}
+
+impl LfoValue {
+    pub fn new(target: LfoTarget, rate: f64, depth: f64) -> Self {
+        let depth = match target {
+            LfoTarget::Pitch => depth.clamp(0.0, 24.0),
+            LfoTarget::Cutoff => depth.clamp(0.0, 20_000.0),
+            LfoTarget::Amplitude => depth.clamp(0.0, 1.0),
+        };
+        Self {
+            target,
+            shape: LfoShape::Sine,
+            rate: rate.clamp(0.01, 50.0),
+            depth,
+        }
+    }
+}
+
 /// Oscillator value
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct OscillatorValue {
     pub waveform: Waveform,
     /// Mix level (0.0 to 1.0)
@@ -458,6 +1563,7 @@ impl OscillatorValue {
 
 /// ADSR Envelope
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ADSREnvelope {
     /// Attack time in seconds
     pub attack: f64,
@@ -493,6 +1599,7 @@ impl ADSREnvelope {
 
 /// Filter value
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FilterValue {
     pub filter_type: FilterType,
     /// Cutoff frequency in Hz
@@ -536,6 +1643,7 @@ impl FilterValue {
 
 /// Synth value - complete synthesizer configuration
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SynthValue {
     pub name: String,
     pub oscillators: Vec<OscillatorValue>,
@@ -545,6 +1653,8 @@ pub struct SynthValue {
     pub detune_cents: f64,
     /// Pitch envelope for drums (start_hz, end_hz, time_seconds)
     pub pitch_envelope: Option<(f64, f64, f64)>,
+    /// LFOs modulating pitch, filter cutoff and amplitude
+    pub lfos: Vec<LfoValue>,
 }
 
 impl SynthValue {
@@ -556,9 +1666,28 @@ impl SynthValue {
             filter: None,
             detune_cents: 0.0,
             pitch_envelope: None,
+            lfos: Vec::new(),
         }
     }
 
+    /// Whether any of the synth's oscillators plays a WAV file
+    pub fn plays_samples(&self) -> bool {
+        self.oscillators
+            .iter()
+            .any(|osc| matches!(osc.waveform, Waveform::Sample(_)))
+    }
+
+    /// GM percussion key the synth plays as a drum: its preset's, else the
+    /// one its sample's file name gives
+    pub fn drum_key(&self) -> Option<u8> {
+        gm_drum_key(&self.name).or_else(|| {
+            self.oscillators.iter().find_map(|osc| match &osc.waveform {
+                Waveform::Sample(sample) => sample.drum_key(),
+                _ => None,
+            })
+        })
+    }
+
     pub fn with_oscillators(mut self, oscillators: Vec<OscillatorValue>) -> Self {
         self.oscillators = oscillators;
         self
@@ -583,6 +1712,26 @@ impl SynthValue {
         self.pitch_envelope = Some((start_hz, end_hz, time));
         self
     }
+
+    pub fn with_lfo(mut self, lfo: LfoValue) -> Self {
+        self.lfos.push(lfo);
+        self
+    }
+}
+
+impl From<Dynamic> for DynamicValue {
+    fn from(dynamic: Dynamic) -> Self {
+        match dynamic {
+            Dynamic::Pianississimo => DynamicValue::PPP,
+            Dynamic::Pianissimo => DynamicValue::PP,
+            Dynamic::Piano => DynamicValue::P,
+            Dynamic::MezzoPiano => DynamicValue::MP,
+            Dynamic::MezzoForte => DynamicValue::MF,
+            Dynamic::Forte => DynamicValue::F,
+            Dynamic::Fortissimo => DynamicValue::FF,
+            Dynamic::Fortississimo => DynamicValue::FFF,
+        }
+    }
 }
 
 impl DynamicValue {
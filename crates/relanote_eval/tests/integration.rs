@@ -1,6 +1,9 @@
 //! Integration tests for the evaluator
 
-use relanote_eval::{Evaluator, Value};
+use relanote_ast::Articulation;
+use relanote_eval::{
+    BlockValue, EffectValue, Evaluator, NoteMeta, ProgramDiff, SectionValue, SlotValue, Value,
+};
 use relanote_parser::parse;
 
 fn eval(input: &str) -> Value {
@@ -33,9 +36,9 @@ fn test_eval_integer() {
 
 #[test]
 fn test_eval_float() {
-    let result = eval("3.14");
+    let result = eval("2.75");
     match result {
-        Value::Float(f) => assert!((f - 3.14).abs() < 0.001),
+        Value::Float(f) => assert!((f - 2.75).abs() < 0.001),
         _ => panic!("Expected Float"),
     }
 }
@@ -73,11 +76,45 @@ fn test_eval_multiplication() {
 }
 
 #[test]
-#[ignore = "division being parsed as function application - needs investigation"]
 fn test_eval_division() {
     assert!(matches!(eval("10 / 2"), Value::Int(5)));
 }
 
+#[test]
+fn test_eval_modulo_and_power() {
+    assert!(matches!(eval("7 % 3"), Value::Int(1)));
+    // Modulo wraps negative numbers into range
+    assert!(matches!(eval("-1 % 7"), Value::Int(6)));
+    assert!(matches!(eval("2 ^ 10"), Value::Int(1024)));
+    // ^ groups to the right and binds tighter than unary minus
+    assert!(matches!(eval("2 ^ 3 ^ 2"), Value::Int(512)));
+    assert!(matches!(eval("-2 ^ 2"), Value::Int(-4)));
+    assert!(eval_fails("2 ^ -1"));
+    assert!(eval_fails("7 % 0"));
+}
+
+#[test]
+fn test_eval_float_arithmetic() {
+    let float = |src| match eval(src) {
+        Value::Float(x) => x,
+        other => panic!("Expected Float, got {:?}", other),
+    };
+    assert_eq!(float("0.5 * 2"), 1.0);
+    assert_eq!(float("let tempo = 120\ntempo / 2.0"), 60.0);
+    assert_eq!(float("1.5 + 1.5 - 1"), 2.0);
+    assert_eq!(float("7.5 % 2"), 1.5);
+    assert_eq!(float("2.0 ^ -1"), 0.5);
+    assert_eq!(float("9 ^ 0.5"), 3.0);
+    assert!(eval_fails("1.0 / 0"));
+
+    assert!(matches!(eval("2.5 > 2"), Value::Bool(true)));
+    assert!(matches!(eval("1 >= 1.0"), Value::Bool(true)));
+    assert!(matches!(
+        eval("if 0.5 * 2 > 1 then 1 else 2"),
+        Value::Int(2)
+    ));
+}
+
 #[test]
 fn test_eval_complex_arithmetic() {
     assert!(matches!(eval("(1 + 2) * 3"), Value::Int(9)));
@@ -252,7 +289,6 @@ fn test_eval_array() {
 }
 
 #[test]
-#[ignore = "array indexing syntax [arr][idx] parsed as function application"]
 fn test_eval_array_index() {
     let result = eval("[10, 20, 30][1]");
     assert!(matches!(result, Value::Int(20)));
@@ -351,12 +387,7 @@ fn test_eval_transpose() {
     assert!(matches!(result, Value::Block(_)));
 }
 
-// NOTE: map, filter, any, all tests are ignored because the functional builtins
-// need access to the evaluator to apply closures, but the current architecture
-// doesn't support that. See builtins/functional.rs apply_closure().
-
 #[test]
-#[ignore = "functional builtins need evaluator context for closure application"]
 fn test_eval_map() {
     // Use pipe syntax since f (x) is parsed same as f(x)
     let result = eval("[1, 2, 3] |> map (\\x -> x * 2)");
@@ -372,7 +403,6 @@ fn test_eval_map() {
 }
 
 #[test]
-#[ignore = "functional builtins need evaluator context for closure application"]
 fn test_eval_filter() {
     // Use pipe syntax
     let result = eval("[1, 2, 3, 4] |> filter (\\x -> x > 2)");
@@ -387,7 +417,6 @@ fn test_eval_filter() {
 }
 
 #[test]
-#[ignore = "functional builtins need evaluator context for closure application"]
 fn test_eval_foldl() {
     let result = eval("foldl (\\acc x -> acc + x) 0 [1, 2, 3]");
     assert!(matches!(result, Value::Int(6)));
@@ -427,7 +456,6 @@ fn test_eval_concat() {
 }
 
 #[test]
-#[ignore = "functional builtins need evaluator context for closure application"]
 fn test_eval_any() {
     // Use pipe syntax
     assert!(matches!(
@@ -441,7 +469,6 @@ fn test_eval_any() {
 }
 
 #[test]
-#[ignore = "functional builtins need evaluator context for closure application"]
 fn test_eval_all() {
     // Use pipe syntax
     assert!(matches!(
@@ -454,6 +481,222 @@ fn test_eval_all() {
     ));
 }
 
+#[test]
+fn test_eval_humanize() {
+    let result = eval("| R M3 - P5 | |> humanize(20, 10)");
+    match result {
+        Value::Block(block) => {
+            assert_eq!(block.slots.len(), 4);
+            for slot in &block.slots {
+                match slot {
+                    SlotValue::Note { meta, .. } => {
+                        assert!(meta.timing_offset_ms.abs() <= 20.0);
+                        assert!(meta.velocity_offset.abs() <= 10);
+                    }
+                    SlotValue::Rest { .. } => {}
+                    _ => panic!("Expected Note or Rest"),
+                }
+            }
+        }
+        _ => panic!("Expected Block"),
+    }
+}
+
+#[test]
+fn test_eval_humanize_is_seeded() {
+    let offsets = |input: &str| match eval(input) {
+        Value::Block(block) => block
+            .slots
+            .iter()
+            .map(|slot| match slot {
+                SlotValue::Note { meta, .. } => meta.clone(),
+                _ => NoteMeta::default(),
+            })
+            .collect::<Vec<_>>(),
+        _ => panic!("Expected Block"),
+    };
+
+    let a = offsets("humanize(20, 10, | R M3 P5 |)");
+    let b = offsets("| R M3 P5 | |> humanize(20, 10)");
+    let c = offsets("humanize(20, 10, | R M3 P5 |, 7)");
+    assert_eq!(a, b);
+    assert_ne!(a, c);
+    assert!(a.iter().any(|m| *m != NoteMeta::default()));
+}
+
+#[test]
+fn test_eval_swing() {
+    let swing_of = |slot: &SlotValue| match slot {
+        SlotValue::Note { meta, .. } | SlotValue::Chord { meta, .. } => meta.swing,
+        _ => None,
+    };
+
+    match eval("| R M3 P5 M3 | |> swing") {
+        Value::Block(block) => {
+            // Timing is left to the renderer: the slots are unchanged
+            assert_eq!(block.slots.len(), 4);
+            let swing = swing_of(&block.slots[0]).expect("swing should be set");
+            assert_eq!(swing.ratio, 0.6);
+            assert_eq!(swing.grid_beats, 0.5);
+            assert!((swing.warp(0.25) - 0.3).abs() < 1e-9);
+        }
+        _ => panic!("Expected Block"),
+    }
+
+    match eval("| R [R, M3, P5] { R M3 P5 }:1 | |> swing 0.67") {
+        Value::Block(block) => {
+            assert_eq!(swing_of(&block.slots[1]).unwrap().ratio, 0.67);
+            match &block.slots[2] {
+                SlotValue::Tuplet { slots, .. } => {
+                    assert!(slots.iter().all(|s| swing_of(s).is_some()))
+                }
+                _ => panic!("Expected Tuplet"),
+            }
+        }
+        _ => panic!("Expected Block"),
+    }
+}
+
+#[test]
+fn test_eval_mark_and_lyrics() {
+    let metas = |block: &relanote_eval::BlockValue| -> Vec<NoteMeta> {
+        block
+            .slots
+            .iter()
+            .filter_map(|slot| slot.meta().cloned())
+            .collect()
+    };
+
+    match eval(r#"| - R M3 [R, P5] | |> mark "Chorus" |> lyrics "Hel- lo""#) {
+        Value::Block(block) => {
+            let metas = metas(&block);
+            // The marker lands on the first sounding slot
+            assert_eq!(metas[0].marker.as_deref(), Some("Chorus"));
+            assert_eq!(metas[1].marker, None);
+            assert_eq!(metas[0].lyric.as_deref(), Some("Hel-"));
+            assert_eq!(metas[1].lyric.as_deref(), Some("lo"));
+            assert_eq!(metas[2].lyric, None);
+        }
+        _ => panic!("Expected Block"),
+    }
+
+    match eval(r#"| R M3 | |> lyrics ["one", "two", "three"]"#) {
+        Value::Block(block) => assert_eq!(metas(&block)[1].lyric.as_deref(), Some("two")),
+        _ => panic!("Expected Block"),
+    }
+
+    assert!(eval_fails("| R | |> mark 1"));
+}
+
+#[test]
+fn test_eval_glide() {
+    let result = eval("| R M3 - | |> glide(80)");
+    match result {
+        Value::Block(block) => {
+            assert_eq!(block.slots.len(), 3);
+            for slot in &block.slots[..2] {
+                match slot {
+                    SlotValue::Note {
+                        articulations,
+                        meta,
+                        ..
+                    } => {
+                        assert!(articulations.contains(&Articulation::Portamento));
+                        assert_eq!(meta.glide_ms, Some(80.0));
+                    }
+                    _ => panic!("Expected Note"),
+                }
+            }
+        }
+        _ => panic!("Expected Block"),
+    }
+}
+
+#[test]
+fn test_eval_drum_map() {
+    match eval("| R M2 | |> drum_map [(R, 36), (M2, 38)]") {
+        Value::Part(part) => {
+            let drum_map = part.drum_map.expect("drum map should be set");
+            assert_eq!(drum_map.key_for(0.0), Some(36));
+            assert_eq!(drum_map.key_for(200.0), Some(38));
+            assert_eq!(drum_map.key_for(400.0), None);
+        }
+        _ => panic!("Expected Part"),
+    }
+
+    match eval("| R | |> drum_map 42") {
+        Value::Part(part) => assert_eq!(part.drum_map.unwrap().key_for(700.0), Some(42)),
+        _ => panic!("Expected Part"),
+    }
+
+    assert!(eval_fails("| R | |> drum_map 200"));
+
+    // Synth settings applied afterwards keep the map
+    match eval("| R M2 | |> drum_map [(R, 36), (M2, 38)] |> cutoff 2000.0") {
+        Value::Part(part) => {
+            let drum_map = part.drum_map.expect("cutoff should keep the drum map");
+            assert_eq!(drum_map.key_for(200.0), Some(38));
+        }
+        _ => panic!("Expected Part"),
+    }
+}
+
+#[test]
+fn test_eval_pan() {
+    match eval("| R M3 P5 | |> pan (-0.5)") {
+        Value::Part(part) => assert_eq!(part.pan_level, Some(-0.5)),
+        _ => panic!("Expected Part"),
+    }
+
+    // Int positions are percentages and chaining keeps other settings
+    match eval("| R | |> volume 0.8 |> pan 150") {
+        Value::Part(part) => {
+            assert_eq!(part.volume_level, Some(0.8));
+            assert_eq!(part.pan_level, Some(1.0));
+        }
+        _ => panic!("Expected Part"),
+    }
+}
+
+#[test]
+fn test_eval_automate() {
+    match eval("| R M3 P5 | |> automate 74 [0.0, 1.0, 50]") {
+        Value::Part(part) => {
+            assert_eq!(part.automation.len(), 1);
+            assert_eq!(part.automation[0].cc, 74);
+            assert_eq!(part.automation[0].points, vec![0.0, 1.0, 0.5]);
+            assert_eq!(part.automation[0].level_at(0.25), 0.5);
+        }
+        _ => panic!("Expected Part"),
+    }
+
+    // Chained automations accumulate on the part
+    match eval("| R | |> volume 0.8 |> automate 7 [1.0, 0.0] |> automate 10 [0.5]") {
+        Value::Part(part) => {
+            assert_eq!(part.volume_level, Some(0.8));
+            assert_eq!(part.automation.len(), 2);
+        }
+        _ => panic!("Expected Part"),
+    }
+
+    assert!(eval_fails("| R | |> automate 128 [0.5]"));
+    assert!(eval_fails("| R | |> automate 74 []"));
+
+    // Synth settings keep what the part already has
+    match eval(
+        "let kick = | R R |\n\
+         | R | |> automate 74 [0.5] |> duck kick 0.5 |> drum_map 36 |> voice Lead \
+         |> cutoff 2000.0 |> resonance 0.5 |> detune 5 |> adsr 0.1 0.2 0.5 0.3",
+    ) {
+        Value::Part(part) => {
+            assert_eq!(part.automation.len(), 1);
+            assert_eq!(part.ducks.len(), 1);
+            assert!(part.drum_map.is_some());
+        }
+        _ => panic!("Expected Part"),
+    }
+}
+
 // ===== Synth Tests =====
 
 #[test]
@@ -485,6 +728,67 @@ scale Major = { R, M2, M3, P4, P5, M6, M7 }
     assert!(matches!(result, Value::Song(_)));
 }
 
+#[test]
+fn test_eval_sample_oscillator() {
+    use relanote_eval::value::{SampleValue, Waveform};
+
+    let result = eval(r#"sample "kick.wav" |> sample_root 36 |> sample_loop 0.1 0.2"#);
+    let Value::Oscillator(osc) = result else {
+        panic!("Expected an oscillator, got {:?}", result);
+    };
+    assert_eq!(
+        osc.waveform,
+        Waveform::Sample(SampleValue {
+            path: "kick.wav".to_string(),
+            root: 36.0,
+            loop_points: Some((0.1, 0.2)),
+        })
+    );
+
+    // Only samples have roots and loops, and loops end after they start
+    assert!(eval_fails("Saw |> sample_root 36"));
+    assert!(eval_fails(r#"sample "pad.wav" |> sample_loop 1.0 0.5"#));
+}
+
+#[test]
+fn test_eval_synth_lfos_and_noise() {
+    use relanote_eval::value::{LfoShape, LfoTarget, LfoValue, Waveform};
+
+    let result = eval(
+        r#"
+synth Wobble = {
+  osc: PinkNoise + BrownNoise,
+  filter: LowPass 800 0.5,
+  lfo: (PitchLfo 5 0.5 |> lfo_shape Triangle) + CutoffLfo 2 600 + AmpLfo 4 2.0
+}
+Wobble
+"#,
+    );
+    let Value::Synth(synth) = result else {
+        panic!("Expected a synth, got {:?}", result);
+    };
+    let waveforms: Vec<_> = synth.oscillators.iter().map(|o| &o.waveform).collect();
+    assert_eq!(waveforms, [&Waveform::PinkNoise, &Waveform::BrownNoise]);
+    assert_eq!(
+        synth.lfos,
+        vec![
+            LfoValue {
+                shape: LfoShape::Triangle,
+                ..LfoValue::new(LfoTarget::Pitch, 5.0, 0.5)
+            },
+            LfoValue::new(LfoTarget::Cutoff, 2.0, 600.0),
+            // Amplitude depth is at most 1.0
+            LfoValue::new(LfoTarget::Amplitude, 4.0, 1.0),
+        ]
+    );
+
+    // LFO shapes are the plain waveforms
+    assert!(eval_fails("PitchLfo 5 0.5 |> lfo_shape Noise"));
+    // Every element of an LFO array is an LFO
+    assert!(eval_fails("synth Bad = { lfo: [1, 2] }\nBad"));
+    assert!(eval_fails("synth Bad = { lfo: [PitchLfo 5 0.5, 2] }\nBad"));
+}
+
 // ===== Scale and Chord Tests =====
 
 #[test]
@@ -511,6 +815,61 @@ fn test_eval_chord_definition() {
     assert!(matches!(result, Value::Unit));
 }
 
+fn interval_cents(value: &Value) -> Vec<f64> {
+    match value {
+        Value::Scale(scale) => scale.intervals.iter().map(|i| i.cents).collect(),
+        Value::Chord(chord) => chord.intervals.iter().map(|i| i.cents).collect(),
+        other => panic!("Expected Scale or Chord, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_eval_with_modifies_scales() {
+    // The prelude's Lydian is Major with a raised fourth
+    assert_eq!(
+        interval_cents(&eval("Lydian")),
+        vec![0.0, 200.0, 400.0, 600.0, 700.0, 900.0, 1100.0]
+    );
+    assert_eq!(
+        interval_cents(&eval("Locrian")),
+        vec![0.0, 100.0, 300.0, 500.0, 600.0, 800.0, 1000.0]
+    );
+
+    // Removing a degree, and adding one the base lacks
+    assert_eq!(
+        interval_cents(&eval("Major with { -P4, -M7 }")),
+        vec![0.0, 200.0, 400.0, 700.0, 900.0]
+    );
+    assert_eq!(
+        interval_cents(&eval("MajorPentatonic with { m3 }")),
+        vec![0.0, 200.0, 300.0, 700.0, 900.0]
+    );
+    assert_eq!(
+        interval_cents(&eval("MinorPentatonic with { M2 }")),
+        vec![0.0, 200.0, 300.0, 500.0, 700.0, 1000.0]
+    );
+}
+
+#[test]
+fn test_eval_with_modifies_chords() {
+    // A diminished triad's tritone is its fifth
+    assert_eq!(
+        interval_cents(&eval("Diminished with { P5 }")),
+        vec![0.0, 300.0, 700.0]
+    );
+    assert_eq!(
+        interval_cents(&eval("Dominant7 with { -P5, M9 }")),
+        vec![0.0, 400.0, 1000.0, 1400.0]
+    );
+    assert_eq!(
+        interval_cents(&eval("MajorTriad with { 386c }")),
+        vec![0.0, 386.0, 700.0]
+    );
+
+    assert!(eval_fails("MajorTriad with { -M7 }"));
+    assert!(eval_fails("1 with { M3 }"));
+}
+
 // ===== Interval Tests =====
 
 #[test]
@@ -525,6 +884,20 @@ fn test_eval_interval_addition() {
     }
 }
 
+#[test]
+fn test_eval_interval_arithmetic() {
+    let cents = |src| match eval(src) {
+        Value::Interval(i) => i.cents,
+        other => panic!("Expected Interval, got {:?}", other),
+    };
+    assert_eq!(cents("M3 + 12st"), 1600.0);
+    assert_eq!(cents("P5 * 2"), 1400.0);
+    assert_eq!(cents("3 * P8"), 3600.0);
+    assert_eq!(cents("R - P8"), -1200.0);
+    assert_eq!(cents("-P8 + 7st"), -500.0);
+    assert!(eval_fails("P5 * 1.5"));
+}
+
 #[test]
 fn test_eval_chromatic_modifiers() {
     // P1+ should be 100 cents
@@ -552,6 +925,145 @@ layer [melody, bass]
     assert!(matches!(result, Value::Song(_)));
 }
 
+#[test]
+fn test_values_as_songs() {
+    // A block plays as a piano part, alone in its section
+    let song = eval("| R M3 P5 |").into_song().expect("a block is music");
+    assert_eq!(song.sections.len(), 1);
+    assert_eq!(song.sections[0].parts[0].instrument, "Piano");
+
+    let part = eval("| R | |> volume 0.5");
+    assert!(matches!(part, Value::Part(_)));
+    let song = part.into_song().expect("a part is music");
+    assert_eq!(song.sections[0].parts[0].volume_level, Some(0.5));
+
+    assert!(eval("1 + 1").into_song().is_none());
+}
+
+#[test]
+fn test_eval_effect_chains_and_buses() {
+    let effect_names =
+        |effects: &[EffectValue]| -> Vec<&str> { effects.iter().map(EffectValue::name).collect() };
+
+    // Effects chain in order; applying one again changes it in place
+    let Value::Part(part) =
+        eval("| R | |> delay 250 0.3 0.4 |> reverb 0.2 |> delay 100 0.3 0.4 |> dry")
+    else {
+        panic!("Expected a part");
+    };
+    assert_eq!(effect_names(&part.effects), ["delay", "reverb"]);
+    assert_eq!(part.reverb_level(), Some(0.0));
+
+    // A layer's effects go on its master bus; parts send to buses
+    let result = eval(
+        r#"
+let room = bus "room" |> reverb 0.6
+layer [| R | |> send room 0.5, | M3 |] |> delay 300 0.3 0.4
+"#,
+    );
+    let Value::Song(song) = result else {
+        panic!("Expected a song, got {:?}", result);
+    };
+    assert_eq!(effect_names(&song.master), ["delay"]);
+    let parts = &song.sections[0].parts;
+    assert_eq!(parts[0].sends[0].bus.name, "room");
+    assert_eq!(song.buses().len(), 1);
+    assert_eq!(song.reverb_level(&parts[0]), Some(0.3));
+    assert_eq!(song.reverb_level(&parts[1]), None);
+
+    // In a section, the layer's master effects become a bus of its own
+    let result = eval(
+        r#"section "Verse" { layer [| R |, | M3 |] |> reverb 0.4 } ++ section "End" { | R | }"#,
+    );
+    let Value::Song(song) = result else {
+        panic!("Expected a song, got {:?}", result);
+    };
+    assert!(song.master.is_empty());
+    let buses: Vec<_> = song.buses().iter().map(|bus| bus.name.as_str()).collect();
+    assert_eq!(buses, ["Verse"]);
+    assert!(song.sections[1].parts[0].sends.is_empty());
+
+    assert!(eval_fails("| R | |> send 0.5 0.5"));
+}
+
+#[test]
+fn test_eval_compressor_and_eq() {
+    let result = eval("| R | |> eq 4 0 (-30) |> compressor (-18) 4 5 120");
+    let Value::Part(part) = result else {
+        panic!("Expected a part, got {:?}", result);
+    };
+    let [EffectValue::Eq(eq), EffectValue::Compressor(compressor)] = part.effects.as_slice() else {
+        panic!("Expected an EQ and a compressor, got {:?}", part.effects);
+    };
+    // Gains are limited to 24 dB
+    assert_eq!((eq.low, eq.mid, eq.high), (4.0, 0.0, -24.0));
+    assert_eq!(compressor.threshold, -18.0);
+    assert_eq!(compressor.ratio, 4.0);
+    assert_eq!((compressor.attack_ms, compressor.release_ms), (5.0, 120.0));
+
+    // The threshold is below full scale either way
+    let result = eval("| R | |> compressor 18 4 5 120");
+    let Value::Part(part) = result else {
+        panic!("Expected a part, got {:?}", result);
+    };
+    assert!(matches!(
+        part.effects.as_slice(),
+        [EffectValue::Compressor(compressor)] if compressor.threshold == -18.0
+    ));
+
+    assert!(eval_fails(r#"| R | |> eq 1 2 "high""#));
+}
+
+#[test]
+fn test_eval_chorus_and_width() {
+    let result = eval("| R | |> chorus_fx 0.8 0.4 0.5 |> width 3.0");
+    let Value::Part(part) = result else {
+        panic!("Expected a part, got {:?}", result);
+    };
+    let [EffectValue::Chorus(chorus), EffectValue::Width(width)] = part.effects.as_slice() else {
+        panic!("Expected a chorus and a width, got {:?}", part.effects);
+    };
+    assert_eq!((chorus.rate, chorus.depth, chorus.mix), (0.8, 0.4, 0.5));
+    // Width goes up to twice as wide
+    assert_eq!(*width, 2.0);
+
+    // Chorus on a bus adds to what the parts sending to it are heard with
+    let result = eval(
+        r#"
+let wide = bus "wide" |> chorus_fx 1 0.5 0.5
+layer [| R | |> chorus_fx 1 0.5 0.25 |> send wide 0.5, | M3 |]
+"#,
+    );
+    let Value::Song(song) = result else {
+        panic!("Expected a song, got {:?}", result);
+    };
+    let parts = &song.sections[0].parts;
+    assert_eq!(song.chorus_level(&parts[0]), Some(0.5));
+    assert_eq!(song.chorus_level(&parts[1]), None);
+
+    assert!(eval_fails(r#"| R | |> width "wide""#));
+}
+
+#[test]
+fn test_eval_duck() {
+    // Piped in or called directly, the trigger ducks the target
+    for source in [
+        "let kick = | R R | |> volume 0.9\n| M3 | |> duck kick 0.6",
+        "let kick = | R R | |> volume 0.9\nduck kick 60 | M3 |",
+    ] {
+        let result = eval(source);
+        let Value::Part(part) = result else {
+            panic!("Expected a part, got {:?}", result);
+        };
+        assert_eq!(part.ducks.len(), 1);
+        assert_eq!(part.ducks[0].trigger.instrument, "Volume");
+        assert_eq!(part.ducks[0].amount, 0.6);
+        assert_eq!(part.blocks[0].slots.len(), 1);
+    }
+
+    assert!(eval_fails("| R | |> duck 0.5 0.5"));
+}
+
 // ===== Error Cases =====
 
 #[test]
@@ -565,6 +1077,80 @@ fn test_eval_type_error() {
     assert!(eval_fails(r#""hello" + 1"#));
 }
 
+#[test]
+fn test_builtin_error_located_at_call() {
+    let (program, _) = parse("let x = 1\nlet y = reverse 3");
+    let error = Evaluator::new().eval_program(&program).unwrap_err();
+    let span = error.span();
+    assert_eq!((span.start, span.end), (18, 27));
+}
+
+#[test]
+fn test_builtin_errors_point_at_pipe_stage_and_argument() {
+    let error_text = |source: &str| {
+        let (program, _) = parse(source);
+        let span = Evaluator::new().eval_program(&program).unwrap_err().span();
+        source[span.start..span.end].to_string()
+    };
+
+    // A wrong argument count points at the stage, not the whole pipeline
+    assert_eq!(
+        error_text("| R M3 | |> reverse |> reverb 0.3 0.5"),
+        "reverb 0.3 0.5"
+    );
+    // A wrong argument points at the argument
+    assert_eq!(error_text("| R M3 | |> reverse |> lyrics 3"), "3");
+    assert_eq!(error_text("edo \"19\""), "\"19\"");
+}
+
+#[test]
+fn test_builtin_partial_application() {
+    let delay_of = |source: &str| match eval(source) {
+        Value::Part(part) => part
+            .effects
+            .into_iter()
+            .find_map(|effect| match effect {
+                EffectValue::Delay(delay) => Some(delay),
+                _ => None,
+            })
+            .expect("part has a delay"),
+        other => panic!("Expected Part, got {:?}", other),
+    };
+
+    let echo = delay_of("let echo = delay 250 0.4 0.3\n| R M3 | |> echo");
+    assert_eq!((echo.time_ms, echo.feedback, echo.mix), (250.0, 0.4, 0.3));
+
+    // Named arguments go by name; the ones left out take their defaults
+    let named = delay_of("| R M3 | |> delay(mix: 0.3, time: 250)");
+    assert_eq!(
+        (named.time_ms, named.feedback, named.mix),
+        (250.0, 0.35, 0.3)
+    );
+
+    // Without the block, the call waits for it
+    let later = delay_of("let slap = delay(time: 120, feedback: 0.1)\n| R | |> slap");
+    assert_eq!(
+        (later.time_ms, later.feedback, later.mix),
+        (120.0, 0.1, 0.4)
+    );
+
+    // A piped value fills the last slot, after the arguments given
+    let partial = delay_of("let e = delay(feedback: 0.2, mix: 0.3)\n| R | |> e 100");
+    assert_eq!(
+        (partial.time_ms, partial.feedback, partial.mix),
+        (100.0, 0.2, 0.3)
+    );
+    let rest = delay_of("| R | |> delay(feedback: 0.2) 100");
+    assert_eq!((rest.time_ms, rest.feedback, rest.mix), (100.0, 0.2, 0.4));
+
+    assert!(matches!(
+        eval("let add = \\x y -> x + y\nlet inc = add 1\ninc 2"),
+        Value::Int(3)
+    ));
+    assert!(eval_fails("| R | |> delay(time: 250, speed: 2)"));
+    assert!(eval_fails("| R | |> delay(time: 250, time: 300)"));
+}
+
 // ===== Function Composition Tests =====
 
 #[test]
@@ -602,6 +1188,999 @@ scale Major = { R, M2, M3, P4, P5, M6, M7 }
     assert!(matches!(result, Value::Song(_)));
 }
 
+#[test]
+fn test_strict_scale_warns_about_notes_outside_scale() {
+    let warnings = |input: &str| {
+        let (program, _) = parse(input);
+        let mut evaluator = Evaluator::new();
+        evaluator.eval_program(&program).unwrap();
+        evaluator
+            .warnings()
+            .iter()
+            .map(|w| w.message.clone())
+            .collect::<Vec<_>>()
+    };
+
+    let source = "| R M3 m3 A4 [R, m7] | |> in Minor";
+    assert!(warnings(source).is_empty());
+    // Minor maps M3 onto its m3 and has m3 and m7, but not A4
+    assert_eq!(
+        warnings(&format!("set strict_scale = true\n{}", source)),
+        vec!["notes outside Minor: `A4`"]
+    );
+}
+
+#[test]
+fn test_set_key_with_mode() {
+    let melody = "| R M3 P5 M6 |";
+    let in_key = eval(&format!("set key = D minor\n{} |> in key", melody));
+    let in_minor = eval(&format!("{} |> in Minor", melody));
+    assert_eq!(format!("{:?}", in_key), format!("{:?}", in_minor));
+
+    // The key itself is still the tonic the renderers start from
+    let (program, _) = parse("set key = F# dorian");
+    let mut evaluator = Evaluator::new();
+    evaluator.eval_program(&program).unwrap();
+    match evaluator.get_binding("key") {
+        Some(Value::AbsolutePitch(pitch)) => assert_eq!(pitch.midi_note, 66),
+        other => panic!("Expected AbsolutePitch, got {:?}", other),
+    }
+
+    // Without a mode there's no scale to apply
+    assert!(eval_fails(&format!("set key = D4\n{} |> in key", melody)));
+}
+
+#[test]
+fn test_sections_join_into_a_song_with_their_own_keys() {
+    let result = eval(
+        r#"
+let melody = | R M3 P5 |
+let verse = section "Verse" melody
+let bridge = section "Bridge" with key: G4 { melody }
+verse ++ bridge ++ (verse |> modulate M2)
+"#,
+    );
+    match result {
+        Value::Song(song) => {
+            let names: Vec<&str> = song.sections.iter().map(|s| s.name.as_str()).collect();
+            assert_eq!(names, vec!["Verse", "Bridge", "Verse"]);
+            let base_notes: Vec<u8> = song.sections.iter().map(|s| s.base_note(60)).collect();
+            assert_eq!(base_notes, vec![60, 67, 62]);
+        }
+        other => panic!("Expected Song, got {:?}", other),
+    }
+
+    // Modulating a block transposes it
+    match eval("| R | |> modulate P5") {
+        Value::Block(block) => match &block.slots[0] {
+            SlotValue::Note { interval, .. } => assert_eq!(interval.semitones(), 7.0),
+            other => panic!("Expected Note, got {:?}", other),
+        },
+        other => panic!("Expected Block, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_meta_block_sets_song_metadata() {
+    let (program, _) = parse("meta { title: \"Etude\", copyright: \"(c) 2026\" }\n| R |");
+    let mut evaluator = Evaluator::new();
+    evaluator.eval_program(&program).unwrap();
+    assert_eq!(evaluator.metadata().title.as_deref(), Some("Etude"));
+    assert_eq!(evaluator.metadata().composer, None);
+    assert_eq!(evaluator.metadata().copyright.as_deref(), Some("(c) 2026"));
+
+    assert!(eval_fails("meta { tempo: \"fast\" }\n| R |"));
+    assert!(eval_fails("meta { title: 3 }\n| R |"));
+}
+
+#[test]
+fn test_tests_do_not_end_a_program() {
+    // Tests only run under `relanote test`, even failing ones
+    match eval("let melody = | R M3 |\nmelody\ntest \"broken\" { undefined_name }") {
+        Value::Block(block) => assert_eq!(block.slots.len(), 2),
+        other => panic!("Expected Block, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_microtonal_scales_keep_their_cents() {
+    let cents = |value: Value| match value {
+        Value::Block(block) => block
+            .slots
+            .iter()
+            .map(|slot| match slot {
+                SlotValue::Note { interval, .. } => interval.cents,
+                other => panic!("Expected Note, got {:?}", other),
+            })
+            .collect::<Vec<f64>>(),
+        other => panic!("Expected Block, got {:?}", other),
+    };
+
+    let just = cents(eval(
+        "scale Just = { 0c, 204c, 386.3c }\n| R M2 M3 | |> in Just",
+    ));
+    assert_eq!(just, vec![0.0, 204.0, 386.3]);
+
+    match eval("edo 19") {
+        Value::Scale(scale) => {
+            assert_eq!(scale.intervals.len(), 19);
+            assert!((scale.intervals[1].cents - 1200.0 / 19.0).abs() < 1e-9);
+        }
+        other => panic!("Expected Scale, got {:?}", other),
+    }
+    let nineteen = cents(eval("let nineteen = edo 19\n| R M2 M3 | |> in nineteen"));
+    assert!((nineteen[2] - 2400.0 / 19.0).abs() < 1e-9);
+
+    assert!(eval_fails("edo 0"));
+}
+
+#[test]
+fn test_assertions() {
+    assert!(matches!(
+        eval("let m = | R M3 P5 |\nassert_eq(m |> reverse |> reverse, m)"),
+        Value::Bool(true)
+    ));
+    match eval("| R M3 <2> |:4 |> assert_beats 4 |> assert_in_scale Major") {
+        Value::Block(block) => assert_eq!(block.slots.len(), 3),
+        other => panic!("Expected Block, got {:?}", other),
+    }
+
+    let source = "let m = | R m3 P5 |\nm |> assert_in_scale Major";
+    let (program, _) = parse(source);
+    let error = Evaluator::new().eval_program(&program).unwrap_err();
+    assert!(error.to_string().contains("notes outside Major: m3"));
+    let span = error.span();
+    assert_eq!(&source[span.start..span.end], "assert_in_scale Major");
+
+    assert!(eval_fails("assert_eq(| R |, | M3 |)"));
+    assert!(eval_fails("| R M3 |:2 |> assert_beats 3"));
+    assert!(eval_fails("assert_beats(3, 3)"));
+}
+
+// ===== Durations =====
+
+fn slot_beats(value: &Value) -> Vec<Option<f64>> {
+    match value {
+        Value::Block(block) => block
+            .slots
+            .iter()
+            .map(|slot| match slot {
+                SlotValue::Note { duration_beats, .. }
+                | SlotValue::Rest { duration_beats }
+                | SlotValue::Chord { duration_beats, .. } => *duration_beats,
+                SlotValue::Tuplet { target_beats, .. } => Some(*target_beats),
+                SlotValue::Overlay { .. } => slot.duration_beats(),
+            })
+            .collect(),
+        other => panic!("Expected Block, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_durations() {
+    assert_eq!(
+        slot_beats(&eval("| R:1/8 M3:1/4. P5:2 - |")),
+        vec![Some(0.5), Some(1.5), Some(2.0), None]
+    );
+    assert_eq!(
+        slot_beats(&eval(
+            "set time_signature = (3, 4)
+| R:1bar |"
+        )),
+        vec![Some(3.0)]
+    );
+    assert_eq!(
+        slot_beats(&eval("| { R M3 P5 }:2 beats |")),
+        vec![Some(2.0)]
+    );
+
+    let beats = |source: &str| match eval(source) {
+        Value::Duration(duration) => duration.beats,
+        other => panic!("Expected Duration, got {:?}", other),
+    };
+    assert_eq!(beats("1 beat * 3"), 3.0);
+    assert_eq!(beats("1 bar - 1/4"), 3.0);
+    assert_eq!(beats("2 * 1/8."), 1.5);
+    assert!(matches!(eval("2 beats > 1/4"), Value::Bool(true)));
+    assert_eq!(beats("1 bar / 2"), 2.0);
+    assert_eq!(beats("1 beat / 0.5"), 2.0);
+    assert!(eval_fails("1 bar / 0"));
+
+    // A spaced `/` divides numbers, while an unspaced fraction is a note
+    // value; a slot duration reads either spelling
+    assert!(matches!(eval("1 / 2"), Value::Int(0)));
+    assert!(matches!(eval("0 / 5"), Value::Int(0)));
+    assert!(matches!(eval("120 * 1 / 2"), Value::Int(60)));
+    assert!(matches!(eval("10 - 3 / 4"), Value::Int(10)));
+    assert_eq!(beats("6/2"), 12.0);
+    assert_eq!(
+        slot_beats(&eval("| R:1 / 8 M3:3 / 2 |")),
+        vec![Some(0.5), Some(6.0)]
+    );
+
+    assert!(matches!(
+        eval("| R M3 |:2 |> assert_beats 2 beats"),
+        Value::Block(_)
+    ));
+}
+
+#[test]
+fn test_ratio_and_nested_tuplets() {
+    assert_eq!(
+        slot_beats(&eval(
+            "| { R M3 P5 M6 M7 }:5:4 { R:1/8 M3:1/8 P5:1/8 }:3:2 |"
+        )),
+        vec![Some(4.0), Some(1.0)]
+    );
+    assert_eq!(
+        slot_beats(&eval("| { R:1/8 M3:1/8 P5:1/8 }:3/4 |")),
+        vec![Some(3.0)]
+    );
+    match eval("| { R { M3 P5 M6 }:3:2 }:3 |") {
+        Value::Block(block) => match &block.slots[0] {
+            SlotValue::Tuplet { slots, .. } => {
+                assert_eq!(
+                    slot_beats(&Value::Block(BlockValue::new(slots.clone()))),
+                    vec![None, Some(2.0)]
+                );
+            }
+            other => panic!("Expected Tuplet, got {:?}", other),
+        },
+        other => panic!("Expected Block, got {:?}", other),
+    }
+
+    assert!(eval_fails("| { R M3 }:0 |"));
+    assert!(eval_fails("| { R M3 }:3:0 |"));
+    assert!(eval_fails("| { R M3 }:1/2:3 |"));
+}
+
+#[test]
+fn test_kits_map_hits_to_drum_keys() {
+    let keys = |value: &Value| match value {
+        Value::Block(block) => block
+            .slots
+            .iter()
+            .map(|slot| match slot {
+                SlotValue::Note { meta, .. } => meta.drum_key,
+                _ => None,
+            })
+            .collect::<Vec<_>>(),
+        other => panic!("Expected Block, got {:?}", other),
+    };
+    assert_eq!(
+        keys(&eval("let rock = kit { x: 36, o: 38 }\nrock | x - o x |")),
+        vec![Some(36), None, Some(38), Some(36)]
+    );
+    match eval("| x o |") {
+        Value::Block(block) => match &block.slots[1] {
+            SlotValue::Note { meta, .. } => {
+                assert_eq!(meta.hit.as_deref(), Some("o"));
+                assert_eq!(meta.drum_key, None);
+            }
+            other => panic!("Expected Note, got {:?}", other),
+        },
+        other => panic!("Expected Block, got {:?}", other),
+    }
+
+    assert!(eval_fails("let rock = kit { x: 36 }\nrock | x q |"));
+    assert!(eval_fails("kit { x: 128 }"));
+    assert!(eval_fails("kit { x: \"kick\" }"));
+}
+
+#[test]
+fn test_stdlib_scales() {
+    let semitones = |name: &str| -> Vec<f64> {
+        interval_cents(&eval(name))
+            .iter()
+            .map(|cents| cents / 100.0)
+            .collect()
+    };
+    assert_eq!(semitones("Hirajoshi"), vec![0.0, 2.0, 3.0, 7.0, 8.0]);
+    assert_eq!(
+        semitones("HungarianMinor"),
+        vec![0.0, 2.0, 3.0, 6.0, 7.0, 8.0, 11.0]
+    );
+    assert_eq!(
+        semitones("PhrygianDominant"),
+        vec![0.0, 1.0, 4.0, 5.0, 7.0, 8.0, 10.0]
+    );
+    assert_eq!(
+        semitones("LydianDominant"),
+        vec![0.0, 2.0, 4.0, 6.0, 7.0, 9.0, 10.0]
+    );
+    assert_eq!(semitones("Rast"), vec![0.0, 2.0, 3.5, 5.0, 7.0, 9.0, 10.5]);
+
+    // Every scale editors offer is one the prelude binds
+    for scale in relanote_stdlib::scales() {
+        assert!(
+            matches!(eval(scale.name), Value::Scale(_)),
+            "{} is not a scale",
+            scale.name
+        );
+    }
+}
+
+#[test]
+fn test_stdlib_drums() {
+    fn keys(slot: &SlotValue, into: &mut Vec<u8>) {
+        match slot {
+            SlotValue::Note { meta, .. } => into.extend(meta.drum_key),
+            SlotValue::Overlay { voices } => voices
+                .iter()
+                .flat_map(|voice| &voice.slots)
+                .for_each(|slot| keys(slot, into)),
+            _ => {}
+        }
+    }
+    let drum_keys = |src: &str| match eval(src) {
+        Value::Block(block) => {
+            let mut found = Vec::new();
+            block.slots.iter().for_each(|slot| keys(slot, &mut found));
+            (block.beats, found)
+        }
+        other => panic!("Expected Block, got {:?}", other),
+    };
+
+    let (beats, found) = drum_keys("use std::drums::*\ntr808 four_on_floor");
+    assert_eq!(beats, 4.0);
+    assert_eq!(found, vec![36, 36, 36, 36, 46, 46, 46, 46]);
+
+    // The grooves share their hits, so every kit plays every groove
+    for kit in ["tr808", "tr909", "acoustic", "gm_kit"] {
+        for groove in ["backbeat", "breakbeat", "half_time", "fill"] {
+            let (beats, _) = drum_keys(&format!("use std::drums::*\n{} {}", kit, groove));
+            assert_eq!(beats, 4.0, "{} {}", kit, groove);
+        }
+    }
+    assert_eq!(drum_keys("use std::drums::*\ngm_kit phrase").0, 16.0);
+
+    // Hits stay free until the module is used
+    assert!(eval_fails("tr808 backbeat"));
+}
+
+#[test]
+fn test_stdlib_patterns() {
+    fn pitches(slots: &[SlotValue], into: &mut Vec<f64>) {
+        for slot in slots {
+            match slot {
+                SlotValue::Note { interval, .. } => into.push(interval.cents),
+                SlotValue::Overlay { voices } => {
+                    voices.iter().for_each(|voice| pitches(&voice.slots, into))
+                }
+                _ => {}
+            }
+        }
+    }
+    let notes = |src: &str| match eval(&format!("use std::patterns::*\n{}", src)) {
+        Value::Block(block) => {
+            let mut found = Vec::new();
+            pitches(&block.slots, &mut found);
+            (block.beats, found)
+        }
+        other => panic!("Expected Block, got {:?}", other),
+    };
+
+    // Degrees count chord tones, wrapping past the top of the chord
+    assert_eq!(
+        notes("alberti MajorTriad 4"),
+        (
+            4.0,
+            vec![0.0, 700.0, 400.0, 700.0, 0.0, 700.0, 400.0, 700.0]
+        )
+    );
+    assert_eq!(
+        notes("broken MinorTriad 2"),
+        (2.0, vec![0.0, 300.0, 700.0, 1200.0])
+    );
+    assert_eq!(
+        notes("broken_down Dominant7 2"),
+        (2.0, vec![1000.0, 700.0, 400.0, 0.0])
+    );
+
+    // The bass sounds an octave below the chord
+    assert_eq!(
+        notes("boom_chick MajorTriad 2"),
+        (2.0, vec![-1200.0, 0.0, 400.0, 700.0])
+    );
+    let (beats, found) = notes("waltz MinorTriad 6");
+    assert_eq!(beats, 6.0);
+    assert_eq!(found.len(), 14);
+
+    // `in` a chord outside the module works the same way
+    assert_eq!(
+        notes("| <1> <2> <3> <4> | |> in Sus4"),
+        (1.0, vec![0.0, 500.0, 700.0, 1200.0])
+    );
+}
+
+#[test]
+fn test_steps_builds_drum_blocks() {
+    match eval("steps \"x..X | x.\" 36") {
+        Value::Block(block) => {
+            assert_eq!(block.beats, 1.5);
+            let steps: Vec<_> = block
+                .slots
+                .iter()
+                .map(|slot| match slot {
+                    SlotValue::Note {
+                        articulations,
+                        meta,
+                        ..
+                    } => (meta.drum_key, articulations.contains(&Articulation::Accent)),
+                    SlotValue::Rest { .. } => (None, false),
+                    other => panic!("Expected Note or Rest, got {:?}", other),
+                })
+                .collect();
+            assert_eq!(
+                steps,
+                vec![
+                    (Some(36), false),
+                    (None, false),
+                    (None, false),
+                    (Some(36), true),
+                    (Some(36), false),
+                    (None, false)
+                ]
+            );
+        }
+        other => panic!("Expected Block, got {:?}", other),
+    }
+    assert_eq!(
+        slot_beats(&eval("steps \"x.x\" 42 1/8")),
+        vec![Some(0.5), Some(0.5), Some(0.5)]
+    );
+
+    assert!(eval_fails("steps \"x?\" 36"));
+    assert!(eval_fails("steps \"x.\" 200"));
+    assert!(eval_fails("steps \"x.\" 36 0 beats"));
+}
+
+#[test]
+fn test_chord_symbols() {
+    let chords = |value: &Value| match value {
+        Value::Block(block) => block
+            .slots
+            .iter()
+            .map(|slot| match slot {
+                SlotValue::Chord {
+                    intervals,
+                    duration_beats,
+                    ..
+                } => (
+                    intervals
+                        .iter()
+                        .map(|i| (i.cents / 100.0).round() as i32)
+                        .collect::<Vec<_>>(),
+                    duration_beats.unwrap(),
+                ),
+                other => panic!("Expected Chord, got {:?}", other),
+            })
+            .collect::<Vec<_>>(),
+        other => panic!("Expected Block, got {:?}", other),
+    };
+
+    assert_eq!(
+        chords(&eval("chords \"Cmaj7 | F#m7b5 B7 | Em/G\"")),
+        vec![
+            (vec![0, 4, 7, 11], 4.0),
+            (vec![6, 9, 12, 16], 2.0),
+            (vec![11, 15, 18, 21], 2.0),
+            (vec![-5, 4, 7, 11], 4.0)
+        ]
+    );
+    // Roots are read in the key
+    assert_eq!(
+        chords(&eval(
+            "set key = D4\nset time_signature = (3, 4)\nchords \"D | Bbaug\""
+        )),
+        vec![(vec![0, 4, 7], 3.0), (vec![8, 12, 16], 3.0)]
+    );
+
+    assert!(eval_fails("chords \"Cmaj13\""));
+    assert!(eval_fails("chords \"H7\""));
+}
+
+#[test]
+fn test_roman_numeral_progressions() {
+    let roots = |value: &Value| match value {
+        Value::Block(block) => block
+            .slots
+            .iter()
+            .map(|slot| match slot {
+                SlotValue::Chord { intervals, .. } => intervals
+                    .iter()
+                    .map(|i| (i.cents / 100.0).round() as i32)
+                    .collect::<Vec<_>>(),
+                other => panic!("Expected Chord, got {:?}", other),
+            })
+            .collect::<Vec<_>>(),
+        other => panic!("Expected Block, got {:?}", other),
+    };
+
+    assert_eq!(
+        roots(&eval("progression \"ii7 | V7 | Imaj7 bVII\"")),
+        vec![
+            vec![2, 5, 9, 12],
+            vec![7, 11, 14, 17],
+            vec![0, 4, 7, 11],
+            vec![10, 14, 17]
+        ]
+    );
+    assert_eq!(
+        roots(&eval("progression \"vii° | viiø7 | iv | IV+\"")),
+        vec![
+            vec![11, 14, 17],
+            vec![11, 14, 17, 21],
+            vec![5, 8, 12],
+            vec![5, 9, 13]
+        ]
+    );
+
+    assert!(eval_fails("progression \"VIII\""));
+    assert!(eval_fails("progression \"V13\""));
+}
+
+#[test]
+fn test_smooth_voicing() {
+    let voicings = |value: &Value| match value {
+        Value::Block(block) => block
+            .slots
+            .iter()
+            .map(|slot| match slot {
+                SlotValue::Chord { intervals, .. } => intervals
+                    .iter()
+                    .map(|i| (i.cents / 100.0).round() as i32)
+                    .collect::<Vec<_>>(),
+                other => panic!("Expected Chord, got {:?}", other),
+            })
+            .collect::<Vec<_>>(),
+        other => panic!("Expected Block, got {:?}", other),
+    };
+
+    // ii-V-I with the seventh of each chord falling to the third of the next
+    assert_eq!(
+        voicings(&eval("progression \"ii7 | V7 | Imaj7\" |> smooth_voicing")),
+        vec![vec![2, 5, 9, 12], vec![2, 5, 7, 11], vec![0, 4, 7, 11]]
+    );
+    match eval("smooth_voicing [MajorTriad, Sus4]") {
+        Value::Block(block) => {
+            assert_eq!(block.beats, 8.0);
+            assert_eq!(
+                voicings(&Value::Block(block)),
+                vec![vec![0, 4, 7], vec![0, 5, 7]]
+            );
+        }
+        other => panic!("Expected Block, got {:?}", other),
+    }
+
+    assert!(eval_fails("smooth_voicing [1, 2]"));
+}
+
+#[test]
+fn test_scale_degrees_resolve_in_the_applied_scale() {
+    let semitones = |value: &Value| match value {
+        Value::Block(block) => block
+            .slots
+            .iter()
+            .map(|slot| match slot {
+                SlotValue::Note { interval, .. } => (interval.cents / 100.0).round() as i32,
+                other => panic!("Expected Note, got {:?}", other),
+            })
+            .collect::<Vec<_>>(),
+        other => panic!("Expected Block, got {:?}", other),
+    };
+
+    // Octave marks, read in the major scale until a scale is applied
+    assert_eq!(
+        semitones(&eval("| <1> <3'> <5,> <7-''> |")),
+        vec![0, 16, -5, 34]
+    );
+    // A five-note scale takes its own degrees: the sixth is the next octave's first
+    assert_eq!(
+        semitones(&eval("| <1> <3> <5> <6> <7> | |> in MinorPentatonic")),
+        vec![0, 5, 10, 12, 15]
+    );
+    // So does an eight-note one
+    assert_eq!(
+        semitones(&eval("| <6> <7> <8> | |> in BebopDominant")),
+        vec![9, 10, 11]
+    );
+    // Degrees are re-read by each scale applied
+    assert_eq!(
+        semitones(&eval("| <3> <6'> | |> in Minor |> in Dorian")),
+        vec![3, 21]
+    );
+    // Transposed notes are mapped from the pitch they have moved to
+    assert_eq!(
+        semitones(&eval("| <1> <2> | |> transpose M2 |> in Minor")),
+        vec![2, 3]
+    );
+}
+
+#[test]
+fn test_negative_harmony_and_map_mode() {
+    let semitones = |value: &Value| match value {
+        Value::Block(block) => block
+            .slots
+            .iter()
+            .map(|slot| match slot {
+                SlotValue::Note { interval, .. } => vec![(interval.cents / 100.0).round() as i32],
+                SlotValue::Chord { intervals, .. } => intervals
+                    .iter()
+                    .map(|i| (i.cents / 100.0).round() as i32)
+                    .collect(),
+                other => panic!("Expected Note or Chord, got {:?}", other),
+            })
+            .collect::<Vec<_>>(),
+        other => panic!("Expected Block, got {:?}", other),
+    };
+
+    // C major becomes C minor, upside down: G Eb C
+    assert_eq!(
+        semitones(&eval("| R M3 P5 [R, M3, P5] | |> negative_harmony R")),
+        vec![vec![7], vec![3], vec![0], vec![7, 3, 0]]
+    );
+    // Around D, the axis moves up a whole step
+    assert_eq!(
+        semitones(&eval("negative_harmony M2 | R |")),
+        vec![vec![11]]
+    );
+
+    assert_eq!(
+        semitones(&eval("| R M3 P5 M7 M9 A4 | |> map_mode Major Minor")),
+        vec![vec![0], vec![3], vec![7], vec![10], vec![14], vec![6]]
+    );
+    assert_eq!(
+        semitones(&eval("| M3 M6 P8 | |> map_mode Major Dorian")),
+        vec![vec![3], vec![9], vec![12]]
+    );
+
+    assert!(eval_fails("| R | |> map_mode Major MinorPentatonic"));
+}
+
+#[test]
+fn test_time_and_order_transforms() {
+    let semitones = |value: &Value| match value {
+        Value::Block(block) => block
+            .slots
+            .iter()
+            .map(|slot| match slot {
+                SlotValue::Note { interval, .. } => vec![(interval.cents / 100.0).round() as i32],
+                SlotValue::Chord { intervals, .. } => intervals
+                    .iter()
+                    .map(|i| (i.cents / 100.0).round() as i32)
+                    .collect(),
+                SlotValue::Rest { .. } => vec![],
+                other => panic!("Expected Note, Chord or Rest, got {:?}", other),
+            })
+            .collect::<Vec<_>>(),
+        other => panic!("Expected Block, got {:?}", other),
+    };
+    let total_beats = |value: &Value| match value {
+        Value::Block(block) => block.total_beats(),
+        other => panic!("Expected Block, got {:?}", other),
+    };
+
+    assert_eq!(total_beats(&eval("| R M3 P5 | |> half_time")), 2.0);
+    assert_eq!(
+        slot_beats(&eval("| R:1/8 M3 | |> stretch 3")),
+        vec![Some(1.5), None]
+    );
+    assert_eq!(total_beats(&eval("compress 2 | R:2 M3:2 |")), 2.0);
+    assert_eq!(
+        slot_beats(&eval("| { R M3 P5 }:2 | |> stretch 1.5")),
+        vec![Some(3.0)]
+    );
+    assert!(eval_fails("| R | |> stretch 0"));
+
+    // Slots end on the nearest grid line, and none vanishes
+    assert_eq!(
+        slot_beats(&eval("| R:1/16 M3:1/4. P5:1/8 | |> quantize 1/8")),
+        vec![Some(0.5), Some(1.5), Some(0.5)]
+    );
+    assert_eq!(
+        slot_beats(&eval("| R:1/32 M3:1/32 | |> quantize 1/8")),
+        vec![Some(0.5), Some(0.5)]
+    );
+
+    assert_eq!(
+        semitones(&eval("| R M3 P5 | |> invert")),
+        vec![vec![0], vec![-4], vec![-7]]
+    );
+    assert_eq!(
+        semitones(&eval("invert P5 | R M3 |")),
+        vec![vec![14], vec![10]]
+    );
+
+    // The rhythm stays put while the pitches run backwards
+    let retro = eval("| R:2 M3 - [P5, M7] | |> retrograde");
+    assert_eq!(
+        semitones(&retro),
+        vec![vec![7, 11], vec![4], vec![], vec![0]]
+    );
+    assert_eq!(slot_beats(&retro), vec![Some(2.0), None, None, None]);
+
+    // Shuffling keeps every pitch and is the same every time
+    let mut shuffled = semitones(&eval("| R M2 M3 P4 P5 M6 | |> shuffle"));
+    assert_eq!(
+        shuffled,
+        semitones(&eval("| R M2 M3 P4 P5 M6 | |> shuffle"))
+    );
+    assert_ne!(
+        shuffled,
+        semitones(&eval("| R M2 M3 P4 P5 M6 | |> shuffle 7"))
+    );
+    shuffled.sort();
+    assert_eq!(
+        shuffled,
+        vec![vec![0], vec![2], vec![4], vec![5], vec![7], vec![9]]
+    );
+}
+
+#[test]
+fn test_map_and_filter_notes() {
+    let semitones = |value: &Value| match value {
+        Value::Block(block) => block
+            .slots
+            .iter()
+            .map(|slot| match slot {
+                SlotValue::Note { interval, .. } => vec![(interval.cents / 100.0).round() as i32],
+                SlotValue::Chord { intervals, .. } => intervals
+                    .iter()
+                    .map(|i| (i.cents / 100.0).round() as i32)
+                    .collect(),
+                SlotValue::Rest { .. } => vec![],
+                other => panic!("Expected Note, Chord or Rest, got {:?}", other),
+            })
+            .collect::<Vec<_>>(),
+        other => panic!("Expected Block, got {:?}", other),
+    };
+
+    assert_eq!(
+        semitones(&eval("| R M3 [R, P5] | |> map_notes (\\i -> i + P8)")),
+        vec![vec![12], vec![16], vec![12, 19]]
+    );
+    // Lengths and indices come along, and a pair changes the length
+    let held =
+        eval("| R - M3 | |> map_notes (\\i d n -> if n == 2 then (i + M2, d * 2) else (i, d))");
+    assert_eq!(semitones(&held), vec![vec![0], vec![], vec![6]]);
+    assert_eq!(
+        slot_beats(&held),
+        vec![Some(1.0 / 3.0), None, Some(2.0 / 3.0)]
+    );
+    assert!(eval_fails("| R | |> map_notes (\\i -> 1)"));
+    assert!(eval_fails(
+        "| { R M3 P5 } | |> map_notes (\\i d -> (i, d * 2))"
+    ));
+
+    // Rejected notes become rests of the same length
+    let filtered = eval("| R:2 M3 [R, M3, P5] | |> filter_notes (\\i -> not (i == M3))");
+    assert_eq!(semitones(&filtered), vec![vec![0], vec![], vec![0, 7]]);
+    assert_eq!(slot_beats(&filtered), vec![Some(2.0), None, None]);
+    assert_eq!(
+        semitones(&eval("| R M3 P5 | |> filter_notes (\\i d n -> n > 0)")),
+        vec![vec![], vec![4], vec![7]]
+    );
+}
+
+#[test]
+fn test_slicing_and_indexing_blocks() {
+    let semitones = |value: &Value| match value {
+        Value::Block(block) => block
+            .slots
+            .iter()
+            .map(|slot| match slot {
+                SlotValue::Note { interval, .. } => Some((interval.cents / 100.0).round() as i32),
+                _ => None,
+            })
+            .collect::<Vec<_>>(),
+        other => panic!("Expected Block, got {:?}", other),
+    };
+
+    // Notes crossing either end are cut to fit
+    let sliced = eval("| R:2 M3:2 P5:2 | |> slice 1 3");
+    assert_eq!(semitones(&sliced), vec![Some(0), Some(4)]);
+    assert_eq!(slot_beats(&sliced), vec![Some(1.0), Some(1.0)]);
+    assert_eq!(
+        slot_beats(&eval("slice 1/32 1/8 | R M3 P5 M7 |")),
+        vec![Some(0.125), Some(0.25)]
+    );
+    assert!(eval_fails("| { R M3 P5 }:2 | |> slice 0 1"));
+    assert!(eval_fails("| R | |> slice 2 1"));
+
+    assert!(matches!(
+        eval("0..3"),
+        Value::Array(items) if items.len() == 3 && matches!(items[2], Value::Int(2))
+    ));
+    assert!(matches!(eval("3..0"), Value::Array(items) if items.is_empty()));
+    assert!(matches!(eval("0..1000000"), Value::Array(items) if items.len() == 1_000_000));
+    assert!(eval_fails("0..1000001"));
+    assert!(eval_fails("0..9223372036854775807"));
+    let song = "set time_signature = (3, 4)
+let verse = | R:3 M3:3 P5:3 M7:3 |
+";
+    assert_eq!(
+        semitones(&eval(&format!("{}verse |> bars 1..3", song))),
+        vec![Some(4), Some(7)]
+    );
+    assert_eq!(
+        semitones(&eval(&format!("{}bars [3, 0] verse", song))),
+        vec![Some(11), Some(0)]
+    );
+    assert!(eval_fails(&format!(
+        "{}verse |> bars 9223372036854775807",
+        song
+    )));
+    assert_eq!(
+        slot_beats(&eval(&format!("{}verse |> bars 1", song))),
+        vec![Some(3.0)]
+    );
+
+    // An index takes one slot, as long as it was
+    let melody = "let melody = | R M3 - P5 |\n";
+    let second = eval(&format!("{}melody[1]", melody));
+    assert_eq!(semitones(&second), vec![Some(4)]);
+    assert_eq!(slot_beats(&second), vec![Some(0.25)]);
+    assert_eq!(
+        slot_beats(&eval(&format!("{}melody[3] ++ melody[0]", melody))),
+        vec![Some(0.25), Some(0.25)]
+    );
+    assert!(eval_fails(&format!("{}melody[4]", melody)));
+
+    // An index out of range is reported as written: scale degrees count
+    // from 1
+    let error = |source: &str| {
+        let (program, _) = parse(source);
+        Evaluator::new()
+            .eval_program(&program)
+            .unwrap_err()
+            .to_string()
+    };
+    assert_eq!(error("Major[0]"), "index 0 out of range 1..=7");
+    assert_eq!(error("Major[8]"), "index 8 out of range 1..=7");
+    assert_eq!(
+        error(&format!("{}melody[-1]", melody)),
+        "index -1 out of range 0..=3"
+    );
+}
+
+#[test]
+fn test_overlaying_blocks() {
+    let voices = |value: &Value| match value {
+        Value::Block(block) => match block.slots.as_slice() {
+            [SlotValue::Overlay { voices }] => voices.clone(),
+            other => panic!("Expected one overlay, got {:?}", other),
+        },
+        other => panic!("Expected Block, got {:?}", other),
+    };
+
+    // The overlay lasts as long as its longest block
+    let overlay = eval("| R M3 P5 |:3 & | P8 |:2");
+    assert_eq!(slot_beats(&overlay), vec![Some(3.0)]);
+    assert_eq!(voices(&overlay).len(), 2);
+    // Chained overlays are flattened into one
+    assert_eq!(voices(&eval("| R | & | M3 | & | P5 |")).len(), 3);
+
+    // Joined to other blocks, it takes its place in the rhythm
+    let joined = eval("| R | ++ (| M3 |:2 & | P5 |) ++ | R |");
+    assert_eq!(slot_beats(&joined), vec![Some(1.0), Some(2.0), Some(1.0)]);
+
+    // Transforms reach into every voice
+    match eval("(| R | & | M3 |) |> transpose P5") {
+        Value::Block(block) => match &block.slots[0] {
+            SlotValue::Overlay { voices } => {
+                let cents: Vec<f64> = voices
+                    .iter()
+                    .map(|voice| match &voice.slots[0] {
+                        SlotValue::Note { interval, .. } => interval.cents,
+                        other => panic!("Expected Note, got {:?}", other),
+                    })
+                    .collect();
+                assert_eq!(cents, vec![700.0, 1100.0]);
+            }
+            other => panic!("Expected Overlay, got {:?}", other),
+        },
+        other => panic!("Expected Block, got {:?}", other),
+    }
+    assert!(eval_fails("(| R |:2 & | M3 |) |> slice 0 1"));
+    assert!(eval_fails("| R | & 1"));
+}
+
+#[test]
+fn test_render_targets() {
+    let (program, _) = parse(
+        "let song = layer [| R M3 P5 |]
+render song
+render \"high\" = layer [| P5 M7 P8 |]
+let unused = 1",
+    );
+    let mut evaluator = Evaluator::new();
+    evaluator.eval_program(&program).unwrap();
+    let targets = evaluator.render_targets();
+    assert_eq!(targets.len(), 2);
+    assert_eq!(targets[0].name, None);
+    assert_eq!(targets[1].name.as_deref(), Some("high"));
+    match &targets[1].song.sections[0].parts[0].blocks[0].slots[0] {
+        SlotValue::Note { interval, .. } => assert_eq!(interval.cents, 700.0),
+        other => panic!("Expected Note, got {:?}", other),
+    }
+
+    // Without render items there are no targets, and only songs render
+    let (program, _) = parse("layer [| R |]");
+    let mut evaluator = Evaluator::new();
+    evaluator.eval_program(&program).unwrap();
+    assert!(evaluator.render_targets().is_empty());
+    assert!(eval_fails("render | R M3 P5 |"));
+    // Names are unique
+    assert!(eval_fails(
+        "let s = layer [| R |]\nrender \"demo\" = s\nrender \"demo\" = s"
+    ));
+}
+
+#[test]
+fn test_context_settings() {
+    let song = match eval(
+        "let verse = section \"Verse\" | R M3 |
+let bridge = section \"Bridge\" with tempo: 90 { | R M3 |  }
+verse ++ context { tempo = 140, key = A3, swing = 0.6 } in (verse ++ bridge)",
+    ) {
+        Value::Song(song) => song,
+        other => panic!("Expected Song, got {:?}", other),
+    };
+    // The first verse keeps the song's settings
+    let settings: Vec<_> = song
+        .sections
+        .iter()
+        .map(|section| (section.tempo, section.key))
+        .collect();
+    assert_eq!(
+        settings,
+        vec![
+            (None, None),
+            (Some(140.0), Some(57)),
+            (Some(90.0), Some(57))
+        ]
+    );
+    let swing = |section: &SectionValue| match &section.parts[0].blocks[0].slots[0] {
+        SlotValue::Note { meta, .. } => meta.swing.map(|swing| swing.ratio),
+        other => panic!("Expected Note, got {:?}", other),
+    };
+    assert_eq!(swing(&song.sections[0]), None);
+    assert_eq!(swing(&song.sections[1]), Some(0.6));
+
+    // Blocks become a song, and notes already swung keep their feel
+    match eval("context { swing = 0.6 } in (| R M3 | |> swing 0.7)") {
+        Value::Song(song) => assert_eq!(swing(&song.sections[0]), Some(0.7)),
+        other => panic!("Expected Song, got {:?}", other),
+    }
+    assert!(eval_fails("context { tempo = 0 } in | R |"));
+    assert!(eval_fails("context { tempo = 120 } in 1"));
+}
+
+#[test]
+fn test_apply_env() {
+    let part = |source: &str| match eval(source) {
+        Value::Part(part) => part,
+        other => panic!("Expected Part, got {:?}", other),
+    };
+    let swell = part("| R M3 | |> apply_env(env(pp, ff, 4 beats))");
+    let dynamics = swell.envelope.unwrap().dynamics.unwrap();
+    assert_eq!(dynamics.duration_beats, 4.0);
+    assert_eq!(dynamics.level_at(0.0), 33.0 / 127.0);
+    assert_eq!(dynamics.level_at(8.0), 112.0 / 127.0);
+
+    // Both kinds of envelope can be applied to one part
+    let shaped =
+        part("| R M3 | |> apply_env(env(mf, p, 2)) |> apply_env(envelope 0.2 0.1 0.8 0.5)");
+    let envelope = shaped.envelope.unwrap();
+    assert!(envelope.dynamics.is_some());
+    assert_eq!(envelope.adsr.unwrap().attack, 0.2);
+
+    // A binding named like a dynamic shadows it
+    assert!(eval_fails("let pp = 1\nenv(pp, ff, 4)"));
+    assert!(eval_fails("| R | |> apply_env 1"));
+}
+
 // ===== Complex Examples =====
 
 #[test]
@@ -619,3 +2198,132 @@ melody |> transpose P5 |> repeat 2
     );
     assert!(matches!(result, Value::Block(_)));
 }
+
+// ===== Modules =====
+
+#[test]
+fn test_modules_resolve_like_the_resolver() {
+    use relanote_resolver::{FileProvider, MemoryFileProvider, ResolvedPackage};
+    use std::path::PathBuf;
+    use std::rc::Rc;
+
+    let mut files = MemoryFileProvider::new();
+    files.insert("song/motifs.rela", "export let motif = | R M3 |\n");
+    files.insert("lib/riffs.rela", "export let riff = | P5 |\n");
+    files.insert("pkg/main.rela", "export let hook = | P4 |\n");
+    files.insert("pkg/fills.rela", "export let fill = | M2 |\n");
+    let files: Rc<dyn FileProvider> = Rc::new(files);
+
+    let evaluator = || {
+        let mut evaluator = Evaluator::with_base_dir(Some(PathBuf::from("song")));
+        evaluator.set_file_provider(files.clone());
+        evaluator.add_search_path(PathBuf::from("lib"));
+        evaluator.add_package(ResolvedPackage {
+            name: "pkg".to_string(),
+            version: None,
+            root: PathBuf::from("pkg"),
+            entry: PathBuf::from("pkg/main.rela"),
+        });
+        evaluator
+    };
+    let (program, _) = parse(
+        "use motifs::motif\nuse riffs::riff\nuse pkg::hook\nuse pkg::fills::fill\n\
+         motif ++ riff ++ hook ++ fill",
+    );
+    match evaluator().eval_program(&program) {
+        Ok(Value::Block(block)) => assert_eq!(block.slots.len(), 5),
+        other => panic!("Expected Block, got {:?}", other),
+    }
+
+    // A module missing from the package isn't looked for elsewhere
+    let (program, _) = parse("use pkg::riffs::riff\nriff");
+    assert!(evaluator().eval_program(&program).is_err());
+}
+
+// ===== Live Patching =====
+
+/// Slot counts of the blocks of a value
+fn block_lengths(value: &Value) -> Vec<usize> {
+    match value {
+        Value::Block(block) => vec![block.slots.len()],
+        Value::Song(song) => song
+            .sections
+            .iter()
+            .flat_map(|section| &section.parts)
+            .flat_map(|part| &part.blocks)
+            .map(|block| block.slots.len())
+            .collect(),
+        other => panic!("Expected music, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_patch_matches_full_evaluation() {
+    let versions = [
+        "let bass = | R R |\nlet riff = | R M3 |\nlet twice b = b ++ b\nlayer [twice riff, bass]",
+        "let bass = | R R |\nlet riff = | R M3 P5 |\nlet twice b = b ++ b\nlayer [twice riff, bass]",
+        "let bass = | R R R |\nlet riff = | R M3 P5 |\nlet twice b = b ++ b ++ b\nlayer [twice riff, bass]",
+        // Shadowing, then removing a definition
+        "let bass = | R R R |\nlet riff = | R M3 P5 |\nlet riff = riff ++ | R |\nlet twice b = b ++ b\nlayer [twice riff, bass]",
+        "let bass = | R |\nlet riff = | R M3 P5 |\nlet twice b = b ++ b\nlayer [twice riff, bass]",
+        "set tempo = 90\nlet bass = | R |\nlet riff = | R |\nlet twice b = b ++ b\nlayer [twice riff, bass]",
+    ];
+
+    let (mut old, _) = parse(versions[0]);
+    let mut evaluator = Evaluator::new();
+    evaluator.eval_program(&old).unwrap();
+    for pair in versions.windows(2) {
+        let (new, _) = parse(pair[1]);
+        let diff = ProgramDiff::between(pair[0], &old, pair[1], &new);
+        let patched = evaluator.patch(&diff).unwrap();
+        assert_eq!(
+            block_lengths(&patched),
+            block_lengths(&eval(pair[1])),
+            "{}",
+            pair[1]
+        );
+        old = new;
+    }
+    assert_eq!(
+        evaluator.get_binding("tempo").map(|v| format!("{:?}", v)),
+        Some("Int(90)".to_string())
+    );
+}
+
+#[test]
+fn test_patch_unbinds_removed_definitions() {
+    let old_source = "let extra = | R |\nlet riff = | R M3 |\nriff";
+    let new_source = "let riff = | R M3 |\nextra";
+    let (old, _) = parse(old_source);
+    let (new, _) = parse(new_source);
+    let mut evaluator = Evaluator::new();
+    evaluator.eval_program(&old).unwrap();
+    let diff = ProgramDiff::between(old_source, &old, new_source, &new);
+    assert!(!diff.is_empty());
+    assert!(evaluator.patch(&diff).is_err());
+}
+
+// ===== Serialization =====
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_song_serde_round_trip() {
+    let result = eval(
+        r#"
+let melody = | R M3* [R, P5] - | |> volume 0.5
+layer [melody]
+"#,
+    );
+    let Value::Song(song) = result else {
+        panic!("Expected Song");
+    };
+
+    let json = serde_json::to_string(&song).unwrap();
+    assert!(json.contains(r#""articulations":["Staccato"]"#));
+
+    let restored: relanote_eval::SongValue = serde_json::from_str(&json).unwrap();
+    let part = &restored.sections[0].parts[0];
+    assert_eq!(part.volume_level, Some(0.5));
+    assert_eq!(part.blocks[0].slots.len(), 4);
+    assert!(matches!(part.blocks[0].slots[2], SlotValue::Chord { .. }));
+}
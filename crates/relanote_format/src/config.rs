@@ -1,7 +1,22 @@
 //! Formatter configuration
 
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use thiserror::Error;
+
+/// Dedicated formatter config file, searched for before `relanote.toml`
+pub const RELAFMT_FILE: &str = ".relafmt";
+
+/// Project manifest whose `[format]` table configures the formatter
+pub const MANIFEST_FILE: &str = "relanote.toml";
+
 /// Configuration options for the formatter
-#[derive(Clone, Debug)]
+///
+/// Loaded from a `.relafmt` file (top-level keys) or the `[format]` table of
+/// a `relanote.toml`; missing keys keep their default value.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct FormatConfig {
     /// Number of spaces per indentation level
     pub indent_size: usize,
@@ -11,6 +26,8 @@ pub struct FormatConfig {
     pub trailing_commas: bool,
     /// Whether to put block contents on separate lines
     pub block_multiline: bool,
+    /// Whether to pad slots so the rows of a multi-line block line up in columns
+    pub align_bars: bool,
 }
 
 impl Default for FormatConfig {
@@ -20,6 +37,113 @@ impl Default for FormatConfig {
             max_line_width: 80,
             trailing_commas: true,
             block_multiline: false,
+            align_bars: false,
+        }
+    }
+}
+
+/// Error loading a formatter config file
+#[derive(Debug, Error)]
+pub enum FormatConfigError {
+    #[error("failed to read {}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("invalid formatter config in {}: {source}", path.display())]
+    Invalid {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+}
+
+#[derive(Deserialize)]
+struct Manifest {
+    #[serde(default)]
+    format: FormatConfig,
+}
+
+impl FormatConfig {
+    /// Parse the contents of a `.relafmt` file
+    pub fn from_relafmt(text: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(text)
+    }
+
+    /// Parse the `[format]` table of a `relanote.toml`, ignoring other tables
+    pub fn from_manifest(text: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str::<Manifest>(text).map(|manifest| manifest.format)
+    }
+
+    /// Load a config file, picking the format from its file name
+    pub fn load(path: &Path) -> Result<Self, FormatConfigError> {
+        let text = std::fs::read_to_string(path).map_err(|source| FormatConfigError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let parsed = if path.file_name().is_some_and(|name| name == MANIFEST_FILE) {
+            Self::from_manifest(&text)
+        } else {
+            Self::from_relafmt(&text)
+        };
+        parsed.map_err(|source| FormatConfigError::Invalid {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    /// Find the config file that applies to `path`
+    ///
+    /// Walks up from `path` (a file or directory) and returns the first
+    /// `.relafmt` or `relanote.toml` found, preferring `.relafmt` within a
+    /// directory.
+    pub fn discover(path: &Path) -> Option<PathBuf> {
+        let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        let start = if path.is_dir() {
+            path.as_path()
+        } else {
+            path.parent()?
+        };
+        start.ancestors().find_map(|dir| {
+            [RELAFMT_FILE, MANIFEST_FILE]
+                .iter()
+                .map(|name| dir.join(name))
+                .find(|candidate| candidate.is_file())
+        })
+    }
+
+    /// Load the config that applies to `path`, or the default if there is none
+    pub fn resolve(path: &Path) -> Result<Self, FormatConfigError> {
+        match Self::discover(path) {
+            Some(config_path) => Self::load(&config_path),
+            None => Ok(Self::default()),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relafmt_overrides_defaults() {
+        let config = FormatConfig::from_relafmt("indent_size = 4\nalign_bars = true\n").unwrap();
+        assert_eq!(config.indent_size, 4);
+        assert!(config.align_bars);
+        assert_eq!(
+            config.max_line_width,
+            FormatConfig::default().max_line_width
+        );
+        assert!(FormatConfig::from_relafmt("indent = 4").is_err());
+    }
+
+    #[test]
+    fn test_manifest_format_table() {
+        let manifest = "[package]\nname = \"song\"\n\n[format]\nmax_line_width = 100\n";
+        let config = FormatConfig::from_manifest(manifest).unwrap();
+        assert_eq!(config.max_line_width, 100);
+        assert_eq!(
+            FormatConfig::from_manifest("[package]\nname = \"song\"\n").unwrap(),
+            FormatConfig::default()
+        );
+    }
+}
@@ -3,13 +3,70 @@
 mod config;
 mod printer;
 
-pub use config::FormatConfig;
+pub use config::{FormatConfig, FormatConfigError, MANIFEST_FILE, RELAFMT_FILE};
 pub use printer::Formatter;
 
+use std::ops::Range;
+
 use relanote_ast::Program;
+use relanote_core::Span;
 
 /// Format a program to a string
 pub fn format(program: &Program, config: &FormatConfig) -> String {
     let mut formatter = Formatter::new(config.clone());
     formatter.format_program(program)
 }
+
+/// Format a program parsed from `source`, keeping the layout the AST doesn't record
+pub fn format_with_source(program: &Program, source: &str, config: &FormatConfig) -> String {
+    let mut formatter = Formatter::new(config.clone()).with_source(source);
+    formatter.format_program(program)
+}
+
+/// Format the top-level items that overlap `range` (byte offsets into `source`)
+///
+/// Returns the span of source to replace, covering the selected items and the
+/// comments between them, together with its formatted text. An empty range
+/// selects the item containing that position. Returns `None` when no item
+/// overlaps the range.
+pub fn format_range(
+    program: &Program,
+    source: &str,
+    config: &FormatConfig,
+    range: Range<usize>,
+) -> Option<(Range<usize>, String)> {
+    let overlaps = |span: &Span| {
+        if range.is_empty() {
+            span.start <= range.start && range.start <= span.end
+        } else {
+            span.start < range.end && range.start < span.end
+        }
+    };
+    let items: Vec<_> = program
+        .items
+        .iter()
+        .filter(|item| overlaps(&item.span))
+        .cloned()
+        .collect();
+    let start = items.first()?.span.start;
+    let mut end = items.last()?.span.end;
+
+    // Take along a trailing comment on the last item's line
+    if let Some(comment) = program
+        .comments
+        .iter()
+        .find(|c| c.trailing && c.span.start >= end && !source[end..c.span.start].contains('\n'))
+    {
+        end = comment.span.end;
+    }
+
+    let comments = program
+        .comments
+        .iter()
+        .filter(|c| start <= c.span.start && c.span.end <= end)
+        .cloned()
+        .collect();
+    let selection = Program { items, comments };
+    let formatted = format_with_source(&selection, source, config);
+    Some((start..end, formatted.trim_end_matches('\n').to_string()))
+}
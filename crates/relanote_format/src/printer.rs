@@ -1,17 +1,34 @@
 //! Pretty printer for relanote AST
 
+use std::sync::Arc;
+
+use relanote_ast::music::{SectionContext, SynthProperty};
 use relanote_ast::*;
-use relanote_core::Spanned;
+use relanote_core::{Span, Spanned};
+use relanote_lexer::token::{Accidental, IntervalQuality};
 
 use crate::config::FormatConfig;
 
 /// Formatter for relanote code
+///
+/// Layout decisions (where to break `++` chains, block rows, list items) are
+/// made against `max_line_width`. When the original source is supplied with
+/// [`Formatter::with_source`], the formatter also keeps the row structure of
+/// multi-line blocks and lists, blank lines between items, the spelling of
+/// number literals and the call style (`f x` vs `f(x)`) of applications.
 pub struct Formatter {
     config: FormatConfig,
     output: String,
     indent_level: usize,
-    comments: Vec<Comment>,
+    comments: Arc<Vec<Comment>>,
     comment_idx: usize,
+    source: Option<Arc<str>>,
+    /// End of the last thing printed at the top level, for blank-line detection
+    last_end: Option<usize>,
+    /// Print everything on one line (used to measure expressions)
+    flat: bool,
+    /// Set in flat mode when a construct has to span several lines
+    broken: bool,
 }
 
 impl Formatter {
@@ -20,60 +37,271 @@ impl Formatter {
             config,
             output: String::new(),
             indent_level: 0,
-            comments: Vec::new(),
+            comments: Arc::new(Vec::new()),
             comment_idx: 0,
+            source: None,
+            last_end: None,
+            flat: false,
+            broken: false,
         }
     }
 
+    /// Use the original source text to preserve layout the AST doesn't record
+    pub fn with_source(mut self, source: &str) -> Self {
+        self.source = Some(Arc::from(source));
+        self
+    }
+
     pub fn format_program(&mut self, program: &Program) -> String {
         // Sort comments by position
-        self.comments = program.comments.clone();
-        self.comments.sort_by_key(|c| c.span.start);
+        let mut comments = program.comments.clone();
+        comments.sort_by_key(|c| c.span.start);
+        self.comments = Arc::new(comments);
+        self.comment_idx = 0;
+        self.last_end = None;
 
         for (i, item) in program.items.iter().enumerate() {
             // Print comments that come before this item
             self.print_comments_before(item.span.start);
 
-            if i > 0 && !self.output.ends_with('\n') {
-                self.output.push('\n');
-            }
+            self.blank_line_before(item.span.start);
             self.format_item(item);
+
+            // Keep a trailing comment on the line of the item it follows
+            let next_start = program
+                .items
+                .get(i + 1)
+                .map_or(usize::MAX, |next| next.span.start);
+            self.print_trailing_comment(next_start);
             self.output.push('\n');
+            self.last_end = Some(item.span.end);
+
+            // Comments inside the item that no layout could place go after it
+            self.print_comments_before(item.span.end);
         }
 
         // Print any remaining comments at the end
-        while self.comment_idx < self.comments.len() {
-            self.output.push_str(&self.comments[self.comment_idx].text);
+        self.print_comments_before(usize::MAX);
+
+        std::mem::take(&mut self.output)
+    }
+
+    // ===== Comments and whitespace =====
+
+    /// Print top-level comments that start before `pos`, one per line
+    fn print_comments_before(&mut self, pos: usize) {
+        while let Some(comment) = self.comments.get(self.comment_idx) {
+            if comment.span.start >= pos {
+                break;
+            }
+            let (text, span) = (comment.text.clone(), comment.span);
+            self.blank_line_before(span.start);
+            self.output.push_str(&text);
             self.output.push('\n');
             self.comment_idx += 1;
+            self.last_end = Some(span.end);
         }
+    }
 
-        std::mem::take(&mut self.output)
+    /// Keep a single blank line where the source had one or more
+    fn blank_line_before(&mut self, pos: usize) {
+        let (Some(source), Some(last_end)) = (&self.source, self.last_end) else {
+            return;
+        };
+        let gap = source.get(last_end..pos).unwrap_or("");
+        let whitespace = gap
+            .rsplit(|c: char| !c.is_whitespace())
+            .next()
+            .unwrap_or("");
+        if whitespace.matches('\n').count() >= 2 && !self.output.is_empty() {
+            self.output.push('\n');
+        }
     }
 
-    fn print_comments_before(&mut self, pos: usize) {
-        while self.comment_idx < self.comments.len() {
-            if self.comments[self.comment_idx].span.start < pos {
-                let text = self.comments[self.comment_idx].text.clone();
-                self.indent();
+    /// Append the next comment if it trails the current line and starts before `before`
+    fn print_trailing_comment(&mut self, before: usize) -> bool {
+        if self.flat {
+            return false;
+        }
+        match self.comments.get(self.comment_idx) {
+            Some(comment) if comment.trailing && comment.span.start < before => {
+                let text = comment.text.clone();
+                self.output.push(' ');
                 self.output.push_str(&text);
-                self.output.push('\n');
                 self.comment_idx += 1;
-            } else {
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Print comments starting before `before` on their own lines at `level`
+    ///
+    /// Must be called at the start of a freshly indented line.
+    fn print_nested_comments(&mut self, before: usize, level: usize) {
+        if self.flat {
+            return;
+        }
+        while let Some(comment) = self.comments.get(self.comment_idx) {
+            if comment.span.start >= before {
                 break;
             }
+            let text = comment.text.clone();
+            self.output.push_str(&text);
+            self.comment_idx += 1;
+            self.break_line(level);
         }
     }
 
+    fn comment_pending_before(&self, pos: usize) -> bool {
+        self.comments
+            .get(self.comment_idx)
+            .is_some_and(|comment| comment.span.start < pos)
+    }
+
+    fn has_comment_in(&self, span: Span) -> bool {
+        let idx = self
+            .comments
+            .partition_point(|comment| comment.span.start < span.start);
+        self.comments
+            .get(idx)
+            .is_some_and(|comment| comment.span.start < span.end)
+    }
+
+    fn source_text(&self, span: Span) -> Option<&str> {
+        self.source.as_deref()?.get(span.start..span.end)
+    }
+
+    /// Whether the source has a line break between two positions
+    fn source_breaks(&self, from: usize, to: usize) -> bool {
+        self.source
+            .as_deref()
+            .and_then(|source| source.get(from..to))
+            .is_some_and(|gap| gap.contains('\n'))
+    }
+
+    // ===== Output helpers =====
+
     fn indent(&mut self) {
         for _ in 0..(self.indent_level * self.config.indent_size) {
             self.output.push(' ');
         }
     }
 
+    /// Start a new line indented to `level`
+    fn break_line(&mut self, level: usize) {
+        self.break_to_column(level * self.config.indent_size);
+    }
+
+    fn break_to_column(&mut self, column: usize) {
+        let trimmed = self.output.trim_end_matches(' ').len();
+        self.output.truncate(trimmed);
+        self.output.push('\n');
+        self.output.push_str(&" ".repeat(column));
+    }
+
+    fn column(&self) -> usize {
+        let line_start = self.output.rfind('\n').map_or(0, |i| i + 1);
+        self.output[line_start..].chars().count()
+    }
+
+    fn fits(&self, text: &str) -> bool {
+        self.column() + text.chars().count() <= self.config.max_line_width
+    }
+
+    /// Render something on a single line without touching the output
+    ///
+    /// Returns `None` if it contains comments or a construct that must
+    /// span several lines.
+    fn measure(&self, span: Span, print: impl FnOnce(&mut Formatter)) -> Option<String> {
+        if self.has_comment_in(span) {
+            return None;
+        }
+        let mut sub = Formatter {
+            config: self.config.clone(),
+            output: String::new(),
+            indent_level: 0,
+            comments: Arc::clone(&self.comments),
+            comment_idx: self.comments.len(),
+            source: self.source.clone(),
+            last_end: None,
+            flat: true,
+            broken: false,
+        };
+        print(&mut sub);
+        (!sub.broken).then_some(sub.output)
+    }
+
+    fn measure_expr(&self, expr: &Spanned<Expr>) -> Option<String> {
+        self.measure(expr.span, |f| f.format_expr(expr))
+    }
+
+    /// Lay out a bracketed list, on one line if it fits and one element per line otherwise
+    fn format_list<T>(
+        &mut self,
+        open: &str,
+        close: &str,
+        elements: &[T],
+        span: Span,
+        element_span: impl Fn(&T) -> Span,
+        print: impl Fn(&mut Formatter, &T),
+    ) {
+        let starts_broken = elements
+            .first()
+            .is_some_and(|first| self.source_breaks(span.start, element_span(first).start));
+        let single = self.measure(span, |f| {
+            f.output.push_str(open);
+            for (i, element) in elements.iter().enumerate() {
+                if i > 0 {
+                    f.output.push_str(", ");
+                }
+                print(f, element);
+            }
+            f.output.push_str(close);
+        });
+
+        if self.flat {
+            self.broken |= starts_broken || single.is_none();
+            self.output
+                .push_str(single.as_deref().unwrap_or_else(|| open.trim_end()));
+            return;
+        }
+        if let Some(single) = single.filter(|s| !starts_broken && self.fits(s)) {
+            self.output.push_str(&single);
+            return;
+        }
+
+        let level = self.indent_level;
+        self.output.push_str(open.trim_end());
+        for (i, element) in elements.iter().enumerate() {
+            let element_start = element_span(element).start;
+            self.break_line(level + 1);
+            self.print_nested_comments(element_start, level + 1);
+            self.indent_level = level + 1;
+            print(self, element);
+            self.indent_level = level;
+            if i + 1 < elements.len() || self.config.trailing_commas {
+                self.output.push(',');
+            }
+            let next_start = elements
+                .get(i + 1)
+                .map_or(span.end, |next| element_span(next).start);
+            self.print_trailing_comment(next_start);
+        }
+        self.break_line(level);
+        self.print_nested_comments(span.end, level);
+        self.output.push_str(close.trim_start());
+    }
+
+    // ===== Items =====
+
     fn format_item(&mut self, item: &Spanned<Item>) {
         self.indent();
-        match &item.node {
+        self.format_item_kind(&item.node, item.span);
+    }
+
+    fn format_item_kind(&mut self, item: &Item, span: Span) {
+        match item {
             Item::ScaleDef(scale) => {
                 self.output.push_str("scale ");
                 self.output.push_str(scale.name.name.as_ref());
@@ -81,69 +309,54 @@ impl Formatter {
 
                 if let Some(base) = &scale.base {
                     self.format_expr(base);
-                    self.output.push_str(" with ");
-                }
-
-                self.output.push_str("{ ");
-                for (i, interval) in scale.intervals.iter().enumerate() {
-                    if i > 0 {
-                        self.output.push_str(", ");
-                    }
-                    self.format_interval(&interval.node);
+                } else {
+                    self.output.push_str("{ ");
+                    self.format_interval_list(&scale.intervals);
+                    self.output.push_str(" }");
                 }
-                self.output.push_str(" }");
             }
 
             Item::ChordDef(chord) => {
                 self.output.push_str("chord ");
                 self.output.push_str(chord.name.name.as_ref());
                 self.output.push_str(" = [ ");
-                for (i, interval) in chord.intervals.iter().enumerate() {
-                    if i > 0 {
-                        self.output.push_str(", ");
-                    }
-                    self.format_interval(&interval.node);
-                }
+                self.format_interval_list(&chord.intervals);
                 self.output.push_str(" ]");
             }
 
             Item::SynthDef(synth) => {
                 self.output.push_str("synth ");
                 self.output.push_str(synth.name.name.as_ref());
-                self.output.push_str(" = { ");
-                for (i, prop) in synth.properties.iter().enumerate() {
-                    if i > 0 {
-                        self.output.push_str(", ");
-                    }
-                    match &prop.node {
-                        relanote_ast::music::SynthProperty::Oscillator(expr) => {
-                            self.output.push_str("osc: ");
-                            self.format_expr(expr);
-                        }
-                        relanote_ast::music::SynthProperty::Envelope(expr) => {
-                            self.output.push_str("env: ");
-                            self.format_expr(expr);
-                        }
-                        relanote_ast::music::SynthProperty::Filter(expr) => {
-                            self.output.push_str("filter: ");
-                            self.format_expr(expr);
-                        }
-                        relanote_ast::music::SynthProperty::Detune(expr) => {
-                            self.output.push_str("detune: ");
-                            self.format_expr(expr);
-                        }
-                        relanote_ast::music::SynthProperty::PitchEnvelope(expr) => {
-                            self.output.push_str("pitch_env: ");
-                            self.format_expr(expr);
-                        }
-                    }
-                }
-                self.output.push_str(" }");
+                self.output.push_str(" = ");
+                self.format_list(
+                    "{ ",
+                    " }",
+                    &synth.properties,
+                    span,
+                    |prop| prop.span,
+                    |f, prop| {
+                        let (name, value) = match &prop.node {
+                            SynthProperty::Oscillator(expr) => ("osc", expr),
+                            SynthProperty::Envelope(expr) => ("env", expr),
+                            SynthProperty::Filter(expr) => ("filter", expr),
+                            SynthProperty::Detune(expr) => ("detune", expr),
+                            SynthProperty::PitchEnvelope(expr) => ("pitch_env", expr),
+                            SynthProperty::Lfo(expr) => ("lfo", expr),
+                        };
+                        f.output.push_str(name);
+                        f.output.push_str(": ");
+                        f.format_expr(value);
+                    },
+                );
             }
 
             Item::LetBinding(binding) => {
                 self.output.push_str("let ");
                 self.format_pattern(&binding.pattern);
+                if let Some(ty) = &binding.type_ann {
+                    self.output.push_str(": ");
+                    self.format_type(ty);
+                }
                 self.output.push_str(" = ");
                 self.format_expr(&binding.value);
             }
@@ -152,7 +365,51 @@ impl Formatter {
                 self.output.push_str("set ");
                 self.output.push_str(binding.name.name.as_ref());
                 self.output.push_str(" = ");
-                self.format_expr(&binding.value);
+                match (&binding.value.node, &binding.mode) {
+                    // The octave a key's tonic defaults to goes unwritten
+                    (Expr::AbsolutePitch(pitch), Some(_)) if pitch.octave == 4 => {
+                        self.format_note_name(pitch)
+                    }
+                    _ => self.format_expr(&binding.value),
+                }
+                if let Some(mode) = &binding.mode {
+                    self.output.push(' ');
+                    self.output.push_str(mode.name.as_ref());
+                }
+            }
+
+            Item::Meta(meta) => {
+                self.output.push_str("meta ");
+                self.format_list(
+                    "{ ",
+                    " }",
+                    &meta.fields,
+                    span,
+                    |field| field.span,
+                    |f, field| {
+                        f.output.push_str(field.node.name.name.as_ref());
+                        f.output.push_str(": ");
+                        f.format_expr(&field.node.value);
+                    },
+                );
+            }
+
+            Item::Test(test) => {
+                self.output.push_str("test \"");
+                self.output.push_str(&test.name);
+                self.output.push_str("\" { ");
+                self.format_expr(&test.body);
+                self.output.push_str(" }");
+            }
+
+            Item::Render(render) => {
+                self.output.push_str("render ");
+                if let Some(name) = &render.name {
+                    self.output.push('"');
+                    self.output.push_str(name);
+                    self.output.push_str("\" = ");
+                }
+                self.format_expr(&render.target);
             }
 
             Item::FunctionDef(func) => {
@@ -162,12 +419,20 @@ impl Formatter {
                     self.output.push(' ');
                     self.format_pattern(param);
                 }
+                if let Some(ty) = &func.return_type {
+                    self.output.push_str(": ");
+                    self.format_type(ty);
+                }
                 self.output.push_str(" = ");
                 self.format_expr(&func.body);
             }
 
             Item::Import(import) => {
                 self.output.push_str("import ");
+                let braced = import.items.len() > 1;
+                if braced {
+                    self.output.push_str("{ ");
+                }
                 for (i, item) in import.items.iter().enumerate() {
                     if i > 0 {
                         self.output.push_str(", ");
@@ -190,13 +455,27 @@ impl Formatter {
                         }
                     }
                 }
+                if braced {
+                    self.output.push_str(" }");
+                }
                 self.output.push_str(" from \"");
                 self.output.push_str(&import.from);
                 self.output.push('"');
             }
 
-            Item::Export(_) => {
-                self.output.push_str("export ...");
+            Item::Export(export) => {
+                self.output.push_str("export ");
+                match export {
+                    ExportDecl::Named(names) => self.format_ident_list(names),
+                    ExportDecl::Definition(item) => self.format_item_kind(item, span),
+                    ExportDecl::ReExport { items, from } => {
+                        self.output.push_str("{ ");
+                        self.format_ident_list(items);
+                        self.output.push_str(" } from \"");
+                        self.output.push_str(from);
+                        self.output.push('"');
+                    }
+                }
             }
 
             Item::Mod(mod_decl) => {
@@ -212,6 +491,24 @@ impl Formatter {
                     }
                     self.output.push_str(segment.name.as_ref());
                 }
+                match &use_decl.path.kind {
+                    UseKind::Simple => {}
+                    UseKind::Glob => self.output.push_str("::*"),
+                    UseKind::Group(items) => {
+                        self.output.push_str("::{");
+                        for (i, item) in items.iter().enumerate() {
+                            if i > 0 {
+                                self.output.push_str(", ");
+                            }
+                            self.output.push_str(item.name.name.as_ref());
+                            if let Some(alias) = &item.alias {
+                                self.output.push_str(" as ");
+                                self.output.push_str(alias.name.as_ref());
+                            }
+                        }
+                        self.output.push('}');
+                    }
+                }
             }
 
             Item::ExprStmt(expr) => {
@@ -220,13 +517,45 @@ impl Formatter {
         }
     }
 
+    fn format_ident_list(&mut self, idents: &[Ident]) {
+        for (i, ident) in idents.iter().enumerate() {
+            if i > 0 {
+                self.output.push_str(", ");
+            }
+            self.output.push_str(ident.name.as_ref());
+        }
+    }
+
+    fn format_interval_list(&mut self, intervals: &[Spanned<IntervalLit>]) {
+        for (i, interval) in intervals.iter().enumerate() {
+            if i > 0 {
+                self.output.push_str(", ");
+            }
+            // `R` is stored as P1 in interval lists
+            if self.source_text(interval.span) == Some("R") {
+                self.output.push('R');
+            } else {
+                self.format_interval(&interval.node);
+            }
+        }
+    }
+
+    // ===== Expressions =====
+
     fn format_expr(&mut self, expr: &Spanned<Expr>) {
         match &expr.node {
             Expr::Integer(n) => {
                 self.output.push_str(&n.to_string());
             }
             Expr::Float(n) => {
-                self.output.push_str(&n.to_string());
+                let text = match self.source_text(expr.span) {
+                    Some(text) if text.parse::<f64>() == Ok(*n) => text.to_string(),
+                    _ => float_literal(*n),
+                };
+                self.output.push_str(&text);
+            }
+            Expr::Duration(duration) => {
+                self.output.push_str(&duration_literal(duration, false));
             }
             Expr::String(s) => {
                 self.output.push('"');
@@ -246,26 +575,104 @@ impl Formatter {
                 self.format_interval(interval);
             }
             Expr::AbsolutePitch(pitch) => {
-                self.output.push(pitch.note);
-                match pitch.accidental {
-                    1 => self.output.push('#'),
-                    -1 => self.output.push('b'),
-                    _ => {}
-                }
+                self.format_note_name(pitch);
                 self.output.push_str(&pitch.octave.to_string());
             }
             Expr::Root => {
                 self.output.push('R');
             }
+            Expr::Articulation(art) => {
+                self.format_articulations(std::slice::from_ref(art));
+            }
             Expr::Block(block) => {
-                self.output.push_str("| ");
-                for (i, slot) in block.slots.iter().enumerate() {
-                    if i > 0 {
+                self.format_block(block, expr.span);
+            }
+            Expr::Tuplet(tuplet) => {
+                self.format_tuplet(tuplet);
+            }
+            Expr::Envelope(env) => {
+                self.output.push_str("env(");
+                self.format_expr(&env.from);
+                self.output.push_str(", ");
+                self.format_expr(&env.to);
+                self.output.push_str(", ");
+                self.format_expr(&env.duration);
+                self.output.push(')');
+            }
+            Expr::Part(part) => {
+                self.output.push_str("part ");
+                self.format_expr(&part.instrument);
+                if let Some(body) = &part.body {
+                    // Without braces the body has to be a primary expression
+                    if matches!(body.node, Expr::Ident(_) | Expr::Block(_) | Expr::Paren(_)) {
                         self.output.push(' ');
+                        self.format_expr(body);
+                    } else {
+                        self.output.push_str(" { ");
+                        self.format_expr(body);
+                        self.output.push_str(" }");
                     }
-                    self.format_slot(slot);
                 }
-                self.output.push_str(" |");
+            }
+            Expr::Section(section) => {
+                self.output.push_str("section ");
+                self.format_expr(&section.name);
+                if let Some(context) = &section.context {
+                    self.format_section_context(context);
+                    // Braces keep the body from being read as part of the context
+                    self.output.push_str(" { ");
+                    self.format_expr(&section.body);
+                    self.output.push_str(" }");
+                } else {
+                    self.output.push(' ');
+                    self.format_expr(&section.body);
+                }
+            }
+            Expr::Layer(layer) => {
+                self.output.push_str("layer ");
+                self.format_list(
+                    "[",
+                    "]",
+                    &layer.parts,
+                    expr.span,
+                    |part| part.span,
+                    |f, part| f.format_expr(part),
+                );
+            }
+            Expr::Context(context) => {
+                self.output.push_str("context {");
+                let settings = [
+                    ("tempo", &context.tempo),
+                    ("key", &context.key),
+                    ("swing", &context.swing),
+                ];
+                let mut first = true;
+                for (name, value) in settings {
+                    if let Some(value) = value {
+                        self.output.push_str(if first { " " } else { ", " });
+                        first = false;
+                        self.output.push_str(name);
+                        self.output.push_str(" = ");
+                        self.format_expr(value);
+                    }
+                }
+                self.output.push_str(if first { "} in " } else { " } in " });
+                self.format_expr(&context.body);
+            }
+            Expr::Kit(kit) => {
+                self.output.push_str("kit ");
+                self.format_list(
+                    "{ ",
+                    " }",
+                    &kit.hits,
+                    expr.span,
+                    |hit| hit.name_span,
+                    |f, hit| {
+                        f.output.push_str(hit.name.name.as_str());
+                        f.output.push_str(": ");
+                        f.format_expr(&hit.drum);
+                    },
+                );
             }
             Expr::Lambda(lambda) => {
                 self.output.push('\\');
@@ -279,15 +686,7 @@ impl Formatter {
                 self.format_expr(&lambda.body);
             }
             Expr::Application(app) => {
-                self.format_expr(&app.func);
-                self.output.push('(');
-                for (i, arg) in app.args.iter().enumerate() {
-                    if i > 0 {
-                        self.output.push_str(", ");
-                    }
-                    self.format_expr(arg);
-                }
-                self.output.push(')');
+                self.format_application(app);
             }
             Expr::Pipe(pipe) => {
                 self.format_expr(&pipe.left);
@@ -296,39 +695,431 @@ impl Formatter {
             }
             Expr::Array(elements) => {
                 self.output.push('[');
-                for (i, elem) in elements.iter().enumerate() {
-                    if i > 0 {
-                        self.output.push_str(", ");
-                    }
-                    self.format_expr(elem);
+                self.format_expr_list(elements);
+                self.output.push(']');
+            }
+            Expr::Tuple(elements) => {
+                self.output.push('(');
+                self.format_expr_list(elements);
+                if elements.len() == 1 {
+                    self.output.push(',');
                 }
+                self.output.push(')');
+            }
+            Expr::Binary(binary) if binary.op == BinaryOp::Concat => {
+                self.format_concat(expr);
+            }
+            Expr::Binary(binary) if binary.op == BinaryOp::Range => {
+                self.format_expr(&binary.left);
+                self.output.push_str("..");
+                self.format_expr(&binary.right);
+            }
+            Expr::Binary(binary) => {
+                self.format_expr(&binary.left);
+                self.output.push(' ');
+                self.output.push_str(binary_op(binary.op));
+                self.output.push(' ');
+                self.format_expr(&binary.right);
+            }
+            Expr::Unary(unary) => {
+                match unary.op {
+                    UnaryOp::Neg => self.output.push('-'),
+                    UnaryOp::Not => self.output.push_str("not "),
+                }
+                self.format_expr(&unary.operand);
+            }
+            Expr::Index(index) => {
+                self.format_expr(&index.base);
+                self.output.push('[');
+                self.format_expr(&index.index);
                 self.output.push(']');
             }
-            _ => {
+            Expr::Field(field) => {
+                self.format_expr(&field.base);
+                self.output.push('.');
+                self.output.push_str(field.field.name.as_ref());
+            }
+            Expr::If(if_expr) => {
+                self.output.push_str("if ");
+                self.format_expr(&if_expr.condition);
+                self.output.push_str(" then ");
+                self.format_expr(&if_expr.then_branch);
+                if let Some(else_branch) = &if_expr.else_branch {
+                    self.output.push_str(" else ");
+                    self.format_expr(else_branch);
+                }
+            }
+            Expr::Match(match_expr) => {
+                self.output.push_str("match ");
+                self.format_expr(&match_expr.scrutinee);
+                self.output.push(' ');
+                self.format_list(
+                    "{ ",
+                    " }",
+                    &match_expr.arms,
+                    expr.span,
+                    |arm| arm.pattern.span,
+                    |f, arm| {
+                        f.format_pattern(&arm.pattern);
+                        if let Some(guard) = &arm.guard {
+                            f.output.push_str(" if ");
+                            f.format_expr(guard);
+                        }
+                        f.output.push_str(" -> ");
+                        f.format_expr(&arm.body);
+                    },
+                );
+            }
+            Expr::Let(let_expr) => {
+                self.output.push_str("let ");
+                self.format_pattern(&let_expr.pattern);
+                if let Some(ty) = &let_expr.type_ann {
+                    self.output.push_str(": ");
+                    self.format_type(ty);
+                }
+                self.output.push_str(" = ");
+                self.format_expr(&let_expr.value);
+                self.output.push_str(" in ");
+                self.format_expr(&let_expr.body);
+            }
+            Expr::With(with) => {
+                self.format_expr(&with.base);
+                self.output.push_str(" with { ");
+                self.format_expr_list(&with.modifications);
+                self.output.push_str(" }");
+            }
+            Expr::InScale(in_scale) => {
+                self.output.push_str("in ");
+                self.format_expr(&in_scale.scale);
+            }
+            Expr::Annotated(inner, ty) => {
+                self.output.push('(');
+                self.format_expr(inner);
+                self.output.push_str(" : ");
+                self.format_type(ty);
+                self.output.push(')');
+            }
+            Expr::Paren(inner) => {
+                self.output.push('(');
+                self.format_expr(inner);
+                self.output.push(')');
+            }
+            Expr::Error => {
                 self.output.push_str("...");
             }
         }
     }
 
+    fn format_expr_list(&mut self, elements: &[Spanned<Expr>]) {
+        for (i, element) in elements.iter().enumerate() {
+            if i > 0 {
+                self.output.push_str(", ");
+            }
+            self.format_expr(element);
+        }
+    }
+
+    fn format_application(&mut self, app: &Application) {
+        self.format_expr(&app.func);
+
+        // Keep Haskell-style application (`f x y`) when that's what the source used
+        let haskell_style = match (&self.source, app.args.first()) {
+            (Some(source), Some(first)) => source
+                .get(app.func.span.end..first.span.start)
+                .is_some_and(|gap| !gap.contains('(')),
+            _ => false,
+        };
+        if haskell_style {
+            for arg in &app.args {
+                self.output.push(' ');
+                self.format_expr(arg);
+            }
+            return;
+        }
+
+        // `f (x)` and `f(x)` parse the same; keep whichever spacing was used
+        let first = app
+            .args
+            .iter()
+            .map(|arg| arg.span.start)
+            .chain(app.named.iter().map(|arg| arg.name_span.start))
+            .min();
+        let spaced = match (&self.source, first) {
+            (Some(source), Some(first)) => source
+                .get(app.func.span.end..first)
+                .is_some_and(|gap| gap.starts_with(' ')),
+            _ => false,
+        };
+        if spaced {
+            self.output.push(' ');
+        }
+        self.output.push('(');
+        self.format_expr_list(&app.args);
+        for (i, arg) in app.named.iter().enumerate() {
+            if i > 0 || !app.args.is_empty() {
+                self.output.push_str(", ");
+            }
+            self.output.push_str(arg.name.name.as_ref());
+            self.output.push_str(": ");
+            self.format_expr(&arg.value);
+        }
+        self.output.push(')');
+    }
+
+    /// Print a `++` chain, filling lines and wrapping at `max_line_width`
+    fn format_concat(&mut self, expr: &Spanned<Expr>) {
+        let mut operands = Vec::new();
+        collect_concat(expr, &mut operands);
+
+        if !self.flat {
+            if let Some(single) = self.measure_expr(expr) {
+                if self.fits(&single) {
+                    self.output.push_str(&single);
+                    return;
+                }
+            }
+        }
+
+        // Continuation lines line up with the first operand
+        let mut continuation = self.column();
+        if continuation > self.config.max_line_width / 2 {
+            continuation = (self.indent_level + 1) * self.config.indent_size;
+        }
+
+        for (i, operand) in operands.iter().enumerate() {
+            if i > 0 {
+                self.output.push_str(" ++");
+                let wrap = !self.flat
+                    && (self.comment_pending_before(operand.span.start)
+                        || self
+                            .measure_expr(operand)
+                            .is_some_and(|text| !self.fits(&format!(" {}", text))));
+                if wrap {
+                    self.print_trailing_comment(operand.span.start);
+                    self.break_to_column(continuation);
+                    while self.comment_pending_before(operand.span.start) {
+                        let text = self.comments[self.comment_idx].text.clone();
+                        self.output.push_str(&text);
+                        self.comment_idx += 1;
+                        self.break_to_column(continuation);
+                    }
+                } else {
+                    self.output.push(' ');
+                }
+            }
+            self.format_expr(operand);
+        }
+    }
+
+    fn format_section_context(&mut self, context: &SectionContext) {
+        self.output.push_str(" with ");
+        let entries = [
+            ("key", &context.key),
+            ("scale", &context.scale),
+            ("tempo", &context.tempo),
+        ];
+        let mut first = true;
+        for (name, value) in entries {
+            if let Some(value) = value {
+                if !first {
+                    self.output.push_str(", ");
+                }
+                first = false;
+                self.output.push_str(name);
+                self.output.push_str(": ");
+                self.format_expr(value);
+            }
+        }
+    }
+
+    // ===== Blocks =====
+
+    /// Print a block, keeping the source's rows and wrapping long rows
+    fn format_block(&mut self, block: &Block, span: Span) {
+        let cells: Vec<String> = block
+            .slots
+            .iter()
+            .map(|slot| {
+                self.measure(slot.span, |f| f.format_slot(slot))
+                    .unwrap_or_default()
+            })
+            .collect();
+        let beats = block
+            .beats
+            .map(|beats| format!(":{}", beats_literal(beats)))
+            .unwrap_or_default();
+
+        // Group slots into rows by source line
+        let mut rows: Vec<Vec<usize>> = Vec::new();
+        for (i, slot) in block.slots.iter().enumerate() {
+            let new_row =
+                i == 0 || self.source_breaks(block.slots[i - 1].span.end, slot.span.start);
+            if new_row {
+                rows.push(vec![i]);
+            } else if let Some(row) = rows.last_mut() {
+                row.push(i);
+            }
+        }
+
+        let single = if cells.is_empty() {
+            format!("| |{}", beats)
+        } else {
+            format!("| {} |{}", cells.join(" "), beats)
+        };
+        let multiline = rows.len() > 1 || self.config.block_multiline || self.has_comment_in(span);
+
+        if self.flat {
+            self.broken |= multiline;
+            self.output.push_str(&single);
+            return;
+        }
+        if !multiline && self.fits(&single) {
+            self.output.push_str(&single);
+            return;
+        }
+
+        // Wrap rows that don't fit in the remaining width
+        let level = self.indent_level + 1;
+        let available = self
+            .config
+            .max_line_width
+            .saturating_sub(level * self.config.indent_size);
+        let mut wrapped: Vec<Vec<usize>> = Vec::new();
+        for row in rows {
+            let mut current: Vec<usize> = Vec::new();
+            let mut width = 0;
+            for i in row {
+                let cell_width = cells[i].chars().count();
+                if !current.is_empty() && width + 1 + cell_width > available {
+                    wrapped.push(std::mem::take(&mut current));
+                    width = 0;
+                }
+                width += if current.is_empty() { 0 } else { 1 } + cell_width;
+                current.push(i);
+            }
+            wrapped.push(current);
+        }
+
+        // Column widths for bar alignment
+        let mut widths: Vec<usize> = Vec::new();
+        if self.config.align_bars {
+            for row in &wrapped {
+                for (column, &i) in row.iter().enumerate() {
+                    let width = cells[i].chars().count();
+                    match widths.get_mut(column) {
+                        Some(max) => *max = (*max).max(width),
+                        None => widths.push(width),
+                    }
+                }
+            }
+        }
+
+        self.output.push('|');
+        for (r, row) in wrapped.iter().enumerate() {
+            self.break_line(level);
+            if let Some(&first) = row.first() {
+                self.print_nested_comments(block.slots[first].span.start, level);
+            }
+            for (column, &i) in row.iter().enumerate() {
+                if column > 0 {
+                    self.output.push(' ');
+                }
+                self.output.push_str(&cells[i]);
+                if column + 1 < row.len() {
+                    let pad = widths
+                        .get(column)
+                        .map_or(0, |width| width.saturating_sub(cells[i].chars().count()));
+                    self.output.push_str(&" ".repeat(pad));
+                }
+            }
+            let next_start = wrapped
+                .get(r + 1)
+                .and_then(|next| next.first())
+                .map_or(span.end, |&i| block.slots[i].span.start);
+            self.print_trailing_comment(next_start);
+        }
+        self.break_line(self.indent_level);
+        self.print_nested_comments(span.end, self.indent_level);
+        self.output.push('|');
+        self.output.push_str(&beats);
+    }
+
+    fn format_tuplet(&mut self, tuplet: &Tuplet) {
+        self.output.push_str("{ ");
+        for (i, slot) in tuplet.contents.iter().enumerate() {
+            if i > 0 {
+                self.output.push(' ');
+            }
+            self.format_slot(slot);
+        }
+        self.output.push_str(" }:");
+        self.format_expr(&tuplet.target_beats);
+        if let Some(in_time_of) = &tuplet.in_time_of {
+            self.output.push(':');
+            self.format_expr(in_time_of);
+        }
+    }
+
     fn format_interval(&mut self, interval: &IntervalLit) {
+        if let Some(cents) = interval.cents {
+            self.output.push_str(&format!("{}c", cents));
+            return;
+        }
+        if let Some(steps) = interval.steps {
+            self.output.push_str(&format!("{}st", steps));
+            return;
+        }
         let quality = match interval.quality {
-            relanote_lexer::token::IntervalQuality::Major => "M",
-            relanote_lexer::token::IntervalQuality::Minor => "m",
-            relanote_lexer::token::IntervalQuality::Perfect => "P",
-            relanote_lexer::token::IntervalQuality::Diminished => "d",
-            relanote_lexer::token::IntervalQuality::Augmented => "A",
+            IntervalQuality::Major => "M",
+            IntervalQuality::Minor => "m",
+            IntervalQuality::Perfect => "P",
+            IntervalQuality::Diminished => "d",
+            IntervalQuality::Augmented => "A",
         };
         self.output.push_str(quality);
         self.output.push_str(&interval.degree.to_string());
-        for acc in &interval.accidentals {
+        self.format_accidentals(&interval.accidentals);
+    }
+
+    fn format_accidentals(&mut self, accidentals: &[Accidental]) {
+        for acc in accidentals {
             match acc {
-                relanote_lexer::token::Accidental::Sharp => self.output.push('+'),
-                relanote_lexer::token::Accidental::Flat => self.output.push('-'),
+                Accidental::Sharp => self.output.push('+'),
+                Accidental::Flat => self.output.push('-'),
             }
         }
     }
 
+    fn format_articulations(&mut self, articulations: &[Articulation]) {
+        for art in articulations {
+            match art {
+                Articulation::Staccato => self.output.push('*'),
+                Articulation::Accent => self.output.push('^'),
+                Articulation::Portamento => self.output.push('~'),
+                // Printed around the pitch and duration by `format_slot`
+                Articulation::Tie | Articulation::Tied => {}
+            }
+        }
+    }
+
+    fn format_duration(&mut self, duration: Option<DurationLit>) {
+        if let Some(duration) = duration {
+            self.output.push(':');
+            self.output.push_str(&duration_literal(&duration, true));
+        }
+    }
+
     fn format_slot(&mut self, slot: &Spanned<Slot>) {
+        let articulations = match &slot.node {
+            Slot::Note { articulations, .. }
+            | Slot::Chord { articulations, .. }
+            | Slot::Hit { articulations, .. } => articulations.as_slice(),
+            Slot::Rest { .. } | Slot::Tuplet(_) => &[],
+        };
+        if articulations.contains(&Articulation::Tied) {
+            self.output.push('~');
+        }
+
         match &slot.node {
             Slot::Note {
                 pitch,
@@ -336,24 +1127,12 @@ impl Formatter {
                 duration,
             } => {
                 self.format_pitch(&pitch.node);
-                for art in articulations {
-                    match art {
-                        Articulation::Staccato => self.output.push('*'),
-                        Articulation::Accent => self.output.push('^'),
-                        Articulation::Portamento => self.output.push('~'),
-                    }
-                }
-                if let Some(d) = duration {
-                    self.output.push(':');
-                    self.output.push_str(&d.to_string());
-                }
+                self.format_articulations(articulations);
+                self.format_duration(*duration);
             }
             Slot::Rest { duration } => {
                 self.output.push('-');
-                if let Some(d) = duration {
-                    self.output.push(':');
-                    self.output.push_str(&d.to_string());
-                }
+                self.format_duration(*duration);
             }
             Slot::Chord {
                 pitches,
@@ -363,34 +1142,30 @@ impl Formatter {
                 self.output.push('[');
                 for (i, pitch) in pitches.iter().enumerate() {
                     if i > 0 {
-                        self.output.push(' ');
+                        self.output.push_str(", ");
                     }
                     self.format_pitch(&pitch.node);
                 }
                 self.output.push(']');
-                for art in articulations {
-                    match art {
-                        Articulation::Staccato => self.output.push('*'),
-                        Articulation::Accent => self.output.push('^'),
-                        Articulation::Portamento => self.output.push('~'),
-                    }
-                }
-                if let Some(d) = duration {
-                    self.output.push(':');
-                    self.output.push_str(&d.to_string());
-                }
+                self.format_articulations(articulations);
+                self.format_duration(*duration);
             }
             Slot::Tuplet(tuplet) => {
-                self.output.push_str("{ ");
-                for (i, s) in tuplet.contents.iter().enumerate() {
-                    if i > 0 {
-                        self.output.push(' ');
-                    }
-                    self.format_slot(s);
-                }
-                self.output.push_str(" }:");
-                self.format_expr(&tuplet.target_beats);
+                self.format_tuplet(tuplet);
             }
+            Slot::Hit {
+                name,
+                articulations,
+                duration,
+            } => {
+                self.output.push_str(name.name.as_str());
+                self.format_articulations(articulations);
+                self.format_duration(*duration);
+            }
+        }
+
+        if articulations.contains(&Articulation::Tie) {
+            self.output.push('~');
         }
     }
 
@@ -403,27 +1178,27 @@ impl Formatter {
                 self.output.push_str(&idx.to_string());
                 self.output.push('>');
             }
-            Pitch::ScaleIndexMod(idx, accs) => {
+            Pitch::ScaleIndexMod(idx, accs, octave) => {
                 self.output.push('<');
                 self.output.push_str(&idx.to_string());
-                for acc in accs {
-                    match acc {
-                        relanote_lexer::token::Accidental::Sharp => self.output.push('+'),
-                        relanote_lexer::token::Accidental::Flat => self.output.push('-'),
-                    }
-                }
+                self.format_accidentals(accs);
+                let mark = if *octave > 0 { "'" } else { "," };
+                self.output
+                    .push_str(&mark.repeat(octave.unsigned_abs() as usize));
                 self.output.push('>');
             }
         }
     }
 
+    // ===== Patterns and types =====
+
     fn format_pattern(&mut self, pattern: &Spanned<Pattern>) {
         match &pattern.node {
             Pattern::Wildcard => self.output.push('_'),
             Pattern::Ident(ident) => self.output.push_str(ident.name.as_ref()),
             Pattern::Literal(lit) => match lit {
                 LiteralPattern::Integer(n) => self.output.push_str(&n.to_string()),
-                LiteralPattern::Float(n) => self.output.push_str(&n.to_string()),
+                LiteralPattern::Float(n) => self.output.push_str(&float_literal(*n)),
                 LiteralPattern::String(s) => {
                     self.output.push('"');
                     self.output.push_str(s);
@@ -436,15 +1211,403 @@ impl Formatter {
             },
             Pattern::Tuple(patterns) => {
                 self.output.push('(');
-                for (i, p) in patterns.iter().enumerate() {
+                self.format_pattern_list(patterns);
+                self.output.push(')');
+            }
+            Pattern::Array(array) => {
+                self.output.push('[');
+                self.format_pattern_list(&array.elements);
+                if let Some(rest) = &array.rest {
+                    if !array.elements.is_empty() {
+                        self.output.push_str(", ");
+                    }
+                    self.output.push_str("...");
+                    self.format_pattern(rest);
+                }
+                self.output.push(']');
+            }
+            Pattern::Constructor { name, args } => {
+                self.output.push_str(name.name.as_ref());
+                if !args.is_empty() {
+                    self.output.push('(');
+                    self.format_pattern_list(args);
+                    self.output.push(')');
+                }
+            }
+            Pattern::Or(left, right) => {
+                self.format_pattern(left);
+                self.output.push_str(" | ");
+                self.format_pattern(right);
+            }
+            Pattern::Annotated(inner, ty) => {
+                self.output.push('(');
+                self.format_pattern(inner);
+                self.output.push_str(": ");
+                self.format_type(ty);
+                self.output.push(')');
+            }
+        }
+    }
+
+    fn format_pattern_list(&mut self, patterns: &[Spanned<Pattern>]) {
+        for (i, p) in patterns.iter().enumerate() {
+            if i > 0 {
+                self.output.push_str(", ");
+            }
+            self.format_pattern(p);
+        }
+    }
+
+    fn format_note_name(&mut self, pitch: &AbsolutePitchLit) {
+        self.output.push(pitch.note);
+        match pitch.accidental {
+            1 => self.output.push('#'),
+            -1 => self.output.push('b'),
+            _ => {}
+        }
+    }
+
+    fn format_type(&mut self, ty: &TypeAnnotation) {
+        match ty {
+            TypeAnnotation::Named(name) => self.output.push_str(name.name.as_ref()),
+            TypeAnnotation::Generic(name, args) => {
+                self.output.push_str(name.name.as_ref());
+                self.output.push('<');
+                for (i, arg) in args.iter().enumerate() {
                     if i > 0 {
                         self.output.push_str(", ");
                     }
-                    self.format_pattern(p);
+                    self.format_type(arg);
+                }
+                self.output.push('>');
+            }
+            TypeAnnotation::Function(param, ret) => {
+                if matches!(**param, TypeAnnotation::Function(..)) {
+                    self.output.push('(');
+                    self.format_type(param);
+                    self.output.push(')');
+                } else {
+                    self.format_type(param);
+                }
+                self.output.push_str(" -> ");
+                self.format_type(ret);
+            }
+            TypeAnnotation::Tuple(elems) => {
+                self.output.push('(');
+                for (i, elem) in elems.iter().enumerate() {
+                    if i > 0 {
+                        self.output.push_str(", ");
+                    }
+                    self.format_type(elem);
                 }
                 self.output.push(')');
             }
-            _ => self.output.push_str("..."),
+            TypeAnnotation::Array(elem) => {
+                self.output.push('[');
+                self.format_type(elem);
+                self.output.push(']');
+            }
+            TypeAnnotation::Unit => self.output.push_str("()"),
+            TypeAnnotation::Var(name) => {
+                self.output.push('\'');
+                self.output.push_str(name.name.as_ref());
+            }
         }
     }
 }
+
+/// Flatten a left-associated `++` chain into its operands
+fn collect_concat<'a>(expr: &'a Spanned<Expr>, operands: &mut Vec<&'a Spanned<Expr>>) {
+    match &expr.node {
+        Expr::Binary(binary) if binary.op == BinaryOp::Concat => {
+            collect_concat(&binary.left, operands);
+            collect_concat(&binary.right, operands);
+        }
+        _ => operands.push(expr),
+    }
+}
+
+fn binary_op(op: BinaryOp) -> &'static str {
+    match op {
+        BinaryOp::Add => "+",
+        BinaryOp::Sub => "-",
+        BinaryOp::Mul => "*",
+        BinaryOp::Div => "/",
+        BinaryOp::Mod => "%",
+        BinaryOp::Pow => "^",
+        BinaryOp::Eq => "==",
+        BinaryOp::Ne => "!=",
+        BinaryOp::Lt => "<",
+        BinaryOp::Le => "<=",
+        BinaryOp::Gt => ">",
+        BinaryOp::Ge => ">=",
+        BinaryOp::And => "and",
+        BinaryOp::Or => "or",
+        BinaryOp::Concat => "++",
+        BinaryOp::Compose => ">>",
+        BinaryOp::Range => "..",
+        BinaryOp::Overlay => "&",
+    }
+}
+
+/// Float literal that still lexes as a float (`2.0`, not `2`)
+fn float_literal(value: f64) -> String {
+    let text = value.to_string();
+    if text.contains('.') || !value.is_finite() {
+        text
+    } else {
+        format!("{}.0", text)
+    }
+}
+
+/// Block duration after `|...|:`, which accepts integers and floats
+fn beats_literal(beats: f64) -> String {
+    if beats.fract() == 0.0 {
+        format!("{}", beats as i64)
+    } else {
+        beats.to_string()
+    }
+}
+
+/// A duration as written after a slot (`:2`, `:1/8`, `:1bar`) or in an
+/// expression (`2 beats`, `1/8`, `1 bar`)
+fn duration_literal(duration: &DurationLit, in_slot: bool) -> String {
+    let counted = |count: f64, unit: &str| {
+        let plural = if count == 1.0 { "" } else { "s" };
+        let separator = if in_slot { "" } else { " " };
+        format!("{}{}{}{}", beats_literal(count), separator, unit, plural)
+    };
+    match *duration {
+        DurationLit::Beats(beats) if in_slot => beats_literal(beats),
+        DurationLit::Beats(beats) => counted(beats, "beat"),
+        DurationLit::Bars(bars) => counted(bars, "bar"),
+        DurationLit::NoteValue { num, den, dotted } => {
+            format!("{}/{}{}", num, den, if dotted { "." } else { "" })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{format, format_range, format_with_source, FormatConfig};
+    use relanote_parser::parse;
+
+    fn fmt(source: &str) -> String {
+        let (program, diagnostics) = parse(source);
+        assert!(!diagnostics.has_errors(), "{:?}", diagnostics);
+        format(&program, &FormatConfig::default())
+    }
+
+    fn fmt_source(source: &str, config: &FormatConfig) -> String {
+        let (program, diagnostics) = parse(source);
+        assert!(!diagnostics.has_errors(), "{:?}", diagnostics);
+        format_with_source(&program, source, config)
+    }
+
+    #[test]
+    fn test_leading_and_trailing_comments() {
+        let source = "; header\nlet x = 1 ; one\n\n; body\nx\n; end\n";
+        assert_eq!(fmt(source), "; header\nlet x = 1 ; one\n; body\nx\n; end\n");
+    }
+
+    #[test]
+    fn test_type_annotations_round_trip() {
+        let source = "let up: Block -> Block = transpose P8\nlet twice (b: Block): Block = b ++ b\nlet n = (4 : Int)\n";
+        assert_eq!(fmt_source(source, &FormatConfig::default()), source);
+    }
+
+    #[test]
+    fn test_set_key_with_mode() {
+        assert_eq!(fmt("set key = F# minor"), "set key = F# minor\n");
+        assert_eq!(fmt("set key = Bb3 dorian"), "set key = Bb3 dorian\n");
+        assert_eq!(fmt("set key = D4 major"), "set key = D major\n");
+        assert_eq!(fmt("set key = D4"), "set key = D4\n");
+    }
+
+    #[test]
+    fn test_meta_block() {
+        assert_eq!(
+            fmt("meta {\n  title: \"Etude\"\n  composer: \"Anon\"\n}"),
+            "meta { title: \"Etude\", composer: \"Anon\" }\n"
+        );
+    }
+
+    #[test]
+    fn test_test_item() {
+        assert_eq!(
+            fmt("test \"chorus\" {\n  chorus |> transpose P5\n}"),
+            "test \"chorus\" { chorus |> transpose(P5) }\n"
+        );
+    }
+
+    #[test]
+    fn test_render_item() {
+        assert_eq!(fmt("render   song"), "render song\n");
+        assert_eq!(fmt("render \"demo\"=verse"), "render \"demo\" = verse\n");
+    }
+
+    #[test]
+    fn test_context() {
+        assert_eq!(
+            fmt("context {tempo=140,swing=0.6} in chorus"),
+            "context { tempo = 140, swing = 0.6 } in chorus\n"
+        );
+        assert_eq!(fmt("context {} in chorus"), "context {} in chorus\n");
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        assert_eq!(fmt("let x = tempo/2.0%7"), "let x = tempo / 2.0 % 7\n");
+        assert_eq!(fmt("let y = -2^0.5"), "let y = -2 ^ 0.5\n");
+    }
+
+    #[test]
+    fn test_with_modifications() {
+        let source = "scale Lydian = Major with { P4+ }\nlet c = Dominant7 with { -P5, M9 }\n";
+        assert_eq!(fmt(source), source);
+    }
+
+    #[test]
+    fn test_semitones() {
+        let source = "let up = M3 + 12st\nlet m = | R 7st 12st |\n";
+        assert_eq!(fmt(source), source);
+    }
+
+    #[test]
+    fn test_scale_in_cents() {
+        let source = "scale Just = { 0c, 204c, 386.3c }\n";
+        assert_eq!(fmt(source), source);
+    }
+
+    #[test]
+    fn test_comment_inside_block_stays_on_its_row() {
+        let source = "let m = | R\n  M3 ; third\n  P5 |\n";
+        assert_eq!(fmt(source), "let m = |\n    R M3 P5 ; third\n|\n");
+        assert_eq!(
+            fmt_source(source, &FormatConfig::default()),
+            "let m = |\n    R\n    M3 ; third\n    P5\n|\n"
+        );
+    }
+
+    #[test]
+    fn test_source_layout_is_preserved() {
+        let source = "set tempo = 120\n\n\nlet a = |\n  R M3\n  P5 -\n|:8\nlet b = transpose (R - P8) a\nlayer [\n  a,\n  b\n]\n";
+        assert_eq!(
+            fmt_source(source, &FormatConfig::default()),
+            "set tempo = 120\n\nlet a = |\n    R M3\n    P5 -\n|:8\nlet b = transpose (R - P8) a\nlayer [\n    a,\n    b,\n]\n"
+        );
+    }
+
+    #[test]
+    fn test_named_arguments() {
+        let source = "let p = m |> delay(time:250,mix:0.3)\n";
+        assert_eq!(
+            fmt_source(source, &FormatConfig::default()),
+            "let p = m |> delay(time: 250, mix: 0.3)\n"
+        );
+    }
+
+    #[test]
+    fn test_durations() {
+        let source = "let m = | R:1/8 -:1/4. M3:2 P5:1bar |\nlet d = 2 beats+1 bar-3/8\n";
+        assert_eq!(
+            fmt_source(source, &FormatConfig::default()),
+            "let m = | R:1/8 -:1/4. M3:2 P5:1bar |\nlet d = 2 beats + 1 bar - 3/8\n"
+        );
+    }
+
+    #[test]
+    fn test_tuplets() {
+        let source = "let m = | {R {M3 P5 M6}:3:2}:3 {R M3}:1/4 |\n";
+        assert_eq!(
+            fmt_source(source, &FormatConfig::default()),
+            "let m = | { R { M3 P5 M6 }:3:2 }:3 { R M3 }:1/4 |\n"
+        );
+    }
+
+    #[test]
+    fn test_kits_and_percussion_blocks() {
+        let source = "let rock = kit {x:Kick,o:Snare,h:42}\nlet beat = rock | x h o^ h:2 |\n";
+        assert_eq!(
+            fmt_source(source, &FormatConfig::default()),
+            "let rock = kit { x: Kick, o: Snare, h: 42 }\nlet beat = rock | x h o^ h:2 |\n"
+        );
+    }
+
+    #[test]
+    fn test_scale_degree_octaves() {
+        let source = "let m = | <1'> <5,> <4+''> <7-,> |\n";
+        assert_eq!(fmt_source(source, &FormatConfig::default()), source);
+    }
+
+    #[test]
+    fn test_ranges_and_indexing() {
+        let source = "let b = verse |> bars 0..2\nlet s = melody[2] ++ melody[0]\n";
+        assert_eq!(fmt_source(source, &FormatConfig::default()), source);
+    }
+
+    #[test]
+    fn test_overlay() {
+        let source = "let p = melody&bass ++ fill\n";
+        assert_eq!(
+            fmt_source(source, &FormatConfig::default()),
+            "let p = melody & bass ++ fill\n"
+        );
+    }
+
+    #[test]
+    fn test_ties() {
+        let source = "let m = | R:4~ | ++ | ~R:2 [R,M3]^:2~ |\n";
+        assert_eq!(
+            fmt_source(source, &FormatConfig::default()),
+            "let m = | R:4~ | ++ | ~R:2 [R, M3]^:2~ |\n"
+        );
+    }
+
+    #[test]
+    fn test_concat_wraps_at_max_line_width() {
+        let config = FormatConfig {
+            max_line_width: 30,
+            ..FormatConfig::default()
+        };
+        let source = "let song = intro ++ verse ++ chorus ++ outro\n";
+        assert_eq!(
+            fmt_source(source, &config),
+            "let song = intro ++ verse ++\n           chorus ++ outro\n"
+        );
+    }
+
+    #[test]
+    fn test_align_bars_and_trailing_commas() {
+        let config = FormatConfig {
+            indent_size: 4,
+            trailing_commas: false,
+            align_bars: true,
+            ..FormatConfig::default()
+        };
+        let source = "let a = |\n  <1>~ - <3>\n  - <10> -\n|\nlayer [\n  a,\n  a\n]\n";
+        assert_eq!(
+            fmt_source(source, &config),
+            "let a = |\n    <1>~ -    <3>\n    -    <10> -\n|\nlayer [\n    a,\n    a\n]\n"
+        );
+    }
+
+    #[test]
+    fn test_format_range_only_touches_overlapping_items() {
+        let source = "let a   =  1
+let b = |R   M3|  ; bass
+let c   = 3
+";
+        let (program, _) = parse(source);
+        let config = FormatConfig::default();
+        let (range, text) = format_range(&program, source, &config, 15..16).unwrap();
+        assert_eq!(&source[range.clone()], "let b = |R   M3|  ; bass");
+        assert_eq!(text, "let b = | R M3 | ; bass");
+
+        // An empty range picks the item under the cursor
+        let (range, text) = format_range(&program, source, &config, 4..4).unwrap();
+        assert_eq!(range, 0..12);
+        assert_eq!(text, "let a = 1");
+        assert!(format_range(&program, source, &config, source.len()..source.len()).is_none());
+    }
+}
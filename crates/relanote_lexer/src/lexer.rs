@@ -19,6 +19,9 @@ pub enum LexerError {
 /// Lexer for relanote source code
 pub struct Lexer<'src> {
     source_id: SourceId,
+    content: &'src str,
+    /// Where in `content` the inner lexer starts
+    offset: usize,
     inner: logos::Lexer<'src, TokenKind>,
     peeked: Option<Token>,
 }
@@ -26,17 +29,15 @@ pub struct Lexer<'src> {
 impl<'src> Lexer<'src> {
     /// Create a new lexer from source content
     pub fn new(source: &'src Source) -> Self {
-        Self {
-            source_id: source.id,
-            inner: TokenKind::lexer(&source.content),
-            peeked: None,
-        }
+        Self::from_str(source.id, &source.content)
     }
 
     /// Create a new lexer from a string (for testing)
     pub fn from_str(source_id: SourceId, content: &'src str) -> Self {
         Self {
             source_id,
+            content,
+            offset: 0,
             inner: TokenKind::lexer(content),
             peeked: None,
         }
@@ -45,7 +46,20 @@ impl<'src> Lexer<'src> {
     /// Get the current span
     fn current_span(&self) -> Span {
         let range = self.inner.span();
-        Span::new(self.source_id, range.start, range.end)
+        Span::new(
+            self.source_id,
+            self.offset + range.start,
+            self.offset + range.end,
+        )
+    }
+
+    /// Whether a doc comment starting at `start` is the first thing on its
+    /// line; elsewhere, `---` and `--|` are rests and bar lines
+    fn starts_line(&self, start: usize) -> bool {
+        self.content[..start]
+            .rsplit('\n')
+            .next()
+            .is_some_and(|line| line.trim().is_empty())
     }
 
     /// Peek at the next token without consuming it
@@ -64,6 +78,19 @@ impl<'src> Lexer<'src> {
 
         loop {
             match self.inner.next() {
+                Some(Ok(TokenKind::LineComment(text)))
+                    if !text.starts_with(';') && !self.starts_line(self.current_span().start) =>
+                {
+                    // Not a doc comment: take its first `-` and lex the
+                    // rest again
+                    let span = self.current_span();
+                    self.offset = span.start + 1;
+                    self.inner = TokenKind::lexer(&self.content[self.offset..]);
+                    return Some(Token::new(
+                        TokenKind::Minus,
+                        Span::new(self.source_id, span.start, span.start + 1),
+                    ));
+                }
                 Some(Ok(kind)) => {
                     // Keep all tokens including comments - formatter needs them
                     return Some(Token::new(kind, self.current_span()));
@@ -88,7 +115,8 @@ impl<'src> Lexer<'src> {
             tokens.push(token);
         }
         // Add EOF token
-        let eof_span = Span::new(self.source_id, self.inner.span().end, self.inner.span().end);
+        let end = self.offset + self.inner.span().end;
+        let eof_span = Span::new(self.source_id, end, end);
         tokens.push(Token::eof(eof_span));
         tokens
     }
@@ -232,6 +260,35 @@ mod tests {
         assert_eq!(tokens[3], TokenKind::Ident("b".to_string()));
     }
 
+    #[test]
+    fn test_lex_rest_runs_are_not_doc_comments() {
+        let tokens = lex("| R ---- M3 |\n| R --|\n  --- doc");
+        assert_eq!(
+            &tokens[..6],
+            &[
+                TokenKind::Pipe,
+                TokenKind::Root,
+                TokenKind::Minus,
+                TokenKind::Minus,
+                TokenKind::Minus,
+                TokenKind::Minus,
+            ]
+        );
+        assert!(matches!(tokens[6], TokenKind::Interval(_)));
+        assert_eq!(tokens[7], TokenKind::Pipe);
+        assert_eq!(
+            &tokens[9..14],
+            &[
+                TokenKind::Pipe,
+                TokenKind::Root,
+                TokenKind::Minus,
+                TokenKind::Minus,
+                TokenKind::Pipe,
+            ]
+        );
+        assert_eq!(tokens[15], TokenKind::LineComment("--- doc".to_string()));
+    }
+
     #[test]
     fn test_lex_comment_preserved() {
         let tokens = lex("a ; this is a comment\nb");
@@ -274,6 +331,29 @@ mod tests {
         assert!(matches!(tokens[3], TokenKind::AbsolutePitch(_)));
     }
 
+    #[test]
+    fn test_lex_note_names_without_octave() {
+        let tokens = lex("F# D Bb");
+        assert_eq!(tokens[0], TokenKind::SharpNote('F'));
+        assert_eq!(tokens[1], TokenKind::Ident("D".to_string()));
+        assert_eq!(tokens[2], TokenKind::Ident("Bb".to_string()));
+    }
+
+    #[test]
+    fn test_lex_cents() {
+        let tokens = lex("0c 204c 386.3c");
+        assert_eq!(tokens[0], TokenKind::Cents(0.0));
+        assert_eq!(tokens[1], TokenKind::Cents(204.0));
+        assert_eq!(tokens[2], TokenKind::Cents(386.3));
+    }
+
+    #[test]
+    fn test_lex_semitones() {
+        let tokens = lex("7st 12st");
+        assert_eq!(tokens[0], TokenKind::Semitones(7));
+        assert_eq!(tokens[1], TokenKind::Semitones(12));
+    }
+
     // ===== Operator Tests =====
 
     #[test]
@@ -372,9 +452,9 @@ mod tests {
 
     #[test]
     fn test_lex_floats() {
-        let tokens = lex("0.0 3.14 0.5");
+        let tokens = lex("0.0 2.75 0.5");
         assert_eq!(tokens[0], TokenKind::Float(0.0));
-        assert_eq!(tokens[1], TokenKind::Float(3.14));
+        assert_eq!(tokens[1], TokenKind::Float(2.75));
         assert_eq!(tokens[2], TokenKind::Float(0.5));
     }
 
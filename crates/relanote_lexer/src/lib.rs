@@ -2,4 +2,4 @@ mod lexer;
 pub mod token;
 
 pub use lexer::{Lexer, LexerError};
-pub use token::{Accidental, IntervalData, IntervalQuality, Token, TokenKind};
+pub use token::{Accidental, IntervalData, IntervalQuality, NoteValueData, Token, TokenKind};
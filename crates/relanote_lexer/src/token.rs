@@ -62,6 +62,24 @@ impl AbsolutePitchData {
 
 // Note: Dynamic markings are defined in relanote-ast to avoid conflicts with identifiers
 
+/// Note value data: `num/den` of a whole note, dotted with a trailing `.`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NoteValueData {
+    pub num: u32,
+    pub den: u32,
+    pub dotted: bool,
+}
+
+fn parse_note_value(s: &str) -> Option<NoteValueData> {
+    let dotted = s.ends_with('.');
+    let (num, den) = s.trim_end_matches('.').split_once('/')?;
+    Some(NoteValueData {
+        num: num.parse().ok()?,
+        den: den.parse().ok()?,
+        dotted,
+    })
+}
+
 fn parse_absolute_pitch(s: &str) -> Option<AbsolutePitchData> {
     let mut chars = s.chars().peekable();
 
@@ -146,6 +164,12 @@ pub enum TokenKind {
     #[token("set")]
     Set,
 
+    #[token("meta")]
+    Meta,
+
+    #[token("test")]
+    Test,
+
     #[token("in")]
     In,
 
@@ -176,6 +200,9 @@ pub enum TokenKind {
     #[token("layer")]
     Layer,
 
+    #[token("kit")]
+    Kit,
+
     #[token("part")]
     Part,
 
@@ -218,7 +245,7 @@ pub enum TokenKind {
     #[token("render")]
     Render,
 
-    #[token("Context")]
+    #[token("context")]
     Context,
 
     #[token("Key")]
@@ -239,14 +266,23 @@ pub enum TokenKind {
     #[regex(r"([CDEFGB][#b]?|A[#b])[0-9]", priority = 4, callback = |lex| parse_absolute_pitch(lex.slice()))]
     AbsolutePitch(AbsolutePitchData),
 
+    /// Sharp note name without an octave (C#, F#), as in `set key = F# minor`
+    /// Naturals and flats (D, Bb) lex as identifiers
+    #[regex(r"[A-G]#", callback = |lex| lex.slice().chars().next())]
+    SharpNote(char),
+
     // Note: Dynamic markings (pp, mf, ff, etc.) are handled at the parser level
     // to avoid conflicts with identifiers like 'f', 'p', 'm'
     /// Duration unit (e.g., 4bars, 2beats)
-    #[regex(r"[0-9]+bars?")]
-    Bars,
+    #[regex(r"[0-9]+bars?", |lex| lex.slice().trim_end_matches(['b', 'a', 'r', 's']).parse::<u32>().ok())]
+    Bars(u32),
+
+    #[regex(r"[0-9]+beats?", |lex| lex.slice().trim_end_matches(['b', 'e', 'a', 't', 's']).parse::<u32>().ok())]
+    Beats(u32),
 
-    #[regex(r"[0-9]+beats?")]
-    Beats,
+    /// Note value as a fraction of a whole note, optionally dotted (1/8, 1/4.)
+    #[regex(r"[0-9]+/[0-9]+\.?", |lex| parse_note_value(lex.slice()))]
+    NoteValue(NoteValueData),
 
     // ===== Articulations =====
     /// Staccato
@@ -289,6 +325,10 @@ pub enum TokenKind {
     #[token(">")]
     RAngle,
 
+    /// Octave up mark in a scale degree (`<3'>`)
+    #[token("'")]
+    Apostrophe,
+
     // ===== Operators =====
     #[token("|>", priority = 3)]
     PipeOp,
@@ -317,15 +357,28 @@ pub enum TokenKind {
     #[token(".")]
     Dot,
 
+    #[token("..")]
+    DotDot,
+
     #[token("-")]
     Minus,
 
     #[token("++")]
     PlusPlus,
 
+    #[token("&")]
+    Ampersand,
+
     #[token("+")]
     Plus,
 
+    /// Division; `1/8` without spaces is a note value
+    #[token("/")]
+    Slash,
+
+    #[token("%")]
+    Percent,
+
     // ===== Literals =====
     /// Integer literal
     #[regex(r"[0-9]+", |lex| lex.slice().parse::<i64>().ok())]
@@ -335,6 +388,14 @@ pub enum TokenKind {
     #[regex(r"[0-9]+\.[0-9]+", |lex| lex.slice().parse::<f64>().ok())]
     Float(f64),
 
+    /// Interval size in cents (204c, 386.3c)
+    #[regex(r"[0-9]+(\.[0-9]+)?c", |lex| lex.slice().trim_end_matches('c').parse::<f64>().ok())]
+    Cents(f64),
+
+    /// Interval size in semitones (7st, 12st)
+    #[regex(r"[0-9]+st", |lex| lex.slice().trim_end_matches("st").parse::<i32>().ok())]
+    Semitones(i32),
+
     /// String literal
     #[regex(r#""[^"]*""#, |lex| {
         let s = lex.slice();
@@ -347,8 +408,9 @@ pub enum TokenKind {
     Ident(String),
 
     // ===== Comments =====
-    /// Line comment (; ...)
-    #[regex(r";[^\n]*", |lex| lex.slice().to_string())]
+    /// Line comment (; ...), or doc comment (--- ... or --| ...) documenting
+    /// the definition below it
+    #[regex(r";[^\n]*|---[^\n]*|--\|[^\n]*", |lex| lex.slice().to_string())]
     LineComment(String),
 
     // ===== Newline (significant for some constructs) =====
@@ -375,6 +437,7 @@ impl TokenKind {
                 | TokenKind::Chord
                 | TokenKind::Section
                 | TokenKind::Layer
+                | TokenKind::Kit
                 | TokenKind::Part
                 | TokenKind::Synth
                 | TokenKind::Osc
@@ -402,8 +465,12 @@ impl TokenKind {
                 | TokenKind::Colon
                 | TokenKind::Comma
                 | TokenKind::Dot
+                | TokenKind::DotDot
+                | TokenKind::Ampersand
                 | TokenKind::Minus
                 | TokenKind::Plus
+                | TokenKind::Slash
+                | TokenKind::Percent
         )
     }
 
@@ -603,6 +670,22 @@ mod tests {
         assert_eq!(lexer.next(), Some(Ok(TokenKind::Ident("ff".to_string()))));
     }
 
+    #[test]
+    fn test_lex_durations() {
+        let mut lexer = TokenKind::lexer("1/8 3/4. 2beats 1bar");
+        let note_value = |num, den, dotted| NoteValueData { num, den, dotted };
+        assert_eq!(
+            lexer.next(),
+            Some(Ok(TokenKind::NoteValue(note_value(1, 8, false))))
+        );
+        assert_eq!(
+            lexer.next(),
+            Some(Ok(TokenKind::NoteValue(note_value(3, 4, true))))
+        );
+        assert_eq!(lexer.next(), Some(Ok(TokenKind::Beats(2))));
+        assert_eq!(lexer.next(), Some(Ok(TokenKind::Bars(1))));
+    }
+
     #[test]
     fn test_lex_string() {
         let mut lexer = TokenKind::lexer(r#""Piano" "Hello World""#);
@@ -632,4 +715,23 @@ mod tests {
         assert_eq!(lexer.next(), Some(Ok(TokenKind::Newline)));
         assert_eq!(lexer.next(), Some(Ok(TokenKind::Let)));
     }
+
+    #[test]
+    fn test_lex_doc_comment() {
+        let mut lexer = TokenKind::lexer("--- A riff\n--| in G\nlet x = 1 - -1");
+        assert_eq!(
+            lexer.next(),
+            Some(Ok(TokenKind::LineComment("--- A riff".to_string())))
+        );
+        assert_eq!(lexer.next(), Some(Ok(TokenKind::Newline)));
+        assert_eq!(
+            lexer.next(),
+            Some(Ok(TokenKind::LineComment("--| in G".to_string())))
+        );
+        assert_eq!(lexer.next(), Some(Ok(TokenKind::Newline)));
+        assert_eq!(lexer.next(), Some(Ok(TokenKind::Let)));
+        lexer.nth(2);
+        assert_eq!(lexer.next(), Some(Ok(TokenKind::Minus)));
+        assert_eq!(lexer.next(), Some(Ok(TokenKind::Minus)));
+    }
 }
@@ -0,0 +1,354 @@
+//! Code actions: quick fixes for diagnostics, and rewrites of the code at
+//! the cursor
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+use relanote_ast::{walk_expr, Expr, Item, Program, Visitor};
+use relanote_core::{DiagnosticCode, Source, Span, Spanned};
+use relanote_types::TypeChecker;
+use tower_lsp::lsp_types::{
+    CodeAction, CodeActionKind, Diagnostic, NumberOrString, TextEdit, Url, WorkspaceEdit,
+};
+
+use crate::server::{offset_to_position, position_to_offset};
+
+/// Whether an LSP diagnostic was reported with `code`
+pub fn has_code(diagnostic: &Diagnostic, code: DiagnosticCode) -> bool {
+    diagnostic.code == Some(NumberOrString::String(code.to_string()))
+}
+
+/// "Did you mean `transpose`?" for a name the checker doesn't know,
+/// replacing it with the closest name in scope
+pub fn did_you_mean(
+    uri: &Url,
+    source: &Source,
+    diagnostic: &Diagnostic,
+    checker: &TypeChecker,
+) -> Option<CodeAction> {
+    let start = position_to_offset(&source.content, diagnostic.range.start);
+    let end = position_to_offset(&source.content, diagnostic.range.end);
+    let name = source.content.get(start..end)?;
+    let suggestion = checker.similar_name(name)?;
+    Some(quick_fix(
+        format!("Did you mean `{}`?", suggestion),
+        diagnostic,
+        edit(uri, source, start..end, suggestion.to_string()),
+    ))
+}
+
+/// Insert the delimiter an unclosed block (or other construct) is missing
+/// where the diagnostic points
+pub fn close_delimiter(uri: &Url, source: &Source, diagnostic: &Diagnostic) -> Option<CodeAction> {
+    // "unclosed delimiter: expected |"
+    let expected = diagnostic.message.rsplit(' ').next()?;
+    let title = if expected == "|" {
+        "Add missing bar delimiter `|`".to_string()
+    } else {
+        format!("Add missing `{}`", expected)
+    };
+    let offset = position_to_offset(&source.content, diagnostic.range.start);
+    Some(quick_fix(
+        title,
+        diagnostic,
+        edit(uri, source, offset..offset, format!(" {}", expected)),
+    ))
+}
+
+/// Turn the interval array at `offset` (`[R, M3, P5]`) into a named chord
+/// defined above the item using it, so it can be passed where a chord is
+/// expected and reused
+pub fn extract_chord(
+    uri: &Url,
+    source: &Source,
+    program: &Program,
+    offset: usize,
+) -> Option<CodeAction> {
+    let mut finder = ChordLiteralAt {
+        offset,
+        found: None,
+    };
+    finder.visit_program(program);
+    let (span, intervals) = finder.found?;
+    let item = item_at(program, offset)?;
+
+    let content = &source.content;
+    let intervals: Vec<&str> = intervals
+        .iter()
+        .map(|interval| &content[interval.start..interval.end])
+        .collect();
+    let name = fresh_name(content, "NewChord");
+    let definition = format!("chord {} = [ {} ]\n", name, intervals.join(", "));
+    let insert_at = definition_offset(content, item.span.start);
+
+    let mut edits = HashMap::new();
+    edits.insert(
+        uri.clone(),
+        vec![
+            text_edit(source, insert_at..insert_at, definition),
+            text_edit(source, span.start..span.end, name),
+        ],
+    );
+    Some(CodeAction {
+        title: "Convert to a named chord definition".to_string(),
+        kind: Some(CodeActionKind::REFACTOR_EXTRACT),
+        edit: Some(WorkspaceEdit {
+            changes: Some(edits),
+            ..Default::default()
+        }),
+        ..Default::default()
+    })
+}
+
+/// Wrap the selection, or the expression of the item at the cursor when
+/// nothing is selected, in `layer [...]`
+pub fn wrap_in_layer(
+    uri: &Url,
+    source: &Source,
+    program: &Program,
+    range: Range<usize>,
+) -> Option<CodeAction> {
+    let content = &source.content;
+    let range = if range.is_empty() {
+        let expr = match &item_at(program, range.start)?.node {
+            Item::LetBinding(binding) => &binding.value,
+            Item::ExprStmt(expr) => expr,
+            _ => return None,
+        };
+        if matches!(expr.node, Expr::Layer(_)) {
+            return None;
+        }
+        expr.span.start..expr.span.end
+    } else {
+        // Leave the whitespace around the selection outside the layer
+        let text = content.get(range.clone())?;
+        let start = range.start + (text.len() - text.trim_start().len());
+        start..start + text.trim().len()
+    };
+    if range.is_empty() {
+        return None;
+    }
+
+    let wrapped = format!("layer [ {} ]", &content[range.clone()]);
+    Some(CodeAction {
+        title: "Wrap in `layer [...]`".to_string(),
+        kind: Some(CodeActionKind::REFACTOR_REWRITE),
+        edit: Some(edit(uri, source, range, wrapped)),
+        ..Default::default()
+    })
+}
+
+/// The innermost array of interval literals around an offset, with the
+/// spans of its intervals
+struct ChordLiteralAt {
+    offset: usize,
+    found: Option<(Span, Vec<Span>)>,
+}
+
+impl Visitor for ChordLiteralAt {
+    fn visit_expr(&mut self, expr: &Spanned<Expr>) {
+        if expr.span.start <= self.offset && self.offset <= expr.span.end {
+            if let Expr::Array(elements) = &expr.node {
+                let is_chord = !elements.is_empty()
+                    && elements
+                        .iter()
+                        .all(|e| matches!(e.node, Expr::Interval(_) | Expr::Root));
+                if is_chord {
+                    let intervals = elements.iter().map(|e| e.span).collect();
+                    self.found = Some((expr.span, intervals));
+                }
+            }
+        }
+        walk_expr(self, expr);
+    }
+}
+
+/// The top-level item spanning `offset`
+fn item_at(program: &Program, offset: usize) -> Option<&Spanned<Item>> {
+    program
+        .items
+        .iter()
+        .find(|item| item.span.start <= offset && offset <= item.span.end)
+}
+
+/// Where a definition needed by the item starting at `item_start` goes: the
+/// start of its line, above any doc comment so the comment stays with it
+fn definition_offset(content: &str, item_start: usize) -> usize {
+    let mut offset = content[..item_start].rfind('\n').map_or(0, |i| i + 1);
+    while offset > 0 {
+        let line_start = content[..offset - 1].rfind('\n').map_or(0, |i| i + 1);
+        let line = content[line_start..offset - 1].trim_start();
+        if !(line.starts_with("---") || line.starts_with("--|")) {
+            break;
+        }
+        offset = line_start;
+    }
+    offset
+}
+
+/// `base`, or `base2`, `base3`, ... when the document already uses it
+fn fresh_name(content: &str, base: &str) -> String {
+    let used = |name: &str| {
+        content.match_indices(name).any(|(i, _)| {
+            let after = content[i + name.len()..].chars().next();
+            !after.is_some_and(|c| c.is_alphanumeric() || c == '_')
+        })
+    };
+    if !used(base) {
+        return base.to_string();
+    }
+    (2..)
+        .map(|n| format!("{}{}", base, n))
+        .find(|name| !used(name))
+        .unwrap()
+}
+
+fn quick_fix(title: String, diagnostic: &Diagnostic, edit: WorkspaceEdit) -> CodeAction {
+    CodeAction {
+        title,
+        kind: Some(CodeActionKind::QUICKFIX),
+        diagnostics: Some(vec![diagnostic.clone()]),
+        edit: Some(edit),
+        is_preferred: Some(true),
+        ..Default::default()
+    }
+}
+
+/// A workspace edit replacing `range` (byte offsets) of one document
+fn edit(uri: &Url, source: &Source, range: Range<usize>, new_text: String) -> WorkspaceEdit {
+    let mut changes = HashMap::new();
+    changes.insert(uri.clone(), vec![text_edit(source, range, new_text)]);
+    WorkspaceEdit {
+        changes: Some(changes),
+        ..Default::default()
+    }
+}
+
+fn text_edit(source: &Source, range: Range<usize>, new_text: String) -> TextEdit {
+    TextEdit {
+        range: tower_lsp::lsp_types::Range {
+            start: offset_to_position(source, range.start),
+            end: offset_to_position(source, range.end),
+        },
+        new_text,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use relanote_resolver::ModuleChecker;
+    use tower_lsp::lsp_types::Range as LspRange;
+
+    fn uri() -> Url {
+        Url::parse("file:///song.rela").unwrap()
+    }
+
+    fn source(content: &str) -> Source {
+        Source::from_string("song.rela", content.to_string())
+    }
+
+    fn diagnostic(source: &Source, range: Range<usize>, code: DiagnosticCode) -> Diagnostic {
+        Diagnostic {
+            range: LspRange {
+                start: offset_to_position(source, range.start),
+                end: offset_to_position(source, range.end),
+            },
+            code: Some(NumberOrString::String(code.to_string())),
+            message: "unclosed delimiter: expected |".to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// The document after the action's edits
+    fn apply(content: &str, action: CodeAction) -> String {
+        let mut edits = action
+            .edit
+            .unwrap()
+            .changes
+            .unwrap()
+            .remove(&uri())
+            .unwrap();
+        edits.sort_by_key(|edit| edit.range.start);
+        let mut result = content.to_string();
+        for edit in edits.iter().rev() {
+            let start = position_to_offset(content, edit.range.start);
+            let end = position_to_offset(content, edit.range.end);
+            result.replace_range(start..end, &edit.new_text);
+        }
+        result
+    }
+
+    #[test]
+    fn test_did_you_mean() {
+        let content = "let riff = | R M3 | |> transpos P5";
+        let source = source(content);
+        let (program, _) = relanote_parser::parse_source(&source);
+        let (checker, _) = ModuleChecker::new().check(&program);
+        let start = content.find("transpos").unwrap();
+        let diagnostic = diagnostic(&source, start..start + 8, DiagnosticCode::UndefinedVariable);
+
+        let action = did_you_mean(&uri(), &source, &diagnostic, &checker).unwrap();
+        assert_eq!(action.title, "Did you mean `transpose`?");
+        assert_eq!(
+            apply(content, action),
+            "let riff = | R M3 | |> transpose P5"
+        );
+    }
+
+    #[test]
+    fn test_close_delimiter() {
+        let content = "let riff = | R M3 P5\nlet song = riff";
+        let source = source(content);
+        let (_, diagnostics) = relanote_parser::parse_source(&source);
+        let error = diagnostics.errors().next().unwrap();
+        let diagnostic = diagnostic(
+            &source,
+            error.span.start..error.span.end,
+            DiagnosticCode::UnclosedDelimiter,
+        );
+
+        let action = close_delimiter(&uri(), &source, &diagnostic).unwrap();
+        assert_eq!(action.title, "Add missing bar delimiter `|`");
+        assert_eq!(
+            apply(content, action),
+            "let riff = | R M3 P5 |\nlet song = riff"
+        );
+    }
+
+    #[test]
+    fn test_extract_chord() {
+        let content = "use std::patterns::*\n--- Left hand\nlet low = alberti [R, M3, P5] 4\n";
+        let source = source(content);
+        let (program, _) = relanote_parser::parse_source(&source);
+        let offset = content.find("M3").unwrap();
+
+        let action = extract_chord(&uri(), &source, &program, offset).unwrap();
+        assert_eq!(
+            apply(content, action),
+            "use std::patterns::*\nchord NewChord = [ R, M3, P5 ]\n--- Left hand\nlet low = alberti NewChord 4\n"
+        );
+        assert!(extract_chord(&uri(), &source, &program, 0).is_none());
+    }
+
+    #[test]
+    fn test_wrap_in_layer() {
+        let content = "let riff = | R M3 |\nriff ++ riff\n";
+        let source = source(content);
+        let (program, _) = relanote_parser::parse_source(&source);
+
+        let cursor = content.find("++").unwrap();
+        let action = wrap_in_layer(&uri(), &source, &program, cursor..cursor).unwrap();
+        assert_eq!(
+            apply(content, action),
+            "let riff = | R M3 |\nlayer [ riff ++ riff ]\n"
+        );
+
+        let selection = content.find("| R").unwrap()..content.find('\n').unwrap();
+        let action = wrap_in_layer(&uri(), &source, &program, selection).unwrap();
+        assert_eq!(
+            apply(content, action),
+            "let riff = layer [ | R M3 | ]\nriff ++ riff\n"
+        );
+    }
+}
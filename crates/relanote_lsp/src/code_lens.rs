@@ -0,0 +1,140 @@
+//! Code lenses: "▶ Play" and "Render MIDI" above render targets and
+//! sections, running commands the editor implements
+
+use relanote_ast::{walk_expr, Expr, Item, Program, Visitor};
+use relanote_core::{Source, Span, Spanned};
+use serde::Serialize;
+use tower_lsp::lsp_types::{CodeLens, Command, Position, Range, Url};
+
+/// Command that plays the song a lens is above
+pub const PLAY_COMMAND: &str = "relanote.play";
+/// Command that renders the song a lens is above to a MIDI file
+pub const RENDER_MIDI_COMMAND: &str = "relanote.renderMidi";
+
+/// The argument of a lens command: the document and which of its songs to
+/// play or render, as `relanote render --target`/`--section` select them
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LensTarget {
+    pub uri: Url,
+    /// Name of a render target, or the number of an unnamed one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    /// Name of a section
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub section: Option<String>,
+}
+
+/// Lenses above each `render` item and each named `section` of a document
+pub fn code_lenses(uri: &Url, source: &Source, program: &Program) -> Vec<CodeLens> {
+    let mut lenses = Vec::new();
+    let mut renders = 0;
+    for item in &program.items {
+        if let Item::Render(render) = &item.node {
+            renders += 1;
+            let target = render.name.clone().unwrap_or_else(|| renders.to_string());
+            let target = LensTarget {
+                uri: uri.clone(),
+                target: Some(target),
+                section: None,
+            };
+            lenses.extend(lenses_for(source, item.span, &target));
+        }
+    }
+
+    let mut sections = Sections::default();
+    sections.visit_program(program);
+    for (span, name) in sections.found {
+        let target = LensTarget {
+            uri: uri.clone(),
+            target: None,
+            section: Some(name),
+        };
+        lenses.extend(lenses_for(source, span, &target));
+    }
+    lenses.sort_by_key(|lens| lens.range.start);
+    lenses
+}
+
+fn lenses_for(source: &Source, span: Span, target: &LensTarget) -> [CodeLens; 2] {
+    let loc = source.location(span.start);
+    let start = Position {
+        line: (loc.line - 1) as u32,
+        character: (loc.column - 1) as u32,
+    };
+    let range = Range { start, end: start };
+    let arguments = Some(vec![serde_json::to_value(target).unwrap()]);
+    let lens = |title: &str, command: &str| CodeLens {
+        range,
+        command: Some(Command {
+            title: title.to_string(),
+            command: command.to_string(),
+            arguments: arguments.clone(),
+        }),
+        data: None,
+    };
+    [
+        lens("▶ Play", PLAY_COMMAND),
+        lens("Render MIDI", RENDER_MIDI_COMMAND),
+    ]
+}
+
+/// Sections named by a string literal, which `--section` can select
+#[derive(Default)]
+struct Sections {
+    found: Vec<(Span, String)>,
+}
+
+impl Visitor for Sections {
+    fn visit_expr(&mut self, expr: &Spanned<Expr>) {
+        if let Expr::Section(section) = &expr.node {
+            if let Expr::String(name) = &section.name.node {
+                self.found.push((expr.span, name.clone()));
+            }
+        }
+        walk_expr(self, expr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lenses_above_render_targets_and_sections() {
+        let content = "let verse = section \"Verse\" { layer [| R M3 |] }\n\
+                       render \"demo\" = verse\n\
+                       render verse ++ verse\n";
+        let source = Source::from_string("song.rela", content.to_string());
+        let (program, _) = relanote_parser::parse_source(&source);
+        let uri = Url::parse("file:///song.rela").unwrap();
+
+        let lenses = code_lenses(&uri, &source, &program);
+        let summary: Vec<(u32, String, serde_json::Value)> = lenses
+            .into_iter()
+            .map(|lens| {
+                let command = lens.command.unwrap();
+                (
+                    lens.range.start.line,
+                    command.title,
+                    command.arguments.unwrap().remove(0),
+                )
+            })
+            .collect();
+
+        let section = serde_json::json!({ "uri": uri, "section": "Verse" });
+        let demo = serde_json::json!({ "uri": uri, "target": "demo" });
+        let second = serde_json::json!({ "uri": uri, "target": "2" });
+        assert_eq!(
+            summary,
+            vec![
+                (0, "▶ Play".to_string(), section.clone()),
+                (0, "Render MIDI".to_string(), section),
+                (1, "▶ Play".to_string(), demo.clone()),
+                (1, "Render MIDI".to_string(), demo),
+                (2, "▶ Play".to_string(), second.clone()),
+                (2, "Render MIDI".to_string(), second),
+            ]
+        );
+    }
+}
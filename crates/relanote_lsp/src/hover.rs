@@ -0,0 +1,440 @@
+//! Hover content resolved from the program: the length of a block, the
+//! intervals of a scale and the pitches of a chord in the current key
+
+use relanote_ast::{walk_expr, walk_slot, ExportDecl, Expr, IntervalLit, Item, Pitch};
+use relanote_ast::{Program, ScaleDef, Slot, UnaryOp, Visitor};
+use relanote_core::{Span, Spanned};
+use relanote_eval::pitch_name;
+use relanote_lexer::{Accidental, IntervalQuality};
+
+use crate::inlay_hints::{beats_label, beats_per_bar};
+
+/// The key chords are heard in without a `set key`: middle C
+const DEFAULT_KEY: i32 = 60;
+
+/// Hover for the innermost chord slot or block literal at `offset`: a
+/// chord's pitches in the key, a block's length and number of notes
+pub fn literal_hover(program: &Program, offset: usize) -> Option<(Span, String)> {
+    let mut literals = Literals {
+        offset,
+        found: Vec::new(),
+    };
+    literals.visit_program(program);
+    let (span, literal) = literals
+        .found
+        .into_iter()
+        .min_by_key(|(span, _)| span.end - span.start)?;
+
+    let content = match literal {
+        Literal::Block(block) => {
+            let mut lines = vec!["**Block**".to_string(), String::new()];
+            if let Some(beats) = block.written_beats(beats_per_bar(program)) {
+                lines.push(format!("- Length: `{}`", beats_label(beats)));
+            }
+            lines.push(format!("- Notes: `{}`", note_count(&block.slots)));
+            lines.join("\n")
+        }
+        Literal::Chord(pitches) => {
+            let intervals = pitches
+                .iter()
+                .map(|pitch| match &pitch.node {
+                    Pitch::Interval(interval) => Some(interval.clone()),
+                    Pitch::Root => Some(IntervalLit::new(IntervalQuality::Perfect, 1)),
+                    // Scale degrees depend on the scale the block is played in
+                    Pitch::ScaleIndex(_) | Pitch::ScaleIndexMod(..) => None,
+                })
+                .collect::<Option<Vec<_>>>()?;
+            format!(
+                "**Chord**\n\n{}",
+                chord_lines(&intervals, key_at(program, offset))
+            )
+        }
+    };
+    Some((span, content))
+}
+
+/// Markdown for the scale `name` refers to at `offset`, a definition of the
+/// program or of the prelude, listing its intervals with derivations and
+/// accidentals applied
+pub fn scale_hover(program: &Program, name: &str, offset: usize) -> Option<String> {
+    let (def, start) = find_scale(program, name, Some(offset))?;
+    let (definition, description) = match start {
+        Some(_) => (
+            format!("scale {}", def.name.name),
+            def.doc
+                .clone()
+                .unwrap_or_else(|| "User-defined scale".to_string()),
+        ),
+        None => {
+            let scale = relanote_meta::scale(name)?;
+            (
+                format!("scale {} = {}", scale.name, scale.definition),
+                scale.description.to_string(),
+            )
+        }
+    };
+
+    let mut content = format!("```rela\n{}\n```\n\n{}", definition, description);
+    if let Some(intervals) = scale_intervals(program, &def, start) {
+        content.push_str(&format!(
+            "\n\n- Intervals: `{}`",
+            interval_names(&intervals)
+        ));
+    }
+    Some(content)
+}
+
+/// Markdown for the chord `name` refers to at `offset`, with its pitches
+/// relative to the `set key` in effect there
+pub fn chord_hover(program: &Program, name: &str, offset: usize) -> Option<String> {
+    let defined = definitions(program, offset).find_map(|(_, item)| match item {
+        Item::ChordDef(def) if def.name.name.as_str() == name => Some(def),
+        _ => None,
+    });
+    let (def, definition, description) = match defined {
+        Some(def) => (
+            def.clone(),
+            format!("chord {}", def.name.name),
+            def.doc
+                .clone()
+                .unwrap_or_else(|| "User-defined chord".to_string()),
+        ),
+        _ => {
+            let chord = relanote_meta::chord(name)?;
+            let program = prelude_definition("chord", chord.name, chord.definition)?;
+            let Some(Item::ChordDef(def)) = program.items.first().map(|item| &item.node) else {
+                return None;
+            };
+            (
+                def.clone(),
+                format!("chord {} = {}", chord.name, chord.definition),
+                chord.description.to_string(),
+            )
+        }
+    };
+
+    let intervals: Vec<IntervalLit> = def.intervals.iter().map(|i| i.node.clone()).collect();
+    Some(format!(
+        "```rela\n{}\n```\n\n{}\n\n{}",
+        definition,
+        description,
+        chord_lines(&intervals, key_at(program, offset))
+    ))
+}
+
+/// The intervals of a chord and its pitches from `key`
+fn chord_lines(intervals: &[IntervalLit], key: i32) -> String {
+    let pitches: Vec<String> = intervals
+        .iter()
+        .map(|interval| pitch_name(key + interval.semitones()))
+        .collect();
+    format!(
+        "- Intervals: `{}`\n- In {}: `{}`",
+        interval_names(intervals),
+        pitch_name(key),
+        pitches.join(" ")
+    )
+}
+
+/// Top-level items made before `offset` with where each starts, last
+/// first, exported definitions unwrapped
+fn definitions(program: &Program, offset: usize) -> impl Iterator<Item = (usize, &Item)> {
+    program
+        .items
+        .iter()
+        .rev()
+        .filter(move |item| item.span.start <= offset)
+        .map(|item| match &item.node {
+            Item::Export(ExportDecl::Definition(def)) => (item.span.start, def.as_ref()),
+            node => (item.span.start, node),
+        })
+}
+
+/// The definition of the scale `name`, with where it starts when the
+/// program defines it before `before`, or the prelude's when `before` is
+/// `None` or the program doesn't define it
+fn find_scale(
+    program: &Program,
+    name: &str,
+    before: Option<usize>,
+) -> Option<(ScaleDef, Option<usize>)> {
+    if let Some(before) = before {
+        let defined = definitions(program, before).find_map(|(start, item)| match item {
+            Item::ScaleDef(def) if def.name.name.as_str() == name => {
+                Some((def.clone(), Some(start)))
+            }
+            _ => None,
+        });
+        if defined.is_some() {
+            return defined;
+        }
+    }
+    let scale = relanote_meta::scale(name)?;
+    let program = prelude_definition("scale", scale.name, scale.definition)?;
+    match program.items.into_iter().next()?.node {
+        Item::ScaleDef(def) => Some((def, None)),
+        _ => None,
+    }
+}
+
+/// A prelude scale or chord parsed from its definition
+fn prelude_definition(keyword: &str, name: &str, definition: &str) -> Option<Program> {
+    let (program, diagnostics) =
+        relanote_parser::parse(&format!("{} {} = {}", keyword, name, definition));
+    diagnostics.is_empty().then_some(program)
+}
+
+/// The intervals of a scale, lowest first; `None` when they are only known
+/// once the program is evaluated
+fn scale_intervals(
+    program: &Program,
+    def: &ScaleDef,
+    start: Option<usize>,
+) -> Option<Vec<IntervalLit>> {
+    match &def.base {
+        Some(base) => derived_intervals(program, base, start),
+        None => Some(def.intervals.iter().map(|i| i.node.clone()).collect()),
+    }
+}
+
+/// The intervals of a scale expression a scale is derived from: another
+/// scale, or one `with` some degrees changed
+fn derived_intervals(
+    program: &Program,
+    expr: &Spanned<Expr>,
+    start: Option<usize>,
+) -> Option<Vec<IntervalLit>> {
+    match &expr.node {
+        Expr::Ident(ident) => {
+            // Scales defined before this one, which can't refer to itself
+            let before = start.and_then(|start| start.checked_sub(1));
+            let (def, start) = find_scale(program, ident.name.as_str(), before)?;
+            scale_intervals(program, &def, start)
+        }
+        Expr::With(with) => {
+            let mut intervals = derived_intervals(program, &with.base, start)?;
+            for change in &with.modifications {
+                let (target, remove) = match &change.node {
+                    Expr::Unary(unary) if unary.op == UnaryOp::Neg => (&unary.operand.node, true),
+                    node => (node, false),
+                };
+                let interval = match target {
+                    Expr::Interval(interval) if interval.cents.is_none() => interval.clone(),
+                    Expr::Root => IntervalLit::new(IntervalQuality::Perfect, 1),
+                    _ => return None,
+                };
+                let existing = intervals.iter().position(|i| i.degree == interval.degree);
+                match (existing, remove) {
+                    (Some(index), true) => {
+                        intervals.remove(index);
+                    }
+                    (Some(index), false) => intervals[index] = interval,
+                    (None, true) => return None,
+                    (None, false) => intervals.push(interval),
+                }
+            }
+            intervals.sort_by(|a, b| a.cents().total_cmp(&b.cents()));
+            Some(intervals)
+        }
+        _ => None,
+    }
+}
+
+/// `R, M2, A4`: intervals named by their size, so accidentals show as the
+/// quality they make (`P4+` is `A4`)
+fn interval_names(intervals: &[IntervalLit]) -> String {
+    intervals
+        .iter()
+        .map(interval_name)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn interval_name(interval: &IntervalLit) -> String {
+    if let Some(cents) = interval.cents {
+        return format!("{}c", cents);
+    }
+    if let Some(steps) = interval.steps {
+        return format!("{}st", steps);
+    }
+    let degree = interval.degree;
+    let simple = (degree.max(1) - 1) % 7 + 1;
+    let perfect = matches!(simple, 1 | 4 | 5);
+    let natural = IntervalLit::new(
+        if perfect {
+            IntervalQuality::Perfect
+        } else {
+            IntervalQuality::Major
+        },
+        degree,
+    )
+    .semitones();
+    let quality = match (interval.semitones() - natural, perfect) {
+        (0, true) if degree == 1 => return "R".to_string(),
+        (0, true) => "P",
+        (0, false) => "M",
+        (-1, false) => "m",
+        (1, _) => "A",
+        (-1, true) | (-2, false) => "d",
+        // Doubly altered: keep the accidentals as written
+        _ => {
+            let written = match interval.quality {
+                IntervalQuality::Perfect => "P",
+                IntervalQuality::Major => "M",
+                IntervalQuality::Minor => "m",
+                IntervalQuality::Augmented => "A",
+                IntervalQuality::Diminished => "d",
+            };
+            let accidentals: String = interval
+                .accidentals
+                .iter()
+                .map(|a| match a {
+                    Accidental::Sharp => '+',
+                    Accidental::Flat => '-',
+                })
+                .collect();
+            return format!("{}{}{}", written, degree, accidentals);
+        }
+    };
+    format!("{}{}", quality, degree)
+}
+
+/// MIDI note of the last top-level `set key` before `offset`
+fn key_at(program: &Program, offset: usize) -> i32 {
+    definitions(program, offset)
+        .find_map(|(_, item)| match item {
+            Item::SetBinding(set) if set.name.name.as_str() == "key" => match &set.value.node {
+                Expr::AbsolutePitch(pitch) => Some(pitch.to_midi_note() as i32),
+                _ => None,
+            },
+            _ => None,
+        })
+        .unwrap_or(DEFAULT_KEY)
+}
+
+/// Notes the slots sound, each pitch of a chord counting as one
+fn note_count(slots: &[Spanned<Slot>]) -> usize {
+    slots
+        .iter()
+        .map(|slot| match &slot.node {
+            Slot::Note { .. } | Slot::Hit { .. } => 1,
+            Slot::Chord { pitches, .. } => pitches.len(),
+            Slot::Rest { .. } => 0,
+            Slot::Tuplet(tuplet) => note_count(&tuplet.contents),
+        })
+        .sum()
+}
+
+enum Literal {
+    Block(relanote_ast::Block),
+    Chord(Vec<Spanned<Pitch>>),
+}
+
+/// Block literals and chord slots around an offset
+struct Literals {
+    offset: usize,
+    found: Vec<(Span, Literal)>,
+}
+
+impl Literals {
+    fn contains(&self, span: Span) -> bool {
+        span.start <= self.offset && self.offset <= span.end
+    }
+}
+
+impl Visitor for Literals {
+    fn visit_expr(&mut self, expr: &Spanned<Expr>) {
+        if let Expr::Block(block) = &expr.node {
+            if self.contains(expr.span) {
+                self.found.push((expr.span, Literal::Block(block.clone())));
+            }
+        }
+        walk_expr(self, expr);
+    }
+
+    fn visit_slot(&mut self, slot: &Spanned<Slot>) {
+        if let Slot::Chord { pitches, .. } = &slot.node {
+            if self.contains(slot.span) {
+                self.found
+                    .push((slot.span, Literal::Chord(pitches.clone())));
+            }
+        }
+        walk_slot(self, slot);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The bulleted lines of `content`
+    fn facts(content: &str) -> Vec<&str> {
+        content
+            .lines()
+            .filter(|line| line.starts_with("- "))
+            .collect()
+    }
+
+    #[test]
+    fn test_block_length_and_notes() {
+        let source =
+            "set time_signature = (3, 4)\nlet riff = | R M3 [R M3 P5] - |:2 ++ | R:1bar |\n";
+        let (program, _) = relanote_parser::parse(source);
+
+        let (span, content) = literal_hover(&program, source.find("- |").unwrap()).unwrap();
+        assert_eq!(&source[span.start..span.end], "| R M3 [R M3 P5] - |:2");
+        // Each pitch of the chord is a note
+        assert_eq!(facts(&content), vec!["- Length: `2 beats`", "- Notes: `5`"]);
+
+        let (_, content) = literal_hover(&program, source.find("R:1bar").unwrap()).unwrap();
+        assert_eq!(facts(&content), vec!["- Length: `3 beats`", "- Notes: `1`"]);
+    }
+
+    #[test]
+    fn test_chord_pitches_in_key() {
+        let source =
+            "let a = | [R M3 P5] |\nset key = D4\nlet b = | [R m3 P5] |\nlet c = MajorTriad\n";
+        let (program, _) = relanote_parser::parse(source);
+
+        let (_, content) = literal_hover(&program, source.find("[R M3").unwrap()).unwrap();
+        assert_eq!(
+            facts(&content),
+            vec!["- Intervals: `R, M3, P5`", "- In C4: `C4 E4 G4`"]
+        );
+        let (_, content) = literal_hover(&program, source.find("[R m3").unwrap()).unwrap();
+        assert_eq!(
+            facts(&content),
+            vec!["- Intervals: `R, m3, P5`", "- In D4: `D4 F4 A4`"]
+        );
+
+        let content = chord_hover(&program, "MajorTriad", source.rfind("MajorTriad").unwrap());
+        assert_eq!(
+            facts(&content.unwrap()),
+            vec!["- Intervals: `R, M3, P5`", "- In D4: `D4 F#4 A4`"]
+        );
+    }
+
+    #[test]
+    fn test_scale_intervals_with_accidentals_applied() {
+        let source = "scale Bright = { R, M2, M3, P4+, P5, M6, M7- }\nscale Dreamy = Lydian with { m7 }\nlet x = Dreamy\n";
+        let (program, _) = relanote_parser::parse(source);
+
+        let intervals = |name: &str, at: &str| {
+            let content = scale_hover(&program, name, source.rfind(at).unwrap()).unwrap();
+            facts(&content)[0].to_string()
+        };
+        assert_eq!(
+            intervals("Bright", "Bright"),
+            "- Intervals: `R, M2, M3, A4, P5, M6, m7`"
+        );
+        // Derived from a prelude scale that is itself derived from Major
+        assert_eq!(
+            intervals("Lydian", "Lydian"),
+            "- Intervals: `R, M2, M3, A4, P5, M6, M7`"
+        );
+        assert_eq!(
+            intervals("Dreamy", "Dreamy"),
+            "- Intervals: `R, M2, M3, A4, P5, M6, m7`"
+        );
+    }
+}
@@ -0,0 +1,264 @@
+//! Inlay hints: inferred types after `let` names and beat counts after block
+//! literals
+
+use relanote_ast::{walk_expr, ExportDecl, Expr, Item, Pattern, Program, Visitor};
+use relanote_core::{Source, Span, Spanned};
+use relanote_types::{Type, TypeChecker};
+use serde::Deserialize;
+use tower_lsp::lsp_types::{InlayHint, InlayHintKind, InlayHintLabel, Position};
+
+/// Which inlay hints are shown, read from the `inlayHints` initialization
+/// option: `{ "inlayHints": { "types": true, "beats": false } }`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct InlayHintSettings {
+    /// Inferred types of top-level bindings without a type annotation
+    pub types: bool,
+    /// Lengths of block literals in beats
+    pub beats: bool,
+}
+
+impl Default for InlayHintSettings {
+    fn default() -> Self {
+        Self {
+            types: true,
+            beats: true,
+        }
+    }
+}
+
+impl InlayHintSettings {
+    /// Settings from the client's initialization options; hints the options
+    /// leave out are shown
+    pub fn from_initialization_options(options: Option<&serde_json::Value>) -> Self {
+        options
+            .and_then(|options| options.get("inlayHints"))
+            .and_then(|hints| serde_json::from_value(hints.clone()).ok())
+            .unwrap_or_default()
+    }
+}
+
+/// Inlay hints for a document checked by `checker`
+pub fn inlay_hints(
+    source: &Source,
+    program: &Program,
+    checker: &TypeChecker,
+    settings: InlayHintSettings,
+) -> Vec<InlayHint> {
+    let mut hints = Vec::new();
+    if settings.types {
+        hints.extend(type_hints(source, program, checker));
+    }
+    if settings.beats {
+        let mut blocks = BlockLengths {
+            beats_per_bar: beats_per_bar(program),
+            lengths: Vec::new(),
+        };
+        blocks.visit_program(program);
+        for (span, beats) in blocks.lengths {
+            hints.push(hint(source, span.end, beats_label(beats), None));
+        }
+    }
+    hints.sort_by_key(|hint| (hint.position.line, hint.position.character));
+    hints
+}
+
+/// `: Type` after the name of each top-level `let` without an annotation,
+/// and after the parameters of each function without a return type
+fn type_hints(source: &Source, program: &Program, checker: &TypeChecker) -> Vec<InlayHint> {
+    let definitions: Vec<&Item> = program
+        .items
+        .iter()
+        .map(|item| match &item.node {
+            Item::Export(ExportDecl::Definition(def)) => def.as_ref(),
+            node => node,
+        })
+        .collect();
+    // The checker knows the type of the last definition of a name only
+    let defined_once = |name: &str| {
+        definitions
+            .iter()
+            .filter(|def| def.defined_name().is_some_and(|n| n.name.as_str() == name))
+            .count()
+            == 1
+    };
+
+    let mut hints = Vec::new();
+    for definition in definitions.iter().copied() {
+        match definition {
+            Item::LetBinding(binding) if binding.type_ann.is_none() => {
+                let Pattern::Ident(ident) = &binding.pattern.node else {
+                    continue;
+                };
+                if !defined_once(&ident.name) {
+                    continue;
+                }
+                if let Some(ty) = checker.lookup_type(&ident.name) {
+                    let label = format!(": {}", ty.lettered());
+                    hints.push(hint(
+                        source,
+                        binding.pattern.span.end,
+                        label,
+                        Some(InlayHintKind::TYPE),
+                    ));
+                }
+            }
+            Item::FunctionDef(def) if def.return_type.is_none() => {
+                let Some(last_param) = def.params.last() else {
+                    continue;
+                };
+                if !defined_once(&def.name.name) {
+                    continue;
+                }
+                let Some(mut ty) = checker.lookup_type(&def.name.name) else {
+                    continue;
+                };
+                for _ in &def.params {
+                    match ty {
+                        Type::Function(_, ret) => ty = ret.as_ref().clone(),
+                        _ => break,
+                    }
+                }
+                hints.push(hint(
+                    source,
+                    last_param.span.end,
+                    format!(": {}", ty.lettered()),
+                    Some(InlayHintKind::TYPE),
+                ));
+            }
+            _ => {}
+        }
+    }
+    hints
+}
+
+/// `3 beats`, to the thousandth of a beat
+pub(crate) fn beats_label(beats: f64) -> String {
+    let beats = (beats * 1000.0).round() / 1000.0;
+    let unit = if beats == 1.0 { "beat" } else { "beats" };
+    format!("{} {}", beats, unit)
+}
+
+/// Length of a bar in beats under a top-level `set time_signature`, 4
+/// without one
+pub(crate) fn beats_per_bar(program: &Program) -> f64 {
+    program
+        .items
+        .iter()
+        .rev()
+        .find_map(|item| match &item.node {
+            Item::SetBinding(set) if set.name.name.as_str() == "time_signature" => {
+                let Expr::Tuple(items) = &set.value.node else {
+                    return None;
+                };
+                match items.as_slice() {
+                    [beats, unit] => match (&beats.node, &unit.node) {
+                        (Expr::Integer(beats @ 1..), Expr::Integer(unit @ 1..)) => {
+                            Some(*beats as f64 * 4.0 / *unit as f64)
+                        }
+                        _ => None,
+                    },
+                    _ => None,
+                }
+            }
+            _ => None,
+        })
+        .unwrap_or(4.0)
+}
+
+/// Lengths of the block literals of a program whose length is known
+/// without evaluating it
+struct BlockLengths {
+    beats_per_bar: f64,
+    lengths: Vec<(Span, f64)>,
+}
+
+impl Visitor for BlockLengths {
+    fn visit_expr(&mut self, expr: &Spanned<Expr>) {
+        if let Expr::Block(block) = &expr.node {
+            if let Some(beats) = block.written_beats(self.beats_per_bar) {
+                self.lengths.push((expr.span, beats));
+            }
+        }
+        walk_expr(self, expr);
+    }
+}
+
+fn hint(source: &Source, offset: usize, label: String, kind: Option<InlayHintKind>) -> InlayHint {
+    let loc = source.location(offset);
+    InlayHint {
+        position: Position {
+            line: (loc.line - 1) as u32,
+            character: (loc.column - 1) as u32,
+        },
+        label: InlayHintLabel::String(label),
+        kind,
+        text_edits: None,
+        tooltip: None,
+        padding_left: Some(true),
+        padding_right: None,
+        data: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use relanote_resolver::ModuleChecker;
+
+    fn labels(content: &str, settings: InlayHintSettings) -> Vec<(u32, u32, String)> {
+        let source = Source::from_string("test.rela", content.to_string());
+        let (program, _) = relanote_parser::parse_source(&source);
+        let (checker, _) = ModuleChecker::new().check(&program);
+        inlay_hints(&source, &program, &checker, settings)
+            .into_iter()
+            .map(|hint| match hint.label {
+                InlayHintLabel::String(label) => {
+                    (hint.position.line, hint.position.character, label)
+                }
+                InlayHintLabel::LabelParts(_) => unreachable!(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_type_and_beat_hints() {
+        let content = "let riff = | R M3 P5 M3 |:4\nlet twice b = b ++ b\nlet n: Int = 3\n";
+        assert_eq!(
+            labels(content, InlayHintSettings::default()),
+            vec![
+                (0, 8, ": Block".to_string()),
+                (0, 27, "4 beats".to_string()),
+                (1, 11, ": a".to_string()),
+            ]
+        );
+
+        let beats_only = InlayHintSettings {
+            types: false,
+            beats: true,
+        };
+        assert_eq!(labels(content, beats_only).len(), 1);
+    }
+
+    #[test]
+    fn test_beat_hints_follow_durations() {
+        let content = "set time_signature = (3, 4)\n| R:1bar M3:1/8 -:1/8 |\n| R { R M3 P5 }:1 |";
+        let beats: Vec<String> = labels(content, InlayHintSettings::default())
+            .into_iter()
+            .map(|(_, _, label)| label)
+            .collect();
+        // A bar of 3/4 and two eighths; half a beat and a one-beat tuplet
+        assert_eq!(beats, vec!["4 beats", "1.5 beats"]);
+    }
+
+    #[test]
+    fn test_settings_from_initialization_options() {
+        let options = serde_json::json!({ "inlayHints": { "beats": false } });
+        let settings = InlayHintSettings::from_initialization_options(Some(&options));
+        assert!(settings.types && !settings.beats);
+        assert_eq!(
+            InlayHintSettings::from_initialization_options(None),
+            InlayHintSettings::default()
+        );
+    }
+}
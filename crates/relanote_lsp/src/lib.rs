@@ -1,6 +1,17 @@
 //! Language Server Protocol implementation for relanote
 
+mod code_actions;
+mod code_lens;
+mod hover;
+mod inlay_hints;
+mod preview;
+mod ranges;
 mod server;
+mod workspace;
+
+pub use code_lens::{LensTarget, PLAY_COMMAND, RENDER_MIDI_COMMAND};
+pub use inlay_hints::InlayHintSettings;
+pub use preview::{PreviewParams, RenderedSelection, AUDIO_DATA_METHOD, RENDER_SELECTION_METHOD};
 
 pub use server::RelanoteLanguageServer;
 
@@ -11,7 +22,13 @@ pub async fn run_server() {
     let stdin = tokio::io::stdin();
     let stdout = tokio::io::stdout();
 
-    let (service, socket) = LspService::new(RelanoteLanguageServer::new);
+    let (service, socket) = LspService::build(RelanoteLanguageServer::new)
+        .custom_method(
+            RENDER_SELECTION_METHOD,
+            RelanoteLanguageServer::render_selection,
+        )
+        .custom_method(AUDIO_DATA_METHOD, RelanoteLanguageServer::audio_data)
+        .finish();
 
     Server::new(stdin, stdout, socket).serve(service).await;
 }
@@ -0,0 +1,269 @@
+//! Custom requests that preview the music of a document, or of a selection
+//! in it, so editors can play it without running the CLI:
+//!
+//! - `relanote/renderSelection` returns the bytes of a Standard MIDI File
+//! - `relanote/audioData` returns the notes with the synths that play
+//!   them, as the playground's audio engine takes them
+
+use std::path::Path;
+
+use relanote_ast::{walk_expr, Binary, BinaryOp, Expr, Program, Visitor};
+use relanote_core::{Source, Span, Spanned};
+use relanote_eval::{Evaluator, SongValue, Value};
+use relanote_parser::parse_source;
+use relanote_render::{playback_data, AudioPlaybackData, MidiRenderer, RenderContext};
+use serde::{Deserialize, Serialize};
+use tower_lsp::lsp_types::{Range, TextDocumentIdentifier};
+
+use crate::server::position_to_offset;
+
+/// Request rendering a document or selection to MIDI
+pub const RENDER_SELECTION_METHOD: &str = "relanote/renderSelection";
+/// Request for the audio playback data of a document or selection
+pub const AUDIO_DATA_METHOD: &str = "relanote/audioData";
+
+/// Parameters of both requests
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PreviewParams {
+    pub text_document: TextDocumentIdentifier,
+    /// The selection to play; the document's song plays without one, or
+    /// when it is empty
+    #[serde(default)]
+    pub range: Option<Range>,
+}
+
+/// Result of `relanote/renderSelection`
+#[derive(Debug, Serialize)]
+pub struct RenderedSelection {
+    /// A Standard MIDI File
+    pub midi: Vec<u8>,
+}
+
+/// The music a selection plays, with the tempo and key in effect there
+pub struct Preview {
+    song: SongValue,
+    context: RenderContext,
+}
+
+impl Preview {
+    /// Evaluate the selection `range` of `source`, the document at `path`
+    ///
+    /// A selection plays the innermost expression around it, evaluated
+    /// after the items before it; in a `++` sequence, just the operands it
+    /// touches, so selecting a few bars of a long line plays those bars.
+    pub fn of_selection(
+        path: Option<&Path>,
+        source: &Source,
+        range: Option<Range>,
+    ) -> Result<Self, String> {
+        let (program, diagnostics) = parse_source(source);
+        if diagnostics.has_errors() {
+            return Err("the document has syntax errors".to_string());
+        }
+        let base_dir = path.and_then(Path::parent).map(Path::to_path_buf);
+        let mut evaluator = Evaluator::with_base_dir(base_dir);
+
+        let selection = match range {
+            Some(range) => {
+                let start = position_to_offset(&source.content, range.start);
+                let end = position_to_offset(&source.content, range.end);
+                let selected = source
+                    .content
+                    .get(start..end)
+                    .ok_or("the selection isn't part of the document")?;
+                // Whitespace around the selection doesn't widen it
+                let start = start + (selected.len() - selected.trim_start().len());
+                let end = end - (selected.len() - selected.trim_end().len());
+                Some((start, end.max(start)))
+            }
+            None => None,
+        }
+        .filter(|(start, end)| start < end);
+
+        let value = match selection {
+            None => {
+                let value = evaluator
+                    .eval_program(&program)
+                    .map_err(|e| e.to_string())?;
+                match evaluator.render_targets().first() {
+                    Some(target) => Value::Song(target.song.clone()),
+                    None => value,
+                }
+            }
+            Some((start, end)) => {
+                let (item, expr) = selected_expr(&program, start, end)
+                    .ok_or("the selection isn't part of an expression")?;
+                let before = Program {
+                    items: program.items[..item].to_vec(),
+                    comments: Vec::new(),
+                };
+                evaluator.eval_program(&before).map_err(|e| e.to_string())?;
+                evaluator.eval_expr(&expr).map_err(|e| e.to_string())?
+            }
+        };
+
+        let song = value
+            .into_song()
+            .ok_or("the selection isn't music: a block, part, section or song")?;
+        Ok(Self {
+            song,
+            context: RenderContext::from_evaluator(&evaluator),
+        })
+    }
+
+    /// The selection as a Standard MIDI File
+    pub fn midi(&self) -> Result<Vec<u8>, String> {
+        MidiRenderer::new(self.context.midi_config())
+            .render(&self.song)
+            .map_err(|e| e.to_string())
+    }
+
+    /// The selection's notes with their synths
+    pub fn audio_data(&self) -> AudioPlaybackData {
+        playback_data(&Value::Song(self.song.clone()), &self.context)
+    }
+}
+
+/// The index of the item a selection is in, and the expression to play for
+/// it
+fn selected_expr(program: &Program, start: usize, end: usize) -> Option<(usize, Spanned<Expr>)> {
+    let item = program
+        .items
+        .iter()
+        .position(|item| item.span.start <= start && end <= item.span.end)?;
+    let mut around = Around {
+        start,
+        end,
+        innermost: None,
+    };
+    around.visit_item(&program.items[item]);
+    let expr = around.innermost?;
+
+    let mut operands = Vec::new();
+    sequence_operands(&expr, &mut operands);
+    let touched: Vec<&Spanned<Expr>> = operands
+        .into_iter()
+        .filter(|operand| operand.span.start < end && start < operand.span.end)
+        .collect();
+    let expr = match touched.split_first() {
+        // Selecting a `++` itself plays the whole sequence
+        None => expr,
+        Some((first, rest)) => rest.iter().fold((*first).clone(), |left, right| {
+            let span = Span::new(left.span.source, left.span.start, right.span.end);
+            Spanned::new(
+                Expr::Binary(Binary {
+                    op: BinaryOp::Concat,
+                    left: Box::new(left),
+                    right: Box::new((*right).clone()),
+                }),
+                span,
+            )
+        }),
+    };
+    Some((item, expr))
+}
+
+/// The operands of a `++` sequence, in order; any other expression is a
+/// sequence of one
+fn sequence_operands<'a>(expr: &'a Spanned<Expr>, operands: &mut Vec<&'a Spanned<Expr>>) {
+    match &expr.node {
+        Expr::Binary(binary) if binary.op == BinaryOp::Concat => {
+            sequence_operands(&binary.left, operands);
+            sequence_operands(&binary.right, operands);
+        }
+        _ => operands.push(expr),
+    }
+}
+
+/// The innermost expression around a selection
+struct Around {
+    start: usize,
+    end: usize,
+    innermost: Option<Spanned<Expr>>,
+}
+
+impl Visitor for Around {
+    fn visit_expr(&mut self, expr: &Spanned<Expr>) {
+        if expr.span.start <= self.start && self.end <= expr.span.end {
+            self.innermost = Some(expr.clone());
+            walk_expr(self, expr);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tower_lsp::lsp_types::Position;
+
+    const SONG: &str =
+        "set tempo = 90\nlet riff = | R M3 | ++ | P5 | ++ | P4 P5 | ++ | R |\nriff\n";
+
+    /// A selection of the first occurrence of `selected` in `SONG`
+    fn selection(selected: &str) -> Option<Range> {
+        let start = SONG.find(selected).unwrap();
+        let position = |offset: usize| {
+            let line = SONG[..offset].matches('\n').count();
+            let line_start = SONG[..offset].rfind('\n').map_or(0, |i| i + 1);
+            Position::new(line as u32, (offset - line_start) as u32)
+        };
+        Some(Range::new(
+            position(start),
+            position(start + selected.len()),
+        ))
+    }
+
+    fn preview(range: Option<Range>) -> Result<Preview, String> {
+        let source = Source::from_string("song.rela", SONG.to_string());
+        Preview::of_selection(None, &source, range)
+    }
+
+    #[test]
+    fn test_selection_plays_the_bars_it_touches() {
+        let (program, _) = relanote_parser::parse(SONG);
+        let start = SONG.find("M3 |").unwrap();
+        let end = SONG.find("P4").unwrap();
+        let (item, expr) = selected_expr(&program, start, end).unwrap();
+        assert_eq!(item, 1);
+        assert_eq!(
+            &SONG[expr.span.start..expr.span.end],
+            "| R M3 | ++ | P5 | ++ | P4 P5 |"
+        );
+
+        let audio = preview(selection("| P5 | ++ | P4 P5 |"))
+            .unwrap()
+            .audio_data();
+        assert_eq!(audio.tempo, 90);
+        let pitches: Vec<f64> = audio.notes.iter().map(|note| note.pitch).collect();
+        assert_eq!(pitches, vec![67.0, 65.0, 67.0]);
+    }
+
+    #[test]
+    fn test_whole_song_without_a_selection() {
+        let song = preview(None).unwrap();
+        assert_eq!(song.audio_data().notes.len(), 6);
+        assert!(song.midi().unwrap().starts_with(b"MThd"));
+        // An empty selection is no selection
+        let cursor = selection("riff").map(|range| Range::new(range.start, range.start));
+        assert_eq!(preview(cursor).unwrap().audio_data().notes.len(), 6);
+    }
+
+    #[test]
+    fn test_selection_on_a_line_with_wide_characters() {
+        let source = Source::from_string("song.rela", "lyrics \"♪é\" | R M3 |\n".to_string());
+        // Columns count UTF-16 code units, so `♪` and `é` are one column each
+        let range = Range::new(Position::new(0, 12), Position::new(0, 20));
+        let song = Preview::of_selection(None, &source, Some(range)).unwrap();
+        assert_eq!(song.audio_data().notes.len(), 2);
+        // A selection that ends before it starts plays nothing
+        let backwards = Range::new(Position::new(0, 20), Position::new(0, 12));
+        assert!(Preview::of_selection(None, &source, Some(backwards)).is_err());
+    }
+
+    #[test]
+    fn test_selection_that_isnt_music() {
+        assert!(preview(selection("90")).is_err());
+        assert!(preview(selection("set")).is_err());
+    }
+}
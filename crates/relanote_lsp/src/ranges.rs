@@ -0,0 +1,201 @@
+//! Folding and selection ranges, following how a score nests: slots in
+//! blocks, blocks in sequences, sequences in layers and sections
+
+use relanote_ast::{walk_expr, walk_item, walk_pattern, walk_slot, BinaryOp, Expr, Item};
+use relanote_ast::{Pattern, Program, Slot, Visitor};
+use relanote_core::{Source, Span, Spanned};
+use tower_lsp::lsp_types::{FoldingRange, FoldingRangeKind, Position, Range, SelectionRange};
+
+use crate::server::{offset_to_position, position_to_offset};
+
+/// Ranges that fold a section, layer, block or `++` sequence spanning
+/// several lines
+pub fn folding_ranges(source: &Source, program: &Program) -> Vec<FoldingRange> {
+    let mut folds = Folds::default();
+    folds.visit_program(program);
+
+    let mut ranges: Vec<FoldingRange> = Vec::new();
+    for span in folds.spans {
+        let start = source.location(span.start).line - 1;
+        let end = source.location(span.end).line - 1;
+        // The outermost construct starting on a line folds it
+        if end <= start || ranges.iter().any(|r| r.start_line == start as u32) {
+            continue;
+        }
+        ranges.push(FoldingRange {
+            start_line: start as u32,
+            start_character: None,
+            end_line: end as u32,
+            end_character: None,
+            kind: Some(FoldingRangeKind::Region),
+            collapsed_text: None,
+        });
+    }
+    ranges
+}
+
+/// For each position, the ranges of the nodes around it, innermost first:
+/// a slot, its block, the sequence of blocks, the layer, the item
+pub fn selection_ranges(
+    source: &Source,
+    program: &Program,
+    positions: &[Position],
+) -> Vec<SelectionRange> {
+    let mut nodes = Nodes::default();
+    nodes.visit_program(program);
+
+    positions
+        .iter()
+        .map(|&position| {
+            let offset = position_to_offset(&source.content, position);
+            let mut around: Vec<Span> = nodes
+                .spans
+                .iter()
+                .copied()
+                .filter(|span| span.start <= offset && offset <= span.end)
+                .collect();
+            around.sort_by_key(|span| (span.end - span.start, usize::MAX - span.end));
+
+            // Each range must contain the one before it
+            let mut chain: Vec<Span> = Vec::new();
+            for span in around {
+                let contains_last = chain
+                    .last()
+                    .is_none_or(|last| span.start <= last.start && last.end <= span.end);
+                if contains_last && chain.last() != Some(&span) {
+                    chain.push(span);
+                }
+            }
+
+            let empty = Range {
+                start: position,
+                end: position,
+            };
+            chain
+                .into_iter()
+                .rev()
+                .fold(None, |parent, span| {
+                    Some(SelectionRange {
+                        range: Range {
+                            start: offset_to_position(source, span.start),
+                            end: offset_to_position(source, span.end),
+                        },
+                        parent: parent.map(Box::new),
+                    })
+                })
+                .unwrap_or(SelectionRange {
+                    range: empty,
+                    parent: None,
+                })
+        })
+        .collect()
+}
+
+/// Spans of the constructs that fold, outer ones first
+#[derive(Default)]
+struct Folds {
+    spans: Vec<Span>,
+    /// Whether the expression visited is an operand of `++`, which folds
+    /// with the whole sequence
+    in_sequence: bool,
+}
+
+impl Visitor for Folds {
+    fn visit_expr(&mut self, expr: &Spanned<Expr>) {
+        let is_sequence = matches!(
+            &expr.node,
+            Expr::Binary(binary) if binary.op == BinaryOp::Concat
+        );
+        let folds = match &expr.node {
+            Expr::Section(_) | Expr::Layer(_) | Expr::Block(_) => true,
+            _ => is_sequence && !self.in_sequence,
+        };
+        if folds {
+            self.spans.push(expr.span);
+        }
+        let in_sequence = std::mem::replace(&mut self.in_sequence, is_sequence);
+        walk_expr(self, expr);
+        self.in_sequence = in_sequence;
+    }
+}
+
+/// Spans of every item, expression, pattern and slot
+#[derive(Default)]
+struct Nodes {
+    spans: Vec<Span>,
+}
+
+impl Visitor for Nodes {
+    fn visit_item(&mut self, item: &Spanned<Item>) {
+        self.spans.push(item.span);
+        walk_item(self, item);
+    }
+
+    fn visit_expr(&mut self, expr: &Spanned<Expr>) {
+        self.spans.push(expr.span);
+        walk_expr(self, expr);
+    }
+
+    fn visit_pattern(&mut self, pattern: &Spanned<Pattern>) {
+        self.spans.push(pattern.span);
+        walk_pattern(self, pattern);
+    }
+
+    fn visit_slot(&mut self, slot: &Spanned<Slot>) {
+        self.spans.push(slot.span);
+        walk_slot(self, slot);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(content: &str) -> (Source, Program) {
+        let source = Source::from_string("song.rela", content.to_string());
+        let (program, _) = relanote_parser::parse_source(&source);
+        (source, program)
+    }
+
+    #[test]
+    fn test_folding_ranges() {
+        let content = "let verse = section \"Verse\" {\n  layer [\n    | R M3\n      P5 |,\n    | R |\n  ]\n}\nlet riff = | R | ++\n  | M3 | ++\n  | P5 |\n";
+        let (source, program) = parse(content);
+        let lines: Vec<(u32, u32)> = folding_ranges(&source, &program)
+            .into_iter()
+            .map(|range| (range.start_line, range.end_line))
+            .collect();
+        // The section, the layer, the two-line block and the sequence
+        assert_eq!(lines, vec![(0, 6), (1, 5), (2, 3), (7, 9)]);
+    }
+
+    #[test]
+    fn test_selection_ranges_expand_musically() {
+        let content = "let song = layer [| R M3 P5 | ++ | P4 |]";
+        let (source, program) = parse(content);
+        let position = Position {
+            line: 0,
+            character: content.find("M3").unwrap() as u32,
+        };
+        let ranges = selection_ranges(&source, &program, &[position]);
+
+        let mut texts = Vec::new();
+        let mut range = Some(&ranges[0]);
+        while let Some(r) = range {
+            let start = position_to_offset(content, r.range.start);
+            let end = position_to_offset(content, r.range.end);
+            texts.push(&content[start..end]);
+            range = r.parent.as_deref();
+        }
+        assert_eq!(
+            texts,
+            vec![
+                "M3",
+                "| R M3 P5 |",
+                "| R M3 P5 | ++ | P4 |",
+                "layer [| R M3 P5 | ++ | P4 |]",
+                content,
+            ]
+        );
+    }
+}
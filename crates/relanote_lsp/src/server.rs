@@ -1,74 +1,33 @@
 //! LSP server implementation
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use tokio::sync::RwLock;
-use tower_lsp::jsonrpc::Result;
+use tower_lsp::jsonrpc::{Error, Result};
 use tower_lsp::lsp_types::*;
 use tower_lsp::{Client, LanguageServer};
 
-use relanote_core::{Source, SourceDb};
-use relanote_format::{format, FormatConfig};
+use relanote_ast::Program;
+use relanote_core::{DiagnosticCode, DiagnosticKind, Diagnostics, Source, SourceDb, Span};
+use relanote_format::{format_range, format_with_source, FormatConfig};
 use relanote_lexer::{Lexer, TokenKind};
 use relanote_parser::parse_source;
-use relanote_types::TypeChecker;
-
-/// Get documentation for builtin functions
-fn get_builtin_docs(name: &str) -> Option<(&'static str, &'static str)> {
-    // Returns (signature, description)
-    match name {
-        "reverse" => Some((
-            "reverse : Block -> Block",
-            "Reverses the order of slots in a block.\n\n**Example:**\n```rela\n| R M3 P5 | |> reverse  -- becomes | P5 M3 R |\n```",
-        )),
-        "repeat" => Some((
-            "repeat : (Int, Block) -> Block",
-            "Repeats a block n times.\n\n**Example:**\n```rela\n| R M3 | |> repeat(4)  -- plays the phrase 4 times\n```",
-        )),
-        "transpose" => Some((
-            "transpose : (Interval, Block) -> Block",
-            "Transposes all notes in a block by the given interval.\n\n**Example:**\n```rela\n| R M3 P5 | |> transpose(P5)  -- transposes up a fifth\n```",
-        )),
-        "metronome" => Some((
-            "metronome : (Int, Int) -> Part",
-            "Generates a metronome click track.\n\nParameters:\n- `bars`: Number of bars\n- `beats_per_bar`: Beats per bar (time signature)\n\n**Example:**\n```rela\nlayer [\n  melody,\n  metronome(8, 4) |> volume(0.3)\n]\n```",
-        )),
-        "swing" => Some((
-            "swing : (Float, Block) -> Block",
-            "Applies swing feel to a block.\n\nThe ratio determines the swing amount (0.5 = straight, 0.67 = triplet swing).\n\n**Example:**\n```rela\n| R M3 P5 M3 | |> swing(0.6)\n```",
-        )),
-        "double_time" => Some((
-            "double_time : Block -> Block",
-            "Doubles the tempo of a block (halves durations).\n\n**Example:**\n```rela\nmelody |> double_time  -- plays twice as fast\n```",
-        )),
-        "reverb" => Some((
-            "reverb : (Float, Block) -> Part",
-            "Applies reverb with specified level (0.0-1.0).\n\n**Example:**\n```rela\nmelody |> reverb(0.5)  -- 50% reverb send\n```",
-        )),
-        "hall_reverb" => Some((
-            "hall_reverb : Block -> Part",
-            "Applies hall reverb preset (high reverb level).\n\n**Example:**\n```rela\nmelody |> hall_reverb\n```",
-        )),
-        "room_reverb" => Some((
-            "room_reverb : Block -> Part",
-            "Applies room reverb preset (medium reverb level).\n\n**Example:**\n```rela\nmelody |> room_reverb\n```",
-        )),
-        "plate_reverb" => Some((
-            "plate_reverb : Block -> Part",
-            "Applies plate reverb preset (bright, metallic reverb).\n\n**Example:**\n```rela\nmelody |> plate_reverb\n```",
-        )),
-        "dry" => Some((
-            "dry : Block -> Part",
-            "No reverb (dry signal only).\n\n**Example:**\n```rela\nmelody |> dry\n```",
-        )),
-        "volume" => Some((
-            "volume : (Float, Block | Part) -> Part",
-            "Sets volume level (0.0-1.0 or 0-100).\n\nCan be chained with other effects.\n\n**Example:**\n```rela\nmelody |> reverb(0.5) |> volume(0.8)\nmetronome(8, 4) |> volume(0.25)\n```",
-        )),
-        _ => None,
-    }
-}
+use relanote_render::AudioPlaybackData;
+use relanote_resolver::{
+    DiskFileProvider, FileProvider, MemoryFileProvider, ModuleChecker, ModuleResolver, UseDef,
+    ENTRY_MODULE,
+};
+use relanote_types::{Type, TypeChecker};
+
+use crate::code_actions::{close_delimiter, did_you_mean, extract_chord, has_code, wrap_in_layer};
+use crate::code_lens::code_lenses;
+use crate::hover;
+use crate::inlay_hints::{inlay_hints, InlayHintSettings};
+use crate::preview::{Preview, PreviewParams, RenderedSelection};
+use crate::ranges::{folding_ranges, selection_ranges};
+use crate::workspace::Workspace;
 
 /// Get documentation for keywords
 fn get_keyword_docs(keyword: &str) -> Option<(&'static str, &'static str)> {
@@ -77,10 +36,34 @@ fn get_keyword_docs(keyword: &str) -> Option<(&'static str, &'static str)> {
             "let <pattern> = <expr> in <body>",
             "Binds a value to a name.\n\n**Example:**\n```rela\nlet melody = | R M3 P5 | in melody |> transpose(P5)\n```",
         )),
+        "meta" => Some((
+            "meta { title: <string>, composer: <string>, copyright: <string> }",
+            "Song metadata, written to MIDI meta events and ABC headers. `relanote render` names its output after the title.\n\n**Example:**\n```rela\nmeta { title: \"Etude\", composer: \"Anon\" }\n```",
+        )),
+        "test" => Some((
+            "test <string> { <expr> }",
+            "A test run by `relanote test`. It passes when the body evaluates to `true`, or to music matching the snapshot saved by an earlier run.\n\n**Example:**\n```rela\ntest \"chorus\" { chorus |> transpose P5 }\n```",
+        )),
+        "render" => Some((
+            "render [<string> =] <song>",
+            "Declares a song the program outputs. `relanote render` writes every render target, a named one to a file of its name when rendering into a directory; without any, the program's last expression is rendered.\n\n**Example:**\n```rela\nrender song\nrender \"verse_demo\" = verse |> hall_reverb\n```",
+        )),
+        "env" => Some((
+            "env(<from>, <to>, <duration>)",
+            "A dynamics envelope: a ramp from one dynamic marking (`ppp` to `fff`) to another over a duration, applied to a block or part with `apply_env`.\n\n**Example:**\n```rela\nlet swell = env(pp, ff, 4 beats)\npad |> apply_env(swell)\n```",
+        )),
+        "context" => Some((
+            "context { tempo = <bpm>, key = <pitch>, swing = <ratio> } in <music>",
+            "Plays its body as a song with the given settings: a tempo in BPM, the key its intervals are relative to, and a swing ratio. Settings made inside the body, such as a section's own `with tempo:`, take precedence.\n\n**Example:**\n```rela\nintro ++ context { tempo = 140, key = A3, swing = 0.6 } in chorus\n```",
+        )),
         "layer" => Some((
             "layer [ <parts...> ]",
             "Combines multiple parts to play simultaneously.\n\n**Example:**\n```rela\nlayer [\n  melody |> room_reverb,\n  bass |> volume(0.8),\n  drums\n]\n```",
         )),
+        "kit" => Some((
+            "kit { <hit>: <drum>, ... }",
+            "A drum kit for percussion blocks. Each hit letter plays a drum synth or a GM percussion key; apply the kit to a block of hits.\n\n**Example:**\n```rela\nlet rock = kit { x: Kick, o: Snare, h: HiHat }\nlet beat = rock | x h o h x x o h |\n```",
+        )),
         "scale" => Some((
             "scale <name> { <intervals...> }",
             "Defines a scale with intervals from root.\n\n**Example:**\n```rela\nscale major { R M2 M3 P4 P5 M6 M7 }\nscale minor { R M2 m3 P4 P5 m6 m7 }\n```",
@@ -154,6 +137,9 @@ struct Document {
 pub struct RelanoteLanguageServer {
     client: Client,
     documents: Arc<RwLock<HashMap<Url, Document>>>,
+    /// Inlay hints the client asked for when initializing
+    inlay_hint_settings: Arc<RwLock<InlayHintSettings>>,
+    workspace: Arc<RwLock<Workspace>>,
     #[allow(dead_code)]
     source_db: Arc<RwLock<SourceDb>>,
 }
@@ -163,29 +149,47 @@ impl RelanoteLanguageServer {
         Self {
             client,
             documents: Arc::new(RwLock::new(HashMap::new())),
+            inlay_hint_settings: Arc::new(RwLock::new(InlayHintSettings::default())),
+            workspace: Arc::new(RwLock::new(Workspace::new())),
             source_db: Arc::new(RwLock::new(SourceDb::new())),
         }
     }
 
+    /// Publish the diagnostics of an open document, or of a workspace file
+    /// read from disk
     async fn analyze_document(&self, uri: &Url) {
         let documents = self.documents.read().await;
-        let doc = match documents.get(uri) {
-            Some(d) => d,
-            None => return,
+        let (content, version) = match documents.get(uri) {
+            Some(doc) => (doc.content.clone(), Some(doc.version)),
+            None => {
+                let disk = uri
+                    .to_file_path()
+                    .ok()
+                    .and_then(|path| std::fs::read_to_string(path).ok());
+                match disk {
+                    Some(content) => (content, None),
+                    None => return,
+                }
+            }
         };
 
         // Parse the document
-        let source = Source::from_string(uri.path().to_string(), doc.content.clone());
+        let source = Source::from_string(uri.path().to_string(), content.clone());
         let (program, parse_diagnostics) = parse_source(&source);
 
-        // Type check
-        let mut type_checker = TypeChecker::new();
-        let type_diagnostics = type_checker.check_program(&program);
+        // Type check against the modules the document uses, reading other
+        // open documents from their buffers rather than from disk
+        let (type_diagnostics, dependencies) =
+            check_with_modules(uri, &content, &program, open_files(&documents));
+        self.workspace
+            .write()
+            .await
+            .set_dependencies(uri, dependencies);
 
         // Convert to LSP diagnostics
         let mut lsp_diagnostics = Vec::new();
 
-        for diag in parse_diagnostics.iter() {
+        for diag in parse_diagnostics.iter().chain(type_diagnostics.iter()) {
             let start_loc = source.location(diag.span.start);
             let end_loc = source.location(diag.span.end);
 
@@ -200,43 +204,284 @@ impl RelanoteLanguageServer {
                         character: (end_loc.column - 1) as u32,
                     },
                 },
-                severity: Some(DiagnosticSeverity::ERROR),
+                severity: Some(to_lsp_severity(diag.kind)),
+                code: diag
+                    .code
+                    .map(|code| NumberOrString::String(code.to_string())),
+                source: Some("relanote".to_string()),
                 message: diag.message.clone(),
                 ..Default::default()
             });
         }
 
-        for diag in type_diagnostics.iter() {
-            let start_loc = source.location(diag.span.start);
-            let end_loc = source.location(diag.span.end);
+        // Publish diagnostics
+        self.client
+            .publish_diagnostics(uri.clone(), lsp_diagnostics, version)
+            .await;
+    }
 
-            lsp_diagnostics.push(Diagnostic {
-                range: Range {
-                    start: Position {
-                        line: (start_loc.line - 1) as u32,
-                        character: (start_loc.column - 1) as u32,
-                    },
-                    end: Position {
-                        line: (end_loc.line - 1) as u32,
-                        character: (end_loc.column - 1) as u32,
-                    },
-                },
-                severity: Some(DiagnosticSeverity::ERROR),
-                message: diag.message.clone(),
-                ..Default::default()
-            });
+    /// Analyze the documents checked against the module in `path` again,
+    /// after it changed
+    async fn analyze_dependents(&self, path: &Path) {
+        let dependents = self.workspace.read().await.dependents(path);
+        for uri in dependents {
+            self.analyze_document(&uri).await;
         }
+    }
 
-        // Publish diagnostics
+    /// Analyze workspace files that aren't open, whose diagnostics come
+    /// from disk
+    async fn analyze_files(&self, files: Vec<PathBuf>) {
+        for path in files {
+            let Ok(uri) = Url::from_file_path(&path) else {
+                continue;
+            };
+            if !self.documents.read().await.contains_key(&uri) {
+                self.analyze_document(&uri).await;
+            }
+        }
+    }
+
+    /// Drop the diagnostics of a document that is no longer analyzed
+    async fn clear_document(&self, uri: &Url) {
+        self.workspace.write().await.forget(uri);
         self.client
-            .publish_diagnostics(uri.clone(), lsp_diagnostics, Some(doc.version))
+            .publish_diagnostics(uri.clone(), Vec::new(), None)
             .await;
     }
+
+    /// `relanote/renderSelection`: a document or selection as a Standard
+    /// MIDI File
+    pub async fn render_selection(&self, params: PreviewParams) -> Result<RenderedSelection> {
+        let preview = self.preview(params).await?;
+        let midi = preview.midi().map_err(Error::invalid_params)?;
+        Ok(RenderedSelection { midi })
+    }
+
+    /// `relanote/audioData`: the notes of a document or selection with the
+    /// synths that play them
+    pub async fn audio_data(&self, params: PreviewParams) -> Result<AudioPlaybackData> {
+        Ok(self.preview(params).await?.audio_data())
+    }
+
+    /// The music a preview request asks for, from the document's buffer when
+    /// it is open
+    async fn preview(&self, params: PreviewParams) -> Result<Preview> {
+        let uri = params.text_document.uri;
+        let path = uri.to_file_path().ok();
+        let content = match self.documents.read().await.get(&uri) {
+            Some(doc) => doc.content.clone(),
+            None => path
+                .as_ref()
+                .and_then(|path| std::fs::read_to_string(path).ok())
+                .ok_or_else(|| Error::invalid_params(format!("cannot read {}", uri)))?,
+        };
+        let source = Source::from_string(uri.path().to_string(), content);
+        Preview::of_selection(path.as_deref(), &source, params.range).map_err(Error::invalid_params)
+    }
+
+    /// Formatter config for a document: the nearest `.relafmt`/`relanote.toml`,
+    /// falling back to the editor's indentation settings
+    async fn format_config(&self, uri: &Url, options: &FormattingOptions) -> FormatConfig {
+        let config_path = uri
+            .to_file_path()
+            .ok()
+            .and_then(|path| FormatConfig::discover(&path));
+        if let Some(path) = config_path {
+            match FormatConfig::load(&path) {
+                Ok(config) => return config,
+                Err(e) => {
+                    self.client
+                        .log_message(MessageType::WARNING, e.to_string())
+                        .await
+                }
+            }
+        }
+        FormatConfig {
+            indent_size: options.tab_size as usize,
+            ..FormatConfig::default()
+        }
+    }
+
+    /// Edits that reformat the top-level items overlapping `range` (byte offsets)
+    async fn format_range_edits(
+        &self,
+        uri: &Url,
+        range: std::ops::Range<usize>,
+        options: &FormattingOptions,
+    ) -> Option<Vec<TextEdit>> {
+        let documents = self.documents.read().await;
+        let doc = documents.get(uri)?;
+        let source = Source::from_string(uri.path().to_string(), doc.content.clone());
+        let (program, diagnostics) = parse_source(&source);
+        if diagnostics.has_errors() {
+            return None;
+        }
+
+        let config = self.format_config(uri, options).await;
+        let (span, new_text) = format_range(&program, &doc.content, &config, range)?;
+        if doc.content[span.clone()] == new_text {
+            return Some(Vec::new());
+        }
+        Some(vec![TextEdit {
+            range: Range {
+                start: offset_to_position(&source, span.start),
+                end: offset_to_position(&source, span.end),
+            },
+            new_text,
+        }])
+    }
+}
+
+/// Files as the editor has them: open documents from their buffers, the
+/// rest from disk
+fn open_files(documents: &HashMap<Url, Document>) -> MemoryFileProvider {
+    let mut files = MemoryFileProvider::over(DiskFileProvider);
+    for (open_uri, open_doc) in documents.iter() {
+        if let Ok(path) = open_uri.to_file_path() {
+            files.insert(path, open_doc.content.clone());
+        }
+    }
+    files
+}
+
+/// Type check a document against the modules it uses, resolved next to its
+/// file and read from `files` (documents without a file only see the
+/// standard library), returning the files of those modules too
+fn check_with_modules(
+    uri: &Url,
+    content: &str,
+    program: &Program,
+    files: impl FileProvider + 'static,
+) -> (Diagnostics, HashSet<PathBuf>) {
+    let mut checker = ModuleChecker::new();
+    if let Ok(path) = uri.to_file_path() {
+        let mut resolver = ModuleResolver::for_file(&path);
+        resolver.set_file_provider(files);
+        if resolver
+            .resolve_source(ENTRY_MODULE, &path, content.to_string())
+            .is_ok()
+        {
+            let diagnostics = checker.check_entry(&resolver, ENTRY_MODULE, program);
+            let dependencies = resolver
+                .modules()
+                .filter(|module| module.name != ENTRY_MODULE)
+                .map(|module| module.path.clone())
+                .collect();
+            return (diagnostics, dependencies);
+        }
+    }
+    (checker.check_program(ENTRY_MODULE, program), HashSet::new())
+}
+
+/// A type checker that has checked a document against the modules it uses,
+/// for the types and doc comments of its bindings
+fn checker_with_modules(
+    uri: &Url,
+    content: &str,
+    program: &Program,
+    files: impl FileProvider + 'static,
+) -> TypeChecker {
+    let mut checker = ModuleChecker::new();
+    if let Ok(path) = uri.to_file_path() {
+        let mut resolver = ModuleResolver::for_file(&path);
+        resolver.set_file_provider(files);
+        if resolver
+            .resolve_source(ENTRY_MODULE, &path, content.to_string())
+            .is_ok()
+        {
+            checker.check_dependencies(&resolver, ENTRY_MODULE);
+        }
+    }
+    checker.check(program).0
+}
+
+/// Convert an LSP position to a byte offset into `content`
+pub(crate) fn position_to_offset(content: &str, position: Position) -> usize {
+    let mut offset = 0usize;
+    for (i, line) in content.lines().enumerate() {
+        if i == position.line as usize {
+            // Columns count UTF-16 code units
+            let mut units = 0;
+            let column = line
+                .char_indices()
+                .find(|(_, c)| {
+                    let reached = units >= position.character as usize;
+                    units += c.len_utf16();
+                    reached
+                })
+                .map_or(line.len(), |(column, _)| column);
+            return offset + column;
+        }
+        offset += line.len() + 1; // +1 for newline
+    }
+    offset.min(content.len())
+}
+
+/// Convert a byte offset to an LSP position
+pub(crate) fn offset_to_position(source: &Source, offset: usize) -> Position {
+    let loc = source.location(offset);
+    Position {
+        line: (loc.line - 1) as u32,
+        character: (loc.column - 1) as u32,
+    }
+}
+
+/// Convert a span to an LSP range
+fn span_to_range(source: &Source, span: Span) -> Range {
+    Range {
+        start: offset_to_position(source, span.start),
+        end: offset_to_position(source, span.end),
+    }
+}
+
+/// Hover showing markdown `content` over `span`
+fn markdown_hover(source: &Source, span: Span, content: String) -> Hover {
+    Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: content,
+        }),
+        range: Some(span_to_range(source, span)),
+    }
+}
+
+/// Map a relanote diagnostic kind to an LSP severity
+fn to_lsp_severity(kind: DiagnosticKind) -> DiagnosticSeverity {
+    match kind {
+        DiagnosticKind::Error => DiagnosticSeverity::ERROR,
+        DiagnosticKind::Warning => DiagnosticSeverity::WARNING,
+        DiagnosticKind::Info => DiagnosticSeverity::INFORMATION,
+        DiagnosticKind::Hint => DiagnosticSeverity::HINT,
+    }
 }
 
 #[tower_lsp::async_trait]
 impl LanguageServer for RelanoteLanguageServer {
-    async fn initialize(&self, _: InitializeParams) -> Result<InitializeResult> {
+    async fn initialize(&self, params: InitializeParams) -> Result<InitializeResult> {
+        *self.inlay_hint_settings.write().await =
+            InlayHintSettings::from_initialization_options(params.initialization_options.as_ref());
+
+        // Clients without workspace folders may still send the root
+        #[allow(deprecated)]
+        let folders: Vec<PathBuf> = match &params.workspace_folders {
+            Some(folders) => folders
+                .iter()
+                .filter_map(|folder| folder.uri.to_file_path().ok())
+                .collect(),
+            None => params
+                .root_uri
+                .iter()
+                .filter_map(|uri| uri.to_file_path().ok())
+                .collect(),
+        };
+        {
+            let mut workspace = self.workspace.write().await;
+            for folder in folders {
+                workspace.add_folder(folder);
+            }
+        }
+
         Ok(InitializeResult {
             capabilities: ServerCapabilities {
                 text_document_sync: Some(TextDocumentSyncCapability::Kind(
@@ -248,7 +493,37 @@ impl LanguageServer for RelanoteLanguageServer {
                 }),
                 hover_provider: Some(HoverProviderCapability::Simple(true)),
                 definition_provider: Some(OneOf::Left(true)),
+                references_provider: Some(OneOf::Left(true)),
+                document_highlight_provider: Some(OneOf::Left(true)),
                 document_formatting_provider: Some(OneOf::Left(true)),
+                document_range_formatting_provider: Some(OneOf::Left(true)),
+                document_on_type_formatting_provider: Some(DocumentOnTypeFormattingOptions {
+                    first_trigger_character: "|".to_string(),
+                    more_trigger_character: None,
+                }),
+                inlay_hint_provider: Some(OneOf::Left(true)),
+                folding_range_provider: Some(FoldingRangeProviderCapability::Simple(true)),
+                selection_range_provider: Some(SelectionRangeProviderCapability::Simple(true)),
+                code_lens_provider: Some(CodeLensOptions {
+                    resolve_provider: Some(false),
+                }),
+                code_action_provider: Some(CodeActionProviderCapability::Options(
+                    CodeActionOptions {
+                        code_action_kinds: Some(vec![
+                            CodeActionKind::QUICKFIX,
+                            CodeActionKind::REFACTOR_EXTRACT,
+                            CodeActionKind::REFACTOR_REWRITE,
+                        ]),
+                        ..Default::default()
+                    },
+                )),
+                workspace: Some(WorkspaceServerCapabilities {
+                    workspace_folders: Some(WorkspaceFoldersServerCapabilities {
+                        supported: Some(true),
+                        change_notifications: Some(OneOf::Left(true)),
+                    }),
+                    file_operations: None,
+                }),
                 ..Default::default()
             },
             ..Default::default()
@@ -259,6 +534,10 @@ impl LanguageServer for RelanoteLanguageServer {
         self.client
             .log_message(MessageType::INFO, "Relanote language server initialized")
             .await;
+
+        // Report problems in every file of the workspace, not just open ones
+        let files = self.workspace.read().await.files().cloned().collect();
+        self.analyze_files(files).await;
     }
 
     async fn shutdown(&self) -> Result<()> {
@@ -292,16 +571,93 @@ impl LanguageServer for RelanoteLanguageServer {
             }
 
             self.analyze_document(&uri).await;
+            // Documents using this one read its buffer
+            if let Ok(path) = uri.to_file_path() {
+                self.analyze_dependents(&path).await;
+            }
         }
     }
 
     async fn did_close(&self, params: DidCloseTextDocumentParams) {
         let uri = params.text_document.uri;
-        let mut documents = self.documents.write().await;
-        documents.remove(&uri);
+        self.documents.write().await.remove(&uri);
+
+        // A workspace file goes back to being analyzed from disk
+        let Ok(path) = uri.to_file_path() else {
+            self.clear_document(&uri).await;
+            return;
+        };
+        if self.workspace.read().await.contains(&path) {
+            self.analyze_document(&uri).await;
+        } else {
+            self.clear_document(&uri).await;
+        }
+        self.analyze_dependents(&path).await;
     }
 
-    async fn completion(&self, _params: CompletionParams) -> Result<Option<CompletionResponse>> {
+    async fn did_change_workspace_folders(&self, params: DidChangeWorkspaceFoldersParams) {
+        for folder in params.event.removed {
+            let Ok(folder) = folder.uri.to_file_path() else {
+                continue;
+            };
+            let removed = self.workspace.write().await.remove_folder(&folder);
+            for path in removed {
+                let Ok(uri) = Url::from_file_path(&path) else {
+                    continue;
+                };
+                if !self.documents.read().await.contains_key(&uri) {
+                    self.clear_document(&uri).await;
+                }
+            }
+        }
+        for folder in params.event.added {
+            let Ok(folder) = folder.uri.to_file_path() else {
+                continue;
+            };
+            let files = self.workspace.write().await.add_folder(folder);
+            self.analyze_files(files).await;
+        }
+    }
+
+    async fn did_change_watched_files(&self, params: DidChangeWatchedFilesParams) {
+        for change in params.changes {
+            let Ok(path) = change.uri.to_file_path() else {
+                continue;
+            };
+            let open = self.documents.read().await.contains_key(&change.uri);
+            match change.typ {
+                FileChangeType::DELETED => {
+                    self.workspace.write().await.file_deleted(&path);
+                    if !open {
+                        self.clear_document(&change.uri).await;
+                    }
+                }
+                FileChangeType::CREATED => {
+                    let in_workspace = self.workspace.write().await.file_created(&path);
+                    if in_workspace && !open {
+                        self.analyze_document(&change.uri).await;
+                    }
+                }
+                _ => {
+                    let in_workspace = self.workspace.read().await.contains(&path);
+                    if in_workspace && !open {
+                        self.analyze_document(&change.uri).await;
+                    }
+                }
+            }
+            if change.typ == FileChangeType::CREATED {
+                // A new file may be a module that a `use` couldn't find
+                let analyzed = self.workspace.read().await.analyzed();
+                for uri in analyzed.iter().filter(|uri| **uri != change.uri) {
+                    self.analyze_document(uri).await;
+                }
+            } else {
+                self.analyze_dependents(&path).await;
+            }
+        }
+    }
+
+    async fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
         let mut completions = Vec::new();
 
         // Keywords
@@ -312,13 +668,19 @@ impl LanguageServer for RelanoteLanguageServer {
             ("in", "Local binding scope"),
             ("section", "Define a section"),
             ("layer", "Combine multiple parts"),
+            ("kit", "Define a drum kit"),
             ("Part", "Define a part"),
             ("if", "Conditional expression"),
             ("then", "Then branch"),
             ("else", "Else branch"),
             ("match", "Pattern matching"),
-            ("with", "Match patterns"),
+            ("with", "Modify a scale or chord"),
             ("set", "Set global property"),
+            ("meta", "Song title, composer and copyright"),
+            ("test", "Test run by relanote test"),
+            ("render", "Song the program outputs"),
+            ("context", "Scope tempo, key and swing"),
+            ("env", "Dynamics envelope"),
             ("import", "Import module"),
             ("export", "Export binding"),
             ("from", "Import source"),
@@ -350,29 +712,16 @@ impl LanguageServer for RelanoteLanguageServer {
             });
         }
 
-        // Built-in functions
-        let functions = [
-            ("reverse", "Reverse a block"),
-            ("transpose", "Transpose by an interval"),
-            ("repeat", "Repeat n times"),
-            ("volume", "Set volume (0.0-1.0)"),
-            ("reverb", "Apply reverb (0.0-1.0)"),
-            ("hall_reverb", "Hall reverb preset"),
-            ("room_reverb", "Room reverb preset"),
-            ("plate_reverb", "Plate reverb preset"),
-            ("dry", "No reverb"),
-            ("voice", "Set instrument voice"),
-            ("swing", "Apply swing feel"),
-            ("double_time", "Double tempo"),
-            ("half_time", "Half tempo"),
-            ("metronome", "Generate metronome"),
-            ("cutoff", "Filter cutoff frequency"),
-        ];
-        for (label, detail) in functions {
+        // Builtins, from the registry
+        for builtin in relanote_meta::BUILTINS {
             completions.push(CompletionItem {
-                label: label.to_string(),
-                kind: Some(CompletionItemKind::FUNCTION),
-                detail: Some(detail.to_string()),
+                label: builtin.name.to_string(),
+                kind: Some(if builtin.is_value() {
+                    CompletionItemKind::CONSTANT
+                } else {
+                    CompletionItemKind::FUNCTION
+                }),
+                detail: Some(builtin.summary().to_string()),
                 ..Default::default()
             });
         }
@@ -465,64 +814,28 @@ impl LanguageServer for RelanoteLanguageServer {
             });
         }
 
-        // Scales (predefined)
-        let scales = [
-            ("Major", "Major scale { R, M2, M3, P4, P5, M6, M7 }"),
-            ("Minor", "Natural minor { R, M2, m3, P4, P5, m6, m7 }"),
-            (
-                "HarmonicMinor",
-                "Harmonic minor { R, M2, m3, P4, P5, m6, M7 }",
-            ),
-            (
-                "MelodicMinor",
-                "Melodic minor { R, M2, m3, P4, P5, M6, M7 }",
-            ),
-            ("Dorian", "Dorian mode { R, M2, m3, P4, P5, M6, m7 }"),
-            ("Phrygian", "Phrygian mode { R, m2, m3, P4, P5, m6, m7 }"),
-            ("Lydian", "Lydian mode { R, M2, M3, A4, P5, M6, M7 }"),
-            (
-                "Mixolydian",
-                "Mixolydian mode { R, M2, M3, P4, P5, M6, m7 }",
-            ),
-            ("Locrian", "Locrian mode { R, m2, m3, P4, d5, m6, m7 }"),
-            ("MajorPentatonic", "Major pentatonic { R, M2, M3, P5, M6 }"),
-            ("MinorPentatonic", "Minor pentatonic { R, m3, P4, P5, m7 }"),
-            ("Blues", "Blues scale { R, m3, P4, d5, P5, m7 }"),
-            ("WholeTone", "Whole tone { R, M2, M3, A4, A5, A6 }"),
-            ("Chromatic", "Chromatic scale"),
-        ];
-        for (label, detail) in scales {
+        // Scales, as the prelude defines them
+        for scale in relanote_meta::scales() {
             completions.push(CompletionItem {
-                label: label.to_string(),
+                label: scale.name.to_string(),
                 kind: Some(CompletionItemKind::CLASS),
-                detail: Some(format!("Scale: {}", detail)),
+                detail: Some(format!(
+                    "Scale: {} ({})",
+                    scale.definition, scale.description
+                )),
                 ..Default::default()
             });
         }
 
-        // Chords (predefined)
-        let chords = [
-            ("MajorTriad", "Major triad { R, M3, P5 }"),
-            ("MinorTriad", "Minor triad { R, m3, P5 }"),
-            ("Diminished", "Diminished { R, m3, d5 }"),
-            ("Augmented", "Augmented { R, M3, A5 }"),
-            ("Major7", "Major 7th { R, M3, P5, M7 }"),
-            ("Minor7", "Minor 7th { R, m3, P5, m7 }"),
-            ("Dominant7", "Dominant 7th { R, M3, P5, m7 }"),
-            ("MinorMajor7", "Minor-major 7th { R, m3, P5, M7 }"),
-            ("HalfDiminished7", "Half-diminished { R, m3, d5, m7 }"),
-            ("Diminished7", "Diminished 7th { R, m3, d5, d7 }"),
-            ("Sus2", "Suspended 2nd { R, M2, P5 }"),
-            ("Sus4", "Suspended 4th { R, P4, P5 }"),
-            ("Add9", "Add 9 { R, M3, P5, M9 }"),
-            ("Add11", "Add 11 { R, M3, P5, P11 }"),
-            ("Power", "Power chord { R, P5 }"),
-        ];
-        for (label, detail) in chords {
+        // Chords, as the prelude defines them
+        for chord in relanote_meta::chords() {
             completions.push(CompletionItem {
-                label: label.to_string(),
+                label: chord.name.to_string(),
                 kind: Some(CompletionItemKind::CLASS),
-                detail: Some(format!("Chord: {}", detail)),
+                detail: Some(format!(
+                    "Chord: {} ({})",
+                    chord.definition, chord.description
+                )),
                 ..Default::default()
             });
         }
@@ -566,6 +879,40 @@ impl LanguageServer for RelanoteLanguageServer {
             });
         }
 
+        // Bindings the document defines or imports, with their doc comments;
+        // they shadow builtins of the same name
+        let uri = params.text_document_position.text_document.uri;
+        let documents = self.documents.read().await;
+        if let Some(doc) = documents.get(&uri) {
+            let source = Source::from_string(uri.path().to_string(), doc.content.clone());
+            let (program, _) = parse_source(&source);
+            let checker =
+                checker_with_modules(&uri, &doc.content, &program, open_files(&documents));
+            let bindings = checker.top_level_bindings();
+            completions
+                .retain(|item| !bindings.iter().any(|(name, _)| item.label == name.as_str()));
+            for (name, ty) in bindings {
+                let kind = match ty {
+                    Type::Function(..) => CompletionItemKind::FUNCTION,
+                    Type::Scale | Type::Chord => CompletionItemKind::CLASS,
+                    Type::Synth => CompletionItemKind::ENUM_MEMBER,
+                    _ => CompletionItemKind::VARIABLE,
+                };
+                completions.push(CompletionItem {
+                    label: name.to_string(),
+                    kind: Some(kind),
+                    detail: Some(format!("{}: {}", name, ty)),
+                    documentation: checker.doc(&name).map(|doc| {
+                        Documentation::MarkupContent(MarkupContent {
+                            kind: MarkupKind::Markdown,
+                            value: doc.to_string(),
+                        })
+                    }),
+                    ..Default::default()
+                });
+            }
+        }
+
         Ok(Some(CompletionResponse::Array(completions)))
     }
 
@@ -577,18 +924,9 @@ impl LanguageServer for RelanoteLanguageServer {
         if let Some(doc) = documents.get(&uri) {
             let source = Source::from_string(uri.path().to_string(), doc.content.clone());
 
-            // Convert line/column to byte offset
-            let offset = {
-                let mut off = 0usize;
-                for (i, line) in doc.content.lines().enumerate() {
-                    if i == position.line as usize {
-                        off += (position.character as usize).min(line.len());
-                        break;
-                    }
-                    off += line.len() + 1; // +1 for newline
-                }
-                off
-            };
+            let offset = position_to_offset(&doc.content, position);
+
+            let (program, _) = parse_source(&source);
 
             // Tokenize and find the token at offset
             let lexer = Lexer::new(&source);
@@ -600,17 +938,34 @@ impl LanguageServer for RelanoteLanguageServer {
                     let hover_content = match &token.kind {
                         // Identifiers - check for builtins or show type
                         TokenKind::Ident(name) => {
-                            if let Some((sig, desc)) = get_builtin_docs(name) {
-                                Some(format!("```rela\n{}\n```\n\n{}", sig, desc))
+                            if let Some(builtin) = relanote_meta::builtin(name) {
+                                Some(builtin.hover())
+                            } else if let Some(scale) = hover::scale_hover(&program, name, offset)
+                            {
+                                Some(scale)
+                            } else if let Some(chord) = hover::chord_hover(&program, name, offset)
+                            {
+                                Some(chord)
                             } else {
-                                // Parse and type check to get variable type
-                                let (program, _) = parse_source(&source);
-                                let mut checker = TypeChecker::new();
-                                checker.check_program(&program);
-                                if let Some(ty) = checker.lookup_type(name) {
-                                    Some(format!("```rela\n{}: {}\n```\n\nUser-defined binding", name, ty))
-                                } else {
-                                    Some(format!("```rela\n{}\n```\n\nIdentifier", name))
+                                // Type check to get variable type
+                                let checker = checker_with_modules(
+                                    &uri,
+                                    &doc.content,
+                                    &program,
+                                    open_files(&documents),
+                                );
+                                let ty = match checker.declared_type(name) {
+                                    Some(ann) => Some(ann.to_string()),
+                                    None => checker.lookup_type(name).map(|ty| ty.to_string()),
+                                };
+                                match ty {
+                                    Some(ty) => Some(format!(
+                                        "```rela\n{}: {}\n```\n\n{}",
+                                        name,
+                                        ty,
+                                        checker.doc(name).unwrap_or("User-defined binding")
+                                    )),
+                                    None => Some(format!("```rela\n{}\n```\n\nIdentifier", name)),
                                 }
                             }
                         }
@@ -619,9 +974,27 @@ impl LanguageServer for RelanoteLanguageServer {
                         TokenKind::Let => get_keyword_docs("let").map(|(sig, desc)| {
                             format!("```rela\n{}\n```\n\n{}", sig, desc)
                         }),
+                        TokenKind::Meta => get_keyword_docs("meta").map(|(sig, desc)| {
+                            format!("```rela\n{}\n```\n\n{}", sig, desc)
+                        }),
+                        TokenKind::Test => get_keyword_docs("test").map(|(sig, desc)| {
+                            format!("```rela\n{}\n```\n\n{}", sig, desc)
+                        }),
+                        TokenKind::Render => get_keyword_docs("render").map(|(sig, desc)| {
+                            format!("```rela\n{}\n```\n\n{}", sig, desc)
+                        }),
+                        TokenKind::Env => get_keyword_docs("env").map(|(sig, desc)| {
+                            format!("```rela\n{}\n```\n\n{}", sig, desc)
+                        }),
+                        TokenKind::Context => get_keyword_docs("context").map(|(sig, desc)| {
+                            format!("```rela\n{}\n```\n\n{}", sig, desc)
+                        }),
                         TokenKind::Layer => get_keyword_docs("layer").map(|(sig, desc)| {
                             format!("```rela\n{}\n```\n\n{}", sig, desc)
                         }),
+                        TokenKind::Kit => get_keyword_docs("kit").map(|(sig, desc)| {
+                            format!("```rela\n{}\n```\n\n{}", sig, desc)
+                        }),
                         TokenKind::Scale => get_keyword_docs("scale").map(|(sig, desc)| {
                             format!("```rela\n{}\n```\n\n{}", sig, desc)
                         }),
@@ -686,6 +1059,16 @@ impl LanguageServer for RelanoteLanguageServer {
                                 quality_name, degree_name, semitones, cents
                             ))
                         }
+                        TokenKind::Cents(cents) => Some(format!(
+                            "**Interval in cents**\n\n- Semitones: `{}`\n- Cents: `{}`",
+                            cents / 100.0,
+                            cents
+                        )),
+                        TokenKind::Semitones(steps) => Some(format!(
+                            "**Interval in semitones**\n\n- Semitones: `{}`\n- Cents: `{}`",
+                            steps,
+                            steps * 100
+                        )),
 
                         // Root/Rest
                         TokenKind::Root => Some(
@@ -700,7 +1083,7 @@ impl LanguageServer for RelanoteLanguageServer {
                             "**Accent** (`^`)\n\nEmphasizes the note with increased velocity.".to_string()
                         ),
                         TokenKind::Portamento => Some(
-                            "**Portamento/Slur** (`~`)\n\nSmooth transition between notes.".to_string()
+                            "**Portamento/Slur** (`~`)\n\nSmooth transition between notes.\n\nAfter a duration (`R:4~`) or before a note (`~R:2`), `~` is a tie: the notes sound as one.".to_string()
                         ),
 
                         // Pipe operator
@@ -712,32 +1095,185 @@ impl LanguageServer for RelanoteLanguageServer {
                     };
 
                     if let Some(content) = hover_content {
-                        let start_loc = source.location(token.span.start);
-                        let end_loc = source.location(token.span.end);
-                        return Ok(Some(Hover {
-                            contents: HoverContents::Markup(MarkupContent {
-                                kind: MarkupKind::Markdown,
-                                value: content,
-                            }),
-                            range: Some(Range {
-                                start: Position {
-                                    line: (start_loc.line - 1) as u32,
-                                    character: (start_loc.column - 1) as u32,
-                                },
-                                end: Position {
-                                    line: (end_loc.line - 1) as u32,
-                                    character: (end_loc.column - 1) as u32,
-                                },
-                            }),
-                        }));
+                        return Ok(Some(markdown_hover(&source, token.span, content)));
                     }
                 }
             }
+
+            // Between tokens, or on the delimiters of a block or chord
+            if let Some((span, content)) = hover::literal_hover(&program, offset) {
+                return Ok(Some(markdown_hover(&source, span, content)));
+            }
         }
 
         Ok(None)
     }
 
+    async fn references(&self, params: ReferenceParams) -> Result<Option<Vec<Location>>> {
+        let uri = params.text_document_position.text_document.uri;
+        let position = params.text_document_position.position;
+        let documents = self.documents.read().await;
+        let Some(doc) = documents.get(&uri) else {
+            return Ok(None);
+        };
+        let source = Source::from_string(uri.path().to_string(), doc.content.clone());
+        let (program, _) = parse_source(&source);
+        let use_def = UseDef::of_program(&doc.content, &program);
+
+        let offset = position_to_offset(&doc.content, position);
+        let Some(definition) = use_def.definition_at(offset) else {
+            return Ok(None);
+        };
+        let declaration = params
+            .context
+            .include_declaration
+            .then_some(definition.span);
+        let locations = declaration
+            .into_iter()
+            .chain(use_def.uses_of(definition))
+            .map(|span| Location {
+                uri: uri.clone(),
+                range: span_to_range(&source, span),
+            })
+            .collect();
+        Ok(Some(locations))
+    }
+
+    async fn document_highlight(
+        &self,
+        params: DocumentHighlightParams,
+    ) -> Result<Option<Vec<DocumentHighlight>>> {
+        let uri = params.text_document_position_params.text_document.uri;
+        let position = params.text_document_position_params.position;
+        let documents = self.documents.read().await;
+        let Some(doc) = documents.get(&uri) else {
+            return Ok(None);
+        };
+        let source = Source::from_string(uri.path().to_string(), doc.content.clone());
+        let (program, _) = parse_source(&source);
+        let use_def = UseDef::of_program(&doc.content, &program);
+
+        let offset = position_to_offset(&doc.content, position);
+        let Some(definition) = use_def.definition_at(offset) else {
+            return Ok(None);
+        };
+        // The definition is where the name is written, its uses read it
+        let highlight = |span, kind| DocumentHighlight {
+            range: span_to_range(&source, span),
+            kind: Some(kind),
+        };
+        let highlights = std::iter::once(highlight(definition.span, DocumentHighlightKind::WRITE))
+            .chain(
+                use_def
+                    .uses_of(definition)
+                    .into_iter()
+                    .map(|span| highlight(span, DocumentHighlightKind::READ)),
+            )
+            .collect();
+        Ok(Some(highlights))
+    }
+
+    async fn inlay_hint(&self, params: InlayHintParams) -> Result<Option<Vec<InlayHint>>> {
+        let uri = params.text_document.uri;
+        let settings = *self.inlay_hint_settings.read().await;
+
+        let documents = self.documents.read().await;
+        let Some(doc) = documents.get(&uri) else {
+            return Ok(None);
+        };
+        let source = Source::from_string(uri.path().to_string(), doc.content.clone());
+        let (program, _) = parse_source(&source);
+        let checker = checker_with_modules(&uri, &doc.content, &program, open_files(&documents));
+
+        let hints = inlay_hints(&source, &program, &checker, settings)
+            .into_iter()
+            .filter(|hint| params.range.start <= hint.position && hint.position <= params.range.end)
+            .collect();
+        Ok(Some(hints))
+    }
+
+    async fn code_action(&self, params: CodeActionParams) -> Result<Option<CodeActionResponse>> {
+        let uri = params.text_document.uri;
+        let documents = self.documents.read().await;
+        let Some(doc) = documents.get(&uri) else {
+            return Ok(None);
+        };
+        let source = Source::from_string(uri.path().to_string(), doc.content.clone());
+        let (program, _) = parse_source(&source);
+        let mut actions = Vec::new();
+
+        // Quick fixes for the diagnostics in range
+        let diagnostics = &params.context.diagnostics;
+        if diagnostics
+            .iter()
+            .any(|d| has_code(d, DiagnosticCode::UndefinedVariable))
+        {
+            let checker =
+                checker_with_modules(&uri, &doc.content, &program, open_files(&documents));
+            actions.extend(
+                diagnostics
+                    .iter()
+                    .filter(|d| has_code(d, DiagnosticCode::UndefinedVariable))
+                    .filter_map(|d| did_you_mean(&uri, &source, d, &checker)),
+            );
+        }
+        actions.extend(
+            diagnostics
+                .iter()
+                .filter(|d| has_code(d, DiagnosticCode::UnclosedDelimiter))
+                .filter_map(|d| close_delimiter(&uri, &source, d)),
+        );
+
+        // Rewrites of the code at the cursor
+        let start = position_to_offset(&doc.content, params.range.start);
+        let end = position_to_offset(&doc.content, params.range.end);
+        actions.extend(extract_chord(&uri, &source, &program, start));
+        actions.extend(wrap_in_layer(&uri, &source, &program, start..end));
+
+        Ok(Some(
+            actions
+                .into_iter()
+                .map(CodeActionOrCommand::CodeAction)
+                .collect(),
+        ))
+    }
+
+    async fn code_lens(&self, params: CodeLensParams) -> Result<Option<Vec<CodeLens>>> {
+        let uri = params.text_document.uri;
+        let documents = self.documents.read().await;
+        let Some(doc) = documents.get(&uri) else {
+            return Ok(None);
+        };
+        let source = Source::from_string(uri.path().to_string(), doc.content.clone());
+        let (program, _) = parse_source(&source);
+        Ok(Some(code_lenses(&uri, &source, &program)))
+    }
+
+    async fn folding_range(&self, params: FoldingRangeParams) -> Result<Option<Vec<FoldingRange>>> {
+        let uri = params.text_document.uri;
+        let documents = self.documents.read().await;
+        let Some(doc) = documents.get(&uri) else {
+            return Ok(None);
+        };
+        let source = Source::from_string(uri.path().to_string(), doc.content.clone());
+        let (program, _) = parse_source(&source);
+        Ok(Some(folding_ranges(&source, &program)))
+    }
+
+    async fn selection_range(
+        &self,
+        params: SelectionRangeParams,
+    ) -> Result<Option<Vec<SelectionRange>>> {
+        let uri = params.text_document.uri;
+        let documents = self.documents.read().await;
+        let Some(doc) = documents.get(&uri) else {
+            return Ok(None);
+        };
+        let source = Source::from_string(uri.path().to_string(), doc.content.clone());
+        let (program, _) = parse_source(&source);
+        Ok(Some(selection_ranges(&source, &program, &params.positions)))
+    }
+
     async fn formatting(&self, params: DocumentFormattingParams) -> Result<Option<Vec<TextEdit>>> {
         let uri = params.text_document.uri;
 
@@ -747,8 +1283,8 @@ impl LanguageServer for RelanoteLanguageServer {
             let (program, diagnostics) = parse_source(&source);
 
             if !diagnostics.has_errors() {
-                let config = FormatConfig::default();
-                let formatted = format(&program, &config);
+                let config = self.format_config(&uri, &params.options).await;
+                let formatted = format_with_source(&program, &doc.content, &config);
 
                 let lines: Vec<&str> = doc.content.lines().collect();
                 let last_line = lines.len().saturating_sub(1) as u32;
@@ -772,4 +1308,49 @@ impl LanguageServer for RelanoteLanguageServer {
 
         Ok(None)
     }
+
+    async fn range_formatting(
+        &self,
+        params: DocumentRangeFormattingParams,
+    ) -> Result<Option<Vec<TextEdit>>> {
+        let uri = params.text_document.uri;
+        let range = {
+            let documents = self.documents.read().await;
+            match documents.get(&uri) {
+                Some(doc) => {
+                    position_to_offset(&doc.content, params.range.start)
+                        ..position_to_offset(&doc.content, params.range.end)
+                }
+                None => return Ok(None),
+            }
+        };
+
+        Ok(self.format_range_edits(&uri, range, &params.options).await)
+    }
+
+    async fn on_type_formatting(
+        &self,
+        params: DocumentOnTypeFormattingParams,
+    ) -> Result<Option<Vec<TextEdit>>> {
+        // Typing the closing `|` of a block re-lays out the item around it,
+        // lining up the bars; while the block is still open the document
+        // doesn't parse and nothing changes
+        if params.ch != "|" {
+            return Ok(None);
+        }
+        let uri = params.text_document_position.text_document.uri;
+        let offset = {
+            let documents = self.documents.read().await;
+            match documents.get(&uri) {
+                Some(doc) => {
+                    position_to_offset(&doc.content, params.text_document_position.position)
+                }
+                None => return Ok(None),
+            }
+        };
+
+        Ok(self
+            .format_range_edits(&uri, offset..offset, &params.options)
+            .await)
+    }
 }
@@ -0,0 +1,214 @@
+//! The workspace folders the editor opened, the relanote files in them, and
+//! which files each analyzed document depends on
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use relanote_resolver::PACKAGES_DIR;
+use tower_lsp::lsp_types::Url;
+
+/// Directories never searched for relanote files: installed packages and
+/// build output
+const SKIPPED_DIRS: &[&str] = &[PACKAGES_DIR, "target", "node_modules"];
+
+/// Workspace state shared by the analyses of all documents
+#[derive(Default)]
+pub struct Workspace {
+    folders: Vec<PathBuf>,
+    /// `.rela` files found in the folders, analyzed from disk when they
+    /// aren't open
+    files: BTreeSet<PathBuf>,
+    /// Module files each analyzed document was checked against
+    dependencies: HashMap<Url, HashSet<PathBuf>>,
+}
+
+impl Workspace {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a workspace folder, returning the relanote files found in it
+    pub fn add_folder(&mut self, folder: PathBuf) -> Vec<PathBuf> {
+        let found = rela_files(&folder);
+        self.files.extend(found.iter().cloned());
+        self.folders.push(folder);
+        found
+    }
+
+    /// Remove a workspace folder, returning the files that were only part of
+    /// the workspace through it
+    pub fn remove_folder(&mut self, folder: &Path) -> Vec<PathBuf> {
+        self.folders.retain(|f| f != folder);
+        let removed: Vec<PathBuf> = self
+            .files
+            .iter()
+            .filter(|file| file.starts_with(folder) && !self.in_folder(file))
+            .cloned()
+            .collect();
+        for file in &removed {
+            self.files.remove(file);
+        }
+        removed
+    }
+
+    /// Files of the workspace folders
+    pub fn files(&self) -> impl Iterator<Item = &PathBuf> {
+        self.files.iter()
+    }
+
+    /// Whether `path` is one of the workspace's files
+    pub fn contains(&self, path: &Path) -> bool {
+        self.files.contains(path)
+    }
+
+    /// Note a relanote file created on disk, returning whether it is in a
+    /// workspace folder
+    pub fn file_created(&mut self, path: &Path) -> bool {
+        let in_folder = self.folders.iter().any(|folder| {
+            path.strip_prefix(folder)
+                .is_ok_and(|relative| !is_skipped(relative))
+        });
+        if in_folder {
+            self.files.insert(path.to_path_buf());
+        }
+        in_folder
+    }
+
+    /// Note a relanote file deleted from disk
+    pub fn file_deleted(&mut self, path: &Path) {
+        self.files.remove(path);
+    }
+
+    /// Record the module files `uri` was last checked against
+    pub fn set_dependencies(&mut self, uri: &Url, dependencies: HashSet<PathBuf>) {
+        self.dependencies.insert(uri.clone(), dependencies);
+    }
+
+    /// Forget a document that is no longer analyzed
+    pub fn forget(&mut self, uri: &Url) {
+        self.dependencies.remove(uri);
+    }
+
+    /// Documents checked against `path`, directly or through other modules,
+    /// which need analyzing again when it changes
+    pub fn dependents(&self, path: &Path) -> Vec<Url> {
+        let mut dependents: Vec<Url> = self
+            .dependencies
+            .iter()
+            .filter(|(_, dependencies)| dependencies.contains(path))
+            .map(|(uri, _)| uri.clone())
+            .collect();
+        dependents.sort();
+        dependents
+    }
+
+    /// Every analyzed document
+    pub fn analyzed(&self) -> Vec<Url> {
+        let mut analyzed: Vec<Url> = self.dependencies.keys().cloned().collect();
+        analyzed.sort();
+        analyzed
+    }
+
+    fn in_folder(&self, path: &Path) -> bool {
+        self.folders.iter().any(|folder| path.starts_with(folder))
+    }
+}
+
+/// The `.rela` files under `dir`, leaving out hidden directories and the
+/// `SKIPPED_DIRS`
+pub fn rela_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            if file_type.is_dir() {
+                if !is_skipped_dir(&entry.file_name().to_string_lossy()) {
+                    dirs.push(path);
+                }
+            } else if path.extension().is_some_and(|ext| ext == "rela") {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+fn is_skipped_dir(name: &str) -> bool {
+    name.starts_with('.') || SKIPPED_DIRS.contains(&name)
+}
+
+/// Whether a file, given relative to a workspace folder, is in a directory
+/// `rela_files` leaves out
+fn is_skipped(relative: &Path) -> bool {
+    relative.parent().is_some_and(|dir| {
+        dir.components()
+            .any(|c| is_skipped_dir(&c.as_os_str().to_string_lossy()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_folders_and_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        for file in [
+            "main.rela",
+            "parts/bass.rela",
+            "notes.txt",
+            ".git/hooks.rela",
+            "packages/drums/mod.rela",
+            "target/out.rela",
+        ] {
+            let path = root.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+
+        let mut workspace = Workspace::new();
+        let found = workspace.add_folder(root.to_path_buf());
+        assert_eq!(
+            found,
+            vec![root.join("main.rela"), root.join("parts/bass.rela")]
+        );
+
+        assert!(workspace.file_created(&root.join("parts/lead.rela")));
+        assert!(!workspace.file_created(&root.join("target/new.rela")));
+        assert!(!workspace.file_created(Path::new("/elsewhere/song.rela")));
+        assert_eq!(workspace.files().count(), 3);
+
+        let removed = workspace.remove_folder(root);
+        assert_eq!(removed.len(), 3);
+        assert_eq!(workspace.files().count(), 0);
+    }
+
+    #[test]
+    fn test_dependents() {
+        let mut workspace = Workspace::new();
+        let main = Url::parse("file:///song/main.rela").unwrap();
+        let verse = Url::parse("file:///song/verse.rela").unwrap();
+        let bass = PathBuf::from("/song/bass.rela");
+        let melody = PathBuf::from("/song/melody.rela");
+        workspace.set_dependencies(&main, HashSet::from([bass.clone(), melody.clone()]));
+        workspace.set_dependencies(&verse, HashSet::from([melody.clone()]));
+
+        assert_eq!(
+            workspace.dependents(&melody),
+            vec![main.clone(), verse.clone()]
+        );
+        assert_eq!(workspace.dependents(&bass), vec![main.clone()]);
+        assert_eq!(workspace.analyzed(), vec![main.clone(), verse.clone()]);
+        workspace.forget(&main);
+        assert!(workspace.dependents(&bass).is_empty());
+    }
+}
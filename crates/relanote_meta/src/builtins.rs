@@ -0,0 +1,1289 @@
+//! The builtin registry
+
+use crate::{Builtin, Category};
+
+/// Builtins, in the order the builtin reference lists them
+///
+/// Signatures are parsed by the type checker. Parameters may be named
+/// (`times: Int`) so errors can say which argument is wrong, and lowercase
+/// names are type variables. A parameter may have a default
+/// (`mix: Float = 0.4`), which a call that names its arguments gets when it
+/// leaves that parameter out. A builtin that takes different kinds of
+/// values has one signature per kind, separated by `|`; a call uses the
+/// first one its arguments fit.
+///
+/// Builtins also take an `Int` wherever they take a `Float`.
+pub const BUILTINS: &[Builtin] = &[
+    Builtin {
+        name: "reverse",
+        category: Category::Block,
+        signature: "block: Block -> Block",
+        doc: "Reverses the order of notes in a block.",
+        example: concat!(
+            "| <1> <2> <3> | |> reverse\n",
+            "; Result: | <3> <2> <1> |",
+        ),
+        notes: "",
+    },
+    Builtin {
+        name: "transpose",
+        category: Category::Block,
+        signature: "interval: Interval -> block: Block -> Block",
+        doc: "Shifts all notes by a given interval.",
+        example: concat!(
+            "| <1> <3> <5> | |> transpose P5\n",
+            "; All notes shifted up by a perfect fifth\n",
+            "\n",
+            "| <1> <3> <5> | |> transpose (R - P8)\n",
+            "; Transpose down an octave (use parentheses for expressions)",
+        ),
+        notes: "",
+    },
+    Builtin {
+        name: "modulate",
+        category: Category::Block,
+        signature: "interval: Interval -> block: Block -> Block \
+         | interval: Interval -> part: Part -> Part \
+         | interval: Interval -> section: Section -> Section \
+         | interval: Interval -> song: Song -> Song",
+        doc: "Changes key by a given interval. A section, or every section of a song, keeps its \
+             intervals and moves the base note they are relative to, so renderers play each \
+             section in its own key. A block or part is transposed.",
+        example: concat!(
+            "let chorus = section \"Chorus\" | <1> <3> <5> |\n",
+            "chorus ++ (chorus |> modulate M2)\n",
+            "; The second chorus is a whole step higher",
+        ),
+        notes: "A section's key moves by whole semitones.",
+    },
+    Builtin {
+        name: "repeat",
+        category: Category::Block,
+        signature: "times: Int -> block: Block -> Block",
+        doc: "Repeats a block n times.",
+        example: concat!(
+            "| <1> <2> | |> repeat 3\n",
+            "; Result: | <1> <2> <1> <2> <1> <2> |",
+        ),
+        notes: "",
+    },
+    Builtin {
+        name: "rotate",
+        category: Category::Block,
+        signature: "steps: Int -> block: Block -> Block",
+        doc: "Rotates the slots of a block: a positive count moves the first slots to the end, a \
+             negative one the last slots to the start.",
+        example: concat!(
+            "| <1> <2> <3> <4> | |> rotate 1\n",
+            "; Result: | <2> <3> <4> <1> |",
+        ),
+        notes: "",
+    },
+    Builtin {
+        name: "octaveUp",
+        category: Category::Block,
+        signature: "block: Block -> Block",
+        doc: "Transposes a block up an octave.",
+        example: "| <1> <3> <5> | |> octaveUp",
+        notes: "",
+    },
+    Builtin {
+        name: "octaveDown",
+        category: Category::Block,
+        signature: "block: Block -> Block",
+        doc: "Transposes a block down an octave.",
+        example: "| <1> <3> <5> | |> octaveDown",
+        notes: "",
+    },
+    Builtin {
+        name: "slice",
+        category: Category::Block,
+        signature: "start_beat: Float -> end_beat: Float -> block: Block -> Block \
+         | start: Duration -> end: Duration -> block: Block -> Block",
+        doc: "Cuts out the part of a block between two points, in beats from its start. Notes that \
+             cross either point are shortened to fit; cutting through a tuplet is an error. The \
+             slots of the result have explicit lengths, so it can be joined to other material \
+             with `++`.",
+        example: concat!(
+            "| R:2 M3:2 P5:2 | |> slice 1 3\n",
+            "; Result: | R:1 M3:1 |",
+        ),
+        notes: "",
+    },
+    Builtin {
+        name: "bars",
+        category: Category::Block,
+        signature: "bars: [Int] -> block: Block -> Block | bar: Int -> block: Block -> Block",
+        doc: "Takes whole bars of a block, counted from 0, with the bar length of the time \
+             signature. A range takes the bars in it; an array of bar numbers joins the bars it \
+             lists, in its order.",
+        example: concat!(
+            "verse |> bars 0..2      ; the first two bars\n",
+            "verse |> bars [3, 1]    ; the fourth bar, then the second",
+        ),
+        notes: "A single slot can be taken with an index, counted from 0: `melody[2]` is the third \
+             slot as a block of its own, as long as it was.",
+    },
+    Builtin {
+        name: "invert",
+        category: Category::Block,
+        signature: "block: Block -> Block | axis: Interval -> block: Block -> Block",
+        doc: "Turns a melody upside down: each pitch is reflected around the first note, or around \
+             the given interval.",
+        example: concat!(
+            "| R M3 P5 | |> invert\n",
+            "; Result: | R -M3 -P5 |",
+        ),
+        notes: "",
+    },
+    Builtin {
+        name: "retrograde",
+        category: Category::Block,
+        signature: "block: Block -> Block",
+        doc: "Plays the pitches in reverse order over the original rhythm. Unlike `reverse`, \
+             durations, rests and articulations stay where they were.",
+        example: concat!(
+            "| R:2 M3 - P5 | |> retrograde\n",
+            "; Result: | P5:2 M3 - R |",
+        ),
+        notes: "",
+    },
+    Builtin {
+        name: "shuffle",
+        category: Category::Block,
+        signature: "block: Block -> Block | seed: Int -> block: Block -> Block",
+        doc: "Plays the pitches in a random order over the original rhythm. The order is fixed by \
+             a seed, so a song always renders the same; pass a seed to pick another order.",
+        example: concat!(
+            "motif |> shuffle\n",
+            "motif |> shuffle 7",
+        ),
+        notes: "",
+    },
+    Builtin {
+        name: "voice",
+        category: Category::Synth,
+        signature: "synth: Synth -> block: Block -> Part | synth: Synth -> part: Part -> Part",
+        doc: "Applies a synth preset or custom synth to a block.",
+        example: concat!(
+            "| <1> <3> <5> | |> voice Lead\n",
+            "| <1> <3> <5> | |> voice Chiptune\n",
+            "| <1> <3> <5> | |> voice MyCustomSynth",
+        ),
+        notes: "",
+    },
+    Builtin {
+        name: "drum_map",
+        category: Category::Synth,
+        signature: "key: Int -> block: Block -> Part \
+         | key: Int -> part: Part -> Part \
+         | mapping: [(Interval, Int)] -> block: Block -> Part \
+         | mapping: [(Interval, Int)] -> part: Part -> Part",
+        doc: "Renders a part on the MIDI percussion channel (10) with General MIDI drum keys: one \
+             key for every note, or a key for each interval. Parts voiced with a drum preset \
+             (`Kick`, `Snare`, `HiHat`, ...) use the percussion channel without it.",
+        example: concat!(
+            "drums |> drum_map 36\n",
+            "drums |> drum_map [(R, 36), (M2, 38), (M3, 42)]",
+        ),
+        notes: "",
+    },
+    Builtin {
+        name: "cutoff",
+        category: Category::Synth,
+        signature: "frequency: Float -> part: Part -> Part",
+        doc: "Sets the filter cutoff frequency in Hz.",
+        example: concat!(
+            "melody |> voice Lead |> cutoff 800    ; Dark sound\n",
+            "melody |> voice Lead |> cutoff 4000   ; Bright sound",
+        ),
+        notes: "",
+    },
+    Builtin {
+        name: "resonance",
+        category: Category::Synth,
+        signature: "q: Float -> part: Part -> Part",
+        doc: "Sets the filter resonance (Q factor).",
+        example: concat!(
+            "melody |> voice Lead |> resonance 0.3   ; Subtle\n",
+            "melody |> voice Lead |> resonance 0.8   ; Resonant peak",
+        ),
+        notes: "**Range:** 0.0 to 1.0",
+    },
+    Builtin {
+        name: "detune",
+        category: Category::Synth,
+        signature: "cents: Float -> part: Part -> Part",
+        doc: "Detunes the oscillators in cents.",
+        example: concat!(
+            "melody |> voice Lead |> detune 10    ; Slight thickening\n",
+            "melody |> voice Lead |> detune 50    ; Heavy chorus effect",
+        ),
+        notes: "",
+    },
+    Builtin {
+        name: "adsr",
+        category: Category::Synth,
+        signature: "attack: Float -> decay: Float -> sustain: Float -> release: Float -> part: Part -> Part",
+        doc: "Sets the ADSR envelope (Attack, Decay, Sustain, Release).",
+        example: concat!(
+            "; adsr attack decay sustain release\n",
+            "melody |> voice Lead |> adsr 0.01 0.1 0.7 0.3   ; Quick attack\n",
+            "melody |> voice Lead |> adsr 0.5 0.2 0.8 1.0    ; Slow pad",
+        ),
+        notes: concat!(
+            "**Parameters:**\n",
+            "- Attack: Time in seconds to reach peak volume\n",
+            "- Decay: Time in seconds to reach sustain level\n",
+            "- Sustain: Volume level during sustain (0.0 - 1.0)\n",
+            "- Release: Time in seconds to fade after note off",
+        ),
+    },
+    Builtin {
+        name: "envelope",
+        category: Category::Synth,
+        signature: "attack: Float -> decay: Float -> sustain: Float -> release: Float -> Envelope",
+        doc: "Creates an ADSR envelope value for use in synth definitions or with `apply_env`.",
+        example: "let myEnv = envelope 0.1 0.2 0.7 0.4",
+        notes: "",
+    },
+    Builtin {
+        name: "apply_env",
+        category: Category::Synth,
+        signature: "envelope: Envelope -> block: Block -> Part \
+         | envelope: Envelope -> part: Part -> Part",
+        doc: "Applies an envelope to a block or part: an ADSR envelope from `envelope`, or a \
+             dynamics envelope. `env(from, to, duration)` is a dynamics envelope, a ramp from \
+             one dynamic marking (`ppp`, `pp`, `p`, `mp`, `mf`, `f`, `ff`, `fff`) to another \
+             over a duration.",
+        example: concat!(
+            "pad |> apply_env(env(pp, ff, 4 beats))                 ; Swell in\n",
+            "pad |> apply_env(env(mf, ppp, 2 bars))                 ; Fade out\n",
+            "melody |> voice Lead |> apply_env(envelope 0.2 0.1 0.8 0.5) ; Slower attack",
+        ),
+        notes: "A dynamics envelope starts with the part and holds its last dynamic once the ramp is \
+             over. MIDI output ramps the expression controller (CC 11); the web player scales the \
+             velocity of each note by the level where it starts. An ADSR envelope replaces the \
+             amplitude envelope of the part's instrument: its synth's, or the player's default \
+             for a part without one. A part keeps one envelope of each kind.",
+    },
+    Builtin {
+        name: "Sine",
+        category: Category::Oscillator,
+        signature: "Oscillator",
+        doc: "Pure sine wave - smooth, fundamental tone.",
+        example: concat!(
+            "synth Pad = {\n",
+            "  osc: Sine,\n",
+            "  env: envelope 0.3 0.1 0.8 0.5\n",
+            "}",
+        ),
+        notes: "",
+    },
+    Builtin {
+        name: "Saw",
+        category: Category::Oscillator,
+        signature: "Oscillator",
+        doc: "Sawtooth wave - bright, rich harmonics. Classic lead sound.",
+        example: concat!(
+            "synth BrightLead = {\n",
+            "  osc: Saw,\n",
+            "  env: envelope 0.02 0.1 0.6 0.2\n",
+            "}",
+        ),
+        notes: "",
+    },
+    Builtin {
+        name: "Square",
+        category: Category::Oscillator,
+        signature: "Oscillator",
+        doc: "Square wave - hollow, woody tone. Classic chiptune sound.",
+        example: concat!(
+            "synth Retro = {\n",
+            "  osc: Square,\n",
+            "  env: envelope 0.01 0.1 0.5 0.1\n",
+            "}",
+        ),
+        notes: "",
+    },
+    Builtin {
+        name: "Triangle",
+        category: Category::Oscillator,
+        signature: "Oscillator",
+        doc: "Triangle wave - softer than square, flute-like.",
+        example: concat!(
+            "synth Soft = {\n",
+            "  osc: Triangle,\n",
+            "  env: envelope 0.1 0.2 0.7 0.3\n",
+            "}",
+        ),
+        notes: "",
+    },
+    Builtin {
+        name: "Pulse",
+        category: Category::Oscillator,
+        signature: "duty: Float -> Oscillator",
+        doc: "Pulse wave with variable duty cycle (0.0 - 1.0). Creates different timbres.",
+        example: concat!(
+            "synth NES = {\n",
+            "  osc: Pulse 0.25,    ; 25% duty cycle\n",
+            "  env: envelope 0.01 0.05 0.4 0.1\n",
+            "}",
+        ),
+        notes: "",
+    },
+    Builtin {
+        name: "Noise",
+        category: Category::Oscillator,
+        signature: "Oscillator",
+        doc: "White noise - used for drums, percussion, and effects.",
+        example: concat!(
+            "synth Snare = {\n",
+            "  osc: Noise,\n",
+            "  env: envelope 0.001 0.1 0.0 0.1\n",
+            "}",
+        ),
+        notes: "",
+    },
+    Builtin {
+        name: "PinkNoise",
+        category: Category::Oscillator,
+        signature: "Oscillator",
+        doc: "Pink noise - softer than white noise, with less treble. Rain, wind and hi-hat \
+              washes.",
+        example: concat!(
+            "synth Wind = {\n",
+            "  osc: PinkNoise,\n",
+            "  env: envelope 0.5 0.2 0.8 1.0\n",
+            "}",
+        ),
+        notes: "",
+    },
+    Builtin {
+        name: "BrownNoise",
+        category: Category::Oscillator,
+        signature: "Oscillator",
+        doc: "Brown noise - deep and rumbling, with little treble. Surf, thunder and engine \
+              sounds.",
+        example: concat!(
+            "synth Surf = {\n",
+            "  osc: BrownNoise,\n",
+            "  env: envelope 1.0 0.5 0.6 2.0\n",
+            "}",
+        ),
+        notes: "",
+    },
+    Builtin {
+        name: "sample",
+        category: Category::Oscillator,
+        signature: "path: String -> Oscillator",
+        doc: "Plays a WAV file instead of a waveform, pitched from its root note (middle C unless \
+              set with `sample_root`).",
+        example: concat!(
+            "synth Kick = {\n",
+            "  osc: sample \"samples/kick.wav\",\n",
+            "  env: envelope 0.0 0.3 0.0 0.1\n",
+            "}",
+        ),
+        notes: "The path is relative to the song's file. The audio renderers play the file; in \
+                MIDI a sampled part plays as drums, on the key its synth's or file's name gives \
+                (`kick.wav` plays 36) or on its drum map.",
+    },
+    Builtin {
+        name: "mix",
+        category: Category::Oscillator,
+        signature: "level: Float -> oscillator: Oscillator -> Oscillator",
+        doc: "Sets an oscillator's level in a mix of oscillators (0.0 - 1.0).",
+        example: concat!(
+            "synth Thick = {\n",
+            "  osc: (Saw |> mix 0.7) + (Square |> mix 0.3)\n",
+            "}",
+        ),
+        notes: "",
+    },
+    Builtin {
+        name: "octave",
+        category: Category::Oscillator,
+        signature: "offset: Int -> oscillator: Oscillator -> Oscillator",
+        doc: "Moves an oscillator up or down by whole octaves.",
+        example: concat!(
+            "synth Sub = {\n",
+            "  osc: Saw + (Sine |> octave(-1))\n",
+            "}",
+        ),
+        notes: "",
+    },
+    Builtin {
+        name: "osc_detune",
+        category: Category::Oscillator,
+        signature: "cents: Float -> oscillator: Oscillator -> Oscillator",
+        doc: "Detunes an oscillator in cents.",
+        example: concat!(
+            "synth Wide = {\n",
+            "  osc: Saw + (Saw |> osc_detune 7)\n",
+            "}",
+        ),
+        notes: "",
+    },
+    Builtin {
+        name: "sample_root",
+        category: Category::Oscillator,
+        signature: "note: Int -> oscillator: Oscillator -> Oscillator",
+        doc: "Sets the MIDI note a sample sounds at when played as is.",
+        example: concat!(
+            "synth Bass = {\n",
+            "  osc: sample \"bass_c2.wav\" |> sample_root 36\n",
+            "}",
+        ),
+        notes: "",
+    },
+    Builtin {
+        name: "sample_loop",
+        category: Category::Oscillator,
+        signature: "start: Float -> end: Float -> oscillator: Oscillator -> Oscillator",
+        doc: "Loops a sample between two points, in seconds into the file, for as long as the note \
+              holds.",
+        example: concat!(
+            "synth Strings = {\n",
+            "  osc: sample \"strings.wav\" |> sample_root 60 |> sample_loop 0.5 1.5,\n",
+            "  env: envelope 0.2 0.1 0.8 0.4\n",
+            "}",
+        ),
+        notes: "",
+    },
+    Builtin {
+        name: "LowPass",
+        category: Category::Filter,
+        signature: "cutoff: Float -> resonance: Float -> Filter",
+        doc: "Low-pass filter - removes high frequencies. Makes sound darker/warmer.",
+        example: concat!(
+            "; LowPass cutoff_hz resonance\n",
+            "\n",
+            "synth Warm = {\n",
+            "  osc: Saw,\n",
+            "  filter: LowPass 800 0.3,\n",
+            "  env: envelope 0.1 0.2 0.6 0.3\n",
+            "}",
+        ),
+        notes: "",
+    },
+    Builtin {
+        name: "HighPass",
+        category: Category::Filter,
+        signature: "cutoff: Float -> resonance: Float -> Filter",
+        doc: "High-pass filter - removes low frequencies. Makes sound thinner/brighter.",
+        example: concat!(
+            "; HighPass cutoff_hz resonance\n",
+            "\n",
+            "synth Thin = {\n",
+            "  osc: Saw,\n",
+            "  filter: HighPass 500 0.2,\n",
+            "  env: envelope 0.05 0.1 0.7 0.2\n",
+            "}",
+        ),
+        notes: "",
+    },
+    Builtin {
+        name: "BandPass",
+        category: Category::Filter,
+        signature: "cutoff: Float -> resonance: Float -> Filter",
+        doc: "Band-pass filter - keeps only frequencies around the cutoff. Creates nasal, focused \
+             sound.",
+        example: concat!(
+            "; BandPass center_hz resonance\n",
+            "\n",
+            "synth Telephone = {\n",
+            "  osc: Saw,\n",
+            "  filter: BandPass 1000 0.8,\n",
+            "  env: envelope 0.02 0.1 0.5 0.2\n",
+            "}",
+        ),
+        notes: "",
+    },
+    Builtin {
+        name: "PitchLfo",
+        category: Category::Lfo,
+        signature: "rate: Float -> depth: Float -> Lfo",
+        doc: "Bends the pitch up and down by `depth` semitones, `rate` times a second: vibrato.",
+        example: concat!(
+            "synth Violin = {\n",
+            "  osc: Saw,\n",
+            "  lfo: PitchLfo 5.5 0.2\n",
+            "}",
+        ),
+        notes: "Rates run from 0.01 to 50 Hz, depths up to 24 semitones.",
+    },
+    Builtin {
+        name: "CutoffLfo",
+        category: Category::Lfo,
+        signature: "rate: Float -> depth: Float -> Lfo",
+        doc: "Sweeps the filter's cutoff up and down by `depth` Hz, `rate` times a second: wah and \
+              wobble.",
+        example: concat!(
+            "synth Wobble = {\n",
+            "  osc: Saw,\n",
+            "  filter: LowPass 800 0.6,\n",
+            "  lfo: CutoffLfo 2 600\n",
+            "}",
+        ),
+        notes: "A synth without a filter has no cutoff to move.",
+    },
+    Builtin {
+        name: "AmpLfo",
+        category: Category::Lfo,
+        signature: "rate: Float -> depth: Float -> Lfo",
+        doc: "Swells the volume, `rate` times a second: tremolo. At depth 1.0 it falls to silence \
+              at each trough.",
+        example: concat!(
+            "synth Tremolo = {\n",
+            "  osc: Triangle,\n",
+            "  lfo: AmpLfo 6 0.5\n",
+            "}",
+        ),
+        notes: "",
+    },
+    Builtin {
+        name: "lfo_shape",
+        category: Category::Lfo,
+        signature: "shape: Oscillator -> lfo: Lfo -> Lfo",
+        doc: "Sets the shape of an LFO's wave: `Sine` (the default), `Triangle`, `Square` or `Saw`.",
+        example: concat!(
+            "synth Trill = {\n",
+            "  osc: Square,\n",
+            "  lfo: (PitchLfo 8 2 |> lfo_shape Square) + AmpLfo 4 0.2\n",
+            "}",
+        ),
+        notes: "",
+    },
+    Builtin {
+        name: "reverb",
+        category: Category::Effect,
+        signature: "level: Float -> block: Block -> Part | level: Float -> part: Part -> Part \
+         | level: Float -> bus: Bus -> Bus | level: Float -> song: Song -> Song",
+        doc: "Applies reverb effect.",
+        example: "melody |> reverb 0.5   ; 50% wet",
+        notes: "**Range:** 0.0 (dry) to 1.0 (fully wet)",
+    },
+    Builtin {
+        name: "hall_reverb",
+        category: Category::Effect,
+        signature: "block: Block -> Part | part: Part -> Part | bus: Bus -> Bus | song: Song -> Song",
+        doc: "Applies hall-style reverb preset.",
+        example: "melody |> hall_reverb",
+        notes: "",
+    },
+    Builtin {
+        name: "room_reverb",
+        category: Category::Effect,
+        signature: "block: Block -> Part | part: Part -> Part | bus: Bus -> Bus | song: Song -> Song",
+        doc: "Applies room-style reverb preset.",
+        example: "melody |> room_reverb",
+        notes: "",
+    },
+    Builtin {
+        name: "plate_reverb",
+        category: Category::Effect,
+        signature: "block: Block -> Part | part: Part -> Part | bus: Bus -> Bus | song: Song -> Song",
+        doc: "Applies plate-style reverb preset.",
+        example: "melody |> plate_reverb",
+        notes: "",
+    },
+    Builtin {
+        name: "dry",
+        category: Category::Effect,
+        signature: "block: Block -> Part | part: Part -> Part | bus: Bus -> Bus | song: Song -> Song",
+        doc: "Creates a dry (no reverb) part.",
+        example: "melody |> dry",
+        notes: "",
+    },
+    Builtin {
+        name: "volume",
+        category: Category::Effect,
+        signature: "level: Float -> block: Block -> Part | level: Float -> part: Part -> Part",
+        doc: "Sets the volume level.",
+        example: concat!(
+            "melody |> volume 0.8            ; 80% volume\n",
+            "melody |> voice Lead |> volume 0.5   ; Chain with synth",
+        ),
+        notes: "**Range:** 0.0 (silent) to 1.0 (full volume)",
+    },
+    Builtin {
+        name: "pan",
+        category: Category::Effect,
+        signature: "position: Float -> block: Block -> Part \
+         | position: Float -> part: Part -> Part",
+        doc: "Sets the stereo position, from -1.0 (left) to 1.0 (right), or -100 to 100. Rendered \
+             as MIDI CC 10.",
+        example: "melody |> pan(-0.5)   ; halfway left",
+        notes: "",
+    },
+    Builtin {
+        name: "automate",
+        category: Category::Effect,
+        signature: "cc: Int -> curve: [Float] -> block: Block -> Part \
+         | cc: Int -> curve: [Float] -> part: Part -> Part",
+        doc: "Automates a MIDI controller with a curve of levels from 0.0 to 1.0, spread evenly \
+             over the part. The curve is interpolated into a stream of CC events, for filter \
+             sweeps and fades.",
+        example: "melody |> automate 74 [0.2, 1.0, 0.4]   ; filter sweep up and back",
+        notes: "",
+    },
+    Builtin {
+        name: "duck",
+        category: Category::Effect,
+        signature: "trigger: Part -> amount: Float -> block: Block -> Part \
+         | trigger: Part -> amount: Float -> part: Part -> Part \
+         | trigger: Block -> amount: Float -> block: Block -> Part \
+         | trigger: Block -> amount: Float -> part: Part -> Part",
+        doc: concat!(
+            "Ducks a part under another, as a sidechain compressor would: each note of\n",
+            "`trigger` dips the volume by `amount` (0.0 to 1.0), which comes back over\n",
+            "half a beat. The trigger is timed from the start of the ducked part, so\n",
+            "layer the two together. Rendered as MIDI CC 7 automation.",
+        ),
+        example: concat!(
+            "let kick = | R R R R | |> voice DeepKick\n",
+            "layer [kick, pad |> duck kick 0.6]   ; the pad pumps with the kick",
+        ),
+        notes: "",
+    },
+    Builtin {
+        name: "delay",
+        category: Category::Effect,
+        signature: "time: Float -> feedback: Float = 0.35 -> mix: Float = 0.4 -> block: Block -> Part \
+         | time: Float -> feedback: Float = 0.35 -> mix: Float = 0.4 -> part: Part -> Part \
+         | time: Float -> feedback: Float = 0.35 -> mix: Float = 0.4 -> bus: Bus -> Bus \
+         | time: Float -> feedback: Float = 0.35 -> mix: Float = 0.4 -> song: Song -> Song",
+        doc: concat!(
+            "Adds an echo: `time` in milliseconds, with `feedback` and `mix` from 0.0\n",
+            "to 1.0.",
+        ),
+        example: concat!(
+            "melody |> delay 250 0.4 0.3\n",
+            "melody |> delay(time: 250, mix: 0.3)   ; feedback defaults to 0.35",
+        ),
+        notes: "",
+    },
+    Builtin {
+        name: "phaser",
+        category: Category::Effect,
+        signature: "rate: Float -> depth: Float = 0.5 -> mix: Float = 0.4 -> block: Block -> Part \
+         | rate: Float -> depth: Float = 0.5 -> mix: Float = 0.4 -> part: Part -> Part \
+         | rate: Float -> depth: Float = 0.5 -> mix: Float = 0.4 -> bus: Bus -> Bus \
+         | rate: Float -> depth: Float = 0.5 -> mix: Float = 0.4 -> song: Song -> Song",
+        doc: "Adds a phaser: `rate` in Hz, with `depth` and `mix` from 0.0 to 1.0.",
+        example: concat!(
+            "pad |> phaser 0.5 0.7 0.5\n",
+            "pad |> phaser(rate: 2, mix: 0.3)   ; depth defaults to 0.5",
+        ),
+        notes: "",
+    },
+    Builtin {
+        name: "distortion",
+        category: Category::Effect,
+        signature: "amount: Float -> type: DistortionType -> mix: Float = 0.7 -> block: Block -> Part \
+         | amount: Float -> type: DistortionType -> mix: Float = 0.7 -> part: Part -> Part \
+         | amount: Float -> type: DistortionType -> mix: Float = 0.7 -> bus: Bus -> Bus \
+         | amount: Float -> type: DistortionType -> mix: Float = 0.7 -> song: Song -> Song",
+        doc: "Distorts a block or part: `amount` and `mix` from 0.0 to 1.0, with one of the \
+             distortion types below.",
+        example: concat!(
+            "guitar |> distortion 0.6 SoftClip 0.8\n",
+            "bass |> distortion(amount: 0.4, type: Fuzz)   ; mix defaults to 0.7",
+        ),
+        notes: "",
+    },
+    Builtin {
+        name: "compressor",
+        category: Category::Effect,
+        signature: "threshold: Float -> ratio: Float = 4.0 -> attack: Float = 10.0 -> release: Float = 200.0 -> block: Block -> Part \
+         | threshold: Float -> ratio: Float = 4.0 -> attack: Float = 10.0 -> release: Float = 200.0 -> part: Part -> Part \
+         | threshold: Float -> ratio: Float = 4.0 -> attack: Float = 10.0 -> release: Float = 200.0 -> bus: Bus -> Bus \
+         | threshold: Float -> ratio: Float = 4.0 -> attack: Float = 10.0 -> release: Float = 200.0 -> song: Song -> Song",
+        doc: concat!(
+            "Evens out the dynamics: above `threshold` dB below full scale (24 and -24\n",
+            "are both -24 dB), the sound is turned down by `ratio`, over `attack`\n",
+            "milliseconds, and comes back over `release` milliseconds. Named\n",
+            "`compressor` as `compress` squeezes rhythms.",
+        ),
+        example: concat!(
+            "drums |> compressor 18 4 5 120\n",
+            "layer [lead, pad] |> compressor(threshold: 12, ratio: 2)   ; glue the mix",
+        ),
+        notes: "MIDI can't compress, so the settings go out as CC 102 to 105 for a \
+                DAW or hardware compressor to map.",
+    },
+    Builtin {
+        name: "eq",
+        category: Category::Effect,
+        signature: "low: Float -> mid: Float -> high: Float -> block: Block -> Part \
+         | low: Float -> mid: Float -> high: Float -> part: Part -> Part \
+         | low: Float -> mid: Float -> high: Float -> bus: Bus -> Bus \
+         | low: Float -> mid: Float -> high: Float -> song: Song -> Song",
+        doc: concat!(
+            "Three-band EQ: cuts or boosts, by up to 24 dB, the lows (a shelf below\n",
+            "250 Hz), the mids (around 1 kHz) and the highs (a shelf above 4 kHz).",
+        ),
+        example: concat!(
+            "bass |> eq 4 0 (-6)      ; fuller and darker\n",
+            "pad |> eq (-12) 0 3      ; out of the bass's way",
+        ),
+        notes: "In MIDI the gains go out as CC 106 to 108, with 64 for 0 dB.",
+    },
+    Builtin {
+        name: "chorus_fx",
+        category: Category::Effect,
+        signature: "rate: Float -> depth: Float = 0.5 -> mix: Float = 0.5 -> block: Block -> Part \
+         | rate: Float -> depth: Float = 0.5 -> mix: Float = 0.5 -> part: Part -> Part \
+         | rate: Float -> depth: Float = 0.5 -> mix: Float = 0.5 -> bus: Bus -> Bus \
+         | rate: Float -> depth: Float = 0.5 -> mix: Float = 0.5 -> song: Song -> Song",
+        doc: concat!(
+            "Thickens the sound with slightly detuned copies: `rate` in Hz, with\n",
+            "`depth` and `mix` from 0.0 to 1.0. The copies spread across the stereo\n",
+            "field. Rendered as MIDI CC 93 at the mix level. Named `chorus_fx`, as\n",
+            "`chorus` usually names a song's chorus.",
+        ),
+        example: concat!(
+            "strings |> chorus_fx 0.8 0.4 0.5\n",
+            "guitar |> chorus_fx(rate: 1.5)   ; depth and mix default to 0.5",
+        ),
+        notes: "",
+    },
+    Builtin {
+        name: "width",
+        category: Category::Effect,
+        signature: "amount: Float -> block: Block -> Part \
+         | amount: Float -> part: Part -> Part \
+         | amount: Float -> bus: Bus -> Bus \
+         | amount: Float -> song: Song -> Song",
+        doc: "Narrows or widens the stereo image: 0.0 folds it to mono, 1.0 leaves it \
+              as is and up to 2.0 widens it. Put it after effects that make stereo, such \
+              as `chorus_fx`.",
+        example: "pad |> chorus_fx 0.6 0.5 0.5 |> width 1.6",
+        notes: "MIDI has no stereo width, so MIDI output leaves it out.",
+    },
+    Builtin {
+        name: "bus",
+        category: Category::Effect,
+        signature: "name: String -> Bus",
+        doc: "A bus: an effects chain that parts `send` to and share, such as one reverb for \
+              every part of a `layer`. Add effects to it as to a part.",
+        example: concat!(
+            "let room = bus \"room\" |> hall_reverb\n",
+            "layer [\n",
+            "  piano |> send room 0.5,\n",
+            "  strings |> send room 0.8\n",
+            "]",
+        ),
+        notes: "Parts sending to buses of the same name share the first one's effects.",
+    },
+    Builtin {
+        name: "send",
+        category: Category::Effect,
+        signature: "bus: Bus -> level: Float -> block: Block -> Part \
+         | bus: Bus -> level: Float -> part: Part -> Part",
+        doc: "Sends some of a block's or part's sound, after its own effects, to a bus.",
+        example: "melody |> delay 250 |> send room 0.4",
+        notes: "**Range:** 0.0 (nothing) to 1.0 (all of it)",
+    },
+    Builtin {
+        name: "SoftClip",
+        category: Category::Effect,
+        signature: "DistortionType",
+        doc: "Soft clipping, a warm tube-like distortion.",
+        example: "lead |> distortion 0.5 SoftClip 0.6",
+        notes: "",
+    },
+    Builtin {
+        name: "HardClip",
+        category: Category::Effect,
+        signature: "DistortionType",
+        doc: "Hard clipping, a harsh transistor-like distortion.",
+        example: "lead |> distortion 0.5 HardClip 0.6",
+        notes: "",
+    },
+    Builtin {
+        name: "Fuzz",
+        category: Category::Effect,
+        signature: "DistortionType",
+        doc: "Asymmetric clipping, a fuzz pedal sound.",
+        example: "riff |> distortion 0.8 Fuzz 0.7",
+        notes: "",
+    },
+    Builtin {
+        name: "BitCrush",
+        category: Category::Effect,
+        signature: "DistortionType",
+        doc: "Bit reduction, a lo-fi digital sound.",
+        example: "chip |> distortion 0.7 BitCrush 0.5",
+        notes: "",
+    },
+    Builtin {
+        name: "swing",
+        category: Category::Rhythm,
+        signature: "block: Block -> Block \
+         | part: Part -> Part \
+         | ratio: Float -> block: Block -> Block \
+         | ratio: Float -> part: Part -> Part",
+        doc: "Applies swing feel to a block or part by delaying the off-beat of each pair of \
+             steps.",
+        example: concat!(
+            "| <1> <2> <3> <4> | |> swing\n",
+            "| <1> <2> <3> <4> | |> swing 0.67  ; triplet swing",
+        ),
+        notes: "The optional ratio sets where the off-beat falls within a pair: 0.5 is straight, 0.6 \
+             (the default) is a light 3:2 swing and 0.67 is triplet swing. Swing is applied when \
+             rendering, so notes with explicit durations, tuplets and chords are swung on the \
+             same grid.",
+    },
+    Builtin {
+        name: "humanize",
+        category: Category::Rhythm,
+        signature: "timing_ms: Int -> velocity_range: Int -> block: Block -> Block \
+         | timing_ms: Int -> velocity_range: Int -> part: Part -> Part \
+         | timing_ms: Int -> velocity_range: Int -> seed: Int -> block: Block -> Block \
+         | timing_ms: Int -> velocity_range: Int -> seed: Int -> part: Part -> Part",
+        doc: "Nudges note timing (in ms) and velocity by small random amounts. The offsets are \
+             seeded, so a song always renders the same; an extra seed picks another take.",
+        example: concat!(
+            "melody |> humanize 10 8     ; up to 10 ms and 8 velocity steps\n",
+            "melody |> humanize 10 8 42  ; another take",
+        ),
+        notes: "",
+    },
+    Builtin {
+        name: "glide",
+        category: Category::Rhythm,
+        signature: "ms: Float -> block: Block -> Block | ms: Float -> part: Part -> Part",
+        doc: "Glides (portamento) from each note to the next with a pitch-bend ramp of the given \
+             length in ms. A single note can glide into the next with `~`.",
+        example: "| R M3 P5 | |> glide 80",
+        notes: "",
+    },
+    Builtin {
+        name: "double_time",
+        category: Category::Rhythm,
+        signature: "block: Block -> Block",
+        doc: "Doubles the tempo (halves note durations).",
+        example: "| <1> <2> <3> <4> | |> double_time",
+        notes: "",
+    },
+    Builtin {
+        name: "half_time",
+        category: Category::Rhythm,
+        signature: "block: Block -> Block",
+        doc: "Halves the tempo (doubles note durations).",
+        example: "| <1> <2> <3> <4> | |> half_time",
+        notes: "",
+    },
+    Builtin {
+        name: "stretch",
+        category: Category::Rhythm,
+        signature: "factor: Float -> block: Block -> Block",
+        doc: "Multiplies every duration by a factor.",
+        example: "| <1> <2> <3> | |> stretch 1.5",
+        notes: "",
+    },
+    Builtin {
+        name: "compress",
+        category: Category::Rhythm,
+        signature: "factor: Float -> block: Block -> Block",
+        doc: "Divides every duration by a factor; `compress 2` is `double_time`.",
+        example: "| <1> <2> <3> | |> compress 3",
+        notes: "",
+    },
+    Builtin {
+        name: "quantize",
+        category: Category::Rhythm,
+        signature: "grid: Duration -> block: Block -> Block",
+        doc: "Snaps the rhythm to a grid. Each slot ends on the grid line nearest its written end, \
+             so every slot starts on the grid; a slot that would vanish keeps one grid step.",
+        example: concat!(
+            "| R:1/16 M3:1/4. P5:1/8 | |> quantize 1/8\n",
+            "; Result: | R:1/8 M3:1/4. P5:1/8 |",
+        ),
+        notes: "",
+    },
+    Builtin {
+        name: "metronome",
+        category: Category::Rhythm,
+        signature: "bars: Int -> beats_per_bar: Int -> Part",
+        doc: "Creates a metronome click track.",
+        example: "metronome 4 4   ; 4 bars of 4 beats",
+        notes: "",
+    },
+    Builtin {
+        name: "steps",
+        category: Category::Rhythm,
+        signature: "pattern: String -> drum: Synth -> Block \
+         | pattern: String -> drum: Int -> Block \
+         | pattern: String -> drum: Synth -> step: Duration -> Block \
+         | pattern: String -> drum: Int -> step: Duration -> Block",
+        doc: "Builds a drum block from a step pattern. `x` is a hit, `X` an accented hit, and `.` \
+             or `-` a rest; spaces and `|` only group the steps. Each step is a sixteenth note \
+             unless a step length is given. The drum is a drum synth or a GM percussion key, and \
+             the block plays on the MIDI percussion channel.",
+        example: concat!(
+            "steps \"x..x ..x. | x... x...\" Kick8bit   ; one bar of sixteenths\n",
+            "steps \"xxxx xxxx\" 42 1/8                  ; closed hi-hat on eighths",
+        ),
+        notes: "",
+    },
+    Builtin {
+        name: "chords",
+        category: Category::Harmony,
+        signature: "symbols: String -> Block",
+        doc: "Builds a block of chords from jazz chord symbols. Roots are read in the key set with \
+             `set key`, so `chords \"Dm7 | G7 | Cmaj7\"` in C is a ii–V–I from the second degree. \
+             Bars are separated by `|` and last a bar each; the chords written in one bar share \
+             it.",
+        example: concat!(
+            "set key = C4\n",
+            "chords \"Cmaj7 | F#m7b5 B7 | Em\"",
+        ),
+        notes: concat!(
+            "Qualities stand for the prelude's chords:\n",
+            "\n",
+            "| Symbol | Chord |\n",
+            "|--------|-------|\n",
+            "| `C`, `Cmaj`, `CM` | `MajorTriad` |\n",
+            "| `Cm`, `Cmin`, `C-` | `MinorTriad` |\n",
+            "| `Cdim`, `C°` | `Diminished` |\n",
+            "| `Caug`, `C+` | `Augmented` |\n",
+            "| `Cmaj7`, `CM7`, `CΔ7` | `Major7` |\n",
+            "| `Cm7`, `Cmin7`, `C-7` | `Minor7` |\n",
+            "| `C7` | `Dominant7` |\n",
+            "| `CmMaj7`, `CmM7` | `MinorMajor7` |\n",
+            "| `Cm7b5`, `Cø` | `HalfDiminished7` |\n",
+            "| `Cdim7`, `C°7` | `Diminished7` |\n",
+            "| `Csus2`, `Csus4`, `Cadd9` | `Sus2`, `Sus4`, `Add9` |\n",
+            "\n",
+            "A slash chord (`C/E`) adds its bass note below the chord.",
+        ),
+    },
+    Builtin {
+        name: "progression",
+        category: Category::Harmony,
+        signature: "numerals: String -> Block",
+        doc: "Builds a block of chords from Roman numerals in the key. Numerals count degrees of \
+             the major scale from the tonic, and `b` or `#` in front moves the root (`bVII`, \
+             `#iv°`). Upper-case numerals are major chords and lower-case ones minor; a suffix \
+             picks another quality from the table above (`ii°`, `V7`, `IVmaj7`, `viiø7`, \
+             `Vsus4`). Bars work as in `chords`.",
+        example: concat!(
+            "progression \"ii7 | V7 | Imaj7\"\n",
+            "progression \"I | bVII | IV | I\"",
+        ),
+        notes: "",
+    },
+    Builtin {
+        name: "smooth_voicing",
+        category: Category::Harmony,
+        signature: "chords: Block -> Block | chords: [Chord] -> Block",
+        doc: "Revoices a progression for smooth voice leading: each chord takes the inversion and \
+             octave that moves its notes least from the chord before. The first chord stays as \
+             written. A block keeps its rhythm, and an array of chords becomes a block with a bar \
+             per chord.",
+        example: concat!(
+            "progression \"ii7 | V7 | Imaj7\" |> smooth_voicing\n",
+            "; Dm7 (D F A C), G7 as D F G B, Cmaj7 as C E G B",
+        ),
+        notes: "",
+    },
+    Builtin {
+        name: "negative_harmony",
+        category: Category::Harmony,
+        signature: "axis: Interval -> block: Block -> Block",
+        doc: "Mirrors a block's pitches for negative harmony. Notes are reflected around the point \
+             halfway between the axis and the fifth above it, so with the axis on the tonic the \
+             root and fifth swap and a major third becomes a minor third.",
+        example: "| R M3 P5 | |> negative_harmony R   ; P5 m3 R",
+        notes: "",
+    },
+    Builtin {
+        name: "map_mode",
+        category: Category::Harmony,
+        signature: "from: Scale -> to: Scale -> block: Block -> Block",
+        doc: "Moves a block from one mode to another, degree by degree. Each note takes the step \
+             of the second scale at the degree it has in the first; notes between degrees keep \
+             their distance from the degree below. Both scales need the same number of steps.",
+        example: "| R M3 P5 M7 | |> map_mode Major Minor   ; R m3 P5 m7",
+        notes: "",
+    },
+    Builtin {
+        name: "mark",
+        category: Category::Text,
+        signature: "text: String -> block: Block -> Block | text: String -> part: Part -> Part",
+        doc: "Places a marker at the first note of a block. Markers are written as MIDI Marker \
+             events on the conductor track, so DAWs show the song structure.",
+        example: "chorus |> mark \"Chorus\"",
+        notes: "",
+    },
+    Builtin {
+        name: "lyrics",
+        category: Category::Text,
+        signature: "syllables: String -> block: Block -> Block \
+         | syllables: String -> part: Part -> Part \
+         | syllables: [String] -> block: Block -> Block \
+         | syllables: [String] -> part: Part -> Part",
+        doc: "Attaches lyric syllables to successive notes and chords, written as MIDI Lyric \
+             events. A string is split on whitespace; an array gives the syllables directly.",
+        example: concat!(
+            "| <1> <2> <3> | |> lyrics \"Hel- lo world\"\n",
+            "| <1> <2> | |> lyrics [\"Hel-\", \"lo\"]",
+        ),
+        notes: "",
+    },
+    Builtin {
+        name: "edo",
+        category: Category::Tuning,
+        signature: "steps: Int -> Scale",
+        doc: "Builds a scale that divides the octave into equal steps, for microtonal tunings. \
+             Notes in the scale are rendered with pitch bends.",
+        example: concat!(
+            "let nineteen = edo 19\n",
+            "| R M2 M3 | |> in nineteen   ; steps 0, 1 and 2 of 19",
+        ),
+        notes: "",
+    },
+    Builtin {
+        name: "assert_eq",
+        category: Category::Assertion,
+        signature: "expected: a -> actual: a -> Bool",
+        doc: "Fails unless two values are equal. Intervals within a hundredth of a cent count as \
+             equal. Returns `true`.",
+        example: "assert_eq(melody |> reverse |> reverse, melody)",
+        notes: "",
+    },
+    Builtin {
+        name: "assert_beats",
+        category: Category::Assertion,
+        signature: "beats: Float -> block: Block -> Block \
+         | block: Block -> beats: Float -> Block \
+         | beats: Duration -> block: Block -> Block \
+         | block: Block -> beats: Duration -> Block",
+        doc: "Fails unless a block lasts the given number of beats, or the given duration. Returns \
+             the block, so it can sit in a pipeline.",
+        example: concat!(
+            "let bar = generated |> assert_beats 4\n",
+            "let phrase = generated |> assert_beats 2 bars",
+        ),
+        notes: "",
+    },
+    Builtin {
+        name: "assert_in_scale",
+        category: Category::Assertion,
+        signature: "scale: Scale -> block: Block -> Block | block: Block -> scale: Scale -> Block",
+        doc: "Fails unless every note of a block is in the scale, naming the intervals that \
+             aren't. Returns the block.",
+        example: concat!(
+            "let line = generated |> assert_in_scale Major\n",
+            "; error: assertion failed: notes outside Major: m3",
+        ),
+        notes: "",
+    },
+    Builtin {
+        name: "compose",
+        category: Category::Composition,
+        signature: "sections: [Section] -> Song",
+        doc: "Combines sections into a song.",
+        example: "compose [intro, verse, chorus, verse, chorus, outro]",
+        notes: "",
+    },
+    Builtin {
+        name: "play",
+        category: Category::Composition,
+        signature: "block: Block -> part: Part -> Part",
+        doc: "Adds a block to an existing part.",
+        example: "bass |> play fill",
+        notes: "",
+    },
+    Builtin {
+        name: "take",
+        category: Category::Array,
+        signature: "n: Int -> array: [a] -> [a]",
+        doc: "Takes the first n elements from an array.",
+        example: concat!(
+            "[1, 2, 3, 4, 5] |> take 3\n",
+            "; Result: [1, 2, 3]",
+        ),
+        notes: "",
+    },
+    Builtin {
+        name: "drop",
+        category: Category::Array,
+        signature: "n: Int -> array: [a] -> [a]",
+        doc: "Drops the first n elements from an array.",
+        example: concat!(
+            "[1, 2, 3, 4, 5] |> drop 2\n",
+            "; Result: [3, 4, 5]",
+        ),
+        notes: "",
+    },
+    Builtin {
+        name: "zip",
+        category: Category::Array,
+        signature: "first: [a] -> second: [b] -> [(a, b)]",
+        doc: "Combines two arrays into an array of tuples.",
+        example: concat!(
+            "zip [1, 2, 3] [\"a\", \"b\", \"c\"]\n",
+            "; Result: [(1, \"a\"), (2, \"b\"), (3, \"c\")]",
+        ),
+        notes: "",
+    },
+    Builtin {
+        name: "concat",
+        category: Category::Array,
+        signature: "first: [a] -> second: [a] -> [a]",
+        doc: "Concatenates two arrays.",
+        example: concat!(
+            "concat [1, 2] [3, 4]\n",
+            "; Result: [1, 2, 3, 4]",
+        ),
+        notes: "",
+    },
+    Builtin {
+        name: "len",
+        category: Category::Array,
+        signature: "array: [a] -> Int | string: String -> Int",
+        doc: "Returns the length of an array or string.",
+        example: concat!(
+            "len [1, 2, 3]\n",
+            "; Result: 3\n",
+            "\n",
+            "len \"hello\"\n",
+            "; Result: 5",
+        ),
+        notes: "",
+    },
+    Builtin {
+        name: "foldl",
+        category: Category::Array,
+        signature: "f: (a -> b -> a) -> init: a -> array: [b] -> a",
+        doc: "Left fold - accumulates from left to right.",
+        example: concat!(
+            "; foldl f z [a,b,c] = f (f (f z a) b) c\n",
+            "foldl (\\acc x -> acc + x) 0 [1, 2, 3, 4]\n",
+            "; Result: 10",
+        ),
+        notes: "",
+    },
+    Builtin {
+        name: "foldr",
+        category: Category::Array,
+        signature: "f: (a -> b -> b) -> init: b -> array: [a] -> b",
+        doc: "Right fold - accumulates from right to left.",
+        example: concat!(
+            "; foldr f z [a,b,c] = f a (f b (f c z))\n",
+            "foldr (\\x acc -> concat [x] acc) [] [1, 2, 3]\n",
+            "; Result: [1, 2, 3]",
+        ),
+        notes: "",
+    },
+    Builtin {
+        name: "map",
+        category: Category::Array,
+        signature: "f: (a -> b) -> array: [a] -> [b]",
+        doc: "Transforms each element of an array.",
+        example: concat!(
+            "map (\\x -> x * 2) [1, 2, 3]\n",
+            "; Result: [2, 4, 6]",
+        ),
+        notes: "",
+    },
+    Builtin {
+        name: "filter",
+        category: Category::Array,
+        signature: "predicate: (a -> Bool) -> array: [a] -> [a]",
+        doc: "Keeps elements matching a predicate.",
+        example: concat!(
+            "filter (\\x -> x > 2) [1, 2, 3, 4]\n",
+            "; Result: [3, 4]",
+        ),
+        notes: "",
+    },
+    Builtin {
+        name: "flat_map",
+        category: Category::Array,
+        signature: "f: (a -> [b]) -> array: [a] -> [b]",
+        doc: "Maps a function over an array and flattens the result.",
+        example: concat!(
+            "flat_map (\\x -> [x, x * 2]) [1, 2, 3]\n",
+            "; Result: [1, 2, 2, 4, 3, 6]",
+        ),
+        notes: "",
+    },
+    Builtin {
+        name: "find",
+        category: Category::Array,
+        signature: "predicate: (a -> Bool) -> array: [a] -> a",
+        doc: "Returns the first element matching a predicate, or Unit if not found.",
+        example: concat!(
+            "find (\\x -> x > 2) [1, 2, 3, 4]\n",
+            "; Result: 3",
+        ),
+        notes: "",
+    },
+    Builtin {
+        name: "any",
+        category: Category::Array,
+        signature: "predicate: (a -> Bool) -> array: [a] -> Bool",
+        doc: "Checks if any element satisfies a predicate.",
+        example: concat!(
+            "any (\\x -> x > 3) [1, 2, 3, 4]\n",
+            "; Result: true\n",
+            "\n",
+            "any (\\x -> x > 5) [1, 2, 3, 4]\n",
+            "; Result: false",
+        ),
+        notes: "",
+    },
+    Builtin {
+        name: "all",
+        category: Category::Array,
+        signature: "predicate: (a -> Bool) -> array: [a] -> Bool",
+        doc: "Checks if all elements satisfy a predicate.",
+        example: concat!(
+            "all (\\x -> x > 0) [1, 2, 3, 4]\n",
+            "; Result: true\n",
+            "\n",
+            "all (\\x -> x > 2) [1, 2, 3, 4]\n",
+            "; Result: false",
+        ),
+        notes: "",
+    },
+    Builtin {
+        name: "map_notes",
+        category: Category::Array,
+        signature: "f: (Interval -> a) -> block: Block -> Block \
+         | f: (Interval -> Duration -> a) -> block: Block -> Block \
+         | f: (Interval -> Duration -> Int -> a) -> block: Block -> Block",
+        doc: "Transforms each note of a block with a function. The function gets the note's \
+             interval, its length and its index among the block's slots, as many of them as it \
+             takes, and returns the new interval, or an `(interval, duration)` pair to change the \
+             length too. Each note of a chord is passed on its own.",
+        example: concat!(
+            "| R M3 P5 | |> map_notes (\\i -> i + P8)\n",
+            "; Result: | P8 M10 P12 |\n",
+            "\n",
+            "| R M3 P5 | |> map_notes (\\i d n -> if n == 0 then (i, d * 2) else (i, d))\n",
+            "; The first note is held twice as long",
+        ),
+        notes: "",
+    },
+    Builtin {
+        name: "filter_notes",
+        category: Category::Array,
+        signature: "predicate: (Interval -> Bool) -> block: Block -> Block \
+         | predicate: (Interval -> Duration -> Bool) -> block: Block -> Block \
+         | predicate: (Interval -> Duration -> Int -> Bool) -> block: Block -> Block",
+        doc: "Keeps the notes of a block a predicate accepts. The predicate gets the same \
+             arguments as the function of `map_notes`. Rejected notes become rests, so the rhythm \
+             is kept; a chord keeps the notes that pass.",
+        example: concat!(
+            "| R M3 P5 M3 | |> filter_notes (\\i -> not (i == M3))\n",
+            "; Result: | R - P5 - |",
+        ),
+        notes: "",
+    },
+];
@@ -0,0 +1,308 @@
+//! Metadata of relanote's builtins, scales and chords
+//!
+//! One registry of what every program can use: the type checker reads the
+//! builtins' signatures from it, the evaluator binds its implementations
+//! to the names it lists, editors complete and hover from it, and the
+//! builtin reference (`docs/reference/builtins.md`) is generated from it.
+
+mod builtins;
+
+pub use builtins::BUILTINS;
+pub use relanote_stdlib::{chord, chords, scale, scales, Definition};
+
+/// Section of the builtin reference a builtin is listed under
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Category {
+    Block,
+    Synth,
+    Oscillator,
+    Filter,
+    Lfo,
+    Effect,
+    Rhythm,
+    Harmony,
+    Text,
+    Tuning,
+    Assertion,
+    Composition,
+    Array,
+}
+
+impl Category {
+    /// Categories in the order of the reference
+    pub const ALL: [Category; 13] = [
+        Category::Block,
+        Category::Synth,
+        Category::Oscillator,
+        Category::Filter,
+        Category::Lfo,
+        Category::Effect,
+        Category::Rhythm,
+        Category::Harmony,
+        Category::Text,
+        Category::Tuning,
+        Category::Assertion,
+        Category::Composition,
+        Category::Array,
+    ];
+
+    /// Heading of the category in the reference
+    pub fn title(self) -> &'static str {
+        match self {
+            Category::Block => "Block Transformations",
+            Category::Synth => "Synth Functions",
+            Category::Oscillator => "Oscillator Constructors",
+            Category::Filter => "Filter Constructors",
+            Category::Lfo => "LFO Constructors",
+            Category::Effect => "Effect Functions",
+            Category::Rhythm => "Rhythm Functions",
+            Category::Harmony => "Harmony Functions",
+            Category::Text => "Text Functions",
+            Category::Tuning => "Tuning Functions",
+            Category::Assertion => "Assertion Functions",
+            Category::Composition => "Composition Functions",
+            Category::Array => "Array Functions",
+        }
+    }
+
+    /// Paragraph introducing the category in the reference, if any
+    pub fn intro(self) -> &'static str {
+        match self {
+            Category::Oscillator => {
+                "Oscillators generate the raw waveform for a synth. Use these when defining \
+                 custom synths."
+            }
+            Category::Filter => {
+                "Filters shape the harmonic content of a sound. Use these when defining custom \
+                 synths."
+            }
+            Category::Lfo => {
+                "LFOs (low-frequency oscillators) slowly move a synth's pitch, filter cutoff or \
+                 amplitude, for vibrato, wah and tremolo. Use these in a synth's `lfo` property; \
+                 add them together for more than one."
+            }
+            Category::Effect => {
+                "Effects go into a chain in the order they are applied; applying one again \
+                 changes its settings in place. Applied to a song, such as a `layer`, they go on \
+                 the master bus all of its parts play through."
+            }
+            Category::Assertion => {
+                "Assertions stop evaluation with an error pointing at the call when music isn't \
+                 what a program expects. They are handy as sanity checks in generative code and \
+                 inside `test` items."
+            }
+            _ => "",
+        }
+    }
+}
+
+/// A builtin function or value
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Builtin {
+    pub name: &'static str,
+    pub category: Category,
+    /// Signatures as the type checker reads them, see [`BUILTINS`]
+    pub signature: &'static str,
+    /// What the builtin does, in Markdown
+    pub doc: &'static str,
+    /// Code showing it in use
+    pub example: &'static str,
+    /// Details the reference gives after the example, e.g. value ranges
+    pub notes: &'static str,
+}
+
+impl Builtin {
+    /// Whether this is a value (`Saw`, `SoftClip`) rather than a function
+    pub fn is_value(&self) -> bool {
+        !self.signature.contains("->")
+    }
+
+    /// One line per signature, as the reference shows them:
+    /// `delay : Float -> (feedback: Float = 0.35) -> ... -> Part`
+    ///
+    /// Parameter names are left out, except for parameters with a default.
+    pub fn signatures(&self) -> Vec<String> {
+        self.signature
+            .split('|')
+            .map(|overload| {
+                let types: Vec<String> = split_arrows(overload.trim())
+                    .into_iter()
+                    .map(|param| match param.split_once(':') {
+                        Some((name, ty)) if is_param_name(name) => {
+                            if ty.contains('=') {
+                                format!("({}:{})", name, ty)
+                            } else {
+                                ty.trim().to_string()
+                            }
+                        }
+                        _ => param.to_string(),
+                    })
+                    .collect();
+                format!("{} : {}", self.name, types.join(" -> "))
+            })
+            .collect()
+    }
+
+    /// First sentence of the doc, for completion lists
+    pub fn summary(&self) -> &'static str {
+        let first = match self.doc.find(". ") {
+            Some(end) => &self.doc[..end],
+            None => self.doc,
+        };
+        first.trim_end_matches('.')
+    }
+
+    /// Hover documentation: the signatures, then the doc, example and notes
+    pub fn hover(&self) -> String {
+        let mut hover = format!(
+            "```rela\n{}\n```\n\n{}",
+            self.signatures().join("\n"),
+            self.doc
+        );
+        if !self.example.is_empty() {
+            hover.push_str(&format!("\n\n**Example:**\n```rela\n{}\n```", self.example));
+        }
+        if !self.notes.is_empty() {
+            hover.push_str(&format!("\n\n{}", self.notes));
+        }
+        hover
+    }
+}
+
+/// The builtin called `name`
+pub fn builtin(name: &str) -> Option<&'static Builtin> {
+    BUILTINS.iter().find(|builtin| builtin.name == name)
+}
+
+/// The builtin sections of the reference, a `##` heading per category and
+/// a `###` heading per builtin
+pub fn reference() -> String {
+    let mut out = String::new();
+    for category in Category::ALL {
+        out.push_str(&format!("## {}\n\n", category.title()));
+        if !category.intro().is_empty() {
+            out.push_str(&format!("{}\n\n", category.intro()));
+        }
+        for builtin in BUILTINS.iter().filter(|b| b.category == category) {
+            out.push_str(&format!("### {}\n\n{}\n\n", builtin.name, builtin.doc));
+            out.push_str(&format!("```rela\n{}\n", builtin.signatures().join("\n")));
+            if !builtin.example.is_empty() {
+                out.push_str(&format!("\n{}\n", builtin.example));
+            }
+            out.push_str("```\n\n");
+            if !builtin.notes.is_empty() {
+                out.push_str(&format!("{}\n\n", builtin.notes));
+            }
+        }
+    }
+    out
+}
+
+/// Split a signature at its top-level arrows, leaving function types in
+/// parentheses whole
+fn split_arrows(signature: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    let bytes = signature.as_bytes();
+    for (i, byte) in bytes.iter().enumerate() {
+        match byte {
+            b'(' | b'[' => depth += 1,
+            b')' | b']' => depth -= 1,
+            b'-' if depth == 0 && bytes.get(i + 1) == Some(&b'>') => {
+                parts.push(signature[start..i].trim());
+                start = i + 2;
+            }
+            _ => {}
+        }
+    }
+    parts.push(signature[start..].trim());
+    parts
+}
+
+fn is_param_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REFERENCE: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/../../docs/reference/builtins.md"
+    );
+    const BEGIN: &str = "<!-- builtins:begin -->\n";
+    const END: &str = "<!-- builtins:end -->";
+
+    #[test]
+    fn test_builtin_names_are_unique() {
+        for (i, builtin) in BUILTINS.iter().enumerate() {
+            assert!(
+                BUILTINS[..i].iter().all(|b| b.name != builtin.name),
+                "`{}` is listed twice",
+                builtin.name
+            );
+        }
+    }
+
+    #[test]
+    fn test_signatures_for_display() {
+        assert_eq!(
+            builtin("transpose").unwrap().signatures(),
+            vec!["transpose : Interval -> Block -> Block"]
+        );
+        assert_eq!(
+            builtin("delay").unwrap().signatures()[0],
+            "delay : Float -> (feedback: Float = 0.35) -> (mix: Float = 0.4) -> Block -> Part"
+        );
+        assert_eq!(
+            builtin("map").unwrap().signatures(),
+            vec!["map : (a -> b) -> [a] -> [b]"]
+        );
+        assert_eq!(
+            builtin("Saw").unwrap().signatures(),
+            vec!["Saw : Oscillator"]
+        );
+        assert!(builtin("Saw").unwrap().is_value());
+        assert_eq!(
+            builtin("modulate").unwrap().summary(),
+            "Changes key by a given interval"
+        );
+    }
+
+    #[test]
+    fn test_examples_parse() {
+        for builtin in BUILTINS {
+            let (_, diagnostics) = relanote_parser::parse(builtin.example);
+            assert!(
+                !diagnostics.has_errors(),
+                "example of `{}` doesn't parse: {:?}",
+                builtin.name,
+                diagnostics
+            );
+        }
+    }
+
+    /// The reference between its markers is generated; run with
+    /// `UPDATE_DOCS=1` to regenerate it
+    #[test]
+    fn test_reference_is_up_to_date() {
+        let docs = std::fs::read_to_string(REFERENCE).unwrap();
+        let start = docs.find(BEGIN).expect("begin marker") + BEGIN.len();
+        let end = docs.find(END).expect("end marker");
+        let generated = reference();
+        if std::env::var_os("UPDATE_DOCS").is_some() {
+            let updated = format!("{}{}{}", &docs[..start], generated, &docs[end..]);
+            std::fs::write(REFERENCE, updated).unwrap();
+            return;
+        }
+        assert!(
+            docs[start..end] == generated,
+            "docs/reference/builtins.md is out of date; run `UPDATE_DOCS=1 cargo test -p relanote_meta`"
+        );
+    }
+}
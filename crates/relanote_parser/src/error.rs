@@ -1,4 +1,4 @@
-use relanote_core::Span;
+use relanote_core::{DiagnosticCode, Span};
 use relanote_lexer::TokenKind;
 use thiserror::Error;
 
@@ -44,6 +44,18 @@ impl ParseError {
         }
     }
 
+    pub fn code(&self) -> DiagnosticCode {
+        match self {
+            ParseError::UnexpectedToken { .. } => DiagnosticCode::UnexpectedToken,
+            ParseError::UnexpectedEof { .. } => DiagnosticCode::UnexpectedEof,
+            ParseError::InvalidInterval { .. } => DiagnosticCode::InvalidInterval,
+            ParseError::InvalidScaleIndex { .. } => DiagnosticCode::InvalidScaleIndex,
+            ParseError::UnclosedDelimiter { .. } => DiagnosticCode::UnclosedDelimiter,
+            ParseError::InvalidExpression { .. } => DiagnosticCode::InvalidExpression,
+            ParseError::Custom { .. } => DiagnosticCode::InvalidSyntax,
+        }
+    }
+
     pub fn unexpected_token(expected: impl Into<String>, found: TokenKind, span: Span) -> Self {
         ParseError::UnexpectedToken {
             expected: expected.into(),
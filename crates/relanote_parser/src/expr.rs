@@ -5,8 +5,15 @@ use relanote_core::{intern, Spanned};
 use relanote_lexer::TokenKind;
 
 use crate::error::{ParseError, ParseResult};
+use crate::music::note_value;
 use crate::parser::Parser;
 
+/// An argument in a parenthesized call
+enum CallArg {
+    Positional(Spanned<Expr>),
+    Named(NamedArg),
+}
+
 impl Parser {
     /// Parse any expression
     pub fn parse_expression(&mut self) -> ParseResult<Spanned<Expr>> {
@@ -94,13 +101,13 @@ impl Parser {
 
     /// Parse equality: expr == expr
     fn parse_equality_expr(&mut self) -> ParseResult<Spanned<Expr>> {
-        let mut left = self.parse_comparison_expr()?;
+        let mut left = self.parse_overlay_expr()?;
 
         loop {
             if self.check(&TokenKind::Eq) && self.peek_next().kind == TokenKind::Eq {
                 self.advance();
                 self.advance();
-                let right = self.parse_comparison_expr()?;
+                let right = self.parse_overlay_expr()?;
                 let span = left.span.merge(right.span);
                 left = Spanned::new(
                     Expr::Binary(Binary {
@@ -118,9 +125,29 @@ impl Parser {
         Ok(left)
     }
 
+    /// Parse overlay: expr & expr
+    fn parse_overlay_expr(&mut self) -> ParseResult<Spanned<Expr>> {
+        let mut left = self.parse_comparison_expr()?;
+
+        while self.match_token(&TokenKind::Ampersand) {
+            let right = self.parse_comparison_expr()?;
+            let span = left.span.merge(right.span);
+            left = Spanned::new(
+                Expr::Binary(Binary {
+                    op: BinaryOp::Overlay,
+                    left: Box::new(left),
+                    right: Box::new(right),
+                }),
+                span,
+            );
+        }
+
+        Ok(left)
+    }
+
     /// Parse comparison: expr < expr | expr > expr
     fn parse_comparison_expr(&mut self) -> ParseResult<Spanned<Expr>> {
-        let mut left = self.parse_additive_expr()?;
+        let mut left = self.parse_range_expr()?;
 
         loop {
             let op = if self.match_token(&TokenKind::LAngle) {
@@ -140,7 +167,7 @@ impl Parser {
             };
 
             if let Some(op) = op {
-                let right = self.parse_additive_expr()?;
+                let right = self.parse_range_expr()?;
                 let span = left.span.merge(right.span);
                 left = Spanned::new(
                     Expr::Binary(Binary {
@@ -158,6 +185,43 @@ impl Parser {
         Ok(left)
     }
 
+    /// Parse an argument of Haskell-style application, where a range of
+    /// simple operands needs no parentheses: `bars 0..2 verse`
+    fn parse_argument(&mut self) -> ParseResult<Spanned<Expr>> {
+        let start = self.parse_primary_expr()?;
+        if !self.match_token(&TokenKind::DotDot) {
+            return Ok(start);
+        }
+        let end = self.parse_primary_expr()?;
+        let span = start.span.merge(end.span);
+        Ok(Spanned::new(
+            Expr::Binary(Binary {
+                op: BinaryOp::Range,
+                left: Box::new(start),
+                right: Box::new(end),
+            }),
+            span,
+        ))
+    }
+
+    /// Parse a range: expr..expr
+    fn parse_range_expr(&mut self) -> ParseResult<Spanned<Expr>> {
+        let left = self.parse_additive_expr()?;
+        if !self.match_token(&TokenKind::DotDot) {
+            return Ok(left);
+        }
+        let right = self.parse_additive_expr()?;
+        let span = left.span.merge(right.span);
+        Ok(Spanned::new(
+            Expr::Binary(Binary {
+                op: BinaryOp::Range,
+                left: Box::new(left),
+                right: Box::new(right),
+            }),
+            span,
+        ))
+    }
+
     /// Parse addition/subtraction/concatenation
     fn parse_additive_expr(&mut self) -> ParseResult<Spanned<Expr>> {
         let mut left = self.parse_multiplicative_expr()?;
@@ -195,7 +259,7 @@ impl Parser {
         Ok(left)
     }
 
-    /// Parse multiplication/division
+    /// Parse multiplication/division/modulo
     fn parse_multiplicative_expr(&mut self) -> ParseResult<Spanned<Expr>> {
         let mut left = self.parse_unary_expr()?;
 
@@ -203,6 +267,10 @@ impl Parser {
             let op = if self.match_token(&TokenKind::Staccato) {
                 // * is also used for multiplication
                 Some(BinaryOp::Mul)
+            } else if self.match_token(&TokenKind::Slash) {
+                Some(BinaryOp::Div)
+            } else if self.match_token(&TokenKind::Percent) {
+                Some(BinaryOp::Mod)
             } else {
                 None
             };
@@ -254,7 +322,27 @@ impl Parser {
             ));
         }
 
-        self.parse_postfix_expr()
+        self.parse_power_expr()
+    }
+
+    /// Parse exponentiation: expr ^ expr, binding tighter than unary minus
+    /// and grouping to the right
+    fn parse_power_expr(&mut self) -> ParseResult<Spanned<Expr>> {
+        let base = self.parse_postfix_expr()?;
+        // ^ is also the accent articulation
+        if !self.match_token(&TokenKind::Accent) {
+            return Ok(base);
+        }
+        let exponent = self.parse_unary_expr()?;
+        let span = base.span.merge(exponent.span);
+        Ok(Spanned::new(
+            Expr::Binary(Binary {
+                op: BinaryOp::Pow,
+                left: Box::new(base),
+                right: Box::new(exponent),
+            }),
+            span,
+        ))
     }
 
     /// Check if the current token can start a function argument (for Haskell-style application)
@@ -282,6 +370,11 @@ impl Parser {
                 | TokenKind::False
                 | TokenKind::Root
                 | TokenKind::Interval(_)
+                | TokenKind::Cents(_)
+                | TokenKind::Semitones(_)
+                | TokenKind::Beats(_)
+                | TokenKind::Bars(_)
+                | TokenKind::NoteValue(_)
                 | TokenKind::AbsolutePitch(_)
                 | TokenKind::Ident(_)
                 | TokenKind::LParen
@@ -291,19 +384,75 @@ impl Parser {
         )
     }
 
+    /// Parse the unit after a number that makes it a duration: `2 beats`,
+    /// `1 bar`
+    fn parse_duration_unit(&mut self, count: f64) -> Option<DurationLit> {
+        let duration = match self.current() {
+            TokenKind::Ident(unit) if unit == "beat" || unit == "beats" => {
+                DurationLit::Beats(count)
+            }
+            TokenKind::Ident(unit) if unit == "bar" || unit == "bars" => DurationLit::Bars(count),
+            _ => return None,
+        };
+        self.advance();
+        Some(duration)
+    }
+
+    /// Parse an argument in parentheses: positional, or `name: value`
+    fn parse_call_arg(&mut self) -> ParseResult<CallArg> {
+        if matches!(self.current(), TokenKind::Ident(_))
+            && self.peek_next().kind == TokenKind::Colon
+        {
+            let name_span = self.current_span();
+            let name = self.parse_ident()?;
+            self.advance();
+            let value = self.parse_expression()?;
+            return Ok(CallArg::Named(NamedArg {
+                name,
+                name_span,
+                value,
+            }));
+        }
+        self.parse_expression().map(CallArg::Positional)
+    }
+
     /// Parse postfix: expr(args) | expr[index] | expr.field | expr arg (Haskell-style)
     fn parse_postfix_expr(&mut self) -> ParseResult<Spanned<Expr>> {
         let mut expr = self.parse_primary_expr()?;
 
         loop {
             if self.match_token(&TokenKind::LParen) {
-                let args = self.parse_list(&TokenKind::RParen, |p| p.parse_expression())?;
+                let items = self.parse_list(&TokenKind::RParen, |p| p.parse_call_arg())?;
                 self.expect(&TokenKind::RParen, ")")?;
                 let span = self.span_from(expr.span);
+                let mut args = Vec::new();
+                let mut named = Vec::new();
+                for item in items {
+                    match item {
+                        CallArg::Positional(arg) => args.push(arg),
+                        CallArg::Named(arg) => named.push(arg),
+                    }
+                }
                 expr = Spanned::new(
                     Expr::Application(Application {
                         func: Box::new(expr),
                         args,
+                        named,
+                    }),
+                    span,
+                );
+            } else if self.check(&TokenKind::LBracket) && self.current_span().start == expr.span.end
+            {
+                // Index access: `a[1]`, with the bracket right after the
+                // base (`f [1]` applies `f` to an array)
+                self.advance();
+                let index = self.parse_expression()?;
+                self.expect(&TokenKind::RBracket, "]")?;
+                let span = self.span_from(expr.span);
+                expr = Spanned::new(
+                    Expr::Index(Index {
+                        base: Box::new(expr),
+                        index: Box::new(index),
                     }),
                     span,
                 );
@@ -312,7 +461,7 @@ impl Parser {
                 // Collect all adjacent arguments into a single Application
                 let mut args = Vec::new();
                 while self.can_start_argument() {
-                    args.push(self.parse_primary_expr()?);
+                    args.push(self.parse_argument()?);
                 }
                 let span = if let Some(last) = args.last() {
                     expr.span.merge(last.span)
@@ -323,17 +472,7 @@ impl Parser {
                     Expr::Application(Application {
                         func: Box::new(expr),
                         args,
-                    }),
-                    span,
-                );
-            } else if self.match_token(&TokenKind::LBracket) {
-                let index = self.parse_expression()?;
-                self.expect(&TokenKind::RBracket, "]")?;
-                let span = self.span_from(expr.span);
-                expr = Spanned::new(
-                    Expr::Index(Index {
-                        base: Box::new(expr),
-                        index: Box::new(index),
+                        named: Vec::new(),
                     }),
                     span,
                 );
@@ -347,8 +486,9 @@ impl Parser {
                     }),
                     span,
                 );
-            } else if self.match_ident("with") {
+            } else if self.check(&TokenKind::With) && self.peek_next().kind == TokenKind::LBrace {
                 // With expression: expr with { modifications }
+                self.advance();
                 self.expect(&TokenKind::LBrace, "{")?;
                 self.skip_comments_and_newlines();
                 let modifications =
@@ -378,11 +518,45 @@ impl Parser {
             // Literals
             TokenKind::Integer(n) => {
                 self.advance();
+                if let Some(duration) = self.parse_duration_unit(n as f64) {
+                    return Ok(Spanned::new(
+                        Expr::Duration(duration),
+                        self.span_from(start),
+                    ));
+                }
                 Ok(Spanned::new(Expr::Integer(n), start))
             }
 
+            TokenKind::Beats(n) => {
+                self.advance();
+                Ok(Spanned::new(
+                    Expr::Duration(DurationLit::Beats(n as f64)),
+                    start,
+                ))
+            }
+
+            TokenKind::Bars(n) => {
+                self.advance();
+                Ok(Spanned::new(
+                    Expr::Duration(DurationLit::Bars(n as f64)),
+                    start,
+                ))
+            }
+
+            TokenKind::NoteValue(value) => {
+                self.advance();
+                let duration = note_value(value.num, value.den, value.dotted, start)?;
+                Ok(Spanned::new(Expr::Duration(duration), start))
+            }
+
             TokenKind::Float(n) => {
                 self.advance();
+                if let Some(duration) = self.parse_duration_unit(n) {
+                    return Ok(Spanned::new(
+                        Expr::Duration(duration),
+                        self.span_from(start),
+                    ));
+                }
                 Ok(Spanned::new(Expr::Float(n), start))
             }
 
@@ -410,11 +584,21 @@ impl Parser {
             // Interval
             TokenKind::Interval(data) => {
                 self.advance();
-                let interval = IntervalLit {
-                    quality: data.quality,
-                    degree: data.degree,
-                    accidentals: data.accidentals,
-                };
+                let interval = IntervalLit::from(data);
+                Ok(Spanned::new(Expr::Interval(interval), start))
+            }
+
+            // Interval in cents (386c)
+            TokenKind::Cents(cents) => {
+                self.advance();
+                let interval = IntervalLit::from_cents(cents);
+                Ok(Spanned::new(Expr::Interval(interval), start))
+            }
+
+            // Interval in semitones (12st)
+            TokenKind::Semitones(steps) => {
+                self.advance();
+                let interval = IntervalLit::from_semitones(steps);
                 Ok(Spanned::new(Expr::Interval(interval), start))
             }
 
@@ -460,6 +644,14 @@ impl Parser {
 
                 let first = self.parse_expression()?;
 
+                // Annotated expression: (e : Block)
+                if self.match_token(&TokenKind::Colon) {
+                    let ty = self.parse_type()?;
+                    self.expect(&TokenKind::RParen, ")")?;
+                    let span = self.span_from(start);
+                    return Ok(Spanned::new(Expr::Annotated(Box::new(first), ty), span));
+                }
+
                 if self.match_token(&TokenKind::Comma) {
                     let mut elements = vec![first];
                     if !self.check(&TokenKind::RParen) {
@@ -478,39 +670,20 @@ impl Parser {
 
             // Scale index <n>
             TokenKind::LAngle => {
-                self.advance();
-                if let TokenKind::Integer(n) = self.current().clone() {
-                    self.advance();
-                    let mut accidentals = Vec::new();
-                    while self.match_token(&TokenKind::Plus) {
-                        accidentals.push(relanote_lexer::token::Accidental::Sharp);
-                    }
-                    while self.match_token(&TokenKind::Minus) {
-                        accidentals.push(relanote_lexer::token::Accidental::Flat);
-                    }
-                    self.expect(&TokenKind::RAngle, ">")?;
-                    let span = self.span_from(start);
+                let pitch = self.parse_scale_index()?;
+                let span = self.span_from(start);
 
-                    let pitch = if accidentals.is_empty() {
-                        Pitch::ScaleIndex(n as u8)
-                    } else {
-                        Pitch::ScaleIndexMod(n as u8, accidentals)
-                    };
-
-                    Ok(Spanned::new(
-                        Expr::Block(Block::new(vec![Spanned::new(
-                            Slot::Note {
-                                pitch: Spanned::new(pitch, span),
-                                articulations: vec![],
-                                duration: None,
-                            },
-                            span,
-                        )])),
+                Ok(Spanned::new(
+                    Expr::Block(Block::new(vec![Spanned::new(
+                        Slot::Note {
+                            pitch: Spanned::new(pitch, span),
+                            articulations: vec![],
+                            duration: None,
+                        },
                         span,
-                    ))
-                } else {
-                    Err(ParseError::custom("expected integer in scale index", start))
-                }
+                    )])),
+                    span,
+                ))
             }
 
             // Section
@@ -518,6 +691,7 @@ impl Parser {
 
             // Layer
             TokenKind::Layer => self.parse_layer(),
+            TokenKind::Kit => self.parse_kit(),
 
             // Part
             TokenKind::Part => self.parse_part(),
@@ -525,20 +699,15 @@ impl Parser {
             // Env
             TokenKind::Env => self.parse_envelope(),
 
-            // Render - treat as function identifier
-            TokenKind::Render => {
-                self.advance();
-                Ok(Spanned::new(
-                    Expr::Ident(Ident::new(intern("render"))),
-                    start,
-                ))
-            }
+            // Context
+            TokenKind::Context => self.parse_context(),
 
-            // Context - treat as function identifier
-            TokenKind::Context => {
+            // Filter - a keyword only inside synth definitions, the
+            // builtin everywhere else
+            TokenKind::Filter => {
                 self.advance();
                 Ok(Spanned::new(
-                    Expr::Ident(Ident::new(intern("Context"))),
+                    Expr::Ident(Ident::new(intern("filter"))),
                     start,
                 ))
             }
@@ -674,6 +843,11 @@ impl Parser {
         self.expect(&TokenKind::Let, "let")?;
 
         let pattern = self.parse_pattern()?;
+        let type_ann = if self.match_token(&TokenKind::Colon) {
+            Some(self.parse_type()?)
+        } else {
+            None
+        };
         self.expect(&TokenKind::Eq, "=")?;
         let value = self.parse_expression()?;
 
@@ -683,7 +857,7 @@ impl Parser {
             Ok(Spanned::new(
                 Expr::Let(Box::new(LetExpr {
                     pattern,
-                    type_ann: None,
+                    type_ann,
                     value,
                     body,
                 })),
@@ -752,6 +926,13 @@ impl Parser {
                 }
 
                 let first = self.parse_pattern()?;
+                // Annotated pattern: (x: Block)
+                if self.match_token(&TokenKind::Colon) {
+                    let ty = self.parse_type()?;
+                    self.expect(&TokenKind::RParen, ")")?;
+                    let span = self.span_from(start);
+                    return Ok(Spanned::new(Pattern::Annotated(Box::new(first), ty), span));
+                }
                 if self.match_token(&TokenKind::Comma) {
                     let mut patterns = vec![first];
                     if !self.check(&TokenKind::RParen) {
@@ -770,4 +951,58 @@ impl Parser {
             _ => Err(ParseError::custom("expected pattern", start)),
         }
     }
+
+    /// Parse a type: Block, [Int], (Int, Float), Interval -> Block -> Block
+    ///
+    /// Lowercase names are type variables.
+    pub fn parse_type(&mut self) -> ParseResult<TypeAnnotation> {
+        let param = self.parse_type_atom()?;
+        if self.match_token(&TokenKind::Arrow) {
+            let ret = self.parse_type()?;
+            Ok(TypeAnnotation::Function(Box::new(param), Box::new(ret)))
+        } else {
+            Ok(param)
+        }
+    }
+
+    fn parse_type_atom(&mut self) -> ParseResult<TypeAnnotation> {
+        let start = self.current_span();
+        match self.current().clone() {
+            TokenKind::Ident(name) => {
+                self.advance();
+                let ident = Ident::new(intern(&name));
+                if name.starts_with(|c: char| c.is_ascii_lowercase()) {
+                    Ok(TypeAnnotation::Var(ident))
+                } else {
+                    Ok(TypeAnnotation::Named(ident))
+                }
+            }
+
+            TokenKind::LBracket => {
+                self.advance();
+                let elem = self.parse_type()?;
+                self.expect(&TokenKind::RBracket, "]")?;
+                Ok(TypeAnnotation::Array(Box::new(elem)))
+            }
+
+            TokenKind::LParen => {
+                self.advance();
+                if self.match_token(&TokenKind::RParen) {
+                    return Ok(TypeAnnotation::Unit);
+                }
+                let mut elems = vec![self.parse_type()?];
+                while self.match_token(&TokenKind::Comma) {
+                    elems.push(self.parse_type()?);
+                }
+                self.expect(&TokenKind::RParen, ")")?;
+                if elems.len() == 1 {
+                    Ok(elems.pop().unwrap())
+                } else {
+                    Ok(TypeAnnotation::Tuple(elems))
+                }
+            }
+
+            _ => Err(ParseError::custom("expected type", start)),
+        }
+    }
 }
@@ -18,6 +18,9 @@ impl Parser {
             TokenKind::Synth => self.parse_synth_def(),
             TokenKind::Let => self.parse_let_binding(),
             TokenKind::Set => self.parse_set_binding(),
+            TokenKind::Meta => self.parse_meta_decl(),
+            TokenKind::Test => self.parse_test_decl(),
+            TokenKind::Render => self.parse_render_decl(),
             TokenKind::Import => self.parse_import(),
             TokenKind::Export => self.parse_export(),
             TokenKind::Mod => self.parse_mod(),
@@ -38,20 +41,17 @@ impl Parser {
         let name = self.parse_ident()?;
         self.expect(&TokenKind::Eq, "=")?;
 
-        // Check if this is a modification
-        if self.check(&TokenKind::Ident("".to_string())) && !self.check(&TokenKind::LBrace) {
+        // Derived from another scale: `Major with { P4+ }`
+        if self.check(&TokenKind::Ident("".to_string())) {
             let base = self.parse_expression()?;
-            self.expect(&TokenKind::With, "with")?;
-            self.expect(&TokenKind::LBrace, "{")?;
-            let intervals = self.parse_interval_list()?;
-            self.expect(&TokenKind::RBrace, "}")?;
             let span = self.span_from(start);
 
             Ok(Spanned::new(
                 Item::ScaleDef(ScaleDef {
                     name,
                     base: Some(base),
-                    intervals,
+                    intervals: Vec::new(),
+                    doc: None,
                 }),
                 span,
             ))
@@ -66,6 +66,7 @@ impl Parser {
                     name,
                     base: None,
                     intervals,
+                    doc: None,
                 }),
                 span,
             ))
@@ -87,7 +88,11 @@ impl Parser {
         let span = self.span_from(start);
 
         Ok(Spanned::new(
-            Item::ChordDef(ChordDef { name, intervals }),
+            Item::ChordDef(ChordDef {
+                name,
+                intervals,
+                doc: None,
+            }),
             span,
         ))
     }
@@ -111,7 +116,7 @@ impl Parser {
         while !self.check(&TokenKind::RBrace) && !self.is_at_end() {
             let prop_start = self.current_span();
 
-            // Parse property name (osc, env, filter, detune)
+            // Parse property name (osc, env, filter, detune, pitch_env, lfo)
             let prop_name = match self.current().clone() {
                 TokenKind::Osc => {
                     self.advance();
@@ -133,9 +138,13 @@ impl Parser {
                     self.advance();
                     "pitch_env"
                 }
+                TokenKind::Ident(ref s) if s == "lfo" => {
+                    self.advance();
+                    "lfo"
+                }
                 _ => {
                     return Err(ParseError::unexpected_token(
-                        "synth property (osc, env, filter, detune, pitch_env)",
+                        "synth property (osc, env, filter, detune, pitch_env, lfo)",
                         self.current().clone(),
                         self.current_span(),
                     ))
@@ -154,6 +163,7 @@ impl Parser {
                 "filter" => SynthProperty::Filter(value),
                 "detune" => SynthProperty::Detune(value),
                 "pitch_env" => SynthProperty::PitchEnvelope(value),
+                "lfo" => SynthProperty::Lfo(value),
                 _ => unreachable!(),
             };
 
@@ -169,7 +179,11 @@ impl Parser {
         let span = self.span_from(start);
 
         Ok(Spanned::new(
-            Item::SynthDef(SynthDef { name, properties }),
+            Item::SynthDef(SynthDef {
+                name,
+                properties,
+                doc: None,
+            }),
             span,
         ))
     }
@@ -182,10 +196,18 @@ impl Parser {
         let first_pattern = self.parse_pattern()?;
 
         let mut params = Vec::new();
-        while !self.check(&TokenKind::Eq) && !self.is_at_end() {
+        while !self.check(&TokenKind::Eq) && !self.check(&TokenKind::Colon) && !self.is_at_end() {
             params.push(self.parse_pattern()?);
         }
 
+        // `let x: Block = ...`, or the result type of a function:
+        // `let f (x: Block): Block = ...`
+        let type_ann = if self.match_token(&TokenKind::Colon) {
+            Some(self.parse_type()?)
+        } else {
+            None
+        };
+
         self.expect(&TokenKind::Eq, "=")?;
         let value = self.parse_expression()?;
 
@@ -199,7 +221,7 @@ impl Parser {
                     Item::ExprStmt(Spanned::new(
                         Expr::Let(Box::new(LetExpr {
                             pattern: first_pattern,
-                            type_ann: None,
+                            type_ann,
                             value,
                             body,
                         })),
@@ -218,6 +240,13 @@ impl Parser {
                         ))
                     }
                 };
+                let value = match type_ann {
+                    Some(ty) => {
+                        let span = value.span;
+                        Spanned::new(Expr::Annotated(Box::new(value), ty), span)
+                    }
+                    None => value,
+                };
                 let lambda = self.build_lambda(&params, value);
                 Ok(Spanned::new(
                     Item::ExprStmt(Spanned::new(
@@ -239,8 +268,9 @@ impl Parser {
                 Ok(Spanned::new(
                     Item::LetBinding(LetBinding {
                         pattern: first_pattern,
-                        type_ann: None,
+                        type_ann,
                         value,
+                        doc: None,
                     }),
                     span,
                 ))
@@ -259,8 +289,9 @@ impl Parser {
                     Item::FunctionDef(FunctionDef {
                         name,
                         params,
-                        return_type: None,
+                        return_type: type_ann,
                         body: value,
+                        doc: None,
                     }),
                     span,
                 ))
@@ -268,6 +299,82 @@ impl Parser {
         }
     }
 
+    /// Parse song metadata: meta { title: "Etude", composer: "..." }
+    fn parse_meta_decl(&mut self) -> ParseResult<Spanned<Item>> {
+        let start = self.current_span();
+        self.expect(&TokenKind::Meta, "meta")?;
+        self.expect(&TokenKind::LBrace, "{")?;
+        self.skip_comments_and_newlines();
+
+        let mut fields = Vec::new();
+        while !self.check(&TokenKind::RBrace) && !self.is_at_end() {
+            let field_start = self.current_span();
+            let name = self.parse_ident()?;
+            self.expect(&TokenKind::Colon, ":")?;
+            let value = self.parse_expression()?;
+            fields.push(Spanned::new(
+                MetaField { name, value },
+                self.span_from(field_start),
+            ));
+
+            // Optional comma between fields
+            self.match_token(&TokenKind::Comma);
+            self.skip_comments_and_newlines();
+        }
+
+        self.expect(&TokenKind::RBrace, "}")?;
+        let span = self.span_from(start);
+        Ok(Spanned::new(Item::Meta(MetaDecl { fields }), span))
+    }
+
+    /// Parse a test: test "name" { body }
+    fn parse_test_decl(&mut self) -> ParseResult<Spanned<Item>> {
+        let start = self.current_span();
+        self.expect(&TokenKind::Test, "test")?;
+        let name = match self.current().clone() {
+            TokenKind::String(name) => {
+                self.advance();
+                name
+            }
+            _ => {
+                return Err(ParseError::unexpected_token(
+                    "test name string",
+                    self.current().clone(),
+                    self.current_span(),
+                ))
+            }
+        };
+        self.expect(&TokenKind::LBrace, "{")?;
+        self.skip_comments_and_newlines();
+        let body = self.parse_expression()?;
+        self.skip_comments_and_newlines();
+        self.expect(&TokenKind::RBrace, "}")?;
+
+        let span = self.span_from(start);
+        Ok(Spanned::new(Item::Test(TestDecl { name, body }), span))
+    }
+
+    /// Parse a render item: render song, or render "name" = song
+    fn parse_render_decl(&mut self) -> ParseResult<Spanned<Item>> {
+        let start = self.current_span();
+        self.expect(&TokenKind::Render, "render")?;
+        let name = match self.current().clone() {
+            TokenKind::String(name) if self.peek_next().kind == TokenKind::Eq => {
+                self.advance();
+                self.advance();
+                Some(name)
+            }
+            _ => None,
+        };
+        let target = self.parse_expression()?;
+
+        let span = self.span_from(start);
+        Ok(Spanned::new(
+            Item::Render(RenderDecl { name, target }),
+            span,
+        ))
+    }
+
     /// Parse set binding for built-in configuration variables
     fn parse_set_binding(&mut self) -> ParseResult<Spanned<Item>> {
         let start = self.current_span();
@@ -275,15 +382,65 @@ impl Parser {
 
         let name = self.parse_ident()?;
         self.expect(&TokenKind::Eq, "=")?;
-        let value = self.parse_expression()?;
+
+        // `set key = D minor`: a tonic, with an optional octave, then a mode
+        let tonic = if name.name.as_str() == "key" {
+            self.parse_key_tonic()
+        } else {
+            None
+        };
+        let (value, mode) = match tonic {
+            Some(tonic) => (tonic, self.parse_key_mode()),
+            None => (self.parse_expression()?, None),
+        };
 
         let span = self.span_from(start);
         Ok(Spanned::new(
-            Item::SetBinding(SetBinding { name, value }),
+            Item::SetBinding(SetBinding { name, value, mode }),
             span,
         ))
     }
 
+    /// Parse a key's tonic: an absolute pitch, or a note name in octave 4
+    pub(crate) fn parse_key_tonic(&mut self) -> Option<Spanned<Expr>> {
+        let pitch = match self.current() {
+            TokenKind::AbsolutePitch(data) => AbsolutePitchLit::from(data.clone()),
+            TokenKind::SharpNote(note) => AbsolutePitchLit::new(*note, 1, 4),
+            // A natural A with an octave lexes as an augmented interval (A4)
+            TokenKind::Interval(data)
+                if data.quality == relanote_lexer::token::IntervalQuality::Augmented
+                    && data.accidentals.is_empty()
+                    && data.degree <= 9 =>
+            {
+                AbsolutePitchLit::new('A', 0, data.degree)
+            }
+            TokenKind::Ident(name) => {
+                let mut chars = name.chars();
+                let note = chars.next().filter(|c| matches!(c, 'A'..='G'))?;
+                let accidental = match chars.as_str() {
+                    "" => 0,
+                    "b" => -1,
+                    _ => return None,
+                };
+                AbsolutePitchLit::new(note, accidental, 4)
+            }
+            _ => return None,
+        };
+        let span = self.current_span();
+        self.advance();
+        Some(Spanned::new(Expr::AbsolutePitch(pitch), span))
+    }
+
+    /// Parse the mode after a key's tonic, if there is one
+    fn parse_key_mode(&mut self) -> Option<Ident> {
+        match self.current() {
+            TokenKind::Ident(mode) if SetBinding::mode_scale(mode).is_some() => {
+                self.parse_ident().ok()
+            }
+            _ => None,
+        }
+    }
+
     /// Build a lambda expression from parameters and body
     fn build_lambda(&self, params: &[Spanned<Pattern>], body: Spanned<Expr>) -> Spanned<Expr> {
         if params.is_empty() {
@@ -511,14 +668,15 @@ impl Parser {
                 }
                 TokenKind::Interval(data) => {
                     self.advance();
-                    intervals.push(Spanned::new(
-                        IntervalLit {
-                            quality: data.quality,
-                            degree: data.degree,
-                            accidentals: data.accidentals,
-                        },
-                        start,
-                    ));
+                    intervals.push(Spanned::new(IntervalLit::from(data), start));
+                }
+                TokenKind::Cents(cents) => {
+                    self.advance();
+                    intervals.push(Spanned::new(IntervalLit::from_cents(cents), start));
+                }
+                TokenKind::Semitones(steps) => {
+                    self.advance();
+                    intervals.push(Spanned::new(IntervalLit::from_semitones(steps), start));
                 }
                 _ => break,
             }
@@ -31,3 +31,12 @@ pub fn parse_string(name: &str, content: &str) -> (Program, Diagnostics) {
     let source = Source::from_string(name, content.to_string());
     parse_source(&source)
 }
+
+/// Parse arbitrary bytes, as a fuzzer or a pasted playground buffer
+/// might supply them
+///
+/// Invalid UTF-8 is replaced rather than rejected, so every input reaches
+/// the lexer and parser.
+pub fn parse_arbitrary(data: &[u8]) -> (Program, Diagnostics) {
+    parse_string("<arbitrary>", &String::from_utf8_lossy(data))
+}
@@ -1,7 +1,7 @@
 //! Music-specific parsing
 
 use relanote_ast::*;
-use relanote_core::Spanned;
+use relanote_core::{intern, Span, Spanned};
 use relanote_lexer::TokenKind;
 
 use crate::error::{ParseError, ParseResult};
@@ -18,13 +18,28 @@ impl Parser {
         // Skip initial newlines/comments inside block
         self.skip_comments_and_newlines();
 
-        while !self.check(&TokenKind::Pipe) && !self.is_at_end() {
-            slots.push(self.parse_slot()?);
+        while !self.check(&TokenKind::Pipe) && !self.is_at_end() && !self.at_next_item() {
+            match self.parse_slot() {
+                Ok(slot) => slots.push(slot),
+                Err(err) => {
+                    // Keep the slots parsed so far and resume at the bar delimiter
+                    self.add_error(err);
+                    self.synchronize_slot();
+                }
+            }
             // Skip newlines/comments between slots
             self.skip_comments_and_newlines();
         }
 
-        self.expect(&TokenKind::Pipe, "|")?;
+        if !self.match_token(&TokenKind::Pipe) {
+            // The block ran into the next item or the end of the file;
+            // point at where its closing `|` belongs
+            let end = slots.last().map_or(start.end, |slot| slot.span.end);
+            return Err(ParseError::UnclosedDelimiter {
+                expected: '|',
+                span: Span::new(start.source, end, end),
+            });
+        }
 
         // Check for optional duration: |...|:n
         let block = if self.check(&TokenKind::Colon) {
@@ -50,50 +65,150 @@ impl Parser {
             Block::new(slots)
         };
 
+        // Hits only mean something once a kit plays them, so a block is
+        // either notes or hits
+        if let (Some(hit), true) = (first_hit(&block.slots), has_pitch(&block.slots)) {
+            return Err(ParseError::custom(
+                "a block can't mix drum hits with notes",
+                hit,
+            ));
+        }
+
         let span = self.span_from(start);
         Ok(Spanned::new(Expr::Block(block), span))
     }
 
-    /// Parse a single slot
+    /// Parse a note slot whose pitch is the current token
+    fn parse_note_slot(&mut self, pitch: Pitch) -> ParseResult<Spanned<Slot>> {
+        let start = self.current_span();
+        self.advance();
+        let articulations = self.parse_articulations();
+        let duration = self.parse_slot_duration()?;
+        let span = self.span_from(start);
+        Ok(Spanned::new(
+            Slot::Note {
+                pitch: Spanned::new(pitch, span),
+                articulations,
+                duration,
+            },
+            span,
+        ))
+    }
+
+    /// Parse a scale index: `<3>`, with accidentals (`<4+>`, `<7->`) and
+    /// octave marks (`<1'>` an octave up, `<5,>` an octave down)
+    pub fn parse_scale_index(&mut self) -> ParseResult<Pitch> {
+        let start = self.current_span();
+        self.expect(&TokenKind::LAngle, "<")?;
+        let TokenKind::Integer(n) = self.current().clone() else {
+            return Err(ParseError::custom("expected integer in scale index", start));
+        };
+        self.advance();
+
+        let mut accidentals = Vec::new();
+        while self.match_token(&TokenKind::Plus) {
+            accidentals.push(relanote_lexer::token::Accidental::Sharp);
+        }
+        while self.match_token(&TokenKind::Minus) {
+            accidentals.push(relanote_lexer::token::Accidental::Flat);
+        }
+        let mut octave: i8 = 0;
+        let too_many = || ParseError::custom("too many octave marks in scale index", start);
+        while self.match_token(&TokenKind::Apostrophe) {
+            octave = octave.checked_add(1).ok_or_else(too_many)?;
+        }
+        while self.match_token(&TokenKind::Comma) {
+            octave = octave.checked_sub(1).ok_or_else(too_many)?;
+        }
+        self.expect(&TokenKind::RAngle, ">")?;
+
+        Ok(if accidentals.is_empty() && octave == 0 {
+            Pitch::ScaleIndex(n as u8)
+        } else {
+            Pitch::ScaleIndexMod(n as u8, accidentals, octave)
+        })
+    }
+
+    /// Parse a single slot, with its ties
+    ///
+    /// A `~` before a note or chord continues the previous note (`~R:2`),
+    /// and a `~` after its duration holds it into the next one (`R:4~`).
     pub fn parse_slot(&mut self) -> ParseResult<Spanned<Slot>> {
         let start = self.current_span();
+        let tied = self.match_token(&TokenKind::Portamento);
+        let mut slot = self.parse_untied_slot()?;
+
+        if let Slot::Note {
+            articulations,
+            duration,
+            ..
+        }
+        | Slot::Chord {
+            articulations,
+            duration,
+            ..
+        } = &mut slot.node
+        {
+            if tied {
+                articulations.insert(0, Articulation::Tied);
+            }
+            if duration.is_some() && self.match_token(&TokenKind::Portamento) {
+                articulations.push(Articulation::Tie);
+            }
+        } else if tied {
+            return Err(ParseError::custom(
+                "only notes and chords can be tied",
+                start,
+            ));
+        }
+
+        slot.span = self.span_from(start);
+        Ok(slot)
+    }
+
+    fn parse_untied_slot(&mut self) -> ParseResult<Spanned<Slot>> {
+        let start = self.current_span();
 
         match self.current().clone() {
             TokenKind::Minus => {
                 self.advance();
-                let duration = self.parse_slot_duration();
+                let duration = self.parse_slot_duration()?;
                 let span = self.span_from(start);
                 Ok(Spanned::new(Slot::Rest { duration }, span))
             }
 
-            TokenKind::Root => {
+            TokenKind::Root => self.parse_note_slot(Pitch::Root),
+
+            // Drum hits of percussion blocks: | x - o - |
+            TokenKind::Ident(name) if is_hit_name(&name) => {
                 self.advance();
                 let articulations = self.parse_articulations();
-                let duration = self.parse_slot_duration();
-                let span = self.span_from(start);
+                let duration = self.parse_slot_duration()?;
                 Ok(Spanned::new(
-                    Slot::Note {
-                        pitch: Spanned::new(Pitch::Root, span),
+                    Slot::Hit {
+                        name: Ident::new(intern(&name)),
                         articulations,
                         duration,
                     },
-                    span,
+                    self.span_from(start),
                 ))
             }
+            TokenKind::Interval(data) => self.parse_note_slot(Pitch::Interval(data.into())),
+            TokenKind::Cents(cents) => {
+                self.parse_note_slot(Pitch::Interval(IntervalLit::from_cents(cents)))
+            }
+            TokenKind::Semitones(steps) => {
+                self.parse_note_slot(Pitch::Interval(IntervalLit::from_semitones(steps)))
+            }
 
-            TokenKind::Interval(data) => {
-                self.advance();
+            TokenKind::LAngle => {
+                let pitch = self.parse_scale_index()?;
                 let articulations = self.parse_articulations();
-                let duration = self.parse_slot_duration();
+                let duration = self.parse_slot_duration()?;
                 let span = self.span_from(start);
-                let interval = IntervalLit {
-                    quality: data.quality,
-                    degree: data.degree,
-                    accidentals: data.accidentals,
-                };
                 Ok(Spanned::new(
                     Slot::Note {
-                        pitch: Spanned::new(Pitch::Interval(interval), span),
+                        pitch: Spanned::new(pitch, span),
                         articulations,
                         duration,
                     },
@@ -101,43 +216,6 @@ impl Parser {
                 ))
             }
 
-            TokenKind::LAngle => {
-                self.advance();
-                if let TokenKind::Integer(n) = self.current().clone() {
-                    self.advance();
-
-                    let mut accidentals = Vec::new();
-                    while self.match_token(&TokenKind::Plus) {
-                        accidentals.push(relanote_lexer::token::Accidental::Sharp);
-                    }
-                    while self.match_token(&TokenKind::Minus) {
-                        accidentals.push(relanote_lexer::token::Accidental::Flat);
-                    }
-
-                    self.expect(&TokenKind::RAngle, ">")?;
-                    let articulations = self.parse_articulations();
-                    let duration = self.parse_slot_duration();
-                    let span = self.span_from(start);
-
-                    let pitch = if accidentals.is_empty() {
-                        Pitch::ScaleIndex(n as u8)
-                    } else {
-                        Pitch::ScaleIndexMod(n as u8, accidentals)
-                    };
-
-                    Ok(Spanned::new(
-                        Slot::Note {
-                            pitch: Spanned::new(pitch, span),
-                            articulations,
-                            duration,
-                        },
-                        span,
-                    ))
-                } else {
-                    Err(ParseError::custom("expected integer in scale index", start))
-                }
-            }
-
             TokenKind::LBrace => {
                 self.advance();
                 let mut contents = Vec::new();
@@ -147,17 +225,14 @@ impl Parser {
                 }
 
                 self.expect(&TokenKind::RBrace, "}")?;
-                self.expect(&TokenKind::Colon, ":")?;
-
-                // Parse target_beats as primary expression only to avoid
-                // consuming subsequent slots as part of a comparison expression
-                let target_beats = self.parse_primary_expr()?;
+                let (target_beats, in_time_of) = self.parse_tuplet_length()?;
                 let span = self.span_from(start);
 
                 Ok(Spanned::new(
                     Slot::Tuplet(Tuplet {
                         contents,
-                        target_beats: Box::new(target_beats),
+                        target_beats,
+                        in_time_of,
                     }),
                     span,
                 ))
@@ -176,11 +251,15 @@ impl Parser {
                         }
                         TokenKind::Interval(data) => {
                             self.advance();
-                            Pitch::Interval(IntervalLit {
-                                quality: data.quality,
-                                degree: data.degree,
-                                accidentals: data.accidentals,
-                            })
+                            Pitch::Interval(IntervalLit::from(data))
+                        }
+                        TokenKind::Cents(cents) => {
+                            self.advance();
+                            Pitch::Interval(IntervalLit::from_cents(cents))
+                        }
+                        TokenKind::Semitones(steps) => {
+                            self.advance();
+                            Pitch::Interval(IntervalLit::from_semitones(steps))
                         }
                         _ => {
                             return Err(ParseError::custom("expected pitch in chord", pitch_start))
@@ -195,7 +274,7 @@ impl Parser {
 
                 self.expect(&TokenKind::RBracket, "]")?;
                 let articulations = self.parse_articulations();
-                let duration = self.parse_slot_duration();
+                let duration = self.parse_slot_duration()?;
                 let span = self.span_from(start);
 
                 Ok(Spanned::new(
@@ -212,16 +291,60 @@ impl Parser {
         }
     }
 
-    /// Parse optional slot duration (:n)
-    fn parse_slot_duration(&mut self) -> Option<u32> {
+    /// Parse optional slot duration: beats (`:2`), a note value (`:1/8`,
+    /// dotted `:1/4.`) or bars (`:1bar`)
+    fn parse_slot_duration(&mut self) -> ParseResult<Option<DurationLit>> {
         if self.check(&TokenKind::Colon) {
             self.advance(); // consume ':'
-            if let TokenKind::Integer(n) = self.current().clone() {
-                self.advance();
-                return Some(n as u32);
-            }
+            let duration = match self.current().clone() {
+                TokenKind::Integer(n) => {
+                    let start = self.current_span();
+                    self.advance();
+                    let duration = self.parse_spaced_note_value(n, start)?;
+                    return Ok(Some(duration.unwrap_or(DurationLit::Beats(n as f64))));
+                }
+                TokenKind::Float(f) => DurationLit::Beats(f),
+                TokenKind::Beats(n) => DurationLit::Beats(n as f64),
+                TokenKind::Bars(n) => DurationLit::Bars(n as f64),
+                TokenKind::NoteValue(value) => {
+                    note_value(value.num, value.den, value.dotted, self.current_span())?
+                }
+                _ => return Ok(None),
+            };
+            self.advance();
+            return Ok(Some(duration));
         }
-        None
+        Ok(None)
+    }
+
+    /// Parse the rest of a slot duration written with spaces, `:1 / 8`,
+    /// after its numerator, so that it means the same as `:1/8`
+    ///
+    /// Only slot durations read it: in an expression a spaced `/` divides.
+    fn parse_spaced_note_value(
+        &mut self,
+        num: i64,
+        start: Span,
+    ) -> ParseResult<Option<DurationLit>> {
+        let TokenKind::Integer(den) = self.peek_next().kind else {
+            return Ok(None);
+        };
+        let (Ok(num), Ok(den)) = (u32::try_from(num), u32::try_from(den)) else {
+            return Ok(None);
+        };
+        let den_end = self.peek_next().span.end;
+        // A `.` right after the denominator dots the note value
+        let dot = self.peek_nth(2);
+        let dotted = dot.kind == TokenKind::Dot && dot.span.start == den_end;
+        if !self.check(&TokenKind::Slash) {
+            return Ok(None);
+        }
+        self.advance();
+        self.advance();
+        if dotted {
+            self.advance();
+        }
+        note_value(num, den, dotted, self.span_from(start)).map(Some)
     }
 
     /// Parse tuplet as expression
@@ -235,21 +358,36 @@ impl Parser {
         }
 
         self.expect(&TokenKind::RBrace, "}")?;
-        self.expect(&TokenKind::Colon, ":")?;
-        // Parse target_beats as primary expression only to avoid
-        // consuming subsequent slots as part of a comparison expression
-        let target_beats = self.parse_primary_expr()?;
+        let (target_beats, in_time_of) = self.parse_tuplet_length()?;
         let span = self.span_from(start);
 
         Ok(Spanned::new(
             Expr::Tuplet(Tuplet {
                 contents,
-                target_beats: Box::new(target_beats),
+                target_beats,
+                in_time_of,
             }),
             span,
         ))
     }
 
+    /// Parse a tuplet's length after its `}`: `:n` or a ratio `:n:m`
+    #[allow(clippy::type_complexity)]
+    fn parse_tuplet_length(
+        &mut self,
+    ) -> ParseResult<(Box<Spanned<Expr>>, Option<Box<Spanned<Expr>>>)> {
+        self.expect(&TokenKind::Colon, ":")?;
+        // Parse the length as a primary expression only to avoid
+        // consuming subsequent slots as part of a comparison expression
+        let target_beats = self.parse_primary_expr()?;
+        let in_time_of = if self.match_token(&TokenKind::Colon) {
+            Some(Box::new(self.parse_primary_expr()?))
+        } else {
+            None
+        };
+        Ok((Box::new(target_beats), in_time_of))
+    }
+
     /// Parse section
     /// Supports: section "name" body or section "name" { body }
     pub fn parse_section(&mut self) -> ParseResult<Spanned<Expr>> {
@@ -258,26 +396,29 @@ impl Parser {
 
         let name = self.parse_primary_expr()?;
 
+        // Context values are single terms, so `scale: Dorian { body }` doesn't
+        // read as an application
         let context = if self.match_token(&TokenKind::With) {
             let mut key = None;
             let mut scale = None;
             let mut tempo = None;
 
             loop {
-                if self.check(&TokenKind::Ident("key".to_string())) || self.check(&TokenKind::Key) {
+                if self.check_ident("key") || self.check(&TokenKind::Key) {
                     self.advance();
                     self.expect(&TokenKind::Colon, ":")?;
-                    key = Some(self.parse_expression()?);
-                } else if self.check(&TokenKind::Ident("scale".to_string()))
-                    || self.check(&TokenKind::Scale)
-                {
+                    key = match self.parse_key_tonic() {
+                        Some(tonic) => Some(tonic),
+                        None => Some(self.parse_primary_expr()?),
+                    };
+                } else if self.check_ident("scale") || self.check(&TokenKind::Scale) {
                     self.advance();
                     self.expect(&TokenKind::Colon, ":")?;
-                    scale = Some(self.parse_expression()?);
-                } else if self.check(&TokenKind::Ident("tempo".to_string())) {
+                    scale = Some(self.parse_primary_expr()?);
+                } else if self.check_ident("tempo") {
                     self.advance();
                     self.expect(&TokenKind::Colon, ":")?;
-                    tempo = Some(self.parse_expression()?);
+                    tempo = Some(self.parse_primary_expr()?);
                 } else {
                     break;
                 }
@@ -294,7 +435,9 @@ impl Parser {
 
         // Support both `section "name" { body }` and `section "name" body`
         let body = if self.match_token(&TokenKind::LBrace) {
+            self.skip_comments_and_newlines();
             let body = self.parse_expression()?;
+            self.skip_comments_and_newlines();
             self.expect(&TokenKind::RBrace, "}")?;
             body
         } else {
@@ -338,6 +481,110 @@ impl Parser {
         Ok(Spanned::new(Expr::Layer(LayerExpr { parts }), span))
     }
 
+    /// Parse a context: context { tempo = 140, key = A3, swing = 0.6 } in body
+    pub fn parse_context(&mut self) -> ParseResult<Spanned<Expr>> {
+        let start = self.current_span();
+        self.expect(&TokenKind::Context, "context")?;
+        self.expect(&TokenKind::LBrace, "{")?;
+        self.skip_comments_and_newlines();
+
+        let mut tempo = None;
+        let mut key = None;
+        let mut swing = None;
+        while !self.check(&TokenKind::RBrace) && !self.is_at_end() {
+            let name_span = self.current_span();
+            let name = self.parse_ident()?;
+            let setting = match name.name.as_str() {
+                "tempo" => &mut tempo,
+                "key" => &mut key,
+                "swing" => &mut swing,
+                other => {
+                    return Err(ParseError::custom(
+                        format!(
+                            "unknown context setting '{}' (expected tempo, key or swing)",
+                            other
+                        ),
+                        name_span,
+                    ))
+                }
+            };
+            self.expect(&TokenKind::Eq, "=")?;
+            // A key's tonic may be written A3, which lexes as an interval
+            let tonic = if name.name.as_str() == "key" {
+                self.parse_key_tonic()
+            } else {
+                None
+            };
+            *setting = match tonic {
+                Some(tonic) => Some(tonic),
+                None => Some(self.parse_expression()?),
+            };
+            self.skip_comments_and_newlines();
+
+            if self.match_token(&TokenKind::Comma) {
+                self.skip_comments_and_newlines();
+            } else {
+                break;
+            }
+        }
+
+        self.expect(&TokenKind::RBrace, "}")?;
+        self.skip_comments_and_newlines();
+        self.expect(&TokenKind::In, "in")?;
+        self.skip_comments_and_newlines();
+        let body = self.parse_expression()?;
+        let span = self.span_from(start);
+
+        Ok(Spanned::new(
+            Expr::Context(Box::new(ContextExpr {
+                tempo,
+                key,
+                swing,
+                body,
+            })),
+            span,
+        ))
+    }
+
+    /// Parse a drum kit: kit { x: Kick, o: Snare }
+    pub fn parse_kit(&mut self) -> ParseResult<Spanned<Expr>> {
+        let start = self.current_span();
+        self.expect(&TokenKind::Kit, "kit")?;
+        self.expect(&TokenKind::LBrace, "{")?;
+        self.skip_comments_and_newlines();
+
+        let mut hits = Vec::new();
+        while !self.check(&TokenKind::RBrace) && !self.is_at_end() {
+            let name_span = self.current_span();
+            let name = self.parse_ident()?;
+            if !is_hit_name(name.name.as_str()) {
+                return Err(ParseError::custom(
+                    format!("kit hit `{}` must be a single lowercase letter", name.name),
+                    name_span,
+                ));
+            }
+            self.expect(&TokenKind::Colon, ":")?;
+            let drum = self.parse_expression()?;
+            hits.push(KitHit {
+                name,
+                name_span,
+                drum,
+            });
+            self.skip_comments_and_newlines();
+
+            if self.match_token(&TokenKind::Comma) {
+                self.skip_comments_and_newlines();
+            } else {
+                break;
+            }
+        }
+
+        self.expect(&TokenKind::RBrace, "}")?;
+        let span = self.span_from(start);
+
+        Ok(Spanned::new(Expr::Kit(KitExpr { hits }), span))
+    }
+
     /// Parse part: part "instrument" body or part "instrument" { body }
     /// Also supports: part "instrument" (no body, will get body through pipe)
     pub fn parse_part(&mut self) -> ParseResult<Spanned<Expr>> {
@@ -413,3 +660,38 @@ impl Parser {
         articulations
     }
 }
+
+/// Whether `name` can name a drum hit: a single lowercase letter
+fn is_hit_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!((chars.next(), chars.next()), (Some(c), None) if c.is_ascii_lowercase())
+}
+
+/// Span of the first drum hit among `slots`, in tuplets too
+fn first_hit(slots: &[Spanned<Slot>]) -> Option<Span> {
+    slots.iter().find_map(|slot| match &slot.node {
+        Slot::Hit { .. } => Some(slot.span),
+        Slot::Tuplet(tuplet) => first_hit(&tuplet.contents),
+        _ => None,
+    })
+}
+
+/// Whether any of `slots` is a note or chord, in tuplets too
+fn has_pitch(slots: &[Spanned<Slot>]) -> bool {
+    slots.iter().any(|slot| match &slot.node {
+        Slot::Note { .. } | Slot::Chord { .. } => true,
+        Slot::Tuplet(tuplet) => has_pitch(&tuplet.contents),
+        Slot::Rest { .. } | Slot::Hit { .. } => false,
+    })
+}
+
+/// A note value of `num/den` of a whole note, written at `span`
+pub(crate) fn note_value(num: u32, den: u32, dotted: bool, span: Span) -> ParseResult<DurationLit> {
+    if den == 0 {
+        return Err(ParseError::custom(
+            format!("the note value {}/{} has a zero denominator", num, den),
+            span,
+        ));
+    }
+    Ok(DurationLit::NoteValue { num, den, dotted })
+}
@@ -14,6 +14,8 @@ pub struct Parser {
     pos: usize,
     diagnostics: Diagnostics,
     comments: Vec<Comment>,
+    /// Doc comment lines read since the last item, for the next definition
+    doc: Option<String>,
 }
 
 impl Parser {
@@ -28,6 +30,7 @@ impl Parser {
             pos: 0,
             diagnostics: Diagnostics::new(),
             comments: Vec::new(),
+            doc: None,
         };
         // Skip any leading comments
         parser.skip_comments();
@@ -42,13 +45,20 @@ impl Parser {
         self.skip_comments_and_newlines();
 
         while !self.is_at_end() {
+            let item_start = self.pos;
+            let doc = self.doc.take();
             match self.parse_item() {
-                Ok(item) => items.push(item),
+                Ok(mut item) => {
+                    item.node.set_doc(doc);
+                    items.push(item);
+                }
                 Err(err) => {
                     self.add_error(err);
-                    self.synchronize();
+                    self.synchronize(item_start);
                 }
             }
+            // Doc comments inside the item document nothing
+            self.doc = None;
             // Skip comments after each item
             self.skip_comments_and_newlines();
         }
@@ -63,13 +73,7 @@ impl Parser {
     pub fn skip_comments(&mut self) {
         while self.pos < self.tokens.len() {
             match &self.tokens[self.pos].kind {
-                TokenKind::LineComment(text) => {
-                    self.comments.push(Comment {
-                        text: text.clone(),
-                        span: self.tokens[self.pos].span,
-                    });
-                    self.pos += 1;
-                }
+                TokenKind::LineComment(_) => self.collect_comment(),
                 _ => break,
             }
         }
@@ -79,14 +83,12 @@ impl Parser {
     pub fn skip_comments_and_newlines(&mut self) {
         while !self.is_at_end() {
             match &self.tokens[self.pos].kind {
-                TokenKind::LineComment(text) => {
-                    self.comments.push(Comment {
-                        text: text.clone(),
-                        span: self.tokens[self.pos].span,
-                    });
-                    self.pos += 1;
-                }
+                TokenKind::LineComment(_) => self.collect_comment(),
                 TokenKind::Newline => {
+                    // A blank line separates doc comments from what follows
+                    if self.pos > 0 && self.tokens[self.pos - 1].kind == TokenKind::Newline {
+                        self.doc = None;
+                    }
                     self.pos += 1;
                 }
                 _ => break,
@@ -94,6 +96,28 @@ impl Parser {
         }
     }
 
+    /// Record the comment at the current position and step past it
+    fn collect_comment(&mut self) {
+        let token = &self.tokens[self.pos];
+        if let TokenKind::LineComment(text) = &token.kind {
+            let trailing = self.pos > 0 && self.tokens[self.pos - 1].kind != TokenKind::Newline;
+            let comment = Comment {
+                text: text.clone(),
+                span: token.span,
+                trailing,
+            };
+            self.doc = match comment.doc() {
+                Some(line) if !trailing => Some(match self.doc.take() {
+                    Some(doc) => format!("{}\n{}", doc, line),
+                    None => line.to_string(),
+                }),
+                _ => None,
+            };
+            self.comments.push(comment);
+        }
+        self.pos += 1;
+    }
+
     // ===== Token Navigation =====
 
     /// Check if we've reached the end of input
@@ -115,6 +139,13 @@ impl Parser {
             .unwrap_or_else(|| self.tokens.last().expect("Token stream should have EOF"))
     }
 
+    /// Get the token `n` tokens ahead without consuming
+    pub fn peek_nth(&self, n: usize) -> &Token {
+        self.tokens
+            .get(self.pos + n)
+            .unwrap_or_else(|| self.tokens.last().expect("Token stream should have EOF"))
+    }
+
     /// Get the current token's kind
     pub fn current(&self) -> &TokenKind {
         &self.peek().kind
@@ -138,9 +169,13 @@ impl Parser {
         &self.tokens[consumed_pos]
     }
 
-    /// Get the previous token
+    /// Get the previous token, looking past any comments skipped after it
     pub fn previous(&self) -> &Token {
-        &self.tokens[self.pos.saturating_sub(1)]
+        self.tokens[..self.pos]
+            .iter()
+            .rev()
+            .find(|token| !matches!(token.kind, TokenKind::LineComment(_)))
+            .unwrap_or(&self.tokens[0])
     }
 
     /// Check if current token matches the given kind
@@ -196,27 +231,64 @@ impl Parser {
     /// Add an error to diagnostics
     pub fn add_error(&mut self, error: ParseError) {
         self.diagnostics
-            .add(Diagnostic::error(error.to_string(), error.span()));
+            .add(Diagnostic::error(error.to_string(), error.span()).with_code(error.code()));
     }
 
-    /// Synchronize after an error
-    fn synchronize(&mut self) {
-        self.advance();
+    /// Synchronize after an error in the item starting at token `item_start`
+    ///
+    /// Skips to the next item keyword or the next line that starts at
+    /// column 0, so the remaining items still get parsed and reported.
+    fn synchronize(&mut self, item_start: usize) {
+        // Always make progress, even if the item failed on its first token
+        if self.pos == item_start {
+            self.advance();
+        }
 
-        while !self.is_at_end() {
-            match self.current() {
-                TokenKind::Let
+        while !self.is_at_end() && !self.is_item_start() && !self.at_line_start() {
+            self.advance();
+        }
+    }
+
+    /// Skip the rest of a malformed slot, stopping at the next `|`
+    pub fn synchronize_slot(&mut self) {
+        while !self.is_at_end() && !self.check(&TokenKind::Pipe) && !self.at_next_item() {
+            self.advance();
+        }
+    }
+
+    /// Check if the current token begins an item on its own unindented line
+    ///
+    /// Inside a block this usually means the closing `|` is missing.
+    pub fn at_next_item(&self) -> bool {
+        self.at_line_start() && self.is_item_start()
+    }
+
+    /// Check if the current token is a keyword that begins an item
+    fn is_item_start(&self) -> bool {
+        matches!(
+            self.current(),
+            TokenKind::Let
+                | TokenKind::Set
+                | TokenKind::Meta
+                | TokenKind::Test
                 | TokenKind::Scale
                 | TokenKind::Chord
+                | TokenKind::Synth
                 | TokenKind::Section
                 | TokenKind::Import
                 | TokenKind::Export
                 | TokenKind::Mod
-                | TokenKind::Use => return,
-                _ => {
-                    self.advance();
-                }
+                | TokenKind::Use
+        )
+    }
+
+    /// Check if the current token is the first thing on an unindented line
+    fn at_line_start(&self) -> bool {
+        match self.pos.checked_sub(1).map(|i| &self.tokens[i]) {
+            Some(prev) => {
+                prev.kind == TokenKind::Newline && prev.span.end == self.current_span().start
             }
+            None => true,
         }
     }
 
@@ -421,4 +493,28 @@ mod tests {
             panic!("Expected use declaration");
         }
     }
+
+    #[test]
+    fn test_recover_multiple_errors() {
+        let (program, diagnostics) =
+            parse("let a = foo (\nlet b = | R |\nlet c = bar )\nlet d = | P5 |");
+        assert_eq!(diagnostics.error_count(), 2, "{:?}", diagnostics);
+        // The well-formed items around the errors are kept
+        assert_eq!(program.items.len(), 3);
+    }
+
+    #[test]
+    fn test_recover_inside_block() {
+        let (program, diagnostics) = parse("let a = | R = M3 | ++ | P5 , |");
+        assert_eq!(diagnostics.error_count(), 2, "{:?}", diagnostics);
+        if let Item::LetBinding(binding) = &program.items[0].node {
+            if let Expr::Binary(binary) = &binding.value.node {
+                if let Expr::Block(block) = &binary.left.node {
+                    assert_eq!(block.slots.len(), 1);
+                    return;
+                }
+            }
+        }
+        panic!("Expected a let binding of two blocks");
+    }
 }
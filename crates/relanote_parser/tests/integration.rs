@@ -38,11 +38,11 @@ fn test_parse_integer() {
 
 #[test]
 fn test_parse_float() {
-    let program = parse("3.14");
+    let program = parse("2.75");
     assert_eq!(program.items.len(), 1);
     match &program.items[0].node {
         Item::ExprStmt(expr) => match &expr.node {
-            Expr::Float(n) => assert!((n - 3.14).abs() < 0.001),
+            Expr::Float(n) => assert!((n - 2.75).abs() < 0.001),
             _ => panic!("Expected Float"),
         },
         _ => panic!("Expected ExprStmt"),
@@ -156,6 +156,56 @@ fn test_parse_scale_definition() {
     }
 }
 
+#[test]
+fn test_parse_scale_definition_in_cents() {
+    let program = parse("scale Just = { 0c, 204c, 386.3c, M3 }");
+    match &program.items[0].node {
+        Item::ScaleDef(scale) => {
+            let cents: Vec<f64> = scale.intervals.iter().map(|i| i.node.cents()).collect();
+            assert_eq!(cents, vec![0.0, 204.0, 386.3, 400.0]);
+        }
+        _ => panic!("Expected ScaleDef"),
+    }
+}
+
+#[test]
+fn test_parse_derived_scale_definition() {
+    let program = parse("scale Lydian = Major with { P4+ }");
+    match &program.items[0].node {
+        Item::ScaleDef(scale) => {
+            assert!(scale.intervals.is_empty());
+            match &scale.base.as_ref().expect("a base scale").node {
+                Expr::With(with) => {
+                    assert!(
+                        matches!(&with.base.node, Expr::Ident(name) if name.name.as_str() == "Major")
+                    );
+                    assert_eq!(with.modifications.len(), 1);
+                }
+                other => panic!("Expected With, got {:?}", other),
+            }
+        }
+        _ => panic!("Expected ScaleDef"),
+    }
+}
+
+#[test]
+fn test_parse_with_removal() {
+    let program = parse("let c = Dominant7 with { -P5, M9 }");
+    match &program.items[0].node {
+        Item::LetBinding(binding) => match &binding.value.node {
+            Expr::With(with) => {
+                assert!(matches!(
+                    &with.modifications[0].node,
+                    Expr::Unary(unary) if unary.op == UnaryOp::Neg
+                ));
+                assert!(matches!(&with.modifications[1].node, Expr::Interval(_)));
+            }
+            other => panic!("Expected With, got {:?}", other),
+        },
+        _ => panic!("Expected LetBinding"),
+    }
+}
+
 // ===== Chord Definition Tests =====
 
 #[test]
@@ -234,6 +284,291 @@ fn test_parse_block_with_duration() {
     }
 }
 
+#[test]
+fn test_parse_note_value_durations() {
+    let program = parse("| R:1/8 - :1/4. M3:1bar |");
+    match &program.items[0].node {
+        Item::ExprStmt(expr) => match &expr.node {
+            Expr::Block(block) => {
+                let durations: Vec<_> = block
+                    .slots
+                    .iter()
+                    .map(|slot| match &slot.node {
+                        Slot::Note { duration, .. } | Slot::Rest { duration } => *duration,
+                        _ => panic!("Expected Note or Rest"),
+                    })
+                    .collect();
+                assert_eq!(
+                    durations,
+                    vec![
+                        Some(DurationLit::NoteValue {
+                            num: 1,
+                            den: 8,
+                            dotted: false
+                        }),
+                        Some(DurationLit::NoteValue {
+                            num: 1,
+                            den: 4,
+                            dotted: true
+                        }),
+                        Some(DurationLit::Bars(1.0)),
+                    ]
+                );
+            }
+            _ => panic!("Expected Block"),
+        },
+        _ => panic!("Expected ExprStmt"),
+    }
+}
+
+#[test]
+fn test_parse_note_value_zero_denominator() {
+    // Spaced or not, in a slot or an expression
+    for source in [
+        "| R:1/0 |",
+        "| R:0/0 |",
+        "| R:1 / 0 |",
+        "| R:1/0. |",
+        "let x = 1/0",
+    ] {
+        let (_, has_errors) = parse_with_errors(source);
+        assert!(has_errors, "{} should not parse", source);
+    }
+
+    // A spaced `/` in an expression divides, so dividing by zero is left to
+    // the evaluator
+    let (_, has_errors) = parse_with_errors("let x = 1 / 0");
+    assert!(!has_errors);
+}
+
+#[test]
+fn test_parse_rest_runs() {
+    // Runs of rests aren't doc comments, which start a line
+    let program = parse("| R ---- M3 |\nlet x = | R --|\n  --- A riff\nlet y = | R |");
+    match &program.items[0].node {
+        Item::ExprStmt(expr) => match &expr.node {
+            Expr::Block(block) => assert_eq!(block.slots.len(), 6),
+            _ => panic!("Expected Block"),
+        },
+        _ => panic!("Expected ExprStmt"),
+    }
+    assert_eq!(program.items.len(), 3);
+}
+
+#[test]
+fn test_parse_ties() {
+    let program = parse("| R~:2~ | ++ | ~R:2 [R, M3]:2~ |");
+    let binary = match &program.items[0].node {
+        Item::ExprStmt(expr) => match &expr.node {
+            Expr::Binary(binary) => binary,
+            _ => panic!("Expected Binary"),
+        },
+        _ => panic!("Expected ExprStmt"),
+    };
+    let articulations = |block: &Expr| match block {
+        Expr::Block(block) => block
+            .slots
+            .iter()
+            .map(|slot| match &slot.node {
+                Slot::Note { articulations, .. } | Slot::Chord { articulations, .. } => {
+                    articulations.clone()
+                }
+                _ => panic!("Expected Note or Chord"),
+            })
+            .collect::<Vec<_>>(),
+        _ => panic!("Expected Block"),
+    };
+    assert_eq!(
+        articulations(&binary.left.node),
+        vec![vec![Articulation::Portamento, Articulation::Tie]]
+    );
+    assert_eq!(
+        articulations(&binary.right.node),
+        vec![vec![Articulation::Tied], vec![Articulation::Tie]]
+    );
+
+    assert!(parse_with_errors("| ~- |").1);
+}
+
+#[test]
+fn test_parse_scale_degree_octaves() {
+    let program = parse("| <3> <3'> <5,,> <4+'> |");
+    let pitches: Vec<_> = match &program.items[0].node {
+        Item::ExprStmt(expr) => match &expr.node {
+            Expr::Block(block) => block
+                .slots
+                .iter()
+                .map(|slot| match &slot.node {
+                    Slot::Note { pitch, .. } => match &pitch.node {
+                        Pitch::ScaleIndex(n) => (*n, 0, 0),
+                        Pitch::ScaleIndexMod(n, accidentals, octave) => {
+                            (*n, accidentals.len(), *octave)
+                        }
+                        _ => panic!("Expected scale index"),
+                    },
+                    _ => panic!("Expected Note"),
+                })
+                .collect(),
+            _ => panic!("Expected Block"),
+        },
+        _ => panic!("Expected ExprStmt"),
+    };
+    assert_eq!(pitches, vec![(3, 0, 0), (3, 0, 1), (5, 0, -2), (4, 1, 1)]);
+
+    assert!(parse_with_errors("| <3,'> |").1);
+    // Octaves are counted in an i8
+    let marks = |mark: &str, n: usize| format!("| <1{}> |", mark.repeat(n));
+    assert!(!parse_with_errors(&marks("'", 127)).1);
+    assert!(parse_with_errors(&marks("'", 128)).1);
+    assert!(!parse_with_errors(&marks(",", 128)).1);
+    assert!(parse_with_errors(&marks(",", 129)).1);
+}
+
+#[test]
+fn test_parse_ranges_and_indexing() {
+    let program = parse("melody[1]\nf [1]\nbars 0..n verse\n(0..n + 1)");
+    let exprs: Vec<_> = program
+        .items
+        .iter()
+        .map(|item| match &item.node {
+            Item::ExprStmt(expr) => &expr.node,
+            _ => panic!("Expected ExprStmt"),
+        })
+        .collect();
+    assert!(matches!(exprs[0], Expr::Index(_)));
+    match exprs[1] {
+        Expr::Application(app) => assert!(matches!(app.args[0].node, Expr::Array(_))),
+        other => panic!("Expected Application, got {:?}", other),
+    }
+    // A range needs no parentheses as an argument
+    match exprs[2] {
+        Expr::Application(app) => {
+            assert_eq!(app.args.len(), 2);
+            match &app.args[0].node {
+                Expr::Binary(binary) => assert!(matches!(binary.op, BinaryOp::Range)),
+                other => panic!("Expected Binary, got {:?}", other),
+            }
+        }
+        other => panic!("Expected Application, got {:?}", other),
+    }
+    // Elsewhere it binds looser than arithmetic
+    match exprs[3] {
+        Expr::Paren(inner) => match &inner.node {
+            Expr::Binary(binary) => {
+                assert!(matches!(binary.op, BinaryOp::Range));
+                assert!(matches!(binary.right.node, Expr::Binary(_)));
+            }
+            other => panic!("Expected Binary, got {:?}", other),
+        },
+        other => panic!("Expected Paren, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_parse_overlay() {
+    let program = parse("melody & bass ++ fill & pad");
+    let expr = match &program.items[0].node {
+        Item::ExprStmt(expr) => &expr.node,
+        _ => panic!("Expected ExprStmt"),
+    };
+    // Left-associative, and looser than concatenation
+    match expr {
+        Expr::Binary(outer) => {
+            assert!(matches!(outer.op, BinaryOp::Overlay));
+            match &outer.left.node {
+                Expr::Binary(inner) => {
+                    assert!(matches!(inner.op, BinaryOp::Overlay));
+                    assert!(matches!(
+                        &inner.right.node,
+                        Expr::Binary(concat) if matches!(concat.op, BinaryOp::Concat)
+                    ));
+                }
+                other => panic!("Expected Binary, got {:?}", other),
+            }
+        }
+        other => panic!("Expected Binary, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_parse_kits_and_hits() {
+    let program = parse("kit { x: Kick,\n  o: 38 }\n| x - o:2 |");
+    match &program.items[0].node {
+        Item::ExprStmt(expr) => match &expr.node {
+            Expr::Kit(kit) => {
+                let names: Vec<_> = kit.hits.iter().map(|hit| hit.name.name.as_str()).collect();
+                assert_eq!(names, vec!["x", "o"]);
+            }
+            _ => panic!("Expected Kit"),
+        },
+        _ => panic!("Expected ExprStmt"),
+    }
+    match &program.items[1].node {
+        Item::ExprStmt(expr) => match &expr.node {
+            Expr::Block(block) => {
+                assert!(
+                    matches!(&block.slots[0].node, Slot::Hit { name, duration: None, .. } if name.name.as_str() == "x")
+                );
+                assert!(matches!(&block.slots[1].node, Slot::Rest { .. }));
+                assert!(matches!(
+                    &block.slots[2].node,
+                    Slot::Hit {
+                        duration: Some(_),
+                        ..
+                    }
+                ));
+            }
+            _ => panic!("Expected Block"),
+        },
+        _ => panic!("Expected ExprStmt"),
+    }
+
+    // Hits are single letters in blocks of their own
+    assert!(parse_with_errors("| R a b |").1);
+    assert!(parse_with_errors("| R M3 P5x |").1);
+    assert!(parse_with_errors("| x { o R }:1 |").1);
+    assert!(parse_with_errors("| kick - |").1);
+    assert!(parse_with_errors("kit { kick: 36 }").1);
+    assert!(!parse_with_errors("| x { o o }:1 - |").1);
+}
+
+#[test]
+fn test_parse_duration_expressions() {
+    let program = parse("2 beats + 1 bar - 3/8");
+    match &program.items[0].node {
+        Item::ExprStmt(expr) => match &expr.node {
+            Expr::Binary(binary) => {
+                assert!(matches!(
+                    binary.right.node,
+                    Expr::Duration(DurationLit::NoteValue { num: 3, den: 8, .. })
+                ));
+            }
+            _ => panic!("Expected Binary"),
+        },
+        _ => panic!("Expected ExprStmt"),
+    }
+
+    // Only an unspaced fraction is a note value, whatever its size; with
+    // spaces, `/` always divides
+    for (source, expected) in [("1/8", (1, 8)), ("6/2", (6, 2))] {
+        let program = parse(source);
+        assert!(matches!(
+            &program.items[0].node,
+            Item::ExprStmt(expr)
+                if matches!(expr.node, Expr::Duration(DurationLit::NoteValue { num, den, .. })
+                    if (num, den) == expected)
+        ));
+    }
+    for source in ["1 / 8", "6 / 2", "0 / 5"] {
+        let program = parse(source);
+        assert!(matches!(
+            &program.items[0].node,
+            Item::ExprStmt(expr)
+                if matches!(&expr.node, Expr::Binary(binary) if binary.op == BinaryOp::Div)
+        ));
+    }
+}
+
 #[test]
 fn test_parse_multiline_block() {
     let program = parse(
@@ -344,6 +679,22 @@ fn test_parse_function_call_parens() {
     }
 }
 
+#[test]
+fn test_parse_named_arguments() {
+    let program = parse("delay(250, mix: 0.3)");
+    match &program.items[0].node {
+        Item::ExprStmt(expr) => match &expr.node {
+            Expr::Application(app) => {
+                assert_eq!(app.args.len(), 1);
+                assert_eq!(app.named.len(), 1);
+                assert_eq!(app.named[0].name.name.as_ref() as &str, "mix");
+            }
+            _ => panic!("Expected Application"),
+        },
+        _ => panic!("Expected ExprStmt"),
+    }
+}
+
 #[test]
 fn test_parse_haskell_style_application() {
     let program = parse("f x y z");
@@ -443,6 +794,37 @@ x
     assert_eq!(program.items.len(), 2);
 }
 
+#[test]
+fn test_parse_doc_comments() {
+    let program = parse(
+        r#"
+--- The hook of the chorus
+--- in two bars
+let hook = | R M3 P5 |
+
+--| Sus chord for the bridge
+export chord Bridge = [ R, P4, P5 ]
+
+--- Detached by a blank line
+
+; a plain comment
+let riff x = x |> reverse
+--- Not above anything
+"#,
+    );
+    let docs: Vec<_> = program.items.iter().map(|item| item.node.doc()).collect();
+    assert_eq!(
+        docs,
+        vec![
+            Some("The hook of the chorus\nin two bars"),
+            Some("Sus chord for the bridge"),
+            None,
+        ]
+    );
+    // Doc comments are kept for the formatter like any comment
+    assert_eq!(program.comments.len(), 6);
+}
+
 // ===== Error Case Tests =====
 
 #[test]
@@ -451,6 +833,24 @@ fn test_parse_error_unclosed_block() {
     assert!(has_errors);
 }
 
+#[test]
+fn test_parse_error_unclosed_block_points_at_its_end() {
+    let source = Source::from_string("test", "let a = | R M3\nlet b = 1".to_string());
+    let (program, diagnostics) = Parser::new(&source).parse_program();
+    let errors: Vec<_> = diagnostics.errors().collect();
+    assert_eq!(errors.len(), 1, "{:?}", errors);
+    assert_eq!(
+        errors[0].code,
+        Some(relanote_core::DiagnosticCode::UnclosedDelimiter)
+    );
+    assert_eq!((errors[0].span.start, errors[0].span.end), (14, 14));
+    // The next item still parses
+    assert!(program.items.iter().any(|item| item
+        .node
+        .defined_name()
+        .is_some_and(|n| n.name.as_str() == "b")));
+}
+
 #[test]
 fn test_parse_error_unclosed_paren() {
     let (_, has_errors) = parse_with_errors("f(x");
@@ -503,6 +903,115 @@ fn test_parse_set_key() {
     }
 }
 
+#[test]
+fn test_parse_meta_block() {
+    let program = parse("meta {\n  title: \"Etude\", ; the working title\n  composer: \"Anon\"\n}");
+    match &program.items[0].node {
+        Item::Meta(meta) => {
+            let names: Vec<&str> = meta
+                .fields
+                .iter()
+                .map(|field| field.node.name.name.as_str())
+                .collect();
+            assert_eq!(names, vec!["title", "composer"]);
+        }
+        _ => panic!("Expected Meta"),
+    }
+}
+
+#[test]
+fn test_parse_test_item() {
+    let program = parse("test \"chorus\" {\n  | R M3 P5 |\n}");
+    match &program.items[0].node {
+        Item::Test(test) => {
+            assert_eq!(test.name, "chorus");
+            assert!(matches!(test.body.node, Expr::Block(_)));
+        }
+        _ => panic!("Expected Test"),
+    }
+}
+
+#[test]
+fn test_parse_render_item() {
+    let program = parse("render layer [melody]\nrender(song)\nrender \"demo\" = verse");
+    let targets: Vec<_> = program
+        .items
+        .iter()
+        .map(|item| match &item.node {
+            Item::Render(render) => (render.name.as_deref(), &render.target.node),
+            other => panic!("Expected Render, got {:?}", other),
+        })
+        .collect();
+    assert!(matches!(targets[0], (None, Expr::Layer(_))));
+    assert!(matches!(targets[1], (None, Expr::Paren(_))));
+    assert!(matches!(targets[2], (Some("demo"), Expr::Ident(_))));
+}
+
+#[test]
+fn test_parse_context() {
+    let program = parse("verse ++ context { tempo = 140, key = A3, swing = 0.6 } in chorus");
+    let expr = match &program.items[0].node {
+        Item::ExprStmt(expr) => &expr.node,
+        _ => panic!("Expected ExprStmt"),
+    };
+    match expr {
+        Expr::Binary(concat) => match &concat.right.node {
+            Expr::Context(context) => {
+                assert!(matches!(
+                    context.tempo.as_ref().map(|e| &e.node),
+                    Some(Expr::Integer(140))
+                ));
+                assert!(matches!(
+                    context.key.as_ref().map(|e| &e.node),
+                    Some(Expr::AbsolutePitch(_))
+                ));
+                assert!(matches!(
+                    context.swing.as_ref().map(|e| &e.node),
+                    Some(Expr::Float(_))
+                ));
+                assert!(matches!(context.body.node, Expr::Ident(_)));
+            }
+            other => panic!("Expected Context, got {:?}", other),
+        },
+        other => panic!("Expected Binary, got {:?}", other),
+    }
+
+    // Settings are optional, but must be known
+    assert!(!parse_with_errors("context {} in chorus").1);
+    assert!(parse_with_errors("context { volume = 1 } in chorus").1);
+}
+
+#[test]
+fn test_parse_set_key_with_mode() {
+    let key = |input: &str| match &parse(input).items[0].node {
+        Item::SetBinding(binding) => match &binding.value.node {
+            Expr::AbsolutePitch(pitch) => (
+                pitch.clone(),
+                binding.mode.as_ref().map(|mode| mode.name.to_string()),
+            ),
+            other => panic!("Expected AbsolutePitch, got {:?}", other),
+        },
+        _ => panic!("Expected SetBinding"),
+    };
+
+    assert_eq!(
+        key("set key = D minor"),
+        (AbsolutePitchLit::new('D', 0, 4), Some("minor".to_string()))
+    );
+    assert_eq!(
+        key("set key = F# dorian"),
+        (AbsolutePitchLit::new('F', 1, 4), Some("dorian".to_string()))
+    );
+    assert_eq!(
+        key("set key = Bb3 major"),
+        (AbsolutePitchLit::new('B', -1, 3), Some("major".to_string()))
+    );
+    assert_eq!(
+        key("set key = C4"),
+        (AbsolutePitchLit::new('C', 0, 4), None)
+    );
+}
+
 #[test]
 fn test_parse_set_tempo() {
     let program = parse("set tempo = 120");
@@ -514,6 +1023,35 @@ fn test_parse_set_tempo() {
     }
 }
 
+// ===== Section Tests =====
+
+#[test]
+fn test_parse_section_with_context() {
+    let program = parse(
+        "section \"Bridge\" with key: G4, scale: Dorian {
+  melody
+}",
+    );
+    match &program.items[0].node {
+        Item::ExprStmt(expr) => match &expr.node {
+            Expr::Section(section) => {
+                let context = section.context.as_ref().expect("Expected context");
+                assert!(matches!(
+                    context.key.as_ref().map(|key| &key.node),
+                    Some(Expr::AbsolutePitch(_))
+                ));
+                assert!(matches!(
+                    context.scale.as_ref().map(|scale| &scale.node),
+                    Some(Expr::Ident(_))
+                ));
+                assert!(matches!(section.body.node, Expr::Ident(_)));
+            }
+            _ => panic!("Expected Section"),
+        },
+        _ => panic!("Expected ExprStmt"),
+    }
+}
+
 // ===== Layer Tests =====
 
 #[test]
@@ -567,3 +1105,18 @@ fn test_parse_layer_trailing_comma() {
         _ => panic!("Expected ExprStmt"),
     }
 }
+
+#[test]
+fn test_parse_arbitrary_never_panics() {
+    let program = b"let melody = | R M3 P5 |:3\nsection \"A\" { melody |> reverb 0.4 }\n";
+
+    // Every prefix, so unterminated blocks, strings and sections are covered
+    for end in 0..=program.len() {
+        let _ = relanote_parser::parse_arbitrary(&program[..end]);
+    }
+
+    // Invalid UTF-8 is replaced, and the lexer skips what it can't read
+    let (program, diagnostics) = relanote_parser::parse_arbitrary(b"| R \xff\xfe M3 |");
+    assert!(!diagnostics.has_errors());
+    assert_eq!(program.items.len(), 1);
+}
@@ -0,0 +1,1135 @@
+//! ABC to relanote source conversion
+//!
+//! Pitches become intervals from the tune's tonic (`set key`), and each bar
+//! becomes runs of equal-length notes written as relative-rhythm blocks
+//! (`| R M2 M3 |:1.5`) joined with `++`. Repeats and first/second endings are
+//! written out in full. Grace notes, slurs, chord symbols and most
+//! decorations are dropped; staccato and accents are kept.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use thiserror::Error;
+
+/// Errors converting ABC notation
+#[derive(Debug, Error)]
+pub enum AbcError {
+    #[error("line {line}: {message}")]
+    Syntax { line: usize, message: String },
+
+    #[error("the tune has no notes")]
+    Empty,
+}
+
+/// A non-negative fraction (of a whole note, for lengths)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Ratio {
+    num: u64,
+    den: u64,
+}
+
+impl Ratio {
+    fn new(num: u64, den: u64) -> Self {
+        let divisor = gcd64(num, den.max(1));
+        Self {
+            num: num / divisor,
+            den: den.max(1) / divisor,
+        }
+    }
+
+    /// Product, or None if it doesn't fit
+    fn mul(self, other: Ratio) -> Option<Ratio> {
+        Some(Ratio::new(
+            self.num.checked_mul(other.num)?,
+            self.den.checked_mul(other.den)?,
+        ))
+    }
+
+    /// Sum, or None if it doesn't fit
+    fn add(self, other: Ratio) -> Option<Ratio> {
+        Some(Ratio::new(
+            self.num
+                .checked_mul(other.den)?
+                .checked_add(other.num.checked_mul(self.den)?)?,
+            self.den.checked_mul(other.den)?,
+        ))
+    }
+
+    fn as_f64(self) -> f64 {
+        self.num as f64 / self.den as f64
+    }
+}
+
+fn gcd64(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        a.max(1)
+    } else {
+        gcd64(b, a % b)
+    }
+}
+
+/// A parsed note, chord, rest or bar line
+#[derive(Clone, Debug)]
+enum Element {
+    Note {
+        /// MIDI keys (several for a chord)
+        keys: Vec<i32>,
+        /// Length as a fraction of a whole note
+        length: Ratio,
+        staccato: bool,
+        accent: bool,
+        tie: bool,
+    },
+    Rest(Ratio),
+    Bar,
+}
+
+impl Element {
+    fn length_mut(&mut self) -> Option<&mut Ratio> {
+        match self {
+            Element::Note { length, .. } | Element::Rest(length) => Some(length),
+            Element::Bar => None,
+        }
+    }
+}
+
+/// Key signature (C major by default)
+#[derive(Clone, Copy, Default)]
+struct Key {
+    /// Pitch class of the tonic
+    tonic: i32,
+    /// Accidental applied to each letter (C D E F G A B)
+    signature: [i32; 7],
+    /// Whether the key is spelled with flats
+    flats: bool,
+}
+
+/// Letter index (C=0 .. B=6) and pitch class
+const LETTERS: [(char, i32); 7] = [
+    ('C', 0),
+    ('D', 2),
+    ('E', 4),
+    ('F', 5),
+    ('G', 7),
+    ('A', 9),
+    ('B', 11),
+];
+
+fn letter_index(letter: char) -> Option<usize> {
+    LETTERS
+        .iter()
+        .position(|(l, _)| *l == letter.to_ascii_uppercase())
+}
+
+impl Key {
+    /// Parse a K: field such as `D`, `Ador`, `F#m` or `Bb mix`
+    fn parse(field: &str) -> Option<Self> {
+        let field = field.trim();
+        let mut chars = field.chars().peekable();
+        let letter = match chars.next() {
+            Some(c @ 'A'..='G') => c,
+            // No tonic ("K:none", "K:" or a clef only)
+            _ => return Some(Self::default()),
+        };
+        let index = letter_index(letter)?;
+        let accidental = match chars.peek() {
+            Some('#') => {
+                chars.next();
+                1
+            }
+            Some('b') => {
+                chars.next();
+                -1
+            }
+            _ => 0,
+        };
+
+        let mode: String = chars
+            .skip_while(|c| c.is_whitespace())
+            .take_while(|c| c.is_alphabetic())
+            .collect::<String>()
+            .to_ascii_lowercase();
+        let mode_offset = match mode.get(..3).unwrap_or(&mode) {
+            "" | "maj" | "ion" => 0,
+            "m" | "min" | "aeo" => -3,
+            "dor" => -2,
+            "phr" => -4,
+            "lyd" => 1,
+            "mix" => -1,
+            "loc" => -5,
+            // Clef or other key field parameters
+            _ => 0,
+        };
+
+        // Sharps (positive) or flats (negative) in the signature
+        const MAJOR_FIFTHS: [i32; 7] = [0, 2, 4, -1, 1, 3, 5];
+        let fifths = MAJOR_FIFTHS[index] + accidental * 7 + mode_offset;
+
+        let mut signature = [0; 7];
+        const SHARP_ORDER: [usize; 7] = [3, 0, 4, 1, 5, 2, 6];
+        const FLAT_ORDER: [usize; 7] = [6, 2, 5, 1, 4, 0, 3];
+        if fifths > 0 {
+            for letter in SHARP_ORDER.iter().take(fifths.min(7) as usize) {
+                signature[*letter] = 1;
+            }
+        } else {
+            for letter in FLAT_ORDER.iter().take((-fifths).min(7) as usize) {
+                signature[*letter] = -1;
+            }
+        }
+
+        Some(Self {
+            tonic: (LETTERS[index].1 + accidental).rem_euclid(12),
+            signature,
+            flats: fifths < 0,
+        })
+    }
+}
+
+/// One voice (V: field) of the tune
+struct Voice {
+    id: String,
+    name: Option<String>,
+    elements: Vec<Element>,
+    /// Where the current repeated section starts
+    repeat_start: usize,
+    /// Where the first ending of the current repeat starts
+    ending_start: Option<usize>,
+}
+
+impl Voice {
+    fn new(id: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            name: None,
+            elements: Vec::new(),
+            repeat_start: 0,
+            ending_start: None,
+        }
+    }
+
+    fn bar(&mut self) {
+        if !matches!(self.elements.last(), None | Some(Element::Bar)) {
+            self.elements.push(Element::Bar);
+        }
+    }
+
+    /// Play the repeated section again (without its first ending)
+    fn repeat(&mut self) {
+        let end = self.ending_start.take().unwrap_or(self.elements.len());
+        let section = self.elements[self.repeat_start.min(end)..end].to_vec();
+        self.bar();
+        self.elements.extend(section);
+        self.bar();
+        self.repeat_start = self.elements.len();
+    }
+}
+
+/// Tune being parsed
+struct Tune {
+    title: Option<String>,
+    tempo: Option<u32>,
+    meter: Ratio,
+    unit: Option<Ratio>,
+    key: Key,
+    voices: Vec<Voice>,
+    voice: usize,
+    in_body: bool,
+}
+
+/// Parser state that lasts until the next note or bar line
+#[derive(Default)]
+struct Pending {
+    staccato: bool,
+    accent: bool,
+    /// Notes left in the current tuplet and their length factor
+    tuplet: Option<(u32, Ratio)>,
+    /// Length factor for the note after a broken rhythm (`>`/`<`)
+    broken: Option<Ratio>,
+    /// Accidentals set in the current bar, by (letter, octave)
+    accidentals: HashMap<(usize, i32), i32>,
+}
+
+impl Tune {
+    fn new() -> Self {
+        Self {
+            title: None,
+            tempo: None,
+            meter: Ratio::new(4, 4),
+            unit: None,
+            key: Key::default(),
+            voices: vec![Voice::new("1")],
+            voice: 0,
+            in_body: false,
+        }
+    }
+
+    /// Unit note length (L:), defaulting by meter as the standard specifies
+    fn unit(&self) -> Ratio {
+        self.unit.unwrap_or(if self.meter.as_f64() < 0.75 {
+            Ratio::new(1, 16)
+        } else {
+            Ratio::new(1, 8)
+        })
+    }
+
+    /// Apply an information field (header line or inline `[K:...]`)
+    fn field(&mut self, name: char, value: &str, line: usize) -> Result<(), AbcError> {
+        let value = value.trim();
+        let syntax = |message: String| AbcError::Syntax { line, message };
+        match name {
+            'T' if self.title.is_none() && !self.in_body => {
+                self.title = Some(value.to_string());
+            }
+            'M' => {
+                self.meter = match value {
+                    "C" => Ratio::new(4, 4),
+                    "C|" => Ratio::new(2, 2),
+                    "none" | "" => Ratio::new(4, 4),
+                    _ => parse_fraction(value)
+                        .ok_or_else(|| syntax(format!("invalid meter '{}'", value)))?,
+                };
+            }
+            'L' => {
+                self.unit = Some(
+                    parse_fraction(value)
+                        .ok_or_else(|| syntax(format!("invalid unit note length '{}'", value)))?,
+                );
+            }
+            'Q' => self.tempo = parse_tempo(value),
+            'K' => {
+                self.key =
+                    Key::parse(value).ok_or_else(|| syntax(format!("invalid key '{}'", value)))?;
+                self.in_body = true;
+            }
+            'V' => {
+                let mut words = value.split_whitespace();
+                let id = words.next().unwrap_or("1");
+                self.voice = match self.voices.iter().position(|v| v.id == id) {
+                    Some(index) => index,
+                    // The implicit first voice is taken over by the first V: field
+                    None if self.voices.len() == 1 && self.voices[0].elements.is_empty() => {
+                        self.voices[0].id = id.to_string();
+                        0
+                    }
+                    None => {
+                        self.voices.push(Voice::new(id));
+                        self.voices.len() - 1
+                    }
+                };
+                if let Some(name) = parse_voice_name(value) {
+                    self.voices[self.voice].name = Some(name);
+                }
+            }
+            // Other fields (composer, origin, notes, lyrics, ...) don't affect the music
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Parse one line of music
+    fn music(&mut self, line: &str, number: usize, pending: &mut Pending) -> Result<(), AbcError> {
+        let chars: Vec<char> = line.chars().collect();
+        let syntax = |message: String| AbcError::Syntax {
+            line: number,
+            message,
+        };
+        let too_long = |column: usize| syntax(format!("length too long at column {}", column));
+        let mut i = 0;
+
+        while i < chars.len() {
+            let c = chars[i];
+            match c {
+                ' ' | '\t' | '`' | '\\' | ')' | 'y' => i += 1,
+
+                // Chord symbols and annotations
+                '"' => i = skip_past(&chars, i + 1, '"'),
+
+                // Grace notes
+                '{' => i = skip_past(&chars, i + 1, '}'),
+
+                '!' | '+' => {
+                    let end = skip_past(&chars, i + 1, c);
+                    let name: String = chars[i + 1..end.saturating_sub(1).max(i + 1)]
+                        .iter()
+                        .collect();
+                    match name.as_str() {
+                        "staccato" => pending.staccato = true,
+                        "accent" | ">" | "emphasis" => pending.accent = true,
+                        _ => {}
+                    }
+                    i = end;
+                }
+                '.' => {
+                    pending.staccato = true;
+                    i += 1;
+                }
+                'L' => {
+                    pending.accent = true;
+                    i += 1;
+                }
+                '~' | 'H' | 'M' | 'O' | 'P' | 'S' | 'T' | 'u' | 'v' | 'J' | 'R' => i += 1,
+
+                '(' => {
+                    if chars.get(i + 1).is_some_and(|c| c.is_ascii_digit()) {
+                        let (tuplet, next) = self
+                            .tuplet(&chars, i + 1)
+                            .ok_or_else(|| syntax(format!("invalid tuplet at column {}", i + 1)))?;
+                        pending.tuplet = Some(tuplet);
+                        i = next;
+                    } else {
+                        // Slur
+                        i += 1;
+                    }
+                }
+
+                '[' if chars.get(i + 1).is_some_and(|c| c.is_ascii_digit()) => {
+                    // Alternate ending after a bar line
+                    self.ending(chars[i + 1]);
+                    i += 2;
+                    while chars
+                        .get(i)
+                        .is_some_and(|c| c.is_ascii_digit() || *c == ',' || *c == '-')
+                    {
+                        i += 1;
+                    }
+                }
+                '[' if chars.get(i + 2) == Some(&':') && chars[i + 1].is_ascii_alphabetic() => {
+                    // Inline field
+                    let end = skip_past(&chars, i + 3, ']');
+                    let value: String = chars[i + 3..end.saturating_sub(1).max(i + 3)]
+                        .iter()
+                        .collect();
+                    self.field(chars[i + 1], &value, number)?;
+                    i = end;
+                }
+                '[' if chars.get(i + 1) == Some(&'|') => {
+                    i = self.bar_line(&chars, i + 1, pending);
+                }
+                '[' => {
+                    let mut keys = Vec::new();
+                    let mut inner_length = None;
+                    let mut j = i + 1;
+                    while j < chars.len() && chars[j] != ']' {
+                        if chars[j] == ' ' {
+                            j += 1;
+                            continue;
+                        }
+                        let (key, length, next) = self
+                            .note(&chars, j, pending)
+                            .ok_or_else(|| syntax(format!("invalid chord at column {}", j + 1)))?;
+                        keys.push(key);
+                        inner_length.get_or_insert(length);
+                        // Ties inside chords
+                        j = if chars.get(next) == Some(&'-') {
+                            next + 1
+                        } else {
+                            next
+                        };
+                    }
+                    if j >= chars.len() {
+                        return Err(syntax("unclosed chord".to_string()));
+                    }
+                    let (length, next) =
+                        parse_length(&chars, j + 1).ok_or_else(|| too_long(j + 2))?;
+                    let length = inner_length
+                        .unwrap_or(Ratio::new(1, 1))
+                        .mul(length)
+                        .ok_or_else(|| too_long(i + 1))?;
+                    i = self
+                        .push_note(keys, length, &chars, next, pending)
+                        .ok_or_else(|| too_long(i + 1))?;
+                }
+
+                '|' | ':' => i = self.bar_line(&chars, i, pending),
+
+                '^' | '_' | '=' | 'A'..='G' | 'a'..='g' => {
+                    let (key, length, next) = self
+                        .note(&chars, i, pending)
+                        .ok_or_else(|| syntax(format!("invalid note at column {}", i + 1)))?;
+                    i = self
+                        .push_note(vec![key], length, &chars, next, pending)
+                        .ok_or_else(|| too_long(i + 1))?;
+                }
+
+                'z' | 'x' => {
+                    let (length, next) =
+                        parse_length(&chars, i + 1).ok_or_else(|| too_long(i + 2))?;
+                    let length = self
+                        .unit()
+                        .mul(length)
+                        .and_then(|length| self.apply_pending(length, pending))
+                        .ok_or_else(|| too_long(i + 1))?;
+                    self.current().elements.push(Element::Rest(length));
+                    i = next;
+                }
+                'Z' | 'X' => {
+                    // Multi-measure rest
+                    let mut j = i + 1;
+                    let mut bars = 0u32;
+                    while let Some(digit) = chars.get(j).and_then(|c| c.to_digit(10)) {
+                        bars = bars
+                            .checked_mul(10)
+                            .and_then(|bars| bars.checked_add(digit))
+                            .ok_or_else(|| syntax(format!("too many bars at column {}", i + 1)))?;
+                        j += 1;
+                    }
+                    let meter = self.meter;
+                    for bar in 0..bars.max(1) {
+                        if bar > 0 {
+                            self.current().bar();
+                        }
+                        self.current().elements.push(Element::Rest(meter));
+                    }
+                    i = j;
+                }
+
+                '>' | '<' => {
+                    let column = i + 1;
+                    let mut count = 0;
+                    while chars.get(i) == Some(&c) {
+                        count += 1;
+                        i += 1;
+                    }
+                    // `>` dots the previous note and shortens the next
+                    let halves = 1u64.checked_shl(count).filter(|h| *h < 1 << 62);
+                    let halves = halves.ok_or_else(|| too_long(column))?;
+                    let short = Ratio::new(1, halves);
+                    let long = Ratio::new(2 * halves - 1, halves);
+                    let (previous, next) = if c == '>' {
+                        (long, short)
+                    } else {
+                        (short, long)
+                    };
+                    if let Some(length) = self
+                        .current()
+                        .elements
+                        .iter_mut()
+                        .rev()
+                        .find_map(Element::length_mut)
+                    {
+                        *length = length.mul(previous).ok_or_else(|| too_long(column))?;
+                    }
+                    pending.broken = Some(next);
+                }
+
+                _ => return Err(syntax(format!("unexpected '{}' at column {}", c, i + 1))),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn current(&mut self) -> &mut Voice {
+        &mut self.voices[self.voice]
+    }
+
+    /// Parse a tuplet specifier `(p:q:r` starting after the parenthesis
+    fn tuplet(&self, chars: &[char], start: usize) -> Option<((u32, Ratio), usize)> {
+        let mut numbers = [None; 3];
+        let mut i = start;
+        for (n, number) in numbers.iter_mut().enumerate() {
+            if n > 0 {
+                if chars.get(i) != Some(&':') {
+                    break;
+                }
+                i += 1;
+            }
+            let mut value = None;
+            while let Some(digit) = chars.get(i).and_then(|c| c.to_digit(10)) {
+                value = Some(value.unwrap_or(0u32).checked_mul(10)?.checked_add(digit)?);
+                i += 1;
+            }
+            *number = value;
+        }
+
+        let p = numbers[0].unwrap_or(3).max(1);
+        let compound = matches!(self.meter.num, 6 | 9 | 12);
+        let q = numbers[1].unwrap_or(match p {
+            2 | 4 | 8 => 3,
+            3 | 6 => 2,
+            _ if compound => 3,
+            _ => 2,
+        });
+        let r = numbers[2].unwrap_or(p);
+        Some(((r, Ratio::new(q as u64, p as u64)), i))
+    }
+
+    /// Parse a bar line (`|`, `||`, `|]`, `|:`, `:|`, `::`, `:|2`, ...)
+    fn bar_line(&mut self, chars: &[char], start: usize, pending: &mut Pending) -> usize {
+        let mut i = start;
+        while chars.get(i).is_some_and(|c| matches!(c, '|' | ':' | ']')) {
+            i += 1;
+        }
+        let bar: String = chars[start..i].iter().collect();
+
+        pending.accidentals.clear();
+        let voice = self.current();
+        voice.bar();
+        if bar.starts_with(':') {
+            voice.repeat();
+        }
+        if bar.ends_with(':') {
+            voice.repeat_start = voice.elements.len();
+            voice.ending_start = None;
+        }
+
+        // Ending number right after the bar line (`|1`, `:|2`)
+        if let Some(digit) = chars.get(i).filter(|c| c.is_ascii_digit()) {
+            self.ending(*digit);
+            i += 1;
+        }
+        i
+    }
+
+    fn ending(&mut self, number: char) {
+        let voice = self.current();
+        if number == '1' {
+            voice.ending_start = Some(voice.elements.len());
+        }
+    }
+
+    /// Parse a single pitch with its length, returning (key, length, next index)
+    fn note(
+        &self,
+        chars: &[char],
+        start: usize,
+        pending: &mut Pending,
+    ) -> Option<(i32, Ratio, usize)> {
+        let mut i = start;
+        let mut accidental = None;
+        while let Some(c) = chars.get(i) {
+            let step = match c {
+                '^' => 1,
+                '_' => -1,
+                '=' => 0,
+                _ => break,
+            };
+            accidental = Some(accidental.unwrap_or(0) + step);
+            i += 1;
+        }
+
+        let letter = *chars.get(i)?;
+        let index = letter_index(letter)?;
+        i += 1;
+        let mut octave = if letter.is_ascii_lowercase() { 5 } else { 4 };
+        while let Some(c) = chars.get(i) {
+            match c {
+                '\'' => octave += 1,
+                ',' => octave -= 1,
+                _ => break,
+            }
+            i += 1;
+        }
+
+        // Accidentals last until the end of the bar
+        let accidental = match accidental {
+            Some(accidental) => {
+                pending.accidentals.insert((index, octave), accidental);
+                accidental
+            }
+            None => pending
+                .accidentals
+                .get(&(index, octave))
+                .copied()
+                .unwrap_or(self.key.signature[index]),
+        };
+
+        let key = (octave + 1) * 12 + LETTERS[index].1 + accidental;
+        let (length, next) = parse_length(chars, i)?;
+        Some((key, length, next))
+    }
+
+    /// Add a note or chord (with a length multiplier) and parse a following tie,
+    /// or None if its length doesn't fit
+    fn push_note(
+        &mut self,
+        keys: Vec<i32>,
+        length: Ratio,
+        chars: &[char],
+        next: usize,
+        pending: &mut Pending,
+    ) -> Option<usize> {
+        let length = self.apply_pending(self.unit().mul(length)?, pending)?;
+        let tie = chars.get(next) == Some(&'-');
+        let element = Element::Note {
+            keys,
+            length,
+            staccato: std::mem::take(&mut pending.staccato),
+            accent: std::mem::take(&mut pending.accent),
+            tie,
+        };
+        self.current().elements.push(element);
+        Some(if tie { next + 1 } else { next })
+    }
+
+    /// Apply tuplet and broken-rhythm factors to a length
+    fn apply_pending(&self, mut length: Ratio, pending: &mut Pending) -> Option<Ratio> {
+        if let Some((remaining, factor)) = pending.tuplet {
+            length = length.mul(factor)?;
+            pending.tuplet = (remaining > 1).then_some((remaining - 1, factor));
+        }
+        if let Some(factor) = pending.broken.take() {
+            length = length.mul(factor)?;
+        }
+        Some(length)
+    }
+}
+
+/// Index just past the next `end` character (or the end of the line)
+fn skip_past(chars: &[char], start: usize, end: char) -> usize {
+    chars[start.min(chars.len())..]
+        .iter()
+        .position(|c| *c == end)
+        .map_or(chars.len(), |offset| start + offset + 1)
+}
+
+/// Parse a length multiplier (`2`, `/`, `//`, `3/2`, `/4`), or None if it
+/// doesn't fit
+fn parse_length(chars: &[char], start: usize) -> Option<(Ratio, usize)> {
+    let mut i = start;
+    let number = |i: &mut usize| {
+        let mut value = None;
+        while let Some(digit) = chars.get(*i).and_then(|c| c.to_digit(10)) {
+            value = Some(
+                value
+                    .unwrap_or(0u64)
+                    .checked_mul(10)?
+                    .checked_add(digit as u64)?,
+            );
+            *i += 1;
+        }
+        Some(value)
+    };
+
+    let num = number(&mut i)?.unwrap_or(1);
+    let mut den: u64 = 1;
+    if chars.get(i) == Some(&'/') {
+        i += 1;
+        match number(&mut i)? {
+            Some(value) => den = value.max(1),
+            None => {
+                den = 2;
+                while chars.get(i) == Some(&'/') {
+                    den = den.checked_mul(2)?;
+                    i += 1;
+                }
+            }
+        }
+    }
+    Some((Ratio::new(num, den), i))
+}
+
+/// Parse `n/d`
+fn parse_fraction(value: &str) -> Option<Ratio> {
+    let (num, den) = value.split_once('/')?;
+    let num: u64 = num.trim().parse().ok()?;
+    let den: u64 = den.trim().parse().ok()?;
+    (num > 0 && den > 0).then(|| Ratio::new(num, den))
+}
+
+/// Parse a Q: field as quarter notes per minute (`1/4=120`, `3/8=80`, `120`)
+fn parse_tempo(value: &str) -> Option<u32> {
+    // Drop quoted tempo text such as "Allegro"
+    let value: String = value.split('"').step_by(2).collect();
+    let (beat, bpm) = match value.split_once('=') {
+        Some((beat, bpm)) => {
+            let beat = beat
+                .split_whitespace()
+                .filter_map(parse_fraction)
+                .try_fold(Ratio::new(0, 1), Ratio::add)?;
+            (beat, bpm)
+        }
+        None => (Ratio::new(1, 4), value.as_str()),
+    };
+    let bpm: f64 = bpm.trim().parse().ok()?;
+    let beat = if beat.num == 0 {
+        Ratio::new(1, 4)
+    } else {
+        beat
+    };
+    Some((bpm * beat.as_f64() * 4.0).round() as u32)
+}
+
+/// The `name="..."` (or `nm="..."`) property of a V: field
+fn parse_voice_name(value: &str) -> Option<String> {
+    let start = value
+        .find("name=\"")
+        .map(|i| i + 6)
+        .or_else(|| value.find("nm=\"").map(|i| i + 4))?;
+    let end = value[start..].find('"')?;
+    Some(value[start..start + end].to_string())
+}
+
+/// Remove a `%` comment (but not an escaped `\%`)
+fn strip_comment(line: &str) -> &str {
+    let mut previous = None;
+    for (i, c) in line.char_indices() {
+        if c == '%' && previous != Some('\\') {
+            return &line[..i];
+        }
+        previous = Some(c);
+    }
+    line
+}
+
+/// Parse the first tune of an ABC file
+fn parse(abc: &str) -> Result<Tune, AbcError> {
+    let mut tune = Tune::new();
+    let mut pending = Pending::default();
+    let mut started = false;
+
+    for (index, raw) in abc.lines().enumerate() {
+        let number = index + 1;
+        let line = strip_comment(raw).trim_end();
+        let trimmed = line.trim_start();
+
+        if trimmed.is_empty() {
+            // A blank line ends the tune
+            if tune.in_body && raw.trim().is_empty() {
+                break;
+            }
+            continue;
+        }
+
+        let mut field = trimmed.chars();
+        if let (Some(name), Some(':')) = (field.next(), field.next()) {
+            if name.is_ascii_alphabetic() && !trimmed.starts_with("|:") {
+                if name == 'X' {
+                    if started {
+                        break;
+                    }
+                    started = true;
+                }
+                if tune.in_body && name == 'V' {
+                    pending.accidentals.clear();
+                }
+                tune.field(name, &trimmed[2..], number)?;
+                continue;
+            }
+        }
+
+        if tune.in_body {
+            tune.music(trimmed, number, &mut pending)?;
+        }
+    }
+
+    Ok(tune)
+}
+
+/// Merge tied notes of the same pitch into one longer note
+fn merge_ties(elements: Vec<Element>) -> Vec<Element> {
+    let mut merged: Vec<Element> = Vec::with_capacity(elements.len());
+    // Index in `merged` of a note waiting for its tied continuation
+    let mut open_tie: Option<usize> = None;
+
+    for element in elements {
+        if let (
+            Some(index),
+            Element::Note {
+                keys, length, tie, ..
+            },
+        ) = (open_tie, &element)
+        {
+            if let Element::Note {
+                keys: tied_keys,
+                length: tied_length,
+                tie: tied,
+                ..
+            } = &mut merged[index]
+            {
+                // A tie too long to add up is left as separate notes
+                if let Some(sum) = tied_length.add(*length).filter(|_| tied_keys == keys) {
+                    *tied_length = sum;
+                    *tied = *tie;
+                    open_tie = tie.then_some(index);
+                    continue;
+                }
+            }
+        }
+
+        match &element {
+            Element::Note { tie, .. } => open_tie = tie.then_some(merged.len()),
+            Element::Rest(_) => open_tie = None,
+            Element::Bar => {}
+        }
+        merged.push(element);
+    }
+
+    merged
+}
+
+/// Pitch names usable in `set key` (`A` alone would read as an interval)
+fn key_name(pitch_class: i32, flats: bool) -> Option<&'static str> {
+    const SHARPS: [Option<&str>; 12] = [
+        Some("C"),
+        Some("C#"),
+        Some("D"),
+        Some("D#"),
+        Some("E"),
+        Some("F"),
+        Some("F#"),
+        Some("G"),
+        Some("G#"),
+        None,
+        Some("A#"),
+        Some("B"),
+    ];
+    const FLATS: [Option<&str>; 12] = [
+        Some("C"),
+        Some("Db"),
+        Some("D"),
+        Some("Eb"),
+        Some("E"),
+        Some("F"),
+        Some("Gb"),
+        Some("G"),
+        Some("Ab"),
+        None,
+        Some("Bb"),
+        Some("B"),
+    ];
+    let names = if flats { FLATS } else { SHARPS };
+    names[pitch_class.rem_euclid(12) as usize]
+}
+
+/// Interval name for a number of semitones above the root (`R`, `M3`, `P12`, ...)
+fn interval_name(semitones: i32) -> String {
+    const QUALITIES: [(char, i32); 12] = [
+        ('P', 1),
+        ('m', 2),
+        ('M', 2),
+        ('m', 3),
+        ('M', 3),
+        ('P', 4),
+        ('d', 5),
+        ('P', 5),
+        ('m', 6),
+        ('M', 6),
+        ('m', 7),
+        ('M', 7),
+    ];
+    if semitones == 0 {
+        return "R".to_string();
+    }
+    let (quality, degree) = QUALITIES[semitones.rem_euclid(12) as usize];
+    format!("{}{}", quality, degree + 7 * semitones.div_euclid(12))
+}
+
+/// Duration in beats (quarter notes) of `count` slots of `length`, as
+/// written after `|...|:`
+fn beats_literal(length: Ratio, count: usize) -> String {
+    // Widened so long runs of long notes can't overflow
+    let num = u128::from(length.num) * count as u128 * 4;
+    let den = u128::from(length.den);
+    if num.is_multiple_of(den) {
+        (num / den).to_string()
+    } else {
+        let text = format!("{:.4}", num as f64 / den as f64);
+        text.trim_end_matches('0').to_string()
+    }
+}
+
+/// Write a voice as a `++` chain of blocks, one bar per line
+fn write_voice(out: &mut String, binding: &str, elements: &[Element], root: i32) {
+    let slot = |element: &Element| match element {
+        Element::Note {
+            keys,
+            staccato,
+            accent,
+            ..
+        } => {
+            let mut text = if keys.len() == 1 {
+                interval_name(keys[0] - root)
+            } else {
+                let names: Vec<String> = keys.iter().map(|k| interval_name(k - root)).collect();
+                format!("[{}]", names.join(", "))
+            };
+            if *staccato {
+                text.push('*');
+            }
+            if *accent {
+                text.push('^');
+            }
+            text
+        }
+        _ => "-".to_string(),
+    };
+
+    let mut bars = Vec::new();
+    for bar in elements.split(|e| matches!(e, Element::Bar)) {
+        // Runs of equal-length slots share one relative-rhythm block
+        let mut blocks = Vec::new();
+        let mut run: Vec<&Element> = Vec::new();
+        let mut run_length = None;
+        let mut flush = |run: &mut Vec<&Element>, length: Option<Ratio>| {
+            if let Some(length) = length.filter(|_| !run.is_empty()) {
+                let slots: Vec<String> = run.iter().map(|e| slot(e)).collect();
+                let beats = beats_literal(length, run.len());
+                blocks.push(if beats == "1" {
+                    format!("| {} |", slots.join(" "))
+                } else {
+                    format!("| {} |:{}", slots.join(" "), beats)
+                });
+            }
+            run.clear();
+        };
+        for element in bar {
+            let length = match element {
+                Element::Note { length, .. } | Element::Rest(length) => *length,
+                Element::Bar => continue,
+            };
+            if run_length != Some(length) {
+                flush(&mut run, run_length);
+                run_length = Some(length);
+            }
+            run.push(element);
+        }
+        flush(&mut run, run_length);
+        if !blocks.is_empty() {
+            bars.push(blocks.join(" ++ "));
+        }
+    }
+
+    let _ = writeln!(out, "let {} = {}", binding, bars.join(" ++\n  "));
+}
+
+/// Convert the first tune of an ABC file to relanote source
+pub fn abc_to_rela(abc: &str) -> Result<String, AbcError> {
+    let tune = parse(abc)?;
+    let voices: Vec<(&Voice, Vec<Element>)> = tune
+        .voices
+        .iter()
+        .map(|voice| (voice, merge_ties(voice.elements.clone())))
+        .filter(|(_, elements)| elements.iter().any(|e| matches!(e, Element::Note { .. })))
+        .collect();
+
+    // Root: the tonic at or below the lowest note, so every interval is ascending
+    let lowest = voices
+        .iter()
+        .flat_map(|(_, elements)| elements.iter())
+        .filter_map(|element| match element {
+            Element::Note { keys, .. } => keys.iter().min().copied(),
+            _ => None,
+        })
+        .min()
+        .ok_or(AbcError::Empty)?;
+    let tonic = match key_name(tune.key.tonic, tune.key.flats) {
+        Some(_) => tune.key.tonic,
+        None => 0,
+    };
+    let root = lowest - (lowest - tonic).rem_euclid(12);
+    let key = key_name(tonic, tune.key.flats).unwrap_or("C");
+
+    let mut out = String::new();
+    if let Some(title) = &tune.title {
+        let _ = writeln!(out, "; {}", title);
+    }
+    out.push_str("; Converted from ABC notation\n");
+    if tonic != tune.key.tonic {
+        out.push_str(
+            "; (intervals are relative to C: the key's tonic can't be written in `set key`)\n",
+        );
+    }
+    out.push('\n');
+    let _ = writeln!(out, "set tempo = {}", tune.tempo.unwrap_or(120));
+    let _ = writeln!(out, "set key = {}{}", key, root.div_euclid(12) - 1);
+
+    let mut bindings = Vec::new();
+    for (index, (voice, elements)) in voices.iter().enumerate() {
+        let binding = if voices.len() == 1 {
+            "melody".to_string()
+        } else {
+            format!("voice{}", index + 1)
+        };
+        out.push('\n');
+        if voices.len() > 1 {
+            let _ = writeln!(out, "; V:{}", voice.name.as_deref().unwrap_or(&voice.id));
+        }
+        write_voice(&mut out, &binding, elements, root);
+        bindings.push(format!("  {}", binding));
+    }
+
+    let _ = write!(out, "\nlayer [\n{}\n]\n", bindings.join(",\n"));
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_signatures() {
+        let d = Key::parse("D").unwrap();
+        assert_eq!(d.tonic, 2);
+        assert_eq!(d.signature, [1, 0, 0, 1, 0, 0, 0]);
+
+        let a_dorian = Key::parse("Ador").unwrap();
+        assert_eq!(a_dorian.tonic, 9);
+        assert_eq!(a_dorian.signature, [0, 0, 0, 1, 0, 0, 0]);
+
+        let e_flat = Key::parse("Eb").unwrap();
+        assert_eq!(e_flat.signature, [0, 0, -1, 0, 0, -1, -1]);
+        assert!(e_flat.flats);
+    }
+
+    #[test]
+    fn test_interval_names() {
+        assert_eq!(interval_name(0), "R");
+        assert_eq!(interval_name(7), "P5");
+        assert_eq!(interval_name(12), "P8");
+        assert_eq!(interval_name(16), "M10");
+    }
+
+    #[test]
+    fn test_converts_rhythm_and_key() {
+        let abc = "X:1\nT:Test Reel\nM:4/4\nL:1/8\nQ:1/4=100\nK:D\n\
+                   DEFG A2 d2 | f>e dB A4 |]\n";
+        let rela = abc_to_rela(abc).unwrap();
+        assert!(rela.contains("; Test Reel"));
+        assert!(rela.contains("set tempo = 100"));
+        assert!(rela.contains("set key = D4"));
+        // F is sharp in D major; dotted rhythm splits into single-note blocks
+        assert!(rela.contains("| R M2 M3 P4 |:2 ++ | P5 P8 |:2 ++"));
+        assert!(rela.contains("| M10 |:0.75 ++ | M9 |:0.25 ++ | P8 M6 | ++ | P5 |:2"));
+    }
+
+    #[test]
+    fn test_repeats_and_ties() {
+        let abc = "X:1\nL:1/4\nK:C\n|: C D |1 E2- | E :|2 G4 |]\n";
+        let rela = abc_to_rela(abc).unwrap();
+        // First time: C D E (tied over the bar); second time: C D G
+        assert!(
+            rela.contains("let melody = | R M2 |:2 ++\n  | M3 |:3 ++\n  | R M2 |:2 ++\n  | P5 |:4")
+        );
+    }
+
+    #[test]
+    fn test_lengths_that_overflow() {
+        for body in [
+            "c3/4999999999999999999",
+            "c99999999999999999999",
+            "z/////////////////////////////////////////////////////////////////",
+            "(99999999999 c",
+            "c>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>c",
+        ] {
+            let abc = format!("X:1\nK:C\n{}|]\n", body);
+            assert!(
+                matches!(abc_to_rela(&abc), Err(AbcError::Syntax { line: 3, .. })),
+                "{}",
+                body
+            );
+        }
+        // Long notes still add up
+        let rela = abc_to_rela("X:1\nL:1/4\nK:C\nC999999999999999999 |]\n").unwrap();
+        assert!(rela.contains("| R |:999999999999999999"));
+    }
+
+    #[test]
+    fn test_empty_tune() {
+        assert!(matches!(
+            abc_to_rela("X:1\nK:C\nz4 |]\n"),
+            Err(AbcError::Empty)
+        ));
+    }
+}
@@ -0,0 +1,545 @@
+//! ABC notation export and import
+//!
+//! [ABC](https://abcnotation.com/wiki/abc:standard:v2.1) is a plain-text
+//! notation widely used for folk and traditional tune collections. Songs are
+//! exported as one ABC voice per instrument; ABC tunes can be converted into
+//! relanote source with [`abc_to_rela`].
+
+mod import;
+
+use std::collections::HashMap;
+
+use relanote_ast::Articulation;
+use relanote_eval::value::{tuplet_shares, BlockValue, NoteMeta, PartValue, SlotValue, SongValue};
+
+use crate::drums::DrumKeys;
+use crate::timeline::Timeline;
+
+pub use import::{abc_to_rela, AbcError};
+
+/// Internal time resolution; divisible by every tuplet size up to 10
+const TICKS_PER_BEAT: u32 = 2520;
+
+/// ABC export configuration
+#[derive(Clone)]
+pub struct AbcConfig {
+    /// Tune title (T: field)
+    pub title: String,
+    /// Composer (C: field)
+    pub composer: Option<String>,
+    /// Tempo in quarter notes per minute (Q: field)
+    pub tempo: u32,
+    /// MIDI note of the root (R), as in `MidiConfig`
+    pub base_note: u8,
+    /// Quarter-note beats per bar (M: field is `beats_per_bar/4`)
+    pub beats_per_bar: u32,
+    /// Denominator of the unit note length (L: field is `1/unit_length`)
+    pub unit_length: u32,
+    /// Bars written on each line of music
+    pub bars_per_line: usize,
+}
+
+impl Default for AbcConfig {
+    fn default() -> Self {
+        Self {
+            title: "Untitled".to_string(),
+            composer: None,
+            tempo: 120,
+            base_note: 60,
+            beats_per_bar: 4,
+            unit_length: 8,
+            bars_per_line: 4,
+        }
+    }
+}
+
+/// A note or chord placed on a voice's timeline
+struct Placed<'a> {
+    start: u32,
+    duration: u32,
+    keys: Vec<u8>,
+    articulations: &'a [Articulation],
+    marker: Option<&'a str>,
+    /// The last slot sounding in this note, which later slots may tie into
+    tail: &'a SlotValue,
+}
+
+/// All notes of one instrument across the song
+struct Voice<'a> {
+    name: String,
+    drums: bool,
+    notes: Vec<Placed<'a>>,
+}
+
+/// ABC notation renderer
+pub struct AbcRenderer {
+    config: AbcConfig,
+}
+
+impl AbcRenderer {
+    pub fn new(config: AbcConfig) -> Self {
+        Self { config }
+    }
+
+    /// Render a song to an ABC tune
+    pub fn render(&self, song: &SongValue) -> String {
+        let bar_ticks = self.config.beats_per_bar.max(1) * TICKS_PER_BEAT;
+
+        // Sections follow each other on bar lines, as in the MIDI output
+        let section_lengths: Vec<u32> = song
+            .sections
+            .iter()
+            .map(|section| section.parts.iter().map(part_ticks).max().unwrap_or(0))
+            .collect();
+        let timeline = Timeline::new(&section_lengths, bar_ticks, false);
+
+        // One voice per instrument, in order of first appearance
+        let mut voices: Vec<Voice> = Vec::new();
+        let end = timeline.end().unwrap_or(u32::MAX);
+        for (index, section) in song.sections.iter().enumerate() {
+            let start = timeline.section_start(index);
+            let base_note = section.base_note(self.config.base_note);
+            for part in &section.parts {
+                let position = match voices.iter().position(|v| v.name == part.instrument) {
+                    Some(position) => position,
+                    None => {
+                        voices.push(Voice {
+                            name: part.instrument.clone(),
+                            drums: DrumKeys::for_part(part).is_some(),
+                            notes: Vec::new(),
+                        });
+                        voices.len() - 1
+                    }
+                };
+                self.place_part(&mut voices[position].notes, part, start, base_note);
+            }
+        }
+        let bars = end.div_ceil(bar_ticks).max(1);
+
+        let mut abc = String::new();
+        abc.push_str("X:1\n");
+        abc.push_str(&format!("T:{}\n", self.config.title));
+        if let Some(composer) = &self.config.composer {
+            abc.push_str(&format!("C:{}\n", composer));
+        }
+        abc.push_str(&format!("M:{}/4\n", self.config.beats_per_bar));
+        abc.push_str(&format!("L:1/{}\n", self.config.unit_length));
+        abc.push_str(&format!("Q:1/4={}\n", self.config.tempo));
+        abc.push_str("K:C\n");
+
+        for (index, voice) in voices.iter_mut().enumerate() {
+            abc.push_str(&format!("V:{} name=\"{}\"\n", index + 1, voice.name));
+            if voice.drums {
+                abc.push_str("%%MIDI channel 10\n");
+            }
+            abc.push_str(&self.write_voice(&mut voice.notes, bars, bar_ticks));
+        }
+
+        abc
+    }
+
+    /// Place a part's notes starting at `start` (in ticks), relative to `base_note`
+    fn place_part<'a>(
+        &self,
+        notes: &mut Vec<Placed<'a>>,
+        part: &'a PartValue,
+        start: u32,
+        base_note: u8,
+    ) {
+        let drums = DrumKeys::for_part(part);
+        let mut time = start;
+        for block in &part.blocks {
+            for (slot, duration) in slot_ticks(block) {
+                self.place_slot(notes, slot, time, duration, base_note, drums.as_ref());
+                time = time.saturating_add(duration);
+            }
+        }
+    }
+
+    fn place_slot<'a>(
+        &self,
+        notes: &mut Vec<Placed<'a>>,
+        slot: &'a SlotValue,
+        time: u32,
+        duration: u32,
+        base_note: u8,
+        drums: Option<&DrumKeys>,
+    ) {
+        // A tied slot lengthens the note it continues
+        if let Some(last) = notes.last_mut() {
+            if last.start + last.duration == time && last.tail.ties_into(slot) {
+                last.duration += duration;
+                last.tail = slot;
+                return;
+            }
+        }
+
+        let key = |meta: &NoteMeta, cents: f64| {
+            let pitched = (base_note as f64 + cents / 100.0).round().clamp(0.0, 127.0) as u8;
+            match drums {
+                Some(drums) => drums.key(meta, cents, pitched),
+                None => pitched,
+            }
+        };
+
+        match slot {
+            SlotValue::Note {
+                interval,
+                articulations,
+                meta,
+                ..
+            } => notes.push(Placed {
+                start: time,
+                duration,
+                keys: vec![key(meta, interval.cents)],
+                articulations,
+                marker: meta.marker.as_deref(),
+                tail: slot,
+            }),
+            SlotValue::Chord {
+                intervals,
+                articulations,
+                meta,
+                ..
+            } => notes.push(Placed {
+                start: time,
+                duration,
+                keys: intervals.iter().map(|i| key(meta, i.cents)).collect(),
+                articulations,
+                marker: meta.marker.as_deref(),
+                tail: slot,
+            }),
+            SlotValue::Rest { .. } => {}
+            SlotValue::Tuplet { slots, .. } => {
+                for (inner_slot, offset, share) in tuplet_shares(slots) {
+                    let inner_start = (duration as f64 * offset).round() as u32;
+                    let inner_end = (duration as f64 * (offset + share)).round() as u32;
+                    self.place_slot(
+                        notes,
+                        inner_slot,
+                        time.saturating_add(inner_start),
+                        inner_end - inner_start,
+                        base_note,
+                        drums,
+                    );
+                }
+            }
+            SlotValue::Overlay { voices } => {
+                // Overlaid notes starting together are written as chords
+                for voice in voices {
+                    let mut time = time;
+                    for (slot, duration) in slot_ticks(voice) {
+                        self.place_slot(notes, slot, time, duration, base_note, drums);
+                        time += duration;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Write one voice's music, filling gaps with rests
+    ///
+    /// ABC voices are monophonic apart from chords: notes starting together
+    /// become one chord and a note is cut short where the next one starts.
+    fn write_voice(&self, notes: &mut [Placed], bars: u32, bar_ticks: u32) -> String {
+        notes.sort_by_key(|note| note.start);
+
+        let mut chords: Vec<Placed> = Vec::new();
+        for note in notes.iter_mut() {
+            match chords.last_mut() {
+                Some(last) if last.start == note.start => {
+                    last.keys.append(&mut note.keys);
+                    continue;
+                }
+                Some(last) if last.start + last.duration > note.start => {
+                    last.duration = note.start - last.start;
+                }
+                _ => {}
+            }
+            chords.push(Placed {
+                start: note.start,
+                duration: note.duration,
+                keys: std::mem::take(&mut note.keys),
+                articulations: note.articulations,
+                marker: note.marker,
+                tail: note.tail,
+            });
+        }
+
+        let mut writer = BarWriter::new(self, bar_ticks);
+        let mut time = 0;
+        for chord in chords.iter_mut().filter(|chord| chord.duration > 0) {
+            chord.keys.sort_unstable();
+            chord.keys.dedup();
+            writer.rest(time, chord.start - time);
+            writer.chord(chord);
+            time = chord.start.saturating_add(chord.duration);
+        }
+        writer.rest(time, bars.saturating_mul(bar_ticks).saturating_sub(time));
+        writer.finish()
+    }
+
+    /// Length of a duration in unit notes, e.g. "", "2", "/2", "3/2"
+    fn length(&self, ticks: u32) -> String {
+        let unit = TICKS_PER_BEAT * 4 / self.config.unit_length.max(1);
+        let divisor = gcd(ticks, unit);
+        match (ticks / divisor, unit / divisor) {
+            (1, 1) => String::new(),
+            (n, 1) => n.to_string(),
+            (1, d) => format!("/{}", d),
+            (n, d) => format!("{}/{}", n, d),
+        }
+    }
+}
+
+/// Writes notes and rests, splitting them at bar lines
+struct BarWriter<'a> {
+    renderer: &'a AbcRenderer,
+    bar_ticks: u32,
+    out: String,
+    bar: u32,
+    /// Bars written on the current line
+    line_bars: usize,
+    /// Accidentals written so far in the current bar, by (letter, octave)
+    accidentals: HashMap<(char, i32), i32>,
+}
+
+impl<'a> BarWriter<'a> {
+    fn new(renderer: &'a AbcRenderer, bar_ticks: u32) -> Self {
+        Self {
+            renderer,
+            bar_ticks,
+            out: String::new(),
+            bar: 0,
+            line_bars: 0,
+            accidentals: HashMap::new(),
+        }
+    }
+
+    fn rest(&mut self, start: u32, duration: u32) {
+        for (bar, ticks) in self.pieces(start, duration) {
+            self.advance_to(bar);
+            let length = self.renderer.length(ticks);
+            self.out.push_str(&format!("z{} ", length));
+        }
+    }
+
+    /// Write a note or chord, tying it across bar lines
+    fn chord(&mut self, chord: &Placed) {
+        let pieces = self.pieces(chord.start, chord.duration);
+        let count = pieces.len();
+        for (i, (bar, ticks)) in pieces.into_iter().enumerate() {
+            self.advance_to(bar);
+            let mut text = String::new();
+            if i == 0 {
+                if let Some(marker) = chord.marker {
+                    text.push_str(&format!("\"^{}\"", marker.replace('"', "'")));
+                }
+                if chord.articulations.contains(&Articulation::Accent) {
+                    text.push('L');
+                }
+                if chord.articulations.contains(&Articulation::Staccato) {
+                    text.push('.');
+                }
+            }
+
+            let pitches: Vec<String> = chord.keys.iter().map(|key| self.pitch(*key)).collect();
+            if pitches.len() == 1 {
+                text.push_str(&pitches[0]);
+            } else {
+                text.push_str(&format!("[{}]", pitches.concat()));
+            }
+            text.push_str(&self.renderer.length(ticks));
+            if i + 1 < count {
+                text.push('-');
+            }
+            self.out.push_str(&text);
+            self.out.push(' ');
+        }
+    }
+
+    /// Split a span at bar lines into (bar, length) pieces
+    fn pieces(&self, start: u32, duration: u32) -> Vec<(u32, u32)> {
+        let mut pieces = Vec::new();
+        let mut time = start;
+        let end = start.saturating_add(duration);
+        while time < end {
+            let bar = time / self.bar_ticks;
+            let piece_end = end.min((bar + 1).saturating_mul(self.bar_ticks));
+            pieces.push((bar, piece_end - time));
+            time = piece_end;
+        }
+        pieces
+    }
+
+    /// Write bar lines (and line breaks) until `bar` is the current bar
+    fn advance_to(&mut self, bar: u32) {
+        while self.bar < bar {
+            self.bar += 1;
+            self.accidentals.clear();
+            self.line_bars += 1;
+            if self.line_bars >= self.renderer.config.bars_per_line {
+                self.line_bars = 0;
+                self.out.push_str("|\n");
+            } else {
+                self.out.push_str("| ");
+            }
+        }
+    }
+
+    /// ABC pitch for a MIDI key, with the accidental needed in this bar
+    fn pitch(&mut self, key: u8) -> String {
+        const SPELLING: [(char, i32); 12] = [
+            ('C', 0),
+            ('C', 1),
+            ('D', 0),
+            ('D', 1),
+            ('E', 0),
+            ('F', 0),
+            ('F', 1),
+            ('G', 0),
+            ('G', 1),
+            ('A', 0),
+            ('A', 1),
+            ('B', 0),
+        ];
+        let (letter, accidental) = SPELLING[key as usize % 12];
+        let octave = key as i32 / 12 - 1;
+
+        let mut pitch = String::new();
+        let current = self
+            .accidentals
+            .get(&(letter, octave))
+            .copied()
+            .unwrap_or(0);
+        if current != accidental {
+            pitch.push(if accidental == 1 { '^' } else { '=' });
+            self.accidentals.insert((letter, octave), accidental);
+        }
+        if octave >= 5 {
+            pitch.push(letter.to_ascii_lowercase());
+            pitch.push_str(&"'".repeat((octave - 5) as usize));
+        } else {
+            pitch.push(letter);
+            pitch.push_str(&",".repeat((4 - octave) as usize));
+        }
+        pitch
+    }
+
+    fn finish(mut self) -> String {
+        let mut out = std::mem::take(&mut self.out);
+        let trimmed = out.trim_end().len();
+        out.truncate(trimmed);
+        out.push_str(" |]\n");
+        out
+    }
+}
+
+/// Length of a part in ticks, saturating for parts too long to count
+fn part_ticks(part: &PartValue) -> u32 {
+    part.blocks
+        .iter()
+        .flat_map(slot_ticks)
+        .fold(0, |total: u32, (_, ticks)| total.saturating_add(ticks))
+}
+
+/// Duration in ticks of each slot of a block (see `MidiRenderer::slot_ticks`)
+fn slot_ticks(block: &BlockValue) -> impl Iterator<Item = (&SlotValue, u32)> {
+    let slot_count = block.slots.len();
+    let default_slot_duration = if slot_count > 0 {
+        (block.beats * TICKS_PER_BEAT as f64).round() as u32 / slot_count as u32
+    } else {
+        0
+    };
+
+    block.slots.iter().map(move |slot| {
+        let ticks = slot
+            .duration_beats()
+            .map(|beats| (beats * TICKS_PER_BEAT as f64).round() as u32)
+            .unwrap_or(default_slot_duration);
+        (slot, ticks)
+    })
+}
+
+fn gcd(a: u32, b: u32) -> u32 {
+    if b == 0 {
+        a.max(1)
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// Render a song to ABC notation with default settings
+pub fn render_to_abc(song: &SongValue) -> String {
+    AbcRenderer::new(AbcConfig::default()).render(song)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use relanote_eval::value::{IntervalValue, NoteMeta, SectionValue};
+
+    fn note(semitones: i32) -> SlotValue {
+        SlotValue::Note {
+            interval: IntervalValue::from_semitones(semitones),
+            articulations: vec![],
+            duration_beats: None,
+            meta: NoteMeta::default(),
+        }
+    }
+
+    fn song_of(slots: Vec<SlotValue>, beats: f64) -> SongValue {
+        SongValue::new(vec![SectionValue::new(
+            "Test",
+            vec![PartValue::new(
+                "Test",
+                vec![BlockValue::with_beats(slots, beats)],
+            )],
+        )])
+    }
+
+    /// The music lines of an ABC tune (everything after the voice header)
+    fn body(abc: &str) -> &str {
+        &abc[abc.find("name=\"Test\"\n").unwrap() + 12..]
+    }
+
+    #[test]
+    fn test_ties_notes_across_bar_lines() {
+        let song = song_of(vec![note(0), note(6), note(5)], 9.0);
+        let abc = render_to_abc(&song);
+        assert!(abc.starts_with("X:1\nT:Untitled\nM:4/4\nL:1/8\nQ:1/4=120\nK:C\n"));
+        // Accidentals are rewritten after each bar line
+        assert_eq!(body(&abc), "C6 ^F2- | ^F4 =F4- | F2 z6 |]\n");
+    }
+
+    #[test]
+    fn test_tied_notes_become_one() {
+        let tied = SlotValue::Note {
+            interval: IntervalValue::from_semitones(0),
+            articulations: vec![Articulation::Tie],
+            duration_beats: None,
+            meta: NoteMeta::default(),
+        };
+        let song = song_of(vec![tied, note(0), note(2)], 6.0);
+        assert_eq!(body(&render_to_abc(&song)), "C8 | D4 z4 |]\n");
+    }
+
+    #[test]
+    fn test_chords_and_markers() {
+        let chord = SlotValue::Chord {
+            intervals: vec![
+                IntervalValue::from_semitones(0),
+                IntervalValue::from_semitones(16),
+                IntervalValue::from_semitones(-5),
+            ],
+            articulations: vec![Articulation::Staccato],
+            duration_beats: None,
+            meta: NoteMeta {
+                marker: Some("Intro".to_string()),
+                ..NoteMeta::default()
+            },
+        };
+        let abc = render_to_abc(&song_of(vec![chord], 1.0));
+        assert_eq!(body(&abc), "\"^Intro\".[G,Ce]2 z6 |]\n");
+    }
+}
@@ -0,0 +1,847 @@
+//! Notes with the synths that play them, for hosts that synthesize audio
+//! themselves (the playground's WebAudio engine, editor previews)
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+use relanote_eval::{EffectValue, PartValue, Value};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::RenderContext;
+
+/// Synth oscillator data for WebAudio
+#[derive(Serialize, Deserialize, Clone)]
+pub struct OscillatorData {
+    pub waveform: String, // "sine" | "square" | "sawtooth" | "triangle" | "noise" | "pink_noise" | "brown_noise" | "pulse" | "sample"
+    pub pulse_duty: f64,  // Duty cycle for pulse wave (0.0-1.0)
+    pub mix: f64,         // Volume mix (0.0-1.0)
+    pub octave_offset: i8, // Octave offset (-2 to +2)
+    pub detune_cents: f64, // Detune in cents
+    pub sample: Option<SampleData>, // WAV file a "sample" oscillator plays
+}
+
+/// WAV file data for WebAudio
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SampleData {
+    pub path: String,                    // Path or URL of the file
+    pub root: f64,                       // MIDI note the file sounds at as is
+    pub loop_points: Option<(f64, f64)>, // Loop start and end in seconds
+}
+
+/// Why a sample could not be loaded
+#[derive(Debug, Error)]
+pub enum SampleError {
+    #[error("could not read sample '{path}': {source}")]
+    Read {
+        path: String,
+        source: std::io::Error,
+    },
+
+    #[error("could not decode sample '{path}': {source}")]
+    Decode {
+        path: String,
+        source: relanote_synth::WavError,
+    },
+}
+
+/// WAV files for the synths' sample oscillators, each read from disk once
+///
+/// A file that can't be loaded isn't tried again: its error is kept for the
+/// caller to report, once.
+#[derive(Default)]
+pub struct Samples {
+    /// Loaded files, or None for files that failed
+    files: HashMap<String, Option<relanote_synth::Sample>>,
+    errors: Vec<SampleError>,
+}
+
+impl Samples {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The WAV file at `path`, mixed down to mono, or None if it can't be
+    /// loaded
+    pub fn load(&mut self, path: &str) -> Option<relanote_synth::Sample> {
+        if let Some(sample) = self.files.get(path) {
+            return sample.clone();
+        }
+        let sample = match Self::read(path) {
+            Ok(sample) => Some(sample),
+            Err(error) => {
+                self.errors.push(error);
+                None
+            }
+        };
+        self.files.insert(path.to_string(), sample.clone());
+        sample
+    }
+
+    /// Errors for the files that failed to load since the last call
+    pub fn take_errors(&mut self) -> Vec<SampleError> {
+        std::mem::take(&mut self.errors)
+    }
+
+    fn read(path: &str) -> Result<relanote_synth::Sample, SampleError> {
+        let bytes = std::fs::read(path).map_err(|source| SampleError::Read {
+            path: path.to_string(),
+            source,
+        })?;
+        relanote_synth::Sample::from_wav(&bytes).map_err(|source| SampleError::Decode {
+            path: path.to_string(),
+            source,
+        })
+    }
+}
+
+/// ADSR envelope data for WebAudio
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ADSRData {
+    pub attack: f64,  // Attack time in seconds
+    pub decay: f64,   // Decay time in seconds
+    pub sustain: f64, // Sustain level (0.0-1.0)
+    pub release: f64, // Release time in seconds
+}
+
+/// Filter data for WebAudio
+#[derive(Serialize, Deserialize, Clone)]
+pub struct FilterData {
+    pub filter_type: String, // "lowpass" | "highpass" | "bandpass"
+    pub cutoff: f64,         // Cutoff frequency in Hz
+    pub resonance: f64,      // Q/resonance (0.0-1.0)
+}
+
+/// Pitch envelope data for WebAudio (used for drum sounds like kicks)
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PitchEnvelopeData {
+    pub start_hz: f64,     // Starting frequency in Hz
+    pub end_hz: f64,       // Ending frequency in Hz
+    pub time_seconds: f64, // Duration of the pitch sweep
+}
+
+/// LFO data for WebAudio
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LfoData {
+    pub target: String, // "pitch" | "cutoff" | "amplitude"
+    pub shape: String,  // "sine" | "triangle" | "square" | "sawtooth"
+    pub rate: f64,      // Rate in Hz
+    pub depth: f64,     // Semitones for pitch, Hz for cutoff, 0.0-1.0 for amplitude
+}
+
+/// An effect in a part's or bus's chain, for WebAudio
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EffectData {
+    Reverb {
+        level: f64,
+    },
+    Delay {
+        time_ms: f64,
+        feedback: f64,
+        mix: f64,
+    },
+    Phaser {
+        rate: f64,
+        depth: f64,
+        mix: f64,
+    },
+    Distortion {
+        amount: f64,
+        curve: String, // "soft" | "hard" | "fuzz" | "bitcrush"
+        mix: f64,
+    },
+    Compressor {
+        threshold: f64, // dB
+        ratio: f64,
+        attack_ms: f64,
+        release_ms: f64,
+    },
+    /// Gains in dB of a low shelf at 250 Hz, a peak at 1 kHz and a high
+    /// shelf at 4 kHz
+    Eq {
+        low: f64,
+        mid: f64,
+        high: f64,
+    },
+    Chorus {
+        rate: f64,
+        depth: f64,
+        mix: f64,
+    },
+    /// 0.0 is mono, 1.0 leaves the stereo image as is, 2.0 is widest
+    Width {
+        amount: f64,
+    },
+}
+
+/// A bus notes are sent to, for WebAudio
+#[derive(Serialize, Deserialize, Clone)]
+pub struct BusData {
+    pub name: String,
+    pub effects: Vec<EffectData>,
+}
+
+/// How much of a note goes to a bus
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SendData {
+    pub bus: String,
+    pub level: f64,
+}
+
+/// A point of a note's gain envelope; the gain ramps linearly from point
+/// to point, and jumps where two points share a beat
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct GainPoint {
+    /// Beats from the note's start
+    pub beat: f64,
+    pub gain: f64,
+}
+
+/// Complete synth data for WebAudio playback
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SynthData {
+    pub name: String,
+    pub oscillators: Vec<OscillatorData>,
+    pub envelope: ADSRData,
+    pub filter: Option<FilterData>,
+    pub detune_cents: f64,
+    pub pitch_envelope: Option<PitchEnvelopeData>,
+    pub lfos: Vec<LfoData>,
+}
+
+/// Audio note event with synth information
+#[derive(Serialize, Deserialize, Clone)]
+pub struct AudioNoteEvent {
+    /// MIDI pitch, between keys for microtones (60.5 = a quarter tone above C4)
+    pub pitch: f64,
+    pub start: f64,
+    pub duration: f64,
+    pub velocity: u8,
+    /// Stereo position (-1.0 left to 1.0 right)
+    pub pan: f64,
+    pub synth: Option<SynthData>,
+    /// Amplitude envelope replacing the synth's (`apply_env`)
+    pub envelope: Option<ADSRData>,
+    /// The part's effects, in order
+    pub effects: Vec<EffectData>,
+    /// Buses the part sends to, after its effects
+    pub sends: Vec<SendData>,
+    /// Gain over the note as its part is ducked (`duck`); empty for full gain
+    pub gain: Vec<GainPoint>,
+}
+
+impl ADSRData {
+    /// The envelope for the relanote_synth engine
+    pub fn to_envelope(&self) -> relanote_synth::Envelope {
+        relanote_synth::Envelope {
+            attack: self.attack as f32,
+            decay: self.decay as f32,
+            sustain: self.sustain as f32,
+            release: self.release as f32,
+        }
+    }
+}
+
+impl SynthData {
+    /// The synth as a patch for the relanote_synth engine, loading its
+    /// samples from `samples`; oscillators whose file can't be loaded are
+    /// left out, with the reason in `samples.take_errors()`
+    pub fn to_patch(&self, samples: &mut Samples) -> relanote_synth::Patch {
+        use relanote_synth::{
+            Filter, FilterType, Lfo, LfoShape, LfoTarget, Oscillator, Patch, PitchEnvelope,
+            Waveform,
+        };
+
+        let oscillators = self
+            .oscillators
+            .iter()
+            .filter_map(|osc| {
+                let waveform = match (osc.waveform.as_str(), &osc.sample) {
+                    ("sample", Some(data)) => {
+                        let mut sample = samples.load(&data.path)?;
+                        sample.root = data.root as f32;
+                        sample.loop_points = data
+                            .loop_points
+                            .map(|(start, end)| (start as f32, end as f32));
+                        Waveform::Sample(sample)
+                    }
+                    ("square", _) => Waveform::Square,
+                    ("sawtooth", _) => Waveform::Saw,
+                    ("triangle", _) => Waveform::Triangle,
+                    ("noise", _) => Waveform::Noise,
+                    ("pink_noise", _) => Waveform::PinkNoise,
+                    ("brown_noise", _) => Waveform::BrownNoise,
+                    ("pulse", _) => Waveform::Pulse(osc.pulse_duty as f32),
+                    _ => Waveform::Sine,
+                };
+                Some(Oscillator {
+                    waveform,
+                    mix: osc.mix as f32,
+                    octave_offset: osc.octave_offset,
+                    detune_cents: osc.detune_cents as f32,
+                })
+            })
+            .collect();
+        let filter = self.filter.as_ref().map(|filter| Filter {
+            filter_type: match filter.filter_type.as_str() {
+                "highpass" => FilterType::HighPass,
+                "bandpass" => FilterType::BandPass,
+                _ => FilterType::LowPass,
+            },
+            cutoff: filter.cutoff as f32,
+            resonance: filter.resonance as f32,
+        });
+        Patch {
+            oscillators,
+            envelope: self.envelope.to_envelope(),
+            filter,
+            detune_cents: self.detune_cents as f32,
+            pitch_envelope: self.pitch_envelope.as_ref().map(|sweep| PitchEnvelope {
+                start_hz: sweep.start_hz as f32,
+                end_hz: sweep.end_hz as f32,
+                seconds: sweep.time_seconds as f32,
+            }),
+            lfos: self
+                .lfos
+                .iter()
+                .map(|lfo| Lfo {
+                    target: match lfo.target.as_str() {
+                        "cutoff" => LfoTarget::Cutoff,
+                        "amplitude" => LfoTarget::Amplitude,
+                        _ => LfoTarget::Pitch,
+                    },
+                    shape: match lfo.shape.as_str() {
+                        "triangle" => LfoShape::Triangle,
+                        "square" => LfoShape::Square,
+                        "sawtooth" => LfoShape::Saw,
+                        _ => LfoShape::Sine,
+                    },
+                    rate: lfo.rate as f32,
+                    depth: lfo.depth as f32,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl AudioNoteEvent {
+    /// The patch the note plays on the relanote_synth engine: its synth,
+    /// or the playground's default sound, with its own envelope if it has
+    /// one
+    pub fn patch(&self, samples: &mut Samples) -> relanote_synth::Patch {
+        let mut patch = self
+            .synth
+            .as_ref()
+            .map(|synth| synth.to_patch(samples))
+            .unwrap_or_default();
+        if let Some(envelope) = &self.envelope {
+            patch.envelope = envelope.to_envelope();
+        }
+        patch
+    }
+}
+
+/// Audio playback data with synth information
+#[derive(Serialize, Deserialize)]
+pub struct AudioPlaybackData {
+    pub notes: Vec<AudioNoteEvent>,
+    pub tempo: u32,
+    pub total_beats: f64,
+    /// Buses the notes are sent to
+    pub buses: Vec<BusData>,
+    /// Effects of the master bus, which everything plays through
+    pub master: Vec<EffectData>,
+}
+
+/// Audio notes in a window of the song, for playing long songs in chunks
+#[derive(Serialize, Deserialize)]
+pub struct AudioEventWindow {
+    /// Notes starting in `[from_beat, to_beat)`
+    pub notes: Vec<AudioNoteEvent>,
+    pub tempo: u32,
+    pub from_beat: f64,
+    pub to_beat: f64,
+    /// Length of the whole song in beats
+    pub total_beats: f64,
+    /// Buses the notes are sent to
+    pub buses: Vec<BusData>,
+    /// Effects of the master bus, which everything plays through
+    pub master: Vec<EffectData>,
+}
+
+/// Audio notes of every played part of a block or song that start within
+/// `window`, and the length of the longest part in beats
+pub fn audio_notes(
+    value: &Value,
+    base_note: i32,
+    window: Range<f64>,
+) -> (Vec<AudioNoteEvent>, f64) {
+    let mut all_notes = Vec::new();
+    let mut total_beats: f64 = 0.0;
+
+    match value {
+        Value::Block(block) => {
+            // Create a default part for a single block
+            let part = relanote_eval::PartValue::new("Default", vec![block.clone()]);
+            let (notes, end_beat) = extract_audio_notes_from_part(&part, 0.0, base_note, window);
+            all_notes.extend(notes);
+            total_beats = end_beat;
+        }
+        Value::Song(song) => {
+            for section in &song.sections {
+                let base_note = section.base_note(base_note as u8) as i32;
+                for part in &section.parts {
+                    // Skip metronome parts
+                    if part.instrument.to_lowercase().contains("metronome") {
+                        continue;
+                    }
+                    let (notes, end_beat) =
+                        extract_audio_notes_from_part(part, 0.0, base_note, window.clone());
+                    all_notes.extend(notes);
+                    total_beats = total_beats.max(end_beat);
+                }
+            }
+        }
+        _ => {}
+    }
+
+    (all_notes, total_beats)
+}
+
+/// Playback data of a whole block or song, at the tempo of `context`
+pub fn playback_data(value: &Value, context: &RenderContext) -> AudioPlaybackData {
+    let (notes, _) = audio_notes(
+        value,
+        context.base_note as i32,
+        f64::NEG_INFINITY..f64::INFINITY,
+    );
+    let total_beats = notes
+        .iter()
+        .map(|n| n.start + n.duration)
+        .fold(0.0, f64::max);
+    let (buses, master) = mix_data(value);
+    AudioPlaybackData {
+        notes,
+        tempo: context.tempo,
+        total_beats,
+        buses,
+        master,
+    }
+}
+
+/// The buses of a song and the effects of its master bus; none for a block
+pub fn mix_data(value: &Value) -> (Vec<BusData>, Vec<EffectData>) {
+    match value {
+        Value::Song(song) => (
+            song.buses()
+                .into_iter()
+                .map(|bus| BusData {
+                    name: bus.name.clone(),
+                    effects: bus.effects.iter().map(effect_to_data).collect(),
+                })
+                .collect(),
+            song.master.iter().map(effect_to_data).collect(),
+        ),
+        _ => (vec![], vec![]),
+    }
+}
+
+fn effect_to_data(effect: &EffectValue) -> EffectData {
+    match effect {
+        EffectValue::Reverb(level) => EffectData::Reverb { level: *level },
+        EffectValue::Delay(delay) => EffectData::Delay {
+            time_ms: delay.time_ms,
+            feedback: delay.feedback,
+            mix: delay.mix,
+        },
+        EffectValue::Phaser(phaser) => EffectData::Phaser {
+            rate: phaser.rate,
+            depth: phaser.depth,
+            mix: phaser.mix,
+        },
+        EffectValue::Distortion(distortion) => EffectData::Distortion {
+            amount: distortion.amount,
+            curve: distortion.dist_type.to_web_audio_type().to_string(),
+            mix: distortion.mix,
+        },
+        EffectValue::Compressor(compressor) => EffectData::Compressor {
+            threshold: compressor.threshold,
+            ratio: compressor.ratio,
+            attack_ms: compressor.attack_ms,
+            release_ms: compressor.release_ms,
+        },
+        EffectValue::Eq(eq) => EffectData::Eq {
+            low: eq.low,
+            mid: eq.mid,
+            high: eq.high,
+        },
+        EffectValue::Chorus(chorus) => EffectData::Chorus {
+            rate: chorus.rate,
+            depth: chorus.depth,
+            mix: chorus.mix,
+        },
+        EffectValue::Width(amount) => EffectData::Width { amount: *amount },
+    }
+}
+
+/// A part's effects and sends, as every one of its notes carries them
+fn part_mix_data(part: &PartValue) -> (Vec<EffectData>, Vec<SendData>) {
+    (
+        part.effects.iter().map(effect_to_data).collect(),
+        part.sends
+            .iter()
+            .map(|send| SendData {
+                bus: send.bus.name.clone(),
+                level: send.level,
+            })
+            .collect(),
+    )
+}
+
+/// The notes and chords a slot sounds, with their start and length in
+/// beats; tuplets (also nested ones) share their length among their slots,
+/// and overlaid blocks all start with the slot
+pub fn sounding_slots(
+    slot: &relanote_eval::SlotValue,
+    start: f64,
+    duration: f64,
+) -> Vec<(&relanote_eval::SlotValue, f64, f64)> {
+    match slot {
+        relanote_eval::SlotValue::Tuplet { slots, .. } => relanote_eval::tuplet_shares(slots)
+            .flat_map(|(inner, offset, share)| {
+                sounding_slots(inner, start + duration * offset, duration * share)
+            })
+            .collect(),
+        relanote_eval::SlotValue::Overlay { voices } => voices
+            .iter()
+            .flat_map(|voice| {
+                let mut time = start;
+                voice
+                    .slots
+                    .iter()
+                    .zip(voice.slot_lengths())
+                    .flat_map(move |(inner, length)| {
+                        time += length;
+                        sounding_slots(inner, time - length, length)
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect(),
+        _ => vec![(slot, start, duration)],
+    }
+}
+
+/// Convert an ADSR envelope to ADSRData for WebAudio
+fn adsr_to_data(envelope: &relanote_eval::value::ADSREnvelope) -> ADSRData {
+    ADSRData {
+        attack: envelope.attack,
+        decay: envelope.decay,
+        sustain: envelope.sustain,
+        release: envelope.release,
+    }
+}
+
+/// Convert SynthValue to SynthData for WebAudio
+fn synth_value_to_data(synth: &relanote_eval::value::SynthValue) -> SynthData {
+    use relanote_eval::value::{FilterType, Waveform};
+
+    let oscillators = synth
+        .oscillators
+        .iter()
+        .map(|osc| {
+            let (waveform, pulse_duty) = match &osc.waveform {
+                Waveform::Sine => ("sine".to_string(), 0.0),
+                Waveform::Square => ("square".to_string(), 0.5),
+                Waveform::Saw => ("sawtooth".to_string(), 0.0),
+                Waveform::Triangle => ("triangle".to_string(), 0.0),
+                Waveform::Noise => ("noise".to_string(), 0.0),
+                Waveform::PinkNoise => ("pink_noise".to_string(), 0.0),
+                Waveform::BrownNoise => ("brown_noise".to_string(), 0.0),
+                Waveform::Pulse(duty) => ("pulse".to_string(), *duty),
+                Waveform::Sample(_) => ("sample".to_string(), 0.0),
+            };
+            let sample = match &osc.waveform {
+                Waveform::Sample(sample) => Some(SampleData {
+                    path: sample.path.clone(),
+                    root: sample.root,
+                    loop_points: sample.loop_points,
+                }),
+                _ => None,
+            };
+            OscillatorData {
+                waveform,
+                pulse_duty,
+                mix: osc.mix,
+                octave_offset: osc.octave_offset,
+                detune_cents: osc.detune_cents,
+                sample,
+            }
+        })
+        .collect();
+
+    let envelope = adsr_to_data(&synth.envelope);
+
+    let filter = synth.filter.as_ref().map(|f| {
+        let filter_type = match f.filter_type {
+            FilterType::LowPass => "lowpass".to_string(),
+            FilterType::HighPass => "highpass".to_string(),
+            FilterType::BandPass => "bandpass".to_string(),
+        };
+        FilterData {
+            filter_type,
+            cutoff: f.cutoff,
+            resonance: f.resonance,
+        }
+    });
+
+    let pitch_envelope = synth
+        .pitch_envelope
+        .map(|(start, end, time)| PitchEnvelopeData {
+            start_hz: start,
+            end_hz: end,
+            time_seconds: time,
+        });
+
+    SynthData {
+        name: synth.name.clone(),
+        oscillators,
+        envelope,
+        filter,
+        detune_cents: synth.detune_cents,
+        pitch_envelope,
+        lfos: synth
+            .lfos
+            .iter()
+            .map(|lfo| LfoData {
+                target: lfo.target.name().to_string(),
+                shape: lfo.shape.to_web_audio_type().to_string(),
+                rate: lfo.rate,
+                depth: lfo.depth,
+            })
+            .collect(),
+    }
+}
+
+/// Audio notes of a part that start within `window` (in beats), and the
+/// beat the part ends on
+///
+/// Notes are only extracted from blocks that overlap the window.
+fn extract_audio_notes_from_part(
+    part: &relanote_eval::PartValue,
+    start_beat: f64,
+    base_note: i32, // MIDI note number for root (60 = C4)
+    window: Range<f64>,
+) -> (Vec<AudioNoteEvent>, f64) {
+    use relanote_eval::SlotValue;
+
+    let mut notes = Vec::new();
+    let mut current_beat = start_beat;
+
+    // Get synth data if available
+    let synth_data = part.synth.as_ref().map(synth_value_to_data);
+    let envelope = part
+        .envelope
+        .as_ref()
+        .and_then(|envelope| envelope.adsr.as_ref())
+        .map(adsr_to_data);
+
+    // Calculate velocity from volume_level
+    let velocity = part
+        .volume_level
+        .map(|v| ((v * 100.0).round() as u8).clamp(1, 127))
+        .unwrap_or(100);
+
+    let pan = part.pan_level.unwrap_or(0.0);
+    let (effects, sends) = part_mix_data(part);
+
+    // A dynamics envelope scales the velocity of notes starting during its
+    // ramp, as the expression controller does in MIDI
+    let dynamics = part
+        .envelope
+        .as_ref()
+        .and_then(|envelope| envelope.dynamics.as_ref());
+    let velocity_at = |start: f64| match dynamics {
+        Some(dynamics) => {
+            ((velocity as f64 * dynamics.level_at(start - start_beat)).round() as u8).clamp(1, 127)
+        }
+        None => velocity,
+    };
+
+    // Trigger notes the part is ducked under, timed from where it starts
+    let ducks: Vec<_> = part
+        .ducks
+        .iter()
+        .map(|duck| {
+            let (trigger_notes, _) = extract_audio_notes_from_part(
+                &duck.trigger,
+                start_beat,
+                base_note,
+                f64::NEG_INFINITY..f64::INFINITY,
+            );
+            let mut onsets: Vec<f64> = trigger_notes.iter().map(|note| note.start).collect();
+            onsets.sort_by(f64::total_cmp);
+            onsets.dedup();
+            (duck, onsets)
+        })
+        .collect();
+    let gain = |start: f64, duration: f64| duck_envelope(&ducks, start, duration);
+
+    // Swing moves note boundaries, measured from where the part starts
+    let swung = |meta: &relanote_eval::NoteMeta, start: f64, duration: f64| match &meta.swing {
+        Some(swing) => {
+            let swung_start = start_beat + swing.warp(start - start_beat);
+            let swung_end = start_beat + swing.warp(start + duration - start_beat);
+            (swung_start, swung_end - swung_start)
+        }
+        None => (start, duration),
+    };
+
+    for block in &part.blocks {
+        let slot_count = block.slots.len();
+        let default_beat_duration = if slot_count > 0 {
+            block.beats / slot_count as f64
+        } else {
+            0.0
+        };
+
+        let block_beats: f64 = block
+            .slots
+            .iter()
+            .map(|slot| slot.duration_beats().unwrap_or(default_beat_duration))
+            .sum();
+        if current_beat >= window.end || current_beat + block_beats < window.start {
+            current_beat += block_beats;
+            continue;
+        }
+        let first_note = notes.len();
+
+        for slot in &block.slots {
+            let beat_duration = slot.duration_beats().unwrap_or(default_beat_duration);
+
+            for (slot, start, duration) in sounding_slots(slot, current_beat, beat_duration) {
+                match slot {
+                    SlotValue::Note { interval, meta, .. } => {
+                        let (start, duration) = swung(meta, start, duration);
+                        notes.push(AudioNoteEvent {
+                            pitch: base_note as f64 + interval.semitones(),
+                            start,
+                            duration,
+                            velocity: velocity_at(start),
+                            pan,
+                            synth: synth_data.clone(),
+                            envelope: envelope.clone(),
+                            effects: effects.clone(),
+                            sends: sends.clone(),
+                            gain: gain(start, duration),
+                        });
+                    }
+                    SlotValue::Chord {
+                        intervals, meta, ..
+                    } => {
+                        let (start, duration) = swung(meta, start, duration);
+                        for interval in intervals {
+                            notes.push(AudioNoteEvent {
+                                pitch: base_note as f64 + interval.semitones(),
+                                start,
+                                duration,
+                                velocity: velocity_at(start),
+                                pan,
+                                synth: synth_data.clone(),
+                                envelope: envelope.clone(),
+                                effects: effects.clone(),
+                                sends: sends.clone(),
+                                gain: gain(start, duration),
+                            });
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            current_beat += beat_duration;
+        }
+
+        let block_notes = notes.split_off(first_note);
+        notes.extend(
+            block_notes
+                .into_iter()
+                .filter(|note| window.contains(&note.start)),
+        );
+    }
+
+    (notes, current_beat)
+}
+
+/// Gain envelope of a note from `start` lasting `duration` beats, under
+/// trigger notes starting on `onsets` (in beats, in order) of each duck
+fn duck_envelope(
+    ducks: &[(&relanote_eval::DuckValue, Vec<f64>)],
+    start: f64,
+    duration: f64,
+) -> Vec<GainPoint> {
+    if ducks.is_empty() {
+        return Vec::new();
+    }
+    let end = start + duration;
+    let release = relanote_eval::DuckValue::RELEASE_BEATS;
+    let gain = |beat: f64, before: bool| -> f64 {
+        ducks
+            .iter()
+            .map(|(duck, onsets)| {
+                // Just before a trigger note, the dip it starts hasn't begun
+                let heard = if before {
+                    &onsets[..onsets.partition_point(|&onset| onset < beat)]
+                } else {
+                    &onsets[..]
+                };
+                duck.gain_at(heard, beat)
+            })
+            .product()
+    };
+
+    // The envelope bends where a dip starts and where it has come back
+    let mut bends: Vec<f64> = ducks
+        .iter()
+        .flat_map(|(_, onsets)| onsets.iter().flat_map(|&onset| [onset, onset + release]))
+        .filter(|&beat| beat > start && beat < end)
+        .collect();
+    bends.sort_by(f64::total_cmp);
+    bends.dedup();
+
+    let mut points = vec![GainPoint {
+        beat: 0.0,
+        gain: gain(start, false),
+    }];
+    for beat in bends {
+        let (before, after) = (gain(beat, true), gain(beat, false));
+        if before != after {
+            points.push(GainPoint {
+                beat: beat - start,
+                gain: before,
+            });
+        }
+        points.push(GainPoint {
+            beat: beat - start,
+            gain: after,
+        });
+    }
+    points
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_samples_that_fail_to_load() {
+        let mut samples = Samples::new();
+        assert!(samples.load("missing.wav").is_none());
+        assert!(samples.load("missing.wav").is_none());
+
+        // Reported once, and not read again
+        let errors = samples.take_errors();
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(&errors[0], SampleError::Read { path, .. } if path == "missing.wav"));
+        assert!(samples.take_errors().is_empty());
+    }
+}
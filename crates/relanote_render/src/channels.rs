@@ -0,0 +1,42 @@
+//! MIDI channel allocation
+
+use std::collections::HashMap;
+
+use crate::drums::DRUM_CHANNEL;
+
+/// Assigns MIDI channels to parts across a whole song
+///
+/// Each part gets its own channel, so its volume, pan and effect controllers
+/// don't collide with another part's. Parts are keyed by section and index:
+/// sections that play one after another pass the same section key, so the
+/// nth part keeps its channel from section to section. Drum parts always
+/// use the drum channel, and melodic parts never use it.
+#[derive(Default)]
+pub(crate) struct ChannelAllocator {
+    assigned: HashMap<(usize, usize), u8>,
+    next: usize,
+}
+
+impl ChannelAllocator {
+    /// Melodic channels in allocation order (everything except the drum channel)
+    const MELODIC: [u8; 15] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 10, 11, 12, 13, 14, 15];
+
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Channel for a part, or None when all melodic channels are taken
+    pub(crate) fn allocate(&mut self, section: usize, part: usize, is_drum: bool) -> Option<u8> {
+        if is_drum {
+            return Some(DRUM_CHANNEL);
+        }
+        if let Some(channel) = self.assigned.get(&(section, part)) {
+            return Some(*channel);
+        }
+
+        let channel = *Self::MELODIC.get(self.next)?;
+        self.next += 1;
+        self.assigned.insert((section, part), channel);
+        Some(channel)
+    }
+}
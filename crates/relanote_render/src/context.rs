@@ -0,0 +1,103 @@
+//! Song-wide settings chosen by a program's `set` bindings
+
+use relanote_eval::{AbsolutePitchValue, Evaluator, SongMetadata, Value};
+
+use crate::abc::AbcConfig;
+use crate::midi::MidiConfig;
+
+/// Settings every renderer shares, so MIDI, ABC and the editor previews
+/// agree on a program's tempo, key, meter and metadata
+///
+/// Read from `set tempo = 90`, `set key = D4`, `set time_signature = (3, 4)`
+/// and `meta { title: "..." }`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RenderContext {
+    /// Tempo in BPM
+    pub tempo: u32,
+    /// MIDI note of the root (R)
+    pub base_note: u8,
+    /// Time signature as (beats per bar, beat unit)
+    pub time_signature: (u32, u32),
+    /// Title, composer and copyright
+    pub metadata: SongMetadata,
+}
+
+impl Default for RenderContext {
+    fn default() -> Self {
+        Self {
+            tempo: 120,
+            base_note: 60, // C4 (middle C)
+            time_signature: (4, 4),
+            metadata: SongMetadata::default(),
+        }
+    }
+}
+
+impl RenderContext {
+    /// Context from an evaluated program's bindings
+    ///
+    /// Settings a program leaves out, or sets to something no renderer can
+    /// use (a zero tempo, a beat unit that isn't a power of two), keep their
+    /// defaults.
+    pub fn from_evaluator(evaluator: &Evaluator) -> Self {
+        let mut context = Self {
+            metadata: evaluator.metadata().clone(),
+            ..Self::default()
+        };
+        if let Some(Value::Int(tempo)) = evaluator.get_binding("tempo") {
+            if let Ok(tempo @ 1..) = u32::try_from(tempo) {
+                context.tempo = tempo;
+            }
+        }
+        if let Some(Value::AbsolutePitch(AbsolutePitchValue { midi_note })) =
+            evaluator.get_binding("key")
+        {
+            context.base_note = midi_note;
+        }
+        if let Some(Value::Tuple(items)) = evaluator.get_binding("time_signature") {
+            if let [Value::Int(beats), Value::Int(unit)] = items.as_slice() {
+                if let (Ok(beats @ 1..), Ok(unit)) = (u32::try_from(*beats), u32::try_from(*unit)) {
+                    if unit.is_power_of_two() {
+                        context.time_signature = (beats, unit);
+                    }
+                }
+            }
+        }
+        context
+    }
+
+    /// Length of a bar in quarter-note beats
+    pub fn beats_per_bar(&self) -> f64 {
+        let (beats, unit) = self.time_signature;
+        beats as f64 * 4.0 / unit as f64
+    }
+
+    /// MIDI settings for this context
+    pub fn midi_config(&self) -> MidiConfig {
+        MidiConfig {
+            tempo: self.tempo,
+            base_note: self.base_note,
+            beats_per_bar: self.whole_beats_per_bar(),
+            metadata: self.metadata.clone(),
+            ..MidiConfig::default()
+        }
+    }
+
+    /// ABC settings for this context, titled `title` unless the metadata
+    /// names one
+    pub fn abc_config(&self, title: &str) -> AbcConfig {
+        AbcConfig {
+            title: self.metadata.title.as_deref().unwrap_or(title).to_string(),
+            composer: self.metadata.composer.clone(),
+            tempo: self.tempo,
+            base_note: self.base_note,
+            beats_per_bar: self.whole_beats_per_bar(),
+            ..AbcConfig::default()
+        }
+    }
+
+    /// Bar length for renderers that count bars in whole quarter notes
+    fn whole_beats_per_bar(&self) -> u32 {
+        self.beats_per_bar().round().max(1.0) as u32
+    }
+}
@@ -0,0 +1,248 @@
+//! Musical differences between two song layouts
+
+use crate::inspect::{NoteLayout, PartLayout, SongLayout};
+
+/// How a part differs between two songs
+#[derive(Clone, Debug, PartialEq)]
+pub struct PartDiff {
+    pub section: String,
+    pub instrument: String,
+    pub kind: PartDiffKind,
+    /// Changed notes, in the order they play
+    pub changes: Vec<NoteChange>,
+}
+
+/// Whether a part is new, gone, or in both songs
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PartDiffKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+/// A note that differs between two versions of a part
+#[derive(Clone, Debug, PartialEq)]
+pub enum NoteChange {
+    Added(NoteLayout),
+    Removed(NoteLayout),
+    /// A note starting at the same place with another pitch or length
+    Changed {
+        old: NoteLayout,
+        new: NoteLayout,
+    },
+}
+
+impl NoteChange {
+    /// The note where the change is, in the newer song unless it was removed
+    pub fn note(&self) -> &NoteLayout {
+        match self {
+            NoteChange::Added(note) | NoteChange::Removed(note) => note,
+            NoteChange::Changed { new, .. } => new,
+        }
+    }
+}
+
+/// Parts whose notes differ between `old` and `new`
+///
+/// Parts are matched by section name and instrument (the nth part of that
+/// name in the nth section of that name). Notes are compared from the start
+/// of their section, so moving a section doesn't change every note in it.
+pub fn diff_layouts(old: &SongLayout, new: &SongLayout) -> Vec<PartDiff> {
+    let old_parts = keyed_parts(old);
+    let mut new_parts = keyed_parts(new);
+
+    let mut diffs = Vec::new();
+    for (key, old_offset, old_part) in old_parts {
+        let position = new_parts.iter().position(|(k, _, _)| *k == key);
+        let (section, instrument) = (key.0.clone(), key.2.clone());
+        match position.map(|index| new_parts.remove(index)) {
+            Some((_, new_offset, new_part)) => {
+                let changes = diff_notes(
+                    &relative(&old_part.notes, old_offset),
+                    &relative(&new_part.notes, new_offset),
+                );
+                if !changes.is_empty() {
+                    diffs.push(PartDiff {
+                        section,
+                        instrument,
+                        kind: PartDiffKind::Changed,
+                        changes: absolute(changes, old_offset, new_offset),
+                    });
+                }
+            }
+            None => diffs.push(PartDiff {
+                section,
+                instrument,
+                kind: PartDiffKind::Removed,
+                changes: old_part
+                    .notes
+                    .iter()
+                    .cloned()
+                    .map(NoteChange::Removed)
+                    .collect(),
+            }),
+        }
+    }
+    for (key, _, new_part) in new_parts {
+        diffs.push(PartDiff {
+            section: key.0,
+            instrument: key.2,
+            kind: PartDiffKind::Added,
+            changes: new_part
+                .notes
+                .iter()
+                .cloned()
+                .map(NoteChange::Added)
+                .collect(),
+        });
+    }
+    diffs
+}
+
+/// (section name, occurrence of the section name, instrument, occurrence of
+/// the instrument in the section)
+type PartKey = (String, usize, String, usize);
+
+/// Each part with its key and the beat its section starts on
+fn keyed_parts(layout: &SongLayout) -> Vec<(PartKey, f64, &PartLayout)> {
+    let mut parts = Vec::new();
+    for (index, section) in layout.sections.iter().enumerate() {
+        let section_occurrence = layout.sections[..index]
+            .iter()
+            .filter(|other| other.name == section.name)
+            .count();
+        for (part_index, part) in section.parts.iter().enumerate() {
+            let part_occurrence = section.parts[..part_index]
+                .iter()
+                .filter(|other| other.instrument == part.instrument)
+                .count();
+            let key = (
+                section.name.clone(),
+                section_occurrence,
+                part.instrument.clone(),
+                part_occurrence,
+            );
+            parts.push((key, section.start_beat, part));
+        }
+    }
+    parts
+}
+
+fn relative(notes: &[NoteLayout], offset: f64) -> Vec<NoteLayout> {
+    notes
+        .iter()
+        .map(|note| NoteLayout {
+            start_beat: note.start_beat - offset,
+            ..note.clone()
+        })
+        .collect()
+}
+
+/// Move changes back to song positions: removed notes to the old song's,
+/// the others to the new song's
+fn absolute(changes: Vec<NoteChange>, old_offset: f64, new_offset: f64) -> Vec<NoteChange> {
+    let shift = |note: NoteLayout, offset: f64| NoteLayout {
+        start_beat: note.start_beat + offset,
+        ..note
+    };
+    changes
+        .into_iter()
+        .map(|change| match change {
+            NoteChange::Added(note) => NoteChange::Added(shift(note, new_offset)),
+            NoteChange::Removed(note) => NoteChange::Removed(shift(note, old_offset)),
+            NoteChange::Changed { old, new } => NoteChange::Changed {
+                old: shift(old, old_offset),
+                new: shift(new, new_offset),
+            },
+        })
+        .collect()
+}
+
+/// Changes from `old` to `new` notes
+///
+/// Notes in both are dropped first. A remaining pair starting together is a
+/// change, preferring a pair with the same pitch (a new length); the rest
+/// were added or removed.
+fn diff_notes(old: &[NoteLayout], new: &[NoteLayout]) -> Vec<NoteChange> {
+    let mut removed: Vec<&NoteLayout> = old.iter().collect();
+    let mut added: Vec<&NoteLayout> = Vec::new();
+    for note in new {
+        match removed.iter().position(|old| *old == note) {
+            Some(index) => {
+                removed.remove(index);
+            }
+            None => added.push(note),
+        }
+    }
+
+    let mut changes = Vec::new();
+    for same_pitch in [true, false] {
+        added.retain(|new| {
+            let partner = removed.iter().position(|old| {
+                old.start_beat == new.start_beat && (!same_pitch || old.pitch == new.pitch)
+            });
+            match partner {
+                Some(index) => {
+                    changes.push(NoteChange::Changed {
+                        old: removed.remove(index).clone(),
+                        new: (*new).clone(),
+                    });
+                    false
+                }
+                None => true,
+            }
+        });
+    }
+    changes.extend(removed.into_iter().cloned().map(NoteChange::Removed));
+    changes.extend(added.into_iter().cloned().map(NoteChange::Added));
+    changes.sort_by(|a, b| {
+        (a.note().start_beat, a.note().pitch)
+            .partial_cmp(&(b.note().start_beat, b.note().pitch))
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(start_beat: f64, pitch: f64) -> NoteLayout {
+        NoteLayout {
+            start_beat,
+            beats: 1.0,
+            pitch,
+        }
+    }
+
+    #[test]
+    fn test_diff_notes_pairs_changes_by_start() {
+        let old = [note(0.0, 60.0), note(1.0, 64.0), note(2.0, 67.0)];
+        let longer = NoteLayout {
+            beats: 2.0,
+            ..note(2.0, 67.0)
+        };
+        let new = [
+            note(0.0, 60.0),
+            note(1.0, 65.0),
+            longer.clone(),
+            note(3.0, 72.0),
+        ];
+
+        assert_eq!(
+            diff_notes(&old, &new),
+            vec![
+                NoteChange::Changed {
+                    old: note(1.0, 64.0),
+                    new: note(1.0, 65.0)
+                },
+                NoteChange::Changed {
+                    old: note(2.0, 67.0),
+                    new: longer
+                },
+                NoteChange::Added(note(3.0, 72.0)),
+            ]
+        );
+        assert!(diff_notes(&old, &old).is_empty());
+    }
+}
@@ -0,0 +1,41 @@
+//! Drum mapping (General MIDI percussion)
+
+pub use relanote_eval::value::gm_drum_key;
+use relanote_eval::value::{DrumMapValue, NoteMeta, PartValue, SynthValue};
+
+/// MIDI channel reserved for percussion (channel 10, zero-based)
+pub const DRUM_CHANNEL: u8 = 9;
+
+/// How a drum part's notes are turned into percussion keys
+pub(crate) struct DrumKeys<'a> {
+    map: Option<&'a DrumMapValue>,
+    preset_key: Option<u8>,
+}
+
+impl<'a> DrumKeys<'a> {
+    /// Drum keys for a part, or None if it is a melodic part
+    ///
+    /// MIDI can't carry a synth's samples, so a part playing them plays
+    /// as drums.
+    pub(crate) fn for_part(part: &'a PartValue) -> Option<Self> {
+        let preset_key = part.synth.as_ref().and_then(SynthValue::drum_key);
+        let sampled = part.synth.as_ref().is_some_and(SynthValue::plays_samples);
+        let hits = part.blocks.iter().any(|block| block.has_drum_hits());
+        if part.drum_map.is_none() && preset_key.is_none() && !sampled && !hits {
+            return None;
+        }
+        Some(Self {
+            map: part.drum_map.as_ref(),
+            preset_key,
+        })
+    }
+
+    /// Percussion key for a note: the key its kit gave it, else the mapped
+    /// one; unmapped pitches keep their pitched key
+    pub(crate) fn key(&self, meta: &NoteMeta, cents: f64, pitched_key: u8) -> u8 {
+        meta.drum_key
+            .or_else(|| self.map.and_then(|map| map.key_for(cents)))
+            .or(self.preset_key)
+            .unwrap_or(pitched_key)
+    }
+}
@@ -0,0 +1,19 @@
+//! Errors from rendering music
+
+use thiserror::Error;
+
+/// Why a song could not be rendered
+#[derive(Debug, Error)]
+pub enum RenderError {
+    #[error("could not write MIDI: {0}")]
+    Write(#[from] std::io::Error),
+
+    #[error("could not read the rendered MIDI: {0}")]
+    Read(#[from] midly::Error),
+
+    #[error(
+        "the song is too long to render: it lasts more than {} ticks",
+        u32::MAX
+    )]
+    TooLong,
+}
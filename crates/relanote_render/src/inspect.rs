@@ -0,0 +1,241 @@
+//! Song layout: what the MIDI renderer would place where, for debugging
+
+use relanote_eval::value::{EffectValue, IntervalValue, PartValue, SlotValue, SongValue};
+
+use crate::drums::DrumKeys;
+use crate::midi::MidiRenderer;
+
+/// Where a song's sections and parts land when rendered to MIDI
+#[derive(Clone, Debug)]
+pub struct SongLayout {
+    pub sections: Vec<SectionLayout>,
+    /// Buses parts send to, then the master bus if it has effects
+    pub buses: Vec<BusLayout>,
+    /// Length of the whole song in beats
+    pub total_beats: f64,
+    /// Length of a bar in beats
+    pub beats_per_bar: u32,
+}
+
+/// A bus and its effects, one description each
+#[derive(Clone, Debug)]
+pub struct BusLayout {
+    pub name: String,
+    pub effects: Vec<String>,
+}
+
+/// A section's place in the song
+#[derive(Clone, Debug)]
+pub struct SectionLayout {
+    pub name: String,
+    /// Beat the section starts on
+    pub start_beat: f64,
+    /// Length of the section (its longest part) in beats
+    pub beats: f64,
+    /// MIDI note the section's intervals are relative to
+    pub base_note: u8,
+    /// The section's own tempo in BPM, when it has one
+    pub tempo: Option<f64>,
+    pub parts: Vec<PartLayout>,
+}
+
+/// A part's channel, length and settings
+#[derive(Clone, Debug)]
+pub struct PartLayout {
+    pub instrument: String,
+    /// Zero-based MIDI channel, or None when the part is dropped because
+    /// every melodic channel is in use
+    pub channel: Option<u8>,
+    /// Whether the part plays on the percussion channel
+    pub drums: bool,
+    /// Length of the part in beats
+    pub beats: f64,
+    /// Notes the part plays, in order (a chord is one note per tone)
+    pub notes: Vec<NoteLayout>,
+    /// Volume level (0.0 to 1.0), if set
+    pub volume: Option<f64>,
+    /// Effects and synth settings, one description each (e.g. `reverb 0.40`)
+    pub effects: Vec<String>,
+}
+
+/// A note's place in the song
+#[derive(Clone, Debug, PartialEq)]
+pub struct NoteLayout {
+    /// Beat the note starts on
+    pub start_beat: f64,
+    /// Length of the note in beats
+    pub beats: f64,
+    /// MIDI pitch, with microtones as a fraction of a semitone
+    pub pitch: f64,
+}
+
+impl PartLayout {
+    /// Why the part makes no sound, if it doesn't
+    pub fn silence(&self) -> Option<&'static str> {
+        if self.channel.is_none() {
+            Some("dropped: no free MIDI channel")
+        } else if self.notes.is_empty() {
+            Some("no notes")
+        } else if self.volume.is_some_and(|volume| volume <= 0.0) {
+            Some("volume is 0")
+        } else {
+            None
+        }
+    }
+}
+
+impl MidiRenderer {
+    /// Lay a song out the way [`MidiRenderer::render`] would, without
+    /// rendering it
+    pub fn inspect(&self, song: &SongValue) -> SongLayout {
+        let config = &self.config;
+        let ticks_per_beat = config.ticks_per_beat as f64;
+        let beats = |ticks: u32| ticks as f64 / ticks_per_beat;
+        let timeline = self.timeline(song);
+        let part_channels = self.part_channels(song);
+
+        let sections: Vec<SectionLayout> = song
+            .sections
+            .iter()
+            .zip(part_channels)
+            .enumerate()
+            .map(|(index, (section, channels))| {
+                let start = timeline.section_start(index);
+                let base_note = section.base_note(config.base_note);
+                SectionLayout {
+                    name: section.name.clone(),
+                    start_beat: beats(start),
+                    beats: beats(self.section_ticks(section)),
+                    base_note,
+                    tempo: section.tempo,
+                    parts: section
+                        .parts
+                        .iter()
+                        .zip(channels)
+                        .map(|(part, channel)| PartLayout {
+                            instrument: part.instrument.clone(),
+                            channel,
+                            drums: DrumKeys::for_part(part).is_some(),
+                            beats: beats(self.part_ticks(part)),
+                            notes: self
+                                .schedule_part(part, start)
+                                .0
+                                .iter()
+                                .flat_map(|scheduled| {
+                                    intervals(scheduled.slot).iter().map(|interval| NoteLayout {
+                                        start_beat: beats(scheduled.time),
+                                        beats: beats(scheduled.duration),
+                                        pitch: base_note as f64 + interval.semitones(),
+                                    })
+                                })
+                                .collect(),
+                            volume: part.volume_level,
+                            effects: effects(part),
+                        })
+                        .collect(),
+                }
+            })
+            .collect();
+
+        let total_beats = sections
+            .iter()
+            .map(|section| section.start_beat + section.beats)
+            .fold(0.0, f64::max);
+        let mut buses: Vec<BusLayout> = song
+            .buses()
+            .into_iter()
+            .map(|bus| BusLayout {
+                name: bus.name.clone(),
+                effects: bus.effects.iter().map(describe).collect(),
+            })
+            .collect();
+        if !song.master.is_empty() {
+            buses.push(BusLayout {
+                name: "master".to_string(),
+                effects: song.master.iter().map(describe).collect(),
+            });
+        }
+        SongLayout {
+            sections,
+            buses,
+            total_beats,
+            beats_per_bar: config.beats_per_bar,
+        }
+    }
+}
+
+/// Pitches a scheduled slot sounds
+fn intervals(slot: &SlotValue) -> &[IntervalValue] {
+    match slot {
+        SlotValue::Note { interval, .. } => std::slice::from_ref(interval),
+        SlotValue::Chord { intervals, .. } => intervals,
+        SlotValue::Rest { .. } | SlotValue::Tuplet { .. } | SlotValue::Overlay { .. } => &[],
+    }
+}
+
+/// Descriptions of a part's effect and synth settings
+fn effects(part: &PartValue) -> Vec<String> {
+    let mut effects = Vec::new();
+    if let Some(volume) = part.volume_level {
+        effects.push(format!("volume {:.2}", volume));
+    }
+    if let Some(pan) = part.pan_level {
+        effects.push(format!("pan {:.2}", pan));
+    }
+    effects.extend(part.effects.iter().map(describe));
+    for send in &part.sends {
+        effects.push(format!("send {} {:.2}", send.bus.name, send.level));
+    }
+    if let Some(synth) = &part.synth {
+        effects.push(format!("synth {}", synth.name));
+    }
+    if part.envelope.is_some() {
+        effects.push("envelope".to_string());
+    }
+    if part.drum_map.is_some() {
+        effects.push("drum map".to_string());
+    }
+    for automation in &part.automation {
+        effects.push(format!(
+            "automation CC{} ({} points)",
+            automation.cc,
+            automation.points.len()
+        ));
+    }
+    for duck in &part.ducks {
+        effects.push(format!(
+            "duck under {} {:.2}",
+            duck.trigger.instrument, duck.amount
+        ));
+    }
+    effects
+}
+
+/// Description of an effect in a chain
+fn describe(effect: &EffectValue) -> String {
+    match effect {
+        EffectValue::Reverb(level) => format!("reverb {:.2}", level),
+        EffectValue::Delay(delay) => format!(
+            "delay {}ms feedback {:.2} mix {:.2}",
+            delay.time_ms, delay.feedback, delay.mix
+        ),
+        EffectValue::Phaser(phaser) => format!(
+            "phaser {}Hz depth {:.2} mix {:.2}",
+            phaser.rate, phaser.depth, phaser.mix
+        ),
+        EffectValue::Distortion(distortion) => format!(
+            "distortion {:?} {:.2} mix {:.2}",
+            distortion.dist_type, distortion.amount, distortion.mix
+        ),
+        EffectValue::Compressor(compressor) => format!(
+            "compressor {}dB {}:1 attack {}ms release {}ms",
+            compressor.threshold, compressor.ratio, compressor.attack_ms, compressor.release_ms
+        ),
+        EffectValue::Eq(eq) => format!("eq low {}dB mid {}dB high {}dB", eq.low, eq.mid, eq.high),
+        EffectValue::Chorus(chorus) => format!(
+            "chorus {}Hz depth {:.2} mix {:.2}",
+            chorus.rate, chorus.depth, chorus.mix
+        ),
+        EffectValue::Width(amount) => format!("width {:.2}", amount),
+    }
+}
@@ -1,7 +1,46 @@
 //! Music rendering for relanote
 //!
-//! Converts evaluated music values to MIDI and other formats.
+//! Converts evaluated music values to MIDI and other formats (including
+//! ABC notation, which can also be converted back into relanote source),
+//! and to notes with synth settings for hosts that play them themselves.
+//! Songs can also be played live, as Open Sound Control messages (`osc`).
+//!
+//! Optional backends:
+//! - `sf2` (feature `sf2`): audio rendering with a SoundFont
+//! - `link` (feature `link`): playing in time with an Ableton Link session
 
+pub mod abc;
+mod audio;
+mod channels;
+mod context;
+mod diff;
+mod drums;
+mod error;
+mod inspect;
+#[cfg(feature = "link")]
+pub mod link;
 mod midi;
+mod mpe;
+pub mod osc;
+mod performance;
+mod scheduler;
+#[cfg(feature = "sf2")]
+pub mod sf2;
+mod timeline;
 
+pub use abc::{abc_to_rela, render_to_abc, AbcConfig, AbcError, AbcRenderer};
+pub use audio::{
+    audio_notes, mix_data, playback_data, sounding_slots, ADSRData, AudioEventWindow,
+    AudioNoteEvent, AudioPlaybackData, BusData, EffectData, FilterData, GainPoint, LfoData,
+    OscillatorData, PitchEnvelopeData, SampleData, SampleError, Samples, SendData, SynthData,
+};
+pub use context::RenderContext;
+pub use diff::{diff_layouts, NoteChange, PartDiff, PartDiffKind};
+pub use drums::{gm_drum_key, DRUM_CHANNEL};
+pub use error::RenderError;
+pub use inspect::{BusLayout, NoteLayout, PartLayout, SectionLayout, SongLayout};
 pub use midi::{render_to_midi, MidiConfig, MidiRenderer};
+pub use performance::{Performance, PerformanceEvent, PerformedNote};
+pub use scheduler::{
+    schedule, Clock, ClockMessage, FollowedClock, Timed, WallClock, CLOCK_TICKS_PER_BEAT,
+};
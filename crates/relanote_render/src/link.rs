@@ -0,0 +1,783 @@
+//! Ableton Link: joining a Link session on the local network, so playback
+//! keeps the tempo and beat phase of the other programs in it
+//!
+//! Peers announce their session and its timeline over UDP multicast, and
+//! find a session's shared clock (its "ghost time") by exchanging pings
+//! with one of its peers. Relanote follows the session and never changes
+//! its tempo; alone on the network, it founds a session at the song's
+//! tempo for others to join.
+
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::scheduler::Clock;
+
+/// Where peers announce themselves
+const MULTICAST: SocketAddrV4 = SocketAddrV4::new(Ipv4Addr::new(224, 76, 78, 75), 20808);
+
+const DISCOVERY_HEADER: &[u8; 8] = b"_asdp_v\x01";
+const ALIVE: u8 = 1;
+const RESPONSE: u8 = 2;
+const BYEBYE: u8 = 3;
+
+const MEASUREMENT_HEADER: &[u8; 8] = b"_link_v\x01";
+const PING: u8 = 1;
+const PONG: u8 = 2;
+
+const TIMELINE: [u8; 4] = *b"tmln";
+const SESSION: [u8; 4] = *b"sess";
+const START_STOP: [u8; 4] = *b"stst";
+const ENDPOINT: [u8; 4] = *b"mep4";
+const HOST_TIME: [u8; 4] = *b"__ht";
+const GHOST_TIME: [u8; 4] = *b"__gt";
+const PREV_GHOST_TIME: [u8; 4] = *b"_pgt";
+
+/// Seconds an announcement lasts
+const TTL: u8 = 5;
+/// Microseconds between announcements
+const BROADCAST_PERIOD: i64 = 250_000;
+/// Microseconds before an unanswered ping is sent again
+const PING_PERIOD: i64 = 50_000;
+/// Microseconds a measurement may take
+const MEASUREMENT_TIMEOUT: i64 = 1_000_000;
+/// Microseconds between measurements of the session already joined, which
+/// keep it from drifting
+const REMEASURE_PERIOD: i64 = 30_000_000;
+/// Samples of a session's ghost time that make a measurement
+const DATA_POINTS: usize = 100;
+/// Sessions whose ghost times are closer than this many microseconds are
+/// equally old
+const SESSION_EPSILON: f64 = 500_000.0;
+
+/// A peer's or a session's id
+type NodeId = [u8; 8];
+
+/// A session's beats over its ghost time
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Timeline {
+    micros_per_beat: i64,
+    /// Beat at `time_origin`, in millionths of a beat
+    beat_origin: i64,
+    /// Ghost time in microseconds
+    time_origin: i64,
+}
+
+impl Timeline {
+    fn new(tempo: f64, ghost: i64) -> Self {
+        Self {
+            micros_per_beat: (60_000_000.0 / tempo.max(1.0)).round() as i64,
+            beat_origin: 0,
+            time_origin: ghost,
+        }
+    }
+
+    fn tempo(&self) -> f64 {
+        60_000_000.0 / self.micros_per_beat as f64
+    }
+
+    fn beat_at(&self, ghost: f64) -> f64 {
+        self.beat_origin as f64 / 1_000_000.0
+            + (ghost - self.time_origin as f64) / self.micros_per_beat as f64
+    }
+
+    fn ghost_at(&self, beat: f64) -> f64 {
+        self.time_origin as f64
+            + (beat - self.beat_origin as f64 / 1_000_000.0) * self.micros_per_beat as f64
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        [self.micros_per_beat, self.beat_origin, self.time_origin]
+            .iter()
+            .flat_map(|value| value.to_be_bytes())
+            .collect()
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        Some(Self {
+            micros_per_beat: read_i64(bytes, 0).filter(|&micros| micros > 0)?,
+            beat_origin: read_i64(bytes, 8)?,
+            time_origin: read_i64(bytes, 16)?,
+        })
+    }
+}
+
+fn read_i64(bytes: &[u8], at: usize) -> Option<i64> {
+    Some(i64::from_be_bytes(bytes.get(at..at + 8)?.try_into().ok()?))
+}
+
+/// Append a payload entry: its key, the length of its value and the value
+fn write_entry(payload: &mut Vec<u8>, key: [u8; 4], value: &[u8]) {
+    payload.extend(key);
+    payload.extend((value.len() as u32).to_be_bytes());
+    payload.extend(value);
+}
+
+/// The entries of a payload, up to the first that doesn't fit
+fn entries(mut payload: &[u8]) -> Vec<([u8; 4], &[u8])> {
+    let mut entries = Vec::new();
+    while payload.len() >= 8 {
+        let key: [u8; 4] = payload[..4].try_into().unwrap();
+        let length = u32::from_be_bytes(payload[4..8].try_into().unwrap()) as usize;
+        let Some(value) = payload.get(8..8 + length) else {
+            break;
+        };
+        entries.push((key, value));
+        payload = &payload[8 + length..];
+    }
+    entries
+}
+
+/// What a peer announces: its session, the session's timeline and where
+/// to measure its ghost time
+#[derive(Clone, Debug, PartialEq)]
+struct Announcement {
+    session: NodeId,
+    timeline: Timeline,
+    endpoint: Option<SocketAddrV4>,
+}
+
+impl Announcement {
+    fn encode(&self) -> Vec<u8> {
+        let mut payload = Vec::new();
+        write_entry(&mut payload, TIMELINE, &self.timeline.encode());
+        write_entry(&mut payload, SESSION, &self.session);
+        // Stopped, at time zero, so it never overrides a peer's start/stop
+        write_entry(&mut payload, START_STOP, &[0; 17]);
+        if let Some(endpoint) = self.endpoint {
+            let mut value = endpoint.ip().octets().to_vec();
+            value.extend(endpoint.port().to_be_bytes());
+            write_entry(&mut payload, ENDPOINT, &value);
+        }
+        payload
+    }
+
+    fn decode(payload: &[u8]) -> Option<Self> {
+        let (mut session, mut timeline, mut endpoint) = (None, None, None);
+        for (key, value) in entries(payload) {
+            match key {
+                TIMELINE => timeline = Timeline::decode(value),
+                SESSION => session = value.try_into().ok(),
+                ENDPOINT if value.len() == 6 => {
+                    let ip = Ipv4Addr::new(value[0], value[1], value[2], value[3]);
+                    let port = u16::from_be_bytes([value[4], value[5]]);
+                    endpoint = Some(SocketAddrV4::new(ip, port));
+                }
+                _ => {}
+            }
+        }
+        Some(Self {
+            session: session?,
+            timeline: timeline?,
+            endpoint,
+        })
+    }
+}
+
+fn discovery_message(kind: u8, ttl: u8, id: &NodeId, payload: &[u8]) -> Vec<u8> {
+    let mut message = DISCOVERY_HEADER.to_vec();
+    message.extend([kind, ttl, 0, 0]);
+    message.extend(id);
+    message.extend(payload);
+    message
+}
+
+fn measurement_message(kind: u8, payload: &[u8]) -> Vec<u8> {
+    let mut message = MEASUREMENT_HEADER.to_vec();
+    message.push(kind);
+    message.extend(payload);
+    message
+}
+
+fn time_entry(payload: &mut Vec<u8>, key: [u8; 4], micros: i64) {
+    write_entry(payload, key, &micros.to_be_bytes());
+}
+
+/// A peer heard from
+struct Peer {
+    announcement: Announcement,
+    /// Host time its announcement runs out
+    expires: i64,
+}
+
+/// Pings measuring a session's ghost time
+struct Measurement {
+    session: NodeId,
+    endpoint: SocketAddrV4,
+    /// Samples of the session's ghost time minus our host time
+    samples: Vec<f64>,
+    started: i64,
+    last_ping: i64,
+}
+
+/// Messages to send, and where
+type Outgoing = Vec<(SocketAddr, Vec<u8>)>;
+
+/// A Link peer's protocol state, apart from its sockets
+///
+/// Times are the peer's host time in microseconds.
+struct Node {
+    id: NodeId,
+    /// Where this peer answers pings
+    endpoint: SocketAddrV4,
+    session: NodeId,
+    /// The session's ghost time minus host time
+    ghost_offset: i64,
+    timeline: Timeline,
+    peers: HashMap<NodeId, Peer>,
+    measurement: Option<Measurement>,
+    /// Sessions measured and found younger than ours
+    passed: HashSet<NodeId>,
+    measured_at: i64,
+    broadcast_at: Option<i64>,
+}
+
+impl Node {
+    /// A peer founding a session at `tempo`
+    fn new(id: NodeId, endpoint: SocketAddrV4, tempo: f64, now: i64) -> Self {
+        Self {
+            id,
+            endpoint,
+            session: id,
+            // A session's ghost time starts when it is founded, so older
+            // sessions are further along
+            ghost_offset: -now,
+            timeline: Timeline::new(tempo, 0),
+            peers: HashMap::new(),
+            measurement: None,
+            passed: HashSet::new(),
+            measured_at: now,
+            broadcast_at: None,
+        }
+    }
+
+    fn announcement(&self) -> Announcement {
+        Announcement {
+            session: self.session,
+            timeline: self.timeline,
+            endpoint: Some(self.endpoint),
+        }
+    }
+
+    fn ghost(&self, host: i64) -> i64 {
+        // The offset comes from other peers' times, which may be garbage
+        host.wrapping_add(self.ghost_offset)
+    }
+
+    fn beat_at(&self, host: i64) -> f64 {
+        self.timeline.beat_at(self.ghost(host) as f64)
+    }
+
+    fn host_at(&self, beat: f64) -> f64 {
+        self.timeline.ghost_at(beat) - self.ghost_offset as f64
+    }
+
+    /// Other peers in this peer's session
+    fn peers(&self) -> usize {
+        self.peers
+            .values()
+            .filter(|peer| peer.announcement.session == self.session)
+            .count()
+    }
+
+    fn bye(&self) -> Vec<u8> {
+        discovery_message(BYEBYE, 0, &self.id, &[])
+    }
+
+    /// Take in a message from `from`
+    fn receive(&mut self, message: &[u8], from: SocketAddr, now: i64) -> Outgoing {
+        if let Some(rest) = message.strip_prefix(DISCOVERY_HEADER) {
+            if rest.len() >= 12 {
+                let id: NodeId = rest[4..12].try_into().unwrap();
+                return self.discovered(rest[0], rest[1], id, &rest[12..], from, now);
+            }
+        } else if let Some((&kind, payload)) = message
+            .strip_prefix(MEASUREMENT_HEADER)
+            .and_then(|rest| rest.split_first())
+        {
+            match kind {
+                PING => return self.ping(payload, from, now),
+                PONG => return self.pong(payload, now),
+                _ => {}
+            }
+        }
+        Vec::new()
+    }
+
+    fn discovered(
+        &mut self,
+        kind: u8,
+        ttl: u8,
+        id: NodeId,
+        payload: &[u8],
+        from: SocketAddr,
+        now: i64,
+    ) -> Outgoing {
+        if id == self.id {
+            return Vec::new();
+        }
+        if kind == BYEBYE {
+            self.peers.remove(&id);
+            return Vec::new();
+        }
+        let Some(announcement) = Announcement::decode(payload).filter(|_| kind != 0) else {
+            return Vec::new();
+        };
+
+        let mut outgoing = Vec::new();
+        let known = self.peers.contains_key(&id);
+        if kind == ALIVE && !known {
+            // Answer a new peer at once, instead of at our next announcement
+            let payload = self.announcement().encode();
+            outgoing.push((from, discovery_message(RESPONSE, TTL, &self.id, &payload)));
+        }
+        if announcement.session == self.session {
+            self.timeline = announcement.timeline;
+        } else if !self.passed.contains(&announcement.session) && self.measurement.is_none() {
+            if let Some(endpoint) = announcement.endpoint {
+                outgoing.extend(self.measure(announcement.session, endpoint, now));
+            }
+        }
+        self.peers.insert(
+            id,
+            Peer {
+                announcement,
+                expires: now + ttl as i64 * 1_000_000,
+            },
+        );
+        outgoing
+    }
+
+    fn measure(&mut self, session: NodeId, endpoint: SocketAddrV4, now: i64) -> Outgoing {
+        self.measurement = Some(Measurement {
+            session,
+            endpoint,
+            samples: Vec::new(),
+            started: now,
+            last_ping: now,
+        });
+        let mut payload = Vec::new();
+        time_entry(&mut payload, HOST_TIME, now);
+        vec![(endpoint.into(), measurement_message(PING, &payload))]
+    }
+
+    /// Answer a ping with our session and ghost time, echoing its payload
+    fn ping(&self, payload: &[u8], from: SocketAddr, now: i64) -> Outgoing {
+        let mut pong = Vec::new();
+        write_entry(&mut pong, SESSION, &self.session);
+        time_entry(&mut pong, GHOST_TIME, self.ghost(now));
+        pong.extend(payload);
+        vec![(from, measurement_message(PONG, &pong))]
+    }
+
+    fn pong(&mut self, payload: &[u8], now: i64) -> Outgoing {
+        let Some(measurement) = &mut self.measurement else {
+            return Vec::new();
+        };
+        let (mut session, mut ghost, mut prev_ghost, mut host) = (None, 0, 0, 0);
+        for (key, value) in entries(payload) {
+            match key {
+                SESSION => session = NodeId::try_from(value).ok(),
+                GHOST_TIME => ghost = read_i64(value, 0).unwrap_or(0),
+                PREV_GHOST_TIME => prev_ghost = read_i64(value, 0).unwrap_or(0),
+                HOST_TIME => host = read_i64(value, 0).unwrap_or(0),
+                _ => {}
+            }
+        }
+        if session != Some(measurement.session) || ghost == 0 || host == 0 {
+            return Vec::new();
+        }
+        // The pong left halfway between the ping and its arrival; times
+        // that overflow when added up are malformed and dropped
+        if let Some(round_trip) = now.checked_add(host) {
+            measurement
+                .samples
+                .push(ghost as f64 - round_trip as f64 / 2.0);
+        }
+        if let Some(ghosts) = ghost.checked_add(prev_ghost).filter(|_| prev_ghost != 0) {
+            measurement.samples.push(ghosts as f64 / 2.0 - host as f64);
+        }
+        if measurement.samples.len() >= DATA_POINTS {
+            self.finish_measurement(now);
+            return Vec::new();
+        }
+
+        measurement.last_ping = now;
+        let mut payload = Vec::new();
+        time_entry(&mut payload, HOST_TIME, now);
+        time_entry(&mut payload, PREV_GHOST_TIME, ghost);
+        vec![(
+            measurement.endpoint.into(),
+            measurement_message(PING, &payload),
+        )]
+    }
+
+    /// Join the measured session if it is older than ours
+    fn finish_measurement(&mut self, now: i64) {
+        let Some(mut measurement) = self.measurement.take() else {
+            return;
+        };
+        if measurement.samples.is_empty() {
+            self.passed.insert(measurement.session);
+            return;
+        }
+        measurement.samples.sort_by(f64::total_cmp);
+        let offset = measurement.samples[measurement.samples.len() / 2].round() as i64;
+        self.measured_at = now;
+
+        if measurement.session == self.session {
+            self.ghost_offset = offset;
+            return;
+        }
+        let older = offset
+            .checked_sub(self.ghost_offset)
+            .map_or(f64::NAN, |older| older as f64);
+        if older > SESSION_EPSILON
+            || (older.abs() < SESSION_EPSILON && measurement.session < self.session)
+        {
+            let timeline = self
+                .peers
+                .values()
+                .find(|peer| peer.announcement.session == measurement.session)
+                .map(|peer| peer.announcement.timeline);
+            if let Some(timeline) = timeline {
+                self.session = measurement.session;
+                self.ghost_offset = offset;
+                self.timeline = timeline;
+                self.passed.clear();
+                return;
+            }
+        }
+        self.passed.insert(measurement.session);
+    }
+
+    /// Announce this peer, forget silent ones and keep measurements going
+    fn tick(&mut self, now: i64) -> Outgoing {
+        let mut outgoing = Vec::new();
+        if self
+            .broadcast_at
+            .is_none_or(|at| now - at >= BROADCAST_PERIOD)
+        {
+            self.broadcast_at = Some(now);
+            let payload = self.announcement().encode();
+            let alive = discovery_message(ALIVE, TTL, &self.id, &payload);
+            outgoing.push((MULTICAST.into(), alive));
+        }
+        self.peers.retain(|_, peer| peer.expires > now);
+
+        match &mut self.measurement {
+            Some(measurement) if now - measurement.started > MEASUREMENT_TIMEOUT => {
+                self.finish_measurement(now)
+            }
+            Some(measurement) if now - measurement.last_ping > PING_PERIOD => {
+                measurement.last_ping = now;
+                let mut payload = Vec::new();
+                time_entry(&mut payload, HOST_TIME, now);
+                outgoing.push((
+                    measurement.endpoint.into(),
+                    measurement_message(PING, &payload),
+                ));
+            }
+            Some(_) => {}
+            None if now - self.measured_at > REMEASURE_PERIOD => {
+                let endpoint = self
+                    .peers
+                    .values()
+                    .filter(|peer| peer.announcement.session == self.session)
+                    .find_map(|peer| peer.announcement.endpoint);
+                if let Some(endpoint) = endpoint {
+                    outgoing.extend(self.measure(self.session, endpoint, now));
+                }
+            }
+            None => {}
+        }
+        outgoing
+    }
+}
+
+/// A random, printable peer id
+fn random_id() -> NodeId {
+    use std::hash::BuildHasher;
+    let bits = std::collections::hash_map::RandomState::new()
+        .hash_one((std::process::id(), Instant::now()))
+        .to_be_bytes();
+    bits.map(|bit| b'a' + bit % 26)
+}
+
+/// The address other hosts reach this one at
+fn local_address() -> Ipv4Addr {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0));
+    let address = socket.and_then(|socket| {
+        socket.connect(MULTICAST)?;
+        socket.local_addr()
+    });
+    match address {
+        Ok(SocketAddr::V4(address)) if !address.ip().is_unspecified() => *address.ip(),
+        _ => Ipv4Addr::LOCALHOST,
+    }
+}
+
+/// A socket that hears every peer's announcements, shared with the other
+/// Link programs on this host
+fn multicast_socket(interface: Ipv4Addr) -> io::Result<UdpSocket> {
+    use socket2::{Domain, Protocol, Socket, Type};
+
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    #[cfg(unix)]
+    socket.set_reuse_port(true)?;
+    socket.bind(&SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, MULTICAST.port()).into())?;
+    let socket = UdpSocket::from(socket);
+    socket.join_multicast_v4(MULTICAST.ip(), &interface)?;
+    Ok(socket)
+}
+
+struct Shared {
+    node: Mutex<Node>,
+    /// When host time is zero
+    epoch: Instant,
+    socket: UdpSocket,
+    running: AtomicBool,
+}
+
+impl Shared {
+    fn now(&self) -> i64 {
+        self.epoch.elapsed().as_micros() as i64
+    }
+
+    fn node(&self) -> MutexGuard<'_, Node> {
+        self.node.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn send(&self, outgoing: Outgoing) {
+        for (to, message) in outgoing {
+            // Lost messages are sent again, like dropped packets
+            let _ = self.socket.send_to(&message, to);
+        }
+    }
+
+    /// Take in messages from `socket` until the session is left, ticking
+    /// the node with `tick`
+    fn listen(&self, socket: &UdpSocket, tick: bool) {
+        let mut buffer = [0u8; 512];
+        while self.running.load(Ordering::Relaxed) {
+            let received = socket.recv_from(&mut buffer);
+            let now = self.now();
+            let mut node = self.node();
+            let mut outgoing = match received {
+                Ok((length, from)) => node.receive(&buffer[..length], from, now),
+                Err(_) => Vec::new(),
+            };
+            if tick {
+                outgoing.extend(node.tick(now));
+            }
+            drop(node);
+            self.send(outgoing);
+        }
+    }
+}
+
+/// Membership of a Link session, kept up by background threads until it
+/// is dropped
+pub struct LinkSession {
+    shared: Arc<Shared>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl LinkSession {
+    /// Join the Link sessions on the network, founding one at `tempo` BPM
+    /// until an older one turns up
+    pub fn join(tempo: f64) -> io::Result<Self> {
+        let interface = local_address();
+        let multicast = multicast_socket(interface)?;
+        let socket = UdpSocket::bind((interface, 0))?;
+        let endpoint = match socket.local_addr()? {
+            SocketAddr::V4(endpoint) => endpoint,
+            SocketAddr::V6(_) => unreachable!("bound to an IPv4 address"),
+        };
+        let poll = Some(Duration::from_millis(20));
+        socket.set_read_timeout(poll)?;
+        multicast.set_read_timeout(poll)?;
+
+        let shared = Arc::new(Shared {
+            node: Mutex::new(Node::new(random_id(), endpoint, tempo, 0)),
+            epoch: Instant::now(),
+            socket,
+            running: AtomicBool::new(true),
+        });
+        let unicast = shared.socket.try_clone()?;
+        let threads = vec![
+            spawn_listener(&shared, unicast, true),
+            spawn_listener(&shared, multicast, false),
+        ];
+        Ok(Self { shared, threads })
+    }
+
+    /// How many other programs are in the session
+    pub fn peers(&self) -> usize {
+        self.shared.node().peers()
+    }
+
+    /// The session's tempo in BPM
+    pub fn tempo(&self) -> f64 {
+        self.shared.node().timeline.tempo()
+    }
+
+    /// The session's beat now
+    pub fn beat(&self) -> f64 {
+        let now = self.shared.now();
+        self.shared.node().beat_at(now)
+    }
+
+    /// When the session reaches `beat`, at its tempo now
+    pub fn time_at(&self, beat: f64) -> Instant {
+        let host = self.shared.node().host_at(beat);
+        self.shared.epoch + Duration::from_secs_f64(host.max(0.0) / 1_000_000.0)
+    }
+
+    /// A clock whose beat 0 is the session's next multiple of `quantum`
+    /// beats, so loops line up with the other programs' bars
+    pub fn clock(&self, quantum: f64) -> LinkClock<'_> {
+        let beat = self.beat();
+        let start = if quantum > 0.0 {
+            (beat / quantum).ceil() * quantum
+        } else {
+            beat
+        };
+        LinkClock {
+            session: self,
+            start,
+        }
+    }
+}
+
+fn spawn_listener(shared: &Arc<Shared>, socket: UdpSocket, tick: bool) -> JoinHandle<()> {
+    let shared = Arc::clone(shared);
+    std::thread::spawn(move || shared.listen(&socket, tick))
+}
+
+impl Drop for LinkSession {
+    fn drop(&mut self) {
+        self.shared.running.store(false, Ordering::Relaxed);
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+        let bye = self.shared.node().bye();
+        self.shared.send(vec![(MULTICAST.into(), bye)]);
+    }
+}
+
+/// A Link session's beats, from a bar line of the session
+pub struct LinkClock<'a> {
+    session: &'a LinkSession,
+    /// The session's beat where this clock's beat 0 is
+    start: f64,
+}
+
+impl Clock for LinkClock<'_> {
+    fn wait_until(&mut self, beat: f64) -> bool {
+        // Wait in short steps, so tempo changes take effect
+        loop {
+            let at = self.session.time_at(self.start + beat);
+            let Some(wait) = at.checked_duration_since(Instant::now()) else {
+                return true;
+            };
+            std::thread::sleep(wait.min(Duration::from_millis(20)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn endpoint(port: u16) -> SocketAddrV4 {
+        SocketAddrV4::new(Ipv4Addr::LOCALHOST, port)
+    }
+
+    #[test]
+    fn test_announcement_round_trip() {
+        let announcement = Announcement {
+            session: *b"abcdefgh",
+            timeline: Timeline::new(90.0, 1_000_000),
+            endpoint: Some(endpoint(4000)),
+        };
+        let message = discovery_message(ALIVE, TTL, b"peer0001", &announcement.encode());
+        assert_eq!(&message[..8], b"_asdp_v\x01");
+        assert_eq!(Announcement::decode(&message[20..]), Some(announcement));
+
+        let timeline = Timeline::new(120.0, 1_000_000);
+        assert_eq!(timeline.micros_per_beat, 500_000);
+        assert_eq!(timeline.beat_at(2_000_000.0), 2.0);
+        assert_eq!(timeline.ghost_at(2.0), 2_000_000.0);
+    }
+
+    /// Deliver messages between nodes, by endpoint, until none are left
+    fn exchange(nodes: &mut [(Node, i64)], mut outgoing: Vec<(SocketAddr, SocketAddr, Vec<u8>)>) {
+        while let Some((from, to, message)) = outgoing.pop() {
+            for (node, clock) in nodes.iter_mut() {
+                let node_address = SocketAddr::from(node.endpoint);
+                if to == node_address || (to == SocketAddr::from(MULTICAST) && from != node_address)
+                {
+                    let replies = node.receive(&message, from, *clock);
+                    outgoing.extend(replies.into_iter().map(|(to, m)| (node_address, to, m)));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_join_older_session() {
+        // Host clocks start at different times; `a` founded its session
+        // ten seconds before `b`
+        let mut nodes = [
+            (Node::new(*b"aaaaaaaa", endpoint(1), 100.0, 0), 10_000_000),
+            (Node::new(*b"bbbbbbbb", endpoint(2), 140.0, 3_000), 3_000),
+        ];
+        for _ in 0..3 {
+            for index in 0..nodes.len() {
+                let (node, clock) = &mut nodes[index];
+                node.broadcast_at = None;
+                let from = SocketAddr::from(node.endpoint);
+                let outgoing = node.tick(*clock);
+                let outgoing = outgoing.into_iter().map(|(to, m)| (from, to, m)).collect();
+                exchange(&mut nodes, outgoing);
+            }
+            for (_, clock) in nodes.iter_mut() {
+                *clock += 1_000;
+            }
+        }
+
+        let (a, a_clock) = &nodes[0];
+        let (b, b_clock) = &nodes[1];
+        assert_eq!(a.session, *b"aaaaaaaa");
+        assert_eq!(b.session, *b"aaaaaaaa");
+        assert_eq!(b.timeline.tempo(), 100.0);
+        assert_eq!((a.peers(), b.peers()), (1, 1));
+        assert!((a.beat_at(*a_clock) - b.beat_at(*b_clock)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_malformed_pong() {
+        let mut node = Node::new(*b"aaaaaaaa", endpoint(1), 120.0, 1_000);
+        node.measure(*b"bbbbbbbb", endpoint(2), 2_000);
+        let mut pong = Vec::new();
+        write_entry(&mut pong, SESSION, b"bbbbbbbb");
+        time_entry(&mut pong, GHOST_TIME, i64::MAX);
+        time_entry(&mut pong, PREV_GHOST_TIME, i64::MAX);
+        time_entry(&mut pong, HOST_TIME, 1_000);
+        for _ in 0..DATA_POINTS {
+            node.pong(&pong, 3_000);
+        }
+
+        // The samples that overflow are dropped, and a session whose offset
+        // from ours overflows isn't joined
+        assert!(node.measurement.is_none());
+        assert_eq!(node.session, *b"aaaaaaaa");
+        node.beat_at(4_000);
+    }
+}
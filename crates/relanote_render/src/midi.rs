@@ -2,10 +2,18 @@
 
 use midly::{Format, Header, MidiMessage, Smf, Timing, Track, TrackEvent, TrackEventKind};
 use relanote_ast::Articulation;
+use relanote_core::{Diagnostic, DiagnosticCode, Diagnostics, Span};
 use relanote_eval::value::{
-    BlockValue, IntervalValue, PartValue, SlotValue, SongValue, SynthValue,
+    tuplet_shares, ADSREnvelope, AutomationValue, BlockValue, EffectValue, EqParams, IntervalValue,
+    NoteMeta, PartValue, SectionValue, SlotValue, SongMetadata, SongValue, Swing, SynthValue,
 };
 
+use crate::channels::ChannelAllocator;
+use crate::drums::DrumKeys;
+use crate::error::RenderError;
+use crate::mpe::{MemberChannels, MASTER_CHANNEL, MEMBER_CHANNELS};
+use crate::timeline::Timeline;
+
 // MIDI CC numbers for synth parameters
 const CC_MODULATION: u8 = 1; // Vibrato/Modulation
 const CC_RESONANCE: u8 = 71; // Resonance (Sound Controller 2)
@@ -13,8 +21,36 @@ const CC_RELEASE: u8 = 72; // Release Time (Sound Controller 3)
 const CC_ATTACK: u8 = 73; // Attack Time (Sound Controller 4)
 const CC_CUTOFF: u8 = 74; // Brightness/Cutoff (Sound Controller 5)
 const CC_DECAY: u8 = 75; // Decay Time (Sound Controller 6)
+const CC_VOLUME: u8 = 7; // Channel Volume
+const CC_EXPRESSION: u8 = 11; // Expression (part dynamics)
+const CC_DATA_ENTRY: u8 = 6;
+const CC_DATA_ENTRY_LSB: u8 = 38;
+const CC_RPN_LSB: u8 = 100;
+const CC_RPN_MSB: u8 = 101;
+
+// Undefined controllers carrying compressor and EQ settings, for a DAW or
+// hardware effect to map
+const CC_COMPRESSOR_THRESHOLD: u8 = 102;
+const CC_COMPRESSOR_RATIO: u8 = 103;
+const CC_COMPRESSOR_ATTACK: u8 = 104;
+const CC_COMPRESSOR_RELEASE: u8 = 105;
+const CC_EQ_LOW: u8 = 106;
+const CC_EQ_MID: u8 = 107;
+const CC_EQ_HIGH: u8 = 108;
+
+// Registered parameter numbers (MSB, LSB)
+const RPN_PITCH_BEND_SENSITIVITY: (u8, u8) = (0, 0);
+const RPN_MPE_CONFIGURATION: (u8, u8) = (0, 6);
+const RPN_NULL: (u8, u8) = (127, 127);
+
+/// Number of pitch bend messages used to draw a portamento ramp
+const GLIDE_STEPS: u32 = 16;
+
+/// Controller automation is sampled this many times per beat
+const AUTOMATION_STEP_DIVISION: u32 = 16;
 
 /// MIDI renderer configuration
+#[derive(Clone)]
 pub struct MidiConfig {
     /// Ticks per quarter note
     pub ticks_per_beat: u16,
@@ -24,6 +60,20 @@ pub struct MidiConfig {
     pub base_note: u8,
     /// Pitch bend range in semitones (default: 2)
     pub pitch_bend_range: f64,
+    /// Default portamento glide time in milliseconds
+    pub glide_ms: f64,
+    /// Beats per bar (sections start on bar lines)
+    pub beats_per_bar: u32,
+    /// Start every section at the beginning of the song instead of one after another
+    pub parallel_sections: bool,
+    /// Render melodic parts as an MPE zone, giving every note its own channel
+    /// (and pitch bend) so microtonal chords stay in tune
+    ///
+    /// The zone is one instrument: melodic parts that play at the same time
+    /// share its sound and controllers, and rendering warns about them.
+    pub mpe: bool,
+    /// Title, composer and copyright for the conductor track
+    pub metadata: SongMetadata,
 }
 
 impl Default for MidiConfig {
@@ -33,6 +83,11 @@ impl Default for MidiConfig {
             tempo: 120,
             base_note: 60, // C4 (middle C)
             pitch_bend_range: 2.0,
+            glide_ms: 100.0,
+            beats_per_bar: 4,
+            parallel_sections: false,
+            mpe: false,
+            metadata: SongMetadata::default(),
         }
     }
 }
@@ -56,6 +111,39 @@ fn cents_to_midi(base_note: u8, cents: f64, pitch_bend_range: f64) -> (u8, u16)
     (midi_note, pitch_bend)
 }
 
+/// Set a registered parameter on a channel, then deselect it so later data
+/// entry messages can't change it by accident
+fn rpn_events(channel: u8, parameter: (u8, u8), value: (u8, u8)) -> Vec<TrackEventKind<'static>> {
+    [
+        (CC_RPN_MSB, parameter.0),
+        (CC_RPN_LSB, parameter.1),
+        (CC_DATA_ENTRY, value.0),
+        (CC_DATA_ENTRY_LSB, value.1),
+        (CC_RPN_MSB, RPN_NULL.0),
+        (CC_RPN_LSB, RPN_NULL.1),
+    ]
+    .into_iter()
+    .map(|(controller, value)| TrackEventKind::Midi {
+        channel: channel.into(),
+        message: MidiMessage::Controller {
+            controller: controller.into(),
+            value: value.into(),
+        },
+    })
+    .collect()
+}
+
+/// Set a channel's pitch bend range (RPN 0) in semitones and cents
+fn bend_range_events(channel: u8, semitones: f64) -> Vec<TrackEventKind<'static>> {
+    let semitones = semitones.clamp(0.0, 127.0);
+    let cents = ((semitones - semitones.floor()) * 100.0).round().min(99.0) as u8;
+    rpn_events(
+        channel,
+        RPN_PITCH_BEND_SENSITIVITY,
+        (semitones.floor() as u8, cents),
+    )
+}
+
 /// Convert filter cutoff frequency (Hz) to MIDI CC value (0-127)
 /// Uses logarithmic scaling: 20Hz -> 0, ~5000Hz -> 64, 20000Hz -> 127
 fn cutoff_to_cc(cutoff_hz: f64) -> u8 {
@@ -120,62 +208,166 @@ fn synth_to_cc_events(synth: &SynthValue, channel: u8) -> Vec<TrackEvent<'static
         });
     }
 
-    // ADSR envelope
-    events.push(TrackEvent {
-        delta: 0.into(),
-        kind: TrackEventKind::Midi {
-            channel: channel.into(),
-            message: MidiMessage::Controller {
-                controller: CC_ATTACK.into(),
-                value: adsr_time_to_cc(synth.envelope.attack).into(),
-            },
-        },
-    });
+    events.extend(adsr_to_cc_events(&synth.envelope, channel));
 
-    events.push(TrackEvent {
-        delta: 0.into(),
-        kind: TrackEventKind::Midi {
-            channel: channel.into(),
-            message: MidiMessage::Controller {
-                controller: CC_DECAY.into(),
-                value: adsr_time_to_cc(synth.envelope.decay).into(),
+    // Detune as modulation (if significant)
+    if synth.detune_cents.abs() > 0.1 {
+        // Map detune (-100 to +100 cents typical) to modulation depth
+        let mod_value = ((synth.detune_cents.abs() / 100.0) * 64.0).min(127.0) as u8;
+        events.push(TrackEvent {
+            delta: 0.into(),
+            kind: TrackEventKind::Midi {
+                channel: channel.into(),
+                message: MidiMessage::Controller {
+                    controller: CC_MODULATION.into(),
+                    value: mod_value.into(),
+                },
             },
-        },
-    });
+        });
+    }
+
+    events
+}
 
-    events.push(TrackEvent {
+/// Attack, decay and release times as sound controller messages
+fn adsr_to_cc_events(envelope: &ADSREnvelope, channel: u8) -> Vec<TrackEvent<'static>> {
+    [
+        (CC_ATTACK, envelope.attack),
+        (CC_DECAY, envelope.decay),
+        (CC_RELEASE, envelope.release),
+    ]
+    .into_iter()
+    .map(|(controller, time)| TrackEvent {
         delta: 0.into(),
         kind: TrackEventKind::Midi {
             channel: channel.into(),
             message: MidiMessage::Controller {
-                controller: CC_RELEASE.into(),
-                value: adsr_time_to_cc(synth.envelope.release).into(),
+                controller: controller.into(),
+                value: adsr_time_to_cc(time).into(),
             },
         },
-    });
+    })
+    .collect()
+}
 
-    // Detune as modulation (if significant)
-    if synth.detune_cents.abs() > 0.1 {
-        // Map detune (-100 to +100 cents typical) to modulation depth
-        let mod_value = ((synth.detune_cents.abs() / 100.0) * 64.0).min(127.0) as u8;
-        events.push(TrackEvent {
+/// Settings of the compressor and EQ in a chain as controller messages
+///
+/// Thresholds from -60 to 0 dB, ratios from 1 to 20 and times up to a
+/// second span the controllers' range; EQ gains have 0 dB at 64.
+fn effect_cc_events(chain: &[EffectValue], channel: u8) -> Vec<TrackEvent<'static>> {
+    let scale = |value: f64, min: f64, max: f64| {
+        (((value - min) / (max - min)).clamp(0.0, 1.0) * 127.0).round() as u8
+    };
+    let gain = |db: f64| scale(db, -EqParams::MAX_GAIN, EqParams::MAX_GAIN);
+    chain
+        .iter()
+        .flat_map(|effect| match effect {
+            EffectValue::Compressor(compressor) => vec![
+                (
+                    CC_COMPRESSOR_THRESHOLD,
+                    scale(compressor.threshold, -60.0, 0.0),
+                ),
+                (CC_COMPRESSOR_RATIO, scale(compressor.ratio, 1.0, 20.0)),
+                (
+                    CC_COMPRESSOR_ATTACK,
+                    scale(compressor.attack_ms, 0.0, 1000.0),
+                ),
+                (
+                    CC_COMPRESSOR_RELEASE,
+                    scale(compressor.release_ms, 0.0, 1000.0),
+                ),
+            ],
+            EffectValue::Eq(eq) => vec![
+                (CC_EQ_LOW, gain(eq.low)),
+                (CC_EQ_MID, gain(eq.mid)),
+                (CC_EQ_HIGH, gain(eq.high)),
+            ],
+            _ => vec![],
+        })
+        .map(|(controller, value)| TrackEvent {
             delta: 0.into(),
             kind: TrackEventKind::Midi {
                 channel: channel.into(),
                 message: MidiMessage::Controller {
-                    controller: CC_MODULATION.into(),
-                    value: mod_value.into(),
+                    controller: controller.into(),
+                    value: value.into(),
                 },
             },
+        })
+        .collect()
+}
+
+/// A MIDI event at an absolute tick position within a track
+///
+/// Parts are rendered into absolute-time events first so that per-note
+/// offsets (humanize, etc.) can move events freely; they are sorted and
+/// converted to delta times when the track is assembled.
+struct TimedEvent {
+    tick: u32,
+    kind: TrackEventKind<'static>,
+}
+
+impl TimedEvent {
+    fn new(tick: u32, kind: TrackEventKind<'static>) -> Self {
+        Self { tick, kind }
+    }
+
+    /// Ordering priority at equal ticks: note-offs first so repeated notes retrigger,
+    /// then meta events (names, markers, lyrics) ahead of the notes they label
+    fn priority(&self) -> u8 {
+        match self.kind {
+            TrackEventKind::Midi {
+                message: MidiMessage::NoteOff { .. },
+                ..
+            } => 0,
+            TrackEventKind::Meta(_) => 1,
+            _ => 2,
+        }
+    }
+}
+
+/// A sounding slot (note or chord) placed on a part's timeline
+pub(crate) struct ScheduledSlot<'a> {
+    pub(crate) time: u32,
+    pub(crate) duration: u32,
+    pub(crate) slot: &'a SlotValue,
+}
+
+/// Per-part rendering settings shared by all of the part's notes
+struct PartContext<'a> {
+    channel: u8,
+    /// MIDI note the part's intervals are relative to
+    base_note: u8,
+    velocity_scale: f64,
+    drums: Option<DrumKeys<'a>>,
+    /// Member channels to spread notes over, in MPE mode
+    members: Option<&'a mut MemberChannels>,
+}
+
+/// Sort absolute-time events and convert them into a delta-timed track
+fn events_to_track(mut events: Vec<TimedEvent>) -> Track<'static> {
+    events.sort_by_key(|e| (e.tick, e.priority()));
+
+    let mut track = Track::new();
+    let mut last_tick = 0;
+    for event in events {
+        track.push(TrackEvent {
+            delta: (event.tick - last_tick).into(),
+            kind: event.kind,
         });
+        last_tick = event.tick;
     }
 
-    events
+    track.push(TrackEvent {
+        delta: 0.into(),
+        kind: TrackEventKind::Meta(midly::MetaMessage::EndOfTrack),
+    });
+    track
 }
 
 /// MIDI renderer
 pub struct MidiRenderer {
-    config: MidiConfig,
+    pub(crate) config: MidiConfig,
 }
 
 impl MidiRenderer {
@@ -184,30 +376,91 @@ impl MidiRenderer {
     }
 
     /// Render a song to MIDI
-    pub fn render(&self, song: &SongValue) -> Vec<u8> {
+    pub fn render(&self, song: &SongValue) -> Result<Vec<u8>, RenderError> {
+        Ok(self.render_with_diagnostics(song)?.0)
+    }
+
+    /// Render a song to MIDI, also returning warnings about content that
+    /// could not be represented (e.g. parts dropped for lack of channels)
+    pub fn render_with_diagnostics(
+        &self,
+        song: &SongValue,
+    ) -> Result<(Vec<u8>, Diagnostics), RenderError> {
         let mut tracks = Vec::new();
+        let mut diagnostics = Diagnostics::new();
 
-        // Meta track (tempo)
-        let mut meta_track = Track::new();
-        let tempo_microseconds = 60_000_000 / self.config.tempo;
-        meta_track.push(TrackEvent {
-            delta: 0.into(),
-            kind: TrackEventKind::Meta(midly::MetaMessage::Tempo(tempo_microseconds.into())),
-        });
-        meta_track.push(TrackEvent {
-            delta: 0.into(),
-            kind: TrackEventKind::Meta(midly::MetaMessage::EndOfTrack),
+        // Meta track (metadata, tempo and markers, filled in once parts are rendered)
+        let mut meta_events = self.metadata_events();
+        let timeline = self.timeline(song);
+        if timeline.end().is_none() {
+            return Err(RenderError::TooLong);
+        }
+        meta_events.extend(self.tempo_events(song, &timeline));
+        let part_channels = self.part_channels(song);
+        if let Some((first, second)) = self.shared_zone(song) {
+            diagnostics.add(
+                Diagnostic::warning(
+                    format!(
+                        "parts '{}' and '{}' play together in one MPE zone, so they share its instrument, volume and pan",
+                        first, second
+                    ),
+                    Span::dummy(),
+                )
+                .with_code(DiagnosticCode::SharedMpeZone),
+            );
+        }
+
+        // An MPE zone is configured once, before any notes: the configuration
+        // message resets the member channels' bend range, so it comes first
+        let mut members = self.config.mpe.then(|| {
+            let mut setup = rpn_events(
+                MASTER_CHANNEL,
+                RPN_MPE_CONFIGURATION,
+                (MEMBER_CHANNELS.len() as u8, 0),
+            );
+            for channel in MEMBER_CHANNELS {
+                setup.extend(bend_range_events(channel, self.config.pitch_bend_range));
+            }
+            meta_events.extend(setup.into_iter().map(|kind| TimedEvent::new(0, kind)));
+            MemberChannels::new()
         });
-        tracks.push(meta_track);
 
         // Render each section
-        for section in &song.sections {
-            for (i, part) in section.parts.iter().enumerate() {
-                let track = self.render_part(part, i as u8);
-                tracks.push(track);
+        for (index, section) in song.sections.iter().enumerate() {
+            let start = timeline.section_start(index);
+            let base_note = section.base_note(self.config.base_note);
+            for (part, channel) in section.parts.iter().zip(&part_channels[index]) {
+                let drums = DrumKeys::for_part(part);
+                match *channel {
+                    Some(channel) => {
+                        let members = members.as_mut().filter(|_| drums.is_none());
+                        tracks.push(self.render_part(
+                            part,
+                            song,
+                            start,
+                            channel,
+                            base_note,
+                            drums,
+                            members,
+                            &mut meta_events,
+                        ))
+                    }
+                    None => diagnostics.add(
+                        Diagnostic::warning(
+                            format!(
+                                "part '{}' in section '{}' was dropped: all 15 melodic MIDI channels are in use",
+                                part.instrument, section.name
+                            ),
+                            Span::dummy(),
+                        )
+                        .with_code(DiagnosticCode::PartDropped),
+                    ),
+                }
             }
         }
 
+        tracks.insert(0, events_to_track(meta_events));
+
         // Create MIDI file
         let smf = Smf {
             header: Header {
@@ -218,359 +471,1747 @@ impl MidiRenderer {
         };
 
         let mut buffer = Vec::new();
-        smf.write_std(&mut buffer).unwrap();
-        buffer
+        smf.write_std(&mut buffer)?;
+        Ok((buffer, diagnostics))
+    }
+
+    /// Conductor track events for the song's metadata: the copyright
+    /// notice first, as the MIDI specification asks, then the title as the
+    /// sequence name and the composer as a text event
+    fn metadata_events(&self) -> Vec<TimedEvent> {
+        let metadata = &self.config.metadata;
+        let text = |text: &str| -> &'static [u8] { text.as_bytes().to_vec().leak() };
+        let mut events = Vec::new();
+        if let Some(copyright) = &metadata.copyright {
+            events.push(midly::MetaMessage::Copyright(text(copyright)));
+        }
+        if let Some(title) = &metadata.title {
+            events.push(midly::MetaMessage::TrackName(text(title)));
+        }
+        if let Some(composer) = &metadata.composer {
+            events.push(midly::MetaMessage::Text(text(&format!(
+                "Composer: {}",
+                composer
+            ))));
+        }
+        events
+            .into_iter()
+            .map(|message| TimedEvent::new(0, TrackEventKind::Meta(message)))
+            .collect()
+    }
+
+    /// Conductor track tempo events: the song's tempo at the start, then a
+    /// change wherever a section's own tempo differs from the one before it
+    ///
+    /// Sections played all at once with parallel_sections keep the song's
+    /// tempo.
+    fn tempo_events(&self, song: &SongValue, timeline: &Timeline) -> Vec<TimedEvent> {
+        let song_tempo = self.config.tempo as f64;
+        let mut changes = vec![(0, song_tempo)];
+        if !self.config.parallel_sections {
+            for (index, section) in song.sections.iter().enumerate() {
+                let start = timeline.section_start(index);
+                let tempo = section.tempo.unwrap_or(song_tempo);
+                // A later section starting on the same tick replaces the change
+                if changes.last().is_some_and(|&(time, _)| time == start) {
+                    changes.pop();
+                }
+                if changes.last().map(|&(_, bpm)| bpm) != Some(tempo) {
+                    changes.push((start, tempo));
+                }
+            }
+        }
+        changes
+            .into_iter()
+            .map(|(time, bpm)| {
+                let microseconds = (60_000_000.0 / bpm).min(0xFF_FFFF as f64) as u32;
+                TimedEvent::new(
+                    time,
+                    TrackEventKind::Meta(midly::MetaMessage::Tempo(microseconds.into())),
+                )
+            })
+            .collect()
     }
 
-    fn render_part(&self, part: &PartValue, channel: u8) -> Track<'static> {
-        let mut track = Track::new();
-        let mut time: u32 = 0;
+    /// Where each section starts: one after another, or all at once with
+    /// parallel_sections
+    pub(crate) fn timeline(&self, song: &SongValue) -> Timeline {
+        let section_lengths: Vec<u32> = song
+            .sections
+            .iter()
+            .map(|section| self.section_ticks(section))
+            .collect();
+        Timeline::new(
+            &section_lengths,
+            self.bar_ticks(),
+            self.config.parallel_sections,
+        )
+    }
+
+    /// MIDI channel of each part of each section, or None for parts dropped
+    /// because every melodic channel is in use
+    ///
+    /// Melodic parts in MPE mode share the zone's master channel. Sections
+    /// played one after another reuse channels; with parallel_sections every
+    /// part of every section gets its own.
+    pub(crate) fn part_channels(&self, song: &SongValue) -> Vec<Vec<Option<u8>>> {
+        let mut channels = ChannelAllocator::new();
+        song.sections
+            .iter()
+            .enumerate()
+            .map(|(index, section)| {
+                let section_key = if self.config.parallel_sections {
+                    index
+                } else {
+                    0
+                };
+                section
+                    .parts
+                    .iter()
+                    .enumerate()
+                    .map(|(part_index, part)| {
+                        let drums = DrumKeys::for_part(part).is_some();
+                        if self.config.mpe && !drums {
+                            Some(MASTER_CHANNEL)
+                        } else {
+                            channels.allocate(section_key, part_index, drums)
+                        }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// The first two melodic parts that play at the same time in MPE mode,
+    /// where they share the zone
+    fn shared_zone<'a>(&self, song: &'a SongValue) -> Option<(&'a str, &'a str)> {
+        if !self.config.mpe {
+            return None;
+        }
+        let melodic = |parts: &mut dyn Iterator<Item = &'a PartValue>| {
+            let mut melodic = parts.filter(|part| DrumKeys::for_part(part).is_none());
+            Some((
+                melodic.next()?.instrument.as_str(),
+                melodic.next()?.instrument.as_str(),
+            ))
+        };
+        if self.config.parallel_sections {
+            melodic(&mut song.sections.iter().flat_map(|section| &section.parts))
+        } else {
+            song.sections
+                .iter()
+                .find_map(|section| melodic(&mut section.parts.iter()))
+        }
+    }
+
+    /// Length of a bar in ticks
+    pub(crate) fn bar_ticks(&self) -> u32 {
+        self.config.beats_per_bar * self.config.ticks_per_beat as u32
+    }
+
+    /// Length of a section (its longest part) in ticks
+    pub(crate) fn section_ticks(&self, section: &SectionValue) -> u32 {
+        section
+            .parts
+            .iter()
+            .map(|part| self.part_ticks(part))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Length of a part in ticks, saturating for parts too long to count
+    pub(crate) fn part_ticks(&self, part: &PartValue) -> u32 {
+        part.blocks
+            .iter()
+            .flat_map(|block| self.slot_ticks(block))
+            .fold(0, |total: u32, (_, ticks)| total.saturating_add(ticks))
+    }
+
+    /// Duration in ticks of each slot of a block
+    fn slot_ticks<'a>(&self, block: &'a BlockValue) -> impl Iterator<Item = (&'a SlotValue, u32)> {
+        let ticks_per_beat = self.config.ticks_per_beat as f64;
+
+        // Default slot duration (relative rhythm: equal share of block duration)
+        let slot_count = block.slots.len();
+        let default_slot_duration = if slot_count > 0 {
+            (block.beats * ticks_per_beat).round() as u32 / slot_count as u32
+        } else {
+            0
+        };
+
+        // Use explicit duration if set, otherwise use default (relative rhythm)
+        block.slots.iter().map(move |slot| {
+            let ticks = slot
+                .duration_beats()
+                .map(|beats| (beats * ticks_per_beat).round() as u32)
+                .unwrap_or(default_slot_duration);
+            (slot, ticks)
+        })
+    }
+
+    /// Render a part starting at `start` on its allocated channel
+    /// (drum parts play GM percussion keys)
+    ///
+    /// In MPE mode `channel` is the zone's master channel and notes go to
+    /// `members`. Markers go to `meta_events` so they land on the conductor
+    /// track, where DAWs look for song structure. The reverb and chorus
+    /// levels include what the part is heard with through its sends and
+    /// `song`'s master bus.
+    #[allow(clippy::too_many_arguments)]
+    fn render_part(
+        &self,
+        part: &PartValue,
+        song: &SongValue,
+        start: u32,
+        channel: u8,
+        base_note: u8,
+        drums: Option<DrumKeys>,
+        members: Option<&mut MemberChannels>,
+        meta_events: &mut Vec<TimedEvent>,
+    ) -> Track<'static> {
+        let mut events = Vec::new();
 
         // Track name
-        track.push(TrackEvent {
-            delta: 0.into(),
-            kind: TrackEventKind::Meta(midly::MetaMessage::TrackName(
+        events.push(TimedEvent::new(
+            0,
+            TrackEventKind::Meta(midly::MetaMessage::TrackName(
                 part.instrument.as_bytes().to_vec().leak(),
             )),
-        });
+        ));
+
+        // Part settings take effect when the part starts
+        // Bend range for microtones and glides (MPE zones are set up with the song)
+        if drums.is_none() && members.is_none() {
+            for event in bend_range_events(channel, self.config.pitch_bend_range) {
+                events.push(TimedEvent::new(start, event));
+            }
+        }
 
         // Set volume level (CC#7 - Channel Volume)
         if let Some(volume_level) = part.volume_level {
             let cc_value = (volume_level * 127.0).round() as u8;
-            track.push(TrackEvent {
-                delta: 0.into(),
-                kind: TrackEventKind::Midi {
+            events.push(TimedEvent::new(
+                start,
+                TrackEventKind::Midi {
                     channel: channel.into(),
                     message: MidiMessage::Controller {
                         controller: 7.into(), // CC#7 = Channel Volume
                         value: cc_value.into(),
                     },
                 },
-            });
+            ));
+        }
+
+        // Set pan position (CC#10 - Pan, 64 = center)
+        if let Some(pan_level) = part.pan_level {
+            let cc_value = ((pan_level + 1.0) / 2.0 * 127.0).round() as u8;
+            events.push(TimedEvent::new(
+                start,
+                TrackEventKind::Midi {
+                    channel: channel.into(),
+                    message: MidiMessage::Controller {
+                        controller: 10.into(), // CC#10 = Pan
+                        value: cc_value.into(),
+                    },
+                },
+            ));
         }
 
         // Set reverb level (CC#91 - Effects 1 Depth / Reverb Send Level)
-        if let Some(reverb_level) = part.reverb_level {
+        if let Some(reverb_level) = song.reverb_level(part) {
             let cc_value = (reverb_level * 127.0).round() as u8;
-            track.push(TrackEvent {
-                delta: 0.into(),
-                kind: TrackEventKind::Midi {
+            events.push(TimedEvent::new(
+                start,
+                TrackEventKind::Midi {
                     channel: channel.into(),
                     message: MidiMessage::Controller {
                         controller: 91.into(), // CC#91 = Reverb Send Level
                         value: cc_value.into(),
                     },
                 },
-            });
+            ));
         }
 
-        // Set synth parameters as MIDI CC messages
-        if let Some(synth) = &part.synth {
-            for event in synth_to_cc_events(synth, channel) {
-                track.push(event);
+        // Set chorus level (CC#93 - Effects 3 Depth / Chorus Send Level)
+        if let Some(chorus_level) = song.chorus_level(part) {
+            let cc_value = (chorus_level * 127.0).round() as u8;
+            events.push(TimedEvent::new(
+                start,
+                TrackEventKind::Midi {
+                    channel: channel.into(),
+                    message: MidiMessage::Controller {
+                        controller: 93.into(), // CC#93 = Chorus Send Level
+                        value: cc_value.into(),
+                    },
+                },
+            ));
+        }
+
+        // Compressor and EQ settings (CC#102-108)
+        for event in effect_cc_events(&part.effects, channel) {
+            events.push(TimedEvent::new(start, event.kind));
+        }
+
+        // Set synth parameters as MIDI CC messages (not meaningful for GM
+        // drums); an applied ADSR envelope replaces the synth's
+        if drums.is_none() {
+            let envelope = part.envelope.as_ref().and_then(|e| e.adsr.as_ref());
+            let cc_events = match (&part.synth, envelope) {
+                (Some(synth), Some(envelope)) => synth_to_cc_events(
+                    &SynthValue {
+                        envelope: envelope.clone(),
+                        ..synth.clone()
+                    },
+                    channel,
+                ),
+                (Some(synth), None) => synth_to_cc_events(synth, channel),
+                (None, Some(envelope)) => adsr_to_cc_events(envelope, channel),
+                (None, None) => Vec::new(),
+            };
+            for event in cc_events {
+                events.push(TimedEvent::new(start, event.kind));
             }
         }
 
-        // Render blocks with volume scaling
-        let velocity_scale = part.volume_level.unwrap_or(1.0);
-        for block in &part.blocks {
-            time = self.render_block(&mut track, block, time, channel, velocity_scale);
+        // Lay out all blocks on the timeline, then render with lookahead
+        // (portamento needs to know the following note)
+        let (schedule, end) = self.schedule_part(part, start);
+
+        // Controller curves span the whole part; ducking sends the channel
+        // volume itself, with any volume curve folded in
+        for automation in &part.automation {
+            if part.ducks.is_empty() || automation.cc != CC_VOLUME {
+                self.render_automation(&mut events, automation, start, end - start, channel);
+            }
+        }
+        if !part.ducks.is_empty() {
+            self.render_ducking(&mut events, part, start, end, channel);
         }
 
-        // End of track
-        track.push(TrackEvent {
-            delta: 0.into(),
-            kind: TrackEventKind::Meta(midly::MetaMessage::EndOfTrack),
-        });
+        // A dynamics envelope ramps the expression controller from the
+        // part's start, then holds its final level
+        if let Some(dynamics) = part.envelope.as_ref().and_then(|e| e.dynamics.as_ref()) {
+            let ramp = AutomationValue {
+                cc: CC_EXPRESSION,
+                points: vec![
+                    dynamics.level_at(0.0),
+                    dynamics.level_at(dynamics.duration_beats),
+                ],
+            };
+            let length = (dynamics.duration_beats.max(0.0) * self.config.ticks_per_beat as f64)
+                .round() as u32;
+            self.render_automation(&mut events, &ramp, start, length, channel);
+        }
 
-        track
+        // Render notes with volume scaling
+        let mut ctx = PartContext {
+            channel,
+            base_note,
+            velocity_scale: part.volume_level.unwrap_or(1.0),
+            drums,
+            members,
+        };
+        for (i, scheduled) in schedule.iter().enumerate() {
+            // Glide target: a single note starting exactly when this slot ends
+            let glide_to = schedule
+                .get(i + 1)
+                .filter(|next| next.time == scheduled.time + scheduled.duration)
+                .and_then(|next| match next.slot {
+                    SlotValue::Note { interval, .. } => Some(interval),
+                    _ => None,
+                });
+            self.render_scheduled(&mut events, scheduled, glide_to, &mut ctx);
+
+            if let Some(meta) = scheduled.slot.meta() {
+                if let Some(marker) = &meta.marker {
+                    meta_events.push(TimedEvent::new(
+                        scheduled.time,
+                        TrackEventKind::Meta(midly::MetaMessage::Marker(
+                            marker.as_bytes().to_vec().leak(),
+                        )),
+                    ));
+                }
+                if let Some(lyric) = &meta.lyric {
+                    events.push(TimedEvent::new(
+                        scheduled.time,
+                        TrackEventKind::Meta(midly::MetaMessage::Lyric(
+                            lyric.as_bytes().to_vec().leak(),
+                        )),
+                    ));
+                }
+            }
+        }
+
+        events_to_track(events)
     }
 
-    fn render_block(
+    /// Render a controller curve as interpolated CC events over `length` ticks
+    ///
+    /// The curve is sampled every AUTOMATION_STEP_DIVISION of a beat and an
+    /// event is emitted only when the 7-bit value changes.
+    fn render_automation(
         &self,
-        track: &mut Track<'static>,
-        block: &BlockValue,
-        mut time: u32,
+        events: &mut Vec<TimedEvent>,
+        automation: &AutomationValue,
+        start: u32,
+        length: u32,
         channel: u8,
-        velocity_scale: f64,
-    ) -> u32 {
-        // Default slot duration (relative rhythm: equal share of block duration)
-        let slot_count = block.slots.len();
-        let default_slot_duration = if slot_count > 0 {
-            (block.beats * self.config.ticks_per_beat as f64).round() as u32 / slot_count as u32
-        } else {
-            0
-        };
+    ) {
+        let step = (self.config.ticks_per_beat as u32 / AUTOMATION_STEP_DIVISION).max(1);
+        let mut last_value = None;
+        let mut offset = 0;
+        loop {
+            let position = if length == 0 {
+                0.0
+            } else {
+                offset as f64 / length as f64
+            };
+            let value = (automation.level_at(position) * 127.0).round() as u8;
+            if last_value != Some(value) {
+                events.push(TimedEvent::new(
+                    start + offset,
+                    TrackEventKind::Midi {
+                        channel: channel.into(),
+                        message: MidiMessage::Controller {
+                            controller: automation.cc.into(),
+                            value: value.into(),
+                        },
+                    },
+                ));
+                last_value = Some(value);
+            }
 
-        for slot in &block.slots {
-            // Use explicit duration if set, otherwise use default (relative rhythm)
-            let slot_duration = slot
-                .duration_beats()
-                .map(|beats| (beats * self.config.ticks_per_beat as f64).round() as u32)
-                .unwrap_or(default_slot_duration);
+            if offset >= length {
+                break;
+            }
+            offset = (offset + step).min(length);
+        }
+    }
 
-            match slot {
-                SlotValue::Note {
-                    interval,
-                    articulations,
-                    ..
-                } => {
-                    time += self.render_note(
-                        track,
-                        interval,
-                        articulations,
-                        slot_duration,
-                        channel,
-                        velocity_scale,
-                    );
-                }
+    /// Render a part's ducking as channel volume (CC#7) dipping from the
+    /// part's volume at each trigger note
+    ///
+    /// The volume is sampled like automation, and also right at each
+    /// trigger note so the dips land on them. A CC#7 automation curve
+    /// replaces the part's volume as the level the dips start from.
+    fn render_ducking(
+        &self,
+        events: &mut Vec<TimedEvent>,
+        part: &PartValue,
+        start: u32,
+        end: u32,
+        channel: u8,
+    ) {
+        let ticks_per_beat = self.config.ticks_per_beat as f64;
+        let beats = |tick: u32| tick.saturating_sub(start) as f64 / ticks_per_beat;
+        let step = (self.config.ticks_per_beat as u32 / AUTOMATION_STEP_DIVISION).max(1);
+        let mut ticks: Vec<u32> = (start..=end).step_by(step as usize).collect();
+        let mut ducks = Vec::with_capacity(part.ducks.len());
+        for duck in &part.ducks {
+            let (schedule, _) = self.schedule_part(&duck.trigger, start);
+            ticks.extend(
+                schedule
+                    .iter()
+                    .map(|scheduled| scheduled.time)
+                    .filter(|&tick| tick <= end),
+            );
+            let onsets: Vec<f64> = schedule
+                .iter()
+                .map(|scheduled| beats(scheduled.time))
+                .collect();
+            ducks.push((duck, onsets));
+        }
+        ticks.sort_unstable();
+        ticks.dedup();
 
-                SlotValue::Rest { .. } => {
-                    time += slot_duration;
-                }
+        let curve = part.automation.iter().rfind(|a| a.cc == CC_VOLUME);
+        let volume = |tick: u32| match curve {
+            Some(curve) if end > start => {
+                curve.level_at(tick.saturating_sub(start) as f64 / (end - start) as f64)
+            }
+            Some(curve) => curve.level_at(0.0),
+            None => part.volume_level.unwrap_or(1.0),
+        };
+        let mut last_value = None;
+        for tick in ticks {
+            let gain: f64 = ducks
+                .iter()
+                .map(|(duck, onsets)| duck.gain_at(onsets, beats(tick)))
+                .product();
+            let value = (volume(tick) * gain * 127.0).round() as u8;
+            if last_value != Some(value) {
+                events.push(TimedEvent::new(
+                    tick,
+                    TrackEventKind::Midi {
+                        channel: channel.into(),
+                        message: MidiMessage::Controller {
+                            controller: CC_VOLUME.into(),
+                            value: value.into(),
+                        },
+                    },
+                ));
+                last_value = Some(value);
+            }
+        }
+    }
 
-                SlotValue::Chord {
-                    intervals,
-                    articulations,
-                    ..
-                } => {
-                    time += self.render_chord(
-                        track,
-                        intervals,
-                        articulations,
-                        slot_duration,
-                        channel,
-                        velocity_scale,
-                    );
-                }
+    /// Place each sounding slot of a part starting at `start` on the timeline,
+    /// returning the slots and the end time
+    ///
+    /// Tied notes are merged into the note they continue, so they sound as
+    /// one note-on/note-off pair, also across block boundaries. Overlaid
+    /// blocks interleave on the timeline, so the slots come back in time order.
+    pub(crate) fn schedule_part<'a>(
+        &self,
+        part: &'a PartValue,
+        start: u32,
+    ) -> (Vec<ScheduledSlot<'a>>, u32) {
+        let mut schedule = Vec::new();
+        let mut time = start;
+        for block in &part.blocks {
+            time = self.schedule_block(&mut schedule, block, time, start);
+        }
 
-                SlotValue::Tuplet {
-                    slots,
-                    target_beats,
-                } => {
-                    // Tuplets use their own duration calculation
-                    let tuplet_duration =
-                        (*target_beats as u32) * self.config.ticks_per_beat as u32;
-                    let tuplet_slot_dur = tuplet_duration / slots.len().max(1) as u32;
-
-                    for inner_slot in slots {
-                        match inner_slot {
-                            SlotValue::Note {
-                                interval,
-                                articulations,
-                                ..
-                            } => {
-                                time += self.render_note(
-                                    track,
-                                    interval,
-                                    articulations,
-                                    tuplet_slot_dur,
-                                    channel,
-                                    velocity_scale,
-                                );
-                            }
-                            SlotValue::Rest { .. } => {
-                                time += tuplet_slot_dur;
-                            }
-                            SlotValue::Chord {
-                                intervals,
-                                articulations,
-                                ..
-                            } => {
-                                time += self.render_chord(
-                                    track,
-                                    intervals,
-                                    articulations,
-                                    tuplet_slot_dur,
-                                    channel,
-                                    velocity_scale,
-                                );
-                            }
-                            _ => {}
-                        }
-                    }
+        // Each merged note keeps its `tail`, the last slot merged into it,
+        // which decides whether the tie goes on
+        let mut merged: Vec<(ScheduledSlot, &SlotValue)> = Vec::with_capacity(schedule.len());
+        for scheduled in schedule {
+            let slot = scheduled.slot;
+            let continued = merged.iter_mut().rev().find(|(last, tail)| {
+                last.time.saturating_add(last.duration) == scheduled.time && tail.ties_into(slot)
+            });
+            match continued {
+                Some((last, tail)) => {
+                    last.duration = last.duration.saturating_add(scheduled.duration);
+                    *tail = slot;
                 }
+                None => merged.push((scheduled, slot)),
             }
         }
+        let mut merged: Vec<ScheduledSlot> = merged.into_iter().map(|(slot, _)| slot).collect();
+        merged.sort_by_key(|scheduled| scheduled.time);
+        (merged, time)
+    }
+
+    /// Place each sounding slot of a block on the timeline, returning the end time
+    ///
+    /// `origin` is where the part starts; swing grids are measured from it.
+    /// Times saturate at `u32::MAX` for a part too long to count in ticks.
+    fn schedule_block<'a>(
+        &self,
+        schedule: &mut Vec<ScheduledSlot<'a>>,
+        block: &'a BlockValue,
+        mut time: u32,
+        origin: u32,
+    ) -> u32 {
+        for (slot, slot_duration) in self.slot_ticks(block) {
+            self.schedule_slot(schedule, slot, time, slot_duration, origin);
+            time = time.saturating_add(slot_duration);
+        }
 
         time
     }
 
-    /// Render a single note with optional pitch bend for microtones
-    fn render_note(
+    /// Place a slot starting at `time` and lasting `duration` ticks (rests are skipped)
+    fn schedule_slot<'a>(
         &self,
-        track: &mut Track<'static>,
-        interval: &IntervalValue,
-        articulations: &[Articulation],
+        schedule: &mut Vec<ScheduledSlot<'a>>,
+        slot: &'a SlotValue,
+        time: u32,
         duration: u32,
-        channel: u8,
-        velocity_scale: f64,
-    ) -> u32 {
-        let (note, pitch_bend) = cents_to_midi(
-            self.config.base_note,
-            interval.cents,
-            self.config.pitch_bend_range,
-        );
-        let velocity = ((100.0 * velocity_scale).round() as u8).clamp(1, 127);
+        origin: u32,
+    ) {
+        match slot {
+            SlotValue::Note { meta, .. } | SlotValue::Chord { meta, .. } => {
+                // Swing moves both ends of the note so it stays legato
+                let (time, end) = match &meta.swing {
+                    Some(swing) => (
+                        self.swing_tick(swing, time, origin),
+                        self.swing_tick(swing, time.saturating_add(duration), origin),
+                    ),
+                    None => (time, time.saturating_add(duration)),
+                };
+                schedule.push(ScheduledSlot {
+                    time,
+                    duration: end - time,
+                    slot,
+                })
+            }
 
-        // Apply staccato: shorten note to 50% of duration
-        let is_staccato = articulations.contains(&Articulation::Staccato);
-        let note_duration = if is_staccato { duration / 2 } else { duration };
-        let rest_duration = duration - note_duration;
+            SlotValue::Rest { .. } => {}
 
-        // Set pitch bend if not centered (for microtones)
-        if pitch_bend != 8192 {
-            track.push(TrackEvent {
-                delta: 0.into(),
-                kind: TrackEventKind::Midi {
-                    channel: channel.into(),
-                    message: MidiMessage::PitchBend {
-                        bend: midly::PitchBend(pitch_bend.into()),
-                    },
-                },
-            });
-        }
+            SlotValue::Tuplet { slots, .. } => {
+                // Tuplet slots are scaled together to fill the tuplet's duration
+                for (inner_slot, offset, share) in tuplet_shares(slots) {
+                    let inner_start = (duration as f64 * offset).round() as u32;
+                    let inner_end = (duration as f64 * (offset + share)).round() as u32;
+                    self.schedule_slot(
+                        schedule,
+                        inner_slot,
+                        time.saturating_add(inner_start),
+                        inner_end - inner_start,
+                        origin,
+                    );
+                }
+            }
 
-        // Note on
-        track.push(TrackEvent {
-            delta: 0.into(),
-            kind: TrackEventKind::Midi {
-                channel: channel.into(),
-                message: MidiMessage::NoteOn {
-                    key: note.into(),
-                    vel: velocity.into(),
-                },
-            },
-        });
+            SlotValue::Overlay { voices } => {
+                // Every voice starts with the overlay
+                for voice in voices {
+                    self.schedule_block(schedule, voice, time, origin);
+                }
+            }
+        }
+    }
 
-        // Note off (after note_duration, which may be shorter for staccato)
-        track.push(TrackEvent {
-            delta: note_duration.into(),
-            kind: TrackEventKind::Midi {
-                channel: channel.into(),
-                message: MidiMessage::NoteOff {
-                    key: note.into(),
-                    vel: 0.into(),
-                },
-            },
-        });
+    /// Swung position of a tick, with the swing grid starting at `origin`
+    fn swing_tick(&self, swing: &Swing, tick: u32, origin: u32) -> u32 {
+        let ticks_per_beat = self.config.ticks_per_beat as f64;
+        let beats = (tick - origin) as f64 / ticks_per_beat;
+        origin.saturating_add((swing.warp(beats) * ticks_per_beat).round() as u32)
+    }
 
-        // Reset pitch bend or add rest gap for staccato
-        if pitch_bend != 8192 || is_staccato {
-            track.push(TrackEvent {
-                delta: rest_duration.into(),
-                kind: TrackEventKind::Midi {
-                    channel: channel.into(),
-                    message: MidiMessage::PitchBend {
-                        bend: midly::PitchBend(8192u16.into()),
-                    },
-                },
-            });
+    fn render_scheduled(
+        &self,
+        events: &mut Vec<TimedEvent>,
+        scheduled: &ScheduledSlot,
+        glide_to: Option<&IntervalValue>,
+        ctx: &mut PartContext,
+    ) {
+        match scheduled.slot {
+            SlotValue::Note {
+                interval,
+                articulations,
+                meta,
+                ..
+            } => self.render_chord(
+                events,
+                std::slice::from_ref(interval),
+                articulations,
+                meta,
+                glide_to,
+                scheduled.time,
+                scheduled.duration,
+                ctx,
+            ),
+            SlotValue::Chord {
+                intervals,
+                articulations,
+                meta,
+                ..
+            } => self.render_chord(
+                events,
+                intervals,
+                articulations,
+                meta,
+                None,
+                scheduled.time,
+                scheduled.duration,
+                ctx,
+            ),
+            SlotValue::Rest { .. } | SlotValue::Tuplet { .. } | SlotValue::Overlay { .. } => {}
         }
+    }
 
-        duration
+    /// Convert a timing offset in milliseconds to ticks at the configured tempo
+    fn ms_to_ticks(&self, ms: f64) -> f64 {
+        ms / 60_000.0 * self.config.tempo as f64 * self.config.ticks_per_beat as f64
     }
 
-    /// Render a chord (multiple simultaneous notes)
+    /// Render simultaneous notes (a single note is a one-note chord)
+    ///
+    /// Microtonal notes set a pitch bend before the note-on and reset it at the end.
+    /// MIDI has one pitch bend per channel, so chords use the bend of the first note,
+    /// unless MPE mode gives every note a member channel and a bend of its own.
+    /// A portamento note with a following note (`glide_to`) ramps the pitch bend
+    /// toward it over the glide time before the next note starts.
+    #[allow(clippy::too_many_arguments)]
     fn render_chord(
         &self,
-        track: &mut Track<'static>,
+        events: &mut Vec<TimedEvent>,
         intervals: &[IntervalValue],
         articulations: &[Articulation],
+        meta: &NoteMeta,
+        glide_to: Option<&IntervalValue>,
+        time: u32,
         duration: u32,
-        channel: u8,
-        velocity_scale: f64,
-    ) -> u32 {
-        let velocity = ((100.0 * velocity_scale).round() as u8).clamp(1, 127);
+        ctx: &mut PartContext,
+    ) {
+        let velocity = ((100.0 * ctx.velocity_scale).round() as i32 + meta.velocity_offset)
+            .clamp(1, 127) as u8;
+
+        // Apply humanized timing offset (never before the start of the track)
+        let start = (time as f64 + self.ms_to_ticks(meta.timing_offset_ms))
+            .round()
+            .max(0.0) as u32;
 
-        // Apply staccato: shorten chord to 50% of duration
+        // Apply staccato: shorten note to 50% of duration
         let is_staccato = articulations.contains(&Articulation::Staccato);
         let note_duration = if is_staccato { duration / 2 } else { duration };
-        let rest_duration = duration - note_duration;
-
-        // For chords with microtones, we can only apply pitch bend to all notes equally
-        // (MIDI limitation: one pitch bend per channel)
-        // For simplicity, we use the pitch bend of the first note if it has microtones
-        let first_bend = if let Some(first) = intervals.first() {
-            let (_, bend) = cents_to_midi(
-                self.config.base_note,
-                first.cents,
-                self.config.pitch_bend_range,
-            );
-            if bend != 8192 {
-                track.push(TrackEvent {
-                    delta: 0.into(),
-                    kind: TrackEventKind::Midi {
+        let end = start + note_duration;
+
+        // (channel, key, bend) of each note
+        // Drum keys are unpitched: no microtonal bends or glides
+        let shared_bend = intervals
+            .first()
+            .map(|first| cents_to_midi(ctx.base_note, first.cents, self.config.pitch_bend_range).1)
+            .unwrap_or(8192);
+        let mut voices = Vec::with_capacity(intervals.len());
+        for interval in intervals {
+            let (note, bend) =
+                cents_to_midi(ctx.base_note, interval.cents, self.config.pitch_bend_range);
+            voices.push(match (&ctx.drums, ctx.members.as_deref_mut()) {
+                (Some(drums), _) => (ctx.channel, drums.key(meta, interval.cents, note), 8192),
+                (None, Some(members)) => (members.allocate(start, end), note, bend),
+                (None, None) => (ctx.channel, note, shared_bend),
+            });
+        }
+
+        // Set pitch bend if not centered (for microtones), once per channel
+        let mut bent_channels: Vec<u8> = Vec::new();
+        for &(channel, _, bend) in &voices {
+            if bend != 8192 && !bent_channels.contains(&channel) {
+                bent_channels.push(channel);
+                events.push(TimedEvent::new(
+                    start,
+                    TrackEventKind::Midi {
                         channel: channel.into(),
                         message: MidiMessage::PitchBend {
                             bend: midly::PitchBend(bend.into()),
                         },
                     },
-                });
+                ));
             }
-            bend
-        } else {
-            8192
-        };
+        }
 
-        // All notes on simultaneously
-        for interval in intervals.iter() {
-            let (note, _) = cents_to_midi(
-                self.config.base_note,
-                interval.cents,
-                self.config.pitch_bend_range,
-            );
-            track.push(TrackEvent {
-                delta: 0.into(),
-                kind: TrackEventKind::Midi {
+        for &(channel, note, _) in &voices {
+            events.push(TimedEvent::new(
+                start,
+                TrackEventKind::Midi {
                     channel: channel.into(),
                     message: MidiMessage::NoteOn {
                         key: note.into(),
                         vel: velocity.into(),
                     },
                 },
-            });
-        }
-
-        // All notes off (after note_duration, which may be shorter for staccato)
-        for (i, interval) in intervals.iter().enumerate() {
-            let (note, _) = cents_to_midi(
-                self.config.base_note,
-                interval.cents,
-                self.config.pitch_bend_range,
-            );
-            let delta = if i == 0 { note_duration } else { 0 };
-            track.push(TrackEvent {
-                delta: delta.into(),
-                kind: TrackEventKind::Midi {
+            ));
+            events.push(TimedEvent::new(
+                end,
+                TrackEventKind::Midi {
                     channel: channel.into(),
                     message: MidiMessage::NoteOff {
                         key: note.into(),
                         vel: 0.into(),
                     },
                 },
-            });
+            ));
+        }
+
+        // Portamento: ramp the bend toward the next note at the end of this one
+        let glide_to = glide_to.filter(|_| {
+            intervals.len() == 1
+                && ctx.drums.is_none()
+                && !is_staccato
+                && articulations.contains(&Articulation::Portamento)
+        });
+        if let (Some(target), Some(&(channel, _, bend))) = (glide_to, voices.first()) {
+            let glide_ms = meta.glide_ms.unwrap_or(self.config.glide_ms);
+            let glide_ticks = (self.ms_to_ticks(glide_ms).round() as u32).min(note_duration);
+            let semitones = (target.cents - intervals[0].cents) / 100.0;
+            let target_bend = (bend as f64 + semitones / self.config.pitch_bend_range * 8192.0)
+                .clamp(0.0, 16383.0);
+
+            for step in 1..=GLIDE_STEPS {
+                let t = step as f64 / GLIDE_STEPS as f64;
+                let value = (bend as f64 + (target_bend - bend as f64) * t).round() as u16;
+                events.push(TimedEvent::new(
+                    end - glide_ticks + glide_ticks * step / GLIDE_STEPS,
+                    TrackEventKind::Midi {
+                        channel: channel.into(),
+                        message: MidiMessage::PitchBend {
+                            bend: midly::PitchBend(value.into()),
+                        },
+                    },
+                ));
+            }
+            if !bent_channels.contains(&channel) {
+                bent_channels.push(channel);
+            }
         }
 
-        // Reset pitch bend or add rest gap for staccato
-        if first_bend != 8192 || is_staccato {
-            track.push(TrackEvent {
-                delta: rest_duration.into(),
-                kind: TrackEventKind::Midi {
+        // Reset pitch bend after the note ends
+        for channel in bent_channels {
+            events.push(TimedEvent::new(
+                end,
+                TrackEventKind::Midi {
                     channel: channel.into(),
                     message: MidiMessage::PitchBend {
                         bend: midly::PitchBend(8192u16.into()),
                     },
                 },
-            });
+            ));
         }
-
-        duration
     }
 }
 
 /// Render a song value to MIDI bytes
-pub fn render_to_midi(song: &SongValue) -> Vec<u8> {
+pub fn render_to_midi(song: &SongValue) -> Result<Vec<u8>, RenderError> {
     let renderer = MidiRenderer::new(MidiConfig::default());
     renderer.render(song)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::drums::DRUM_CHANNEL;
+    use relanote_eval::value::{
+        BusValue, ChorusParams, CompressorParams, DrumMapValue, DuckValue, DynamicValue,
+        EffectValue, EnvelopeValue, PartEnvelope, SectionValue, SendValue,
+    };
+
+    fn note(semitones: i32, meta: NoteMeta) -> SlotValue {
+        SlotValue::Note {
+            interval: IntervalValue::from_semitones(semitones),
+            articulations: vec![],
+            duration_beats: None,
+            meta,
+        }
+    }
+
+    fn song_of(slots: Vec<SlotValue>, beats: f64) -> SongValue {
+        SongValue::new(vec![SectionValue::new(
+            "Test",
+            vec![PartValue::new(
+                "Test",
+                vec![BlockValue::with_beats(slots, beats)],
+            )],
+        )])
+    }
+
+    /// Collect (absolute tick, key, velocity) for every note-on in the first part track
+    fn note_ons(bytes: &[u8]) -> Vec<(u32, u8, u8)> {
+        note_ons_with_channel(bytes)
+            .into_iter()
+            .map(|(tick, _, key, vel)| (tick, key, vel))
+            .collect()
+    }
+
+    fn note_ons_with_channel(bytes: &[u8]) -> Vec<(u32, u8, u8, u8)> {
+        let smf = Smf::parse(bytes).unwrap();
+        let mut tick = 0;
+        let mut result = Vec::new();
+        for event in &smf.tracks[1] {
+            tick += event.delta.as_int();
+            if let TrackEventKind::Midi {
+                channel,
+                message: MidiMessage::NoteOn { key, vel },
+            } = event.kind
+            {
+                result.push((tick, channel.as_int(), key.as_int(), vel.as_int()));
+            }
+        }
+        result
+    }
+
+    #[test]
+    fn test_rests_advance_time() {
+        let rest = SlotValue::Rest {
+            duration_beats: None,
+        };
+        let song = song_of(
+            vec![
+                note(0, NoteMeta::default()),
+                rest,
+                note(7, NoteMeta::default()),
+            ],
+            3.0,
+        );
+        let ons = note_ons(&render_to_midi(&song).unwrap());
+        assert_eq!(ons, vec![(0, 60, 100), (960, 67, 100)]);
+    }
+
+    #[test]
+    fn test_ties_merge_notes_across_blocks() {
+        let tied = |semitones, articulation| SlotValue::Note {
+            interval: IntervalValue::from_semitones(semitones),
+            articulations: vec![articulation],
+            duration_beats: None,
+            meta: NoteMeta::default(),
+        };
+        let mut song = song_of(
+            vec![note(0, NoteMeta::default()), tied(7, Articulation::Tie)],
+            2.0,
+        );
+        song.sections[0].parts[0]
+            .blocks
+            .push(BlockValue::with_beats(
+                vec![tied(7, Articulation::Tied), note(7, NoteMeta::default())],
+                2.0,
+            ));
+
+        let bytes = render_to_midi(&song).unwrap();
+        let notes: Vec<(u32, u8)> = note_ons(&bytes)
+            .into_iter()
+            .map(|(tick, key, _)| (tick, key))
+            .collect();
+        // The tied G sounds once for two beats; the last G is struck again
+        assert_eq!(notes, vec![(0, 60), (480, 67), (1440, 67)]);
+    }
+
+    #[test]
+    fn test_overlaid_blocks_sound_together_on_one_channel() {
+        let tied = |semitones, articulation| SlotValue::Note {
+            interval: IntervalValue::from_semitones(semitones),
+            articulations: vec![articulation],
+            duration_beats: None,
+            meta: NoteMeta::default(),
+        };
+        let melody = BlockValue::with_beats(
+            vec![note(0, NoteMeta::default()), note(4, NoteMeta::default())],
+            2.0,
+        );
+        let held = BlockValue::with_beats(
+            vec![tied(7, Articulation::Tie), tied(7, Articulation::Tied)],
+            2.0,
+        );
+        let song = song_of(
+            vec![
+                SlotValue::Overlay {
+                    voices: vec![melody, held],
+                },
+                note(12, NoteMeta::default()).with_duration(1.0),
+            ],
+            3.0,
+        );
+
+        let ons = note_ons_with_channel(&render_to_midi(&song).unwrap());
+        // The tie within the held voice still merges its two Gs
+        assert_eq!(
+            ons,
+            vec![
+                (0, 0, 60, 100),
+                (0, 0, 67, 100),
+                (480, 0, 64, 100),
+                (960, 0, 72, 100)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_humanize_meta_offsets_notes() {
+        let meta = NoteMeta {
+            timing_offset_ms: 125.0, // 1/4 beat at 120 BPM
+            velocity_offset: -20,
+            ..NoteMeta::default()
+        };
+        let song = song_of(vec![note(0, NoteMeta::default()), note(4, meta)], 2.0);
+        let ons = note_ons(&render_to_midi(&song).unwrap());
+        assert_eq!(ons, vec![(0, 60, 100), (600, 64, 80)]);
+    }
+
+    #[test]
+    fn test_portamento_ramps_pitch_bend() {
+        let gliding = SlotValue::Note {
+            interval: IntervalValue::from_semitones(0),
+            articulations: vec![Articulation::Portamento],
+            duration_beats: None,
+            meta: NoteMeta {
+                glide_ms: Some(250.0), // half a beat at 120 BPM
+                ..NoteMeta::default()
+            },
+        };
+        let song = song_of(vec![gliding, note(2, NoteMeta::default())], 2.0);
+        let bytes = render_to_midi(&song).unwrap();
+
+        let smf = Smf::parse(&bytes).unwrap();
+        let mut tick = 0;
+        let mut bends = Vec::new();
+        for event in &smf.tracks[1] {
+            tick += event.delta.as_int();
+            if let TrackEventKind::Midi {
+                message: MidiMessage::PitchBend { bend },
+                ..
+            } = event.kind
+            {
+                bends.push((tick, bend.0.as_int()));
+            }
+        }
+
+        // Ramp covers the last 240 ticks of the first note, up a whole tone (full bend)
+        assert_eq!(bends.len(), GLIDE_STEPS as usize + 1);
+        assert_eq!(bends.first().unwrap().0, 240 + 240 / GLIDE_STEPS);
+        assert_eq!(bends[bends.len() - 2], (480, 16383));
+        assert_eq!(*bends.last().unwrap(), (480, 8192));
+        assert_eq!(note_ons(&bytes), vec![(0, 60, 100), (480, 62, 100)]);
+    }
+
+    #[test]
+    fn test_microtones_bend_the_nearest_key() {
+        let just_third = SlotValue::Note {
+            interval: IntervalValue::from_cents(386.3),
+            articulations: vec![],
+            duration_beats: None,
+            meta: NoteMeta::default(),
+        };
+        let bytes = render_to_midi(&song_of(vec![just_third], 1.0)).unwrap();
+
+        let smf = Smf::parse(&bytes).unwrap();
+        let bends: Vec<u16> = smf.tracks[1]
+            .iter()
+            .filter_map(|event| match event.kind {
+                TrackEventKind::Midi {
+                    message: MidiMessage::PitchBend { bend },
+                    ..
+                } => Some(bend.0.as_int()),
+                _ => None,
+            })
+            .collect();
+
+        // E4 bent 13.7 cents flat over a 2-semitone range, then reset
+        assert_eq!(note_ons(&bytes), vec![(0, 64, 100)]);
+        assert_eq!(bends, vec![7630, 8192]);
+    }
+
+    /// Controller changes on a track as (channel, controller, value)
+    fn controllers(track: &Track) -> Vec<(u8, u8, u8)> {
+        track
+            .iter()
+            .filter_map(|event| match event.kind {
+                TrackEventKind::Midi {
+                    channel,
+                    message: MidiMessage::Controller { controller, value },
+                } => Some((channel.as_int(), controller.as_int(), value.as_int())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parts_set_their_bend_range() {
+        let renderer = MidiRenderer::new(MidiConfig {
+            pitch_bend_range: 12.5,
+            ..MidiConfig::default()
+        });
+        let bytes = renderer
+            .render(&song_of(vec![note(0, NoteMeta::default())], 1.0))
+            .unwrap();
+        let smf = Smf::parse(&bytes).unwrap();
+
+        assert_eq!(
+            controllers(&smf.tracks[1]),
+            vec![
+                (0, 101, 0),
+                (0, 100, 0),
+                (0, 6, 12),
+                (0, 38, 50),
+                (0, 101, 127),
+                (0, 100, 127),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_mpe_gives_chord_notes_their_own_bends() {
+        let chord = SlotValue::Chord {
+            intervals: vec![
+                IntervalValue::from_cents(0.0),
+                IntervalValue::from_cents(386.3),
+                IntervalValue::from_cents(702.0),
+            ],
+            articulations: vec![],
+            duration_beats: None,
+            meta: NoteMeta::default(),
+        };
+        let renderer = MidiRenderer::new(MidiConfig {
+            mpe: true,
+            ..MidiConfig::default()
+        });
+        let bytes = renderer.render(&song_of(vec![chord], 1.0)).unwrap();
+        let smf = Smf::parse(&bytes).unwrap();
+
+        // The zone is announced on the master channel, before member bend ranges
+        let setup = controllers(&smf.tracks[0]);
+        assert_eq!(
+            setup[..4],
+            [(0, 101, 0), (0, 100, 6), (0, 6, 8), (0, 38, 0)]
+        );
+        assert!(setup.contains(&(8, 6, 2)));
+
+        let mut bends = Vec::new();
+        for event in &smf.tracks[1] {
+            if let TrackEventKind::Midi {
+                channel,
+                message: MidiMessage::PitchBend { bend },
+            } = event.kind
+            {
+                bends.push((channel.as_int(), bend.0.as_int()));
+            }
+        }
+        // The third and fifth are bent on their own channels, then reset
+        assert_eq!(
+            note_ons_with_channel(&bytes),
+            vec![(0, 1, 60, 100), (0, 2, 64, 100), (0, 3, 67, 100)]
+        );
+        assert_eq!(bends, vec![(2, 7630), (3, 8273), (2, 8192), (3, 8192)]);
+    }
+
+    #[test]
+    fn test_mpe_warns_about_parts_sharing_the_zone() {
+        let mut song = song_of(vec![note(0, NoteMeta::default())], 1.0);
+        let renderer = MidiRenderer::new(MidiConfig {
+            mpe: true,
+            ..MidiConfig::default()
+        });
+        let (_, diagnostics) = renderer.render_with_diagnostics(&song).unwrap();
+        assert!(diagnostics.is_empty());
+
+        let mut bass = song.sections[0].parts[0].clone();
+        bass.instrument = "Bass".to_string();
+        song.sections[0].parts.push(bass);
+        let (_, diagnostics) = renderer.render_with_diagnostics(&song).unwrap();
+        let warning = diagnostics.iter().next().unwrap();
+        assert_eq!(warning.code, Some(DiagnosticCode::SharedMpeZone));
+        assert!(warning.message.contains("'Bass'"));
+    }
+
+    #[test]
+    fn test_drum_parts_use_percussion_channel() {
+        let mut song = song_of(
+            vec![note(0, NoteMeta::default()), note(2, NoteMeta::default())],
+            2.0,
+        );
+        let part = &mut song.sections[0].parts[0];
+
+        // Drum preset: every note plays the preset's GM key
+        part.synth = Some(SynthValue::new("Kick".to_string()));
+        assert_eq!(
+            note_ons_with_channel(&render_to_midi(&song).unwrap()),
+            vec![(0, DRUM_CHANNEL, 36, 100), (480, DRUM_CHANNEL, 36, 100)]
+        );
+
+        // Explicit drum map: pitches map to keys, unmapped ones fall back to the preset
+        song.sections[0].parts[0].drum_map = Some(DrumMapValue {
+            default_key: None,
+            keys: vec![(200.0, 38)],
+        });
+        assert_eq!(
+            note_ons_with_channel(&render_to_midi(&song).unwrap()),
+            vec![(0, DRUM_CHANNEL, 36, 100), (480, DRUM_CHANNEL, 38, 100)]
+        );
+    }
+
+    #[test]
+    fn test_sampled_parts_play_as_drums() {
+        use relanote_eval::value::{OscillatorValue, SampleValue, Waveform};
+
+        let sampled = |path: &str| {
+            SynthValue::new("Sampler").with_oscillators(vec![OscillatorValue::new(
+                Waveform::Sample(SampleValue::new(path)),
+            )])
+        };
+        let mut song = song_of(
+            vec![note(0, NoteMeta::default()), note(2, NoteMeta::default())],
+            2.0,
+        );
+
+        // The file's name gives the key
+        song.sections[0].parts[0].synth = Some(sampled("samples/kick_01.wav"));
+        assert_eq!(
+            note_ons_with_channel(&render_to_midi(&song).unwrap()),
+            vec![(0, DRUM_CHANNEL, 36, 100), (480, DRUM_CHANNEL, 36, 100)]
+        );
+
+        // Without a drum name, notes keep their pitched keys on the drum channel
+        song.sections[0].parts[0].synth = Some(sampled("vox.wav"));
+        assert_eq!(
+            note_ons_with_channel(&render_to_midi(&song).unwrap()),
+            vec![(0, DRUM_CHANNEL, 60, 100), (480, DRUM_CHANNEL, 62, 100)]
+        );
+    }
+
+    #[test]
+    fn test_kit_hits_play_on_percussion_channel() {
+        let hit = |key| {
+            note(
+                0,
+                NoteMeta {
+                    hit: Some("x".to_string()),
+                    drum_key: Some(key),
+                    ..NoteMeta::default()
+                },
+            )
+        };
+        // No synth or drum map: the kit keys alone make this a drum part
+        let song = song_of(vec![hit(36), hit(42), hit(38)], 3.0);
+        assert_eq!(
+            note_ons_with_channel(&render_to_midi(&song).unwrap()),
+            vec![
+                (0, DRUM_CHANNEL, 36, 100),
+                (480, DRUM_CHANNEL, 42, 100),
+                (960, DRUM_CHANNEL, 38, 100)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_channel_allocation() {
+        let part = |name: &str| PartValue {
+            instrument: name.to_string(),
+            ..song_of(vec![note(0, NoteMeta::default())], 1.0).sections[0].parts[0].clone()
+        };
+        let mut parts: Vec<PartValue> = (0..17).map(|i| part(&format!("Synth {}", i))).collect();
+        parts[3].drum_map = Some(DrumMapValue {
+            default_key: Some(36),
+            keys: vec![],
+        });
+        let song = SongValue::new(vec![
+            SectionValue::new("A", parts),
+            SectionValue::new("B", vec![part("Synth 0")]),
+        ]);
+
+        let (bytes, diagnostics) = MidiRenderer::new(MidiConfig::default())
+            .render_with_diagnostics(&song)
+            .unwrap();
+        let smf = Smf::parse(&bytes).unwrap();
+        let channels: Vec<u8> = smf.tracks[1..]
+            .iter()
+            .filter_map(|track| {
+                track.iter().find_map(|event| match event.kind {
+                    TrackEventKind::Midi { channel, .. } => Some(channel.as_int()),
+                    _ => None,
+                })
+            })
+            .collect();
+
+        // 16 melodic parts compete for 15 channels; the drum part keeps channel 10,
+        // and section B's "Synth 0" reuses its channel from section A
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics
+            .iter()
+            .next()
+            .unwrap()
+            .message
+            .contains("Synth 16"));
+        assert_eq!(channels.len(), 17);
+        assert_eq!(channels[3], DRUM_CHANNEL);
+        assert!(!channels[..3].contains(&DRUM_CHANNEL));
+        assert!(!channels[4..16].contains(&DRUM_CHANNEL));
+        assert_eq!(channels[16], channels[0]);
+    }
+
+    #[test]
+    fn test_parts_with_the_same_instrument_get_their_own_channels() {
+        let part = song_of(vec![note(0, NoteMeta::default())], 1.0).sections[0].parts[0].clone();
+        let section = SectionValue::new("A", vec![part.clone(), part]);
+        let song = SongValue::new(vec![section.clone(), section]);
+
+        let channels = |parallel_sections| {
+            MidiRenderer::new(MidiConfig {
+                parallel_sections,
+                ..MidiConfig::default()
+            })
+            .part_channels(&song)
+        };
+        assert_eq!(
+            channels(false),
+            vec![vec![Some(0), Some(1)], vec![Some(0), Some(1)]]
+        );
+        assert_eq!(
+            channels(true),
+            vec![vec![Some(0), Some(1)], vec![Some(2), Some(3)]]
+        );
+    }
+
+    #[test]
+    fn test_sections_play_in_sequence() {
+        // First section is 3 beats long, so the second starts on the next bar (beat 4)
+        let mut song = song_of(vec![note(0, NoteMeta::default())], 3.0);
+        let mut second = song.sections[0].clone();
+        second.name = "B".to_string();
+        second.parts[0].instrument = "Other".to_string();
+        song.sections.push(second);
+
+        let first_note_on = |bytes: &[u8], track: usize| {
+            let smf = Smf::parse(bytes).unwrap();
+            let mut tick = 0;
+            for event in &smf.tracks[track] {
+                tick += event.delta.as_int();
+                if let TrackEventKind::Midi {
+                    message: MidiMessage::NoteOn { .. },
+                    ..
+                } = event.kind
+                {
+                    return tick;
+                }
+            }
+            panic!("no note-on in track {}", track)
+        };
+
+        let bytes = render_to_midi(&song).unwrap();
+        assert_eq!(first_note_on(&bytes, 1), 0);
+        assert_eq!(first_note_on(&bytes, 2), 4 * 480);
+
+        let parallel = MidiRenderer::new(MidiConfig {
+            parallel_sections: true,
+            ..MidiConfig::default()
+        })
+        .render(&song)
+        .unwrap();
+        assert_eq!(first_note_on(&parallel, 2), 0);
+    }
+
+    #[test]
+    fn test_songs_too_long_for_ticks_are_an_error() {
+        let rest = |beats| SlotValue::Rest {
+            duration_beats: Some(beats),
+        };
+        // One part longer than u32 ticks, and two sections that only add up
+        // to more
+        let long_part = song_of(vec![rest(10_000_000.0), rest(10_000_000.0)], 4.0);
+        let mut long_sections = song_of(vec![rest(5_000_000.0)], 4.0);
+        long_sections
+            .sections
+            .push(long_sections.sections[0].clone());
+
+        for song in [long_part, long_sections] {
+            assert!(matches!(render_to_midi(&song), Err(RenderError::TooLong)));
+            // Laying the song out or writing it as ABC doesn't overflow either
+            MidiRenderer::new(MidiConfig::default()).inspect(&song);
+            assert!(crate::abc::render_to_abc(&song).contains("z"));
+        }
+    }
+
+    #[test]
+    fn test_sections_use_their_own_key() {
+        let mut song = song_of(vec![note(4, NoteMeta::default())], 4.0);
+        let mut in_g = song.sections[0].clone();
+        in_g.key = Some(67);
+        let mut up_a_step = song.sections[0].clone();
+        up_a_step.modulation = 2;
+        song.sections.extend([in_g, up_a_step]);
+
+        let smf_bytes = render_to_midi(&song).unwrap();
+        let smf = Smf::parse(&smf_bytes).unwrap();
+        let keys: Vec<u8> = smf.tracks[1..]
+            .iter()
+            .flatten()
+            .filter_map(|event| match event.kind {
+                TrackEventKind::Midi {
+                    message: MidiMessage::NoteOn { key, .. },
+                    ..
+                } => Some(key.as_int()),
+                _ => None,
+            })
+            .collect();
+        // E4 in the song's key of C4, B4 in G4, then F#4 a whole step up from C4
+        assert_eq!(keys, vec![64, 71, 66]);
+    }
+
+    #[test]
+    fn test_swing_delays_off_beats() {
+        let swing = NoteMeta {
+            swing: Some(Swing {
+                ratio: 2.0 / 3.0,
+                grid_beats: 1.0,
+            }),
+            ..NoteMeta::default()
+        };
+        // Explicit half-beat durations, then a two-note tuplet over one beat
+        let eighth = |semitones| SlotValue::Note {
+            interval: IntervalValue::from_semitones(semitones),
+            articulations: vec![],
+            duration_beats: Some(0.5),
+            meta: swing.clone(),
+        };
+        let tuplet = SlotValue::Tuplet {
+            slots: vec![note(4, swing.clone()), note(5, swing.clone())],
+            target_beats: 1.0,
+        };
+        let song = song_of(vec![eighth(0), eighth(2), tuplet], 1.0);
+
+        let ticks: Vec<u32> = note_ons(&render_to_midi(&song).unwrap())
+            .iter()
+            .map(|(tick, _, _)| *tick)
+            .collect();
+        assert_eq!(ticks, vec![0, 320, 480, 800]);
+    }
+
+    #[test]
+    fn test_tuplets_fill_fractional_beats() {
+        // A triplet over a beat and a half, then a note
+        let tuplet = SlotValue::Tuplet {
+            slots: vec![
+                note(0, NoteMeta::default()),
+                note(2, NoteMeta::default()),
+                note(4, NoteMeta::default()),
+            ],
+            target_beats: 1.5,
+        };
+        let song = song_of(vec![tuplet, note(5, NoteMeta::default())], 2.0);
+
+        let ticks: Vec<u32> = note_ons(&render_to_midi(&song).unwrap())
+            .iter()
+            .map(|(tick, _, _)| *tick)
+            .collect();
+        assert_eq!(ticks, vec![0, 240, 480, 720]);
+    }
+
+    #[test]
+    fn test_nested_tuplets_scale_their_slots() {
+        let eighth = |semitones| SlotValue::Note {
+            interval: IntervalValue::from_semitones(semitones),
+            articulations: vec![],
+            duration_beats: Some(0.5),
+            meta: NoteMeta::default(),
+        };
+        // A beat, then a beat split into a triplet of eighths
+        let inner = SlotValue::Tuplet {
+            slots: vec![eighth(2), eighth(4), eighth(5)],
+            target_beats: 1.0,
+        };
+        let tuplet = SlotValue::Tuplet {
+            slots: vec![note(0, NoteMeta::default()), inner],
+            target_beats: 2.0,
+        };
+        let song = song_of(vec![tuplet], 2.0);
+
+        let ticks: Vec<u32> = note_ons(&render_to_midi(&song).unwrap())
+            .iter()
+            .map(|(tick, _, _)| *tick)
+            .collect();
+        assert_eq!(ticks, vec![0, 480, 640, 800]);
+    }
+
+    #[test]
+    fn test_section_tempos_change_the_tempo() {
+        let section = || {
+            song_of(vec![note(0, NoteMeta::default())], 1.0)
+                .sections
+                .remove(0)
+        };
+        let fast = SectionValue {
+            tempo: Some(140.0),
+            ..section()
+        };
+        let song = SongValue::new(vec![fast.clone(), fast, section()]);
+
+        let bytes = render_to_midi(&song).unwrap();
+        let smf = Smf::parse(&bytes).unwrap();
+        let mut tick = 0;
+        let mut tempos = Vec::new();
+        for event in &smf.tracks[0] {
+            tick += event.delta.as_int();
+            if let TrackEventKind::Meta(midly::MetaMessage::Tempo(microseconds)) = event.kind {
+                tempos.push((tick, microseconds.as_int()));
+            }
+        }
+        // The first section sets the starting tempo; the song's tempo
+        // returns after the fast sections
+        assert_eq!(tempos, vec![(0, 428_571), (2 * 4 * 480, 500_000)]);
+    }
+
+    #[test]
+    fn test_markers_and_lyrics() {
+        let marked = NoteMeta {
+            marker: Some("Chorus".to_string()),
+            lyric: Some("la".to_string()),
+            ..NoteMeta::default()
+        };
+        let mut song = song_of(vec![note(0, NoteMeta::default())], 1.0);
+        let mut chorus = song_of(vec![note(0, marked)], 1.0).sections.remove(0);
+        chorus.parts[0].instrument = "Other".to_string();
+        song.sections.push(chorus);
+
+        let bytes = render_to_midi(&song).unwrap();
+        let smf = Smf::parse(&bytes).unwrap();
+        let meta_at = |track: usize| {
+            let mut tick = 0;
+            let mut found = Vec::new();
+            for event in &smf.tracks[track] {
+                tick += event.delta.as_int();
+                match event.kind {
+                    TrackEventKind::Meta(midly::MetaMessage::Marker(text)) => {
+                        found.push((tick, "marker", text.to_vec()))
+                    }
+                    TrackEventKind::Meta(midly::MetaMessage::Lyric(text)) => {
+                        found.push((tick, "lyric", text.to_vec()))
+                    }
+                    _ => {}
+                }
+            }
+            found
+        };
+
+        // Markers go to the conductor track, lyrics stay with the part's notes
+        let chorus_start = 4 * 480;
+        assert_eq!(
+            meta_at(0),
+            vec![(chorus_start, "marker", b"Chorus".to_vec())]
+        );
+        assert_eq!(meta_at(2), vec![(chorus_start, "lyric", b"la".to_vec())]);
+        assert!(meta_at(1).is_empty());
+    }
+
+    #[test]
+    fn test_metadata_on_conductor_track() {
+        let renderer = MidiRenderer::new(MidiConfig {
+            metadata: SongMetadata {
+                title: Some("Etude".to_string()),
+                composer: Some("A. Composer".to_string()),
+                copyright: Some("(c) 2026".to_string()),
+            },
+            ..MidiConfig::default()
+        });
+        let bytes = renderer
+            .render(&song_of(vec![note(0, NoteMeta::default())], 1.0))
+            .unwrap();
+        let smf = Smf::parse(&bytes).unwrap();
+
+        let texts: Vec<&[u8]> = smf.tracks[0]
+            .iter()
+            .filter_map(|event| match event.kind {
+                TrackEventKind::Meta(
+                    midly::MetaMessage::Copyright(text)
+                    | midly::MetaMessage::TrackName(text)
+                    | midly::MetaMessage::Text(text),
+                ) => Some(text),
+                _ => None,
+            })
+            .collect();
+        let expected: [&[u8]; 3] = [b"(c) 2026", b"Etude", b"Composer: A. Composer"];
+        assert_eq!(texts, expected);
+        // The copyright notice comes first
+        assert!(matches!(
+            smf.tracks[0][0].kind,
+            TrackEventKind::Meta(midly::MetaMessage::Copyright(_))
+        ));
+    }
+
+    #[test]
+    fn test_pan_emits_cc10() {
+        let pan_cc = |pan_level: f64| {
+            let mut song = song_of(vec![note(0, NoteMeta::default())], 1.0);
+            song.sections[0].parts[0].pan_level = Some(pan_level);
+            let bytes = render_to_midi(&song).unwrap();
+            let smf = Smf::parse(&bytes).unwrap();
+            let value = smf.tracks[1].iter().find_map(|event| match event.kind {
+                TrackEventKind::Midi {
+                    message: MidiMessage::Controller { controller, value },
+                    ..
+                } if controller.as_int() == 10 => Some(value.as_int()),
+                _ => None,
+            });
+            value
+        };
+
+        assert_eq!(pan_cc(-1.0), Some(0));
+        assert_eq!(pan_cc(0.0), Some(64));
+        assert_eq!(pan_cc(1.0), Some(127));
+    }
+
+    #[test]
+    fn test_reverb_adds_up_sends_and_master() {
+        let reverb_cc = |song: &SongValue| {
+            let bytes = render_to_midi(song).unwrap();
+            let smf = Smf::parse(&bytes).unwrap();
+            controllers(&smf.tracks[1])
+                .into_iter()
+                .find_map(|(_, controller, value)| (controller == 91).then_some(value))
+        };
+
+        let mut song = song_of(vec![note(0, NoteMeta::default())], 1.0);
+        assert_eq!(reverb_cc(&song), None);
+
+        song.sections[0].parts[0].effects = vec![EffectValue::Reverb(0.2)];
+        song.sections[0].parts[0].sends = vec![SendValue {
+            bus: BusValue {
+                name: "room".to_string(),
+                effects: vec![EffectValue::Reverb(0.5)],
+            },
+            level: 0.4,
+        }];
+        // 0.2 + 0.4 * 0.5
+        assert_eq!(reverb_cc(&song), Some(51));
+
+        song.master = vec![EffectValue::Reverb(0.8)];
+        assert_eq!(reverb_cc(&song), Some(127));
+    }
+
+    #[test]
+    fn test_automation_interpolates_cc() {
+        let mut song = song_of(vec![note(0, NoteMeta::default())], 4.0);
+        song.sections[0].parts[0].automation.push(AutomationValue {
+            cc: 74,
+            points: vec![0.0, 1.0, 0.5],
+        });
+
+        let bytes = render_to_midi(&song).unwrap();
+        let smf = Smf::parse(&bytes).unwrap();
+        let mut tick = 0;
+        let mut sweep = Vec::new();
+        for event in &smf.tracks[1] {
+            tick += event.delta.as_int();
+            if let TrackEventKind::Midi {
+                message: MidiMessage::Controller { controller, value },
+                ..
+            } = event.kind
+            {
+                if controller.as_int() == 74 {
+                    sweep.push((tick, value.as_int()));
+                }
+            }
+        }
+
+        // Rises to the peak at the middle of the part, then falls to half level
+        assert_eq!(sweep.first(), Some(&(0, 0)));
+        assert!(sweep.contains(&(2 * 480, 127)));
+        assert_eq!(sweep.last(), Some(&(4 * 480, 64)));
+        assert!(sweep.windows(2).all(|w| w[0].0 < w[1].0));
+    }
+
+    #[test]
+    fn test_chorus_level() {
+        let mut song = song_of(vec![note(0, NoteMeta::default())], 1.0);
+        let chorus = |mix| EffectValue::Chorus(ChorusParams::new(1.0, 0.5, mix));
+        song.sections[0].parts[0].effects = vec![chorus(0.5), EffectValue::Width(1.5)];
+        song.master = vec![chorus(0.25)];
+
+        let bytes = render_to_midi(&song).unwrap();
+        let smf = Smf::parse(&bytes).unwrap();
+        let chorus_cc = controllers(&smf.tracks[1])
+            .into_iter()
+            .find_map(|(_, controller, value)| (controller == 93).then_some(value));
+        // 0.5 + 0.25
+        assert_eq!(chorus_cc, Some(95));
+    }
+
+    #[test]
+    fn test_compressor_and_eq_controllers() {
+        let mut song = song_of(vec![note(0, NoteMeta::default())], 1.0);
+        song.sections[0].parts[0].effects = vec![
+            EffectValue::Compressor(CompressorParams::new(-30.0, 20.0, 0.0, 1000.0)),
+            EffectValue::Eq(EqParams::new(0.0, 24.0, -24.0)),
+        ];
+        let bytes = render_to_midi(&song).unwrap();
+        let smf = Smf::parse(&bytes).unwrap();
+        let effects: Vec<_> = controllers(&smf.tracks[1])
+            .into_iter()
+            .filter(|(_, controller, _)| (102..=108).contains(controller))
+            .map(|(_, controller, value)| (controller, value))
+            .collect();
+        assert_eq!(
+            effects,
+            [
+                (102, 64),
+                (103, 127),
+                (104, 0),
+                (105, 127),
+                (106, 64),
+                (107, 127),
+                (108, 0)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ducking_dips_channel_volume() {
+        let mut song = song_of(vec![note(0, NoteMeta::default())], 4.0);
+        let part = &mut song.sections[0].parts[0];
+        part.volume_level = Some(0.8);
+        // A kick on beats 1 and 3
+        let kick = vec![note(0, NoteMeta::default()), note(0, NoteMeta::default())];
+        part.ducks.push(DuckValue {
+            trigger: song_of(kick, 4.0).sections[0].parts[0].clone(),
+            amount: 0.5,
+        });
+
+        // CC#7 values by tick; the level at a tick is the last one sent
+        let volume = |song: &SongValue| {
+            let bytes = render_to_midi(song).unwrap();
+            let smf = Smf::parse(&bytes).unwrap();
+            let mut tick = 0;
+            let mut volume = Vec::new();
+            for event in &smf.tracks[1] {
+                tick += event.delta.as_int();
+                if let TrackEventKind::Midi {
+                    message: MidiMessage::Controller { controller, value },
+                    ..
+                } = event.kind
+                {
+                    if controller.as_int() == 7 {
+                        volume.push((tick, value.as_int()));
+                    }
+                }
+            }
+            volume
+        };
+        let level_at = |volume: &[(u32, u8)], at: u32| {
+            volume
+                .iter()
+                .rev()
+                .find(|(tick, _)| *tick <= at)
+                .map(|(_, value)| *value)
+        };
+
+        // Each kick halves the volume, which is back half a beat later
+        let levels = volume(&song);
+        assert_eq!(level_at(&levels, 0), Some(51));
+        assert_eq!(level_at(&levels, 120), Some(76));
+        assert_eq!(level_at(&levels, 240), Some(102));
+        assert_eq!(level_at(&levels, 2 * 480 - 1), Some(102));
+        assert_eq!(level_at(&levels, 2 * 480), Some(51));
+        assert_eq!(level_at(&levels, 4 * 480), Some(102));
+
+        // A volume curve is what the dips start from, not a second CC#7 stream
+        song.sections[0].parts[0].automation.push(AutomationValue {
+            cc: 7,
+            points: vec![0.0, 1.0],
+        });
+        let levels = volume(&song);
+        assert_eq!(level_at(&levels, 0), Some(0));
+        assert_eq!(level_at(&levels, 480), Some(32));
+        assert_eq!(level_at(&levels, 2 * 480), Some(32));
+        assert_eq!(level_at(&levels, 3 * 480), Some(95));
+        assert_eq!(level_at(&levels, 4 * 480), Some(127));
+    }
+
+    #[test]
+    fn test_part_envelopes() {
+        let mut song = song_of(vec![note(0, NoteMeta::default())], 4.0);
+        song.sections[0].parts[0].envelope = Some(PartEnvelope {
+            dynamics: Some(EnvelopeValue {
+                from: DynamicValue::PP,
+                to: DynamicValue::FF,
+                duration_beats: 2.0,
+            }),
+            adsr: Some(ADSREnvelope::new(0.5, 0.1, 0.8, 0.3)),
+        });
+
+        let bytes = render_to_midi(&song).unwrap();
+        let smf = Smf::parse(&bytes).unwrap();
+        let mut tick = 0;
+        let mut controllers = Vec::new();
+        for event in &smf.tracks[1] {
+            tick += event.delta.as_int();
+            if let TrackEventKind::Midi {
+                message: MidiMessage::Controller { controller, value },
+                ..
+            } = event.kind
+            {
+                controllers.push((tick, controller.as_int(), value.as_int()));
+            }
+        }
+
+        // The expression controller ramps from pp to ff over two beats
+        let expression: Vec<_> = controllers
+            .iter()
+            .filter(|(_, cc, _)| *cc == CC_EXPRESSION)
+            .collect();
+        assert_eq!(expression.first(), Some(&&(0, CC_EXPRESSION, 33)));
+        assert_eq!(expression.last(), Some(&&(2 * 480, CC_EXPRESSION, 112)));
+        // The ADSR envelope is sent even though the part has no synth
+        assert!(controllers
+            .iter()
+            .any(|&(tick, cc, _)| tick == 0 && cc == CC_ATTACK));
+    }
+}
@@ -0,0 +1,41 @@
+//! MPE (MIDI Polyphonic Expression) voice allocation
+
+/// Master channel of the MPE lower zone (channel 1, zero-based)
+///
+/// Part-wide controllers (volume, pan, synth settings) are sent here.
+pub(crate) const MASTER_CHANNEL: u8 = 0;
+
+/// Member channels of the zone (channels 2-9), one sounding note each
+///
+/// The zone stops below the drum channel so percussion keeps channel 10.
+pub(crate) const MEMBER_CHANNELS: [u8; 8] = [1, 2, 3, 4, 5, 6, 7, 8];
+
+/// Gives each note a member channel of its own, so every note can carry an
+/// independent pitch bend
+///
+/// Parts are rendered one after another rather than in time order, so each
+/// channel keeps the spans of all the notes placed on it.
+#[derive(Default)]
+pub(crate) struct MemberChannels {
+    busy: [Vec<(u32, u32)>; MEMBER_CHANNELS.len()],
+}
+
+impl MemberChannels {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Member channel for a note sounding from `start` to `end`
+    ///
+    /// When every channel is busy, the note shares the channel with the
+    /// fewest notes overlapping it.
+    pub(crate) fn allocate(&mut self, start: u32, end: u32) -> u8 {
+        let overlaps =
+            |spans: &Vec<(u32, u32)>| spans.iter().filter(|(s, e)| *s < end && start < *e).count();
+        let index = (0..MEMBER_CHANNELS.len())
+            .min_by_key(|&i| overlaps(&self.busy[i]))
+            .unwrap_or(0);
+        self.busy[index].push((start, end));
+        MEMBER_CHANNELS[index]
+    }
+}
@@ -0,0 +1,288 @@
+//! Open Sound Control output, for driving SuperCollider, Max or any other
+//! OSC host live
+//!
+//! A performance is sent over UDP as it plays, one message per event, each
+//! address under a configurable prefix (`/relanote` by default):
+//!
+//! - `/relanote/tempo f:bpm` at the start and on every tempo change
+//! - `/relanote/beat i:beat` on every beat, when the beat clock is on
+//! - `/relanote/note s:instrument i:channel f:pitch i:velocity f:seconds`
+//!   when a note starts, with its length in seconds
+//! - `/relanote/stop` at the end of the song
+
+use std::io;
+use std::net::{ToSocketAddrs, UdpSocket};
+
+use crate::performance::Performance;
+use crate::scheduler::{schedule, Clock, Timed};
+
+/// OSC output settings
+#[derive(Clone, Debug)]
+pub struct OscConfig {
+    /// Host to send to
+    pub host: String,
+    /// UDP port to send to (default: 57120, SuperCollider's language port)
+    pub port: u16,
+    /// Prefix of every address
+    pub prefix: String,
+    /// Send `/beat` on every beat, so the host can follow along
+    pub clock: bool,
+}
+
+impl Default for OscConfig {
+    fn default() -> Self {
+        Self {
+            host: "127.0.0.1".to_string(),
+            port: 57120,
+            prefix: "/relanote".to_string(),
+            clock: true,
+        }
+    }
+}
+
+/// An argument of an OSC message
+#[derive(Clone, Debug, PartialEq)]
+pub enum OscArg {
+    Int(i32),
+    Float(f32),
+    String(String),
+}
+
+/// An OSC message: an address and its arguments
+#[derive(Clone, Debug, PartialEq)]
+pub struct OscMessage {
+    pub address: String,
+    pub args: Vec<OscArg>,
+}
+
+impl OscMessage {
+    pub fn new(address: impl Into<String>, args: Vec<OscArg>) -> Self {
+        Self {
+            address: address.into(),
+            args,
+        }
+    }
+
+    /// The message as an OSC 1.0 packet
+    pub fn encode(&self) -> Vec<u8> {
+        let mut packet = Vec::new();
+        write_string(&mut packet, &self.address);
+        let tags: String = std::iter::once(',')
+            .chain(self.args.iter().map(|arg| match arg {
+                OscArg::Int(_) => 'i',
+                OscArg::Float(_) => 'f',
+                OscArg::String(_) => 's',
+            }))
+            .collect();
+        write_string(&mut packet, &tags);
+        for arg in &self.args {
+            match arg {
+                OscArg::Int(value) => packet.extend(value.to_be_bytes()),
+                OscArg::Float(value) => packet.extend(value.to_be_bytes()),
+                OscArg::String(value) => write_string(&mut packet, value),
+            }
+        }
+        packet
+    }
+}
+
+/// A null-terminated string, padded to a multiple of four bytes
+fn write_string(packet: &mut Vec<u8>, text: &str) {
+    packet.extend(text.as_bytes());
+    packet.extend(std::iter::repeat_n(0, 4 - text.len() % 4));
+}
+
+/// A message and the beat it is sent on
+#[derive(Clone, Debug, PartialEq)]
+pub struct OscEvent {
+    pub beat: f64,
+    pub message: OscMessage,
+}
+
+impl Timed for OscEvent {
+    fn beat(&self) -> f64 {
+        self.beat
+    }
+}
+
+/// The messages of a performance, in the order they are sent
+pub fn osc_events(performance: &Performance, config: &OscConfig) -> Vec<OscEvent> {
+    let address = |name: &str| format!("{}/{}", config.prefix.trim_end_matches('/'), name);
+    let event = |beat: f64, name: &str, args: Vec<OscArg>| OscEvent {
+        beat,
+        message: OscMessage::new(address(name), args),
+    };
+    let total_beats = performance.total_beats();
+
+    let mut events: Vec<OscEvent> = std::iter::once(0.0)
+        .chain(performance.tempo_changes())
+        .map(|beat| {
+            let tempo = performance.tempo_at(beat) as f32;
+            event(beat, "tempo", vec![OscArg::Float(tempo)])
+        })
+        .collect();
+    if config.clock {
+        let beats = (0..).map(f64::from).take_while(|&beat| beat < total_beats);
+        events.extend(beats.map(|beat| event(beat, "beat", vec![OscArg::Int(beat as i32)])));
+    }
+    for note in performance.notes() {
+        let seconds =
+            performance.seconds_at(note.beat + note.beats) - performance.seconds_at(note.beat);
+        events.push(event(
+            note.beat,
+            "note",
+            vec![
+                OscArg::String(note.instrument.clone()),
+                OscArg::Int(note.channel as i32),
+                OscArg::Float(note.pitch as f32),
+                OscArg::Int(note.velocity as i32),
+                OscArg::Float(seconds as f32),
+            ],
+        ));
+    }
+    events.push(event(total_beats, "stop", Vec::new()));
+    // Stable, so a beat's tempo and clock come before its notes
+    events.sort_by(|a, b| a.beat.total_cmp(&b.beat));
+    events
+}
+
+/// Sends OSC messages over UDP
+pub struct OscSender {
+    socket: UdpSocket,
+}
+
+impl OscSender {
+    /// A sender to the host and port of `config`
+    pub fn connect(config: &OscConfig) -> io::Result<Self> {
+        let target = (config.host.as_str(), config.port)
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no address for the host"))?;
+        let local = if target.is_ipv4() {
+            "0.0.0.0:0"
+        } else {
+            "[::]:0"
+        };
+        let socket = UdpSocket::bind(local)?;
+        socket.connect(target)?;
+        Ok(Self { socket })
+    }
+
+    pub fn send(&self, message: &OscMessage) -> io::Result<()> {
+        self.socket.send(&message.encode()).map(|_| ())
+    }
+
+    /// Send the messages of a performance as `clock` plays it, over and
+    /// over with `looping`
+    ///
+    /// Returns whether it played to the end; when the clock stops it
+    /// first, `/stop` is sent then.
+    pub fn play(
+        &self,
+        performance: &Performance,
+        config: &OscConfig,
+        clock: &mut dyn Clock,
+        looping: bool,
+    ) -> io::Result<bool> {
+        let events = osc_events(performance, config);
+        let length = performance.total_beats();
+        let finished = schedule(clock, &events, length, looping, |event| {
+            self.send(&event.message)
+        })?;
+        if !finished {
+            if let Some(stop) = events.last() {
+                self.send(&stop.message)?;
+            }
+        }
+        Ok(finished)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::midi::MidiConfig;
+    use relanote_eval::value::{
+        BlockValue, IntervalValue, NoteMeta, PartValue, SectionValue, SlotValue, SongValue,
+    };
+
+    /// A piano playing a note on each beat, `semitones` above C4
+    fn performance(semitones: &[i32]) -> Performance {
+        let slots = semitones
+            .iter()
+            .map(|&semitones| SlotValue::Note {
+                interval: IntervalValue::from_semitones(semitones),
+                articulations: vec![],
+                duration_beats: None,
+                meta: NoteMeta::default(),
+            })
+            .collect();
+        let block = BlockValue::with_beats(slots, semitones.len() as f64);
+        let song = SongValue::new(vec![SectionValue::new(
+            "Main",
+            vec![PartValue::new("Piano", vec![block])],
+        )]);
+        Performance::new(&song, &MidiConfig::default()).unwrap()
+    }
+
+    #[test]
+    fn test_encode_message() {
+        let message = OscMessage::new(
+            "/relanote/note",
+            vec![
+                OscArg::String("Bass".to_string()),
+                OscArg::Int(60),
+                OscArg::Float(0.5),
+            ],
+        );
+        let mut expected = b"/relanote/note\0\0,sif\0\0\0\0Bass\0\0\0\0".to_vec();
+        expected.extend([0, 0, 0, 60, 0x3f, 0, 0, 0]);
+        assert_eq!(message.encode(), expected);
+    }
+
+    #[test]
+    fn test_events_follow_the_beat() {
+        let performance = performance(&[0, 4, 7]);
+        let events = osc_events(&performance, &OscConfig::default());
+        let sent: Vec<(f64, &str)> = events
+            .iter()
+            .map(|event| (event.beat, event.message.address.as_str()))
+            .collect();
+        assert_eq!(
+            sent,
+            vec![
+                (0.0, "/relanote/tempo"),
+                (0.0, "/relanote/beat"),
+                (0.0, "/relanote/note"),
+                (1.0, "/relanote/beat"),
+                (1.0, "/relanote/note"),
+                (2.0, "/relanote/beat"),
+                (2.0, "/relanote/note"),
+                (3.0, "/relanote/stop"),
+            ]
+        );
+        // A beat at 120 BPM is half a second
+        assert_eq!(
+            events[2].message.args[2..],
+            [OscArg::Float(60.0), OscArg::Int(100), OscArg::Float(0.5)]
+        );
+    }
+
+    #[test]
+    fn test_send_over_udp() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let config = OscConfig {
+            port: receiver.local_addr().unwrap().port(),
+            prefix: "/live".to_string(),
+            clock: false,
+            ..OscConfig::default()
+        };
+        let sender = OscSender::connect(&config).unwrap();
+        let message = OscMessage::new("/live/stop", Vec::new());
+        sender.send(&message).unwrap();
+
+        let mut buffer = [0u8; 64];
+        let length = receiver.recv(&mut buffer).unwrap();
+        assert_eq!(&buffer[..length], message.encode().as_slice());
+    }
+}
@@ -0,0 +1,264 @@
+//! A song as timed notes and MIDI messages, for backends that play it as it
+//! goes instead of writing a file
+//!
+//! The song is laid out with the MIDI renderer, like the SoundFont backend
+//! does, so timing, channels, drum keys and microtones match the MIDI output.
+
+use std::collections::HashMap;
+
+use midly::live::LiveEvent;
+use midly::{MetaMessage, MidiMessage, Smf, Timing, TrackEventKind};
+use relanote_eval::value::SongValue;
+
+use crate::error::RenderError;
+use crate::midi::{MidiConfig, MidiRenderer};
+use crate::scheduler::{ClockMessage, CLOCK_TICKS_PER_BEAT};
+
+/// A song laid out in beats, with the tempo changes that turn beats into
+/// seconds
+#[derive(Clone, Debug)]
+pub struct Performance {
+    notes: Vec<PerformedNote>,
+    events: Vec<PerformanceEvent>,
+    /// Beat of each tempo change and the seconds per beat from there on
+    tempos: Vec<(f64, f64)>,
+    total_beats: f64,
+}
+
+/// A note of a performance
+#[derive(Clone, Debug, PartialEq)]
+pub struct PerformedNote {
+    /// Beat the note starts on
+    pub beat: f64,
+    /// Length of the note in beats
+    pub beats: f64,
+    /// Instrument of the part playing it
+    pub instrument: String,
+    /// Zero-based MIDI channel
+    pub channel: u8,
+    /// MIDI pitch, with microtones as a fraction of a semitone
+    pub pitch: f64,
+    pub velocity: u8,
+}
+
+/// A MIDI channel message of a performance
+#[derive(Clone, Debug, PartialEq)]
+pub struct PerformanceEvent {
+    /// Beat the message is sent on
+    pub beat: f64,
+    /// The message, status byte first
+    pub bytes: Vec<u8>,
+}
+
+impl Performance {
+    /// Lay `song` out as the MIDI renderer would with `config`
+    pub fn new(song: &SongValue, config: &MidiConfig) -> Result<Self, RenderError> {
+        let midi = MidiRenderer::new(config.clone()).render(song)?;
+        let smf = Smf::parse(&midi)?;
+        let ticks_per_beat = match smf.header.timing {
+            Timing::Metrical(ticks) => ticks.as_int() as f64,
+            Timing::Timecode(..) => config.ticks_per_beat as f64,
+        };
+
+        let mut notes = Vec::new();
+        let mut events = Vec::new();
+        let mut tempos = vec![(0.0, 60.0 / config.tempo as f64)];
+        let mut end_tick = 0u64;
+        for track in &smf.tracks {
+            let mut tick = 0u64;
+            let mut instrument = String::new();
+            // Sounding notes by (channel, key): index in `notes` and start tick
+            let mut sounding: HashMap<(u8, u8), Vec<(usize, u64)>> = HashMap::new();
+            let mut bends = [0.0f64; 16];
+            for event in track {
+                tick += event.delta.as_int() as u64;
+                match event.kind {
+                    TrackEventKind::Meta(MetaMessage::TrackName(bytes)) => {
+                        instrument = String::from_utf8_lossy(bytes).to_string()
+                    }
+                    TrackEventKind::Meta(MetaMessage::Tempo(tempo)) => tempos.push((
+                        tick as f64 / ticks_per_beat,
+                        tempo.as_int() as f64 / 1_000_000.0,
+                    )),
+                    TrackEventKind::Midi { channel, message } => {
+                        let mut bytes = Vec::new();
+                        LiveEvent::Midi { channel, message }.write_std(&mut bytes)?;
+                        events.push(PerformanceEvent {
+                            beat: tick as f64 / ticks_per_beat,
+                            bytes,
+                        });
+                        let channel = channel.as_int();
+                        match message {
+                            MidiMessage::NoteOn { key, vel } if vel.as_int() > 0 => {
+                                let key = key.as_int();
+                                sounding
+                                    .entry((channel, key))
+                                    .or_default()
+                                    .push((notes.len(), tick));
+                                notes.push(PerformedNote {
+                                    beat: tick as f64 / ticks_per_beat,
+                                    beats: 0.0,
+                                    instrument: instrument.clone(),
+                                    channel,
+                                    pitch: key as f64 + bends[channel as usize],
+                                    velocity: vel.as_int(),
+                                });
+                            }
+                            MidiMessage::NoteOn { key, .. } | MidiMessage::NoteOff { key, .. } => {
+                                let started = sounding.get_mut(&(channel, key.as_int()));
+                                if let Some((index, start)) =
+                                    started.filter(|s| !s.is_empty()).map(|s| s.remove(0))
+                                {
+                                    notes[index].beats = (tick - start) as f64 / ticks_per_beat;
+                                }
+                            }
+                            MidiMessage::PitchBend { bend } => {
+                                bends[channel as usize] = bend.as_f64() * config.pitch_bend_range
+                            }
+                            _ => {}
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            end_tick = end_tick.max(tick);
+        }
+        notes.sort_by(|a, b| a.beat.total_cmp(&b.beat));
+        // Stable, so each track keeps its order (note-offs before note-ons)
+        events.sort_by(|a, b| a.beat.total_cmp(&b.beat));
+        tempos.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        Ok(Self {
+            notes,
+            events,
+            tempos,
+            total_beats: end_tick as f64 / ticks_per_beat,
+        })
+    }
+
+    /// Notes in the order they start
+    pub fn notes(&self) -> &[PerformedNote] {
+        &self.notes
+    }
+
+    /// MIDI channel messages in the order they are sent, as the MIDI file
+    /// would play them
+    pub fn events(&self) -> &[PerformanceEvent] {
+        &self.events
+    }
+
+    /// The performance's MIDI messages with a MIDI clock tick on every
+    /// 24th of a beat, for a port other programs follow
+    pub fn events_with_clock(&self) -> Vec<PerformanceEvent> {
+        let ticks = (self.total_beats() * CLOCK_TICKS_PER_BEAT as f64).ceil() as u64;
+        let mut events: Vec<PerformanceEvent> = (0..ticks)
+            .map(|tick| PerformanceEvent {
+                beat: tick as f64 / CLOCK_TICKS_PER_BEAT as f64,
+                bytes: vec![ClockMessage::Tick.to_midi()],
+            })
+            .collect();
+        events.extend(self.events().iter().cloned());
+        // Stable, so a tick comes before the notes on it
+        events.sort_by(|a, b| a.beat.total_cmp(&b.beat));
+        events
+    }
+
+    /// Length of the song in beats
+    pub fn total_beats(&self) -> f64 {
+        self.total_beats
+    }
+
+    /// Tempo in BPM at `beat`
+    pub fn tempo_at(&self, beat: f64) -> f64 {
+        60.0 / self.seconds_per_beat(beat)
+    }
+
+    /// Seconds from the start of the song to `beat`
+    pub fn seconds_at(&self, beat: f64) -> f64 {
+        let mut seconds = 0.0;
+        for (index, &(from, seconds_per_beat)) in self.tempos.iter().enumerate() {
+            if from >= beat {
+                break;
+            }
+            let until = self
+                .tempos
+                .get(index + 1)
+                .map_or(beat, |&(next, _)| next.min(beat));
+            seconds += (until - from) * seconds_per_beat;
+        }
+        seconds
+    }
+
+    /// Beats where the tempo changes, after the start
+    pub fn tempo_changes(&self) -> impl Iterator<Item = f64> + '_ {
+        self.tempos
+            .iter()
+            .map(|&(beat, _)| beat)
+            .filter(|&beat| beat > 0.0)
+    }
+
+    fn seconds_per_beat(&self, beat: f64) -> f64 {
+        self.tempos
+            .iter()
+            .rev()
+            .find(|&&(from, _)| from <= beat)
+            .map_or(0.5, |&(_, seconds_per_beat)| seconds_per_beat)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use relanote_eval::value::{
+        BlockValue, IntervalValue, NoteMeta, PartValue, SectionValue, SlotValue,
+    };
+
+    #[test]
+    fn test_notes_and_events_in_beats() {
+        let slots = [0, 7]
+            .into_iter()
+            .map(|semitones| SlotValue::Note {
+                interval: IntervalValue::from_semitones(semitones),
+                articulations: vec![],
+                duration_beats: None,
+                meta: NoteMeta::default(),
+            })
+            .collect();
+        let song = SongValue::new(vec![SectionValue::new(
+            "Main",
+            vec![PartValue::new(
+                "Bass",
+                vec![BlockValue::with_beats(slots, 2.0)],
+            )],
+        )]);
+        let config = MidiConfig {
+            tempo: 90,
+            ..MidiConfig::default()
+        };
+        let performance = Performance::new(&song, &config).unwrap();
+
+        let notes: Vec<(f64, f64, &str, f64)> = performance
+            .notes()
+            .iter()
+            .map(|n| (n.beat, n.beats, n.instrument.as_str(), n.pitch))
+            .collect();
+        assert_eq!(
+            notes,
+            vec![(0.0, 1.0, "Bass", 60.0), (1.0, 1.0, "Bass", 67.0)]
+        );
+        assert_eq!(performance.total_beats(), 2.0);
+        assert_eq!(performance.tempo_at(1.0).round(), 90.0);
+        assert!((performance.seconds_at(2.0) - 4.0 / 3.0).abs() < 1e-5);
+
+        let note_ons: Vec<(f64, &[u8])> = performance
+            .events()
+            .iter()
+            .filter(|event| event.bytes[0] & 0xF0 == 0x90)
+            .map(|event| (event.beat, &event.bytes[..2]))
+            .collect();
+        assert_eq!(
+            note_ons,
+            vec![(0.0, &[0x90, 60][..]), (1.0, &[0x90, 67][..])]
+        );
+    }
+}
@@ -0,0 +1,279 @@
+//! Playing timed events as a clock reaches them, shared by the live
+//! backends: the clock is the performance's own tempo, or MIDI clock from
+//! another program so playback stays locked to it
+
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+use crate::performance::{Performance, PerformanceEvent};
+
+/// MIDI clock ticks per beat
+pub const CLOCK_TICKS_PER_BEAT: u32 = 24;
+
+/// Where the beats of a performance come from
+pub trait Clock {
+    /// Block until the clock reaches `beat`, counted from the start of
+    /// playback; false when the clock stopped before it
+    fn wait_until(&mut self, beat: f64) -> bool;
+}
+
+/// An event played at a beat
+pub trait Timed {
+    fn beat(&self) -> f64;
+}
+
+impl Timed for PerformanceEvent {
+    fn beat(&self) -> f64 {
+        self.beat
+    }
+}
+
+/// Send `events`, which are in order, as `clock` reaches their beats;
+/// with `looping` they repeat every `length` beats
+///
+/// Returns whether the events played to the end, rather than the clock
+/// stopping them.
+pub fn schedule<T: Timed, E>(
+    clock: &mut dyn Clock,
+    events: &[T],
+    length: f64,
+    looping: bool,
+    mut send: impl FnMut(&T) -> Result<(), E>,
+) -> Result<bool, E> {
+    let mut offset = 0.0;
+    loop {
+        for event in events {
+            if !clock.wait_until(offset + event.beat()) {
+                return Ok(false);
+            }
+            send(event)?;
+        }
+        if !looping || length <= 0.0 {
+            return Ok(true);
+        }
+        offset += length;
+    }
+}
+
+/// The performance's own tempo, by the system clock, from when the clock
+/// was made
+pub struct WallClock<'a> {
+    performance: &'a Performance,
+    start: Instant,
+}
+
+impl<'a> WallClock<'a> {
+    pub fn new(performance: &'a Performance) -> Self {
+        Self {
+            performance,
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Clock for WallClock<'_> {
+    fn wait_until(&mut self, beat: f64) -> bool {
+        // Beats past the end are in later passes of a loop
+        let length = self.performance.total_beats();
+        let passes = if length > 0.0 {
+            (beat / length).floor()
+        } else {
+            0.0
+        };
+        let seconds = passes * self.performance.seconds_at(length)
+            + self.performance.seconds_at(beat - passes * length);
+        let at = self.start + Duration::from_secs_f64(seconds.max(0.0));
+        if let Some(wait) = at.checked_duration_since(Instant::now()) {
+            std::thread::sleep(wait);
+        }
+        true
+    }
+}
+
+/// A MIDI real-time message that drives a [`FollowedClock`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClockMessage {
+    Tick,
+    Start,
+    Continue,
+    Stop,
+}
+
+impl ClockMessage {
+    /// The clock message a MIDI message is, if it is one
+    pub fn from_midi(bytes: &[u8]) -> Option<Self> {
+        match bytes.first()? {
+            0xF8 => Some(Self::Tick),
+            0xFA => Some(Self::Start),
+            0xFB => Some(Self::Continue),
+            0xFC => Some(Self::Stop),
+            _ => None,
+        }
+    }
+
+    /// The MIDI message
+    pub fn to_midi(self) -> u8 {
+        match self {
+            Self::Tick => 0xF8,
+            Self::Start => 0xFA,
+            Self::Continue => 0xFB,
+            Self::Stop => 0xFC,
+        }
+    }
+}
+
+/// Incoming MIDI clock, such as a DAW's: playback starts on its Start,
+/// moves with its ticks and stops on its Stop
+///
+/// Between ticks, beats are placed by the length of the last tick, so
+/// events keep their timing finer than a tick.
+pub struct FollowedClock {
+    messages: Receiver<ClockMessage>,
+    running: bool,
+    /// Ticks since Start; the first is beat 0
+    ticks: u64,
+    last_tick: Option<Instant>,
+    tick_length: Option<Duration>,
+}
+
+impl FollowedClock {
+    /// A clock following `messages`, waiting for the next Start
+    pub fn new(messages: Receiver<ClockMessage>) -> Self {
+        Self {
+            messages,
+            running: false,
+            ticks: 0,
+            last_tick: None,
+            tick_length: None,
+        }
+    }
+
+    /// Take in a message; false when it stopped the clock
+    fn receive(&mut self, message: ClockMessage) -> bool {
+        match message {
+            ClockMessage::Start => {
+                self.running = true;
+                self.ticks = 0;
+                self.last_tick = None;
+            }
+            ClockMessage::Continue => self.running = true,
+            ClockMessage::Stop if self.running => {
+                self.running = false;
+                return false;
+            }
+            ClockMessage::Stop => {}
+            ClockMessage::Tick if self.running => {
+                let now = Instant::now();
+                self.tick_length = self.last_tick.map(|last| now - last).or(self.tick_length);
+                self.last_tick = Some(now);
+                self.ticks += 1;
+            }
+            ClockMessage::Tick => {}
+        }
+        true
+    }
+}
+
+impl Clock for FollowedClock {
+    fn wait_until(&mut self, beat: f64) -> bool {
+        loop {
+            let message = match (self.ticks.checked_sub(1), self.last_tick) {
+                (Some(tick), Some(last)) if self.running => {
+                    let ahead = beat * CLOCK_TICKS_PER_BEAT as f64 - tick as f64;
+                    if ahead <= 0.0 {
+                        return true;
+                    }
+                    match self.tick_length.filter(|_| ahead < 1.0) {
+                        // Due before the next tick
+                        Some(length) => {
+                            let due = last + length.mul_f64(ahead);
+                            let Some(wait) = due.checked_duration_since(Instant::now()) else {
+                                return true;
+                            };
+                            match self.messages.recv_timeout(wait) {
+                                Ok(message) => message,
+                                Err(RecvTimeoutError::Timeout) => return true,
+                                Err(RecvTimeoutError::Disconnected) => return false,
+                            }
+                        }
+                        None => match self.messages.recv() {
+                            Ok(message) => message,
+                            Err(_) => return false,
+                        },
+                    }
+                }
+                _ => match self.messages.recv() {
+                    Ok(message) => message,
+                    Err(_) => return false,
+                },
+            };
+            if !self.receive(message) {
+                return false;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    struct Beat(f64);
+
+    impl Timed for Beat {
+        fn beat(&self) -> f64 {
+            self.0
+        }
+    }
+
+    /// A clock that reaches any beat at once, up to a limit
+    struct Instantly(f64);
+
+    impl Clock for Instantly {
+        fn wait_until(&mut self, beat: f64) -> bool {
+            beat <= self.0
+        }
+    }
+
+    #[test]
+    fn test_schedule_loops_until_the_clock_stops() {
+        let events = [Beat(0.0), Beat(1.5)];
+        let mut sent = Vec::new();
+        let finished = schedule(&mut Instantly(7.0), &events, 2.0, true, |event| {
+            sent.push(event.0);
+            Ok::<_, ()>(())
+        });
+        assert_eq!(finished, Ok(false));
+        assert_eq!(sent, vec![0.0, 1.5, 0.0, 1.5, 0.0, 1.5, 0.0]);
+
+        let finished = schedule(
+            &mut Instantly(7.0),
+            &events,
+            2.0,
+            false,
+            |_| Ok::<_, ()>(()),
+        );
+        assert_eq!(finished, Ok(true));
+    }
+
+    #[test]
+    fn test_followed_clock() {
+        let (sender, receiver) = mpsc::channel();
+        let mut clock = FollowedClock::new(receiver);
+        // Ticks before Start don't count
+        sender.send(ClockMessage::Tick).unwrap();
+        sender.send(ClockMessage::Start).unwrap();
+        for _ in 0..=CLOCK_TICKS_PER_BEAT {
+            sender.send(ClockMessage::Tick).unwrap();
+        }
+        assert!(clock.wait_until(0.0));
+        assert!(clock.wait_until(1.0));
+        assert_eq!(clock.ticks, 25);
+
+        sender.send(ClockMessage::Stop).unwrap();
+        assert!(!clock.wait_until(2.0));
+        assert_eq!(ClockMessage::from_midi(&[0xFA]), Some(ClockMessage::Start));
+        assert_eq!(ClockMessage::from_midi(&[0x90, 60, 100]), None);
+    }
+}
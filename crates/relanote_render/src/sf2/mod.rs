@@ -0,0 +1,477 @@
+//! SoundFont (SF2) audio rendering
+//!
+//! Renders a song to audio with the instruments of a user-provided SoundFont.
+//! The song is first laid out with the MIDI renderer (so timing, channels,
+//! drums, pitch bends and controllers match the MIDI output exactly), then the
+//! MIDI events are played through a sample-based synthesizer.
+
+mod soundfont;
+mod voice;
+
+use std::collections::HashMap;
+
+use midly::{MetaMessage, MidiMessage, Smf, Timing, TrackEventKind};
+use relanote_eval::value::SongValue;
+
+use crate::drums::DRUM_CHANNEL;
+use crate::error::RenderError;
+use crate::midi::{MidiConfig, MidiRenderer};
+
+pub use soundfont::{Preset, Sf2Error, SoundFont};
+use voice::Voice;
+
+/// Audio keeps rendering after the last event until voices finish, up to this long
+const MAX_TAIL_SECONDS: f32 = 10.0;
+
+/// SoundFont renderer configuration
+#[derive(Clone)]
+pub struct Sf2Config {
+    /// Output sample rate in Hz
+    pub sample_rate: u32,
+    /// Layout settings shared with the MIDI renderer
+    pub midi: MidiConfig,
+    /// General MIDI program (0-127) for each instrument (part) name
+    pub programs: HashMap<String, u8>,
+    /// Program for instruments not listed in `programs` (0 = Acoustic Grand Piano)
+    pub default_program: u8,
+    /// Master output gain
+    pub gain: f32,
+}
+
+impl Default for Sf2Config {
+    fn default() -> Self {
+        Self {
+            sample_rate: 44_100,
+            midi: MidiConfig::default(),
+            programs: HashMap::new(),
+            default_program: 0,
+            gain: 0.5,
+        }
+    }
+}
+
+/// Per-channel playback state
+struct Channel {
+    /// Channel volume (CC#7) as a gain
+    volume: f32,
+    /// Channel pan (CC#10), -1.0 left to 1.0 right
+    pan: f32,
+    /// Pitch bend as a frequency ratio
+    bend: f64,
+}
+
+impl Default for Channel {
+    fn default() -> Self {
+        Self {
+            volume: (100.0f32 / 127.0).powi(2),
+            pan: 0.0,
+            bend: 1.0,
+        }
+    }
+}
+
+/// A channel event at an absolute tick
+struct Event {
+    tick: u64,
+    channel: u8,
+    message: MidiMessage,
+    /// Preset of the part that sent it (parts in different sections can
+    /// take turns on a channel)
+    preset: Option<usize>,
+}
+
+/// SoundFont audio renderer
+pub struct Sf2Renderer {
+    soundfont: SoundFont,
+    config: Sf2Config,
+}
+
+impl Sf2Renderer {
+    pub fn new(soundfont: SoundFont, config: Sf2Config) -> Self {
+        Self { soundfont, config }
+    }
+
+    /// Render a song to interleaved stereo samples (-1.0 to 1.0)
+    pub fn render(&self, song: &SongValue) -> Result<Vec<f32>, RenderError> {
+        let midi = MidiRenderer::new(self.config.midi.clone()).render(song)?;
+        let smf = Smf::parse(&midi)?;
+
+        let ticks_per_beat = match smf.header.timing {
+            Timing::Metrical(ticks) => ticks.as_int() as f64,
+            Timing::Timecode(..) => self.config.midi.ticks_per_beat as f64,
+        };
+        // Tick of each tempo change and the seconds per tick from there on
+        let mut tempos = vec![(0u64, 60.0 / self.config.midi.tempo as f64 / ticks_per_beat)];
+
+        // Gather channel events from all tracks; each part track picks the
+        // preset for its channels from the track (instrument) name
+        let mut channels: Vec<Channel> = (0..16).map(|_| Channel::default()).collect();
+        let mut events = Vec::new();
+        for track in &smf.tracks {
+            let mut tick = 0u64;
+            let mut name = None;
+            let mut presets = HashMap::new();
+            for event in track {
+                tick += event.delta.as_int() as u64;
+                match event.kind {
+                    TrackEventKind::Meta(MetaMessage::TrackName(bytes)) => {
+                        name = Some(String::from_utf8_lossy(bytes).to_string())
+                    }
+                    TrackEventKind::Meta(MetaMessage::Tempo(tempo)) => {
+                        tempos.push((tick, tempo.as_int() as f64 / 1_000_000.0 / ticks_per_beat))
+                    }
+                    TrackEventKind::Midi { channel, message } => {
+                        let channel = channel.as_int();
+                        let preset = *presets
+                            .entry(channel)
+                            .or_insert_with(|| self.preset_for(channel, name.as_deref()));
+                        events.push(Event {
+                            tick,
+                            channel,
+                            message,
+                            preset,
+                        });
+                    }
+                    _ => {}
+                }
+            }
+        }
+        // Stable sort keeps each track's own ordering (note-offs before note-ons)
+        events.sort_by_key(|event| event.tick);
+        tempos.sort_by_key(|&(tick, _)| tick);
+
+        let sample_rate = self.config.sample_rate;
+        let mut output = Vec::new();
+        let mut voices: Vec<Voice> = Vec::new();
+        for event in &events {
+            let frame = (seconds_at(&tempos, event.tick) * sample_rate as f64).round() as usize;
+            self.render_until(&mut output, &mut voices, &channels, frame);
+            self.handle_event(&mut voices, &mut channels, event);
+        }
+
+        // Let released voices ring out
+        let tail_end = output.len() / 2 + (MAX_TAIL_SECONDS * sample_rate as f32) as usize;
+        while !voices.is_empty() && output.len() / 2 < tail_end {
+            let frame = (output.len() / 2 + sample_rate as usize / 100).min(tail_end);
+            self.render_until(&mut output, &mut voices, &channels, frame);
+        }
+
+        Ok(output)
+    }
+
+    /// Render a song to a 16-bit stereo WAV file
+    pub fn render_wav(&self, song: &SongValue) -> Result<Vec<u8>, RenderError> {
+        Ok(encode_wav(&self.render(song)?, self.config.sample_rate))
+    }
+
+    /// Preset index for a channel: drums use the percussion bank (128)
+    fn preset_for(&self, channel: u8, instrument: Option<&str>) -> Option<usize> {
+        let presets = self.soundfont.presets();
+        let find = |bank: u16, program: u16| {
+            presets
+                .iter()
+                .position(|p| p.bank == bank && p.program == program)
+        };
+
+        if channel == DRUM_CHANNEL {
+            return find(128, 0)
+                .or_else(|| presets.iter().position(|p| p.bank == 128))
+                .or_else(|| find(0, 0));
+        }
+
+        let program = instrument
+            .and_then(|name| self.config.programs.get(name))
+            .copied()
+            .unwrap_or(self.config.default_program);
+        find(0, program as u16)
+            .or_else(|| presets.iter().position(|p| p.program == program as u16))
+            .or_else(|| (!presets.is_empty()).then_some(0))
+    }
+
+    fn handle_event(&self, voices: &mut Vec<Voice>, channels: &mut [Channel], event: &Event) {
+        let channel = event.channel;
+        let state = &mut channels[channel as usize];
+        match event.message {
+            MidiMessage::NoteOn { key, vel } if vel.as_int() > 0 => {
+                let Some(preset) = event.preset.map(|i| &self.soundfont.presets()[i]) else {
+                    return;
+                };
+                let (key, vel) = (key.as_int(), vel.as_int());
+                for region in self.soundfont.regions(preset, key, vel) {
+                    voices.push(Voice::new(
+                        &region,
+                        channel,
+                        key,
+                        vel,
+                        self.config.sample_rate,
+                    ));
+                }
+            }
+            MidiMessage::NoteOn { key, .. } | MidiMessage::NoteOff { key, .. } => {
+                for voice in voices
+                    .iter_mut()
+                    .filter(|v| v.channel == channel && v.key == key.as_int() && !v.released)
+                {
+                    voice.release();
+                }
+            }
+            MidiMessage::Controller { controller, value } => match controller.as_int() {
+                7 => state.volume = (value.as_int() as f32 / 127.0).powi(2),
+                10 => state.pan = (value.as_int() as f32 - 64.0) / 63.0,
+                _ => {}
+            },
+            MidiMessage::PitchBend { bend } => {
+                let semitones = bend.as_f64() * self.config.midi.pitch_bend_range;
+                state.bend = 2f64.powf(semitones / 12.0);
+            }
+            _ => {}
+        }
+    }
+
+    /// Mix all voices into `output` up to (not including) `frame`
+    fn render_until(
+        &self,
+        output: &mut Vec<f32>,
+        voices: &mut Vec<Voice>,
+        channels: &[Channel],
+        frame: usize,
+    ) {
+        let dt = 1.0 / self.config.sample_rate as f32;
+        let samples = &self.soundfont.samples;
+        while output.len() / 2 < frame {
+            let (mut left, mut right) = (0.0, 0.0);
+            for voice in voices.iter_mut() {
+                let channel = &channels[voice.channel as usize];
+                let value = voice.next(samples, channel.bend, dt) * channel.volume;
+                // Constant-power pan
+                let pan = (voice.pan + channel.pan).clamp(-1.0, 1.0);
+                let angle = (pan + 1.0) * std::f32::consts::FRAC_PI_4;
+                left += value * angle.cos();
+                right += value * angle.sin();
+            }
+            output.push((left * self.config.gain).clamp(-1.0, 1.0));
+            output.push((right * self.config.gain).clamp(-1.0, 1.0));
+            voices.retain(|voice| !voice.is_finished());
+        }
+    }
+}
+
+/// Seconds from the start of the song to `tick`, given the tick of each
+/// tempo change (in order) and the seconds per tick from there on
+fn seconds_at(tempos: &[(u64, f64)], tick: u64) -> f64 {
+    let mut seconds = 0.0;
+    for (index, &(from, seconds_per_tick)) in tempos.iter().enumerate() {
+        if from >= tick {
+            break;
+        }
+        let until = tempos
+            .get(index + 1)
+            .map_or(tick, |&(next, _)| next.min(tick));
+        seconds += (until - from) as f64 * seconds_per_tick;
+    }
+    seconds
+}
+
+/// Encode interleaved stereo samples as a 16-bit PCM WAV file
+pub fn encode_wav(samples: &[f32], sample_rate: u32) -> Vec<u8> {
+    let channels: u16 = 2;
+    let bits_per_sample: u16 = 16;
+    let block_align = channels * bits_per_sample / 8;
+    let data_size = (samples.len() * 2) as u32;
+
+    let mut wav = Vec::with_capacity(44 + data_size as usize);
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_size).to_le_bytes());
+    wav.extend_from_slice(b"WAVE");
+    wav.extend_from_slice(b"fmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    wav.extend_from_slice(&channels.to_le_bytes());
+    wav.extend_from_slice(&sample_rate.to_le_bytes());
+    wav.extend_from_slice(&(sample_rate * block_align as u32).to_le_bytes());
+    wav.extend_from_slice(&block_align.to_le_bytes());
+    wav.extend_from_slice(&bits_per_sample.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_size.to_le_bytes());
+    for sample in samples {
+        let value = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+        wav.extend_from_slice(&value.to_le_bytes());
+    }
+    wav
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use relanote_eval::value::{
+        BlockValue, IntervalValue, NoteMeta, PartValue, SectionValue, SlotValue,
+    };
+
+    fn chunk(id: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut bytes = id.to_vec();
+        bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(data);
+        if data.len() % 2 == 1 {
+            bytes.push(0);
+        }
+        bytes
+    }
+
+    fn list(kind: &[u8; 4], chunks: &[Vec<u8>]) -> Vec<u8> {
+        let mut data = kind.to_vec();
+        for c in chunks {
+            data.extend_from_slice(c);
+        }
+        chunk(b"LIST", &data)
+    }
+
+    fn name(text: &str) -> Vec<u8> {
+        let mut bytes = text.as_bytes().to_vec();
+        bytes.resize(20, 0);
+        bytes
+    }
+
+    /// A one-preset SoundFont playing a looped square wave rooted at middle C
+    fn test_soundfont() -> Vec<u8> {
+        let samples: Vec<u8> = (0..200)
+            .flat_map(|i| (if i % 20 < 10 { 16384i16 } else { -16384 }).to_le_bytes())
+            .collect();
+
+        let mut phdr = Vec::new();
+        for (preset, bag) in [("Test", 0u16), ("EOP", 1)] {
+            phdr.extend(name(preset));
+            phdr.extend([0u16, 0, bag].iter().flat_map(|v| v.to_le_bytes()));
+            phdr.extend([0u8; 12]);
+        }
+        let mut inst = Vec::new();
+        for (instrument, bag) in [("Square", 0u16), ("EOI", 1)] {
+            inst.extend(name(instrument));
+            inst.extend(bag.to_le_bytes());
+        }
+        let mut shdr = Vec::new();
+        for (sample, end) in [("Square", 200u32), ("EOS", 0)] {
+            shdr.extend(name(sample));
+            shdr.extend(
+                [0, end, 0, end, 44_100]
+                    .iter()
+                    .flat_map(|v| v.to_le_bytes()),
+            );
+            shdr.extend([60u8, 0, 0, 0, 1, 0]);
+        }
+        let gens = |records: &[(u16, i16)]| -> Vec<u8> {
+            records
+                .iter()
+                .flat_map(|(op, amount)| [op.to_le_bytes(), amount.to_le_bytes()].concat())
+                .collect()
+        };
+        let bags = |indices: &[u16]| -> Vec<u8> {
+            indices
+                .iter()
+                .flat_map(|i| [i.to_le_bytes(), 0u16.to_le_bytes()].concat())
+                .collect()
+        };
+
+        let body = [
+            b"sfbk".to_vec(),
+            list(b"INFO", &[chunk(b"ifil", &[2, 0, 4, 0])]),
+            list(b"sdta", &[chunk(b"smpl", &samples)]),
+            list(
+                b"pdta",
+                &[
+                    chunk(b"phdr", &phdr),
+                    chunk(b"pbag", &bags(&[0, 1])),
+                    chunk(b"pgen", &gens(&[(41, 0), (0, 0)])),
+                    chunk(b"inst", &inst),
+                    chunk(b"ibag", &bags(&[0, 2])),
+                    chunk(b"igen", &gens(&[(54, 1), (53, 0), (0, 0)])),
+                    chunk(b"shdr", &shdr),
+                ],
+            ),
+        ]
+        .concat();
+        chunk(b"RIFF", &body)
+    }
+
+    fn song_with_note(semitones: i32) -> SongValue {
+        SongValue::new(vec![section_with_note(semitones)])
+    }
+
+    fn section_with_note(semitones: i32) -> SectionValue {
+        SectionValue::new(
+            "Test",
+            vec![PartValue::new(
+                "Test",
+                vec![BlockValue::new(vec![SlotValue::Note {
+                    interval: IntervalValue::from_semitones(semitones),
+                    articulations: vec![],
+                    duration_beats: None,
+                    meta: NoteMeta::default(),
+                }])],
+            )],
+        )
+    }
+
+    #[test]
+    fn test_parse_soundfont() {
+        let soundfont = SoundFont::parse(&test_soundfont()).unwrap();
+        assert_eq!(soundfont.presets().len(), 1);
+        assert_eq!(soundfont.presets()[0].name, "Test");
+        assert!(soundfont.preset(0, 0).is_some());
+
+        assert!(matches!(
+            SoundFont::parse(b"RIFF\x04\x00\x00\x00WAVE"),
+            Err(Sf2Error::NotSoundFont)
+        ));
+    }
+
+    #[test]
+    fn test_render_note() {
+        let soundfont = SoundFont::parse(&test_soundfont()).unwrap();
+        let renderer = Sf2Renderer::new(soundfont, Sf2Config::default());
+        let audio = renderer.render(&song_with_note(0)).unwrap();
+
+        // One beat at 120 BPM: half a second of stereo audio, then a short release
+        let frames = audio.len() / 2;
+        assert!((22_050..23_000).contains(&frames), "{} frames", frames);
+        let peak = audio[..40_000]
+            .iter()
+            .fold(0.0f32, |peak, s| peak.max(s.abs()));
+        assert!(peak > 0.05);
+
+        // Middle C plays the sample at its own rate: a 20-sample period
+        let left: Vec<f32> = audio.iter().step_by(2).copied().collect();
+        let crossings = left[1000..3000]
+            .windows(2)
+            .filter(|w| w[0] <= 0.0 && w[1] > 0.0)
+            .count();
+        assert!((99..=101).contains(&crossings), "{} crossings", crossings);
+
+        let wav = renderer.render_wav(&song_with_note(12)).unwrap();
+        assert_eq!(&wav[0..4], b"RIFF");
+        assert_eq!(&wav[8..12], b"WAVE");
+    }
+
+    #[test]
+    fn test_tempo_changes() {
+        let soundfont = SoundFont::parse(&test_soundfont()).unwrap();
+        let renderer = Sf2Renderer::new(soundfont, Sf2Config::default());
+        let mut slow = section_with_note(0);
+        slow.tempo = Some(60.0);
+        let audio = renderer
+            .render(&SongValue::new(vec![section_with_note(0), slow]))
+            .unwrap();
+
+        // The first bar takes 2 seconds at 120 BPM, and the slow section's
+        // one-beat note a second more
+        let second_note = audio
+            .iter()
+            .step_by(2)
+            .skip(44_100)
+            .position(|s| s.abs() > 0.01)
+            .unwrap()
+            + 44_100;
+        assert!((88_000..88_500).contains(&second_note), "{}", second_note);
+        let frames = audio.len() / 2;
+        assert!((132_300..133_500).contains(&frames), "{} frames", frames);
+    }
+}
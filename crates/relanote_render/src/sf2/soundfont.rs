@@ -0,0 +1,430 @@
+//! SoundFont 2 file parsing
+
+use thiserror::Error;
+
+/// Errors reading a SoundFont file
+#[derive(Debug, Error)]
+pub enum Sf2Error {
+    #[error("not a SoundFont 2 file")]
+    NotSoundFont,
+
+    #[error("missing '{0}' chunk")]
+    MissingChunk(&'static str),
+
+    #[error("malformed '{0}' chunk")]
+    Malformed(&'static str),
+}
+
+// Generator operators used by the renderer (SoundFont 2.04, section 8.1.2)
+pub(crate) const GEN_START_ADDRS_OFFSET: usize = 0;
+pub(crate) const GEN_END_ADDRS_OFFSET: usize = 1;
+pub(crate) const GEN_STARTLOOP_ADDRS_OFFSET: usize = 2;
+pub(crate) const GEN_ENDLOOP_ADDRS_OFFSET: usize = 3;
+pub(crate) const GEN_START_ADDRS_COARSE_OFFSET: usize = 4;
+pub(crate) const GEN_END_ADDRS_COARSE_OFFSET: usize = 12;
+pub(crate) const GEN_PAN: usize = 17;
+pub(crate) const GEN_DELAY_VOL_ENV: usize = 33;
+pub(crate) const GEN_ATTACK_VOL_ENV: usize = 34;
+pub(crate) const GEN_HOLD_VOL_ENV: usize = 35;
+pub(crate) const GEN_DECAY_VOL_ENV: usize = 36;
+pub(crate) const GEN_SUSTAIN_VOL_ENV: usize = 37;
+pub(crate) const GEN_RELEASE_VOL_ENV: usize = 38;
+pub(crate) const GEN_INSTRUMENT: usize = 41;
+pub(crate) const GEN_KEY_RANGE: usize = 43;
+pub(crate) const GEN_VEL_RANGE: usize = 44;
+pub(crate) const GEN_STARTLOOP_ADDRS_COARSE_OFFSET: usize = 45;
+pub(crate) const GEN_INITIAL_ATTENUATION: usize = 48;
+pub(crate) const GEN_ENDLOOP_ADDRS_COARSE_OFFSET: usize = 50;
+pub(crate) const GEN_COARSE_TUNE: usize = 51;
+pub(crate) const GEN_FINE_TUNE: usize = 52;
+pub(crate) const GEN_SAMPLE_ID: usize = 53;
+pub(crate) const GEN_SAMPLE_MODES: usize = 54;
+pub(crate) const GEN_SCALE_TUNING: usize = 56;
+pub(crate) const GEN_OVERRIDING_ROOT_KEY: usize = 58;
+
+/// Number of generator operators defined by the specification
+const GEN_COUNT: usize = 61;
+
+/// Generators that only make sense at instrument level (never offset by presets)
+const INSTRUMENT_ONLY: [usize; 13] = [
+    GEN_START_ADDRS_OFFSET,
+    GEN_END_ADDRS_OFFSET,
+    GEN_STARTLOOP_ADDRS_OFFSET,
+    GEN_ENDLOOP_ADDRS_OFFSET,
+    GEN_START_ADDRS_COARSE_OFFSET,
+    GEN_END_ADDRS_COARSE_OFFSET,
+    GEN_STARTLOOP_ADDRS_COARSE_OFFSET,
+    GEN_ENDLOOP_ADDRS_COARSE_OFFSET,
+    GEN_KEY_RANGE,
+    GEN_VEL_RANGE,
+    GEN_SAMPLE_ID,
+    GEN_SAMPLE_MODES,
+    GEN_OVERRIDING_ROOT_KEY,
+];
+
+/// Default generator value when no zone sets it
+fn default_gen(op: usize) -> i32 {
+    match op {
+        GEN_DELAY_VOL_ENV | GEN_ATTACK_VOL_ENV | GEN_HOLD_VOL_ENV | GEN_DECAY_VOL_ENV
+        | GEN_RELEASE_VOL_ENV => -12000,
+        GEN_SCALE_TUNING => 100,
+        GEN_OVERRIDING_ROOT_KEY => -1,
+        _ => 0,
+    }
+}
+
+/// A preset or instrument zone: generator values plus key/velocity ranges
+#[derive(Clone, Debug)]
+pub(crate) struct Zone {
+    gens: [Option<i16>; GEN_COUNT],
+    key_range: (u8, u8),
+    vel_range: (u8, u8),
+}
+
+impl Default for Zone {
+    fn default() -> Self {
+        Self {
+            gens: [None; GEN_COUNT],
+            key_range: (0, 127),
+            vel_range: (0, 127),
+        }
+    }
+}
+
+impl Zone {
+    fn get(&self, op: usize) -> Option<i16> {
+        self.gens.get(op).copied().flatten()
+    }
+
+    fn contains(&self, key: u8, velocity: u8) -> bool {
+        (self.key_range.0..=self.key_range.1).contains(&key)
+            && (self.vel_range.0..=self.vel_range.1).contains(&velocity)
+    }
+
+    /// Index of the instrument (preset zones) or sample (instrument zones)
+    fn link(&self, op: usize) -> Option<usize> {
+        self.get(op).map(|value| value as u16 as usize)
+    }
+}
+
+/// Zones of a preset or instrument, with the optional global zone split out
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Zones {
+    global: Zone,
+    zones: Vec<Zone>,
+}
+
+/// A playable preset (bank and program number)
+#[derive(Clone, Debug)]
+pub struct Preset {
+    pub name: String,
+    pub bank: u16,
+    pub program: u16,
+    zones: Zones,
+}
+
+#[derive(Clone, Debug)]
+struct Instrument {
+    zones: Zones,
+}
+
+/// Sample header: where a sample lives in the sample data and how to tune it
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct SampleHeader {
+    pub start: u32,
+    pub end: u32,
+    pub loop_start: u32,
+    pub loop_end: u32,
+    pub sample_rate: u32,
+    pub original_pitch: u8,
+    pub pitch_correction: i8,
+}
+
+/// A sample with the generator values that apply to one note
+#[derive(Clone, Debug)]
+pub(crate) struct Region {
+    pub sample: SampleHeader,
+    gens: [i32; GEN_COUNT],
+}
+
+impl Region {
+    pub(crate) fn gen(&self, op: usize) -> i32 {
+        self.gens[op]
+    }
+}
+
+/// A parsed SoundFont 2 bank
+#[derive(Clone, Debug)]
+pub struct SoundFont {
+    presets: Vec<Preset>,
+    instruments: Vec<Instrument>,
+    sample_headers: Vec<SampleHeader>,
+    /// Sample data (all samples, normalized to -1.0..1.0)
+    pub(crate) samples: Vec<f32>,
+}
+
+impl SoundFont {
+    /// Parse a SoundFont 2 (.sf2) file
+    pub fn parse(bytes: &[u8]) -> Result<Self, Sf2Error> {
+        if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"sfbk" {
+            return Err(Sf2Error::NotSoundFont);
+        }
+        let riff_size = read_u32(bytes, 4) as usize;
+        let body = &bytes[12..(8 + riff_size).min(bytes.len())];
+
+        let mut sdta = None;
+        let mut pdta = None;
+        for (id, data) in read_chunks(body)? {
+            if &id == b"LIST" && data.len() >= 4 {
+                match &data[0..4] {
+                    b"sdta" => sdta = Some(&data[4..]),
+                    b"pdta" => pdta = Some(&data[4..]),
+                    _ => {}
+                }
+            }
+        }
+        let sdta = sdta.ok_or(Sf2Error::MissingChunk("sdta"))?;
+        let pdta = pdta.ok_or(Sf2Error::MissingChunk("pdta"))?;
+
+        let samples = read_chunks(sdta)?
+            .into_iter()
+            .find(|(id, _)| id == b"smpl")
+            .map(|(_, data)| {
+                data.chunks_exact(2)
+                    .map(|pair| i16::from_le_bytes([pair[0], pair[1]]) as f32 / 32768.0)
+                    .collect()
+            })
+            .ok_or(Sf2Error::MissingChunk("smpl"))?;
+
+        let pdta_chunks = read_chunks(pdta)?;
+        let chunk = |name: &'static str| {
+            pdta_chunks
+                .iter()
+                .find(|(id, _)| id == name.as_bytes())
+                .map(|(_, data)| *data)
+                .ok_or(Sf2Error::MissingChunk(name))
+        };
+
+        let pgen = read_generators(chunk("pgen")?, "pgen")?;
+        let pbag = read_bags(chunk("pbag")?, "pbag")?;
+        let igen = read_generators(chunk("igen")?, "igen")?;
+        let ibag = read_bags(chunk("ibag")?, "ibag")?;
+
+        // Preset headers (38 bytes each, the last one is the terminal "EOP" record)
+        let phdr = records(chunk("phdr")?, 38, "phdr")?;
+        let mut presets = Vec::new();
+        for pair in phdr.windows(2) {
+            let (header, next) = (pair[0], pair[1]);
+            presets.push(Preset {
+                name: read_name(&header[0..20]),
+                program: read_u16(header, 20),
+                bank: read_u16(header, 22),
+                zones: read_zones(
+                    &pbag,
+                    &pgen,
+                    read_u16(header, 24) as usize..read_u16(next, 24) as usize,
+                    GEN_INSTRUMENT,
+                    "pbag",
+                )?,
+            });
+        }
+
+        // Instruments (22 bytes each, terminated by "EOI")
+        let inst = records(chunk("inst")?, 22, "inst")?;
+        let mut instruments = Vec::new();
+        for pair in inst.windows(2) {
+            instruments.push(Instrument {
+                zones: read_zones(
+                    &ibag,
+                    &igen,
+                    read_u16(pair[0], 20) as usize..read_u16(pair[1], 20) as usize,
+                    GEN_SAMPLE_ID,
+                    "ibag",
+                )?,
+            });
+        }
+
+        // Sample headers (46 bytes each, terminated by "EOS")
+        let shdr = records(chunk("shdr")?, 46, "shdr")?;
+        let sample_headers = shdr[..shdr.len().saturating_sub(1)]
+            .iter()
+            .map(|header| SampleHeader {
+                start: read_u32(header, 20),
+                end: read_u32(header, 24),
+                loop_start: read_u32(header, 28),
+                loop_end: read_u32(header, 32),
+                sample_rate: read_u32(header, 36),
+                original_pitch: header[40],
+                pitch_correction: header[41] as i8,
+            })
+            .collect();
+
+        Ok(Self {
+            presets,
+            instruments,
+            sample_headers,
+            samples,
+        })
+    }
+
+    /// All presets in the bank
+    pub fn presets(&self) -> &[Preset] {
+        &self.presets
+    }
+
+    /// Find a preset by bank and program number
+    pub fn preset(&self, bank: u16, program: u16) -> Option<&Preset> {
+        self.presets
+            .iter()
+            .find(|p| p.bank == bank && p.program == program)
+    }
+
+    /// Samples (with resolved generators) that sound for a key and velocity
+    pub(crate) fn regions(&self, preset: &Preset, key: u8, velocity: u8) -> Vec<Region> {
+        let mut regions = Vec::new();
+        for preset_zone in preset
+            .zones
+            .zones
+            .iter()
+            .filter(|z| z.contains(key, velocity))
+        {
+            let Some(instrument) = preset_zone
+                .link(GEN_INSTRUMENT)
+                .and_then(|index| self.instruments.get(index))
+            else {
+                continue;
+            };
+
+            for zone in instrument
+                .zones
+                .zones
+                .iter()
+                .filter(|z| z.contains(key, velocity))
+            {
+                let Some(sample) = zone
+                    .link(GEN_SAMPLE_ID)
+                    .and_then(|index| self.sample_headers.get(index))
+                else {
+                    continue;
+                };
+
+                // Instrument values replace defaults; preset values are offsets
+                let mut gens = [0; GEN_COUNT];
+                for (op, value) in gens.iter_mut().enumerate() {
+                    *value = zone
+                        .get(op)
+                        .or(instrument.zones.global.get(op))
+                        .map(i32::from)
+                        .unwrap_or_else(|| default_gen(op));
+                    if !INSTRUMENT_ONLY.contains(&op) && op != GEN_INSTRUMENT {
+                        *value += preset_zone
+                            .get(op)
+                            .or(preset.zones.global.get(op))
+                            .map(i32::from)
+                            .unwrap_or(0);
+                    }
+                }
+
+                regions.push(Region {
+                    sample: *sample,
+                    gens,
+                });
+            }
+        }
+        regions
+    }
+}
+
+fn read_u16(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        data[offset],
+        data[offset + 1],
+        data[offset + 2],
+        data[offset + 3],
+    ])
+}
+
+fn read_name(data: &[u8]) -> String {
+    let end = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+    String::from_utf8_lossy(&data[..end]).trim().to_string()
+}
+
+/// A RIFF chunk: four-character id and its data
+type Chunk<'a> = ([u8; 4], &'a [u8]);
+
+/// Split RIFF data into chunks
+fn read_chunks(mut data: &[u8]) -> Result<Vec<Chunk<'_>>, Sf2Error> {
+    let mut chunks = Vec::new();
+    while data.len() >= 8 {
+        let id = [data[0], data[1], data[2], data[3]];
+        let size = read_u32(data, 4) as usize;
+        let body = data.get(8..8 + size).ok_or(Sf2Error::Malformed("RIFF"))?;
+        chunks.push((id, body));
+        // Chunks are padded to an even size
+        data = data.get(8 + size + size % 2..).unwrap_or(&[]);
+    }
+    Ok(chunks)
+}
+
+/// Split a chunk into fixed-size records (at least the terminal record)
+fn records<'a>(data: &'a [u8], size: usize, name: &'static str) -> Result<Vec<&'a [u8]>, Sf2Error> {
+    let records = data.chunks_exact(size);
+    if data.is_empty() || !records.remainder().is_empty() {
+        return Err(Sf2Error::Malformed(name));
+    }
+    Ok(records.collect())
+}
+
+/// Generator records as (operator, amount)
+fn read_generators(data: &[u8], name: &'static str) -> Result<Vec<(u16, [u8; 2])>, Sf2Error> {
+    Ok(records(data, 4, name)?
+        .into_iter()
+        .map(|record| (read_u16(record, 0), [record[2], record[3]]))
+        .collect())
+}
+
+/// Bag records as the index of their first generator
+fn read_bags(data: &[u8], name: &'static str) -> Result<Vec<usize>, Sf2Error> {
+    Ok(records(data, 4, name)?
+        .into_iter()
+        .map(|record| read_u16(record, 0) as usize)
+        .collect())
+}
+
+/// Build the zones of one preset or instrument from its range of bags
+fn read_zones(
+    bags: &[usize],
+    gens: &[(u16, [u8; 2])],
+    bag_range: std::ops::Range<usize>,
+    link_op: usize,
+    name: &'static str,
+) -> Result<Zones, Sf2Error> {
+    let mut zones = Zones::default();
+    for bag in bag_range {
+        let (Some(&first), Some(&last)) = (bags.get(bag), bags.get(bag + 1)) else {
+            return Err(Sf2Error::Malformed(name));
+        };
+        let mut zone = Zone::default();
+        for &(op, amount) in gens.get(first..last).ok_or(Sf2Error::Malformed(name))? {
+            let op = op as usize;
+            match op {
+                GEN_KEY_RANGE => zone.key_range = (amount[0], amount[1]),
+                GEN_VEL_RANGE => zone.vel_range = (amount[0], amount[1]),
+                _ if op < GEN_COUNT => zone.gens[op] = Some(i16::from_le_bytes(amount)),
+                _ => {}
+            }
+        }
+
+        // A first zone without an instrument/sample link is the global zone
+        if zone.link(link_op).is_some() {
+            zones.zones.push(zone);
+        } else if zones.zones.is_empty() {
+            zones.global = zone;
+        }
+    }
+    Ok(zones)
+}
@@ -0,0 +1,235 @@
+//! A single sounding sample (one region of one note)
+
+use super::soundfont::*;
+
+/// Convert timecents to seconds
+fn timecents_to_seconds(timecents: i32) -> f32 {
+    2f32.powf(timecents as f32 / 1200.0)
+}
+
+/// Convert an attenuation in centibels to a linear gain
+fn centibels_to_gain(centibels: i32) -> f32 {
+    10f32.powf(-(centibels.max(0) as f32) / 200.0)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Stage {
+    Delay,
+    Attack,
+    Hold,
+    Decay,
+    Sustain,
+    Release,
+    Done,
+}
+
+/// DAHDSR volume envelope (linear segments)
+struct Envelope {
+    stage: Stage,
+    /// Seconds spent in the current stage
+    elapsed: f32,
+    level: f32,
+    release_from: f32,
+    delay: f32,
+    attack: f32,
+    hold: f32,
+    decay: f32,
+    sustain: f32,
+    release: f32,
+}
+
+impl Envelope {
+    fn new(region: &Region) -> Self {
+        Self {
+            stage: Stage::Delay,
+            elapsed: 0.0,
+            level: 0.0,
+            release_from: 0.0,
+            delay: timecents_to_seconds(region.gen(GEN_DELAY_VOL_ENV)),
+            attack: timecents_to_seconds(region.gen(GEN_ATTACK_VOL_ENV)),
+            hold: timecents_to_seconds(region.gen(GEN_HOLD_VOL_ENV)),
+            decay: timecents_to_seconds(region.gen(GEN_DECAY_VOL_ENV)),
+            sustain: centibels_to_gain(region.gen(GEN_SUSTAIN_VOL_ENV)),
+            release: timecents_to_seconds(region.gen(GEN_RELEASE_VOL_ENV)),
+        }
+    }
+
+    fn release(&mut self) {
+        if self.stage != Stage::Done {
+            self.release_from = self.level;
+            self.stage = Stage::Release;
+            self.elapsed = 0.0;
+        }
+    }
+
+    /// Advance by `dt` seconds and return the current level
+    fn next(&mut self, dt: f32) -> f32 {
+        self.elapsed += dt;
+        let progress = |length: f32, elapsed: f32| (elapsed / length.max(f32::EPSILON)).min(1.0);
+        self.level = match self.stage {
+            Stage::Delay => 0.0,
+            Stage::Attack => progress(self.attack, self.elapsed),
+            Stage::Hold => 1.0,
+            Stage::Decay => 1.0 - (1.0 - self.sustain) * progress(self.decay, self.elapsed),
+            Stage::Sustain => self.sustain,
+            Stage::Release => self.release_from * (1.0 - progress(self.release, self.elapsed)),
+            Stage::Done => 0.0,
+        };
+
+        let length = match self.stage {
+            Stage::Delay => self.delay,
+            Stage::Attack => self.attack,
+            Stage::Hold => self.hold,
+            Stage::Decay => self.decay,
+            Stage::Release => self.release,
+            Stage::Sustain | Stage::Done => f32::INFINITY,
+        };
+        if self.elapsed >= length {
+            self.elapsed = 0.0;
+            self.stage = match self.stage {
+                Stage::Delay => Stage::Attack,
+                Stage::Attack => Stage::Hold,
+                Stage::Hold => Stage::Decay,
+                Stage::Decay => Stage::Sustain,
+                Stage::Release => Stage::Done,
+                stage => stage,
+            };
+        }
+        self.level
+    }
+}
+
+/// Loop behavior (sampleModes generator)
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum LoopMode {
+    None,
+    Continuous,
+    UntilRelease,
+}
+
+/// A playing sample
+pub(crate) struct Voice {
+    pub channel: u8,
+    pub key: u8,
+    pub released: bool,
+    position: f64,
+    /// Playback step per output sample before pitch bend
+    step: f64,
+    end: f64,
+    loop_start: f64,
+    loop_end: f64,
+    loop_mode: LoopMode,
+    gain: f32,
+    /// Region pan (-1.0 left to 1.0 right)
+    pub pan: f32,
+    envelope: Envelope,
+}
+
+impl Voice {
+    pub(crate) fn new(
+        region: &Region,
+        channel: u8,
+        key: u8,
+        velocity: u8,
+        output_rate: u32,
+    ) -> Self {
+        let sample = &region.sample;
+        let offset =
+            |fine: usize, coarse: usize| (region.gen(fine) + region.gen(coarse) * 32768) as i64;
+        let address = |base: u32, offset: i64| (base as i64 + offset).max(0) as f64;
+
+        // Pitch relative to the sample's root key, in cents
+        let root = match region.gen(GEN_OVERRIDING_ROOT_KEY) {
+            root @ 0..=127 => root,
+            _ => sample.original_pitch as i32,
+        };
+        let cents = (key as i32 - root) * region.gen(GEN_SCALE_TUNING)
+            + region.gen(GEN_COARSE_TUNE) * 100
+            + region.gen(GEN_FINE_TUNE)
+            + sample.pitch_correction as i32;
+        let step =
+            2f64.powf(cents as f64 / 1200.0) * sample.sample_rate as f64 / output_rate as f64;
+
+        let loop_mode = match region.gen(GEN_SAMPLE_MODES) & 3 {
+            1 => LoopMode::Continuous,
+            3 => LoopMode::UntilRelease,
+            _ => LoopMode::None,
+        };
+
+        let velocity = velocity as f32 / 127.0;
+        Self {
+            channel,
+            key,
+            released: false,
+            position: address(
+                sample.start,
+                offset(GEN_START_ADDRS_OFFSET, GEN_START_ADDRS_COARSE_OFFSET),
+            ),
+            step,
+            end: address(
+                sample.end,
+                offset(GEN_END_ADDRS_OFFSET, GEN_END_ADDRS_COARSE_OFFSET),
+            ),
+            loop_start: address(
+                sample.loop_start,
+                offset(
+                    GEN_STARTLOOP_ADDRS_OFFSET,
+                    GEN_STARTLOOP_ADDRS_COARSE_OFFSET,
+                ),
+            ),
+            loop_end: address(
+                sample.loop_end,
+                offset(GEN_ENDLOOP_ADDRS_OFFSET, GEN_ENDLOOP_ADDRS_COARSE_OFFSET),
+            ),
+            loop_mode,
+            gain: velocity * velocity * centibels_to_gain(region.gen(GEN_INITIAL_ATTENUATION)),
+            pan: (region.gen(GEN_PAN) as f32 / 500.0).clamp(-1.0, 1.0),
+            envelope: Envelope::new(region),
+        }
+    }
+
+    pub(crate) fn release(&mut self) {
+        self.released = true;
+        self.envelope.release();
+    }
+
+    pub(crate) fn is_finished(&self) -> bool {
+        self.envelope.stage == Stage::Done
+    }
+
+    fn is_looping(&self) -> bool {
+        self.loop_end > self.loop_start
+            && match self.loop_mode {
+                LoopMode::Continuous => true,
+                LoopMode::UntilRelease => !self.released,
+                LoopMode::None => false,
+            }
+    }
+
+    /// Next output sample (mono); `bend` is the pitch ratio from the channel's pitch bend
+    pub(crate) fn next(&mut self, samples: &[f32], bend: f64, dt: f32) -> f32 {
+        if self.is_finished() {
+            return 0.0;
+        }
+
+        let looping = self.is_looping();
+        let index = self.position as usize;
+        let frac = (self.position - index as f64) as f32;
+        let next_index = if looping && index + 1 >= self.loop_end as usize {
+            self.loop_start as usize
+        } else {
+            index + 1
+        };
+        let at = |i: usize| samples.get(i).copied().unwrap_or(0.0);
+        let value = at(index) * (1.0 - frac) + at(next_index) * frac;
+
+        self.position += self.step * bend;
+        if looping && self.position >= self.loop_end {
+            self.position -= self.loop_end - self.loop_start;
+        } else if self.position >= self.end {
+            self.envelope.stage = Stage::Done;
+        }
+
+        value * self.gain * self.envelope.next(dt)
+    }
+}
@@ -0,0 +1,57 @@
+//! Song timeline: where each section starts
+
+/// Start offsets (in ticks) of the sections of a song
+pub(crate) struct Timeline {
+    section_starts: Vec<u32>,
+    /// Where the song ends, or None when it is too long to count in ticks
+    end: Option<u32>,
+}
+
+impl Timeline {
+    /// Lay sections out one after another, each starting on a bar line.
+    /// With `parallel` set, every section starts at the beginning of the song.
+    pub(crate) fn new(section_lengths: &[u32], bar_ticks: u32, parallel: bool) -> Self {
+        let mut section_starts = Vec::with_capacity(section_lengths.len());
+        let mut start = Some(0u32);
+        let mut end = Some(0u32);
+        for &length in section_lengths {
+            // Lengths saturate at u32::MAX, so that one may have overflowed
+            let length = (length < u32::MAX).then_some(length);
+            if parallel {
+                section_starts.push(0);
+                end = end.zip(length).map(|(end, length)| end.max(length));
+            } else {
+                section_starts.push(start.unwrap_or(u32::MAX));
+                end = start
+                    .zip(length)
+                    .and_then(|(start, length)| start.checked_add(length));
+                start = start.zip(length).and_then(|(start, length)| {
+                    start.checked_add(round_up_to_bar(length, bar_ticks)?)
+                });
+            }
+        }
+        Self {
+            section_starts,
+            end,
+        }
+    }
+
+    pub(crate) fn section_start(&self, index: usize) -> u32 {
+        self.section_starts.get(index).copied().unwrap_or(0)
+    }
+
+    /// Where the last section ends, or None for a song too long to count in
+    /// ticks (section starts past that point saturate at `u32::MAX`)
+    pub(crate) fn end(&self) -> Option<u32> {
+        self.end
+    }
+}
+
+/// Round a length up to a whole number of bars
+fn round_up_to_bar(ticks: u32, bar_ticks: u32) -> Option<u32> {
+    if bar_ticks == 0 {
+        Some(ticks)
+    } else {
+        ticks.div_ceil(bar_ticks).checked_mul(bar_ticks)
+    }
+}
@@ -0,0 +1,324 @@
+//! Type checking across module boundaries
+
+use std::collections::HashMap;
+
+use relanote_ast::{Item, Program};
+use relanote_core::{Diagnostic, DiagnosticCode, Diagnostics};
+use relanote_types::{ModuleInterface, TypeChecker};
+
+use crate::resolver::{ModuleResolver, ResolvedModule};
+
+/// Module name to resolve a standalone file as; no `use` can refer to it
+pub const ENTRY_MODULE: &str = "<entry>";
+
+/// Type checks modules one after another, giving each the interfaces of the
+/// modules it uses
+///
+/// Modules must be checked after their dependencies, which is the order
+/// [`ModuleResolver::modules`] yields them in. Standard library modules are
+/// checked on first use.
+#[derive(Default)]
+pub struct ModuleChecker {
+    interfaces: HashMap<String, ModuleInterface>,
+    /// Interface of the standard prelude, once checked
+    prelude: Option<ModuleInterface>,
+    /// First error of each module checked by `check_dependencies` that has
+    /// errors, or uses a module that has them
+    broken: HashMap<String, Diagnostic>,
+}
+
+impl ModuleChecker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Type check every module the resolver loaded
+    ///
+    /// Returns each module with its parse diagnostics, followed by its type
+    /// diagnostics when it parsed cleanly.
+    pub fn check_resolved<'a>(
+        &mut self,
+        resolver: &'a ModuleResolver,
+    ) -> Vec<(&'a ResolvedModule, Diagnostics)> {
+        resolver
+            .modules()
+            .map(|module| {
+                let mut diagnostics = module.diagnostics.clone();
+                let type_diagnostics = self.check_program(&module.name, &module.program);
+                // Type checking a program with syntax errors only adds noise
+                if !diagnostics.has_errors() {
+                    diagnostics.merge(localize_labels(resolver, type_diagnostics));
+                }
+                (module, diagnostics)
+            })
+            .collect()
+    }
+
+    /// Type check `program` as module `name` after the other modules the
+    /// resolver loaded, returning only the diagnostics of `program`
+    ///
+    /// This is for a program that may not match what the resolver read for
+    /// `name`, such as an editor buffer parsed with error recovery. The
+    /// errors of other modules aren't repeated, but each `use` of a module
+    /// with errors is reported, noting where the first one is.
+    pub fn check_entry(
+        &mut self,
+        resolver: &ModuleResolver,
+        name: &str,
+        program: &Program,
+    ) -> Diagnostics {
+        self.check_dependencies(resolver, name);
+        let mut diagnostics = self.check_program(name, program);
+        for item in &program.items {
+            let Some(module) = dependency(&item.node) else {
+                continue;
+            };
+            if let Some(error) = self.broken.get(&module) {
+                diagnostics.add(
+                    Diagnostic::error(format!("module `{}` has errors", module), item.span)
+                        .with_code(DiagnosticCode::ModuleHasErrors)
+                        .with_label(error.span, error.message.clone()),
+                );
+            }
+        }
+        localize_labels(resolver, diagnostics)
+    }
+
+    /// Type check the modules the resolver loaded other than `name`, so a
+    /// program standing in for `name` can be checked against them
+    pub fn check_dependencies(&mut self, resolver: &ModuleResolver, name: &str) {
+        for module in resolver.modules().filter(|module| module.name != name) {
+            let diagnostics = self.check_program(&module.name, &module.program);
+            // Modules come after their dependencies, whose errors are known;
+            // an error in a dependency comes first as it may be the cause
+            let error = module
+                .dependencies
+                .iter()
+                .find_map(|dependency| self.broken.get(dependency))
+                .or_else(|| {
+                    module
+                        .diagnostics
+                        .errors()
+                        .chain(diagnostics.errors())
+                        .next()
+                })
+                .cloned();
+            if let Some(error) = error {
+                self.broken.insert(module.name.clone(), error);
+            }
+        }
+    }
+
+    /// Type check a program registered as module `name`
+    ///
+    /// A `use` or `mod` of a module that wasn't checked before (and isn't
+    /// part of the standard library) is reported as unresolved.
+    pub fn check_program(&mut self, name: &str, program: &Program) -> Diagnostics {
+        let (checker, diagnostics) = self.check(program);
+        self.interfaces
+            .insert(name.to_string(), checker.interface());
+        diagnostics
+    }
+
+    /// Type check a program against the modules checked before, without
+    /// registering it as a module
+    ///
+    /// The returned checker answers questions about the program's bindings,
+    /// such as their types for hover and completion.
+    pub fn check(&mut self, program: &Program) -> (TypeChecker, Diagnostics) {
+        let mut checker = TypeChecker::new();
+        checker.add_prelude(self.prelude());
+        let mut diagnostics = Diagnostics::new();
+
+        for item in &program.items {
+            let module = match &item.node {
+                Item::Use(use_decl) => use_decl.path.module_name(),
+                Item::Mod(mod_decl) => mod_decl.name.name.to_string(),
+                _ => continue,
+            };
+            match self.interface(&module) {
+                Some(interface) => checker.add_module(module, interface),
+                None => diagnostics.add(
+                    Diagnostic::error(format!("unresolved module `{}`", module), item.span)
+                        .with_code(DiagnosticCode::UnresolvedModule),
+                ),
+            }
+        }
+
+        diagnostics.merge(checker.check_program(program));
+        (checker, diagnostics)
+    }
+
+    /// Interface of the standard prelude, which every program can use
+    fn prelude(&mut self) -> &ModuleInterface {
+        self.prelude.get_or_insert_with(|| {
+            let (program, _) = relanote_parser::parse(relanote_stdlib::prelude::PRELUDE);
+            let mut checker = TypeChecker::new();
+            checker.check_program(&program);
+            checker.interface()
+        })
+    }
+
+    /// Interface of a module checked before, or of a standard library module
+    fn interface(&mut self, module: &str) -> Option<ModuleInterface> {
+        if let Some(interface) = self.interfaces.get(module) {
+            return Some(interface.clone());
+        }
+        let source = relanote_stdlib::module_source(module)?;
+        let (program, _) = relanote_parser::parse(&source);
+        self.check_program(module, &program);
+        self.interfaces.get(module).cloned()
+    }
+}
+
+/// The module an item makes a program depend on
+fn dependency(item: &Item) -> Option<String> {
+    match item {
+        Item::Import(import) => Some(import.from.clone()),
+        Item::Mod(mod_decl) => Some(mod_decl.name.name.to_string()),
+        Item::Use(use_decl) => Some(use_decl.path.module_name()),
+        _ => None,
+    }
+}
+
+/// Turn labels that point into another module's file into notes
+///
+/// Diagnostics are rendered against the file they were reported in, so a
+/// label such as "`x` is defined here" on a binding in another module is
+/// spelled out with that module's path instead.
+fn localize_labels(resolver: &ModuleResolver, diagnostics: Diagnostics) -> Diagnostics {
+    let mut localized = Diagnostics::new();
+    for mut diagnostic in diagnostics {
+        let (local, foreign): (Vec<_>, Vec<_>) = std::mem::take(&mut diagnostic.labels)
+            .into_iter()
+            .partition(|label| label.span.source == diagnostic.span.source);
+        diagnostic.labels = local;
+        for label in foreign {
+            let Some(source) = resolver.source_db().get(label.span.source) else {
+                continue;
+            };
+            let location = source.location(label.span.start);
+            diagnostic.notes.push(format!(
+                "{}: {}:{}:{}",
+                label.message,
+                source.path.display(),
+                location.line,
+                location.column
+            ));
+        }
+        localized.add(diagnostic);
+    }
+    localized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::MemoryFileProvider;
+
+    #[test]
+    fn test_stdlib_and_unresolved_modules() {
+        let (program, _) = relanote_parser::parse(
+            "use synths::bass::*\nuse scales::Major\nuse drums::kick\nlet x = kick",
+        );
+        let diagnostics = ModuleChecker::new().check_program("main", &program);
+
+        let codes: Vec<_> = diagnostics.errors().map(|d| d.code).collect();
+        assert_eq!(
+            codes,
+            vec![
+                Some(DiagnosticCode::UnresolvedModule),
+                Some(DiagnosticCode::UndefinedVariable)
+            ],
+            "{:?}",
+            diagnostics
+        );
+    }
+
+    #[test]
+    fn test_stdlib_drums_module() {
+        let (program, _) = relanote_parser::parse(
+            "use std::drums::*\nlet beat = tr909 (backbeat ++ fill)\nlet song = gm_kit phrase",
+        );
+        let diagnostics = ModuleChecker::new().check_program("main", &program);
+        assert!(!diagnostics.has_errors(), "{:?}", diagnostics);
+    }
+
+    #[test]
+    fn test_stdlib_patterns_module() {
+        let (program, _) = relanote_parser::parse(
+            "use std::patterns::*\nlet left_hand = alberti MajorTriad 4 ++ waltz Minor7 3",
+        );
+        let diagnostics = ModuleChecker::new().check_program("main", &program);
+        assert!(!diagnostics.has_errors(), "{:?}", diagnostics);
+    }
+
+    #[test]
+    fn test_doc_comments_cross_modules() {
+        let (program, _) = relanote_parser::parse(
+            "use std::patterns::{ alberti as low_high }\nlet echo = slapback\nlet mine = | R |",
+        );
+        let (checker, _) = ModuleChecker::new().check(&program);
+        assert_eq!(checker.doc("low_high"), Some("Low, high, middle, high"));
+        // Prelude definitions are documented too
+        assert_eq!(
+            checker.doc("slapback"),
+            Some("Slapback delay - rockabilly/vintage style")
+        );
+        assert_eq!(checker.doc("mine"), None);
+    }
+
+    #[test]
+    fn test_modules_from_memory() {
+        let mut files = MemoryFileProvider::new();
+        files.insert("song/melody.rela", "export let lead = | R M3 P5 |\n");
+        let mut resolver = ModuleResolver::new("song".into());
+        resolver.set_file_provider(files);
+
+        let source = "use melody::lead\nlet n = lead + 1\n";
+        resolver
+            .resolve_source(ENTRY_MODULE, "song/main.rela".as_ref(), source.to_string())
+            .unwrap();
+        let (program, _) = relanote_parser::parse(source);
+        let diagnostics = ModuleChecker::new().check_entry(&resolver, ENTRY_MODULE, &program);
+
+        let codes: Vec<_> = diagnostics.errors().map(|d| d.code).collect();
+        assert_eq!(
+            codes,
+            vec![Some(DiagnosticCode::TypeMismatch)],
+            "{:?}",
+            diagnostics
+        );
+    }
+
+    #[test]
+    fn test_use_of_module_with_errors() {
+        let mut files = MemoryFileProvider::new();
+        files.insert("song/harmony.rela", "export let pad = | R M3 | + \"x\"\n");
+        files.insert(
+            "song/melody.rela",
+            "use harmony::pad\nexport let lead = pad\n",
+        );
+        let mut resolver = ModuleResolver::new("song".into());
+        resolver.set_file_provider(files);
+
+        let source = "use melody::lead\nlet song = lead\n";
+        resolver
+            .resolve_source(ENTRY_MODULE, "song/main.rela".as_ref(), source.to_string())
+            .unwrap();
+        let (program, _) = relanote_parser::parse(source);
+        let diagnostics = ModuleChecker::new().check_entry(&resolver, ENTRY_MODULE, &program);
+
+        // The error in harmony.rela is reported where main.rela uses it,
+        // through melody.rela
+        let errors: Vec<_> = diagnostics.errors().collect();
+        assert_eq!(errors.len(), 1, "{:?}", diagnostics);
+        assert_eq!(errors[0].code, Some(DiagnosticCode::ModuleHasErrors));
+        assert_eq!(errors[0].message, "module `melody` has errors");
+        assert!(
+            errors[0].notes[0].contains("harmony.rela:1:"),
+            "{:?}",
+            errors[0]
+        );
+    }
+}
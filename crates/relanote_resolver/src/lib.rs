@@ -1,9 +1,23 @@
 //! Module resolution and loading for relanote
 
+mod check;
 mod error;
 mod loader;
+mod lockfile;
+mod manifest;
+mod package;
+mod provider;
 mod resolver;
+mod use_def;
 
+pub use check::{ModuleChecker, ENTRY_MODULE};
 pub use error::ResolveError;
 pub use loader::ModuleLoader;
-pub use resolver::ModuleResolver;
+pub use lockfile::{LockedPackage, Lockfile, LOCK_FILE};
+pub use manifest::{
+    Dependency, DependencyDetail, Manifest, ManifestError, OutputTarget, Package, MANIFEST_FILE,
+};
+pub use package::{resolve_packages, ResolvedPackage, PACKAGES_DIR};
+pub use provider::{CallbackFileProvider, DiskFileProvider, FileProvider, MemoryFileProvider};
+pub use resolver::{ModuleResolver, ResolvedModule};
+pub use use_def::{Definition, UseDef};
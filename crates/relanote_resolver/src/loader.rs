@@ -1,8 +1,11 @@
 use std::path::{Path, PathBuf};
 
+use indexmap::IndexMap;
 use relanote_core::SourceDb;
 
 use crate::error::ResolveError;
+use crate::package::ResolvedPackage;
+use crate::provider::{DiskFileProvider, FileProvider};
 
 /// Module loader responsible for finding and loading source files
 pub struct ModuleLoader {
@@ -11,6 +14,10 @@ pub struct ModuleLoader {
     root: PathBuf,
     /// Search paths for modules
     search_paths: Vec<PathBuf>,
+    /// Packages whose modules are addressed as `name::module`
+    packages: IndexMap<String, ResolvedPackage>,
+    /// Where module files are read from
+    files: Box<dyn FileProvider>,
     /// Source database
     source_db: SourceDb,
 }
@@ -20,15 +27,52 @@ impl ModuleLoader {
         Self {
             root: root.clone(),
             search_paths: vec![root],
+            packages: IndexMap::new(),
+            files: Box::new(DiskFileProvider),
             source_db: SourceDb::new(),
         }
     }
 
+    /// Resolve modules relative to `root` instead, before the other search
+    /// paths
+    pub fn set_root(&mut self, root: PathBuf) {
+        self.search_paths[0] = root.clone();
+        self.root = root;
+    }
+
+    /// Read module files from `files` instead of the disk
+    pub fn set_file_provider(&mut self, files: impl FileProvider + 'static) {
+        self.files = Box::new(files);
+    }
+
     pub fn add_search_path(&mut self, path: PathBuf) {
         self.search_paths.push(path);
     }
 
+    /// Make the modules of `package` available under its name
+    pub fn add_package(&mut self, package: ResolvedPackage) {
+        self.packages.insert(package.name.clone(), package);
+    }
+
     pub fn resolve_path(&self, module_path: &str) -> Option<PathBuf> {
+        let module_path = module_path.replace("::", "/");
+
+        // `name` is a package's entry point, `name/module` a file inside it
+        let (head, rest) = module_path
+            .split_once('/')
+            .unwrap_or((module_path.as_str(), ""));
+        if let Some(package) = self.packages.get(head) {
+            let path = if rest.is_empty() {
+                package.entry.clone()
+            } else {
+                package.root.join(format!(
+                    "{}.rela",
+                    rest.replace('/', std::path::MAIN_SEPARATOR_STR)
+                ))
+            };
+            return self.files.exists(&path).then_some(path);
+        }
+
         let file_name = format!(
             "{}.rela",
             module_path.replace('/', std::path::MAIN_SEPARATOR_STR)
@@ -36,7 +80,7 @@ impl ModuleLoader {
 
         for search_path in &self.search_paths {
             let full_path = search_path.join(&file_name);
-            if full_path.exists() {
+            if self.files.exists(&full_path) {
                 return Some(full_path);
             }
         }
@@ -45,12 +89,14 @@ impl ModuleLoader {
     }
 
     pub fn load(&mut self, path: &Path) -> Result<relanote_core::SourceId, ResolveError> {
-        self.source_db
-            .add_file(path)
-            .map_err(|e| ResolveError::IoError {
-                path: path.to_path_buf(),
-                source: e,
-            })
+        if let Some(source) = self.source_db.get_by_path(path) {
+            return Ok(source.id);
+        }
+        let content = self.files.read(path).map_err(|e| ResolveError::IoError {
+            path: path.to_path_buf(),
+            source: e,
+        })?;
+        Ok(self.source_db.add_source(path.to_path_buf(), content))
     }
 
     pub fn source_db(&self) -> &SourceDb {
@@ -0,0 +1,120 @@
+//! Lockfile recording the packages a build used (`relanote.lock`)
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::manifest::ManifestError;
+use crate::package::ResolvedPackage;
+
+/// File name of a project lockfile, next to its manifest
+pub const LOCK_FILE: &str = "relanote.lock";
+
+/// The packages resolved for a project, pinned by version and source
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Lockfile {
+    /// Lockfile format version
+    pub version: u32,
+    #[serde(default, rename = "package")]
+    pub packages: Vec<LockedPackage>,
+}
+
+/// A `[[package]]` entry of the lockfile
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockedPackage {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// Where the package came from, e.g. `path+packages/melody_lib`
+    pub source: String,
+}
+
+impl Lockfile {
+    /// Current lockfile format version
+    pub const FORMAT_VERSION: u32 = 1;
+
+    /// Lock `packages`, recording their directories relative to `root`
+    pub fn from_packages(packages: &[ResolvedPackage], root: &Path) -> Self {
+        let mut packages: Vec<LockedPackage> = packages
+            .iter()
+            .map(|package| {
+                let dir = package.root.strip_prefix(root).unwrap_or(&package.root);
+                let dir = dir.to_string_lossy().replace('\\', "/");
+                LockedPackage {
+                    name: package.name.clone(),
+                    version: package.version.clone(),
+                    source: format!("path+{}", dir),
+                }
+            })
+            .collect();
+        packages.sort_by(|a, b| a.name.cmp(&b.name));
+        Self {
+            version: Self::FORMAT_VERSION,
+            packages,
+        }
+    }
+
+    /// Parse the contents of a lockfile
+    pub fn parse(text: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(text)
+    }
+
+    /// Load a lockfile, or `None` if it doesn't exist
+    pub fn load(path: &Path) -> Result<Option<Self>, ManifestError> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let text = std::fs::read_to_string(path).map_err(|source| ManifestError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        Self::parse(&text)
+            .map(Some)
+            .map_err(|source| ManifestError::InvalidLockfile {
+                path: path.to_path_buf(),
+                source,
+            })
+    }
+
+    /// Render the lockfile as TOML
+    pub fn to_toml(&self) -> String {
+        let body = toml::to_string(self).expect("lockfile is always representable as TOML");
+        format!(
+            "# This file is generated by `relanote build`. Do not edit it by hand.\n\n{}",
+            body
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_lockfile_roundtrip() {
+        let root = Path::new("/project");
+        let packages = vec![
+            ResolvedPackage {
+                name: "melody_lib".into(),
+                version: Some("0.2.1".into()),
+                root: root.join("packages/melody_lib"),
+                entry: root.join("packages/melody_lib/main.rela"),
+            },
+            ResolvedPackage {
+                name: "drums".into(),
+                version: None,
+                root: PathBuf::from("/shared/drums"),
+                entry: PathBuf::from("/shared/drums/main.rela"),
+            },
+        ];
+        let lockfile = Lockfile::from_packages(&packages, root);
+        assert_eq!(lockfile.packages[0].name, "drums");
+        assert_eq!(lockfile.packages[0].source, "path+/shared/drums");
+        assert_eq!(lockfile.packages[1].source, "path+packages/melody_lib");
+
+        let text = lockfile.to_toml();
+        assert!(text.contains("[[package]]\nname = \"melody_lib\"\nversion = \"0.2.1\""));
+        assert_eq!(Lockfile::parse(&text).unwrap(), lockfile);
+    }
+}
@@ -0,0 +1,258 @@
+//! Project manifest (`relanote.toml`)
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use thiserror::Error;
+
+/// File name of a project manifest
+pub const MANIFEST_FILE: &str = "relanote.toml";
+
+/// A `relanote.toml` project manifest
+///
+/// ```toml
+/// [package]
+/// name = "song"
+/// entry = "src/main.rela"
+/// stdlib = "0.1"
+/// module_paths = ["lib"]
+///
+/// [dependencies]
+/// melody_lib = "0.2"
+/// drums = { path = "../drums" }
+///
+/// [[output]]
+/// path = "dist/song.mid"
+/// ```
+///
+/// Tables used by other tools (such as `[format]`) are ignored.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Manifest {
+    pub package: Package,
+    /// Packages this one uses, by name
+    #[serde(default)]
+    pub dependencies: BTreeMap<String, Dependency>,
+    /// Files written by `relanote build`
+    #[serde(default, rename = "output")]
+    pub outputs: Vec<OutputTarget>,
+}
+
+/// The `[package]` table
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Package {
+    pub name: String,
+    pub version: Option<String>,
+    /// Entry point, relative to the manifest
+    #[serde(default = "default_entry")]
+    pub entry: PathBuf,
+    /// Required standard library version, e.g. `"0.1"`
+    pub stdlib: Option<String>,
+    /// Directories searched for modules after the entry point's directory,
+    /// relative to the manifest
+    #[serde(default)]
+    pub module_paths: Vec<PathBuf>,
+}
+
+fn default_entry() -> PathBuf {
+    PathBuf::from("main.rela")
+}
+
+/// A `[dependencies]` entry
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Dependency {
+    /// `name = "0.2"`: the package in the project's `packages/` directory
+    Version(String),
+    /// `name = { version = "0.2", path = "../lib" }`
+    Detailed(DependencyDetail),
+}
+
+/// A dependency written as a table
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DependencyDetail {
+    /// Required version of the package
+    pub version: Option<String>,
+    /// Package directory, relative to the manifest declaring the dependency
+    pub path: Option<PathBuf>,
+}
+
+impl Dependency {
+    /// Required version of the package, if any
+    pub fn version(&self) -> Option<&str> {
+        match self {
+            Dependency::Version(version) => Some(version),
+            Dependency::Detailed(detail) => detail.version.as_deref(),
+        }
+    }
+
+    /// Explicit package directory, if any
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Dependency::Version(_) => None,
+            Dependency::Detailed(detail) => detail.path.as_deref(),
+        }
+    }
+}
+
+/// An `[[output]]` table: a file rendered from the entry point
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OutputTarget {
+    /// Output file, relative to the manifest; the extension picks the format
+    pub path: PathBuf,
+}
+
+/// Error loading or validating a manifest
+#[derive(Debug, Error)]
+pub enum ManifestError {
+    #[error("failed to read {}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("invalid manifest {}: {source}", path.display())]
+    Invalid {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    #[error("invalid lockfile {}: {source}", path.display())]
+    InvalidLockfile {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    #[error("package requires stdlib {required}, but this is stdlib {found}")]
+    StdlibMismatch { required: String, found: String },
+    #[error("package `{name}` not found: no {} in {}", MANIFEST_FILE, path.display())]
+    PackageNotFound { name: String, path: PathBuf },
+    #[error("package in {} is named `{found}`, expected `{expected}`", path.display())]
+    PackageNameMismatch {
+        expected: String,
+        found: String,
+        path: PathBuf,
+    },
+    #[error("package `{name}` requires version {required}, but {found} was found")]
+    VersionMismatch {
+        name: String,
+        required: String,
+        found: String,
+    },
+    #[error("package `{name}` is used from both {} and {}", first.display(), second.display())]
+    DependencyConflict {
+        name: String,
+        first: PathBuf,
+        second: PathBuf,
+    },
+}
+
+impl Manifest {
+    /// Parse the contents of a `relanote.toml`
+    pub fn parse(text: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(text)
+    }
+
+    /// Load a manifest file
+    pub fn load(path: &Path) -> Result<Self, ManifestError> {
+        let text = std::fs::read_to_string(path).map_err(|source| ManifestError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        Self::parse(&text).map_err(|source| ManifestError::Invalid {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    /// Find the manifest for `dir` by walking up its ancestors
+    pub fn discover(dir: &Path) -> Option<PathBuf> {
+        let dir = std::fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf());
+        dir.ancestors()
+            .map(|ancestor| ancestor.join(MANIFEST_FILE))
+            .find(|candidate| candidate.is_file())
+    }
+
+    /// Check the `stdlib` requirement against the bundled standard library
+    ///
+    /// A requirement matches when its version components are a prefix of the
+    /// stdlib version, so `"0.1"` accepts `0.1.3` but not `0.2.0`.
+    pub fn check_stdlib(&self) -> Result<(), ManifestError> {
+        let Some(required) = &self.package.stdlib else {
+            return Ok(());
+        };
+        let found = relanote_stdlib::VERSION;
+        if version_matches(required, found) {
+            Ok(())
+        } else {
+            Err(ManifestError::StdlibMismatch {
+                required: required.clone(),
+                found: found.to_string(),
+            })
+        }
+    }
+}
+
+/// Whether `found` satisfies the requirement `required`: its version
+/// components must start with those of the requirement
+pub(crate) fn version_matches(required: &str, found: &str) -> bool {
+    let mut found_parts = found.split('.');
+    required
+        .split('.')
+        .all(|part| found_parts.next() == Some(part))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_manifest() {
+        let manifest = Manifest::parse(
+            "[package]\nname = \"song\"\nmodule_paths = [\"lib\"]\n\n\
+             [format]\nindent_size = 4\n\n\
+             [[output]]\npath = \"song.mid\"\n\n[[output]]\npath = \"song.abc\"\n",
+        )
+        .unwrap();
+        assert_eq!(manifest.package.name, "song");
+        assert_eq!(manifest.package.entry, PathBuf::from("main.rela"));
+        assert_eq!(manifest.package.module_paths, vec![PathBuf::from("lib")]);
+        assert_eq!(manifest.outputs.len(), 2);
+        assert!(Manifest::parse("[package]\nname = \"song\"\nentry_point = \"a.rela\"\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_dependencies() {
+        let manifest = Manifest::parse(
+            "[package]\nname = \"song\"\n\n[dependencies]\n\
+             melody_lib = \"0.2\"\ndrums = { path = \"../drums\" }\n",
+        )
+        .unwrap();
+        let melody_lib = &manifest.dependencies["melody_lib"];
+        assert_eq!(melody_lib.version(), Some("0.2"));
+        assert_eq!(melody_lib.path(), None);
+        let drums = &manifest.dependencies["drums"];
+        assert_eq!(drums.version(), None);
+        assert_eq!(drums.path(), Some(Path::new("../drums")));
+
+        assert!(version_matches("0.2", "0.2.5"));
+        assert!(!version_matches("0.2", "0.20.0"));
+        assert!(!version_matches("1.0.1", "1.0"));
+    }
+
+    #[test]
+    fn test_stdlib_requirement() {
+        let mut manifest = Manifest::parse("[package]\nname = \"song\"\n").unwrap();
+        assert!(manifest.check_stdlib().is_ok());
+
+        let major_minor: Vec<&str> = relanote_stdlib::VERSION.split('.').take(2).collect();
+        manifest.package.stdlib = Some(major_minor.join("."));
+        assert!(manifest.check_stdlib().is_ok());
+
+        manifest.package.stdlib = Some("999".to_string());
+        assert!(matches!(
+            manifest.check_stdlib(),
+            Err(ManifestError::StdlibMismatch { .. })
+        ));
+    }
+}
@@ -0,0 +1,107 @@
+//! Package dependencies declared in a manifest
+
+use std::path::{Path, PathBuf};
+
+use crate::manifest::{version_matches, Manifest, ManifestError, MANIFEST_FILE};
+
+/// Directory next to a project's manifest holding the packages it depends on
+/// by version
+pub const PACKAGES_DIR: &str = "packages";
+
+/// A dependency located on disk
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ResolvedPackage {
+    pub name: String,
+    pub version: Option<String>,
+    /// Package directory, holding its manifest
+    pub root: PathBuf,
+    /// Entry point of the package: the module `use name::item` imports from
+    pub entry: PathBuf,
+}
+
+/// Locate every package the project in `root` depends on, directly or
+/// through other packages
+///
+/// A dependency with a `path` is looked up relative to the manifest that
+/// declares it; one with only a version comes from the project's
+/// [`PACKAGES_DIR`]. Each package needs its own manifest, whose name and
+/// version must match the dependency.
+pub fn resolve_packages(
+    manifest: &Manifest,
+    root: &Path,
+) -> Result<Vec<ResolvedPackage>, ManifestError> {
+    let mut packages = Vec::new();
+    collect_packages(manifest, root, root, &mut packages)?;
+    Ok(packages)
+}
+
+fn collect_packages(
+    manifest: &Manifest,
+    manifest_dir: &Path,
+    project_root: &Path,
+    packages: &mut Vec<ResolvedPackage>,
+) -> Result<(), ManifestError> {
+    for (name, dependency) in &manifest.dependencies {
+        let dir = match dependency.path() {
+            Some(path) => manifest_dir.join(path),
+            None => project_root.join(PACKAGES_DIR).join(name),
+        };
+
+        if let Some(existing) = packages.iter().find(|package| package.name == *name) {
+            if !same_dir(&existing.root, &dir) {
+                return Err(ManifestError::DependencyConflict {
+                    name: name.clone(),
+                    first: existing.root.clone(),
+                    second: dir,
+                });
+            }
+            continue;
+        }
+
+        let manifest_path = dir.join(MANIFEST_FILE);
+        if !manifest_path.is_file() {
+            return Err(ManifestError::PackageNotFound {
+                name: name.clone(),
+                path: dir,
+            });
+        }
+        let package_manifest = Manifest::load(&manifest_path)?;
+        let package = &package_manifest.package;
+        if package.name != *name {
+            return Err(ManifestError::PackageNameMismatch {
+                expected: name.clone(),
+                found: package.name.clone(),
+                path: manifest_path,
+            });
+        }
+        if let Some(required) = dependency.version() {
+            let found = package.version.as_deref().unwrap_or("");
+            if !version_matches(required, found) {
+                return Err(ManifestError::VersionMismatch {
+                    name: name.clone(),
+                    required: required.to_string(),
+                    found: package
+                        .version
+                        .clone()
+                        .unwrap_or_else(|| "no version".into()),
+                });
+            }
+        }
+
+        packages.push(ResolvedPackage {
+            name: name.clone(),
+            version: package.version.clone(),
+            entry: dir.join(&package.entry),
+            root: dir.clone(),
+        });
+        collect_packages(&package_manifest, &dir, project_root, packages)?;
+    }
+    Ok(())
+}
+
+fn same_dir(a: &Path, b: &Path) -> bool {
+    match (std::fs::canonicalize(a), std::fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
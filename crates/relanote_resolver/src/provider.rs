@@ -0,0 +1,154 @@
+//! Where module sources are read from
+
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Source of the files modules are loaded from
+///
+/// Module loading goes through a provider instead of `std::fs`, so editors
+/// can supply unsaved buffers and the browser can supply files that only
+/// exist in memory.
+pub trait FileProvider {
+    /// Read the file at `path`
+    fn read(&self, path: &Path) -> io::Result<String>;
+
+    /// Whether there is a file at `path`
+    fn exists(&self, path: &Path) -> bool {
+        self.read(path).is_ok()
+    }
+}
+
+/// Reads files from disk
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DiskFileProvider;
+
+impl FileProvider for DiskFileProvider {
+    fn read(&self, path: &Path) -> io::Result<String> {
+        std::fs::read_to_string(path)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        path.is_file()
+    }
+}
+
+/// Files held in memory, optionally layered over another provider
+///
+/// Files added here shadow the fallback's files at the same path.
+#[derive(Default)]
+pub struct MemoryFileProvider {
+    files: HashMap<PathBuf, String>,
+    fallback: Option<Box<dyn FileProvider>>,
+}
+
+impl MemoryFileProvider {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// In-memory files over `fallback`, such as open editor buffers over
+    /// the files on disk
+    pub fn over(fallback: impl FileProvider + 'static) -> Self {
+        Self {
+            files: HashMap::new(),
+            fallback: Some(Box::new(fallback)),
+        }
+    }
+
+    /// Add or replace a file
+    pub fn insert(&mut self, path: impl Into<PathBuf>, content: impl Into<String>) {
+        self.files.insert(path.into(), content.into());
+    }
+
+    /// Remove a file, returning its content
+    pub fn remove(&mut self, path: &Path) -> Option<String> {
+        self.files.remove(path)
+    }
+
+    /// Paths of the in-memory files
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.files.keys().map(PathBuf::as_path)
+    }
+}
+
+impl FileProvider for MemoryFileProvider {
+    fn read(&self, path: &Path) -> io::Result<String> {
+        if let Some(content) = self.files.get(path) {
+            return Ok(content.clone());
+        }
+        match &self.fallback {
+            Some(fallback) => fallback.read(path),
+            None => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no file at {}", path.display()),
+            )),
+        }
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.files.contains_key(path)
+            || self
+                .fallback
+                .as_ref()
+                .is_some_and(|fallback| fallback.exists(path))
+    }
+}
+
+/// Reads files through a callback, such as a function supplied by a
+/// JavaScript host
+///
+/// The callback returns `None` when there is no file at the path.
+pub struct CallbackFileProvider<F> {
+    read: F,
+}
+
+impl<F: Fn(&Path) -> Option<String>> CallbackFileProvider<F> {
+    pub fn new(read: F) -> Self {
+        Self { read }
+    }
+}
+
+impl<F: Fn(&Path) -> Option<String>> FileProvider for CallbackFileProvider<F> {
+    fn read(&self, path: &Path) -> io::Result<String> {
+        (self.read)(path).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no file at {}", path.display()),
+            )
+        })
+    }
+}
+
+impl<P: FileProvider + ?Sized> FileProvider for std::rc::Rc<P> {
+    fn read(&self, path: &Path) -> io::Result<String> {
+        (**self).read(path)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        (**self).exists(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_files_shadow_fallback() {
+        let mut base = MemoryFileProvider::new();
+        base.insert("lib/a.rela", "let a = 1");
+        base.insert("lib/b.rela", "let b = 2");
+
+        let mut files = MemoryFileProvider::over(base);
+        files.insert("lib/a.rela", "let a = 3");
+
+        assert_eq!(files.read(Path::new("lib/a.rela")).unwrap(), "let a = 3");
+        assert_eq!(files.read(Path::new("lib/b.rela")).unwrap(), "let b = 2");
+        assert!(files.exists(Path::new("lib/b.rela")));
+        assert!(!files.exists(Path::new("lib/c.rela")));
+
+        files.remove(Path::new("lib/a.rela"));
+        assert_eq!(files.read(Path::new("lib/a.rela")).unwrap(), "let a = 1");
+    }
+}
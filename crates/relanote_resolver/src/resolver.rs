@@ -1,17 +1,23 @@
 use std::collections::HashSet;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use indexmap::IndexMap;
-use relanote_ast::Program;
-use relanote_core::{Diagnostics, SourceId};
+use relanote_ast::{Item, Program};
+use relanote_core::{Diagnostics, SourceDb, SourceId};
 use relanote_parser::parse_file;
 
 use crate::error::ResolveError;
 use crate::loader::ModuleLoader;
+use crate::manifest::Manifest;
+use crate::package::{resolve_packages, ResolvedPackage};
+use crate::provider::FileProvider;
 
 /// Resolved module
 pub struct ResolvedModule {
+    /// Module path the module was resolved as
+    pub name: String,
     pub source_id: SourceId,
+    pub path: PathBuf,
     pub program: Program,
     pub diagnostics: Diagnostics,
     pub dependencies: Vec<String>,
@@ -33,6 +39,82 @@ impl ModuleResolver {
         }
     }
 
+    /// Resolver for the modules a single file uses
+    ///
+    /// Modules are searched next to the file and, when a manifest is found
+    /// above it, in the project's module paths and packages. Problems with
+    /// the manifest are ignored here; `relanote build` reports them.
+    pub fn for_file(path: &Path) -> Self {
+        let dir = path
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        let mut resolver = Self::new(dir.to_path_buf());
+
+        let manifest_path = Manifest::discover(dir);
+        let project = manifest_path.as_deref().and_then(|manifest_path| {
+            let manifest = Manifest::load(manifest_path).ok()?;
+            Some((manifest, manifest_path.parent()?))
+        });
+        if let Some((manifest, root)) = project {
+            for module_path in &manifest.package.module_paths {
+                resolver.add_search_path(root.join(module_path));
+            }
+            for package in resolve_packages(&manifest, root).unwrap_or_default() {
+                resolver.add_package(package);
+            }
+        }
+
+        resolver
+    }
+
+    /// Read module files from `files` instead of the disk
+    pub fn set_file_provider(&mut self, files: impl FileProvider + 'static) {
+        self.loader.set_file_provider(files);
+    }
+
+    /// Search `path` for modules after the root directory
+    pub fn add_search_path(&mut self, path: PathBuf) {
+        self.loader.add_search_path(path);
+    }
+
+    /// Make the modules of `package` available as `use name::...`
+    pub fn add_package(&mut self, package: ResolvedPackage) {
+        self.loader.add_package(package);
+    }
+
+    /// Resolve the program in `path` (such as a project's entry point) and
+    /// its dependencies, registering it as `module_path`
+    pub fn resolve_file(
+        &mut self,
+        module_path: &str,
+        path: &Path,
+    ) -> Result<&ResolvedModule, ResolveError> {
+        if self.modules.contains_key(module_path) {
+            return Ok(&self.modules[module_path]);
+        }
+        let source_id = self.loader.load(path)?;
+        self.resolve_loaded(module_path, path.to_path_buf(), source_id)
+    }
+
+    /// Like [`resolve_file`](Self::resolve_file), but with the program's
+    /// source given in memory (such as an unsaved editor buffer)
+    pub fn resolve_source(
+        &mut self,
+        module_path: &str,
+        path: &Path,
+        content: String,
+    ) -> Result<&ResolvedModule, ResolveError> {
+        if self.modules.contains_key(module_path) {
+            return Ok(&self.modules[module_path]);
+        }
+        let source_id = self
+            .loader
+            .source_db_mut()
+            .add_source(path.to_path_buf(), content);
+        self.resolve_loaded(module_path, path.to_path_buf(), source_id)
+    }
+
     /// Resolve a module and its dependencies
     pub fn resolve(&mut self, module_path: &str) -> Result<&ResolvedModule, ResolveError> {
         // Check for circular dependency
@@ -55,8 +137,17 @@ impl ModuleResolver {
                     path: module_path.to_string(),
                 })?;
 
-        // Load and parse the source
         let source_id = self.loader.load(&path)?;
+        self.resolve_loaded(module_path, path, source_id)
+    }
+
+    fn resolve_loaded(
+        &mut self,
+        module_path: &str,
+        path: PathBuf,
+        source_id: SourceId,
+    ) -> Result<&ResolvedModule, ResolveError> {
+        // Parse the source
         let (program, diagnostics) =
             parse_file(self.loader.source_db(), source_id).ok_or_else(|| {
                 ResolveError::ParseError {
@@ -67,17 +158,33 @@ impl ModuleResolver {
         // Mark as resolving
         self.resolving.insert(module_path.to_string());
 
-        // Collect dependencies from import statements
+        // Collect dependencies from import, mod and use items; stdlib
+        // modules are built in and need no file
         let mut dependencies = Vec::new();
         for item in &program.items {
-            if let relanote_ast::Item::Import(import) = &item.node {
-                dependencies.push(import.from.clone());
+            let dependency = match &item.node {
+                Item::Import(import) => import.from.clone(),
+                Item::Mod(mod_decl) => mod_decl.name.name.to_string(),
+                Item::Use(use_decl) => use_decl.path.module_name(),
+                _ => continue,
+            };
+            if relanote_stdlib::module_source(&dependency).is_none()
+                && !dependencies.contains(&dependency)
+            {
+                dependencies.push(dependency);
             }
         }
 
-        // Recursively resolve dependencies
+        // Recursively resolve dependencies. A missing module is left for
+        // the type checker to report at the declaration that uses it.
         for dep in &dependencies {
-            self.resolve(dep)?;
+            match self.resolve(dep) {
+                Ok(_) | Err(ResolveError::ModuleNotFound { .. }) => {}
+                Err(err) => {
+                    self.resolving.remove(module_path);
+                    return Err(err);
+                }
+            }
         }
 
         // Remove from resolving set
@@ -85,7 +192,9 @@ impl ModuleResolver {
 
         // Store resolved module
         let resolved = ResolvedModule {
+            name: module_path.to_string(),
             source_id,
+            path,
             program,
             diagnostics,
             dependencies,
@@ -99,4 +208,9 @@ impl ModuleResolver {
     pub fn modules(&self) -> impl Iterator<Item = &ResolvedModule> {
         self.modules.values()
     }
+
+    /// Sources of the resolved modules
+    pub fn source_db(&self) -> &SourceDb {
+        self.loader.source_db()
+    }
 }
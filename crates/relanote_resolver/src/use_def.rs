@@ -0,0 +1,292 @@
+//! Which definition each name in a program refers to
+
+use relanote_ast::*;
+use relanote_core::{InternedStr, Span, Spanned};
+use relanote_lexer::{Lexer, TokenKind};
+
+/// A name a program defines: a top-level binding, an import, a function
+/// parameter or a local binding
+#[derive(Clone, Debug, PartialEq)]
+pub struct Definition {
+    pub name: InternedStr,
+    /// Span of the name where it is defined
+    pub span: Span,
+}
+
+/// The definitions of a program and where each is used
+///
+/// Names follow the checker's scoping: a top-level definition is visible
+/// from the item after it, and a later definition of the same name shadows
+/// it from there on. Names the program doesn't define (builtins, the
+/// prelude, glob imports) have no definition here.
+#[derive(Debug, Default)]
+pub struct UseDef {
+    definitions: Vec<Definition>,
+    /// Span of each use, with the index of its definition
+    uses: Vec<(Span, usize)>,
+}
+
+impl UseDef {
+    /// Use-def information of `program`, parsed from `source`
+    pub fn of_program(source: &str, program: &Program) -> Self {
+        let mut builder = Builder {
+            source,
+            use_def: UseDef::default(),
+            scope: Vec::new(),
+        };
+        for item in &program.items {
+            builder.item(item);
+        }
+        builder.use_def
+    }
+
+    /// The definition of the name at `offset`, whether it is a use of it or
+    /// where it is defined
+    pub fn definition_at(&self, offset: usize) -> Option<&Definition> {
+        let contains = |span: &Span| span.start <= offset && offset <= span.end;
+        self.uses
+            .iter()
+            .find(|(span, _)| contains(span))
+            .map(|(_, index)| &self.definitions[*index])
+            .or_else(|| self.definitions.iter().find(|def| contains(&def.span)))
+    }
+
+    /// Where `definition` is used, in source order
+    pub fn uses_of(&self, definition: &Definition) -> Vec<Span> {
+        let mut uses: Vec<Span> = self
+            .uses
+            .iter()
+            .filter(|(_, index)| self.definitions[*index] == *definition)
+            .map(|(span, _)| *span)
+            .collect();
+        uses.sort_by_key(|span| span.start);
+        uses
+    }
+}
+
+struct Builder<'a> {
+    source: &'a str,
+    use_def: UseDef,
+    /// Names in scope, innermost last, with their definitions
+    scope: Vec<(InternedStr, usize)>,
+}
+
+impl Builder<'_> {
+    fn item(&mut self, item: &Spanned<Item>) {
+        let span = item.span;
+        match &item.node {
+            Item::LetBinding(binding) => {
+                self.visit_expr(&binding.value);
+                self.bind_pattern(&binding.pattern);
+            }
+            Item::FunctionDef(def) => {
+                let depth = self.scope.len();
+                for param in &def.params {
+                    self.bind_pattern(param);
+                }
+                self.visit_expr(&def.body);
+                self.scope.truncate(depth);
+                self.bind_named(def.name.name, span, Occurrence::First);
+            }
+            Item::ScaleDef(def) => {
+                if let Some(base) = &def.base {
+                    self.visit_expr(base);
+                }
+                self.bind_named(def.name.name, span, Occurrence::First);
+            }
+            Item::ChordDef(def) => self.bind_named(def.name.name, span, Occurrence::First),
+            Item::SynthDef(def) => {
+                walk_item(self, item);
+                self.bind_named(def.name.name, span, Occurrence::First);
+            }
+            Item::Use(use_decl) => match &use_decl.path.kind {
+                UseKind::Simple if use_decl.path.segments.len() >= 2 => {
+                    let name = use_decl.path.segments.last().unwrap().name;
+                    self.bind_named(name, span, Occurrence::Last);
+                }
+                UseKind::Group(items) => {
+                    for use_item in items {
+                        let name = use_item.alias.as_ref().unwrap_or(&use_item.name);
+                        self.bind_named(name.name, span, Occurrence::Last);
+                    }
+                }
+                _ => {}
+            },
+            Item::Import(import) => {
+                for import_item in &import.items {
+                    match import_item {
+                        ImportItem::Named(name) => {
+                            self.bind_named(name.name, span, Occurrence::Last)
+                        }
+                        ImportItem::Aliased { alias, .. } => {
+                            self.bind_named(alias.name, span, Occurrence::Last)
+                        }
+                        ImportItem::All | ImportItem::AllAliased(_) => {}
+                    }
+                }
+            }
+            Item::Export(ExportDecl::Definition(def)) => {
+                self.item(&Spanned::new(def.as_ref().clone(), span))
+            }
+            Item::Export(ExportDecl::Named(names)) => {
+                for name in names {
+                    if let Some(name_span) = self.name_span(name.name, span, Occurrence::First) {
+                        self.use_name(name.name, name_span);
+                    }
+                }
+            }
+            _ => walk_item(self, item),
+        }
+    }
+
+    /// Define `name`, written somewhere in `span`
+    fn bind_named(&mut self, name: InternedStr, span: Span, occurrence: Occurrence) {
+        if let Some(name_span) = self.name_span(name, span, occurrence) {
+            self.bind(name, name_span);
+        }
+    }
+
+    fn bind(&mut self, name: InternedStr, span: Span) {
+        self.use_def.definitions.push(Definition { name, span });
+        self.scope.push((name, self.use_def.definitions.len() - 1));
+    }
+
+    /// Define the names a pattern binds
+    fn bind_pattern(&mut self, pattern: &Spanned<Pattern>) {
+        match &pattern.node {
+            Pattern::Ident(ident) => self.bind(ident.name, pattern.span),
+            Pattern::Tuple(patterns) | Pattern::Constructor { args: patterns, .. } => {
+                for pattern in patterns {
+                    self.bind_pattern(pattern);
+                }
+            }
+            Pattern::Array(array) => {
+                for pattern in array.elements.iter().chain(array.rest.as_deref()) {
+                    self.bind_pattern(pattern);
+                }
+            }
+            // Both sides bind the same names
+            Pattern::Or(left, _) => self.bind_pattern(left),
+            Pattern::Annotated(inner, _) => self.bind_pattern(inner),
+            Pattern::Wildcard | Pattern::Literal(_) => {}
+        }
+    }
+
+    fn use_name(&mut self, name: InternedStr, span: Span) {
+        if let Some((_, index)) = self.scope.iter().rev().find(|(n, _)| *n == name) {
+            self.use_def.uses.push((span, *index));
+        }
+    }
+
+    /// Span of `name` as written in `span`, which items don't record
+    fn name_span(&self, name: InternedStr, span: Span, occurrence: Occurrence) -> Option<Span> {
+        let text = self.source.get(span.start..span.end)?;
+        let mut names = Lexer::from_str(span.source, text)
+            .tokenize()
+            .into_iter()
+            .filter(|token| matches!(&token.kind, TokenKind::Ident(n) if n == name.as_str()));
+        let token = match occurrence {
+            Occurrence::First => names.next(),
+            Occurrence::Last => names.next_back(),
+        }?;
+        Some(Span::new(
+            span.source,
+            span.start + token.span.start,
+            span.start + token.span.end,
+        ))
+    }
+}
+
+/// Which of the names an item writes is the one it defines: `let f x`
+/// defines the first, `use melody::lead` the last
+#[derive(Clone, Copy)]
+enum Occurrence {
+    First,
+    Last,
+}
+
+impl Visitor for Builder<'_> {
+    fn visit_expr(&mut self, expr: &Spanned<Expr>) {
+        let depth = self.scope.len();
+        match &expr.node {
+            Expr::Ident(ident) => self.use_name(ident.name, expr.span),
+            Expr::Lambda(lambda) => {
+                for param in &lambda.params {
+                    self.bind_pattern(param);
+                }
+                self.visit_expr(&lambda.body);
+            }
+            Expr::Let(let_expr) => {
+                self.visit_expr(&let_expr.value);
+                self.bind_pattern(&let_expr.pattern);
+                self.visit_expr(&let_expr.body);
+            }
+            Expr::Match(match_expr) => {
+                self.visit_expr(&match_expr.scrutinee);
+                for arm in &match_expr.arms {
+                    self.bind_pattern(&arm.pattern);
+                    if let Some(guard) = &arm.guard {
+                        self.visit_expr(guard);
+                    }
+                    self.visit_expr(&arm.body);
+                    self.scope.truncate(depth);
+                }
+            }
+            _ => walk_expr(self, expr),
+        }
+        self.scope.truncate(depth);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The definition of the name at `at` and its uses, as `line:name`
+    fn uses(source: &str, at: &str) -> Vec<String> {
+        let (program, _) = relanote_parser::parse(source);
+        let use_def = UseDef::of_program(source, &program);
+        let offset = source.find(at).unwrap();
+        let definition = use_def.definition_at(offset).unwrap();
+        std::iter::once(definition.span)
+            .chain(use_def.uses_of(definition))
+            .map(|span| {
+                let line = source[..span.start].matches('\n').count() + 1;
+                format!("{}:{}", line, &source[span.start..span.end])
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_uses_of_top_level_binding() {
+        let source =
+            "let riff = | R M3 |\nlet song = riff ++ riff |> reverse\nlet riff = | P5 |\nriff";
+        // The second `riff` shadows the first from its definition on
+        assert_eq!(uses(source, "riff"), vec!["1:riff", "2:riff", "2:riff"]);
+        assert_eq!(uses(source, "riff ++"), vec!["1:riff", "2:riff", "2:riff"]);
+        assert_eq!(uses(source, "riff = | P5"), vec!["3:riff", "4:riff"]);
+    }
+
+    #[test]
+    fn test_local_scopes() {
+        let source = "let twice b = b ++ b\nlet b = | R |\nlet f = \\b -> b\nlet g = let b = 1 in b\ntwice b";
+        assert_eq!(uses(source, "b ="), vec!["1:b", "1:b", "1:b"]);
+        assert_eq!(uses(source, "b = | R"), vec!["2:b", "5:b"]);
+        assert_eq!(uses(source, "b -> b"), vec!["3:b", "3:b"]);
+        assert_eq!(uses(source, "twice b"), vec!["1:twice", "5:twice"]);
+    }
+
+    #[test]
+    fn test_imports_and_exports() {
+        let source =
+            "use std::patterns::{ alberti as low_high }\nlet lead = low_high Major 4\nexport lead";
+        assert_eq!(uses(source, "low_high }"), vec!["1:low_high", "2:low_high"]);
+        assert_eq!(uses(source, "lead ="), vec!["2:lead", "3:lead"]);
+        // Builtins have no definition in the program
+        let (program, _) = relanote_parser::parse(source);
+        let use_def = UseDef::of_program(source, &program);
+        assert!(use_def
+            .definition_at(source.find("Major").unwrap())
+            .is_none());
+    }
+}
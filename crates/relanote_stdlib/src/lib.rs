@@ -3,6 +3,9 @@
 //! Provides built-in scales, chords, synth presets, and utility functions
 //! as embedded source code strings.
 
+/// Version of the standard library, checked against a project's `stdlib` requirement
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
 /// The standard prelude - automatically loaded before user code
 /// Organized into modular files for maintainability
 pub mod prelude {
@@ -57,6 +60,13 @@ pub mod prelude {
     /// Distortion effect presets
     pub const EFFECTS_DISTORTION: &str = include_str!("prelude/effects_distortion.rela");
 
+    /// Drum machine presets, kits and grooves; not part of the prelude, so
+    /// that its short names stay free until `use std::drums::*`
+    pub const DRUMS: &str = include_str!("prelude/drums.rela");
+
+    /// Arpeggio and accompaniment patterns, importable as `use std::patterns::*`
+    pub const PATTERNS: &str = include_str!("prelude/patterns.rela");
+
     /// Combined prelude - all modules concatenated
     /// This maintains backward compatibility with existing code
     pub const PRELUDE: &str = concat!(
@@ -99,3 +109,215 @@ pub mod prelude {
         include_str!("prelude/effects_distortion.rela"),
     );
 }
+
+/// Stdlib modules only a `std::` path imports, so that a project's own
+/// module of the same name isn't shadowed by them
+pub const STD_ONLY: &[&str] = &["drums", "patterns"];
+
+/// Stdlib modules `use` can import, in the order they are documented;
+/// the parent modules `synths` and `effects` are left out since they only
+/// combine their children
+pub const MODULES: &[&str] = &[
+    "scales",
+    "chords",
+    "drums",
+    "patterns",
+    "synths::basic",
+    "synths::piano",
+    "synths::bass",
+    "synths::brass",
+    "synths::leads",
+    "synths::pads",
+    "synths::pluck",
+    "synths::drums",
+    "synths::percussion",
+    "synths::retro",
+    "synths::clap",
+    "effects::reverb",
+    "effects::delay",
+    "effects::phaser",
+    "effects::distortion",
+];
+
+/// Source of a stdlib module addressed by `use`/`mod`, e.g. `synths::bass`
+///
+/// An optional `std::` prefix is accepted, except for the modules in
+/// [`STD_ONLY`], which need it. Parent modules (`synths`, `effects`)
+/// combine all of their children.
+pub fn module_source(path: &str) -> Option<String> {
+    use prelude::*;
+
+    let path = match path.strip_prefix("std::") {
+        Some(path) => path,
+        None if STD_ONLY.contains(&path) => return None,
+        None => path,
+    };
+
+    let source = match path {
+        // Core
+        "scales" => SCALES,
+        "chords" => CHORDS,
+        "drums" => DRUMS,
+        "patterns" => PATTERNS,
+
+        // Synths - hierarchical paths
+        "synths::basic" => SYNTHS_BASIC,
+        "synths::bass" => SYNTHS_BASS,
+        "synths::brass" => SYNTHS_BRASS,
+        "synths::leads" => SYNTHS_LEADS,
+        "synths::pads" => SYNTHS_PADS,
+        "synths::piano" => SYNTHS_PIANO,
+        "synths::pluck" => SYNTHS_PLUCK,
+        "synths::drums" => SYNTHS_DRUMS,
+        "synths::percussion" => SYNTHS_PERCUSSION,
+        "synths::retro" => SYNTHS_RETRO,
+        "synths::clap" => SYNTHS_CLAP,
+
+        // Effects - hierarchical paths
+        "effects::reverb" => EFFECTS_REVERB,
+        "effects::delay" => EFFECTS_DELAY,
+        "effects::phaser" => EFFECTS_PHASER,
+        "effects::distortion" => EFFECTS_DISTORTION,
+
+        // Parent modules (all synths/effects combined)
+        "synths" => {
+            return Some(
+                [
+                    SYNTHS_BASIC,
+                    SYNTHS_PIANO,
+                    SYNTHS_BASS,
+                    SYNTHS_BRASS,
+                    SYNTHS_LEADS,
+                    SYNTHS_PADS,
+                    SYNTHS_PLUCK,
+                    SYNTHS_DRUMS,
+                    SYNTHS_PERCUSSION,
+                    SYNTHS_RETRO,
+                    SYNTHS_CLAP,
+                ]
+                .join("\n"),
+            )
+        }
+        "effects" => {
+            return Some(
+                [
+                    EFFECTS_REVERB,
+                    EFFECTS_DELAY,
+                    EFFECTS_PHASER,
+                    EFFECTS_DISTORTION,
+                ]
+                .join("\n"),
+            )
+        }
+
+        _ => return None,
+    };
+    Some(source.to_string())
+}
+
+/// A scale or chord of the prelude, as editors list it in completions and
+/// hover
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Definition {
+    /// Name the scale or chord is bound to, e.g. `Dorian`
+    pub name: &'static str,
+    /// Right-hand side of its definition: `{ R, M2, ... }` or `Minor with { M6 }`
+    pub definition: &'static str,
+    /// The doc comment above the definition
+    pub description: &'static str,
+}
+
+/// Scales of the prelude, in the order `scales.rela` defines them
+///
+/// They are read from the embedded source, so completions and hover offer
+/// exactly the scales a program can use.
+pub fn scales() -> impl Iterator<Item = Definition> {
+    definitions(prelude::SCALES, "export scale ")
+}
+
+/// The prelude scale called `name`
+pub fn scale(name: &str) -> Option<Definition> {
+    scales().find(|scale| scale.name == name)
+}
+
+/// Chords of the prelude, in the order `chords.rela` defines them
+pub fn chords() -> impl Iterator<Item = Definition> {
+    definitions(prelude::CHORDS, "export chord ")
+}
+
+/// The prelude chord called `name`
+pub fn chord(name: &str) -> Option<Definition> {
+    chords().find(|chord| chord.name == name)
+}
+
+/// Definitions in `source` starting with `keyword`, each described by the
+/// doc comment line (`--- ...`) just above it
+fn definitions(source: &'static str, keyword: &'static str) -> impl Iterator<Item = Definition> {
+    let mut description = "";
+    source.lines().filter_map(move |line| {
+        let line = line.trim();
+        if let Some(comment) = line.strip_prefix("---") {
+            description = comment.trim();
+            return None;
+        }
+        let Some((name, definition)) = line
+            .strip_prefix(keyword)
+            .and_then(|rest| rest.split_once('='))
+        else {
+            description = "";
+            return None;
+        };
+        Some(Definition {
+            name: name.trim(),
+            definition: definition.trim(),
+            description: std::mem::take(&mut description),
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_modules_have_sources() {
+        for module in MODULES {
+            let path = format!("std::{}", module);
+            assert!(module_source(&path).is_some(), "{}", module);
+        }
+    }
+
+    #[test]
+    fn test_std_only_modules_need_the_prefix() {
+        assert!(module_source("drums").is_none());
+        assert!(module_source("std::drums").is_some());
+        assert!(module_source("scales").is_some());
+    }
+
+    #[test]
+    fn test_scales_are_read_from_the_prelude() {
+        let dorian = scale("Dorian").unwrap();
+        assert_eq!(dorian.definition, "Minor with { M6 }");
+        assert_eq!(dorian.description, "Minor with a raised sixth");
+
+        for info in scales() {
+            assert!(
+                !info.description.is_empty(),
+                "{} has no description",
+                info.name
+            );
+            assert!(info.definition.contains('{'), "{}", info.name);
+        }
+        assert!(scale("Hirajoshi").is_some());
+        assert!(scale("MajorTriad").is_none());
+    }
+
+    #[test]
+    fn test_chords_are_read_from_the_prelude() {
+        let sus4 = chord("Sus4").unwrap();
+        assert_eq!(sus4.definition, "[ R, P4, P5 ]");
+        assert_eq!(sus4.description, "Fourth in place of the third");
+        assert!(chords().all(|info| !info.description.is_empty()));
+        assert!(chord("Dorian").is_none());
+    }
+}
@@ -0,0 +1,20 @@
+//! The relanote synth engine, for embedding in plugins and game engines
+//!
+//! A [`Patch`] is the synth model relanote songs describe: oscillators, an
+//! ADSR envelope, a filter, a pitch envelope and LFOs, with WAV [`Sample`]s
+//! playing in place of oscillators. A [`Synth`] plays one:
+//! notes go in with [`Synth::note_on`] and [`Synth::note_off`], and audio
+//! comes out a block at a time from [`Synth::process`]. It sounds like the
+//! playground's WebAudio engine, and depends on nothing.
+
+mod patch;
+mod sample;
+mod synth;
+mod voice;
+
+pub use patch::{
+    Envelope, Filter, FilterType, Lfo, LfoShape, LfoTarget, Oscillator, Patch, PitchEnvelope,
+    Waveform,
+};
+pub use sample::{Sample, WavError};
+pub use synth::Synth;
@@ -0,0 +1,162 @@
+//! Synth settings, as relanote songs describe them
+
+use crate::sample::Sample;
+
+/// Shape of an oscillator's wave
+#[derive(Clone, Debug, PartialEq)]
+pub enum Waveform {
+    Sine,
+    Square,
+    Saw,
+    Triangle,
+    /// White noise, at any pitch
+    Noise,
+    /// Noise falling 3 dB an octave
+    PinkNoise,
+    /// Noise falling 6 dB an octave
+    BrownNoise,
+    /// Pulse wave with its duty cycle (0.0 to 1.0, 0.5 = square)
+    Pulse(f32),
+    /// A recording, pitched from its root note
+    Sample(Sample),
+}
+
+/// An oscillator of a patch
+#[derive(Clone, Debug, PartialEq)]
+pub struct Oscillator {
+    pub waveform: Waveform,
+    /// Mix level (0.0 to 1.0)
+    pub mix: f32,
+    /// Octaves above (or below) the note
+    pub octave_offset: i8,
+    /// Detune in cents
+    pub detune_cents: f32,
+}
+
+impl Oscillator {
+    pub fn new(waveform: Waveform) -> Self {
+        Self {
+            waveform,
+            mix: 1.0,
+            octave_offset: 0,
+            detune_cents: 0.0,
+        }
+    }
+}
+
+/// ADSR amplitude envelope
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Envelope {
+    /// Attack time in seconds
+    pub attack: f32,
+    /// Decay time in seconds
+    pub decay: f32,
+    /// Sustain level (0.0 to 1.0)
+    pub sustain: f32,
+    /// Release time in seconds
+    pub release: f32,
+}
+
+impl Default for Envelope {
+    /// The playground's envelope for notes without one
+    fn default() -> Self {
+        Self {
+            attack: 0.02,
+            decay: 0.1,
+            sustain: 0.7,
+            release: 0.1,
+        }
+    }
+}
+
+/// Filter type
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FilterType {
+    LowPass,
+    HighPass,
+    BandPass,
+}
+
+/// Resonant filter the oscillators go through
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Filter {
+    pub filter_type: FilterType,
+    /// Cutoff frequency in Hz
+    pub cutoff: f32,
+    /// Resonance (0.0 to 1.0)
+    pub resonance: f32,
+}
+
+impl Filter {
+    /// The filter's Q, as the playground sets it on a WebAudio
+    /// `BiquadFilterNode` (in decibels for low- and high-pass filters)
+    pub fn q(&self) -> f32 {
+        0.5 + self.resonance.clamp(0.0, 1.0) * 25.0
+    }
+}
+
+/// Pitch sweep replacing the note's pitch, for drums such as kicks
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PitchEnvelope {
+    /// Starting frequency in Hz
+    pub start_hz: f32,
+    /// Frequency in Hz the sweep ends on, and stays at
+    pub end_hz: f32,
+    /// Length of the sweep in seconds
+    pub seconds: f32,
+}
+
+/// What an LFO modulates
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LfoTarget {
+    Pitch,
+    /// The filter's cutoff; without a filter the LFO does nothing
+    Cutoff,
+    Amplitude,
+}
+
+/// Shape of an LFO's wave
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LfoShape {
+    Sine,
+    Triangle,
+    Square,
+    Saw,
+}
+
+/// Low-frequency oscillator modulating every note of a patch
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Lfo {
+    pub target: LfoTarget,
+    pub shape: LfoShape,
+    /// Rate in Hz
+    pub rate: f32,
+    /// Semitones for pitch, Hz for cutoff, 0.0 to 1.0 for amplitude
+    pub depth: f32,
+}
+
+/// A complete synth sound
+#[derive(Clone, Debug, PartialEq)]
+pub struct Patch {
+    pub oscillators: Vec<Oscillator>,
+    pub envelope: Envelope,
+    pub filter: Option<Filter>,
+    /// Detune of every oscillator, in cents
+    pub detune_cents: f32,
+    pub pitch_envelope: Option<PitchEnvelope>,
+    pub lfos: Vec<Lfo>,
+}
+
+impl Default for Patch {
+    /// The playground's sound for parts without a synth: a triangle wave
+    fn default() -> Self {
+        Self {
+            oscillators: vec![Oscillator::new(Waveform::Triangle)],
+            envelope: Envelope::default(),
+            filter: None,
+            detune_cents: 0.0,
+            pitch_envelope: None,
+            lfos: Vec::new(),
+        }
+    }
+}
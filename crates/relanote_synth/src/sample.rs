@@ -0,0 +1,213 @@
+//! Recorded sounds an oscillator plays instead of a waveform
+
+use std::fmt;
+use std::sync::Arc;
+
+/// A recording, played faster or slower to sound at each note's pitch
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sample {
+    /// Mono frames, from -1.0 to 1.0
+    pub frames: Arc<[f32]>,
+    /// Frames per second of the recording
+    pub sample_rate: f32,
+    /// MIDI pitch the recording sounds at when played as is
+    pub root: f32,
+    /// Start and end of the loop, in seconds into the recording; without
+    /// one the sample plays once
+    pub loop_points: Option<(f32, f32)>,
+}
+
+/// Why a WAV file could not be read
+#[derive(Clone, Debug, PartialEq)]
+pub enum WavError {
+    NotWav,
+    MissingChunk(&'static str),
+    /// An encoding other than 8- to 32-bit PCM or 32- and 64-bit float
+    Unsupported {
+        format: u16,
+        bits: u16,
+    },
+}
+
+impl fmt::Display for WavError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WavError::NotWav => write!(f, "not a WAV file"),
+            WavError::MissingChunk(id) => write!(f, "missing '{id}' chunk"),
+            WavError::Unsupported { format, bits } => {
+                write!(f, "unsupported encoding (format {format}, {bits}-bit)")
+            }
+        }
+    }
+}
+
+impl std::error::Error for WavError {}
+
+/// WAVE_FORMAT_PCM
+const FORMAT_PCM: u16 = 1;
+/// WAVE_FORMAT_IEEE_FLOAT
+const FORMAT_FLOAT: u16 = 3;
+/// WAVE_FORMAT_EXTENSIBLE, whose real format is in the chunk's sub-format
+const FORMAT_EXTENSIBLE: u16 = 0xfffe;
+
+impl Sample {
+    /// A recording of `frames` at `sample_rate`, sounding middle C
+    pub fn new(frames: impl Into<Arc<[f32]>>, sample_rate: f32) -> Self {
+        Self {
+            frames: frames.into(),
+            sample_rate,
+            root: 60.0,
+            loop_points: None,
+        }
+    }
+
+    /// Decode a WAV file, mixing its channels down to mono
+    pub fn from_wav(bytes: &[u8]) -> Result<Self, WavError> {
+        if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+            return Err(WavError::NotWav);
+        }
+
+        let mut format = None;
+        let mut data = None;
+        let mut rest = &bytes[12..];
+        while rest.len() >= 8 {
+            let id = &rest[0..4];
+            let size = u32::from_le_bytes([rest[4], rest[5], rest[6], rest[7]]) as usize;
+            let body = &rest[8..(8 + size).min(rest.len())];
+            match id {
+                b"fmt " if body.len() >= 16 => format = Some(body),
+                b"data" => data = Some(body),
+                _ => {}
+            }
+            // Chunks are padded to an even length
+            rest = &rest[(8 + size + size % 2).min(rest.len())..];
+        }
+        let format = format.ok_or(WavError::MissingChunk("fmt "))?;
+        let data = data.ok_or(WavError::MissingChunk("data"))?;
+
+        let u16_at = |at: usize| u16::from_le_bytes([format[at], format[at + 1]]);
+        let mut encoding = u16_at(0);
+        let channels = u16_at(2).max(1) as usize;
+        let sample_rate = u32::from_le_bytes([format[4], format[5], format[6], format[7]]);
+        let bits = u16_at(14);
+        if encoding == FORMAT_EXTENSIBLE && format.len() >= 26 {
+            encoding = u16_at(24);
+        }
+
+        let decode: fn(&[u8]) -> f32 = match (encoding, bits) {
+            (FORMAT_PCM, 8) => |b| (b[0] as f32 - 128.0) / 128.0,
+            (FORMAT_PCM, 16) => |b| i16::from_le_bytes([b[0], b[1]]) as f32 / 32_768.0,
+            (FORMAT_PCM, 24) => {
+                |b| i32::from_le_bytes([0, b[0], b[1], b[2]]) as f32 / 2_147_483_648.0
+            }
+            (FORMAT_PCM, 32) => {
+                |b| i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f32 / 2_147_483_648.0
+            }
+            (FORMAT_FLOAT, 32) => |b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+            (FORMAT_FLOAT, 64) => {
+                |b| f64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]) as f32
+            }
+            (format, bits) => return Err(WavError::Unsupported { format, bits }),
+        };
+
+        let width = bits as usize / 8;
+        let frames: Vec<f32> = data
+            .chunks_exact(width * channels)
+            .map(|frame| frame.chunks_exact(width).map(decode).sum::<f32>() / channels as f32)
+            .collect();
+        Ok(Self::new(frames, sample_rate as f32))
+    }
+
+    /// The loop as a range of frames, if it has a length
+    fn loop_frames(&self) -> Option<(f64, f64)> {
+        let (start, end) = self.loop_points?;
+        let len = self.frames.len() as f64;
+        let rate = self.sample_rate as f64;
+        let start = (start as f64 * rate).clamp(0.0, len);
+        let end = (end as f64 * rate).clamp(0.0, len);
+        (end - start >= 1.0).then_some((start, end))
+    }
+
+    /// The frame at `position`, between frames by linear interpolation;
+    /// silence past the end
+    pub(crate) fn frame_at(&self, position: f64) -> f32 {
+        let index = position as usize;
+        let Some(&frame) = self.frames.get(index) else {
+            return 0.0;
+        };
+        let next = match self.loop_frames() {
+            Some((start, end)) if index + 1 >= end as usize => self.frames[start as usize],
+            _ => self.frames.get(index + 1).copied().unwrap_or(0.0),
+        };
+        let fraction = (position - index as f64) as f32;
+        frame + (next - frame) * fraction
+    }
+
+    /// Where playback goes from `position`, moving `step` frames on and
+    /// jumping back to the loop's start at its end
+    pub(crate) fn advance(&self, position: f64, step: f64) -> f64 {
+        let next = position + step;
+        match self.loop_frames() {
+            Some((start, end)) if next >= end => start + (next - start) % (end - start),
+            _ => next,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A WAV file of 16-bit PCM frames
+    fn wav(channels: u16, sample_rate: u32, samples: &[i16]) -> Vec<u8> {
+        let data: Vec<u8> = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(b"RIFF");
+        bytes.extend_from_slice(&(36 + data.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(b"WAVEfmt ");
+        bytes.extend_from_slice(&16u32.to_le_bytes());
+        bytes.extend_from_slice(&FORMAT_PCM.to_le_bytes());
+        bytes.extend_from_slice(&channels.to_le_bytes());
+        bytes.extend_from_slice(&sample_rate.to_le_bytes());
+        bytes.extend_from_slice(&(sample_rate * channels as u32 * 2).to_le_bytes());
+        bytes.extend_from_slice(&(channels * 2).to_le_bytes());
+        bytes.extend_from_slice(&16u16.to_le_bytes());
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&data);
+        bytes
+    }
+
+    #[test]
+    fn test_from_wav() {
+        // Stereo frames mix down to mono
+        let sample = Sample::from_wav(&wav(2, 22_050, &[16_384, 0, -32_768, -32_768])).unwrap();
+        assert_eq!(sample.sample_rate, 22_050.0);
+        assert_eq!(&sample.frames[..], &[0.25, -1.0]);
+        assert_eq!(sample.root, 60.0);
+
+        assert_eq!(Sample::from_wav(b"RIFF\0\0\0\0AVI "), Err(WavError::NotWav));
+        let mut compressed = wav(1, 8_000, &[0]);
+        compressed[20] = 2;
+        assert_eq!(
+            Sample::from_wav(&compressed),
+            Err(WavError::Unsupported {
+                format: 2,
+                bits: 16
+            })
+        );
+    }
+
+    #[test]
+    fn test_loop() {
+        let mut sample = Sample::new(vec![0.0, 1.0, 2.0, 3.0], 4.0);
+        assert_eq!(sample.frame_at(1.5), 1.5);
+        assert_eq!(sample.frame_at(4.0), 0.0);
+        assert_eq!(sample.advance(3.5, 1.0), 4.5);
+
+        // Loop frames 1 to 3: the last frame leads back into the first
+        sample.loop_points = Some((0.25, 0.75));
+        assert_eq!(sample.advance(2.5, 1.0), 1.5);
+        assert_eq!(sample.frame_at(2.5), 1.5);
+    }
+}
@@ -0,0 +1,323 @@
+//! Polyphonic playback of a patch, a block of samples at a time
+
+use crate::patch::Patch;
+use crate::voice::Voice;
+
+/// A polyphonic synth playing one patch
+///
+/// For sample-accurate timing, split each block at its events: process up
+/// to an event, send it, and process the rest.
+pub struct Synth {
+    patch: Patch,
+    sample_rate: f32,
+    voices: Vec<Voice>,
+    max_voices: usize,
+    /// Seed of the next voice's noise
+    seed: u32,
+}
+
+impl Synth {
+    /// Voices that sound at once before the oldest is cut off
+    pub const DEFAULT_MAX_VOICES: usize = 32;
+
+    pub fn new(patch: Patch, sample_rate: f32) -> Self {
+        Self {
+            patch,
+            sample_rate,
+            voices: Vec::new(),
+            max_voices: Self::DEFAULT_MAX_VOICES,
+            seed: 0x9e37_79b9,
+        }
+    }
+
+    pub fn patch(&self) -> &Patch {
+        &self.patch
+    }
+
+    /// Play `patch` from the next note on; notes already sounding keep
+    /// their patch
+    pub fn set_patch(&mut self, patch: Patch) {
+        self.patch = patch;
+    }
+
+    pub fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+
+    /// Play at `sample_rate` from the next note on
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+    }
+
+    pub fn set_max_voices(&mut self, max_voices: usize) {
+        self.max_voices = max_voices.max(1);
+    }
+
+    /// Start a note; `pitch` is a MIDI note number, between keys for
+    /// microtones (60.5 = a quarter tone above C4)
+    ///
+    /// A note already sounding at the pitch is released first.
+    pub fn note_on(&mut self, pitch: f32, velocity: u8) {
+        self.note_off(pitch);
+        if self.voices.len() >= self.max_voices {
+            // Cut off the oldest voice, preferring one already released
+            let oldest = self
+                .voices
+                .iter()
+                .position(Voice::is_releasing)
+                .unwrap_or(0);
+            self.voices.remove(oldest);
+        }
+        self.seed = self
+            .seed
+            .wrapping_mul(1_664_525)
+            .wrapping_add(1_013_904_223);
+        let voice = Voice::new(&self.patch, pitch, velocity, self.sample_rate, self.seed);
+        self.voices.push(voice);
+    }
+
+    /// Release the note at `pitch`
+    pub fn note_off(&mut self, pitch: f32) {
+        for voice in &mut self.voices {
+            if voice.pitch == pitch {
+                voice.release();
+            }
+        }
+    }
+
+    /// Release every note
+    pub fn all_notes_off(&mut self) {
+        for voice in &mut self.voices {
+            voice.release();
+        }
+    }
+
+    /// Silence every note at once
+    pub fn reset(&mut self) {
+        self.voices.clear();
+    }
+
+    /// Voices sounding, including released ones still fading out
+    pub fn active_voices(&self) -> usize {
+        self.voices.len()
+    }
+
+    /// Render the next `output.len()` samples (mono), replacing what
+    /// `output` held
+    pub fn process(&mut self, output: &mut [f32]) {
+        output.fill(0.0);
+        for voice in &mut self.voices {
+            voice.render(output);
+        }
+        self.voices.retain(|voice| !voice.is_done());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::patch::{
+        Envelope, Filter, FilterType, Lfo, LfoShape, LfoTarget, Oscillator, PitchEnvelope, Waveform,
+    };
+    use crate::sample::Sample;
+
+    const SAMPLE_RATE: f32 = 48_000.0;
+
+    fn patch(waveform: Waveform) -> Patch {
+        Patch {
+            oscillators: vec![Oscillator::new(waveform)],
+            envelope: Envelope {
+                attack: 0.0,
+                decay: 0.0,
+                sustain: 1.0,
+                release: 0.01,
+            },
+            ..Patch::default()
+        }
+    }
+
+    /// Upward zero crossings in `samples`
+    fn crossings(samples: &[f32]) -> usize {
+        samples
+            .windows(2)
+            .filter(|w| w[0] < 0.0 && w[1] >= 0.0)
+            .count()
+    }
+
+    fn rms(samples: &[f32]) -> f32 {
+        (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+    }
+
+    #[test]
+    fn test_pitch_and_release() {
+        let mut synth = Synth::new(patch(Waveform::Sine), SAMPLE_RATE);
+        synth.note_on(69.0, 127);
+        let mut block = vec![0.0; SAMPLE_RATE as usize];
+        synth.process(&mut block);
+        assert!((crossings(&block) as i32 - 440).abs() <= 1);
+        // Full velocity peaks at half scale
+        let peak = block.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+        assert!((peak - 0.5).abs() < 0.01);
+
+        synth.note_off(69.0);
+        let mut block = vec![0.0; 1024];
+        synth.process(&mut block);
+        assert_eq!(synth.active_voices(), 0);
+        assert!(block[600..].iter().all(|&s| s == 0.0));
+    }
+
+    #[test]
+    fn test_octaves_and_pitch_sweep() {
+        let mut octave_up = patch(Waveform::Square);
+        octave_up.oscillators[0].octave_offset = 1;
+        let mut synth = Synth::new(octave_up, SAMPLE_RATE);
+        synth.note_on(57.0, 100);
+        let mut block = vec![0.0; SAMPLE_RATE as usize];
+        synth.process(&mut block);
+        assert!((crossings(&block) as i32 - 440).abs() <= 1);
+
+        // A kick sweeps down and stays at its end frequency
+        let mut kick = patch(Waveform::Sine);
+        kick.pitch_envelope = Some(PitchEnvelope {
+            start_hz: 200.0,
+            end_hz: 50.0,
+            seconds: 0.1,
+        });
+        let mut synth = Synth::new(kick, SAMPLE_RATE);
+        synth.note_on(36.0, 100);
+        let mut block = vec![0.0; SAMPLE_RATE as usize];
+        synth.process(&mut block);
+        let tail = &block[SAMPLE_RATE as usize / 2..];
+        assert!((crossings(tail) as i32 - 25).abs() <= 1);
+    }
+
+    #[test]
+    fn test_filter() {
+        let mut bright = Synth::new(patch(Waveform::Saw), SAMPLE_RATE);
+        let mut filtered = patch(Waveform::Saw);
+        filtered.filter = Some(Filter {
+            filter_type: FilterType::LowPass,
+            cutoff: 200.0,
+            resonance: 0.0,
+        });
+        let mut dark = Synth::new(filtered, SAMPLE_RATE);
+
+        // Well above the cutoff, a low-pass filter takes most of the sound
+        let (mut a, mut b) = (vec![0.0; 4800], vec![0.0; 4800]);
+        for (synth, block) in [(&mut bright, &mut a), (&mut dark, &mut b)] {
+            synth.note_on(96.0, 100);
+            synth.process(block);
+        }
+        assert!(rms(&b) < rms(&a) * 0.2);
+    }
+
+    #[test]
+    fn test_samples() {
+        // A second of A4, recorded at half the output rate
+        let rate = SAMPLE_RATE / 2.0;
+        let frames: Vec<f32> = (0..rate as usize)
+            .map(|i| (i as f32 / rate * 440.0 * std::f32::consts::TAU).sin())
+            .collect();
+        let mut recording = Sample::new(frames, rate);
+        recording.root = 69.0;
+
+        // An octave below the root, it plays at half speed
+        let mut synth = Synth::new(patch(Waveform::Sample(recording.clone())), SAMPLE_RATE);
+        synth.note_on(57.0, 100);
+        let mut block = vec![0.0; SAMPLE_RATE as usize];
+        synth.process(&mut block);
+        assert!((crossings(&block) as i32 - 220).abs() <= 1);
+
+        // An octave above, it runs out halfway through and falls silent
+        synth.reset();
+        synth.note_on(81.0, 100);
+        synth.process(&mut block);
+        assert!(rms(&block[..20_000]) > 0.1);
+        assert!(block[24_100..].iter().all(|&s| s == 0.0));
+
+        // Looping, it keeps sounding
+        recording.loop_points = Some((0.5, 1.0));
+        let mut synth = Synth::new(patch(Waveform::Sample(recording)), SAMPLE_RATE);
+        synth.note_on(81.0, 100);
+        synth.process(&mut block);
+        assert!(rms(&block[24_100..]) > 0.1);
+    }
+
+    #[test]
+    fn test_noise_colours() {
+        // Energy in the top octaves, relative to the whole
+        let brightness = |waveform| {
+            let mut synth = Synth::new(patch(waveform), SAMPLE_RATE);
+            synth.note_on(60.0, 127);
+            let mut block = vec![0.0; 48_000];
+            synth.process(&mut block);
+            let treble: Vec<f32> = block.windows(2).map(|w| w[1] - w[0]).collect();
+            rms(&treble) / rms(&block)
+        };
+        let white = brightness(Waveform::Noise);
+        let pink = brightness(Waveform::PinkNoise);
+        let brown = brightness(Waveform::BrownNoise);
+        assert!(white > pink * 1.5 && pink > brown * 1.5);
+    }
+
+    #[test]
+    fn test_lfos() {
+        let lfo = |target, depth| Lfo {
+            target,
+            shape: LfoShape::Square,
+            rate: 1.0,
+            depth,
+        };
+
+        // A square LFO of an octave: half a second of A5, then of A3
+        let mut vibrato = patch(Waveform::Sine);
+        vibrato.lfos = vec![lfo(LfoTarget::Pitch, 12.0)];
+        let mut synth = Synth::new(vibrato, SAMPLE_RATE);
+        synth.note_on(69.0, 127);
+        let mut block = vec![0.0; SAMPLE_RATE as usize];
+        synth.process(&mut block);
+        assert!((crossings(&block[..24_000]) as i32 - 440).abs() <= 1);
+        assert!((crossings(&block[24_000..]) as i32 - 110).abs() <= 1);
+
+        // Full tremolo: sound, then silence
+        let mut tremolo = patch(Waveform::Sine);
+        tremolo.lfos = vec![lfo(LfoTarget::Amplitude, 1.0)];
+        let mut synth = Synth::new(tremolo, SAMPLE_RATE);
+        synth.note_on(69.0, 127);
+        synth.process(&mut block);
+        assert!(rms(&block[..24_000]) > 0.3);
+        assert!(block[24_000..].iter().all(|&s| s == 0.0));
+
+        // Cutoff swept up and down: brighter while it is up
+        let mut sweep = patch(Waveform::Saw);
+        sweep.filter = Some(Filter {
+            filter_type: FilterType::LowPass,
+            cutoff: 1_000.0,
+            resonance: 0.0,
+        });
+        sweep.lfos = vec![lfo(LfoTarget::Cutoff, 800.0)];
+        let mut synth = Synth::new(sweep, SAMPLE_RATE);
+        synth.note_on(60.0, 127);
+        synth.process(&mut block);
+        let treble: Vec<f32> = block.windows(2).map(|w| w[1] - w[0]).collect();
+        assert!(rms(&treble[2_000..24_000]) > rms(&treble[26_000..]) * 2.0);
+    }
+
+    #[test]
+    fn test_voices() {
+        let mut synth = Synth::new(patch(Waveform::Triangle), SAMPLE_RATE);
+        synth.set_max_voices(2);
+        for pitch in [60.0, 64.0, 67.0] {
+            synth.note_on(pitch, 100);
+        }
+        assert_eq!(synth.active_voices(), 2);
+        // Playing a sounding pitch again releases the note first
+        synth.note_on(67.0, 100);
+        assert_eq!(synth.active_voices(), 2);
+
+        synth.all_notes_off();
+        let mut block = vec![0.0; 2048];
+        synth.process(&mut block);
+        assert_eq!(synth.active_voices(), 0);
+    }
+}
@@ -0,0 +1,423 @@
+//! A sounding note: its oscillators, envelope and filter as they run
+
+use std::f64::consts::TAU;
+
+use crate::patch::{Envelope, Filter, FilterType, Lfo, LfoShape, LfoTarget, Patch, Waveform};
+
+/// Frequency of a MIDI pitch, with A4 (69) at 440 Hz
+fn frequency(pitch: f32) -> f64 {
+    440.0 * 2f64.powf((pitch as f64 - 69.0) / 12.0)
+}
+
+fn cents(cents: f32) -> f64 {
+    2f64.powf(cents as f64 / 1200.0)
+}
+
+struct OscillatorState {
+    waveform: Waveform,
+    mix: f32,
+    /// Frequency as a multiple of the voice's
+    ratio: f64,
+    /// Position in the wave's cycle (0.0 to 1.0), or the frame a sample
+    /// has reached
+    phase: f64,
+    /// Filters shaping white noise into pink or brown
+    colour: [f64; 7],
+}
+
+impl OscillatorState {
+    /// The next sample of the oscillator sounding at `hz`
+    fn next(&mut self, hz: f64, sample_rate: f64, noise: &mut u32) -> f32 {
+        let t = self.phase;
+        let step = (hz / sample_rate).min(0.5);
+        let mut next_phase = (t + step).fract();
+        let sample = match &self.waveform {
+            Waveform::Sine => (t * TAU).sin(),
+            Waveform::Square => pulse(t, step, 0.5),
+            Waveform::Pulse(duty) => pulse(t, step, (*duty as f64).clamp(0.01, 0.99)),
+            Waveform::Saw => 2.0 * t - 1.0 - poly_blep(t, step),
+            Waveform::Triangle => 1.0 - 4.0 * (t - 0.5).abs(),
+            Waveform::Noise => white_noise(noise),
+            Waveform::PinkNoise => {
+                // Paul Kellet's filter
+                let white = white_noise(noise);
+                let b = &mut self.colour;
+                b[0] = 0.99886 * b[0] + white * 0.0555179;
+                b[1] = 0.99332 * b[1] + white * 0.0750759;
+                b[2] = 0.96900 * b[2] + white * 0.1538520;
+                b[3] = 0.86650 * b[3] + white * 0.3104856;
+                b[4] = 0.55000 * b[4] + white * 0.5329522;
+                b[5] = -0.7616 * b[5] - white * 0.0168980;
+                let pink = b[0] + b[1] + b[2] + b[3] + b[4] + b[5] + b[6] + white * 0.5362;
+                b[6] = white * 0.115926;
+                pink * 0.11
+            }
+            Waveform::BrownNoise => {
+                // White noise, integrated and leaking back towards zero
+                let white = white_noise(noise);
+                self.colour[0] = (self.colour[0] + 0.02 * white) / 1.02;
+                self.colour[0] * 3.5
+            }
+            Waveform::Sample(recording) => {
+                // A recording moves through its frames rather than a cycle,
+                // faster the further the note is above its root
+                let rate =
+                    hz / frequency(recording.root) * recording.sample_rate as f64 / sample_rate;
+                next_phase = recording.advance(t, rate);
+                recording.frame_at(t) as f64
+            }
+        };
+        self.phase = next_phase;
+        sample as f32
+    }
+}
+
+/// White noise from a xorshift32 generator
+fn white_noise(noise: &mut u32) -> f64 {
+    *noise ^= *noise << 13;
+    *noise ^= *noise >> 17;
+    *noise ^= *noise << 5;
+    *noise as f64 / u32::MAX as f64 * 2.0 - 1.0
+}
+
+/// A pulse wave, high for `duty` of each cycle
+fn pulse(t: f64, step: f64, duty: f64) -> f64 {
+    let naive = if t < duty { 1.0 } else { -1.0 };
+    naive + poly_blep(t, step) - poly_blep((t + 1.0 - duty).fract(), step)
+}
+
+/// Smooths the jump of a wave at the start of its cycle, which would
+/// otherwise alias
+fn poly_blep(t: f64, step: f64) -> f64 {
+    if t < step {
+        let t = t / step;
+        t + t - t * t - 1.0
+    } else if t > 1.0 - step {
+        let t = (t - 1.0) / step;
+        t * t + t + t + 1.0
+    } else {
+        0.0
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Stage {
+    Attack,
+    Decay,
+    Sustain,
+    Release,
+    Done,
+}
+
+/// Linear ADSR ramps, as the playground schedules them
+struct EnvelopeState {
+    stage: Stage,
+    level: f32,
+    attack_step: f32,
+    decay_step: f32,
+    sustain: f32,
+    release_seconds: f32,
+    release_step: f32,
+    sample_rate: f32,
+}
+
+impl EnvelopeState {
+    fn new(envelope: &Envelope, sample_rate: f32) -> Self {
+        let sustain = envelope.sustain.clamp(0.0, 1.0);
+        Self {
+            stage: Stage::Attack,
+            level: 0.0,
+            attack_step: 1.0 / (envelope.attack * sample_rate).max(1.0),
+            decay_step: (1.0 - sustain) / (envelope.decay * sample_rate).max(1.0),
+            sustain,
+            release_seconds: envelope.release,
+            release_step: 0.0,
+            sample_rate,
+        }
+    }
+
+    fn release(&mut self) {
+        if self.stage != Stage::Done {
+            self.stage = Stage::Release;
+            self.release_step = self.level / (self.release_seconds * self.sample_rate).max(1.0);
+        }
+    }
+
+    fn next(&mut self) -> f32 {
+        match self.stage {
+            Stage::Attack => {
+                self.level += self.attack_step;
+                if self.level >= 1.0 {
+                    self.level = 1.0;
+                    self.stage = Stage::Decay;
+                }
+            }
+            Stage::Decay => {
+                self.level -= self.decay_step;
+                if self.level <= self.sustain {
+                    self.level = self.sustain;
+                    self.stage = Stage::Sustain;
+                }
+            }
+            Stage::Sustain => {}
+            Stage::Release => {
+                self.level -= self.release_step;
+                if self.level <= 0.0 {
+                    self.level = 0.0;
+                    self.stage = Stage::Done;
+                }
+            }
+            Stage::Done => {}
+        }
+        self.level
+    }
+}
+
+/// An LFO as it runs, from -1.0 to 1.0 like a WebAudio oscillator
+struct LfoState {
+    lfo: Lfo,
+    /// Position in the wave's cycle (0.0 to 1.0)
+    phase: f64,
+}
+
+impl LfoState {
+    fn next(&mut self, sample_rate: f64) -> f32 {
+        let t = self.phase;
+        let value = match self.lfo.shape {
+            LfoShape::Sine => (t * TAU).sin(),
+            LfoShape::Triangle => {
+                if t < 0.25 {
+                    4.0 * t
+                } else if t < 0.75 {
+                    2.0 - 4.0 * t
+                } else {
+                    4.0 * t - 4.0
+                }
+            }
+            LfoShape::Square => {
+                if t < 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            LfoShape::Saw => 2.0 * (t + 0.5).fract() - 1.0,
+        };
+        self.phase = (t + self.lfo.rate as f64 / sample_rate).fract();
+        value as f32
+    }
+}
+
+/// Samples between updates of a filter whose cutoff an LFO moves
+const CUTOFF_UPDATE: u32 = 32;
+
+/// A biquad filter with WebAudio's `BiquadFilterNode` coefficients
+struct Biquad {
+    filter: Filter,
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+    z1: f32,
+    z2: f32,
+}
+
+impl Biquad {
+    fn new(filter: &Filter, sample_rate: f32) -> Self {
+        let mut biquad = Self {
+            filter: *filter,
+            b0: 0.0,
+            b1: 0.0,
+            b2: 0.0,
+            a1: 0.0,
+            a2: 0.0,
+            z1: 0.0,
+            z2: 0.0,
+        };
+        biquad.tune(filter.cutoff, sample_rate);
+        biquad
+    }
+
+    /// Set the coefficients for `cutoff`, keeping the filter's state
+    fn tune(&mut self, cutoff: f32, sample_rate: f32) {
+        let filter = &self.filter;
+        let nyquist = sample_rate as f64 / 2.0;
+        let w0 = TAU * (cutoff as f64).clamp(10.0, nyquist * 0.99) / sample_rate as f64;
+        let (sin, cos) = w0.sin_cos();
+        let q = filter.q() as f64;
+        let (b0, b1, b2, alpha) = match filter.filter_type {
+            FilterType::LowPass => {
+                let alpha = sin / (2.0 * 10f64.powf(q / 20.0));
+                ((1.0 - cos) / 2.0, 1.0 - cos, (1.0 - cos) / 2.0, alpha)
+            }
+            FilterType::HighPass => {
+                let alpha = sin / (2.0 * 10f64.powf(q / 20.0));
+                ((1.0 + cos) / 2.0, -(1.0 + cos), (1.0 + cos) / 2.0, alpha)
+            }
+            FilterType::BandPass => {
+                let alpha = sin / (2.0 * q);
+                (alpha, 0.0, -alpha, alpha)
+            }
+        };
+        let a0 = 1.0 + alpha;
+        self.b0 = (b0 / a0) as f32;
+        self.b1 = (b1 / a0) as f32;
+        self.b2 = (b2 / a0) as f32;
+        self.a1 = (-2.0 * cos / a0) as f32;
+        self.a2 = ((1.0 - alpha) / a0) as f32;
+    }
+
+    fn next(&mut self, input: f32) -> f32 {
+        let output = self.b0 * input + self.z1;
+        self.z1 = self.b1 * input - self.a1 * output + self.z2;
+        self.z2 = self.b2 * input - self.a2 * output;
+        output
+    }
+}
+
+/// A note playing a patch
+pub(crate) struct Voice {
+    pub(crate) pitch: f32,
+    oscillators: Vec<OscillatorState>,
+    envelope: EnvelopeState,
+    filter: Option<Biquad>,
+    /// Frequency in Hz, before oscillator ratios
+    frequency: f64,
+    /// Per-sample factor of the pitch sweep, and the samples it has left
+    sweep: Option<(f64, u32, f64)>,
+    lfos: Vec<LfoState>,
+    /// Samples rendered, for when to move an LFO's cutoff
+    elapsed: u32,
+    gain: f32,
+    noise: u32,
+    sample_rate: f64,
+}
+
+impl Voice {
+    pub(crate) fn new(
+        patch: &Patch,
+        pitch: f32,
+        velocity: u8,
+        sample_rate: f32,
+        seed: u32,
+    ) -> Self {
+        let detune = cents(patch.detune_cents);
+        let sweeping = patch.pitch_envelope.is_some();
+        let oscillators = patch
+            .oscillators
+            .iter()
+            .map(|oscillator| OscillatorState {
+                waveform: oscillator.waveform.clone(),
+                mix: oscillator.mix,
+                // A pitch sweep replaces the oscillators' pitch, octaves
+                // included, as in the playground
+                ratio: cents(oscillator.detune_cents)
+                    * detune
+                    * if sweeping {
+                        1.0
+                    } else {
+                        2f64.powi(oscillator.octave_offset as i32)
+                    },
+                phase: 0.0,
+                colour: [0.0; 7],
+            })
+            .collect();
+
+        let (frequency, sweep) = match patch.pitch_envelope {
+            Some(envelope) => {
+                let start = (envelope.start_hz as f64).max(0.01);
+                let end = (envelope.end_hz as f64).max(0.01);
+                let samples = (envelope.seconds as f64 * sample_rate as f64).max(1.0);
+                let factor = (end / start).powf(1.0 / samples);
+                (start, Some((factor, samples as u32, end)))
+            }
+            None => (frequency(pitch), None),
+        };
+
+        Self {
+            pitch,
+            oscillators,
+            envelope: EnvelopeState::new(&patch.envelope, sample_rate),
+            filter: patch
+                .filter
+                .as_ref()
+                .map(|filter| Biquad::new(filter, sample_rate)),
+            frequency,
+            sweep,
+            lfos: patch
+                .lfos
+                .iter()
+                .map(|&lfo| LfoState { lfo, phase: 0.0 })
+                .collect(),
+            elapsed: 0,
+            gain: velocity.min(127) as f32 / 127.0 * 0.5,
+            noise: seed.max(1),
+            sample_rate: sample_rate as f64,
+        }
+    }
+
+    pub(crate) fn release(&mut self) {
+        self.envelope.release();
+    }
+
+    pub(crate) fn is_releasing(&self) -> bool {
+        matches!(self.envelope.stage, Stage::Release | Stage::Done)
+    }
+
+    pub(crate) fn is_done(&self) -> bool {
+        self.envelope.stage == Stage::Done
+    }
+
+    /// Add the voice's next samples to `output`
+    pub(crate) fn render(&mut self, output: &mut [f32]) {
+        for sample in output {
+            if self.is_done() {
+                return;
+            }
+            // Semitones of bend, Hz of cutoff and amplitude from the LFOs
+            let (mut bend, mut cutoff, mut amplitude) = (0.0, 0.0, 1.0);
+            for lfo in &mut self.lfos {
+                let value = lfo.next(self.sample_rate);
+                let depth = lfo.lfo.depth;
+                match lfo.lfo.target {
+                    LfoTarget::Pitch => bend += value * depth,
+                    LfoTarget::Cutoff => cutoff += value * depth,
+                    LfoTarget::Amplitude => {
+                        let depth = depth.clamp(0.0, 1.0);
+                        amplitude *= 1.0 - depth / 2.0 + value * depth / 2.0;
+                    }
+                }
+            }
+
+            let bent = self.frequency * 2f64.powf(bend as f64 / 12.0);
+            let mut mixed = 0.0;
+            for oscillator in &mut self.oscillators {
+                let frequency = bent * oscillator.ratio;
+                mixed +=
+                    oscillator.next(frequency, self.sample_rate, &mut self.noise) * oscillator.mix;
+            }
+            if let Some(filter) = &mut self.filter {
+                let sweeping = self
+                    .lfos
+                    .iter()
+                    .any(|lfo| lfo.lfo.target == LfoTarget::Cutoff);
+                if sweeping && self.elapsed.is_multiple_of(CUTOFF_UPDATE) {
+                    filter.tune(filter.filter.cutoff + cutoff, self.sample_rate as f32);
+                }
+                mixed = filter.next(mixed);
+            }
+            *sample += mixed * self.envelope.next() * self.gain * amplitude;
+            self.elapsed = self.elapsed.wrapping_add(1);
+
+            if let Some((factor, left, end)) = &mut self.sweep {
+                if *left > 0 {
+                    self.frequency *= *factor;
+                    *left -= 1;
+                } else {
+                    self.frequency = *end;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,392 @@
+//! Signatures of the builtin functions and values
+
+use std::collections::HashMap;
+
+use relanote_core::{intern, Span};
+
+use crate::context::TypeContext;
+use crate::error::TypeError;
+use crate::types::{Type, TypeScheme};
+
+/// Effect presets of the standard prelude, each taking a block or a part
+pub(crate) const EFFECT_PRESETS: &[&str] = &[
+    // Delay
+    "slapback",
+    "short_delay",
+    "stereo_delay",
+    "dotted_eighth_delay",
+    "quarter_delay",
+    "pingpong_delay",
+    "tape_delay",
+    "ambient_delay",
+    "dub_delay",
+    "shimmer_delay",
+    // Phaser
+    "subtle_phaser",
+    "slow_phaser",
+    "classic_phaser",
+    "fast_phaser",
+    "deep_phaser",
+    "jet_phaser",
+    "funk_phaser",
+    "space_phaser",
+    "wobble_phaser",
+    "liquid_phaser",
+    // Distortion
+    "saturation",
+    "light_overdrive",
+    "warm_overdrive",
+    "crunch",
+    "classic_dist",
+    "heavy_dist",
+    "light_fuzz",
+    "heavy_fuzz",
+    "classic_fuzz",
+    "lofi_crush",
+    "bit8_crush",
+    "extreme_crush",
+    // Reverb (room, hall and plate are builtins)
+    "studio_reverb",
+    "spring_reverb",
+    "chamber_reverb",
+    "ambient_reverb",
+    "cathedral_reverb",
+    "infinite_reverb",
+];
+
+/// Synth presets of the standard prelude
+pub(crate) const SYNTH_PRESETS: &[&str] = &[
+    // 8-bit
+    "Chiptune",
+    "Chip8bit",
+    "NES",
+    "GameBoy",
+    // Classic
+    "FatBass",
+    "SoftPad",
+    "Lead",
+    "Pluck",
+    "Strings",
+    "Organ",
+    // Drums
+    "Kick",
+    "Snare",
+    "HiHat",
+    "OpenHat",
+    "Tom",
+    "Clap",
+    // Claps
+    "HandClap",
+    "TightClap",
+    "RoomClap",
+    "Clap808",
+    "Clap909",
+    "VintageClap",
+    "SharpClap",
+    "SoftClap",
+    "StadiumClap",
+    "FingerSnap",
+    // Others
+    "Piano",
+    "EPiano",
+    "WarmPad",
+    "AcidBass",
+];
+
+/// One way of calling a builtin: its parameter names, defaults and type
+#[derive(Clone, Debug)]
+pub(crate) struct Overload {
+    pub params: Vec<Option<&'static str>>,
+    pub defaults: Vec<Option<&'static str>>,
+    pub scheme: TypeScheme,
+}
+
+/// A parameter of a builtin, for placing named and partial arguments
+#[derive(Clone, Debug, PartialEq)]
+pub struct BuiltinParam {
+    /// Names the parameter has in the builtin's signatures (e.g. `block`
+    /// and `part`)
+    pub names: Vec<&'static str>,
+    /// Default value, as written in the signature
+    pub default: Option<&'static str>,
+}
+
+impl BuiltinParam {
+    /// Whether a named argument is for this parameter
+    pub fn is_named(&self, name: &str) -> bool {
+        self.names.contains(&name)
+    }
+}
+
+/// Parameters of the builtin `name`, in order
+///
+/// `None` unless every signature of the builtin takes the same number of
+/// arguments, since only then is it known when a call has all of them.
+pub fn builtin_params(name: &str) -> Option<Vec<BuiltinParam>> {
+    let signature = relanote_meta::builtin(name)?.signature;
+    let overloads = parse_overloads(name, signature, &mut TypeContext::new());
+    let arity = overloads[0].params.len();
+    if overloads
+        .iter()
+        .any(|overload| overload.params.len() != arity)
+    {
+        return None;
+    }
+    let params = (0..arity)
+        .map(|i| {
+            let mut names = Vec::new();
+            for overload in &overloads {
+                if let Some(name) = overload.params[i] {
+                    if !names.contains(&name) {
+                        names.push(name);
+                    }
+                }
+            }
+            BuiltinParam {
+                names,
+                default: overloads[0].defaults[i],
+            }
+        })
+        .collect();
+    Some(params)
+}
+
+/// What a parameter gets in a call
+#[derive(Clone, Debug, PartialEq)]
+pub enum Argument<T> {
+    /// An argument of the call
+    Given(T),
+    /// The parameter's default, as written in the signature
+    Default(&'static str),
+    /// Nothing yet: the call returns a function taking it
+    Missing,
+}
+
+/// Place a call's arguments on the parameters of the builtin `function`
+///
+/// Named arguments go to the parameter with that name, a piped value to the
+/// last parameter, and positional arguments fill the rest from the left.
+/// Parameters still empty take their defaults, or are left missing.
+pub fn place_arguments<T>(
+    function: &str,
+    params: &[BuiltinParam],
+    positional: Vec<T>,
+    named: Vec<(&str, Span, T)>,
+    piped: Option<T>,
+    span: Span,
+) -> Result<Vec<Argument<T>>, TypeError> {
+    let mut slots: Vec<Option<T>> = params.iter().map(|_| None).collect();
+    for (name, name_span, value) in named {
+        let index = params
+            .iter()
+            .position(|param| param.is_named(name))
+            .ok_or_else(|| TypeError::InvalidArgument {
+                message: format!("`{function}` has no parameter `{name}`"),
+                span: name_span,
+            })?;
+        if slots[index].replace(value).is_some() {
+            return Err(TypeError::InvalidArgument {
+                message: format!("`{name}` is passed to `{function}` twice"),
+                span: name_span,
+            });
+        }
+    }
+    if let Some(value) = piped {
+        match slots.last_mut() {
+            Some(slot @ None) => *slot = Some(value),
+            _ => {
+                return Err(TypeError::InvalidArgument {
+                    message: format!("`{function}` has no parameter left for the piped value"),
+                    span,
+                })
+            }
+        }
+    }
+    let mut positional = positional.into_iter();
+    for slot in slots.iter_mut().filter(|slot| slot.is_none()) {
+        *slot = positional.next();
+    }
+    if positional.next().is_some() {
+        return Err(TypeError::InvalidArgument {
+            message: format!(
+                "`{function}` takes {} arguments, but is given more",
+                params.len()
+            ),
+            span,
+        });
+    }
+    Ok(slots
+        .into_iter()
+        .zip(params)
+        .map(|(slot, param)| match (slot, param.default) {
+            (Some(value), _) => Argument::Given(value),
+            (None, Some(default)) => Argument::Default(default),
+            (None, None) => Argument::Missing,
+        })
+        .collect())
+}
+
+impl TypeContext {
+    /// Bind a builtin with a signature written as in
+    /// [`relanote_meta::BUILTINS`]
+    ///
+    /// The name is bound to its first signature; calls choose among all of
+    /// them.
+    pub(crate) fn bind_builtin(&mut self, name: &str, signature: &'static str) {
+        let overloads = parse_overloads(name, signature, self);
+        let name = intern(name);
+        self.bind(name, overloads[0].scheme.clone());
+        self.add_overloads(name, overloads);
+    }
+}
+
+fn parse_overloads(name: &str, signature: &'static str, ctx: &mut TypeContext) -> Vec<Overload> {
+    signature
+        .split('|')
+        .map(|overload| {
+            let mut parser = SignatureParser {
+                tokens: tokenize(overload),
+                pos: 0,
+                vars: HashMap::new(),
+                ctx: &mut *ctx,
+            };
+            let (params, defaults, ty) = parser.overload();
+            if parser.pos != parser.tokens.len() {
+                panic!("malformed signature for builtin `{name}`: {signature}");
+            }
+            Overload {
+                params,
+                defaults,
+                scheme: TypeScheme::poly(ty.free_vars(), ty),
+            }
+        })
+        .collect()
+}
+
+fn tokenize(signature: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut rest = signature.trim_start();
+    while !rest.is_empty() {
+        let len = if rest.starts_with("->") {
+            2
+        } else if rest.starts_with(|c: char| c.is_ascii_digit()) {
+            rest.find(|c: char| !(c.is_ascii_digit() || c == '.'))
+                .unwrap_or(rest.len())
+        } else if rest.starts_with(|c: char| c.is_alphanumeric() || c == '_') {
+            rest.find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len())
+        } else {
+            rest.chars().next().map_or(1, char::len_utf8)
+        };
+        tokens.push(&rest[..len]);
+        rest = rest[len..].trim_start();
+    }
+    tokens
+}
+
+struct SignatureParser<'a, 'c> {
+    tokens: Vec<&'a str>,
+    pos: usize,
+    /// Type variables by name
+    vars: HashMap<&'a str, Type>,
+    ctx: &'c mut TypeContext,
+}
+
+impl<'a> SignatureParser<'a, '_> {
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> &'a str {
+        let token = self.peek().expect("unexpected end of builtin signature");
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        let found = self.peek() == Some(token);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, token: &str) {
+        let found = self.next();
+        assert_eq!(found, token, "malformed builtin signature");
+    }
+
+    /// `name: Param = default -> ... -> Result`, where each parameter is a
+    /// single (possibly parenthesized) type
+    #[allow(clippy::type_complexity)]
+    fn overload(&mut self) -> (Vec<Option<&'a str>>, Vec<Option<&'a str>>, Type) {
+        let mut params = Vec::new();
+        let mut defaults = Vec::new();
+        let mut types = Vec::new();
+        loop {
+            let name = match (self.peek(), self.tokens.get(self.pos + 1)) {
+                (Some(name), Some(&":")) => {
+                    self.pos += 2;
+                    Some(name)
+                }
+                _ => None,
+            };
+            types.push(self.atom());
+            params.push(name);
+            defaults.push(if self.eat("=") {
+                Some(self.next())
+            } else {
+                None
+            });
+            if !self.eat("->") {
+                break;
+            }
+        }
+        params.pop();
+        defaults.pop();
+        let result = types.pop().unwrap();
+        (params, defaults, Type::function_n(types, result))
+    }
+
+    fn ty(&mut self) -> Type {
+        let param = self.atom();
+        if self.eat("->") {
+            Type::function(param, self.ty())
+        } else {
+            param
+        }
+    }
+
+    fn atom(&mut self) -> Type {
+        match self.next() {
+            "(" if self.eat(")") => Type::Unit,
+            "(" => {
+                let mut elems = vec![self.ty()];
+                while self.eat(",") {
+                    elems.push(self.ty());
+                }
+                self.expect(")");
+                if elems.len() == 1 {
+                    elems.pop().unwrap()
+                } else {
+                    Type::Tuple(elems)
+                }
+            }
+            "[" => {
+                let elem = self.ty();
+                self.expect("]");
+                Type::array(elem)
+            }
+            var if var.starts_with(|c: char| c.is_ascii_lowercase()) => {
+                if let Some(ty) = self.vars.get(var) {
+                    return ty.clone();
+                }
+                let ty = self.ctx.fresh_var();
+                self.vars.insert(var, ty.clone());
+                ty
+            }
+            name => Type::from_name(name)
+                .unwrap_or_else(|| panic!("unknown type `{name}` in builtin signature")),
+        }
+    }
+}
@@ -1,14 +1,37 @@
+use std::collections::{HashMap, HashSet};
+
+use indexmap::IndexMap;
 use relanote_ast::*;
-use relanote_core::{intern, Diagnostic, Diagnostics};
+use relanote_core::{intern, Diagnostic, DiagnosticCode, Diagnostics, InternedStr, Span};
 
+use crate::builtins::{EFFECT_PRESETS, SYNTH_PRESETS};
 use crate::context::TypeContext;
 use crate::error::TypeError;
+use crate::inference::SignatureVars;
+use crate::interface::ModuleInterface;
+use crate::lint;
 use crate::types::{Type, TypeScheme};
 
 /// Type checker for relanote programs
 pub struct TypeChecker {
     ctx: TypeContext,
     diagnostics: Diagnostics,
+    /// Builtin function names (redefining one is a warning)
+    builtin_functions: HashSet<InternedStr>,
+    /// Names from the standard prelude with what they are (redefining one
+    /// is a warning)
+    prelude_names: HashMap<InternedStr, &'static str>,
+    /// Interfaces of the modules `use` can import from, by module path
+    modules: HashMap<String, ModuleInterface>,
+    /// Names bound at the top level of the program, in order, with the span
+    /// of their definition
+    top_level: IndexMap<InternedStr, Span>,
+    /// Top-level names the program exports
+    exports: HashSet<InternedStr>,
+    /// Types top-level bindings are declared with
+    declared: HashMap<InternedStr, TypeAnnotation>,
+    /// Doc comments of top-level bindings, including imported ones
+    docs: HashMap<InternedStr, String>,
 }
 
 impl TypeChecker {
@@ -16,350 +39,138 @@ impl TypeChecker {
         let mut checker = Self {
             ctx: TypeContext::new(),
             diagnostics: Diagnostics::new(),
+            builtin_functions: HashSet::new(),
+            prelude_names: HashMap::new(),
+            modules: HashMap::new(),
+            top_level: IndexMap::new(),
+            exports: HashSet::new(),
+            declared: HashMap::new(),
+            docs: HashMap::new(),
         };
         checker.add_builtins();
+        checker.builtin_functions = checker
+            .ctx
+            .names()
+            .filter(|name| {
+                checker
+                    .ctx
+                    .lookup(name)
+                    .is_some_and(|scheme| matches!(scheme.ty, Type::Function(..)))
+            })
+            .copied()
+            .collect();
         checker
     }
 
     /// Add built-in functions to the context
     fn add_builtins(&mut self) {
-        // reverse : Block -> Block
-        self.ctx.bind(
-            intern("reverse"),
-            TypeScheme::mono(Type::function(Type::Block, Type::Block)),
-        );
-
-        // transpose : Interval -> Block -> Block
-        self.ctx.bind(
-            intern("transpose"),
-            TypeScheme::mono(Type::function_n(
-                vec![Type::Interval, Type::Block],
-                Type::Block,
-            )),
-        );
-
-        // repeat : Int -> Block -> Block
-        self.ctx.bind(
-            intern("repeat"),
-            TypeScheme::mono(Type::function_n(vec![Type::Int, Type::Block], Type::Block)),
-        );
-
-        // metronome : Int -> Int -> Block
-        self.ctx.bind(
-            intern("metronome"),
-            TypeScheme::mono(Type::function_n(vec![Type::Int, Type::Int], Type::Block)),
-        );
-
-        // swing : Block -> Block
-        self.ctx.bind(
-            intern("swing"),
-            TypeScheme::mono(Type::function(Type::Block, Type::Block)),
-        );
-
-        // double_time : Block -> Block
-        self.ctx.bind(
-            intern("double_time"),
-            TypeScheme::mono(Type::function(Type::Block, Type::Block)),
-        );
-
-        // reverb : Float -> Block -> Part
-        self.ctx.bind(
-            intern("reverb"),
-            TypeScheme::mono(Type::function_n(vec![Type::Float, Type::Block], Type::Part)),
-        );
-
-        // hall_reverb : Block -> Part
-        self.ctx.bind(
-            intern("hall_reverb"),
-            TypeScheme::mono(Type::function(Type::Block, Type::Part)),
-        );
-
-        // room_reverb : Block -> Part
-        self.ctx.bind(
-            intern("room_reverb"),
-            TypeScheme::mono(Type::function(Type::Block, Type::Part)),
-        );
-
-        // plate_reverb : Block -> Part
-        self.ctx.bind(
-            intern("plate_reverb"),
-            TypeScheme::mono(Type::function(Type::Block, Type::Part)),
-        );
-
-        // dry : Block -> Part
-        self.ctx.bind(
-            intern("dry"),
-            TypeScheme::mono(Type::function(Type::Block, Type::Part)),
-        );
-
-        // volume : Float -> Block -> Part
-        self.ctx.bind(
-            intern("volume"),
-            TypeScheme::mono(Type::function_n(vec![Type::Float, Type::Block], Type::Part)),
-        );
-
-        // delay : Float -> Float -> Float -> Part -> Part
-        self.ctx.bind(
-            intern("delay"),
-            TypeScheme::mono(Type::function_n(
-                vec![Type::Float, Type::Float, Type::Float, Type::Part],
-                Type::Part,
-            )),
-        );
-
-        // phaser : Float -> Float -> Float -> Part -> Part
-        self.ctx.bind(
-            intern("phaser"),
-            TypeScheme::mono(Type::function_n(
-                vec![Type::Float, Type::Float, Type::Float, Type::Part],
-                Type::Part,
-            )),
-        );
-
-        // distortion : Float -> DistortionType -> Float -> Part -> Part
-        self.ctx.bind(
-            intern("distortion"),
-            TypeScheme::mono(Type::function_n(
-                vec![Type::Float, Type::DistortionType, Type::Float, Type::Part],
-                Type::Part,
-            )),
-        );
-
-        // Distortion type constructors
-        self.ctx
-            .bind(intern("SoftClip"), TypeScheme::mono(Type::DistortionType));
-        self.ctx
-            .bind(intern("HardClip"), TypeScheme::mono(Type::DistortionType));
-        self.ctx
-            .bind(intern("Fuzz"), TypeScheme::mono(Type::DistortionType));
-        self.ctx
-            .bind(intern("BitCrush"), TypeScheme::mono(Type::DistortionType));
-
-        // Delay presets : Part -> Part
-        for name in [
-            "slapback",
-            "short_delay",
-            "stereo_delay",
-            "dotted_eighth_delay",
-            "quarter_delay",
-            "pingpong_delay",
-            "tape_delay",
-            "ambient_delay",
-            "dub_delay",
-            "shimmer_delay",
-        ] {
-            self.ctx.bind(
-                intern(name),
-                TypeScheme::mono(Type::function(Type::Part, Type::Part)),
-            );
+        for builtin in relanote_meta::BUILTINS {
+            self.ctx.bind_builtin(builtin.name, builtin.signature);
         }
-
-        // Phaser presets : Part -> Part
-        for name in [
-            "subtle_phaser",
-            "slow_phaser",
-            "classic_phaser",
-            "fast_phaser",
-            "deep_phaser",
-            "jet_phaser",
-            "funk_phaser",
-            "space_phaser",
-            "wobble_phaser",
-            "liquid_phaser",
-        ] {
-            self.ctx.bind(
-                intern(name),
-                TypeScheme::mono(Type::function(Type::Part, Type::Part)),
-            );
+        for name in EFFECT_PRESETS {
+            self.ctx
+                .bind_builtin(name, "block: Block -> Part | part: Part -> Part");
         }
-
-        // Distortion presets : Part -> Part
-        for name in [
-            "saturation",
-            "light_overdrive",
-            "warm_overdrive",
-            "crunch",
-            "classic_dist",
-            "heavy_dist",
-            "light_fuzz",
-            "heavy_fuzz",
-            "classic_fuzz",
-            "lofi_crush",
-            "bit8_crush",
-            "extreme_crush",
-        ] {
-            self.ctx.bind(
-                intern(name),
-                TypeScheme::mono(Type::function(Type::Part, Type::Part)),
-            );
-        }
-
-        // Additional reverb presets : Part -> Part
-        // (room_reverb, hall_reverb, plate_reverb already defined above as Block -> Part)
-        for name in [
-            "studio_reverb",
-            "spring_reverb",
-            "chamber_reverb",
-            "ambient_reverb",
-            "cathedral_reverb",
-            "infinite_reverb",
-        ] {
-            self.ctx.bind(
-                intern(name),
-                TypeScheme::mono(Type::function(Type::Part, Type::Part)),
-            );
-        }
-
-        // Synth functions
-        // voice : Synth -> Block -> Part
-        self.ctx.bind(
-            intern("voice"),
-            TypeScheme::mono(Type::function_n(vec![Type::Synth, Type::Block], Type::Part)),
-        );
-
-        // cutoff : Float -> Part -> Part
-        self.ctx.bind(
-            intern("cutoff"),
-            TypeScheme::mono(Type::function_n(vec![Type::Float, Type::Part], Type::Part)),
-        );
-
-        // resonance : Float -> Part -> Part
-        self.ctx.bind(
-            intern("resonance"),
-            TypeScheme::mono(Type::function_n(vec![Type::Float, Type::Part], Type::Part)),
-        );
-
-        // detune : Float -> Part -> Part
-        self.ctx.bind(
-            intern("detune"),
-            TypeScheme::mono(Type::function_n(vec![Type::Float, Type::Part], Type::Part)),
-        );
-
-        // adsr : Float -> Float -> Float -> Float -> Part -> Part
-        self.ctx.bind(
-            intern("adsr"),
-            TypeScheme::mono(Type::function_n(
-                vec![
-                    Type::Float,
-                    Type::Float,
-                    Type::Float,
-                    Type::Float,
-                    Type::Part,
-                ],
-                Type::Part,
-            )),
-        );
-
-        // Synth presets (8-bit)
-        self.ctx
-            .bind(intern("Chiptune"), TypeScheme::mono(Type::Synth));
-        self.ctx
-            .bind(intern("Chip8bit"), TypeScheme::mono(Type::Synth));
-        self.ctx.bind(intern("NES"), TypeScheme::mono(Type::Synth));
-        self.ctx
-            .bind(intern("GameBoy"), TypeScheme::mono(Type::Synth));
-
-        // Synth presets (classic)
-        self.ctx
-            .bind(intern("FatBass"), TypeScheme::mono(Type::Synth));
-        self.ctx
-            .bind(intern("SoftPad"), TypeScheme::mono(Type::Synth));
-        self.ctx.bind(intern("Lead"), TypeScheme::mono(Type::Synth));
-        self.ctx
-            .bind(intern("Pluck"), TypeScheme::mono(Type::Synth));
-        self.ctx
-            .bind(intern("Strings"), TypeScheme::mono(Type::Synth));
-        self.ctx
-            .bind(intern("Organ"), TypeScheme::mono(Type::Synth));
-
-        // Synth presets (drums)
-        self.ctx.bind(intern("Kick"), TypeScheme::mono(Type::Synth));
-        self.ctx
-            .bind(intern("Snare"), TypeScheme::mono(Type::Synth));
-        self.ctx
-            .bind(intern("HiHat"), TypeScheme::mono(Type::Synth));
-        self.ctx
-            .bind(intern("OpenHat"), TypeScheme::mono(Type::Synth));
-        self.ctx.bind(intern("Tom"), TypeScheme::mono(Type::Synth));
-        self.ctx.bind(intern("Clap"), TypeScheme::mono(Type::Synth));
-
-        // Synth presets (claps)
-        for name in [
-            "HandClap",
-            "TightClap",
-            "RoomClap",
-            "Clap808",
-            "Clap909",
-            "VintageClap",
-            "SharpClap",
-            "SoftClap",
-            "StadiumClap",
-            "FingerSnap",
-        ] {
+        for name in SYNTH_PRESETS {
             self.ctx.bind(intern(name), TypeScheme::mono(Type::Synth));
         }
+    }
 
-        // Additional synth presets
-        for name in [
-            "Piano",
-            "EPiano",
-            "WarmPad",
-            "AcidBass",
-        ] {
-            self.ctx.bind(intern(name), TypeScheme::mono(Type::Synth));
+    /// Make the standard prelude's bindings available without a `use`
+    ///
+    /// Names that are already bound, such as builtin synth presets, keep
+    /// their builtin types.
+    pub fn add_prelude(&mut self, prelude: &ModuleInterface) {
+        for (name, scheme) in prelude.iter() {
+            let kind = match scheme.ty {
+                Type::Scale => "scale",
+                Type::Chord => "chord",
+                Type::Synth => "synth",
+                _ => "binding",
+            };
+            self.prelude_names.insert(*name, kind);
+            if self.ctx.lookup(name).is_none() {
+                self.ctx.bind(*name, scheme.clone());
+            }
+            if let Some(doc) = prelude.doc(name) {
+                self.docs.insert(*name, doc.to_string());
+            }
         }
+    }
 
-        // map : (a -> b) -> [a] -> [b]
-        let a = self.ctx.fresh_var();
-        let b = self.ctx.fresh_var();
-        self.ctx.bind(
-            intern("map"),
-            TypeScheme::mono(Type::function_n(
-                vec![Type::function(a.clone(), b.clone()), Type::array(a)],
-                Type::array(b),
-            )),
-        );
-
-        // compose : [Section] -> Song
-        self.ctx.bind(
-            intern("compose"),
-            TypeScheme::mono(Type::function(Type::array(Type::Section), Type::Song)),
-        );
-
-        // play : Block -> Part -> Part
-        self.ctx.bind(
-            intern("play"),
-            TypeScheme::mono(Type::function_n(vec![Type::Block, Type::Part], Type::Part)),
-        );
-
-        // apply_env : Envelope -> Part -> Part
-        self.ctx.bind(
-            intern("apply_env"),
-            TypeScheme::mono(Type::function_n(
-                vec![Type::Envelope, Type::Part],
-                Type::Part,
-            )),
-        );
-
-        // render : Song -> () -> ()
-        self.ctx.bind(
-            intern("render"),
-            TypeScheme::mono(Type::function_n(vec![Type::Song, Type::Unit], Type::Unit)),
-        );
+    /// Make a module's bindings available to `use` declarations
+    ///
+    /// `name` is the module path as written in `use`, e.g. `synths::bass`.
+    /// Modules that were never added are left to the caller to report.
+    pub fn add_module(&mut self, name: impl Into<String>, interface: ModuleInterface) {
+        self.modules.insert(name.into(), interface);
     }
 
     /// Type check a program
     pub fn check_program(&mut self, program: &Program) -> Diagnostics {
         for item in &program.items {
+            // A definition replaces the doc of any name it shadows
+            if let Some(name) = item.node.defined_name() {
+                match item.node.doc() {
+                    Some(doc) => self.docs.insert(name.name, doc.to_string()),
+                    None => self.docs.remove(&name.name),
+                };
+            }
             if let Err(err) = self.check_item(item) {
-                self.diagnostics
-                    .add(Diagnostic::error(err.to_string(), err.span()));
+                self.report(err);
+            }
+            for diagnostic in self.ctx.take_diagnostics() {
+                self.diagnostics.add(diagnostic);
             }
         }
 
+        for item in &program.items {
+            if let Item::Export(ExportDecl::Named(names)) = &item.node {
+                for name in names {
+                    if !self.top_level.contains_key(&name.name) {
+                        self.report(TypeError::UndefinedVariable {
+                            name: name.name.to_string(),
+                            span: item.span,
+                        });
+                    }
+                }
+            }
+        }
+        self.exports
+            .extend(program.exported_names().into_iter().map(|name| name.name));
+        let unplayed = lint::unplayed(program, &self.exports, |name| {
+            let scheme = self.ctx.lookup(name)?;
+            Some(self.ctx.apply(&scheme.ty))
+        });
+        for diagnostic in lint::unused(program, &self.exports)
+            .into_iter()
+            .chain(unplayed)
+        {
+            self.diagnostics.add(diagnostic);
+        }
+
         std::mem::take(&mut self.diagnostics)
     }
 
+    /// The exported bindings of the checked program, for modules that use it
+    pub fn interface(&self) -> ModuleInterface {
+        let mut interface = ModuleInterface::new();
+        for (name, span) in &self.top_level {
+            if !self.exports.contains(name) {
+                interface.insert_private(*name, *span);
+                continue;
+            }
+            if let Some(scheme) = self.ctx.lookup(name) {
+                let ty = self.ctx.apply(&scheme.ty);
+                interface.insert(*name, TypeScheme::poly(ty.free_vars(), ty));
+            }
+            if let Some(doc) = self.docs.get(name) {
+                interface.insert_doc(*name, doc.clone());
+            }
+        }
+        interface
+    }
+
     /// Look up the type of a name (for hover info)
     pub fn lookup_type(&self, name: &str) -> Option<Type> {
         let interned = intern(name);
@@ -369,36 +180,227 @@ impl TypeChecker {
         })
     }
 
+    /// The type a top-level binding is declared with, as written
+    pub fn declared_type(&self, name: &str) -> Option<&TypeAnnotation> {
+        self.declared.get(&intern(name))
+    }
+
+    /// The doc comment of a top-level binding, which may be imported
+    pub fn doc(&self, name: &str) -> Option<&str> {
+        self.docs.get(&intern(name)).map(String::as_str)
+    }
+
+    /// The name in scope that `name` is most likely a misspelling of, for
+    /// "did you mean" suggestions
+    ///
+    /// Names are compared by edit distance, allowing one edit for every
+    /// three characters of `name`.
+    pub fn similar_name(&self, name: &str) -> Option<InternedStr> {
+        let max_distance = (name.chars().count() / 3).max(1);
+        self.ctx
+            .names()
+            .filter(|candidate| candidate.as_str() != name)
+            .map(|candidate| (edit_distance(name, candidate.as_str()), *candidate))
+            .filter(|(distance, _)| *distance <= max_distance)
+            // Closest first, then alphabetically so the pick is stable
+            .min_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.as_str().cmp(b.1.as_str())))
+            .map(|(_, candidate)| candidate)
+    }
+
+    /// Top-level bindings of the checked program, including imported ones,
+    /// in order with their types
+    pub fn top_level_bindings(&self) -> Vec<(InternedStr, Type)> {
+        self.top_level
+            .keys()
+            .filter_map(|name| {
+                let scheme = self.ctx.lookup(name)?;
+                Some((*name, self.ctx.apply(&scheme.ty)))
+            })
+            .collect()
+    }
+
+    fn report(&mut self, err: TypeError) {
+        let mut diagnostic = Diagnostic::error(err.to_string(), err.span()).with_code(err.code());
+        if let TypeError::PrivateModuleItem {
+            name, definition, ..
+        } = &err
+        {
+            // Standard library sources have no file to point into
+            if !definition.source.is_dummy() {
+                diagnostic =
+                    diagnostic.with_label(*definition, format!("`{name}` is defined here"));
+            }
+            diagnostic = diagnostic.with_note("only `export`ed bindings can be imported");
+        }
+        self.diagnostics.add(diagnostic);
+    }
+
+    /// Bind a top-level name defined at `span`
+    fn bind_top_level(&mut self, name: InternedStr, scheme: TypeScheme, span: Span) {
+        self.ctx.bind(name, scheme);
+        self.top_level.insert(name, span);
+    }
+
+    /// Bind the names a `use` declaration imports from a known module
+    fn check_use(&mut self, use_decl: &UseDecl, span: Span) {
+        let module = use_decl.path.module_name();
+        let Some(interface) = self.modules.get(&module).cloned() else {
+            return;
+        };
+
+        let imports: Vec<(InternedStr, InternedStr)> = match &use_decl.path.kind {
+            UseKind::Simple => match use_decl.path.segments.as_slice() {
+                [_, .., last] => vec![(last.name, last.name)],
+                _ => Vec::new(),
+            },
+            UseKind::Glob => interface.iter().map(|(name, _)| (*name, *name)).collect(),
+            UseKind::Group(items) => items
+                .iter()
+                .map(|item| {
+                    let alias = item.alias.as_ref().unwrap_or(&item.name);
+                    (item.name.name, alias.name)
+                })
+                .collect(),
+        };
+
+        for (name, alias) in imports {
+            match (interface.get(&name), interface.private_definition(&name)) {
+                (Some(scheme), _) => {
+                    self.bind_top_level(alias, scheme.clone(), span);
+                    if let Some(doc) = interface.doc(&name) {
+                        self.docs.insert(alias, doc.to_string());
+                    }
+                }
+                (None, Some(definition)) => self.report(TypeError::PrivateModuleItem {
+                    module: module.clone(),
+                    name: name.to_string(),
+                    span,
+                    definition,
+                }),
+                (None, None) => self.report(TypeError::UnknownModuleItem {
+                    module: module.clone(),
+                    name: name.to_string(),
+                    span,
+                }),
+            }
+        }
+    }
+
+    /// Warn when a top-level definition hides a builtin function or a name
+    /// from the standard prelude
+    fn check_shadowing(&mut self, name: &Ident, span: Span) {
+        if let Some(kind) = self.prelude_names.remove(&name.name) {
+            self.diagnostics.add(
+                Diagnostic::warning(
+                    format!(
+                        "`{}` shadows the standard library {} of the same name",
+                        name.name, kind
+                    ),
+                    span,
+                )
+                .with_code(DiagnosticCode::ShadowedPrelude)
+                .with_note("the standard library's definition is no longer reachable by this name"),
+            );
+        }
+        if self.builtin_functions.remove(&name.name) {
+            self.diagnostics.add(
+                Diagnostic::warning(
+                    format!(
+                        "`{}` shadows the builtin function of the same name",
+                        name.name
+                    ),
+                    span,
+                )
+                .with_code(DiagnosticCode::ShadowedBuiltin)
+                .with_note("the builtin is no longer reachable by this name"),
+            );
+        }
+    }
+
+    /// Infer the type of a function definition, checking it against the
+    /// types its parameters and result are declared with
+    fn check_function(&mut self, func_def: &FunctionDef) -> Result<Type, TypeError> {
+        let mut vars = SignatureVars::new();
+        let param_types = self.ctx.bind_params(&func_def.params, &mut vars)?;
+        let body_ty = self.ctx.infer_expr(&func_def.body)?;
+        let declared_ty = match &func_def.return_type {
+            Some(ann) => self
+                .ctx
+                .annotation_type(ann, &mut vars, func_def.body.span)?,
+            None => body_ty.clone(),
+        };
+
+        let declared = Type::function_n(param_types.clone(), declared_ty);
+        let inferred = Type::function_n(param_types, body_ty);
+        self.ctx
+            .check_signature(&vars, &declared, &inferred, func_def.body.span)?;
+        Ok(self.ctx.apply(&declared))
+    }
+
     /// Type check an item
     fn check_item(&mut self, item: &relanote_core::Spanned<Item>) -> Result<(), TypeError> {
         match &item.node {
             Item::ScaleDef(scale_def) => {
-                self.ctx
-                    .bind(scale_def.name.name, TypeScheme::mono(Type::Scale));
-                Ok(())
+                let base_ty = scale_def.base.as_ref().map(|base| {
+                    let ty = self.ctx.infer_expr(base)?;
+                    self.ctx.unify(&ty, &Type::Scale, base.span)
+                });
+                self.check_shadowing(&scale_def.name, item.span);
+                self.bind_top_level(
+                    scale_def.name.name,
+                    TypeScheme::mono(Type::Scale),
+                    item.span,
+                );
+                base_ty.unwrap_or(Ok(()))
             }
 
             Item::ChordDef(chord_def) => {
-                self.ctx
-                    .bind(chord_def.name.name, TypeScheme::mono(Type::Chord));
+                self.check_shadowing(&chord_def.name, item.span);
+                self.bind_top_level(
+                    chord_def.name.name,
+                    TypeScheme::mono(Type::Chord),
+                    item.span,
+                );
                 Ok(())
             }
 
             Item::SynthDef(synth_def) => {
-                self.ctx
-                    .bind(synth_def.name.name, TypeScheme::mono(Type::Synth));
+                self.check_shadowing(&synth_def.name, item.span);
+                self.bind_top_level(
+                    synth_def.name.name,
+                    TypeScheme::mono(Type::Synth),
+                    item.span,
+                );
                 Ok(())
             }
 
             Item::LetBinding(binding) => {
-                let value_ty = self.ctx.infer_expr(&binding.value)?;
-                let scheme = self.ctx.generalize(&value_ty);
+                let value_ty =
+                    self.ctx
+                        .infer_expr(&binding.value)
+                        .and_then(|ty| match &binding.type_ann {
+                            Some(ann) => self.ctx.check_annotation(ann, &ty, binding.value.span),
+                            None => Ok(ty),
+                        });
+                // A binding whose value has an error is still bound, so its
+                // uses aren't reported as undefined
+                let scheme = match &value_ty {
+                    Ok(ty) => self.ctx.generalize(ty),
+                    Err(_) => TypeScheme::mono(Type::Error),
+                };
 
                 if let Pattern::Ident(ident) = &binding.pattern.node {
-                    self.ctx.bind(ident.name, scheme);
+                    self.check_shadowing(ident, binding.pattern.span);
+                    self.bind_top_level(ident.name, scheme, binding.pattern.span);
+                    if self.ctx.pipes_last(&binding.value) {
+                        self.ctx.bind_builtin_value(ident.name);
+                    }
+                    if let Some(ann) = &binding.type_ann {
+                        self.declared.insert(ident.name, ann.clone());
+                    }
                 }
 
-                Ok(())
+                value_ty.map(|_| ())
             }
 
             Item::SetBinding(binding) => {
@@ -408,32 +410,59 @@ impl TypeChecker {
                 Ok(())
             }
 
-            Item::FunctionDef(func_def) => {
-                self.ctx.push_scope();
+            Item::Meta(meta) => {
+                for field in &meta.fields {
+                    let value_ty = self.ctx.infer_expr(&field.node.value)?;
+                    self.ctx
+                        .unify(&value_ty, &Type::String, field.node.value.span)?;
+                }
+                Ok(())
+            }
 
-                let mut param_types = Vec::new();
-                for param in &func_def.params {
-                    let param_ty = self.ctx.fresh_var();
-                    if let Pattern::Ident(ident) = &param.node {
-                        self.ctx.bind_mono(ident.name, param_ty.clone());
-                    }
-                    param_types.push(param_ty);
+            // A test can produce a Bool or music, which `relanote test` tells apart
+            Item::Test(test) => {
+                self.ctx.infer_expr(&test.body)?;
+                Ok(())
+            }
+
+            // A section renders as a song of its own
+            Item::Render(render) => {
+                let target_ty = self.ctx.infer_expr(&render.target)?;
+                if self.ctx.apply(&target_ty) != Type::Section {
+                    self.ctx
+                        .unify(&target_ty, &Type::Song, render.target.span)?;
                 }
+                Ok(())
+            }
 
-                let body_ty = self.ctx.infer_expr(&func_def.body)?;
+            Item::FunctionDef(func_def) => {
+                self.check_shadowing(&func_def.name, item.span);
+                self.ctx.push_scope();
+                let func_ty = self.check_function(func_def);
                 self.ctx.pop_scope();
 
-                let func_ty = Type::function_n(param_types, body_ty);
-                let scheme = self.ctx.generalize(&func_ty);
-                self.ctx.bind(func_def.name.name, scheme);
+                let scheme = match &func_ty {
+                    Ok(ty) => self.ctx.generalize(ty),
+                    Err(_) => TypeScheme::mono(Type::Error),
+                };
+                self.bind_top_level(func_def.name.name, scheme, item.span);
+                if let Some(signature) = func_def.signature() {
+                    self.declared.insert(func_def.name.name, signature);
+                }
 
-                Ok(())
+                func_ty.map(|_| ())
             }
 
             Item::Import(_) => Ok(()),
+            Item::Export(ExportDecl::Definition(def)) => {
+                self.check_item(&relanote_core::Spanned::new((**def).clone(), item.span))
+            }
             Item::Export(_) => Ok(()),
             Item::Mod(_) => Ok(()),
-            Item::Use(_) => Ok(()),
+            Item::Use(use_decl) => {
+                self.check_use(use_decl, item.span);
+                Ok(())
+            }
 
             Item::ExprStmt(expr) => {
                 self.ctx.infer_expr(expr)?;
@@ -443,6 +472,23 @@ impl TypeChecker {
     }
 }
 
+/// Number of single-character insertions, deletions and substitutions
+/// turning `a` into `b`
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+    row[b.len()]
+}
+
 impl Default for TypeChecker {
     fn default() -> Self {
         Self::new()
@@ -455,6 +501,23 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn test_similar_name() {
+        let (program, _) = parse("let melody = | R M3 |\nlet x = melodi |> transpos P5");
+        let mut checker = TypeChecker::new();
+        checker.check_program(&program);
+        assert_eq!(
+            checker.similar_name("transpos").map(|n| n.to_string()),
+            Some("transpose".to_string())
+        );
+        assert_eq!(
+            checker.similar_name("melodi").map(|n| n.to_string()),
+            Some("melody".to_string())
+        );
+        assert_eq!(checker.similar_name("xyzzy"), None);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+
     #[test]
     fn test_check_let_binding() {
         let (program, parse_diags) = parse("let x = 42");
@@ -484,4 +547,305 @@ mod tests {
         let type_diags = checker.check_program(&program);
         assert!(!type_diags.has_errors(), "Type errors: {:?}", type_diags);
     }
+
+    #[test]
+    fn test_builtin_calls_through_pipes() {
+        let (program, _) = parse(
+            "let a = | R | |> transpose P5 |> repeat 3\n\
+             let b = | R | |> voice Lead |> volume 80 |> cutoff 2000.0\n\
+             let n = [1, 2, 3] |> map (\\x -> x * 2) |> len",
+        );
+        let mut checker = TypeChecker::new();
+        let type_diags = checker.check_program(&program);
+        assert!(!type_diags.has_errors(), "Type errors: {:?}", type_diags);
+
+        assert_eq!(checker.lookup_type("a"), Some(Type::Block));
+        assert_eq!(checker.lookup_type("b"), Some(Type::Part));
+        assert_eq!(checker.lookup_type("n"), Some(Type::Int));
+    }
+
+    #[test]
+    fn test_pipes_into_builtin_partials() {
+        // A partially applied builtin takes the piped value last too
+        let (program, _) = parse(
+            "let e = delay(feedback: 0.2, mix: 0.3)\n\
+             let a = | R | |> e 100\n\
+             let b = | R | |> delay(feedback: 0.2) 100",
+        );
+        let mut checker = TypeChecker::new();
+        let type_diags = checker.check_program(&program);
+        assert!(!type_diags.has_errors(), "Type errors: {:?}", type_diags);
+
+        assert_eq!(checker.lookup_type("a"), Some(Type::Part));
+        assert_eq!(checker.lookup_type("b"), Some(Type::Part));
+    }
+
+    #[test]
+    fn test_argument_errors_name_the_parameter() {
+        let source = "let a = | R | |> transpose P5 |> repeat P5\nlet b = a ++ a";
+        let (program, _) = parse(source);
+        let mut checker = TypeChecker::new();
+        let type_diags = checker.check_program(&program);
+
+        // `b` is still bound, so only the bad argument is reported
+        let errors: Vec<_> = type_diags.errors().collect();
+        assert_eq!(errors.len(), 1, "{:?}", type_diags);
+        assert_eq!(
+            errors[0].message,
+            "`repeat` expects Int for `times`, found Interval"
+        );
+        let start = source.rfind("P5").unwrap();
+        assert_eq!(
+            (errors[0].span.start, errors[0].span.end),
+            (start, start + 2)
+        );
+    }
+
+    #[test]
+    fn test_named_and_partial_builtin_calls() {
+        let (program, _) = parse(
+            "let echo = delay(time: 250, mix: 0.3)\n\
+             let a = | R | |> echo\n\
+             let b = | R | |> volume 0.5 |> delay(mix: 0.3, time: 250)\n\
+             let c = delay 250 0.4",
+        );
+        let mut checker = TypeChecker::new();
+        let type_diags = checker.check_program(&program);
+        assert!(!type_diags.has_errors(), "Type errors: {:?}", type_diags);
+
+        assert_eq!(
+            checker.lookup_type("echo"),
+            Some(Type::function(Type::Block, Type::Part))
+        );
+        assert_eq!(checker.lookup_type("a"), Some(Type::Part));
+        assert_eq!(checker.lookup_type("b"), Some(Type::Part));
+        assert_eq!(
+            checker.lookup_type("c"),
+            Some(Type::function_n(vec![Type::Float, Type::Block], Type::Part))
+        );
+
+        let (program, _) = parse("let d = | R | |> delay(time: 250, speed: 2)");
+        let type_diags = TypeChecker::new().check_program(&program);
+        let errors: Vec<_> = type_diags.errors().collect();
+        assert_eq!(errors.len(), 1, "{:?}", type_diags);
+        assert_eq!(errors[0].message, "`delay` has no parameter `speed`");
+    }
+
+    #[test]
+    fn test_duration_arithmetic() {
+        let (program, _) = parse(
+            "let a = 1/4 + 2 beats\n\
+             let b = 1 bar * 2\n\
+             let c = 0.5 * 1/8.\n\
+             let d = | R M3 |:2 |> assert_beats 2 beats",
+        );
+        let mut checker = TypeChecker::new();
+        let type_diags = checker.check_program(&program);
+        assert!(!type_diags.has_errors(), "Type errors: {:?}", type_diags);
+        assert_eq!(checker.lookup_type("a"), Some(Type::Duration));
+        assert_eq!(checker.lookup_type("b"), Some(Type::Duration));
+        assert_eq!(checker.lookup_type("c"), Some(Type::Duration));
+        assert_eq!(checker.lookup_type("d"), Some(Type::Block));
+
+        let (program, _) = parse("let e = 1 beat + 1");
+        assert!(TypeChecker::new().check_program(&program).has_errors());
+
+        // A Duration divides by a number, but not the other way round
+        let (program, _) = parse("let f = 1 bar / 2\nlet g = 6 / 2\nlet h = 10 - 3 / 4");
+        let mut checker = TypeChecker::new();
+        assert!(!checker.check_program(&program).has_errors());
+        assert_eq!(checker.lookup_type("f"), Some(Type::Duration));
+        assert_eq!(checker.lookup_type("g"), Some(Type::Int));
+        assert_eq!(checker.lookup_type("h"), Some(Type::Int));
+        let (program, _) = parse("let h = 2 / 1 bar");
+        assert!(TypeChecker::new().check_program(&program).has_errors());
+    }
+
+    #[test]
+    fn test_signatures_checked_against_inferred_types() {
+        let source = "let up : Block -> Block = \\b -> b |> transpose P8\n\
+                      let twice (b: Block) : Block = b ++ b\n\
+                      let same (x: a) : a = x\n\
+                      let n = (4 : Int)\n\
+                      let melody = | R M3 | |> up |> twice";
+        let (program, parse_diags) = parse(source);
+        assert!(!parse_diags.has_errors(), "{:?}", parse_diags);
+        let mut checker = TypeChecker::new();
+        let type_diags = checker.check_program(&program);
+        assert!(!type_diags.has_errors(), "{:?}", type_diags);
+        assert_eq!(
+            checker.declared_type("up").unwrap().to_string(),
+            "Block -> Block"
+        );
+        assert_eq!(
+            checker.declared_type("twice").unwrap().to_string(),
+            "Block -> Block"
+        );
+        assert_eq!(checker.declared_type("same").unwrap().to_string(), "a -> a");
+
+        let errors = |source: &str| {
+            let (program, _) = parse(source);
+            let mut checker = TypeChecker::new();
+            let diags = checker.check_program(&program);
+            diags
+                .errors()
+                .map(|e| e.message.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            errors("let n : Block = 4"),
+            vec!["type mismatch: expected Block, found Int"]
+        );
+        // A declared type variable must stay general
+        assert_eq!(
+            errors("let inc (x: a) : a = x + 1"),
+            vec!["type variable `a` stands for any type, but is inferred as Int"]
+        );
+        assert_eq!(
+            errors("let pick (x: a) (y: b) : a = if true then x else y"),
+            vec!["type variable `b` stands for any type, but is inferred as the same type as `a`"]
+        );
+        assert_eq!(errors("let x : Tune = 1"), vec!["undefined type: Tune"]);
+    }
+
+    #[test]
+    fn test_match_exhaustiveness() {
+        let source = "let f = \\p -> match p {\n  (true, 0) -> 1,\n  (false, _) -> 2,\n  (_, n) if n > 3 -> 3,\n  (false, 1) -> 4\n}\n(true, 2) |> f";
+        let (program, parse_diags) = parse(source);
+        assert!(!parse_diags.has_errors(), "{:?}", parse_diags);
+        let mut checker = TypeChecker::new();
+        let diags: Vec<_> = checker.check_program(&program).iter().cloned().collect();
+
+        assert_eq!(diags.len(), 2, "{:?}", diags);
+        assert_eq!(diags[0].message, "unreachable match arm");
+        assert_eq!(diags[0].code, Some(DiagnosticCode::UnreachablePattern));
+        assert_eq!(diags[1].message, "non-exhaustive match");
+        assert_eq!(diags[1].notes, vec!["patterns not covered: `(true, _)`"]);
+
+        let (program, _) = parse("let g = \\b -> match b { true -> 1, _ -> 0 }\ng false");
+        assert!(checker.check_program(&program).is_empty());
+    }
+
+    #[test]
+    fn test_shadowed_builtin_warning() {
+        let (program, parse_diags) =
+            parse("let reverse = \\x -> x\nlet melody = | R |\nreverse melody");
+        assert!(!parse_diags.has_errors());
+
+        let mut checker = TypeChecker::new();
+        let type_diags = checker.check_program(&program);
+        assert!(!type_diags.has_errors(), "Type errors: {:?}", type_diags);
+
+        let warnings: Vec<_> = type_diags.iter().collect();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].kind, relanote_core::DiagnosticKind::Warning);
+        assert_eq!(warnings[0].code, Some(DiagnosticCode::ShadowedBuiltin));
+    }
+
+    #[test]
+    fn test_unused_bindings_and_imports() {
+        let source = "use scales::{Major, Minor}\n\
+                      let helper = 1\n\
+                      let _draft = 2\n\
+                      let double x = x * 2\n\
+                      export let riff = let n = 3 in | R M3 | |> in Major\n\
+                      double 4";
+        let (program, _) = parse(source);
+        let mut checker = TypeChecker::new();
+        checker.add_module("scales", scales_interface());
+        let diags = checker.check_program(&program);
+        assert!(!diags.has_errors(), "{:?}", diags);
+
+        let warnings: Vec<_> = diags.iter().map(|d| (d.code, d.message.as_str())).collect();
+        assert_eq!(
+            warnings,
+            vec![
+                (Some(DiagnosticCode::UnusedImport), "unused import `Minor`"),
+                (
+                    Some(DiagnosticCode::UnusedBinding),
+                    "unused binding `helper`"
+                ),
+                (Some(DiagnosticCode::UnusedBinding), "unused binding `n`"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unplayed_music_warning() {
+        let source = "let lead = | R M3 P5 |\n\
+                      let bass = | R - P5 - | |> voice Piano\n\
+                      let verse = layer [lead, bass]\n\
+                      let chorus = layer [lead]\n\
+                      chorus";
+        let (program, _) = parse(source);
+        let mut checker = TypeChecker::new();
+        let diags = checker.check_program(&program);
+        assert!(!diags.has_errors(), "{:?}", diags);
+
+        let warnings: Vec<_> = diags.iter().collect();
+        assert_eq!(warnings.len(), 2, "{:?}", warnings);
+        assert_eq!(warnings[0].message, "unused binding `verse`");
+        assert_eq!(warnings[1].code, Some(DiagnosticCode::UnplayedBinding));
+        assert_eq!(warnings[1].message, "part `bass` is never played");
+        assert_eq!(
+            warnings[1].notes,
+            vec!["it is only used by `verse`, which the program's result doesn't include"]
+        );
+    }
+
+    #[test]
+    fn test_shadowed_prelude_warning() {
+        let mut checker = TypeChecker::new();
+        checker.add_prelude(&scales_interface());
+        let (program, _) = parse("scale Major = { R, M3 }\n| R M3 | |> in Major |> in Minor");
+        let diags = checker.check_program(&program);
+        assert!(!diags.has_errors(), "{:?}", diags);
+
+        let warnings: Vec<_> = diags.iter().collect();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].code, Some(DiagnosticCode::ShadowedPrelude));
+        assert_eq!(
+            warnings[0].message,
+            "`Major` shadows the standard library scale of the same name"
+        );
+    }
+
+    fn scales_interface() -> ModuleInterface {
+        let mut interface = ModuleInterface::new();
+        for name in ["Major", "Minor"] {
+            interface.insert(intern(name), TypeScheme::mono(Type::Scale));
+        }
+        interface
+    }
+
+    #[test]
+    fn test_use_imports_module_interface() {
+        let (library, _) = parse(
+            "export let lead = | R M3 P5 |\nlet twice = \\x -> x ++ x\nlet helper = 1\nexport twice",
+        );
+        let mut checker = TypeChecker::new();
+        assert!(!checker.check_program(&library).has_errors());
+        let interface = checker.interface();
+        assert_eq!(interface.len(), 2);
+
+        let (program, parse_diags) = parse(
+            "use melody::{lead, twice as double}\nlet song = double lead\nuse melody::hook\nuse melody::helper",
+        );
+        assert!(!parse_diags.has_errors());
+        let mut checker = TypeChecker::new();
+        checker.add_module("melody", interface);
+        let type_diags = checker.check_program(&program);
+
+        let codes: Vec<_> = type_diags.errors().map(|d| d.code).collect();
+        assert_eq!(
+            codes,
+            vec![
+                Some(DiagnosticCode::UnknownModuleItem),
+                Some(DiagnosticCode::PrivateModuleItem)
+            ],
+            "{:?}",
+            type_diags
+        );
+        assert_eq!(checker.lookup_type("song"), Some(Type::Block));
+    }
 }
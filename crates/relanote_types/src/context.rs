@@ -1,7 +1,8 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use relanote_core::InternedStr;
+use relanote_core::{Diagnostic, InternedStr};
 
+use crate::builtins::Overload;
 use crate::types::{TyVar, Type, TypeScheme};
 
 /// Type checking context
@@ -17,6 +18,16 @@ pub struct TypeContext {
 
     /// Scope stack for nested contexts
     scopes: Vec<HashMap<InternedStr, TypeScheme>>,
+
+    /// Signatures of the builtins that haven't been redefined
+    builtins: HashMap<InternedStr, Vec<Overload>>,
+
+    /// Names bound to a builtin or a builtin given some of its arguments,
+    /// which take piped values last as builtins do
+    builtin_values: HashSet<InternedStr>,
+
+    /// Problems found while inferring that don't stop inference
+    diagnostics: Vec<Diagnostic>,
 }
 
 impl Default for TypeContext {
@@ -32,6 +43,9 @@ impl TypeContext {
             env: HashMap::new(),
             substitutions: HashMap::new(),
             scopes: Vec::new(),
+            builtins: HashMap::new(),
+            builtin_values: HashSet::new(),
+            diagnostics: Vec::new(),
         }
     }
 
@@ -42,6 +56,16 @@ impl TypeContext {
         Type::Var(var)
     }
 
+    /// Record a problem that doesn't stop inference
+    pub(crate) fn report(&mut self, diagnostic: Diagnostic) {
+        self.diagnostics.push(diagnostic);
+    }
+
+    /// Take the problems recorded since the last call
+    pub(crate) fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.diagnostics)
+    }
+
     /// Enter a new scope
     pub fn push_scope(&mut self) {
         self.scopes.push(HashMap::new());
@@ -52,18 +76,59 @@ impl TypeContext {
         if let Some(scope) = self.scopes.pop() {
             for name in scope.keys() {
                 self.env.remove(name);
+                self.builtin_values.remove(name);
             }
         }
     }
 
     /// Bind a name to a type scheme
     pub fn bind(&mut self, name: InternedStr, scheme: TypeScheme) {
-        if let Some(scope) = self.scopes.last_mut() {
-            scope.insert(name, scheme.clone());
+        match self.scopes.last_mut() {
+            Some(scope) => {
+                scope.insert(name, scheme.clone());
+            }
+            None => {
+                self.builtins.remove(&name);
+            }
         }
+        self.builtin_values.remove(&name);
         self.env.insert(name, scheme);
     }
 
+    /// Record that `name` is bound to a builtin value, so piping into it
+    /// passes the piped value last
+    pub(crate) fn bind_builtin_value(&mut self, name: InternedStr) {
+        self.builtin_values.insert(name);
+    }
+
+    /// Record the signatures of a builtin bound to `name`
+    pub(crate) fn add_overloads(&mut self, name: InternedStr, overloads: Vec<Overload>) {
+        self.builtins.insert(name, overloads);
+    }
+
+    /// Signatures of the builtin `name` refers to, unless it has been
+    /// redefined
+    pub(crate) fn builtin(&self, name: &InternedStr) -> Option<&[Overload]> {
+        if self.scopes.iter().any(|scope| scope.contains_key(name)) {
+            return None;
+        }
+        self.builtins.get(name).map(Vec::as_slice)
+    }
+
+    /// Whether `name` is bound to a builtin value other than a builtin
+    pub(crate) fn is_builtin_value(&self, name: &InternedStr) -> bool {
+        self.builtin_values.contains(name)
+    }
+
+    /// Current substitutions, to go back to after trying a unification
+    pub(crate) fn snapshot(&self) -> HashMap<TyVar, Type> {
+        self.substitutions.clone()
+    }
+
+    pub(crate) fn rollback(&mut self, snapshot: HashMap<TyVar, Type>) {
+        self.substitutions = snapshot;
+    }
+
     /// Bind a name to a monomorphic type
     pub fn bind_mono(&mut self, name: InternedStr, ty: Type) {
         self.bind(name, TypeScheme::mono(ty));
@@ -74,6 +139,11 @@ impl TypeContext {
         self.env.get(name)
     }
 
+    /// Names bound in the environment
+    pub fn names(&self) -> impl Iterator<Item = &InternedStr> {
+        self.env.keys()
+    }
+
     /// Add a substitution
     pub fn add_substitution(&mut self, var: TyVar, ty: Type) {
         self.substitutions.insert(var, ty);
@@ -1,11 +1,11 @@
-use relanote_core::Span;
+use relanote_core::{DiagnosticCode, Span};
 use thiserror::Error;
 
 use crate::types::Type;
 
 #[derive(Debug, Error, Clone)]
 pub enum TypeError {
-    #[error("type mismatch: expected {expected:?}, found {found:?}")]
+    #[error("type mismatch: expected {expected}, found {found}")]
     Mismatch {
         expected: Type,
         found: Type,
@@ -15,6 +15,23 @@ pub enum TypeError {
     #[error("cannot unify types: {0:?} and {1:?}")]
     UnificationError(Type, Type, Span),
 
+    #[error("`{function}` expects {expected} for {parameter}, found {found}")]
+    ArgumentMismatch {
+        function: String,
+        /// The parameter's name in backticks, or its position
+        parameter: String,
+        expected: Type,
+        found: Type,
+        span: Span,
+    },
+
+    #[error("type variable `{name}` stands for any type, but is inferred as {found}")]
+    NotGeneral {
+        name: String,
+        found: String,
+        span: Span,
+    },
+
     #[error("undefined variable: {name}")]
     UndefinedVariable { name: String, span: Span },
 
@@ -33,6 +50,27 @@ pub enum TypeError {
     #[error("invalid scale index: {index}")]
     InvalidScaleIndex { index: u8, span: Span },
 
+    #[error("module `{module}` has no binding `{name}`")]
+    UnknownModuleItem {
+        module: String,
+        name: String,
+        span: Span,
+    },
+
+    #[error("`{name}` is private to module `{module}`")]
+    PrivateModuleItem {
+        module: String,
+        name: String,
+        span: Span,
+        /// Where the private binding is defined
+        definition: Span,
+    },
+
+    /// A call's arguments don't match the parameters: an unknown or
+    /// repeated name, or too many arguments
+    #[error("{message}")]
+    InvalidArgument { message: String, span: Span },
+
     #[error("time alignment mismatch in layer")]
     TimeAlignmentMismatch {
         expected_duration: String,
@@ -47,13 +85,37 @@ impl TypeError {
         match self {
             TypeError::Mismatch { span, .. } => *span,
             TypeError::UnificationError(_, _, span) => *span,
+            TypeError::ArgumentMismatch { span, .. } => *span,
+            TypeError::NotGeneral { span, .. } => *span,
             TypeError::UndefinedVariable { span, .. } => *span,
             TypeError::UndefinedType { span, .. } => *span,
             TypeError::OccursCheck { span } => *span,
             TypeError::NotAFunction(_, span) => *span,
             TypeError::NotAScale { span, .. } => *span,
             TypeError::InvalidScaleIndex { span, .. } => *span,
+            TypeError::UnknownModuleItem { span, .. } => *span,
+            TypeError::PrivateModuleItem { span, .. } => *span,
+            TypeError::InvalidArgument { span, .. } => *span,
             TypeError::TimeAlignmentMismatch { span, .. } => *span,
         }
     }
+
+    pub fn code(&self) -> DiagnosticCode {
+        match self {
+            TypeError::Mismatch { .. }
+            | TypeError::UnificationError(..)
+            | TypeError::ArgumentMismatch { .. }
+            | TypeError::NotGeneral { .. } => DiagnosticCode::TypeMismatch,
+            TypeError::UndefinedVariable { .. } => DiagnosticCode::UndefinedVariable,
+            TypeError::UndefinedType { .. } => DiagnosticCode::UndefinedType,
+            TypeError::OccursCheck { .. } => DiagnosticCode::InfiniteType,
+            TypeError::NotAFunction(..) => DiagnosticCode::NotAFunction,
+            TypeError::NotAScale { .. } => DiagnosticCode::NotAScale,
+            TypeError::InvalidScaleIndex { .. } => DiagnosticCode::InvalidScaleIndex,
+            TypeError::UnknownModuleItem { .. } => DiagnosticCode::UnknownModuleItem,
+            TypeError::PrivateModuleItem { .. } => DiagnosticCode::PrivateModuleItem,
+            TypeError::InvalidArgument { .. } => DiagnosticCode::InvalidArgument,
+            TypeError::TimeAlignmentMismatch { .. } => DiagnosticCode::TimeAlignmentMismatch,
+        }
+    }
 }
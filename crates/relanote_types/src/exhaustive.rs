@@ -0,0 +1,380 @@
+//! Exhaustiveness and reachability of `match` arms
+//!
+//! Arms are checked with the usefulness algorithm from Maranget's "Warnings
+//! for pattern matching": a pattern is useful after some rows when there is
+//! a value it matches that none of the rows do. An arm that isn't useful is
+//! unreachable, and the values a wildcard would still match are the ones no
+//! arm covers.
+
+use relanote_ast::{LiteralPattern, MatchExpr, Pattern};
+use relanote_core::{Diagnostic, DiagnosticCode, InternedStr};
+
+/// At most this many uncovered patterns are listed
+const MAX_LISTED: usize = 5;
+
+/// A pattern reduced to the shape coverage depends on
+#[derive(Clone, Debug)]
+enum Pat {
+    /// Matches anything: `_`, a variable or an array's rest
+    Wild,
+    Ctor(Ctor, Vec<Pat>),
+    /// Array with a rest: matches arrays with at least these elements
+    Rest(Vec<Pat>),
+    Or(Vec<Pat>),
+}
+
+/// The outermost shape of a value
+#[derive(Clone, Debug, PartialEq)]
+enum Ctor {
+    Bool(bool),
+    Unit,
+    Tuple(usize),
+    /// Array of exactly this length
+    Array(usize),
+    Int(i64),
+    /// Float by its bits, so it can be compared exactly
+    Float(u64),
+    String(String),
+    /// Constructor pattern: its siblings aren't known, so a match on
+    /// constructors needs a catch-all arm
+    Named(InternedStr, usize),
+}
+
+impl Ctor {
+    fn arity(&self) -> usize {
+        match self {
+            Ctor::Tuple(n) | Ctor::Array(n) | Ctor::Named(_, n) => *n,
+            _ => 0,
+        }
+    }
+}
+
+impl Pat {
+    fn lower(pattern: &Pattern) -> Pat {
+        match pattern {
+            Pattern::Wildcard | Pattern::Ident(_) => Pat::Wild,
+            Pattern::Literal(lit) => Pat::Ctor(
+                match lit {
+                    LiteralPattern::Integer(n) => Ctor::Int(*n),
+                    LiteralPattern::Float(f) => Ctor::Float(f.to_bits()),
+                    LiteralPattern::String(s) => Ctor::String(s.clone()),
+                    LiteralPattern::Bool(b) => Ctor::Bool(*b),
+                    LiteralPattern::Unit => Ctor::Unit,
+                },
+                vec![],
+            ),
+            Pattern::Tuple(elems) => Pat::Ctor(Ctor::Tuple(elems.len()), lower_all(elems)),
+            Pattern::Array(arr) => {
+                let elems = lower_all(&arr.elements);
+                match arr.rest {
+                    Some(_) => Pat::Rest(elems),
+                    None => Pat::Ctor(Ctor::Array(elems.len()), elems),
+                }
+            }
+            Pattern::Constructor { name, args } => {
+                Pat::Ctor(Ctor::Named(name.name, args.len()), lower_all(args))
+            }
+            Pattern::Or(a, b) => Pat::Or(vec![Pat::lower(&a.node), Pat::lower(&b.node)]),
+            Pattern::Annotated(p, _) => Pat::lower(&p.node),
+        }
+    }
+
+    /// The arguments of this pattern when it matches values built with
+    /// `ctor`, or `None` when it can't
+    fn specialize(&self, ctor: &Ctor) -> Option<Vec<Pat>> {
+        match self {
+            Pat::Wild => Some(vec![Pat::Wild; ctor.arity()]),
+            Pat::Ctor(c, args) if c == ctor => Some(args.clone()),
+            Pat::Ctor(..) => None,
+            Pat::Rest(prefix) => match ctor {
+                Ctor::Array(len) if *len >= prefix.len() => {
+                    let mut args = prefix.clone();
+                    args.resize(*len, Pat::Wild);
+                    Some(args)
+                }
+                _ => None,
+            },
+            Pat::Or(_) => unreachable!("or-patterns are expanded first"),
+        }
+    }
+}
+
+fn lower_all(patterns: &[relanote_core::Spanned<Pattern>]) -> Vec<Pat> {
+    patterns.iter().map(|p| Pat::lower(&p.node)).collect()
+}
+
+type Row = Vec<Pat>;
+
+/// Split rows whose first pattern is an or-pattern into one row per
+/// alternative
+fn expand(rows: &[Row]) -> Vec<Row> {
+    let mut expanded = Vec::new();
+    for row in rows {
+        match row.first() {
+            Some(Pat::Or(alts)) => {
+                let alts: Vec<Row> = alts
+                    .iter()
+                    .map(|alt| {
+                        let mut row = row.clone();
+                        row[0] = alt.clone();
+                        row
+                    })
+                    .collect();
+                expanded.extend(expand(&alts));
+            }
+            _ => expanded.push(row.clone()),
+        }
+    }
+    expanded
+}
+
+/// Rows for values built with `ctor`, with the first column replaced by the
+/// constructor's arguments
+fn specialize(rows: &[Row], ctor: &Ctor) -> Vec<Row> {
+    rows.iter()
+        .filter_map(|row| {
+            let mut args = row[0].specialize(ctor)?;
+            args.extend_from_slice(&row[1..]);
+            Some(args)
+        })
+        .collect()
+}
+
+/// Rows for values whose constructor no row names
+fn default_rows(rows: &[Row]) -> Vec<Row> {
+    rows.iter()
+        .filter(|row| matches!(row[0], Pat::Wild))
+        .map(|row| row[1..].to_vec())
+        .collect()
+}
+
+/// Every constructor of the first column's type, when the patterns in it
+/// show the type has finitely many
+///
+/// Arrays have a constructor for each length, but all lengths from one past
+/// the longest fixed pattern and the longest rest prefix on are matched by
+/// the same rows, so one of them stands for all.
+fn complete_signature<'a>(heads: impl Iterator<Item = &'a Pat>) -> Option<Vec<Ctor>> {
+    let mut arrays = None;
+    for head in heads {
+        match head {
+            Pat::Ctor(Ctor::Bool(_), _) => return Some(vec![Ctor::Bool(true), Ctor::Bool(false)]),
+            Pat::Ctor(Ctor::Unit, _) => return Some(vec![Ctor::Unit]),
+            Pat::Ctor(Ctor::Tuple(n), _) => return Some(vec![Ctor::Tuple(*n)]),
+            Pat::Ctor(Ctor::Array(len), _) => {
+                arrays = Some(arrays.unwrap_or(0).max(len + 1));
+            }
+            Pat::Rest(prefix) => {
+                arrays = Some(arrays.unwrap_or(0).max(prefix.len()));
+            }
+            _ => {}
+        }
+    }
+    arrays.map(|longest| (0..=longest).map(Ctor::Array).collect())
+}
+
+/// Whether some value matches `row` but none of `rows`
+fn is_useful(rows: &[Row], row: &[Pat]) -> bool {
+    let Some(head) = row.first() else {
+        return rows.is_empty();
+    };
+    let rows = expand(rows);
+
+    let specialized = |ctor: &Ctor| {
+        head.specialize(ctor).is_some_and(|mut args| {
+            args.extend_from_slice(&row[1..]);
+            is_useful(&specialize(&rows, ctor), &args)
+        })
+    };
+    match head {
+        Pat::Or(alts) => alts.iter().any(|alt| {
+            let mut row = row.to_vec();
+            row[0] = alt.clone();
+            is_useful(&rows, &row)
+        }),
+        Pat::Ctor(ctor, _) => specialized(ctor),
+        Pat::Wild | Pat::Rest(_) => {
+            let heads = rows.iter().map(|row| &row[0]).chain([head]);
+            match complete_signature(heads) {
+                Some(ctors) => ctors.iter().any(specialized),
+                None => is_useful(&default_rows(&rows), &row[1..]),
+            }
+        }
+    }
+}
+
+/// A value shape no row matches, with `Wild` standing for any value
+#[derive(Clone, Debug)]
+enum Witness {
+    Wild,
+    Ctor(Ctor, Vec<Witness>),
+    /// Arrays of at least this many elements
+    Rest(Vec<Witness>),
+}
+
+/// Value shapes, `width` columns wide, that none of `rows` match
+fn uncovered(rows: &[Row], width: usize) -> Vec<Vec<Witness>> {
+    if width == 0 {
+        return if rows.is_empty() {
+            vec![vec![]]
+        } else {
+            vec![]
+        };
+    }
+    let rows = expand(rows);
+
+    let Some(ctors) = complete_signature(rows.iter().map(|row| &row[0])) else {
+        return uncovered(&default_rows(&rows), width - 1)
+            .into_iter()
+            .map(|mut witness| {
+                witness.insert(0, Witness::Wild);
+                witness
+            })
+            .collect();
+    };
+
+    let longest = ctors.len() - 1;
+    let mut witnesses = Vec::new();
+    for ctor in ctors {
+        let arity = ctor.arity();
+        for mut witness in uncovered(&specialize(&rows, &ctor), arity + width - 1) {
+            let rest = witness.split_off(arity);
+            let head = match ctor {
+                Ctor::Array(len) if len == longest => Witness::Rest(witness),
+                _ => Witness::Ctor(ctor.clone(), witness),
+            };
+            witnesses.push([vec![head], rest].concat());
+        }
+    }
+    witnesses
+}
+
+impl std::fmt::Display for Witness {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let list = |f: &mut std::fmt::Formatter<'_>, items: &[Witness]| {
+            let items: Vec<_> = items.iter().map(ToString::to_string).collect();
+            write!(f, "{}", items.join(", "))
+        };
+        match self {
+            Witness::Wild => write!(f, "_"),
+            Witness::Rest(prefix) => {
+                write!(f, "[")?;
+                list(f, prefix)?;
+                if !prefix.is_empty() {
+                    write!(f, ", ")?;
+                }
+                write!(f, "..._]")
+            }
+            Witness::Ctor(ctor, args) => match ctor {
+                Ctor::Bool(b) => write!(f, "{}", b),
+                Ctor::Unit => write!(f, "()"),
+                Ctor::Tuple(_) => {
+                    write!(f, "(")?;
+                    list(f, args)?;
+                    write!(f, ")")
+                }
+                Ctor::Array(_) => {
+                    write!(f, "[")?;
+                    list(f, args)?;
+                    write!(f, "]")
+                }
+                Ctor::Int(n) => write!(f, "{}", n),
+                Ctor::Float(bits) => write!(f, "{}", f64::from_bits(*bits)),
+                Ctor::String(s) => write!(f, "{:?}", s),
+                Ctor::Named(name, _) if args.is_empty() => write!(f, "{}", name),
+                Ctor::Named(name, _) => {
+                    write!(f, "{}(", name)?;
+                    list(f, args)?;
+                    write!(f, ")")
+                }
+            },
+        }
+    }
+}
+
+/// Report arms that can never match and values no arm matches
+///
+/// Guarded arms may not match, so they don't cover anything.
+pub(crate) fn check_match(match_expr: &MatchExpr) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let mut rows: Vec<Row> = Vec::new();
+    for arm in &match_expr.arms {
+        let pat = Pat::lower(&arm.pattern.node);
+        if !is_useful(&rows, std::slice::from_ref(&pat)) {
+            diagnostics.push(
+                Diagnostic::warning("unreachable match arm", arm.pattern.span)
+                    .with_code(DiagnosticCode::UnreachablePattern)
+                    .with_note("earlier arms already match every value this one does"),
+            );
+        }
+        if arm.guard.is_none() {
+            rows.push(vec![pat]);
+        }
+    }
+
+    let missing = uncovered(&rows, 1);
+    if !missing.is_empty() {
+        let mut listed: Vec<_> = missing
+            .iter()
+            .take(MAX_LISTED)
+            .map(|witness| format!("`{}`", witness[0]))
+            .collect();
+        if missing.len() > MAX_LISTED {
+            listed.push(format!("and {} more", missing.len() - MAX_LISTED));
+        }
+        diagnostics.push(
+            Diagnostic::error("non-exhaustive match", match_expr.scrutinee.span)
+                .with_code(DiagnosticCode::NonExhaustiveMatch)
+                .with_note(format!("patterns not covered: {}", listed.join(", "))),
+        );
+    }
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn int(n: i64) -> Pat {
+        Pat::Ctor(Ctor::Int(n), vec![])
+    }
+
+    fn array(elems: Vec<Pat>) -> Pat {
+        Pat::Ctor(Ctor::Array(elems.len()), elems)
+    }
+
+    fn missing(arms: Vec<Pat>) -> Vec<String> {
+        let rows: Vec<Row> = arms.into_iter().map(|arm| vec![arm]).collect();
+        uncovered(&rows, 1)
+            .iter()
+            .map(|witness| witness[0].to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_array_lengths() {
+        assert_eq!(
+            missing(vec![array(vec![]), Pat::Rest(vec![int(1)])]),
+            vec!["[_, ..._]"]
+        );
+        assert_eq!(
+            missing(vec![array(vec![]), Pat::Rest(vec![Pat::Wild])]),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            missing(vec![array(vec![Pat::Wild])]),
+            vec!["[]", "[_, _, ..._]"]
+        );
+    }
+
+    #[test]
+    fn test_or_patterns() {
+        let bools = Pat::Or(vec![
+            Pat::Ctor(Ctor::Bool(true), vec![]),
+            Pat::Ctor(Ctor::Bool(false), vec![]),
+        ]);
+        assert!(missing(vec![bools.clone()]).is_empty());
+        assert!(!is_useful(&[vec![bools]], &[Pat::Wild]));
+        assert_eq!(missing(vec![int(1)]), vec!["_"]);
+    }
+}
@@ -1,10 +1,17 @@
+use std::collections::HashMap;
+
 use relanote_ast::*;
-use relanote_core::Spanned;
+use relanote_core::{InternedStr, Span, Spanned};
 
+use crate::builtins::{place_arguments, Argument, BuiltinParam, Overload};
 use crate::context::TypeContext;
 use crate::error::TypeError;
+use crate::exhaustive;
 use crate::types::Type;
 
+/// Type variables of a signature, by name
+pub(crate) type SignatureVars = HashMap<InternedStr, Type>;
+
 impl TypeContext {
     /// Infer the type of an expression
     pub fn infer_expr(&mut self, expr: &Spanned<Expr>) -> Result<Type, TypeError> {
@@ -12,6 +19,7 @@ impl TypeContext {
             // Literals
             Expr::Integer(_) => Ok(Type::Int),
             Expr::Float(_) => Ok(Type::Float),
+            Expr::Duration(_) => Ok(Type::Duration),
             Expr::String(_) => Ok(Type::String),
             Expr::Bool(_) => Ok(Type::Bool),
             Expr::Unit => Ok(Type::Unit),
@@ -40,56 +48,73 @@ impl TypeContext {
             Expr::Section(_) => Ok(Type::Section),
             Expr::Layer(_) => Ok(Type::Section),
 
+            // A context's body plays as a song with the context's settings;
+            // tempo may be an Int or a Float, so it is only checked at runtime
+            Expr::Context(context) => {
+                if let Some(tempo) = &context.tempo {
+                    self.infer_expr(tempo)?;
+                }
+                if let Some(key) = &context.key {
+                    let key_ty = self.infer_expr(key)?;
+                    self.unify(&key_ty, &Type::Interval, key.span)?;
+                }
+                if let Some(swing) = &context.swing {
+                    let swing_ty = self.infer_expr(swing)?;
+                    self.unify(&swing_ty, &Type::Float, swing.span)?;
+                }
+                self.infer_expr(&context.body)?;
+                Ok(Type::Song)
+            }
+
+            // A kit turns the hits of a percussion block into drums
+            Expr::Kit(kit) => {
+                for hit in &kit.hits {
+                    self.infer_expr(&hit.drum)?;
+                }
+                Ok(Type::function(Type::Block, Type::Block))
+            }
+
             // Lambda
             Expr::Lambda(lambda) => {
                 self.push_scope();
 
-                let mut param_types = Vec::new();
-                for param in &lambda.params {
-                    let param_ty = self.fresh_var();
-                    if let Pattern::Ident(ident) = &param.node {
-                        self.bind_mono(ident.name, param_ty.clone());
-                    }
-                    param_types.push(param_ty);
-                }
+                let mut vars = SignatureVars::new();
+                let param_types = self.bind_params(&lambda.params, &mut vars)?;
 
                 let body_ty = self.infer_expr(&lambda.body)?;
                 self.pop_scope();
 
-                Ok(Type::function_n(param_types, body_ty))
+                let func_ty = Type::function_n(param_types, body_ty);
+                self.check_signature(&vars, &func_ty, &func_ty, expr.span)?;
+                Ok(func_ty)
             }
 
             // Application
+            Expr::Application(app) if !app.named.is_empty() => {
+                self.infer_named_call(app, None, expr.span)
+            }
             Expr::Application(app) => {
-                let func_ty = self.infer_expr(&app.func)?;
-                let mut result_ty = func_ty;
-
-                for arg in &app.args {
-                    let arg_ty = self.infer_expr(arg)?;
-                    let ret_ty = self.fresh_var();
-
-                    self.unify(
-                        &result_ty,
-                        &Type::function(arg_ty, ret_ty.clone()),
-                        expr.span,
-                    )?;
-
-                    result_ty = self.apply(&ret_ty);
-                }
-
-                Ok(result_ty)
+                let args: Vec<_> = app.args.iter().collect();
+                self.infer_call(&app.func, &args, expr.span)
             }
 
-            // Pipe
-            Expr::Pipe(pipe) => {
-                let arg_ty = self.infer_expr(&pipe.left)?;
-                let func_ty = self.infer_expr(&pipe.right)?;
-                let ret_ty = self.fresh_var();
-
-                self.unify(&func_ty, &Type::function(arg_ty, ret_ty.clone()), expr.span)?;
-
-                Ok(self.apply(&ret_ty))
-            }
+            // Pipe: `x |> f a` calls `f` with `x` as its first argument,
+            // except that builtins take the piped value last
+            Expr::Pipe(pipe) => match &pipe.right.node {
+                Expr::Application(app) if !app.named.is_empty() => {
+                    self.infer_named_call(app, Some(&pipe.left), expr.span)
+                }
+                Expr::Application(app) => {
+                    let mut args: Vec<_> = app.args.iter().collect();
+                    if self.pipes_last(&app.func) {
+                        args.push(&pipe.left);
+                    } else {
+                        args.insert(0, &pipe.left);
+                    }
+                    self.infer_call(&app.func, &args, expr.span)
+                }
+                _ => self.infer_call(&pipe.right, &[&pipe.left], expr.span),
+            },
 
             // Binary operators
             Expr::Binary(binary) => {
@@ -109,40 +134,92 @@ impl TypeContext {
                             self.unify(&left_ty, &Type::Interval, expr.span)?;
                             self.unify(&right_ty, &Type::Interval, expr.span)?;
                             Ok(Type::Interval)
+                        } else if matches!(left_applied, Type::Duration)
+                            || matches!(right_applied, Type::Duration)
+                        {
+                            self.unify(&left_ty, &Type::Duration, expr.span)?;
+                            self.unify(&right_ty, &Type::Duration, expr.span)?;
+                            Ok(Type::Duration)
                         } else {
-                            // Default to Int arithmetic
-                            self.unify(&left_ty, &Type::Int, expr.span)?;
-                            self.unify(&right_ty, &Type::Int, expr.span)?;
-                            Ok(Type::Int)
+                            self.infer_arithmetic(&left_ty, &right_ty, expr.span)
                         }
                     }
                     BinaryOp::Mul | BinaryOp::Div => {
-                        self.unify(&left_ty, &Type::Int, expr.span)?;
-                        self.unify(&right_ty, &Type::Int, expr.span)?;
-                        Ok(Type::Int)
+                        // Durations scale by a number: `1 beat * 3`, `2 * 1/8`,
+                        // `1 bar / 4`
+                        let (left_applied, right_applied) =
+                            (self.apply(&left_ty), self.apply(&right_ty));
+                        let scaled = match (&left_applied, &right_applied) {
+                            (Type::Duration, _) => Some((&right_ty, right_applied.clone())),
+                            (_, Type::Duration) if binary.op == BinaryOp::Mul => {
+                                Some((&left_ty, left_applied.clone()))
+                            }
+                            (_, Type::Duration) => {
+                                return Err(TypeError::InvalidArgument {
+                                    message: "a number can't be divided by a Duration".to_string(),
+                                    span: expr.span,
+                                })
+                            }
+                            _ => None,
+                        };
+                        if let Some((factor_ty, factor)) = scaled {
+                            if factor != Type::Float {
+                                self.unify(factor_ty, &Type::Int, expr.span)?;
+                            }
+                            return Ok(Type::Duration);
+                        }
+                        // Intervals stack a whole number of times: `P5 * 2`
+                        if binary.op == BinaryOp::Mul {
+                            let stacked = match (&left_applied, &right_applied) {
+                                (Type::Interval, _) => Some(&right_ty),
+                                (_, Type::Interval) => Some(&left_ty),
+                                _ => None,
+                            };
+                            if let Some(count_ty) = stacked {
+                                self.unify(count_ty, &Type::Int, expr.span)?;
+                                return Ok(Type::Interval);
+                            }
+                        }
+                        self.infer_arithmetic(&left_ty, &right_ty, expr.span)
                     }
-                    BinaryOp::Eq
-                    | BinaryOp::Ne
-                    | BinaryOp::Lt
-                    | BinaryOp::Le
-                    | BinaryOp::Gt
-                    | BinaryOp::Ge => {
+                    BinaryOp::Mod | BinaryOp::Pow => {
+                        self.infer_arithmetic(&left_ty, &right_ty, expr.span)
+                    }
+                    BinaryOp::Eq | BinaryOp::Ne => {
                         self.unify(&left_ty, &right_ty, expr.span)?;
                         Ok(Type::Bool)
                     }
+                    BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge => {
+                        // Ints and Floats compare with each other
+                        let numeric = |ty: Type| matches!(ty, Type::Int | Type::Float);
+                        if !(numeric(self.apply(&left_ty)) && numeric(self.apply(&right_ty))) {
+                            self.unify(&left_ty, &right_ty, expr.span)?;
+                        }
+                        Ok(Type::Bool)
+                    }
                     BinaryOp::And | BinaryOp::Or => {
                         self.unify(&left_ty, &Type::Bool, expr.span)?;
                         self.unify(&right_ty, &Type::Bool, expr.span)?;
                         Ok(Type::Bool)
                     }
                     BinaryOp::Concat => {
+                        // Sections join into a song
+                        let is_song = |ty: Type| matches!(ty, Type::Section | Type::Song);
+                        if is_song(self.apply(&left_ty)) && is_song(self.apply(&right_ty)) {
+                            return Ok(Type::Song);
+                        }
                         self.unify(&left_ty, &right_ty, expr.span)?;
                         Ok(left_ty)
                     }
-                    BinaryOp::Mod => {
+                    BinaryOp::Overlay => {
+                        self.unify(&left_ty, &Type::Block, expr.span)?;
+                        self.unify(&right_ty, &Type::Block, expr.span)?;
+                        Ok(Type::Block)
+                    }
+                    BinaryOp::Range => {
                         self.unify(&left_ty, &Type::Int, expr.span)?;
                         self.unify(&right_ty, &Type::Int, expr.span)?;
-                        Ok(Type::Int)
+                        Ok(Type::array(Type::Int))
                     }
                     BinaryOp::Compose => {
                         // Function composition: f >> g means \x -> g(f(x))
@@ -167,10 +244,13 @@ impl TypeContext {
                 let operand_ty = self.infer_expr(&unary.operand)?;
 
                 match unary.op {
-                    UnaryOp::Neg => {
-                        self.unify(&operand_ty, &Type::Int, expr.span)?;
-                        Ok(Type::Int)
-                    }
+                    UnaryOp::Neg => match self.apply(&operand_ty) {
+                        ty @ (Type::Float | Type::Interval) => Ok(ty),
+                        _ => {
+                            self.unify(&operand_ty, &Type::Int, expr.span)?;
+                            Ok(Type::Int)
+                        }
+                    },
                     UnaryOp::Not => {
                         self.unify(&operand_ty, &Type::Bool, expr.span)?;
                         Ok(Type::Bool)
@@ -215,6 +295,12 @@ impl TypeContext {
                     return Ok(Type::Interval);
                 }
 
+                // A block's slot, as a block of its own
+                if let Type::Block = self.apply(&base_ty) {
+                    self.unify(&index_ty, &Type::Int, expr.span)?;
+                    return Ok(Type::Block);
+                }
+
                 // Generic case
                 self.unify(&base_ty, &Type::array(elem_ty.clone()), expr.span)?;
                 self.unify(&index_ty, &Type::Int, expr.span)?;
@@ -238,12 +324,18 @@ impl TypeContext {
 
             // Let expression
             Expr::Let(let_expr) => {
-                let value_ty = self.infer_expr(&let_expr.value)?;
+                let mut value_ty = self.infer_expr(&let_expr.value)?;
+                if let Some(ann) = &let_expr.type_ann {
+                    value_ty = self.check_annotation(ann, &value_ty, let_expr.value.span)?;
+                }
 
                 self.push_scope();
                 if let Pattern::Ident(ident) = &let_expr.pattern.node {
                     let scheme = self.generalize(&value_ty);
                     self.bind(ident.name, scheme);
+                    if self.pipes_last(&let_expr.value) {
+                        self.bind_builtin_value(ident.name);
+                    }
                 }
 
                 let body_ty = self.infer_expr(&let_expr.body)?;
@@ -255,6 +347,15 @@ impl TypeContext {
             // With expression
             Expr::With(with_expr) => {
                 let base_ty = self.infer_expr(&with_expr.base)?;
+                // Each modification is an interval, or `-interval` to remove one
+                for modification in &with_expr.modifications {
+                    let interval = match &modification.node {
+                        Expr::Unary(unary) if unary.op == UnaryOp::Neg => &unary.operand,
+                        _ => modification,
+                    };
+                    let ty = self.infer_expr(interval)?;
+                    self.unify(&ty, &Type::Interval, interval.span)?;
+                }
                 // With expressions return the same type as the base
                 Ok(base_ty)
             }
@@ -286,12 +387,18 @@ impl TypeContext {
                     self.unify(&result_ty, &body_ty, arm.body.span)?;
                 }
 
+                for diagnostic in exhaustive::check_match(match_expr) {
+                    self.report(diagnostic);
+                }
                 Ok(self.apply(&result_ty))
             }
 
             // Parenthesized or annotated
             Expr::Paren(inner) => self.infer_expr(inner),
-            Expr::Annotated(inner, _) => self.infer_expr(inner),
+            Expr::Annotated(inner, ann) => {
+                let inner_ty = self.infer_expr(inner)?;
+                self.check_annotation(ann, &inner_ty, inner.span)
+            }
 
             // In scale expression - returns a function Block -> Block
             Expr::InScale(in_scale) => {
@@ -305,6 +412,406 @@ impl TypeContext {
         }
     }
 
+    /// Numeric arithmetic: Int with Int stays Int, and a Float on either
+    /// side makes the result a Float
+    fn infer_arithmetic(
+        &mut self,
+        left: &Type,
+        right: &Type,
+        span: Span,
+    ) -> Result<Type, TypeError> {
+        let (left_applied, right_applied) = (self.apply(left), self.apply(right));
+        if left_applied != Type::Float && right_applied != Type::Float {
+            self.unify(left, &Type::Int, span)?;
+            self.unify(right, &Type::Int, span)?;
+            return Ok(Type::Int);
+        }
+        for (ty, applied) in [(left, left_applied), (right, right_applied)] {
+            if applied != Type::Int {
+                self.unify(ty, &Type::Float, span)?;
+            }
+        }
+        Ok(Type::Float)
+    }
+
+    /// The signatures of the builtin `func` names, if it names one
+    fn callee_builtin(&self, func: &Spanned<Expr>) -> Option<Vec<Overload>> {
+        match &func.node {
+            Expr::Ident(ident) => self.builtin(&ident.name).map(<[_]>::to_vec),
+            _ => None,
+        }
+    }
+
+    /// Whether `func` evaluates to a builtin, or a builtin given some of
+    /// its arguments, which take a piped value last
+    pub(crate) fn pipes_last(&self, func: &Spanned<Expr>) -> bool {
+        match &func.node {
+            Expr::Ident(ident) => {
+                self.builtin(&ident.name).is_some() || self.is_builtin_value(&ident.name)
+            }
+            Expr::Application(app) => self.pipes_last(&app.func),
+            Expr::Paren(inner) => self.pipes_last(inner),
+            _ => false,
+        }
+    }
+
+    /// Infer the type of calling `func` with `args`
+    fn infer_call(
+        &mut self,
+        func: &Spanned<Expr>,
+        args: &[&Spanned<Expr>],
+        span: Span,
+    ) -> Result<Type, TypeError> {
+        let overloads = self.callee_builtin(func);
+        let (func_ty, arg_tys, params) = match &overloads {
+            Some(overloads) => {
+                let arg_tys = self.infer_args(args)?;
+                let overload = self.choose_overload(overloads, &arg_tys);
+                let func_ty = self.instantiate(&overload.scheme);
+                (func_ty, arg_tys, overload.params.clone())
+            }
+            None => {
+                let func_ty = self.infer_expr(func)?;
+                (func_ty, self.infer_args(args)?, Vec::new())
+            }
+        };
+        let name = match &func.node {
+            Expr::Ident(ident) => Some(ident.name.to_string()),
+            _ => None,
+        };
+
+        // Builtins, partial or not, take an Int where a Float is expected
+        let coerce = self.pipes_last(func);
+        let mut result_ty = func_ty;
+        for (i, (arg, arg_ty)) in args.iter().zip(arg_tys).enumerate() {
+            result_ty = match self.apply(&result_ty) {
+                Type::Function(param_ty, ret_ty) => {
+                    if let Err(err) = self.check_argument(&param_ty, &arg_ty, coerce, arg.span) {
+                        let parameter = params.get(i).copied().flatten();
+                        return Err(self.argument_error(
+                            err,
+                            name.as_deref(),
+                            parameter,
+                            i,
+                            (&param_ty, &arg_ty),
+                            arg.span,
+                        ));
+                    }
+                    (*ret_ty).clone()
+                }
+                Type::Var(_) | Type::Error => {
+                    let ret_ty = self.fresh_var();
+                    self.unify(&result_ty, &Type::function(arg_ty, ret_ty.clone()), span)?;
+                    ret_ty
+                }
+                other => return Err(TypeError::NotAFunction(other, arg.span)),
+            };
+        }
+
+        Ok(self.apply(&result_ty))
+    }
+
+    /// Infer the type of calling a builtin with named arguments, and with
+    /// `piped` piped into it
+    ///
+    /// Arguments are placed by [`place_arguments`]; parameters left missing
+    /// make the call return a function taking them.
+    fn infer_named_call(
+        &mut self,
+        app: &Application,
+        piped: Option<&Spanned<Expr>>,
+        span: Span,
+    ) -> Result<Type, TypeError> {
+        let (function, overloads) = match (&app.func.node, self.callee_builtin(&app.func)) {
+            (Expr::Ident(ident), Some(overloads)) => (ident.name.to_string(), overloads),
+            _ => {
+                return Err(TypeError::InvalidArgument {
+                    message: "only builtins take named arguments".to_string(),
+                    span: app.named[0].name_span,
+                })
+            }
+        };
+
+        let mut positional = Vec::new();
+        for arg in &app.args {
+            positional.push((self.infer_expr(arg)?, arg.span));
+        }
+        let mut named = Vec::new();
+        for arg in &app.named {
+            let ty = self.infer_expr(&arg.value)?;
+            named.push((arg.name.name.as_str(), arg.name_span, (ty, arg.value.span)));
+        }
+        let piped = match piped {
+            Some(piped) => Some((self.infer_expr(piped)?, piped.span)),
+            None => None,
+        };
+
+        // Among the signatures with every name, the first the arguments fit
+        let candidates: Vec<&Overload> = overloads
+            .iter()
+            .filter(|overload| {
+                named
+                    .iter()
+                    .all(|(name, _, _)| overload.params.contains(&Some(*name)))
+            })
+            .collect();
+        let call = (positional, named, piped);
+        for overload in &candidates {
+            let snapshot = self.snapshot();
+            match self.check_named_call(&function, overload, call.clone(), span) {
+                Ok(ty) => return Ok(ty),
+                Err(_) => self.rollback(snapshot),
+            }
+        }
+        let first = candidates.first().copied().unwrap_or(&overloads[0]);
+        self.check_named_call(&function, first, call, span)
+    }
+
+    /// Check a call with named arguments against one signature of a builtin
+    #[allow(clippy::type_complexity)]
+    fn check_named_call(
+        &mut self,
+        function: &str,
+        overload: &Overload,
+        (positional, named, piped): (
+            Vec<(Type, Span)>,
+            Vec<(&str, Span, (Type, Span))>,
+            Option<(Type, Span)>,
+        ),
+        span: Span,
+    ) -> Result<Type, TypeError> {
+        let params: Vec<BuiltinParam> = overload
+            .params
+            .iter()
+            .zip(&overload.defaults)
+            .map(|(name, default)| BuiltinParam {
+                names: name.iter().copied().collect(),
+                default: *default,
+            })
+            .collect();
+        let placed = place_arguments(function, &params, positional, named, piped, span)?;
+
+        let mut func_ty = self.instantiate(&overload.scheme);
+        let mut missing = Vec::new();
+        for (i, arg) in placed.into_iter().enumerate() {
+            let (param_ty, ret_ty) = match self.apply(&func_ty) {
+                Type::Function(param_ty, ret_ty) => ((*param_ty).clone(), (*ret_ty).clone()),
+                other => return Err(TypeError::NotAFunction(other, span)),
+            };
+            match arg {
+                Argument::Given((arg_ty, arg_span)) => {
+                    if let Err(err) = self.check_argument(&param_ty, &arg_ty, true, arg_span) {
+                        return Err(self.argument_error(
+                            err,
+                            Some(function),
+                            overload.params[i],
+                            i,
+                            (&param_ty, &arg_ty),
+                            arg_span,
+                        ));
+                    }
+                }
+                Argument::Default(_) => {}
+                Argument::Missing => missing.push(param_ty),
+            }
+            func_ty = ret_ty;
+        }
+        Ok(self.apply(&Type::function_n(missing, func_ty)))
+    }
+
+    /// The error for the `index`th argument of a call not fitting its
+    /// parameter, naming the parameter when the function is known
+    fn argument_error(
+        &mut self,
+        err: TypeError,
+        function: Option<&str>,
+        parameter: Option<&str>,
+        index: usize,
+        (param_ty, arg_ty): (&Type, &Type),
+        span: Span,
+    ) -> TypeError {
+        match (err, function) {
+            (TypeError::UnificationError(..), Some(function)) => {
+                let parameter = match parameter {
+                    Some(param) => format!("`{param}`"),
+                    None => format!("argument {}", index + 1),
+                };
+                TypeError::ArgumentMismatch {
+                    function: function.to_string(),
+                    parameter,
+                    expected: self.apply(param_ty),
+                    found: self.apply(arg_ty),
+                    span,
+                }
+            }
+            (err, _) => err,
+        }
+    }
+
+    fn infer_args(&mut self, args: &[&Spanned<Expr>]) -> Result<Vec<Type>, TypeError> {
+        args.iter().map(|arg| self.infer_expr(arg)).collect()
+    }
+
+    /// The first of a builtin's signatures that takes arguments of
+    /// `arg_tys`, or the first signature when none does
+    fn choose_overload<'o>(&mut self, overloads: &'o [Overload], arg_tys: &[Type]) -> &'o Overload {
+        if overloads.len() == 1 {
+            return &overloads[0];
+        }
+        overloads
+            .iter()
+            .find(|overload| {
+                let snapshot = self.snapshot();
+                let mut func_ty = self.instantiate(&overload.scheme);
+                let fits = arg_tys.iter().all(|arg_ty| match self.apply(&func_ty) {
+                    Type::Function(param_ty, ret_ty) => {
+                        func_ty = (*ret_ty).clone();
+                        self.check_argument(&param_ty, arg_ty, true, Span::dummy())
+                            .is_ok()
+                    }
+                    _ => false,
+                });
+                self.rollback(snapshot);
+                fits
+            })
+            .unwrap_or(&overloads[0])
+    }
+
+    /// Check an argument against its parameter; `coerce` lets an `Int` be
+    /// passed for a `Float`, as builtins allow
+    fn check_argument(
+        &mut self,
+        param_ty: &Type,
+        arg_ty: &Type,
+        coerce: bool,
+        span: Span,
+    ) -> Result<(), TypeError> {
+        if coerce && self.apply(param_ty) == Type::Float && self.apply(arg_ty) == Type::Int {
+            return Ok(());
+        }
+        self.unify(param_ty, arg_ty, span)
+    }
+
+    /// Bind function parameters, giving annotated ones their declared type
+    pub(crate) fn bind_params(
+        &mut self,
+        params: &[Spanned<Pattern>],
+        vars: &mut SignatureVars,
+    ) -> Result<Vec<Type>, TypeError> {
+        let mut param_types = Vec::new();
+        for param in params {
+            let param_ty = match &param.node {
+                Pattern::Annotated(_, ann) => self.annotation_type(ann, vars, param.span)?,
+                _ => self.fresh_var(),
+            };
+            if let Pattern::Ident(ident) = param.node.unannotated() {
+                self.bind_mono(ident.name, param_ty.clone());
+            }
+            param_types.push(param_ty);
+        }
+        Ok(param_types)
+    }
+
+    /// The type a type annotation declares
+    ///
+    /// Type variables are looked up in `vars`, and added to it the first
+    /// time they appear.
+    pub(crate) fn annotation_type(
+        &mut self,
+        ann: &TypeAnnotation,
+        vars: &mut SignatureVars,
+        span: Span,
+    ) -> Result<Type, TypeError> {
+        match ann {
+            TypeAnnotation::Named(name) => {
+                Type::from_name(name.name.as_str()).ok_or_else(|| TypeError::UndefinedType {
+                    name: name.name.to_string(),
+                    span,
+                })
+            }
+            TypeAnnotation::Generic(name, _) => Err(TypeError::UndefinedType {
+                name: name.name.to_string(),
+                span,
+            }),
+            TypeAnnotation::Function(param, ret) => Ok(Type::function(
+                self.annotation_type(param, vars, span)?,
+                self.annotation_type(ret, vars, span)?,
+            )),
+            TypeAnnotation::Tuple(elems) => {
+                let elems: Result<Vec<_>, _> = elems
+                    .iter()
+                    .map(|elem| self.annotation_type(elem, vars, span))
+                    .collect();
+                Ok(Type::Tuple(elems?))
+            }
+            TypeAnnotation::Array(elem) => Ok(Type::array(self.annotation_type(elem, vars, span)?)),
+            TypeAnnotation::Unit => Ok(Type::Unit),
+            TypeAnnotation::Var(name) => {
+                if let Some(ty) = vars.get(&name.name) {
+                    return Ok(ty.clone());
+                }
+                let ty = self.fresh_var();
+                vars.insert(name.name, ty.clone());
+                Ok(ty)
+            }
+        }
+    }
+
+    /// Check the type inferred for the value at `span` against an
+    /// annotation, returning the declared type
+    pub(crate) fn check_annotation(
+        &mut self,
+        ann: &TypeAnnotation,
+        inferred: &Type,
+        span: Span,
+    ) -> Result<Type, TypeError> {
+        let mut vars = SignatureVars::new();
+        let declared = self.annotation_type(ann, &mut vars, span)?;
+        self.check_signature(&vars, &declared, inferred, span)?;
+        Ok(self.apply(&declared))
+    }
+
+    /// Check an inferred type against a declared one
+    ///
+    /// The declared type variables must stay distinct and unknown: a value
+    /// declared `a -> a` has to work for any `a`, so `\x -> x + 1` doesn't
+    /// have that type.
+    pub(crate) fn check_signature(
+        &mut self,
+        vars: &SignatureVars,
+        declared: &Type,
+        inferred: &Type,
+        span: Span,
+    ) -> Result<(), TypeError> {
+        let expected = self.apply(declared);
+        if self.unify(declared, inferred, span).is_err() {
+            return Err(TypeError::Mismatch {
+                expected,
+                found: self.apply(inferred),
+                span,
+            });
+        }
+
+        let mut names: Vec<_> = vars.keys().collect();
+        names.sort_by_key(|name| name.as_str());
+        let mut seen = HashMap::new();
+        for name in names {
+            let found = match self.apply(&vars[name]) {
+                Type::Var(v) => match seen.insert(v, name) {
+                    Some(other) => format!("the same type as `{}`", other.as_str()),
+                    None => continue,
+                },
+                ty => ty.to_string(),
+            };
+            return Err(TypeError::NotGeneral {
+                name: name.to_string(),
+                found,
+                span,
+            });
+        }
+        Ok(())
+    }
+
     /// Infer the type of a pattern
     fn infer_pattern(&mut self, pattern: &Spanned<Pattern>) -> Result<Type, TypeError> {
         match &pattern.node {
@@ -339,7 +846,10 @@ impl TypeContext {
                 Ok(self.fresh_var())
             }
             Pattern::Or(p1, _) => self.infer_pattern(p1),
-            Pattern::Annotated(p, _) => self.infer_pattern(p),
+            Pattern::Annotated(p, ann) => {
+                let ty = self.infer_pattern(p)?;
+                self.check_annotation(ann, &ty, p.span)
+            }
         }
     }
 }
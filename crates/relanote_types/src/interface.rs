@@ -0,0 +1,69 @@
+//! Types a module makes available to the modules that use it
+
+use indexmap::IndexMap;
+use relanote_core::{InternedStr, Span};
+
+use crate::types::TypeScheme;
+
+/// The type schemes of a checked module's exported bindings
+///
+/// Every scheme is closed (all of its type variables are quantified), so it
+/// can be instantiated in another module's type context. Top-level bindings
+/// that are not exported are only recorded by name and definition site, so
+/// an import of one can point at it.
+#[derive(Clone, Debug, Default)]
+pub struct ModuleInterface {
+    bindings: IndexMap<InternedStr, TypeScheme>,
+    private: IndexMap<InternedStr, Span>,
+    /// Doc comments of exported bindings that have one
+    docs: IndexMap<InternedStr, String>,
+}
+
+impl ModuleInterface {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a binding
+    pub fn insert(&mut self, name: InternedStr, scheme: TypeScheme) {
+        self.bindings.insert(name, scheme);
+    }
+
+    /// Record a binding that is not exported
+    pub fn insert_private(&mut self, name: InternedStr, definition: Span) {
+        self.private.insert(name, definition);
+    }
+
+    /// Where a binding that is not exported is defined
+    pub fn private_definition(&self, name: &InternedStr) -> Option<Span> {
+        self.private.get(name).copied()
+    }
+
+    /// Record the doc comment of an exported binding
+    pub fn insert_doc(&mut self, name: InternedStr, doc: String) {
+        self.docs.insert(name, doc);
+    }
+
+    /// The doc comment of an exported binding
+    pub fn doc(&self, name: &InternedStr) -> Option<&str> {
+        self.docs.get(name).map(String::as_str)
+    }
+
+    /// Look up the type scheme of an exported binding
+    pub fn get(&self, name: &InternedStr) -> Option<&TypeScheme> {
+        self.bindings.get(name)
+    }
+
+    /// Exported bindings in definition order
+    pub fn iter(&self) -> impl Iterator<Item = (&InternedStr, &TypeScheme)> {
+        self.bindings.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.bindings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bindings.is_empty()
+    }
+}
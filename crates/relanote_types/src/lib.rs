@@ -1,11 +1,17 @@
+mod builtins;
 mod checker;
 mod context;
 mod error;
+mod exhaustive;
 mod inference;
+mod interface;
+mod lint;
 mod types;
 mod unify;
 
+pub use builtins::{builtin_params, place_arguments, Argument, BuiltinParam};
 pub use checker::TypeChecker;
 pub use context::TypeContext;
 pub use error::TypeError;
+pub use interface::ModuleInterface;
 pub use types::{TyVar, Type, TypeScheme};
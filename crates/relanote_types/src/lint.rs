@@ -0,0 +1,256 @@
+//! Warnings about bindings and imports a program never uses or plays
+
+use std::collections::HashSet;
+
+use relanote_ast::*;
+use relanote_core::{Diagnostic, DiagnosticCode, InternedStr, Span, Spanned};
+
+use crate::types::Type;
+
+/// Names an expression or item refers to
+#[derive(Default)]
+struct References {
+    names: HashSet<InternedStr>,
+}
+
+impl References {
+    fn of_expr(expr: &Spanned<Expr>) -> Self {
+        let mut references = Self::default();
+        references.visit_expr(expr);
+        references
+    }
+
+    fn of_item(item: &Spanned<Item>) -> Self {
+        let mut references = Self::default();
+        references.visit_item(item);
+        references
+    }
+}
+
+impl Visitor for References {
+    fn visit_expr(&mut self, expr: &Spanned<Expr>) {
+        if let Expr::Ident(ident) = &expr.node {
+            self.names.insert(ident.name);
+        }
+        walk_expr(self, expr);
+    }
+}
+
+/// Reports `let ... in` bindings their body never refers to
+#[derive(Default)]
+struct UnusedLocals {
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Visitor for UnusedLocals {
+    fn visit_expr(&mut self, expr: &Spanned<Expr>) {
+        if let Expr::Let(let_expr) = &expr.node {
+            if let Pattern::Ident(ident) = let_expr.pattern.node.unannotated() {
+                if !References::of_expr(&let_expr.body)
+                    .names
+                    .contains(&ident.name)
+                {
+                    self.diagnostics
+                        .extend(unused_binding(ident.name, let_expr.pattern.span));
+                }
+            }
+        }
+        walk_expr(self, expr);
+    }
+}
+
+fn unused_binding(name: InternedStr, span: Span) -> Option<Diagnostic> {
+    if name.as_str().starts_with('_') {
+        return None;
+    }
+    Some(
+        Diagnostic::warning(format!("unused binding `{}`", name), span)
+            .with_code(DiagnosticCode::UnusedBinding)
+            .with_note(format!(
+                "remove it, or name it `_{}` if it is unused on purpose",
+                name
+            )),
+    )
+}
+
+/// Names an item imports, with the span to report them at
+fn imported_names(item: &Spanned<Item>) -> Vec<(InternedStr, Span)> {
+    match &item.node {
+        Item::Use(use_decl) => match &use_decl.path.kind {
+            UseKind::Simple => use_decl
+                .path
+                .segments
+                .last()
+                .filter(|_| use_decl.path.segments.len() >= 2)
+                .map(|last| (last.name, item.span))
+                .into_iter()
+                .collect(),
+            // A glob import doesn't name what it brings in
+            UseKind::Glob => Vec::new(),
+            UseKind::Group(items) => items
+                .iter()
+                .map(|use_item| {
+                    let alias = use_item.alias.as_ref().unwrap_or(&use_item.name);
+                    (alias.name, item.span)
+                })
+                .collect(),
+        },
+        Item::Import(import) => import
+            .items
+            .iter()
+            .filter_map(|import_item| match import_item {
+                ImportItem::Named(name) => Some((name.name, item.span)),
+                ImportItem::Aliased { alias, .. } => Some((alias.name, item.span)),
+                ImportItem::All | ImportItem::AllAliased(_) => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// The name a top-level binding defines, for the bindings that are checked
+/// for use, with the span to report it at
+fn defined_binding(item: &Spanned<Item>) -> Option<(InternedStr, Span)> {
+    match &item.node {
+        Item::LetBinding(binding) => match binding.pattern.node.unannotated() {
+            Pattern::Ident(ident) => Some((ident.name, binding.pattern.span)),
+            _ => None,
+        },
+        Item::FunctionDef(func_def) => Some((func_def.name.name, item.span)),
+        _ => None,
+    }
+}
+
+/// Warn about bindings and imports that nothing refers to
+///
+/// Exported names are used by other modules, and names starting with `_`
+/// are unused on purpose.
+pub(crate) fn unused(program: &Program, exports: &HashSet<InternedStr>) -> Vec<Diagnostic> {
+    let references: Vec<References> = program.items.iter().map(References::of_item).collect();
+    // Whether an item other than the one at `index` refers to `name`
+    let used_elsewhere = |name: &InternedStr, index: usize| {
+        exports.contains(name)
+            || references
+                .iter()
+                .enumerate()
+                .any(|(i, refs)| i != index && refs.names.contains(name))
+    };
+
+    let mut diagnostics = Vec::new();
+    for (index, item) in program.items.iter().enumerate() {
+        for (name, span) in imported_names(item) {
+            if !used_elsewhere(&name, index) {
+                diagnostics.push(
+                    Diagnostic::warning(format!("unused import `{}`", name), span)
+                        .with_code(DiagnosticCode::UnusedImport),
+                );
+            }
+        }
+        if let Some((name, span)) = defined_binding(item) {
+            if !used_elsewhere(&name, index) {
+                diagnostics.extend(unused_binding(name, span));
+            }
+        }
+
+        let mut locals = UnusedLocals::default();
+        locals.visit_item(item);
+        diagnostics.extend(locals.diagnostics);
+    }
+    diagnostics
+}
+
+/// Warn about music bound at the top level that only bindings outside the
+/// program's result use
+///
+/// A section that's defined and referenced but left out of the final song
+/// is silent without this. Bindings nothing refers to at all are already
+/// reported as unused.
+pub(crate) fn unplayed(
+    program: &Program,
+    exports: &HashSet<InternedStr>,
+    type_of: impl Fn(&InternedStr) -> Option<Type>,
+) -> Vec<Diagnostic> {
+    // The program's results are its render targets, or else its final
+    // expression; without either it is a module, not a piece (tests don't
+    // count, as they don't end the program)
+    let mut results: Vec<&Spanned<Expr>> = program
+        .items
+        .iter()
+        .filter_map(|item| match &item.node {
+            Item::Render(render) => Some(&render.target),
+            _ => None,
+        })
+        .collect();
+    if results.is_empty() {
+        let last = program
+            .items
+            .iter()
+            .rev()
+            .find(|item| !matches!(item.node, Item::Test(_)));
+        let Some(Item::ExprStmt(result)) = last.map(|item| &item.node) else {
+            return Vec::new();
+        };
+        results.push(result);
+    }
+
+    let bindings: Vec<(InternedStr, Span, References)> = program
+        .items
+        .iter()
+        .filter_map(|item| {
+            let (name, span) = defined_binding(item)?;
+            Some((name, span, References::of_item(item)))
+        })
+        .collect();
+
+    // Follow references out from the result and the exports
+    let mut reached: HashSet<InternedStr> = exports.clone();
+    let mut pending: Vec<InternedStr> = results
+        .into_iter()
+        .flat_map(|result| References::of_expr(result).names)
+        .chain(exports.iter().copied())
+        .collect();
+    while let Some(name) = pending.pop() {
+        reached.insert(name);
+        for (binding, _, references) in &bindings {
+            if *binding == name {
+                pending.extend(
+                    references
+                        .names
+                        .iter()
+                        .filter(|name| !reached.contains(name)),
+                );
+            }
+        }
+    }
+
+    let mut diagnostics = Vec::new();
+    for (name, span, _) in &bindings {
+        if reached.contains(name) || name.as_str().starts_with('_') {
+            continue;
+        }
+        let kind = match type_of(name) {
+            Some(Type::Block) => "block",
+            Some(Type::Part) => "part",
+            Some(Type::Section) => "section",
+            Some(Type::Song) => "song",
+            _ => continue,
+        };
+        let users: Vec<String> = bindings
+            .iter()
+            .filter(|(user, _, references)| user != name && references.names.contains(name))
+            .map(|(user, _, _)| format!("`{}`", user))
+            .collect();
+        if users.is_empty() {
+            continue;
+        }
+        diagnostics.push(
+            Diagnostic::warning(format!("{} `{}` is never played", kind, name), *span)
+                .with_code(DiagnosticCode::UnplayedBinding)
+                .with_note(format!(
+                    "it is only used by {}, which the program's result doesn't include",
+                    users.join(", ")
+                )),
+        );
+    }
+    diagnostics
+}
@@ -38,9 +38,11 @@ pub enum Type {
     Synth,
     Oscillator,
     Filter,
+    Lfo,
 
     // Effect primitives
     DistortionType,
+    Bus,
 
     // Compound types
     Function(Arc<Type>, Arc<Type>),
@@ -68,6 +70,34 @@ impl Type {
             .fold(ret, |acc, param| Type::function(param, acc))
     }
 
+    /// The primitive type with this name, as written in signatures
+    pub fn from_name(name: &str) -> Option<Type> {
+        Some(match name {
+            "Bool" => Type::Bool,
+            "Int" => Type::Int,
+            "Float" => Type::Float,
+            "String" => Type::String,
+            "Interval" => Type::Interval,
+            "Scale" => Type::Scale,
+            "Chord" => Type::Chord,
+            "Block" => Type::Block,
+            "Part" => Type::Part,
+            "Section" => Type::Section,
+            "Song" => Type::Song,
+            "Articulation" => Type::Articulation,
+            "Envelope" => Type::Envelope,
+            "Duration" => Type::Duration,
+            "Dynamic" => Type::Dynamic,
+            "Synth" => Type::Synth,
+            "Oscillator" => Type::Oscillator,
+            "Filter" => Type::Filter,
+            "Lfo" => Type::Lfo,
+            "DistortionType" => Type::DistortionType,
+            "Bus" => Type::Bus,
+            _ => return None,
+        })
+    }
+
     /// Create an array type
     pub fn array(elem: Type) -> Self {
         Type::Array(Arc::new(elem))
@@ -102,6 +132,25 @@ impl Type {
 
 impl fmt::Display for Type {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, &|v| format!("t{}", v.0))
+    }
+}
+
+impl Type {
+    /// The type for display with its variables named `a`, `b`, ... in order
+    /// of appearance, as signatures are written: `(a -> b) -> [a] -> [b]`
+    pub fn lettered(&self) -> impl fmt::Display + '_ {
+        let mut vars = Vec::new();
+        for var in self.free_vars() {
+            if !vars.contains(&var) {
+                vars.push(var);
+            }
+        }
+        Lettered { ty: self, vars }
+    }
+
+    /// Write the type, naming each variable with `var_name`
+    fn write(&self, f: &mut fmt::Formatter<'_>, var_name: &dyn Fn(TyVar) -> String) -> fmt::Result {
         match self {
             Type::Unit => write!(f, "()"),
             Type::Bool => write!(f, "Bool"),
@@ -122,13 +171,21 @@ impl fmt::Display for Type {
             Type::Synth => write!(f, "Synth"),
             Type::Oscillator => write!(f, "Oscillator"),
             Type::Filter => write!(f, "Filter"),
+            Type::Lfo => write!(f, "Lfo"),
             Type::DistortionType => write!(f, "DistortionType"),
+            Type::Bus => write!(f, "Bus"),
             Type::Function(param, ret) => {
                 // Handle nested functions for curried display
                 match param.as_ref() {
-                    Type::Function(_, _) => write!(f, "({}) -> {}", param, ret),
-                    _ => write!(f, "{} -> {}", param, ret),
+                    Type::Function(_, _) => {
+                        write!(f, "(")?;
+                        param.write(f, var_name)?;
+                        write!(f, ")")?;
+                    }
+                    _ => param.write(f, var_name)?,
                 }
+                write!(f, " -> ")?;
+                ret.write(f, var_name)
             }
             Type::Tuple(elems) => {
                 write!(f, "(")?;
@@ -136,17 +193,40 @@ impl fmt::Display for Type {
                     if i > 0 {
                         write!(f, ", ")?;
                     }
-                    write!(f, "{}", elem)?;
+                    elem.write(f, var_name)?;
                 }
                 write!(f, ")")
             }
-            Type::Array(elem) => write!(f, "[{}]", elem),
-            Type::Var(v) => write!(f, "t{}", v.0),
+            Type::Array(elem) => {
+                write!(f, "[")?;
+                elem.write(f, var_name)?;
+                write!(f, "]")
+            }
+            Type::Var(v) => write!(f, "{}", var_name(*v)),
             Type::Error => write!(f, "Error"),
         }
     }
 }
 
+/// A type displayed with lettered variables, see [`Type::lettered`]
+struct Lettered<'a> {
+    ty: &'a Type,
+    vars: Vec<TyVar>,
+}
+
+impl fmt::Display for Lettered<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.ty.write(f, &|var| {
+            let index = self.vars.iter().position(|v| *v == var).unwrap_or(0);
+            let letter = (b'a' + (index % 26) as u8) as char;
+            match index / 26 {
+                0 => letter.to_string(),
+                n => format!("{}{}", letter, n),
+            }
+        })
+    }
+}
+
 /// Type scheme for polymorphism: forall a b. Type
 #[derive(Clone, Debug)]
 pub struct TypeScheme {
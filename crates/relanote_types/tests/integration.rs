@@ -53,9 +53,21 @@ fn test_check_arithmetic() {
     assert!(check("1 + 2"));
     assert!(check("5 - 3"));
     assert!(check("3 * 4"));
-    // Division has parsing issues - see evaluator tests
-    // assert!(check("10 / 2"));
+    assert!(check("10 / 2"));
     assert!(check("(1 + 2) * 3"));
+    assert!(check("7 % 3"));
+    assert!(check("2 ^ 8"));
+}
+
+#[test]
+fn test_check_float_arithmetic() {
+    assert!(check("0.5 * 2"));
+    assert!(check("let tempo = 120\nlet half: Float = tempo / 2.0"));
+    assert!(check("let x: Float = 1 + 2.5"));
+    assert!(check("let f = \\x -> x * 1.5\nlet y: Float = f 2.0"));
+    assert!(check("2.5 > 2"));
+    assert!(check_fails("let x: Int = 1 + 2.5"));
+    assert!(check_fails(r#""a" > 1.0"#));
 }
 
 #[test]
@@ -220,6 +232,66 @@ fn test_check_double_time() {
     assert!(check("| R M3 P5 | |> double_time"));
 }
 
+#[test]
+fn test_check_map_and_filter_notes() {
+    assert!(check("| R M3 P5 | |> map_notes (\\i -> i + P8)"));
+    assert!(check(
+        "| R M3 P5 | |> map_notes (\\i d n -> if n == 0 then (i, d * 2) else (i, d))"
+    ));
+    assert!(check("| R M3 P5 | |> filter_notes (\\i d -> d >= 1/4)"));
+    assert!(check_fails("| R M3 P5 | |> filter_notes (\\i -> i + P8)"));
+}
+
+#[test]
+fn test_check_slicing_and_indexing() {
+    assert!(check("| R M3 P5 | |> slice 1 2"));
+    assert!(check("| R M3 P5 | |> bars 0..2"));
+    assert!(check("let m = | R M3 P5 |\nm[1] ++ m[0]"));
+    assert!(check_fails("let m = | R M3 P5 |\nm[R]"));
+}
+
+#[test]
+fn test_check_render_targets() {
+    assert!(check("let song = layer [| R M3 P5 |]\nrender song"));
+    assert!(check_fails("render | R M3 P5 |"));
+}
+
+#[test]
+fn test_check_humanize() {
+    assert!(check("| R M3 P5 | |> humanize 10 8"));
+}
+
+#[test]
+fn test_check_glide() {
+    assert!(check("| R M3 P5 | |> glide 80"));
+}
+
+#[test]
+fn test_check_mark_and_lyrics() {
+    assert!(check(
+        r#"| R M3 P5 | |> mark "Chorus" |> lyrics "la la la""#
+    ));
+    assert!(check(r#"| R M3 | |> lyrics ["Hel-", "lo"]"#));
+}
+
+#[test]
+fn test_check_drum_map() {
+    assert!(check("| R R R R | |> voice Kick |> drum_map 36"));
+    assert!(check(
+        "| R M2 | |> voice Kick |> drum_map [(R, 36), (M2, 38)]"
+    ));
+}
+
+#[test]
+fn test_check_pan() {
+    assert!(check("| R M3 P5 | |> pan 0.5"));
+}
+
+#[test]
+fn test_check_automate() {
+    assert!(check("| R M3 P5 | |> automate 74 [0.0, 1.0, 0.5]"));
+}
+
 // ===== Scale and Chord Tests =====
 
 #[test]
@@ -261,6 +333,11 @@ scale Major = { R, M2, M3, P4, P5, M6, M7 }
 fn test_check_interval_arithmetic() {
     assert!(check("R + P5"));
     assert!(check("M3 + m3"));
+    assert!(check("M3 + 12st"));
+    assert!(check("let ninth: Interval = P5 * 2"));
+    assert!(check("let down: Interval = -P8"));
+    assert!(check("| R M3 P5 | |> transpose (2 * P8)"));
+    assert!(check_fails("P5 * 1.5"));
 }
 
 // ===== Function Composition Tests =====
@@ -382,3 +459,12 @@ fn test_lookup_undefined_returns_none() {
     let checker = TypeChecker::new();
     assert!(checker.lookup_type("undefined_name").is_none());
 }
+
+#[test]
+fn test_lettered_type_variables() {
+    let (program, _) = parse("let apply f x = f x");
+    let mut checker = TypeChecker::new();
+    checker.check_program(&program);
+    let ty = checker.lookup_type("apply").unwrap();
+    assert_eq!(ty.lettered().to_string(), "(a -> b) -> a -> b");
+}
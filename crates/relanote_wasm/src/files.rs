@@ -0,0 +1,161 @@
+//! Module files supplied by the JavaScript host
+
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use relanote_ast::Program;
+use relanote_core::Diagnostics;
+use relanote_eval::Evaluator;
+use relanote_resolver::{
+    CallbackFileProvider, FileProvider, MemoryFileProvider, ModuleChecker, ModuleResolver,
+    ENTRY_MODULE,
+};
+use relanote_types::TypeChecker;
+use wasm_bindgen::prelude::*;
+
+/// Path the editor's program is resolved as; modules are looked up next to it
+const ENTRY_FILE: &str = "main.rela";
+
+thread_local! {
+    static HOST_READ: RefCell<Option<js_sys::Function>> = const { RefCell::new(None) };
+    /// Modules added with `add_module`, by module path
+    static MODULES: RefCell<BTreeMap<String, String>> = const { RefCell::new(BTreeMap::new()) };
+    /// Bumped whenever the files modules resolve against change
+    static GENERATION: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Changes whenever modules are added, removed or read from a new provider,
+/// so cached results can tell they are stale
+pub(crate) fn generation() -> u64 {
+    GENERATION.with(Cell::get)
+}
+
+fn bump_generation() {
+    GENERATION.with(|generation| generation.set(generation.get() + 1));
+}
+
+/// Make `source` available to `use` and `mod` as module `name`
+///
+/// `name` is a module path such as `drums` or `lib::bass`. Adding a module
+/// that already exists replaces its source. Added modules take precedence
+/// over files from the host's file provider.
+#[wasm_bindgen]
+pub fn add_module(name: &str, source: &str) {
+    MODULES.with(|modules| {
+        modules
+            .borrow_mut()
+            .insert(name.to_string(), source.to_string())
+    });
+    bump_generation();
+}
+
+/// Remove a module added with `add_module`, returning whether it existed
+#[wasm_bindgen]
+pub fn remove_module(name: &str) -> bool {
+    let removed = MODULES.with(|modules| modules.borrow_mut().remove(name).is_some());
+    bump_generation();
+    removed
+}
+
+/// File a module path resolves to, relative to the editor's program
+fn module_file(name: &str) -> PathBuf {
+    PathBuf::from(format!("{}.rela", name.replace("::", "/")))
+}
+
+/// Let `use` and `mod` load modules through the host
+///
+/// `read` is called with a module path such as `drums.rela` or
+/// `lib/bass.rela` and returns the file's source, or `undefined` when there
+/// is no such file. Pass `undefined` to go back to standard library modules
+/// only.
+#[wasm_bindgen]
+pub fn set_file_provider(read: Option<js_sys::Function>) {
+    HOST_READ.with(|host| *host.borrow_mut() = read);
+    bump_generation();
+}
+
+/// The added modules over the host's file provider, or `None` when
+/// neither exists and only standard library modules resolve
+fn project_files() -> Option<Rc<dyn FileProvider>> {
+    let read = HOST_READ.with(|host| host.borrow().clone());
+    let modules = MODULES.with(|modules| modules.borrow().clone());
+    if read.is_none() && modules.is_empty() {
+        return None;
+    }
+
+    let mut files = match read {
+        Some(read) => MemoryFileProvider::over(CallbackFileProvider::new(move |path: &Path| {
+            let path = JsValue::from_str(&path.to_string_lossy());
+            read.call1(&JsValue::NULL, &path).ok()?.as_string()
+        })),
+        None => MemoryFileProvider::new(),
+    };
+    for (name, source) in modules {
+        files.insert(module_file(&name), source);
+    }
+    Some(Rc::new(files))
+}
+
+/// An evaluator that loads the added modules and the host's files
+pub(crate) fn new_evaluator() -> Evaluator {
+    let mut evaluator = Evaluator::new();
+    if let Some(files) = project_files() {
+        evaluator.set_base_dir(PathBuf::new());
+        evaluator.set_file_provider(files);
+    }
+    evaluator
+}
+
+/// The modules the editor's program uses, or `None` when it only uses
+/// standard library modules or they could not be resolved
+fn resolve_entry(source: &str) -> Option<ModuleResolver> {
+    let files = project_files()?;
+    let mut resolver = ModuleResolver::new(PathBuf::new());
+    resolver.set_file_provider(files);
+    resolver
+        .resolve_source(ENTRY_MODULE, Path::new(ENTRY_FILE), source.to_string())
+        .ok()?;
+    Some(resolver)
+}
+
+/// Type check the editor's program against the modules it uses
+pub(crate) fn check_program(source: &str, program: &Program) -> Diagnostics {
+    let mut checker = ModuleChecker::new();
+    match resolve_entry(source) {
+        Some(resolver) => checker.check_entry(&resolver, ENTRY_MODULE, program),
+        None => checker.check_program(ENTRY_MODULE, program),
+    }
+}
+
+/// A type checker that has checked the editor's program, for looking up
+/// the types of its bindings
+pub(crate) fn type_checker(source: &str, program: &Program) -> TypeChecker {
+    let mut checker = ModuleChecker::new();
+    if let Some(resolver) = resolve_entry(source) {
+        checker.check_dependencies(&resolver, ENTRY_MODULE);
+    }
+    checker.check(program).0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_added_modules_resolve() {
+        add_module("melody", "export let lead = | R M3 P5 |\n");
+        add_module("lib::bass", "export let line = | R R P5 |\n");
+
+        let source = "use melody::lead\nuse lib::bass::line\nlayer [ lead, line ]\n";
+        let (program, _) = relanote_parser::parse(source);
+        let diagnostics = check_program(source, &program);
+        assert!(!diagnostics.has_errors(), "{:?}", diagnostics);
+        assert!(new_evaluator().eval_program(&program).is_ok());
+
+        assert!(remove_module("melody"));
+        assert!(!remove_module("melody"));
+        assert!(check_program(source, &program).has_errors());
+    }
+}
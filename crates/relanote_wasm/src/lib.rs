@@ -1,38 +1,116 @@
 //! WebAssembly bindings for relanote
+//!
+//! This crate is the single package the playground and other JavaScript
+//! hosts build with `wasm-pack`. Exported functions keep their names and
+//! arguments across releases; new parameters are added as optional trailing
+//! arguments so existing callers keep working.
+
+mod files;
+mod piano_roll;
+mod session;
+mod staff;
 
 use serde::{Deserialize, Serialize};
 use wasm_bindgen::prelude::*;
 
-use relanote_core::Source;
-use relanote_eval::{AbsolutePitchValue, Evaluator, SongValue, Value};
-use relanote_format::{format, FormatConfig};
+use relanote_ast::{Item, Program};
+use relanote_core::{Diagnostic, DiagnosticKind, Diagnostics, Source};
+use relanote_eval::{EvalError, Evaluator, SongValue, Value};
+use relanote_format::{format_with_source, FormatConfig};
 use relanote_parser::parse_source;
-use relanote_render::{MidiConfig, MidiRenderer};
-use relanote_types::TypeChecker;
-
-/// Get the MIDI note number for the key from the evaluator
-fn get_key_from_evaluator(evaluator: &Evaluator) -> Option<u8> {
-    evaluator.get_binding("key").and_then(|v| {
-        if let Value::AbsolutePitch(AbsolutePitchValue { midi_note }) = v {
-            Some(midi_note)
-        } else {
-            None
-        }
-    })
-}
+use relanote_render::{
+    audio_notes, mix_data, playback_data, sounding_slots, MidiRenderer, RenderContext,
+};
+use relanote_types::{Type, TypeChecker};
+
+pub use files::{add_module, remove_module, set_file_provider};
+pub use piano_roll::{code_to_notes, notes_to_code, PianoRollNote, QuantizeOptions};
+pub use relanote_render::{
+    ADSRData, AudioEventWindow, AudioNoteEvent, AudioPlaybackData, BusData, EffectData, FilterData,
+    GainPoint, LfoData, OscillatorData, PitchEnvelopeData, SampleData, SendData, SynthData,
+};
+pub use session::RelanoteSession;
+pub use staff::{Spelling, StaffPart};
 
 #[wasm_bindgen(start)]
 pub fn init() {
     console_error_panic_hook::set_once();
 }
 
+/// Convert a result for JavaScript
+///
+/// A result that can't be converted comes back as `{ success: false, error }`
+/// like other failed results, instead of a panic that would abort the module.
+pub(crate) fn to_js<T: Serialize>(value: &T) -> JsValue {
+    serde_wasm_bindgen::to_value(value).unwrap_or_else(|e| {
+        let error = WasmError::unplaced(format!("could not convert the result: {}", e));
+        let error_object = js_sys::Object::new();
+        let result = js_sys::Object::new();
+        let fields = [
+            (&error_object, "message", JsValue::from(error.message)),
+            (&error_object, "start", JsValue::from(error.start)),
+            (&error_object, "end", JsValue::from(error.end)),
+            (&result, "success", JsValue::FALSE),
+            (&result, "error", error_object.clone().into()),
+        ];
+        for (object, key, value) in fields {
+            let _ = js_sys::Reflect::set(object, &JsValue::from_str(key), &value);
+        }
+        result.into()
+    })
+}
+
 /// Diagnostic information for the editor
 #[derive(Clone, Serialize, Deserialize)]
 pub struct WasmDiagnostic {
     pub message: String,
     pub start: usize,
     pub end: usize,
-    pub severity: String, // "error" | "warning" | "info"
+    pub severity: String, // "error" | "warning" | "info" | "hint"
+    /// Stable diagnostic code such as `E0201`
+    pub code: Option<String>,
+}
+
+impl From<&Diagnostic> for WasmDiagnostic {
+    fn from(diag: &Diagnostic) -> Self {
+        Self {
+            message: diag.message.clone(),
+            start: diag.span.start,
+            end: diag.span.end,
+            severity: diag.kind.to_string(),
+            code: diag.code.map(|code| code.to_string()),
+        }
+    }
+}
+
+/// Why a program could not be evaluated, and where
+#[derive(Clone, Serialize, Deserialize)]
+pub struct WasmError {
+    pub message: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl WasmError {
+    /// An error that isn't about any part of the source
+    pub(crate) fn unplaced(message: String) -> Self {
+        Self {
+            message,
+            start: 0,
+            end: 0,
+        }
+    }
+}
+
+impl From<&EvalError> for WasmError {
+    fn from(error: &EvalError) -> Self {
+        let span = error.span();
+        Self {
+            message: error.to_string(),
+            start: span.start,
+            end: span.end,
+        }
+    }
 }
 
 /// Analysis result containing diagnostics and type info
@@ -55,7 +133,10 @@ pub struct FormatResult {
 pub struct EvalResult {
     pub success: bool,
     pub value: Option<String>,
-    pub error: Option<String>,
+    pub error: Option<WasmError>,
+    /// Problems found while evaluating, such as notes outside a scale under
+    /// `set strict_scale = true`
+    pub warnings: Vec<WasmDiagnostic>,
 }
 
 /// MIDI render result
@@ -63,7 +144,17 @@ pub struct EvalResult {
 pub struct RenderResult {
     pub success: bool,
     pub midi_data: Option<Vec<u8>>,
-    pub error: Option<String>,
+    pub error: Option<WasmError>,
+}
+
+/// A `render` item of a program
+#[derive(Serialize, Deserialize)]
+pub struct RenderTargetInfo {
+    /// Name given with `render "name" = ...`
+    pub name: Option<String>,
+    /// Where the item is in the source
+    pub start: usize,
+    pub end: usize,
 }
 
 /// Note event for staff notation
@@ -73,80 +164,60 @@ pub struct NoteEvent {
     pub start: f64,    // Start time in beats
     pub duration: f64, // Duration in beats
     pub velocity: u8,  // Velocity (0-127)
+    /// How the pitch is written in the song's key
+    pub spelling: Spelling,
+    /// Index into `StaffData::parts`
+    pub part: usize,
+    /// Measure the note starts in, from 0
+    pub measure: usize,
+    /// Notes with the same beam group are beamed together
+    pub beam: Option<usize>,
 }
 
-/// Synth oscillator data for WebAudio
-#[derive(Serialize, Deserialize, Clone)]
-pub struct OscillatorData {
-    pub waveform: String, // "sine" | "square" | "sawtooth" | "triangle" | "noise" | "pulse"
-    pub pulse_duty: f64,  // Duty cycle for pulse wave (0.0-1.0)
-    pub mix: f64,         // Volume mix (0.0-1.0)
-    pub octave_offset: i8, // Octave offset (-2 to +2)
-    pub detune_cents: f64, // Detune in cents
-}
-
-/// ADSR envelope data for WebAudio
-#[derive(Serialize, Deserialize, Clone)]
-pub struct ADSRData {
-    pub attack: f64,  // Attack time in seconds
-    pub decay: f64,   // Decay time in seconds
-    pub sustain: f64, // Sustain level (0.0-1.0)
-    pub release: f64, // Release time in seconds
-}
-
-/// Filter data for WebAudio
-#[derive(Serialize, Deserialize, Clone)]
-pub struct FilterData {
-    pub filter_type: String, // "lowpass" | "highpass" | "bandpass"
-    pub cutoff: f64,         // Cutoff frequency in Hz
-    pub resonance: f64,      // Q/resonance (0.0-1.0)
-}
-
-/// Pitch envelope data for WebAudio (used for drum sounds like kicks)
-#[derive(Serialize, Deserialize, Clone)]
-pub struct PitchEnvelopeData {
-    pub start_hz: f64,     // Starting frequency in Hz
-    pub end_hz: f64,       // Ending frequency in Hz
-    pub time_seconds: f64, // Duration of the pitch sweep
-}
-
-/// Complete synth data for WebAudio playback
-#[derive(Serialize, Deserialize, Clone)]
-pub struct SynthData {
-    pub name: String,
-    pub oscillators: Vec<OscillatorData>,
-    pub envelope: ADSRData,
-    pub filter: Option<FilterData>,
-    pub detune_cents: f64,
-    pub pitch_envelope: Option<PitchEnvelopeData>,
-}
-
-/// Audio note event with synth information
-#[derive(Serialize, Deserialize, Clone)]
-pub struct AudioNoteEvent {
-    pub pitch: i32,
-    pub start: f64,
-    pub duration: f64,
-    pub velocity: u8,
-    pub synth: Option<SynthData>,
-}
-
-/// Audio playback data with synth information
-#[derive(Serialize, Deserialize)]
-pub struct AudioPlaybackData {
-    pub notes: Vec<AudioNoteEvent>,
-    pub tempo: u32,
-    pub total_beats: f64,
+impl NoteEvent {
+    fn new(pitch: i32, start: f64, duration: f64, velocity: u8) -> Self {
+        Self {
+            pitch,
+            start,
+            duration,
+            velocity,
+            spelling: Spelling::default(),
+            part: 0,
+            measure: 0,
+            beam: None,
+        }
+    }
 }
 
 /// Staff render data
 #[derive(Serialize, Deserialize)]
 pub struct StaffData {
     pub notes: Vec<NoteEvent>,
+    pub parts: Vec<StaffPart>,
     pub tempo: u32,
     pub time_signature_num: u8,
     pub time_signature_den: u8,
+    /// Key signature: sharps when positive, flats when negative
+    pub key_fifths: i32,
     pub total_beats: f64,
+    /// Why the program can't be drawn on a staff
+    pub error: Option<WasmError>,
+}
+
+impl StaffData {
+    /// A staff with nothing on it
+    fn empty(error: Option<WasmError>) -> Self {
+        Self {
+            notes: vec![],
+            parts: vec![],
+            tempo: 120,
+            time_signature_num: 4,
+            time_signature_den: 4,
+            key_fifths: 0,
+            total_beats: 0.0,
+            error,
+        }
+    }
 }
 
 /// Analyze source code and return diagnostics
@@ -154,43 +225,50 @@ pub struct StaffData {
 pub fn analyze(source: &str) -> JsValue {
     let src = Source::from_string("editor", source.to_string());
     let (program, parse_diagnostics) = parse_source(&src);
+    let result = analysis_result(source, &program, &parse_diagnostics);
+    to_js(&result)
+}
 
-    let mut diagnostics: Vec<WasmDiagnostic> = parse_diagnostics
-        .iter()
-        .map(|d| WasmDiagnostic {
-            message: d.message.clone(),
-            start: d.span.start,
-            end: d.span.end,
-            severity: "error".to_string(),
-        })
-        .collect();
+/// Diagnostics of a parsed program, type checked if it parsed cleanly
+fn analysis_result(
+    source: &str,
+    program: &Program,
+    parse_diagnostics: &Diagnostics,
+) -> AnalysisResult {
+    let mut diagnostics: Vec<WasmDiagnostic> =
+        parse_diagnostics.iter().map(WasmDiagnostic::from).collect();
 
     // Type check if parsing succeeded
     if !parse_diagnostics.has_errors() {
-        let mut checker = TypeChecker::new();
-        let type_diagnostics = checker.check_program(&program);
-
-        for diag in type_diagnostics.iter() {
-            diagnostics.push(WasmDiagnostic {
-                message: diag.message.clone(),
-                start: diag.span.start,
-                end: diag.span.end,
-                severity: "error".to_string(),
-            });
-        }
-    }
+        let type_diagnostics = files::check_program(source, program);
 
-    let result = AnalysisResult {
-        diagnostics: diagnostics.clone(),
-        success: diagnostics.is_empty(),
-    };
+        diagnostics.extend(type_diagnostics.iter().map(WasmDiagnostic::from));
+    }
 
-    serde_wasm_bindgen::to_value(&result).unwrap()
+    AnalysisResult {
+        success: !diagnostics.iter().any(|d| d.severity == "error"),
+        diagnostics,
+    }
 }
 
 /// Format source code
+///
+/// `config` is the contents of a `.relafmt` file; the defaults are used when omitted.
 #[wasm_bindgen]
-pub fn format_code(source: &str) -> JsValue {
+pub fn format_code(source: &str, config: Option<String>) -> JsValue {
+    let config = match config.as_deref().map(FormatConfig::from_relafmt) {
+        None => FormatConfig::default(),
+        Some(Ok(config)) => config,
+        Some(Err(e)) => {
+            let result = FormatResult {
+                formatted: source.to_string(),
+                success: false,
+                error: Some(format!("Invalid formatter config: {}", e)),
+            };
+            return to_js(&result);
+        }
+    };
+
     let src = Source::from_string("editor", source.to_string());
     let (program, diagnostics) = parse_source(&src);
 
@@ -200,205 +278,273 @@ pub fn format_code(source: &str) -> JsValue {
             success: false,
             error: Some("Cannot format: parse errors".to_string()),
         };
-        return serde_wasm_bindgen::to_value(&result).unwrap();
+        return to_js(&result);
     }
 
-    let config = FormatConfig::default();
-    let formatted = format(&program, &config);
+    let formatted = format_with_source(&program, source, &config);
 
     let result = FormatResult {
         formatted,
         success: true,
         error: None,
     };
-    serde_wasm_bindgen::to_value(&result).unwrap()
+    to_js(&result)
 }
 
 /// Evaluate source code and return the result
 #[wasm_bindgen]
 pub fn evaluate(source: &str) -> JsValue {
-    let src = Source::from_string("editor", source.to_string());
-    let (program, diagnostics) = parse_source(&src);
+    let evaluation = Evaluation::of_source(source);
+    to_js(&evaluation.eval_result())
+}
 
-    if diagnostics.has_errors() {
-        let result = EvalResult {
-            success: false,
-            value: None,
-            error: Some("Parse errors".to_string()),
-        };
-        return serde_wasm_bindgen::to_value(&result).unwrap();
+/// The result of evaluating a program, with the settings the calls that
+/// read it need
+pub(crate) struct Evaluation {
+    /// The program's output, or why there is none: its first `render`
+    /// target, or else the value of its last expression
+    value: Result<Value, WasmError>,
+    /// Tempo, key and meter from the program's `set` bindings
+    context: RenderContext,
+    /// Warnings the evaluator reported
+    warnings: Vec<WasmDiagnostic>,
+}
+
+impl Evaluation {
+    pub(crate) fn of_source(source: &str) -> Self {
+        let src = Source::from_string("editor", source.to_string());
+        let (program, diagnostics) = parse_source(&src);
+        Self::of_program(&program, &diagnostics)
     }
 
-    let mut evaluator = Evaluator::new();
-    match evaluator.eval_program(&program) {
-        Ok(value) => {
-            let result = EvalResult {
+    pub(crate) fn of_program(program: &Program, parse_diagnostics: &Diagnostics) -> Self {
+        if let Some(parse_error) = parse_diagnostics
+            .iter()
+            .find(|diag| diag.kind == DiagnosticKind::Error)
+        {
+            return Self {
+                value: Err(WasmError {
+                    message: "Parse errors".to_string(),
+                    start: parse_error.span.start,
+                    end: parse_error.span.end,
+                }),
+                context: RenderContext::default(),
+                warnings: Vec::new(),
+            };
+        }
+
+        let mut evaluator = files::new_evaluator();
+        let result = evaluator.eval_program(program);
+        Self::of_evaluator(&evaluator, result)
+    }
+
+    /// The evaluation `evaluator` was left with, `result` being the value
+    /// of the program it evaluated
+    pub(crate) fn of_evaluator(evaluator: &Evaluator, result: Result<Value, EvalError>) -> Self {
+        let value = result
+            .map(|value| match evaluator.render_targets().first() {
+                Some(target) => Value::Song(target.song.clone()),
+                None => value,
+            })
+            .map_err(|e| WasmError::from(&e));
+        Self {
+            value,
+            context: RenderContext::from_evaluator(evaluator),
+            warnings: evaluator
+                .warnings()
+                .iter()
+                .map(WasmDiagnostic::from)
+                .collect(),
+        }
+    }
+
+    /// MIDI note of the root (default to C4 = 60 if no key is set)
+    fn base_note(&self) -> i32 {
+        self.context.base_note as i32
+    }
+
+    pub(crate) fn eval_result(&self) -> EvalResult {
+        match &self.value {
+            Ok(value) => EvalResult {
                 success: true,
                 value: Some(format!("{:?}", value)),
                 error: None,
-            };
-            serde_wasm_bindgen::to_value(&result).unwrap()
-        }
-        Err(e) => {
-            let result = EvalResult {
+                warnings: self.warnings.clone(),
+            },
+            Err(e) => EvalResult {
                 success: false,
                 value: None,
-                error: Some(e.to_string()),
-            };
-            serde_wasm_bindgen::to_value(&result).unwrap()
+                error: Some(e.clone()),
+                warnings: self.warnings.clone(),
+            },
         }
     }
-}
 
-/// Render source to MIDI data
-#[wasm_bindgen]
-pub fn render_midi(source: &str) -> JsValue {
-    let src = Source::from_string("editor", source.to_string());
-    let (program, diagnostics) = parse_source(&src);
-
-    if diagnostics.has_errors() {
-        let result = RenderResult {
-            success: false,
-            midi_data: None,
-            error: Some("Parse errors".to_string()),
-        };
-        return serde_wasm_bindgen::to_value(&result).unwrap();
-    }
+    pub(crate) fn render_result(&self) -> RenderResult {
+        match &self.value {
+            Ok(value) => {
+                let renderer = MidiRenderer::new(self.context.midi_config());
 
-    let mut evaluator = Evaluator::new();
-    match evaluator.eval_program(&program) {
-        Ok(value) => {
-            // Create MidiConfig with key from environment if available
-            let mut config = MidiConfig::default();
-            if let Some(key_note) = get_key_from_evaluator(&evaluator) {
-                config.base_note = key_note;
-            }
-            let renderer = MidiRenderer::new(config);
-
-            // Extract SongValue from the result
-            if let Value::Song(song) = value {
-                let midi_data = renderer.render(&song);
-                let result = RenderResult {
-                    success: true,
-                    midi_data: Some(midi_data),
-                    error: None,
+                // Extract SongValue from the result, or create a song from a block
+                let midi_data = match value {
+                    Value::Song(song) => renderer.render(song),
+                    _ => renderer.render(&create_song_from_value(value)),
                 };
-                serde_wasm_bindgen::to_value(&result).unwrap()
-            } else {
-                // Try to create a song from a block
-                let song = create_song_from_value(&value);
-                let midi_data = renderer.render(&song);
-                let result = RenderResult {
-                    success: true,
-                    midi_data: Some(midi_data),
-                    error: None,
-                };
-                serde_wasm_bindgen::to_value(&result).unwrap()
+                match midi_data {
+                    Ok(midi_data) => RenderResult {
+                        success: true,
+                        midi_data: Some(midi_data),
+                        error: None,
+                    },
+                    Err(e) => RenderResult {
+                        success: false,
+                        midi_data: None,
+                        error: Some(WasmError::unplaced(e.to_string())),
+                    },
+                }
             }
-        }
-        Err(e) => {
-            let result = RenderResult {
+            Err(e) => RenderResult {
                 success: false,
                 midi_data: None,
-                error: Some(e.to_string()),
-            };
-            serde_wasm_bindgen::to_value(&result).unwrap()
+                error: Some(e.clone()),
+            },
         }
     }
-}
 
-fn create_song_from_value(value: &Value) -> SongValue {
-    use relanote_eval::{PartValue, SectionValue};
+    pub(crate) fn staff_data(&self) -> StaffData {
+        let Ok(value) = &self.value else {
+            return StaffData::empty(None);
+        };
+        let (beats, unit) = self.context.time_signature;
+        let (Ok(time_signature_num), Ok(time_signature_den)) =
+            (u8::try_from(beats), u8::try_from(unit))
+        else {
+            return StaffData::empty(Some(WasmError::unplaced(format!(
+                "the time signature {}/{} is too large for the staff",
+                beats, unit
+            ))));
+        };
 
-    match value {
-        Value::Block(block) => SongValue {
-            sections: vec![SectionValue {
-                name: "Main".to_string(),
-                parts: vec![PartValue {
-                    instrument: "Piano".to_string(),
-                    blocks: vec![block.clone()],
-                    envelope: None,
-                    reverb_level: None,
-                    volume_level: None,
-                    delay: None,
-                    phaser: None,
-                    distortion: None,
-                    synth: None,
-                }],
-            }],
-        },
-        Value::Song(song) => song.clone(),
-        _ => SongValue { sections: vec![] },
-    }
-}
+        // Extract note events from the evaluated value
+        let (mut notes, instruments) = extract_notes_from_value(value, self.base_note());
+        let total_beats = notes
+            .iter()
+            .map(|n| n.start + n.duration)
+            .fold(0.0, f64::max);
 
-/// Get staff notation data for rendering
-#[wasm_bindgen]
-pub fn get_staff_data(source: &str) -> JsValue {
-    let src = Source::from_string("editor", source.to_string());
-    let (program, diagnostics) = parse_source(&src);
+        let key_fifths = staff::key_fifths(self.base_note());
+        for note in &mut notes {
+            note.spelling = staff::spell(note.pitch, key_fifths);
+        }
+        staff::group_notes(&mut notes, self.context.beats_per_bar());
+        let parts = instruments
+            .into_iter()
+            .enumerate()
+            .map(|(index, instrument)| {
+                let pitches = notes
+                    .iter()
+                    .filter(|note| note.part == index)
+                    .map(|note| note.pitch);
+                StaffPart {
+                    instrument,
+                    clef: staff::suggest_clef(pitches).to_string(),
+                }
+            })
+            .collect();
 
-    if diagnostics.has_errors() {
-        // Return empty staff data
-        let data = StaffData {
-            notes: vec![],
-            tempo: 120,
-            time_signature_num: 4,
-            time_signature_den: 4,
-            total_beats: 0.0,
-        };
-        return serde_wasm_bindgen::to_value(&data).unwrap();
+        StaffData {
+            notes,
+            parts,
+            tempo: self.context.tempo,
+            time_signature_num,
+            time_signature_den,
+            key_fifths,
+            total_beats,
+            error: None,
+        }
     }
 
-    let mut evaluator = Evaluator::new();
-    match evaluator.eval_program(&program) {
-        Ok(value) => {
-            // Get key from environment (default to C4 = 60 if not specified)
-            let base_note = get_key_from_evaluator(&evaluator)
-                .map(|n| n as i32)
-                .unwrap_or(60);
-
-            // Extract note events from the evaluated value
-            let notes = extract_notes_from_value(&value, base_note);
-            let total_beats = notes
-                .iter()
-                .map(|n| n.start + n.duration)
-                .fold(0.0, f64::max);
-
-            // Try to get tempo from environment
-            let tempo = evaluator
-                .get_binding("tempo")
-                .and_then(|v| {
-                    if let Value::Int(t) = v {
-                        Some(t as u32)
-                    } else {
-                        None
-                    }
-                })
-                .unwrap_or(120);
-
-            let data = StaffData {
-                notes,
-                tempo,
-                time_signature_num: 4,
-                time_signature_den: 4,
-                total_beats,
-            };
-            serde_wasm_bindgen::to_value(&data).unwrap()
-        }
-        Err(_) => {
-            let data = StaffData {
+    pub(crate) fn audio_data(&self) -> AudioPlaybackData {
+        let Ok(value) = &self.value else {
+            return AudioPlaybackData {
                 notes: vec![],
                 tempo: 120,
-                time_signature_num: 4,
-                time_signature_den: 4,
                 total_beats: 0.0,
+                buses: vec![],
+                master: vec![],
             };
-            serde_wasm_bindgen::to_value(&data).unwrap()
+        };
+        playback_data(value, &self.context)
+    }
+
+    /// Audio notes starting in `[from_beat, to_beat)`
+    pub(crate) fn audio_events(&self, from_beat: f64, to_beat: f64) -> AudioEventWindow {
+        let ((notes, total_beats), (buses, master)) = match &self.value {
+            Ok(value) => (
+                audio_notes(value, self.base_note(), from_beat..to_beat),
+                mix_data(value),
+            ),
+            Err(_) => ((vec![], 0.0), (vec![], vec![])),
+        };
+        AudioEventWindow {
+            notes,
+            tempo: self.context.tempo,
+            from_beat,
+            to_beat,
+            total_beats,
+            buses,
+            master,
         }
     }
 }
 
+/// List the render targets a program declares, in order
+///
+/// The editor plays the first one; without any, it plays the program's
+/// last expression.
+#[wasm_bindgen]
+pub fn list_render_targets(source: &str) -> JsValue {
+    let src = Source::from_string("editor", source.to_string());
+    let (program, _) = parse_source(&src);
+    to_js(&render_targets(&program))
+}
+
+pub(crate) fn render_targets(program: &Program) -> Vec<RenderTargetInfo> {
+    program
+        .items
+        .iter()
+        .filter_map(|item| match &item.node {
+            Item::Render(render) => Some(RenderTargetInfo {
+                name: render.name.clone(),
+                start: item.span.start,
+                end: item.span.end,
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Render source to MIDI data
+#[wasm_bindgen]
+pub fn render_midi(source: &str) -> JsValue {
+    let evaluation = Evaluation::of_source(source);
+    to_js(&evaluation.render_result())
+}
+
+fn create_song_from_value(value: &Value) -> SongValue {
+    value
+        .clone()
+        .into_song()
+        .unwrap_or_else(|| SongValue::new(vec![]))
+}
+
+/// Get staff notation data for rendering
+#[wasm_bindgen]
+pub fn get_staff_data(source: &str) -> JsValue {
+    let evaluation = Evaluation::of_source(source);
+    to_js(&evaluation.staff_data())
+}
+
 fn extract_notes_from_block(
     block: &relanote_eval::BlockValue,
     velocity: u8,
@@ -422,62 +568,27 @@ fn extract_notes_from_block(
         // Use explicit duration if set, otherwise use default (relative rhythm)
         let beat_duration = slot.duration_beats().unwrap_or(default_beat_duration);
 
-        match slot {
-            SlotValue::Note { interval, .. } => {
-                notes.push(NoteEvent {
-                    pitch: base_note + interval.semitones().round() as i32,
-                    start: current_beat,
-                    duration: beat_duration,
-                    velocity,
-                });
-            }
-            SlotValue::Chord { intervals, .. } => {
-                for interval in intervals {
-                    notes.push(NoteEvent {
-                        pitch: base_note + interval.semitones().round() as i32,
-                        start: current_beat,
-                        duration: beat_duration,
+        for (slot, start, duration) in sounding_slots(slot, current_beat, beat_duration) {
+            match slot {
+                SlotValue::Note { interval, .. } => {
+                    notes.push(NoteEvent::new(
+                        base_note + interval.semitones().round() as i32,
+                        start,
+                        duration,
                         velocity,
-                    });
+                    ));
                 }
-            }
-            SlotValue::Rest { .. } => {}
-            SlotValue::Tuplet {
-                slots: tuplet_slots,
-                target_beats,
-            } => {
-                // Tuplet: notes are equally divided within target_beats
-                let tuplet_slot_count = tuplet_slots.len();
-                let tuplet_slot_duration = if tuplet_slot_count > 0 {
-                    (*target_beats as f64) / tuplet_slot_count as f64
-                } else {
-                    0.0
-                };
-                let mut tuplet_beat = current_beat;
-                for slot in tuplet_slots {
-                    match slot {
-                        SlotValue::Note { interval, .. } => {
-                            notes.push(NoteEvent {
-                                pitch: base_note + interval.semitones().round() as i32,
-                                start: tuplet_beat,
-                                duration: tuplet_slot_duration,
-                                velocity,
-                            });
-                        }
-                        SlotValue::Chord { intervals, .. } => {
-                            for interval in intervals {
-                                notes.push(NoteEvent {
-                                    pitch: base_note + interval.semitones().round() as i32,
-                                    start: tuplet_beat,
-                                    duration: tuplet_slot_duration,
-                                    velocity,
-                                });
-                            }
-                        }
-                        _ => {}
+                SlotValue::Chord { intervals, .. } => {
+                    for interval in intervals {
+                        notes.push(NoteEvent::new(
+                            base_note + interval.semitones().round() as i32,
+                            start,
+                            duration,
+                            velocity,
+                        ));
                     }
-                    tuplet_beat += tuplet_slot_duration;
                 }
+                _ => {}
             }
         }
         current_beat += beat_duration;
@@ -486,19 +597,27 @@ fn extract_notes_from_block(
     (notes, current_beat)
 }
 
-fn extract_notes_from_value(value: &relanote_eval::Value, base_note: i32) -> Vec<NoteEvent> {
+/// Notes of a block, part or song, with the instrument of each part they
+/// belong to
+fn extract_notes_from_value(
+    value: &relanote_eval::Value,
+    base_note: i32,
+) -> (Vec<NoteEvent>, Vec<String>) {
     use relanote_eval::Value;
 
     let mut notes = Vec::new();
+    let mut parts = Vec::new();
 
     match value {
         Value::Block(block) => {
             let (block_notes, _) = extract_notes_from_block(block, 100, 0.0, base_note);
             notes.extend(block_notes);
+            parts.push(String::new());
         }
         Value::Song(song) => {
             // Extract notes from all parts in the song
             for section in &song.sections {
+                let base_note = section.base_note(base_note as u8) as i32;
                 for part in &section.parts {
                     // Skip metronome parts - don't show in notation
                     if part.instrument.to_lowercase().contains("metronome") {
@@ -511,11 +630,16 @@ fn extract_notes_from_value(value: &relanote_eval::Value, base_note: i32) -> Vec
                         .map(|v| ((v * 100.0).round() as u8).clamp(1, 127))
                         .unwrap_or(100);
 
+                    let index = parts.len();
+                    parts.push(part.instrument.clone());
                     let mut current_beat = 0.0;
                     for block in &part.blocks {
                         let (block_notes, end_beat) =
                             extract_notes_from_block(block, velocity, current_beat, base_note);
-                        notes.extend(block_notes);
+                        notes.extend(block_notes.into_iter().map(|note| NoteEvent {
+                            part: index,
+                            ..note
+                        }));
                         current_beat = end_beat;
                     }
                 }
@@ -524,7 +648,7 @@ fn extract_notes_from_value(value: &relanote_eval::Value, base_note: i32) -> Vec
         _ => {}
     }
 
-    notes
+    (notes, parts)
 }
 
 /// Get syntax highlighting tokens
@@ -549,6 +673,8 @@ pub fn get_tokens(source: &str) -> JsValue {
             let kind = match &t.kind {
                 TokenKind::Let
                 | TokenKind::Set
+                | TokenKind::Meta
+                | TokenKind::Test
                 | TokenKind::In
                 | TokenKind::If
                 | TokenKind::Then
@@ -559,6 +685,7 @@ pub fn get_tokens(source: &str) -> JsValue {
                 | TokenKind::Chord
                 | TokenKind::Section
                 | TokenKind::Layer
+                | TokenKind::Kit
                 | TokenKind::Part
                 | TokenKind::Env
                 | TokenKind::Import
@@ -573,8 +700,10 @@ pub fn get_tokens(source: &str) -> JsValue {
                 TokenKind::Integer(_) | TokenKind::Float(_) => "number",
                 TokenKind::String(_) => "string",
                 TokenKind::Ident(_) => "identifier",
-                TokenKind::Interval(_) => "interval",
-                TokenKind::AbsolutePitch(_) => "pitch",
+                TokenKind::Interval(_) | TokenKind::Cents(_) | TokenKind::Semitones(_) => {
+                    "interval"
+                }
+                TokenKind::AbsolutePitch(_) | TokenKind::SharpNote(_) => "pitch",
                 TokenKind::Root => "root",
                 TokenKind::Pipe
                 | TokenKind::PipeOp
@@ -584,8 +713,12 @@ pub fn get_tokens(source: &str) -> JsValue {
                 | TokenKind::Colon
                 | TokenKind::Comma
                 | TokenKind::Dot
+                | TokenKind::DotDot
+                | TokenKind::Ampersand
                 | TokenKind::Minus
-                | TokenKind::Plus => "operator",
+                | TokenKind::Plus
+                | TokenKind::Slash
+                | TokenKind::Percent => "operator",
                 TokenKind::Staccato | TokenKind::Accent | TokenKind::Portamento => "articulation",
                 TokenKind::LBrace
                 | TokenKind::RBrace
@@ -595,7 +728,7 @@ pub fn get_tokens(source: &str) -> JsValue {
                 | TokenKind::RParen
                 | TokenKind::LAngle
                 | TokenKind::RAngle => "bracket",
-                TokenKind::Bars | TokenKind::Beats => "duration",
+                TokenKind::Bars(_) | TokenKind::Beats(_) | TokenKind::NoteValue(_) => "duration",
                 _ => "default",
             };
             TokenInfo {
@@ -606,347 +739,7 @@ pub fn get_tokens(source: &str) -> JsValue {
         })
         .collect();
 
-    serde_wasm_bindgen::to_value(&token_infos).unwrap()
-}
-
-/// Convert SynthValue to SynthData for WebAudio
-fn synth_value_to_data(synth: &relanote_eval::value::SynthValue) -> SynthData {
-    use relanote_eval::value::{FilterType, Waveform};
-
-    let oscillators = synth
-        .oscillators
-        .iter()
-        .map(|osc| {
-            let (waveform, pulse_duty) = match &osc.waveform {
-                Waveform::Sine => ("sine".to_string(), 0.0),
-                Waveform::Square => ("square".to_string(), 0.5),
-                Waveform::Saw => ("sawtooth".to_string(), 0.0),
-                Waveform::Triangle => ("triangle".to_string(), 0.0),
-                Waveform::Noise => ("noise".to_string(), 0.0),
-                Waveform::Pulse(duty) => ("pulse".to_string(), *duty),
-            };
-            OscillatorData {
-                waveform,
-                pulse_duty,
-                mix: osc.mix,
-                octave_offset: osc.octave_offset,
-                detune_cents: osc.detune_cents,
-            }
-        })
-        .collect();
-
-    let envelope = ADSRData {
-        attack: synth.envelope.attack,
-        decay: synth.envelope.decay,
-        sustain: synth.envelope.sustain,
-        release: synth.envelope.release,
-    };
-
-    let filter = synth.filter.as_ref().map(|f| {
-        let filter_type = match f.filter_type {
-            FilterType::LowPass => "lowpass".to_string(),
-            FilterType::HighPass => "highpass".to_string(),
-            FilterType::BandPass => "bandpass".to_string(),
-        };
-        FilterData {
-            filter_type,
-            cutoff: f.cutoff,
-            resonance: f.resonance,
-        }
-    });
-
-    let pitch_envelope = synth
-        .pitch_envelope
-        .map(|(start, end, time)| PitchEnvelopeData {
-            start_hz: start,
-            end_hz: end,
-            time_seconds: time,
-        });
-
-    SynthData {
-        name: synth.name.clone(),
-        oscillators,
-        envelope,
-        filter,
-        detune_cents: synth.detune_cents,
-        pitch_envelope,
-    }
-}
-
-/// Extract audio notes with synth data from a part
-fn extract_audio_notes_from_part(
-    part: &relanote_eval::PartValue,
-    start_beat: f64,
-    base_note: i32, // MIDI note number for root (60 = C4)
-) -> (Vec<AudioNoteEvent>, f64) {
-    use relanote_eval::SlotValue;
-
-    let mut notes = Vec::new();
-    let mut current_beat = start_beat;
-
-    // Get synth data if available
-    let synth_data = part.synth.as_ref().map(synth_value_to_data);
-
-    // Calculate velocity from volume_level
-    let velocity = part
-        .volume_level
-        .map(|v| ((v * 100.0).round() as u8).clamp(1, 127))
-        .unwrap_or(100);
-
-    for block in &part.blocks {
-        let slot_count = block.slots.len();
-        let default_beat_duration = if slot_count > 0 {
-            block.beats / slot_count as f64
-        } else {
-            0.0
-        };
-
-        for slot in &block.slots {
-            let beat_duration = slot.duration_beats().unwrap_or(default_beat_duration);
-
-            match slot {
-                SlotValue::Note { interval, .. } => {
-                    notes.push(AudioNoteEvent {
-                        pitch: base_note + interval.semitones().round() as i32,
-                        start: current_beat,
-                        duration: beat_duration,
-                        velocity,
-                        synth: synth_data.clone(),
-                    });
-                }
-                SlotValue::Chord { intervals, .. } => {
-                    for interval in intervals {
-                        notes.push(AudioNoteEvent {
-                            pitch: base_note + interval.semitones().round() as i32,
-                            start: current_beat,
-                            duration: beat_duration,
-                            velocity,
-                            synth: synth_data.clone(),
-                        });
-                    }
-                }
-                SlotValue::Rest { .. } => {}
-                SlotValue::Tuplet {
-                    slots: tuplet_slots,
-                    target_beats,
-                } => {
-                    let tuplet_slot_count = tuplet_slots.len();
-                    let tuplet_slot_duration = if tuplet_slot_count > 0 {
-                        (*target_beats as f64) / tuplet_slot_count as f64
-                    } else {
-                        0.0
-                    };
-                    let mut tuplet_beat = current_beat;
-                    for inner_slot in tuplet_slots {
-                        match inner_slot {
-                            SlotValue::Note { interval, .. } => {
-                                notes.push(AudioNoteEvent {
-                                    pitch: base_note + interval.semitones().round() as i32,
-                                    start: tuplet_beat,
-                                    duration: tuplet_slot_duration,
-                                    velocity,
-                                    synth: synth_data.clone(),
-                                });
-                            }
-                            SlotValue::Chord { intervals, .. } => {
-                                for interval in intervals {
-                                    notes.push(AudioNoteEvent {
-                                        pitch: base_note + interval.semitones().round() as i32,
-                                        start: tuplet_beat,
-                                        duration: tuplet_slot_duration,
-                                        velocity,
-                                        synth: synth_data.clone(),
-                                    });
-                                }
-                            }
-                            _ => {}
-                        }
-                        tuplet_beat += tuplet_slot_duration;
-                    }
-                }
-            }
-            current_beat += beat_duration;
-        }
-    }
-
-    (notes, current_beat)
-}
-
-/// Note data from piano roll for code generation
-#[derive(Serialize, Deserialize, Clone)]
-pub struct PianoRollNote {
-    pub pitch: i32,    // MIDI note (0-127)
-    pub start: f64,    // Start time in beats
-    pub duration: f64, // Duration in beats
-    pub velocity: u8,  // 0-127
-}
-
-/// Generate Relanote code from piano roll notes
-#[wasm_bindgen]
-pub fn notes_to_code(
-    notes_json: &str,
-    synth_name: Option<String>,
-    key_pitch: Option<i32>,
-) -> String {
-    let notes: Vec<PianoRollNote> = match serde_json::from_str(notes_json) {
-        Ok(n) => n,
-        Err(_) => return "".to_string(),
-    };
-
-    if notes.is_empty() {
-        return "| - |".to_string();
-    }
-
-    // Default key is C4 (MIDI 60)
-    let base_pitch = key_pitch.unwrap_or(60);
-
-    // Group notes by start time
-    let mut time_groups: std::collections::BTreeMap<i64, Vec<&PianoRollNote>> =
-        std::collections::BTreeMap::new();
-    for note in &notes {
-        // Round to 1/16 beat precision
-        let start_key = (note.start * 16.0).round() as i64;
-        time_groups.entry(start_key).or_default().push(note);
-    }
-
-    // Find the total duration
-    let total_beats = notes
-        .iter()
-        .map(|n| n.start + n.duration)
-        .fold(0.0_f64, f64::max);
-
-    // Calculate number of bars (4 beats per bar)
-    let num_bars = ((total_beats / 4.0).ceil() as i32).max(1);
-
-    let mut result = String::new();
-
-    // Generate bars
-    for bar in 0..num_bars {
-        let bar_start = bar as f64 * 4.0;
-        let bar_end = bar_start + 4.0;
-
-        result.push_str("| ");
-
-        // Collect notes in this bar
-        let mut bar_slots: Vec<String> = Vec::new();
-        let mut current_time = bar_start;
-
-        // Find all unique time points in this bar
-        let mut time_points: Vec<f64> = time_groups
-            .keys()
-            .map(|k| *k as f64 / 16.0)
-            .filter(|t| *t >= bar_start && *t < bar_end)
-            .collect();
-        time_points.sort_by(|a, b| a.partial_cmp(b).unwrap());
-        time_points.dedup_by(|a, b| (*a - *b).abs() < 0.001);
-
-        if time_points.is_empty() {
-            // Empty bar - add rests
-            bar_slots.push("-".to_string());
-        } else {
-            for &time in &time_points {
-                // Add rest if there's a gap
-                if time > current_time + 0.001 {
-                    let gap = time - current_time;
-                    if gap >= 1.0 {
-                        bar_slots.push(format!("-:{}", gap));
-                    } else {
-                        bar_slots.push("-".to_string());
-                    }
-                }
-
-                let key = (time * 16.0).round() as i64;
-                if let Some(notes_at_time) = time_groups.get(&key) {
-                    if notes_at_time.len() == 1 {
-                        // Single note
-                        let note = notes_at_time[0];
-                        let interval = pitch_to_interval(note.pitch, base_pitch);
-                        if note.duration >= 1.0
-                            && (note.duration - note.duration.round()).abs() < 0.001
-                        {
-                            bar_slots.push(format!(
-                                "{}:{}",
-                                interval,
-                                note.duration.round() as i32
-                            ));
-                        } else {
-                            bar_slots.push(interval);
-                        }
-                        current_time = time + note.duration;
-                    } else {
-                        // Chord
-                        let intervals: Vec<String> = notes_at_time
-                            .iter()
-                            .map(|n| pitch_to_interval(n.pitch, base_pitch))
-                            .collect();
-                        let duration = notes_at_time[0].duration;
-                        let chord_str = format!("[{}]", intervals.join(" "));
-                        if duration >= 1.0 && (duration - duration.round()).abs() < 0.001 {
-                            bar_slots.push(format!("{}:{}", chord_str, duration.round() as i32));
-                        } else {
-                            bar_slots.push(chord_str);
-                        }
-                        current_time = time + duration;
-                    }
-                }
-            }
-        }
-
-        result.push_str(&bar_slots.join(" "));
-        result.push_str(" |");
-
-        if bar < num_bars - 1 {
-            result.push_str(" ++ ");
-        }
-    }
-
-    // Add synth voice if specified
-    if let Some(synth) = synth_name {
-        if !synth.is_empty() && synth != "Default" {
-            result = format!("{} |> voice {}", result, synth);
-        }
-    }
-
-    result
-}
-
-/// Convert MIDI pitch to interval notation
-fn pitch_to_interval(midi_pitch: i32, base_pitch: i32) -> String {
-    let semitones = midi_pitch - base_pitch;
-
-    // Common intervals
-    match semitones {
-        0 => "R".to_string(),
-        1 => "m2".to_string(),
-        2 => "M2".to_string(),
-        3 => "m3".to_string(),
-        4 => "M3".to_string(),
-        5 => "P4".to_string(),
-        6 => "d5".to_string(),
-        7 => "P5".to_string(),
-        8 => "m6".to_string(),
-        9 => "M6".to_string(),
-        10 => "m7".to_string(),
-        11 => "M7".to_string(),
-        12 => "P8".to_string(),
-        _ if semitones > 12 => {
-            let octaves = semitones / 12;
-            let remainder = semitones % 12;
-            let base_interval = pitch_to_interval(base_pitch + remainder, base_pitch);
-            format!("{}+{}", base_interval, octaves)
-        }
-        _ if semitones < 0 => {
-            let octaves = (-semitones) / 12;
-            let remainder = 12 - ((-semitones) % 12);
-            if remainder == 12 {
-                format!("R-{}", octaves)
-            } else {
-                let base_interval = pitch_to_interval(base_pitch + remainder, base_pitch);
-                format!("{}-{}", base_interval, octaves + 1)
-            }
-        }
-        _ => format!("{}st", semitones),
-    }
+    to_js(&token_infos)
 }
 
 // =============================================================================
@@ -962,9 +755,22 @@ pub struct CompletionItem {
     pub insert_text: Option<String>,
 }
 
-/// Get all completion items
+/// Get all completion items, including the bindings `source` defines and
+/// imports
+///
+/// `source` is optional so callers of the older zero-argument form keep
+/// getting the builtin items.
 #[wasm_bindgen]
-pub fn get_completions() -> JsValue {
+pub fn get_completions(source: Option<String>) -> JsValue {
+    let source = source.unwrap_or_default();
+    let src = Source::from_string("editor", source.clone());
+    let (program, _) = parse_source(&src);
+    to_js(&completions(&files::type_checker(&source, &program)))
+}
+
+/// Builtin completion items followed by the bindings of the program
+/// `checker` checked
+pub(crate) fn completions(checker: &TypeChecker) -> Vec<CompletionItem> {
     let mut completions = Vec::new();
 
     // Keywords
@@ -975,13 +781,19 @@ pub fn get_completions() -> JsValue {
         ("in", "Local binding scope"),
         ("section", "Define a section"),
         ("layer", "Combine multiple parts"),
+        ("kit", "Define a drum kit"),
         ("part", "Define a part"),
         ("if", "Conditional expression"),
         ("then", "Then branch"),
         ("else", "Else branch"),
         ("match", "Pattern matching"),
-        ("with", "Match patterns"),
+        ("with", "Modify a scale or chord"),
         ("set", "Set global property"),
+        ("meta", "Song title, composer and copyright"),
+        ("test", "Test run by relanote test"),
+        ("render", "Song the program outputs"),
+        ("context", "Scope tempo, key and swing"),
+        ("env", "Dynamics envelope"),
         ("import", "Import module"),
         ("export", "Export binding"),
         ("from", "Import source"),
@@ -1012,37 +824,17 @@ pub fn get_completions() -> JsValue {
         });
     }
 
-    // Built-in functions
-    let functions = [
-        ("reverse", "Reverse a block"),
-        ("transpose", "Transpose by an interval"),
-        ("repeat", "Repeat n times"),
-        ("volume", "Set volume (0.0-1.0)"),
-        ("reverb", "Apply reverb (0.0-1.0)"),
-        ("hall_reverb", "Hall reverb preset"),
-        ("room_reverb", "Room reverb preset"),
-        ("plate_reverb", "Plate reverb preset"),
-        ("dry", "No reverb"),
-        ("voice", "Set instrument voice"),
-        ("swing", "Apply swing feel"),
-        ("double_time", "Double tempo"),
-        ("half_time", "Half tempo"),
-        ("metronome", "Generate metronome"),
-        ("cutoff", "Filter cutoff frequency"),
-        ("pan", "Stereo pan (-1.0 to 1.0)"),
-        ("delay", "Apply delay effect"),
-        ("stretch", "Time stretch"),
-        ("compress", "Time compress"),
-        ("quantize", "Quantize to note value"),
-        ("invert", "Invert intervals"),
-        ("retrograde", "Retrograde melody"),
-        ("shuffle", "Shuffle notes"),
-    ];
-    for (label, detail) in functions {
+    // Builtins, from the registry
+    for builtin in relanote_meta::BUILTINS {
+        let kind = if builtin.is_value() {
+            "constant"
+        } else {
+            "function"
+        };
         completions.push(CompletionItem {
-            label: label.to_string(),
-            kind: "function".to_string(),
-            detail: detail.to_string(),
+            label: builtin.name.to_string(),
+            kind: kind.to_string(),
+            detail: builtin.summary().to_string(),
             insert_text: None,
         });
     }
@@ -1156,64 +948,22 @@ pub fn get_completions() -> JsValue {
         });
     }
 
-    // Scales
-    let scales = [
-        ("Major", "Major scale { R, M2, M3, P4, P5, M6, M7 }"),
-        ("Minor", "Natural minor { R, M2, m3, P4, P5, m6, m7 }"),
-        (
-            "HarmonicMinor",
-            "Harmonic minor { R, M2, m3, P4, P5, m6, M7 }",
-        ),
-        (
-            "MelodicMinor",
-            "Melodic minor { R, M2, m3, P4, P5, M6, M7 }",
-        ),
-        ("Dorian", "Dorian mode { R, M2, m3, P4, P5, M6, m7 }"),
-        ("Phrygian", "Phrygian mode { R, m2, m3, P4, P5, m6, m7 }"),
-        ("Lydian", "Lydian mode { R, M2, M3, A4, P5, M6, M7 }"),
-        (
-            "Mixolydian",
-            "Mixolydian mode { R, M2, M3, P4, P5, M6, m7 }",
-        ),
-        ("Locrian", "Locrian mode { R, m2, m3, P4, d5, m6, m7 }"),
-        ("MajorPentatonic", "Major pentatonic { R, M2, M3, P5, M6 }"),
-        ("MinorPentatonic", "Minor pentatonic { R, m3, P4, P5, m7 }"),
-        ("Blues", "Blues scale { R, m3, P4, d5, P5, m7 }"),
-        ("WholeTone", "Whole tone { R, M2, M3, A4, A5, A6 }"),
-        ("Chromatic", "Chromatic scale (all 12 tones)"),
-    ];
-    for (label, detail) in scales {
+    // Scales, as the prelude defines them
+    for scale in relanote_meta::scales() {
         completions.push(CompletionItem {
-            label: label.to_string(),
+            label: scale.name.to_string(),
             kind: "class".to_string(),
-            detail: format!("Scale: {}", detail),
+            detail: format!("Scale: {} ({})", scale.definition, scale.description),
             insert_text: None,
         });
     }
 
-    // Chords
-    let chords = [
-        ("MajorTriad", "Major triad { R, M3, P5 }"),
-        ("MinorTriad", "Minor triad { R, m3, P5 }"),
-        ("Diminished", "Diminished { R, m3, d5 }"),
-        ("Augmented", "Augmented { R, M3, A5 }"),
-        ("Major7", "Major 7th { R, M3, P5, M7 }"),
-        ("Minor7", "Minor 7th { R, m3, P5, m7 }"),
-        ("Dominant7", "Dominant 7th { R, M3, P5, m7 }"),
-        ("MinorMajor7", "Minor-major 7th { R, m3, P5, M7 }"),
-        ("HalfDiminished7", "Half-diminished { R, m3, d5, m7 }"),
-        ("Diminished7", "Diminished 7th { R, m3, d5, d7 }"),
-        ("Sus2", "Suspended 2nd { R, M2, P5 }"),
-        ("Sus4", "Suspended 4th { R, P4, P5 }"),
-        ("Add9", "Add 9 { R, M3, P5, M9 }"),
-        ("Add11", "Add 11 { R, M3, P5, P11 }"),
-        ("Power", "Power chord { R, P5 }"),
-    ];
-    for (label, detail) in chords {
+    // Chords, as the prelude defines them
+    for chord in relanote_meta::chords() {
         completions.push(CompletionItem {
-            label: label.to_string(),
+            label: chord.name.to_string(),
             kind: "class".to_string(),
-            detail: format!("Chord: {}", detail),
+            detail: format!("Chord: {} ({})", chord.definition, chord.description),
             insert_text: None,
         });
     }
@@ -1257,7 +1007,29 @@ pub fn get_completions() -> JsValue {
         });
     }
 
-    serde_wasm_bindgen::to_value(&completions).unwrap()
+    // User definitions shadow builtins of the same name
+    let bindings = checker.top_level_bindings();
+    completions.retain(|item| !bindings.iter().any(|(name, _)| item.label == name.as_str()));
+    for (name, ty) in bindings {
+        let kind = match ty {
+            Type::Function(..) => "function",
+            Type::Scale | Type::Chord => "class",
+            Type::Synth => "enum_member",
+            _ => "constant",
+        };
+        let detail = match checker.doc(&name) {
+            Some(doc) => format!("{}: {}\n\n{}", name, ty, doc),
+            None => format!("{}: {}", name, ty),
+        };
+        completions.push(CompletionItem {
+            label: name.to_string(),
+            kind: kind.to_string(),
+            detail,
+            insert_text: None,
+        });
+    }
+
+    completions
 }
 
 /// Hover information result
@@ -1272,6 +1044,15 @@ pub struct HoverResult {
 /// Get hover information at a position
 #[wasm_bindgen]
 pub fn get_hover(source: &str, offset: usize) -> JsValue {
+    let src = Source::from_string("editor", source.to_string());
+    let (program, _) = parse_source(&src);
+    let checker = files::type_checker(source, &program);
+    to_js(&hover(source, &checker, offset))
+}
+
+/// Hover information for the token at `offset` of `source`, whose program
+/// `checker` checked
+pub(crate) fn hover(source: &str, checker: &TypeChecker, offset: usize) -> HoverResult {
     use relanote_lexer::{Lexer, TokenKind};
 
     let src = Source::from_string("editor", source.to_string());
@@ -1281,12 +1062,19 @@ pub fn get_hover(source: &str, offset: usize) -> JsValue {
     for token in &tokens {
         if token.span.start <= offset && offset <= token.span.end {
             let hover_content = match &token.kind {
-                TokenKind::Ident(name) => get_builtin_hover(name),
+                TokenKind::Ident(name) => user_binding_hover(checker, name)
+                    .or_else(|| get_builtin_hover(name)),
                 TokenKind::Interval(interval) => {
                     let semitones = interval_to_semitones(interval);
                     let name = interval_data_to_name(interval);
                     Some(format!("**Interval**: {} ({} semitones)", name, semitones))
                 }
+                TokenKind::Cents(cents) => {
+                    Some(format!("**Interval**: {}c ({} semitones)", cents, cents / 100.0))
+                }
+                TokenKind::Semitones(steps) => {
+                    Some(format!("**Interval**: {}st ({} semitones)", steps, steps))
+                }
                 TokenKind::AbsolutePitch(pitch) => {
                     let midi = pitch.to_midi_note();
                     let acc_str = match pitch.accidental {
@@ -1300,9 +1088,15 @@ pub fn get_hover(source: &str, offset: usize) -> JsValue {
                 TokenKind::Root => Some("**Root** (R): The root/unison of the current scale (0 semitones)".to_string()),
                 TokenKind::Let => Some("**let**: Define a variable binding\n\n```rela\nlet name = value\nlet name = value in expr\n```".to_string()),
                 TokenKind::Set => Some("**set**: Set a global property\n\n```rela\nset tempo = 120\nset key = C4\n```".to_string()),
+                TokenKind::Meta => Some("**meta**: Song metadata written to rendered files\n\n```rela\nmeta { title: \"Etude\", composer: \"Anon\", copyright: \"(c) 2026\" }\n```".to_string()),
+                TokenKind::Test => Some("**test**: A test run by `relanote test`; passes when the body is `true`, or music matching its snapshot\n\n```rela\ntest \"chorus\" { chorus }\n```".to_string()),
+                TokenKind::Render => Some("**render**: Declare a song the program outputs; the editor plays the first one\n\n```rela\nrender song\n```".to_string()),
                 TokenKind::Scale => Some("**scale**: Define a named scale\n\n```rela\nscale Major = { R, M2, M3, P4, P5, M6, M7 }\n```".to_string()),
                 TokenKind::Chord => Some("**chord**: Define a named chord\n\n```rela\nchord Maj = { R, M3, P5 }\n```".to_string()),
+                TokenKind::Env => Some("**env**: Dynamics envelope from one marking to another, applied with `apply_env`\n\n```rela\npad |> apply_env(env(pp, ff, 4 beats))\n```".to_string()),
+                TokenKind::Context => Some("**context**: Play music with its own tempo, key and swing\n\n```rela\ncontext { tempo = 140, key = A3, swing = 0.6 } in chorus\n```".to_string()),
                 TokenKind::Layer => Some("**layer**: Combine multiple parts (polyphony)\n\n```rela\nlayer [\n  melody,\n  bass\n]\n```".to_string()),
+                TokenKind::Kit => Some("**kit**: A drum kit for percussion blocks\n\n```rela\nlet rock = kit { x: Kick, o: Snare }\nlet beat = rock | x - o - |\n```".to_string()),
                 TokenKind::Section => Some("**section**: Define a song section".to_string()),
                 TokenKind::Part => Some("**part**: Define an instrument part".to_string()),
                 TokenKind::PipeOp => Some("**|>**: Pipe operator - applies a function to the left operand".to_string()),
@@ -1311,43 +1105,52 @@ pub fn get_hover(source: &str, offset: usize) -> JsValue {
             };
 
             if let Some(content) = hover_content {
-                let result = HoverResult {
+                return HoverResult {
                     found: true,
                     content: Some(content),
                     start: token.span.start,
                     end: token.span.end,
                 };
-                return serde_wasm_bindgen::to_value(&result).unwrap();
             }
         }
     }
 
-    let result = HoverResult {
+    HoverResult {
         found: false,
         content: None,
         start: 0,
         end: 0,
+    }
+}
+
+/// Inferred type and doc comment of a binding the program defines or
+/// imports
+fn user_binding_hover(checker: &TypeChecker, name: &str) -> Option<String> {
+    // A declared signature reads as the author wrote it
+    let ty = match checker.declared_type(name) {
+        Some(ann) => ann.to_string(),
+        None => checker
+            .top_level_bindings()
+            .into_iter()
+            .find(|(binding, _)| binding.as_str() == name)?
+            .1
+            .to_string(),
     };
-    serde_wasm_bindgen::to_value(&result).unwrap()
+    Some(format!(
+        "```rela\n{}: {}\n```\n\n{}",
+        name,
+        ty,
+        checker.doc(name).unwrap_or("User-defined binding")
+    ))
 }
 
 /// Get hover documentation for builtin identifiers
 fn get_builtin_hover(name: &str) -> Option<String> {
+    if let Some(builtin) = relanote_meta::builtin(name) {
+        return Some(builtin.hover());
+    }
     match name {
-        // Functions
-        "transpose" => Some("**transpose**: Transpose notes by an interval\n\n```rela\nblock |> transpose P8  ; up one octave\nblock |> transpose (R - P8)  ; down one octave\n```".to_string()),
-        "reverse" => Some("**reverse**: Reverse the order of notes in a block".to_string()),
-        "repeat" => Some("**repeat**: Repeat a block N times\n\n```rela\nblock |> repeat 4\n```".to_string()),
-        "volume" => Some("**volume**: Set the volume level (0.0-1.0)\n\n```rela\nblock |> volume 0.8\n```".to_string()),
-        "reverb" => Some("**reverb**: Apply reverb effect (0.0-1.0)\n\n```rela\nblock |> reverb 0.3\n```".to_string()),
-        "voice" => Some("**voice**: Set the instrument/synth voice\n\n```rela\nblock |> voice NES\nblock |> voice Piano\n```".to_string()),
-        "in" => Some("**in**: Apply a scale to a block\n\n```rela\nblock |> in Major\nblock |> in MinorPentatonic\n```".to_string()),
-        "pan" => Some("**pan**: Set stereo pan (-1.0 left to 1.0 right)\n\n```rela\nblock |> pan -0.5  ; left\nblock |> pan 0.5   ; right\n```".to_string()),
-        "delay" => Some("**delay**: Apply delay effect (0.0-1.0)".to_string()),
-        "swing" => Some("**swing**: Apply swing feel (0.5 straight to 0.67 triplet)".to_string()),
-        "double_time" => Some("**double_time**: Double the tempo".to_string()),
-        "half_time" => Some("**half_time**: Halve the tempo".to_string()),
-        "metronome" => Some("**metronome**: Generate a metronome click track".to_string()),
+        "in" => Some("**in**: Apply a scale or chord to a block\n\n```rela\nblock |> in Major\nblock |> in MinorPentatonic\nblock |> in Dominant7\n```".to_string()),
         // Voices
         "NES" => Some("**NES**: NES-style 8-bit pulse wave synthesizer".to_string()),
         "GameBoy" => Some("**GameBoy**: GameBoy-style 8-bit sound".to_string()),
@@ -1358,21 +1161,15 @@ fn get_builtin_hover(name: &str) -> Option<String> {
         "HiHat8bit" => Some("**HiHat8bit**: 8-bit style hi-hat".to_string()),
         "FatBass" => Some("**FatBass**: Fat/thick bass synthesizer".to_string()),
         "Piano" => Some("**Piano**: Acoustic piano sound".to_string()),
-        "Sine" => Some("**Sine**: Pure sine wave oscillator".to_string()),
-        "Square" => Some("**Square**: Square wave oscillator".to_string()),
-        "Sawtooth" => Some("**Sawtooth**: Sawtooth wave oscillator".to_string()),
-        "Triangle" => Some("**Triangle**: Triangle wave oscillator".to_string()),
-        // Scales
-        "Major" => Some("**Major Scale**: { R, M2, M3, P4, P5, M6, M7 }\n\nThe major scale (Ionian mode).".to_string()),
-        "Minor" => Some("**Minor Scale**: { R, M2, m3, P4, P5, m6, m7 }\n\nThe natural minor scale (Aeolian mode).".to_string()),
-        "Dorian" => Some("**Dorian Mode**: { R, M2, m3, P4, P5, M6, m7 }\n\nMinor scale with raised 6th.".to_string()),
-        "Phrygian" => Some("**Phrygian Mode**: { R, m2, m3, P4, P5, m6, m7 }\n\nMinor scale with lowered 2nd.".to_string()),
-        "Lydian" => Some("**Lydian Mode**: { R, M2, M3, A4, P5, M6, M7 }\n\nMajor scale with raised 4th.".to_string()),
-        "Mixolydian" => Some("**Mixolydian Mode**: { R, M2, M3, P4, P5, M6, m7 }\n\nMajor scale with lowered 7th.".to_string()),
-        "Blues" => Some("**Blues Scale**: { R, m3, P4, d5, P5, m7 }\n\nMinor pentatonic with added blue note.".to_string()),
-        "MajorPentatonic" => Some("**Major Pentatonic**: { R, M2, M3, P5, M6 }\n\n5-note major scale.".to_string()),
-        "MinorPentatonic" => Some("**Minor Pentatonic**: { R, m3, P4, P5, m7 }\n\n5-note minor scale.".to_string()),
-        _ => None,
+        // Scales and chords, as the prelude defines them
+        _ => relanote_meta::scale(name)
+            .or_else(|| relanote_meta::chord(name))
+            .map(|definition| {
+                format!(
+                    "**{}**: {}\n\n{}.",
+                    definition.name, definition.definition, definition.description
+                )
+            }),
     }
 }
 
@@ -1456,90 +1253,6 @@ fn interval_data_to_name(interval: &relanote_lexer::token::IntervalData) -> Stri
 /// Get audio playback data including synth information
 #[wasm_bindgen]
 pub fn get_audio_data(source: &str) -> JsValue {
-    let src = Source::from_string("editor", source.to_string());
-    let (program, diagnostics) = parse_source(&src);
-
-    if diagnostics.has_errors() {
-        let data = AudioPlaybackData {
-            notes: vec![],
-            tempo: 120,
-            total_beats: 0.0,
-        };
-        return serde_wasm_bindgen::to_value(&data).unwrap();
-    }
-
-    let mut evaluator = Evaluator::new();
-    match evaluator.eval_program(&program) {
-        Ok(value) => {
-            // Get key from environment (default to C4 = 60 if not specified)
-            let base_note = get_key_from_evaluator(&evaluator)
-                .map(|n| n as i32)
-                .unwrap_or(60);
-
-            let mut all_notes = Vec::new();
-
-            match &value {
-                Value::Block(block) => {
-                    // Create a default part for a single block
-                    let part = relanote_eval::PartValue {
-                        instrument: "Default".to_string(),
-                        blocks: vec![block.clone()],
-                        envelope: None,
-                        reverb_level: None,
-                        volume_level: None,
-                        delay: None,
-                        phaser: None,
-                        distortion: None,
-                        synth: None,
-                    };
-                    let (notes, _) = extract_audio_notes_from_part(&part, 0.0, base_note);
-                    all_notes.extend(notes);
-                }
-                Value::Song(song) => {
-                    for section in &song.sections {
-                        for part in &section.parts {
-                            // Skip metronome parts
-                            if part.instrument.to_lowercase().contains("metronome") {
-                                continue;
-                            }
-                            let (notes, _) = extract_audio_notes_from_part(part, 0.0, base_note);
-                            all_notes.extend(notes);
-                        }
-                    }
-                }
-                _ => {}
-            }
-
-            let total_beats = all_notes
-                .iter()
-                .map(|n| n.start + n.duration)
-                .fold(0.0, f64::max);
-
-            let tempo = evaluator
-                .get_binding("tempo")
-                .and_then(|v| {
-                    if let Value::Int(t) = v {
-                        Some(t as u32)
-                    } else {
-                        None
-                    }
-                })
-                .unwrap_or(120);
-
-            let data = AudioPlaybackData {
-                notes: all_notes,
-                tempo,
-                total_beats,
-            };
-            serde_wasm_bindgen::to_value(&data).unwrap()
-        }
-        Err(_) => {
-            let data = AudioPlaybackData {
-                notes: vec![],
-                tempo: 120,
-                total_beats: 0.0,
-            };
-            serde_wasm_bindgen::to_value(&data).unwrap()
-        }
-    }
+    let evaluation = Evaluation::of_source(source);
+    to_js(&evaluation.audio_data())
 }
@@ -0,0 +1,483 @@
+//! Converting between piano roll notes and Relanote code
+
+use std::collections::BTreeMap;
+
+use relanote_eval::pitch_name;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use crate::{to_js, Evaluation};
+
+/// Note data from piano roll for code generation
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PianoRollNote {
+    pub pitch: i32,    // MIDI note (0-127)
+    pub start: f64,    // Start time in beats
+    pub duration: f64, // Duration in beats
+    pub velocity: u8,  // 0-127
+}
+
+/// How `notes_to_code` fits notes to a rhythm grid
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct QuantizeOptions {
+    /// Grid cells per beat (4 = sixteenth notes)
+    pub grid: u32,
+    pub time_signature_num: u32,
+    pub time_signature_den: u32,
+    /// Snap the notes of a beat to a triplet grid when they sit closer to
+    /// it than to the regular grid
+    pub triplets: bool,
+    /// Guess the key from the notes when no key is given, and write it as
+    /// `set key`
+    pub infer_key: bool,
+}
+
+impl Default for QuantizeOptions {
+    fn default() -> Self {
+        Self {
+            grid: 4,
+            time_signature_num: 4,
+            time_signature_den: 4,
+            triplets: false,
+            infer_key: false,
+        }
+    }
+}
+
+impl QuantizeOptions {
+    /// Cells per beat of the triplet grid (3 for sixteenth notes, so
+    /// eighth-note triplets)
+    fn triplet_cells(&self) -> u64 {
+        3 * u64::from(self.grid / 4).max(1)
+    }
+
+    /// Ticks per beat, fine enough for both grids
+    fn ticks_per_beat(&self) -> u64 {
+        let grid = u64::from(self.grid.max(1));
+        if self.triplets {
+            lcm(grid, self.triplet_cells())
+        } else {
+            grid
+        }
+    }
+}
+
+/// Generate Relanote code from piano roll notes
+///
+/// `options_json` is a JSON `QuantizeOptions`, where missing fields take
+/// their defaults (sixteenth-note grid, 4/4, no triplets or key inference).
+/// Pitches are written as intervals from `key_pitch` (C4 by default).
+#[wasm_bindgen]
+pub fn notes_to_code(
+    notes_json: &str,
+    synth_name: Option<String>,
+    key_pitch: Option<i32>,
+    options_json: Option<String>,
+) -> String {
+    let notes: Vec<PianoRollNote> = match serde_json::from_str(notes_json) {
+        Ok(n) => n,
+        Err(_) => return "".to_string(),
+    };
+    let options = match options_json.as_deref().map(serde_json::from_str) {
+        Some(Ok(options)) => options,
+        Some(Err(_)) => return "".to_string(),
+        None => QuantizeOptions::default(),
+    };
+
+    let mut result = generate_code(&notes, key_pitch, &options);
+
+    // Add synth voice if specified
+    if let Some(synth) = synth_name {
+        if !synth.is_empty() && synth != "Default" {
+            result = format!("{} |> voice {}", result, synth);
+        }
+    }
+
+    result
+}
+
+/// Notes of the program in `source`, for showing code in the piano roll
+#[wasm_bindgen]
+pub fn code_to_notes(source: &str) -> JsValue {
+    to_js(&source_notes(source))
+}
+
+fn source_notes(source: &str) -> Vec<PianoRollNote> {
+    Evaluation::of_source(source)
+        .staff_data()
+        .notes
+        .into_iter()
+        .map(|note| PianoRollNote {
+            pitch: note.pitch,
+            start: note.start,
+            duration: note.duration,
+            velocity: note.velocity,
+        })
+        .collect()
+}
+
+/// A stretch of the piano roll with one thing sounding, in ticks
+struct Segment {
+    start: u64,
+    end: u64,
+    /// Sounding pitches; empty for a rest
+    pitches: Vec<i32>,
+}
+
+fn generate_code(
+    notes: &[PianoRollNote],
+    key_pitch: Option<i32>,
+    options: &QuantizeOptions,
+) -> String {
+    let notes: Vec<&PianoRollNote> = notes
+        .iter()
+        .filter(|note| note.start >= 0.0 && note.duration > 0.0)
+        .collect();
+    if notes.is_empty() {
+        return "| - |".to_string();
+    }
+
+    let ticks_per_beat = options.ticks_per_beat();
+    let bar_ticks = (options.time_signature_num.saturating_mul(4) as f64 * ticks_per_beat as f64
+        / options.time_signature_den.max(1) as f64)
+        .round()
+        .max(1.0) as u64;
+
+    // Beats whose onsets fit the triplet grid better
+    let mut cells = BTreeMap::new();
+    let regular = u64::from(options.grid.max(1));
+    for note in &notes {
+        let beat = note.start.floor() as u64;
+        let offset = note.start.fract();
+        let entry = cells.entry(beat).or_insert((0.0, 0.0));
+        entry.0 += snap_error(offset, regular);
+        if options.triplets {
+            entry.1 += snap_error(offset, options.triplet_cells());
+        }
+    }
+    let cells_in = |beat: u64| match cells.get(&beat) {
+        Some((regular_error, triplet_error))
+            if options.triplets && *triplet_error + 1e-9 < *regular_error =>
+        {
+            options.triplet_cells()
+        }
+        _ => regular,
+    };
+    let snap = |time: f64| {
+        let beat = time.floor() as u64;
+        let cells = cells_in(beat);
+        let cell = (time.fract() * cells as f64).round() as u64;
+        beat.saturating_mul(ticks_per_beat)
+            .saturating_add(cell.saturating_mul(ticks_per_beat / cells))
+    };
+
+    // Notes starting together sound as a chord
+    let mut onsets: BTreeMap<u64, (u64, Vec<i32>)> = BTreeMap::new();
+    for note in &notes {
+        let start = snap(note.start);
+        let shortest = ticks_per_beat / cells_in(note.start.floor() as u64);
+        let end = snap(note.start + note.duration).max(start.saturating_add(shortest));
+        let onset = onsets.entry(start).or_insert((end, Vec::new()));
+        onset.0 = onset.0.max(end);
+        onset.1.push(note.pitch);
+    }
+
+    let mut segments = Vec::new();
+    let mut time = 0;
+    let starts: Vec<u64> = onsets.keys().copied().collect();
+    for (i, (start, (end, mut pitches))) in onsets.into_iter().enumerate() {
+        push_rest(&mut segments, time, start, bar_ticks);
+        // A note is cut short by the next one
+        let end = starts.get(i + 1).map_or(end, |next| end.min(*next));
+        pitches.sort_unstable();
+        pitches.dedup();
+        segments.push(Segment {
+            start,
+            end,
+            pitches,
+        });
+        time = end;
+    }
+    push_rest(
+        &mut segments,
+        time,
+        time.div_ceil(bar_ticks).saturating_mul(bar_ticks),
+        bar_ticks,
+    );
+
+    let lowest = notes.iter().map(|note| note.pitch).min().unwrap_or(60);
+    let (key, key_line) = match key_pitch {
+        Some(key) => (key, None),
+        None if options.infer_key => {
+            let key = infer_key(&notes, lowest);
+            (key, Some(format!("set key = {}\n\n", pitch_name(key))))
+        }
+        None => (60, None),
+    };
+    // Intervals only go up, so notes below the key are written an octave
+    // or more up and transposed back down
+    let octaves_down = key.saturating_sub(lowest).max(0).saturating_add(11) / 12;
+    let base_pitch = key.saturating_sub(octaves_down * 12);
+
+    let mut blocks = Vec::new();
+    let mut run: Vec<&Segment> = Vec::new();
+    for segment in &segments {
+        let duration = segment.end - segment.start;
+        if let Some(first) = run.first() {
+            if first.end - first.start != duration || segment.start % bar_ticks == 0 {
+                blocks.push(block_code(&run, base_pitch, ticks_per_beat));
+                run.clear();
+            }
+        }
+        run.push(segment);
+    }
+    if !run.is_empty() {
+        blocks.push(block_code(&run, base_pitch, ticks_per_beat));
+    }
+
+    let mut result = key_line.unwrap_or_default();
+    result.push_str(&blocks.join(" ++ "));
+    if octaves_down > 0 {
+        result.push_str(&format!(
+            " |> transpose (R - {})",
+            semitones_to_interval(octaves_down * 12)
+        ));
+    }
+    result
+}
+
+/// How far `offset` (a fraction of a beat) is from the nearest of `cells`
+/// grid lines
+fn snap_error(offset: f64, cells: u64) -> f64 {
+    let position = offset * cells as f64;
+    (position - position.round()).abs() / cells as f64
+}
+
+/// Add rests from `from` to `to`, split at bar lines
+fn push_rest(segments: &mut Vec<Segment>, from: u64, to: u64, bar_ticks: u64) {
+    let mut start = from;
+    while start < to {
+        let end = (start / bar_ticks + 1).saturating_mul(bar_ticks).min(to);
+        segments.push(Segment {
+            start,
+            end,
+            pitches: Vec::new(),
+        });
+        start = end;
+    }
+}
+
+/// A block of equally long segments
+fn block_code(run: &[&Segment], base_pitch: i32, ticks_per_beat: u64) -> String {
+    let slots: Vec<String> = run
+        .iter()
+        .map(|segment| match segment.pitches.as_slice() {
+            [] => "-".to_string(),
+            [pitch] => semitones_to_interval(pitch.saturating_sub(base_pitch)),
+            pitches => {
+                let intervals: Vec<String> = pitches
+                    .iter()
+                    .map(|pitch| semitones_to_interval(pitch.saturating_sub(base_pitch)))
+                    .collect();
+                format!("[{}]", intervals.join(", "))
+            }
+        })
+        .collect();
+
+    let ticks: u64 = run.iter().map(|segment| segment.end - segment.start).sum();
+    // Triplet lengths don't end, so they are rounded
+    let beats = ticks as f64 / ticks_per_beat as f64;
+    let beats = ((beats * 10000.0).round() / 10000.0).to_string();
+    if beats == "1" {
+        format!("| {} |", slots.join(" "))
+    } else {
+        format!("| {} |:{}", slots.join(" "), beats)
+    }
+}
+
+/// Interval notation for a number of semitones up, using compound
+/// intervals (`M10`, `P15`) past the octave
+fn semitones_to_interval(semitones: i32) -> String {
+    const SIMPLE: [(&str, i32); 12] = [
+        ("P", 1),
+        ("m", 2),
+        ("M", 2),
+        ("m", 3),
+        ("M", 3),
+        ("P", 4),
+        ("d", 5),
+        ("P", 5),
+        ("m", 6),
+        ("M", 6),
+        ("m", 7),
+        ("M", 7),
+    ];
+    if semitones == 0 {
+        return "R".to_string();
+    }
+    let semitones = semitones.max(0);
+    let (quality, degree) = SIMPLE[(semitones % 12) as usize];
+    format!("{}{}", quality, degree + 7 * (semitones / 12))
+}
+
+/// The major key whose scale covers the most of the notes' duration, as the
+/// highest tonic at or below the lowest note
+fn infer_key(notes: &[&PianoRollNote], lowest: i32) -> i32 {
+    const MAJOR: [i32; 7] = [0, 2, 4, 5, 7, 9, 11];
+    let first = notes
+        .iter()
+        .min_by(|a, b| a.start.total_cmp(&b.start))
+        .map_or(0, |note| note.pitch.rem_euclid(12));
+
+    let score = |tonic: i32| {
+        let mut in_scale = 0.0;
+        let mut on_tonic = 0.0;
+        for note in notes {
+            let degree = (note.pitch.rem_euclid(12) - tonic).rem_euclid(12);
+            if MAJOR.contains(&degree) {
+                in_scale += note.duration;
+            }
+            if degree == 0 {
+                on_tonic += note.duration;
+            }
+        }
+        (in_scale, on_tonic)
+    };
+
+    // Ties go to the pitch class of the first note
+    let tonic = (0..12)
+        .map(|offset| (first + offset) % 12)
+        .fold((first, score(first)), |best, tonic| {
+            let candidate = score(tonic);
+            if candidate.partial_cmp(&best.1) == Some(std::cmp::Ordering::Greater) {
+                (tonic, candidate)
+            } else {
+                best
+            }
+        })
+        .0;
+
+    lowest.saturating_sub((lowest.rem_euclid(12) - tonic).rem_euclid(12))
+}
+
+fn lcm(a: u64, b: u64) -> u64 {
+    let gcd = |mut a: u64, mut b: u64| {
+        while b != 0 {
+            (a, b) = (b, a % b);
+        }
+        a
+    };
+    (a / gcd(a, b)).saturating_mul(b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(pitch: i32, start: f64, duration: f64) -> PianoRollNote {
+        PianoRollNote {
+            pitch,
+            start,
+            duration,
+            velocity: 100,
+        }
+    }
+
+    fn round_trip(notes: &[PianoRollNote], options: &QuantizeOptions) -> Vec<(i32, f64, f64)> {
+        let code = generate_code(notes, None, options);
+        source_notes(&code)
+            .into_iter()
+            .map(|n| {
+                let round = |x: f64| (x * 1000.0).round() / 1000.0;
+                (n.pitch, round(n.start), round(n.duration))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_notes_round_trip_through_code() {
+        let notes = [
+            note(60, 0.0, 1.0),
+            note(64, 1.02, 0.5),
+            note(67, 1.5, 0.48),
+            note(48, 2.0, 2.0),
+            note(55, 2.0, 2.0),
+            note(76, 6.0, 0.25),
+        ];
+        let code = generate_code(&notes, Some(60), &QuantizeOptions::default());
+        assert!(code.ends_with("|> transpose (R - P8)"), "{}", code);
+
+        assert_eq!(
+            round_trip(&notes, &QuantizeOptions::default()),
+            vec![
+                (60, 0.0, 1.0),
+                (64, 1.0, 0.5),
+                (67, 1.5, 0.5),
+                (48, 2.0, 2.0),
+                (55, 2.0, 2.0),
+                (76, 6.0, 0.25),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_triplets_and_time_signature() {
+        let notes = [
+            note(60, 0.0, 0.33),
+            note(62, 0.34, 0.33),
+            note(64, 0.66, 0.33),
+            note(65, 1.0, 2.0),
+        ];
+        let options = QuantizeOptions {
+            triplets: true,
+            time_signature_num: 3,
+            ..QuantizeOptions::default()
+        };
+        let code = generate_code(&notes, Some(60), &options);
+        assert_eq!(code, "| R M2 M3 | ++ | P4 |:2");
+
+        let straight = generate_code(&notes, Some(60), &QuantizeOptions::default());
+        assert_ne!(straight, code);
+    }
+
+    #[test]
+    fn test_extreme_notes_and_options() {
+        // Ticks and pitches this far out saturate instead of overflowing
+        let notes = [note(i32::MAX, 0.0, 1.0), note(i32::MIN, 0.5, 1e12)];
+        let options = QuantizeOptions {
+            grid: u32::MAX,
+            time_signature_num: u32::MAX,
+            triplets: true,
+            infer_key: true,
+            ..QuantizeOptions::default()
+        };
+        assert!(!generate_code(&notes, None, &options).is_empty());
+        assert!(!generate_code(&notes, Some(i32::MAX), &QuantizeOptions::default()).is_empty());
+    }
+
+    #[test]
+    fn test_inferred_key() {
+        // D major: D E F# G A
+        let notes = [
+            note(62, 0.0, 1.0),
+            note(64, 1.0, 1.0),
+            note(66, 2.0, 1.0),
+            note(67, 3.0, 1.0),
+            note(69, 4.0, 2.0),
+        ];
+        let options = QuantizeOptions {
+            infer_key: true,
+            ..QuantizeOptions::default()
+        };
+        let code = generate_code(&notes, None, &options);
+        assert!(code.starts_with("set key = D4\n"), "{}", code);
+        assert!(code.contains("| R M2 M3 P4 |:4"), "{}", code);
+
+        let pitches: Vec<i32> = round_trip(&notes, &options)
+            .into_iter()
+            .map(|(pitch, _, _)| pitch)
+            .collect();
+        assert_eq!(pitches, vec![62, 64, 66, 67, 69]);
+    }
+}
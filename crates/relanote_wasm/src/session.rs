@@ -0,0 +1,404 @@
+//! A document that is parsed and evaluated once for all the calls reading it
+
+use relanote_ast::Program;
+use relanote_core::{Diagnostics, Source};
+use relanote_eval::{Evaluator, ProgramDiff};
+use relanote_parser::parse_source;
+use relanote_types::TypeChecker;
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    analysis_result, completions, files, hover, render_targets, to_js, AnalysisResult, Evaluation,
+};
+
+/// An editor document whose parse, type check and evaluation results are
+/// cached until it changes
+///
+/// The free functions (`analyze`, `get_staff_data`, ...) redo all of the work
+/// on every call; a session does it once per document version:
+///
+/// ```js
+/// const session = new RelanoteSession(source);
+/// session.analyze();
+/// session.get_staff_data(); // evaluates
+/// session.get_audio_data(); // reuses the evaluation
+/// session.update(newSource); // next version
+/// ```
+///
+/// Results are also recomputed after modules are added or removed.
+///
+/// In live mode (`set_live(true)`) each version is evaluated by patching the
+/// evaluation of the one before it, so editing a riff while the song loops
+/// only evaluates what the edit affects. A patch that fails leaves the
+/// evaluator partly updated, so the version after one with errors is
+/// evaluated from scratch.
+#[wasm_bindgen]
+pub struct RelanoteSession {
+    source: String,
+    version: u32,
+    program: Program,
+    parse_diagnostics: Diagnostics,
+    analysis: Option<Cached<AnalysisResult>>,
+    /// Types and docs of the document's bindings, for completion and hover
+    checker: Option<Cached<TypeChecker>>,
+    evaluation: Option<Cached<Evaluation>>,
+    live: bool,
+    /// In live mode, the evaluator of the previous version if it evaluated
+    /// without errors
+    patchable: Option<Cached<Patchable>>,
+}
+
+/// A version of the document and the evaluator that evaluated it
+struct Patchable {
+    source: String,
+    program: Program,
+    evaluator: Evaluator,
+}
+
+/// A result and the module generation it was computed against
+struct Cached<T> {
+    generation: u64,
+    value: T,
+}
+
+#[wasm_bindgen]
+impl RelanoteSession {
+    #[wasm_bindgen(constructor)]
+    pub fn new(source: &str) -> Self {
+        let (program, parse_diagnostics) = parse(source);
+        Self {
+            source: source.to_string(),
+            version: 0,
+            program,
+            parse_diagnostics,
+            analysis: None,
+            checker: None,
+            evaluation: None,
+            live: false,
+            patchable: None,
+        }
+    }
+
+    /// Replace the document, returning its new version
+    ///
+    /// The version only changes (and cached results are only dropped) when
+    /// the source differs from the current one.
+    pub fn update(&mut self, source: &str) -> u32 {
+        if source != self.source {
+            let (program, parse_diagnostics) = parse(source);
+            self.source = source.to_string();
+            self.version += 1;
+            self.program = program;
+            self.parse_diagnostics = parse_diagnostics;
+            self.analysis = None;
+            self.checker = None;
+            self.evaluation = None;
+        }
+        self.version
+    }
+
+    /// Turn live mode on or off
+    pub fn set_live(&mut self, live: bool) {
+        self.live = live;
+        self.patchable = None;
+    }
+
+    /// Version of the current document, starting at 0
+    #[wasm_bindgen(getter)]
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Diagnostics, like `analyze`
+    pub fn analyze(&mut self) -> JsValue {
+        to_js(self.analysis())
+    }
+
+    /// Evaluation result, like `evaluate`
+    pub fn evaluate(&mut self) -> JsValue {
+        to_js(&self.evaluation().eval_result())
+    }
+
+    /// MIDI data, like `render_midi`
+    pub fn render_midi(&mut self) -> JsValue {
+        to_js(&self.evaluation().render_result())
+    }
+
+    /// Staff notation data, like `get_staff_data`
+    pub fn get_staff_data(&mut self) -> JsValue {
+        to_js(&self.evaluation().staff_data())
+    }
+
+    /// Audio playback data, like `get_audio_data`
+    pub fn get_audio_data(&mut self) -> JsValue {
+        to_js(&self.evaluation().audio_data())
+    }
+
+    /// Audio notes starting in `[from_beat, to_beat)`
+    ///
+    /// Only the blocks overlapping the window are turned into notes, so a
+    /// player can fetch a long song a few bars ahead of the playhead instead
+    /// of all at once. `total_beats` in the result is the length of the
+    /// whole song.
+    pub fn get_audio_events(&mut self, from_beat: f64, to_beat: f64) -> JsValue {
+        to_js(&self.evaluation().audio_events(from_beat, to_beat))
+    }
+
+    /// Render targets of the document, like `list_render_targets`
+    pub fn list_render_targets(&self) -> JsValue {
+        to_js(&render_targets(&self.program))
+    }
+
+    /// Completion items, like `get_completions`
+    pub fn get_completions(&mut self) -> JsValue {
+        to_js(&completions(self.type_checker()))
+    }
+
+    /// Hover information at `offset`, like `get_hover`
+    pub fn get_hover(&mut self, offset: usize) -> JsValue {
+        self.type_checker();
+        let checker = &self.checker.as_ref().unwrap().value;
+        to_js(&hover(&self.source, checker, offset))
+    }
+}
+
+impl RelanoteSession {
+    fn analysis(&mut self) -> &AnalysisResult {
+        let generation = files::generation();
+        if self
+            .analysis
+            .as_ref()
+            .is_none_or(|cached| cached.generation != generation)
+        {
+            let value = analysis_result(&self.source, &self.program, &self.parse_diagnostics);
+            self.analysis = Some(Cached { generation, value });
+        }
+        &self.analysis.as_ref().unwrap().value
+    }
+
+    fn type_checker(&mut self) -> &TypeChecker {
+        let generation = files::generation();
+        if self
+            .checker
+            .as_ref()
+            .is_none_or(|cached| cached.generation != generation)
+        {
+            let value = files::type_checker(&self.source, &self.program);
+            self.checker = Some(Cached { generation, value });
+        }
+        &self.checker.as_ref().unwrap().value
+    }
+
+    fn evaluation(&mut self) -> &Evaluation {
+        let generation = files::generation();
+        if self
+            .evaluation
+            .as_ref()
+            .is_none_or(|cached| cached.generation != generation)
+        {
+            let value = if self.live && !self.parse_diagnostics.has_errors() {
+                self.live_evaluation(generation)
+            } else {
+                Evaluation::of_program(&self.program, &self.parse_diagnostics)
+            };
+            self.evaluation = Some(Cached { generation, value });
+        }
+        &self.evaluation.as_ref().unwrap().value
+    }
+
+    /// Evaluate the document by patching the previous version, starting
+    /// over when it had errors or modules changed since
+    fn live_evaluation(&mut self, generation: u64) -> Evaluation {
+        let last = self
+            .patchable
+            .take()
+            .filter(|cached| cached.generation == generation);
+        let (evaluator, result) = match last {
+            Some(Cached {
+                value: mut last, ..
+            }) => {
+                let diff =
+                    ProgramDiff::between(&last.source, &last.program, &self.source, &self.program);
+                let result = last.evaluator.patch(&diff);
+                (last.evaluator, result)
+            }
+            None => {
+                let mut evaluator = files::new_evaluator();
+                let result = evaluator.eval_program(&self.program);
+                (evaluator, result)
+            }
+        };
+        let succeeded = result.is_ok();
+        let evaluation = Evaluation::of_evaluator(&evaluator, result);
+        // An evaluation stopped by an error is only partly up to date
+        if succeeded {
+            let value = Patchable {
+                source: self.source.clone(),
+                program: self.program.clone(),
+                evaluator,
+            };
+            self.patchable = Some(Cached { generation, value });
+        }
+        evaluation
+    }
+}
+
+fn parse(source: &str) -> (Program, Diagnostics) {
+    let src = Source::from_string("editor", source.to_string());
+    parse_source(&src)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_list_render_targets() {
+        let session = RelanoteSession::new("let s = layer [| R |]\nrender s\nrender \"demo\" = s");
+        let targets = render_targets(&session.program);
+        assert_eq!(targets.len(), 2);
+        assert_eq!(targets[0].name, None);
+        assert_eq!(targets[1].name.as_deref(), Some("demo"));
+        assert_eq!(
+            &session.source[targets[1].start..targets[1].end],
+            "render \"demo\" = s"
+        );
+    }
+
+    #[test]
+    fn test_session_caches_until_update() {
+        let mut session = RelanoteSession::new("| R M3 P5 |");
+        assert_eq!(session.evaluation().staff_data().notes.len(), 3);
+        assert!(session.analysis().success);
+
+        session.type_checker();
+
+        assert_eq!(session.update("| R M3 P5 |"), 0);
+        assert!(session.evaluation.is_some());
+        assert!(session.checker.is_some());
+
+        assert_eq!(session.update("| R M3 |"), 1);
+        assert!(session.evaluation.is_none());
+        assert!(session.checker.is_none());
+        assert_eq!(session.evaluation().staff_data().notes.len(), 2);
+
+        // Adding a module invalidates results that may depend on it
+        files::add_module("session_test", "export let x = 1");
+        let generation = session.evaluation.as_ref().unwrap().generation;
+        session.evaluation();
+        assert_ne!(session.evaluation.as_ref().unwrap().generation, generation);
+        files::remove_module("session_test");
+    }
+
+    #[test]
+    fn test_live_mode_patches_the_last_evaluation() {
+        let mut session =
+            RelanoteSession::new("let riff = | R M3 |\nlet bass = | R |\nriff ++ bass");
+        session.set_live(true);
+        assert_eq!(session.evaluation().audio_data().notes.len(), 3);
+        assert!(session.patchable.is_some());
+
+        session.update("let riff = | R M3 P5 |\nlet bass = | R |\nriff ++ bass");
+        assert_eq!(session.evaluation().audio_data().notes.len(), 4);
+
+        // A version with errors isn't patched from, so the next one starts over
+        session.update("let riff = | R M3 P5 |\nlet bass = | R |\nriff ++ bas");
+        assert!(session.evaluation().eval_result().error.is_some());
+        assert!(session.patchable.is_none());
+        session.update("let riff = | R M3 P5 |\nlet bass = | R R |\nriff ++ bass");
+        assert_eq!(session.evaluation().audio_data().notes.len(), 5);
+
+        session.set_live(false);
+        assert!(session.patchable.is_none());
+    }
+
+    #[test]
+    fn test_user_bindings_in_completion_and_hover() {
+        let source = "scale Blues = { R, m3, P4, A4, P5, m7 }\nlet riff = | R m3 P4 |\nriff";
+        let mut session = RelanoteSession::new(source);
+
+        let items = completions(session.type_checker());
+        let riff = items.iter().find(|item| item.label == "riff").unwrap();
+        assert_eq!(riff.detail, "riff: Block");
+        let blues = items.iter().find(|item| item.label == "Blues").unwrap();
+        assert_eq!(blues.kind, "class");
+
+        let offset = source.rfind("riff").unwrap() + 1;
+        let result = hover(source, session.type_checker(), offset);
+        assert!(result.content.unwrap().contains("riff: Block"));
+    }
+
+    #[test]
+    fn test_doc_comments_in_completion_and_hover() {
+        files::add_module(
+            "doc_test",
+            "--- Walking bass in quarters\nexport let walk = | R M3 P5 M6 |\n",
+        );
+        let source = "use doc_test::walk\n--- The hook, twice\nlet hook = walk ++ walk\nhook";
+        let mut session = RelanoteSession::new(source);
+
+        let items = completions(session.type_checker());
+        let hook = items.iter().find(|item| item.label == "hook").unwrap();
+        assert_eq!(hook.detail, "hook: Block\n\nThe hook, twice");
+        let walk = items.iter().find(|item| item.label == "walk").unwrap();
+        assert_eq!(walk.detail, "walk: Block\n\nWalking bass in quarters");
+
+        let offset = source.rfind("walk").unwrap() + 1;
+        let result = hover(source, session.type_checker(), offset);
+        assert!(result.content.unwrap().contains("Walking bass in quarters"));
+        files::remove_module("doc_test");
+    }
+
+    #[test]
+    fn test_hover_shows_declared_signature() {
+        let source = "let twice (b: Block) : Block = b ++ b\n| R | |> twice";
+        let mut session = RelanoteSession::new(source);
+        let offset = source.rfind("twice").unwrap() + 1;
+        let result = hover(source, session.type_checker(), offset);
+        assert!(result.content.unwrap().contains("twice: Block -> Block"));
+    }
+
+    #[test]
+    fn test_runtime_error_location() {
+        let mut session = RelanoteSession::new("let x = reverse 3\nx");
+        let error = session.evaluation().eval_result().error.unwrap();
+        assert!(error.message.contains("expected Block"));
+        assert_eq!((error.start, error.end), (8, 17));
+    }
+
+    #[test]
+    fn test_set_bindings_reach_every_view() {
+        let mut session = RelanoteSession::new(
+            "set tempo = 90\nset key = D4\nset time_signature = (3, 4)\n| R M3 P5 R |:4",
+        );
+        let staff = session.evaluation().staff_data();
+        assert_eq!(staff.tempo, 90);
+        assert_eq!((staff.time_signature_num, staff.time_signature_den), (3, 4));
+        // The fourth beat starts the second bar of 3/4
+        let measures: Vec<usize> = staff.notes.iter().map(|n| n.measure).collect();
+        assert_eq!(measures, vec![0, 0, 0, 1]);
+
+        let audio = session.evaluation().audio_data();
+        assert_eq!(audio.tempo, 90);
+        assert_eq!(audio.notes[0].pitch, 62.0);
+    }
+
+    #[test]
+    fn test_audio_events_window() {
+        let mut session = RelanoteSession::new("| R M3 |:4 ++ | P5 P4 |:4 ++ | M2 R |:4");
+        let all = session.evaluation().audio_data().notes;
+
+        let mut chunked = Vec::new();
+        let mut from = 0.0;
+        while from < 12.0 {
+            let window = session.evaluation().audio_events(from, from + 3.0);
+            assert_eq!(window.total_beats, 12.0);
+            chunked.extend(window.notes.into_iter().map(|n| (n.pitch, n.start)));
+            from += 3.0;
+        }
+
+        let all: Vec<_> = all.into_iter().map(|n| (n.pitch, n.start)).collect();
+        assert_eq!(chunked, all);
+        assert_eq!(session.evaluation().audio_events(4.0, 8.0).notes.len(), 2);
+    }
+}
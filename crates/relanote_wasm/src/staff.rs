@@ -0,0 +1,175 @@
+//! Notation details for the staff view: spelling, clefs, measures and beams
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::NoteEvent;
+
+/// How a pitch is written: a letter, an accidental and an octave, as in
+/// MusicXML's `<pitch>`
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Spelling {
+    /// Letter name, `C` to `B`
+    pub step: String,
+    /// Semitones of the accidental: 1 for a sharp, -1 for a flat
+    pub alter: i8,
+    /// Octave of the letter (4 = middle C octave); B#3 sounds as C4
+    pub octave: i32,
+}
+
+/// A part of the staff view
+#[derive(Serialize, Deserialize, Clone)]
+pub struct StaffPart {
+    pub instrument: String,
+    /// `treble` or `bass`
+    pub clef: String,
+}
+
+/// Letters in the order of the line of fifths, starting from F
+const FIFTHS: [&str; 7] = ["F", "C", "G", "D", "A", "E", "B"];
+
+/// Key signature of the major key on `tonic` (a MIDI pitch), in sharps
+/// (positive) or flats (negative)
+///
+/// F# is preferred over Gb.
+pub(crate) fn key_fifths(tonic: i32) -> i32 {
+    let fifths = (tonic.rem_euclid(12) * 7) % 12;
+    if fifths > 6 {
+        fifths - 12
+    } else {
+        fifths
+    }
+}
+
+/// Spell `pitch` in the key with `fifths` sharps or flats
+///
+/// Notes of the key's scale are spelled as the key signature spells them;
+/// other notes get sharps in sharp keys and flats in flat keys.
+pub(crate) fn spell(pitch: i32, fifths: i32) -> Spelling {
+    // Position on the line of fifths (C = 0, G = 1, F = -1), chosen from
+    // the twelve positions that cover the key and its accidentals
+    let lowest = if fifths >= 0 { fifths - 1 } else { fifths - 6 };
+    let position = lowest + ((pitch.rem_euclid(12) * 7) - lowest).rem_euclid(12);
+
+    let step = FIFTHS[(position + 1).rem_euclid(7) as usize];
+    let alter = (position + 1).div_euclid(7);
+    let natural = [5, 0, 7, 2, 9, 4, 11][(position + 1).rem_euclid(7) as usize];
+    Spelling {
+        step: step.to_string(),
+        alter: alter as i8,
+        octave: (pitch - alter - natural).div_euclid(12) - 1,
+    }
+}
+
+/// Clef that keeps the most of `pitches` on the staff
+pub(crate) fn suggest_clef(pitches: impl Iterator<Item = i32>) -> &'static str {
+    let mut pitches: Vec<i32> = pitches.collect();
+    pitches.sort_unstable();
+    match pitches.get(pitches.len() / 2) {
+        Some(median) if *median < 60 => "bass",
+        _ => "treble",
+    }
+}
+
+/// Number each note's measure and give notes that share a beam the same
+/// beam group
+///
+/// Notes shorter than a beat are beamed with the other notes of their part
+/// that start in the same beat and end within it.
+pub(crate) fn group_notes(notes: &mut [NoteEvent], beats_per_measure: f64) {
+    let mut beats: BTreeMap<(usize, i64), Vec<usize>> = BTreeMap::new();
+    for (i, note) in notes.iter_mut().enumerate() {
+        note.measure = (note.start / beats_per_measure + 1e-9).floor() as usize;
+        let beat = (note.start + 1e-9).floor();
+        if note.duration < 1.0 && note.start + note.duration <= beat + 1.0 + 1e-9 {
+            beats.entry((note.part, beat as i64)).or_default().push(i);
+        }
+    }
+
+    let mut next_beam = 0;
+    for members in beats.values() {
+        // A chord alone in its beat has nothing to be beamed to
+        let first = notes[members[0]].start;
+        if members
+            .iter()
+            .all(|&i| (notes[i].start - first).abs() < 1e-9)
+        {
+            continue;
+        }
+        for &i in members {
+            notes[i].beam = Some(next_beam);
+        }
+        next_beam += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spelled(pitch: i32, fifths: i32) -> (String, i8, i32) {
+        let spelling = spell(pitch, fifths);
+        (spelling.step, spelling.alter, spelling.octave)
+    }
+
+    #[test]
+    fn test_spelling_follows_key() {
+        assert_eq!(key_fifths(62), 2); // D major
+        assert_eq!(key_fifths(65), -1); // F major
+        assert_eq!(key_fifths(66), 6); // F# major
+
+        assert_eq!(spelled(66, 2), ("F".to_string(), 1, 4));
+        assert_eq!(spelled(66, -1), ("G".to_string(), -1, 4));
+        assert_eq!(spelled(70, -1), ("B".to_string(), -1, 4));
+        assert_eq!(spelled(61, 0), ("C".to_string(), 1, 4));
+        // E# and B# in F# major, where B# sits in the octave below
+        assert_eq!(spelled(65, 6), ("E".to_string(), 1, 4));
+        assert_eq!(spelled(60, 6), ("B".to_string(), 1, 3));
+        // Cb4 in Gb major sounds as B3
+        assert_eq!(spelled(59, -6), ("C".to_string(), -1, 4));
+    }
+
+    #[test]
+    fn test_staff_data_notation() {
+        let source = "set key = D3\n| R M3 P5 M3 |:2";
+        let staff = crate::Evaluation::of_source(source).staff_data();
+
+        assert_eq!(staff.key_fifths, 2);
+        assert_eq!(staff.notes[1].spelling.step, "F");
+        assert_eq!(staff.notes[1].spelling.alter, 1);
+        assert_eq!(staff.parts[0].clef, "bass");
+        // Two eighth notes per beat, each pair beamed together
+        let beams: Vec<_> = staff.notes.iter().map(|note| note.beam).collect();
+        assert_eq!(beams, vec![Some(0), Some(0), Some(1), Some(1)]);
+        assert!(staff.error.is_none());
+    }
+
+    #[test]
+    fn test_staff_data_time_signature_too_large() {
+        let source = "set time_signature = (256, 4)
+| R M3 P5 M3 |";
+        let staff = crate::Evaluation::of_source(source).staff_data();
+
+        assert!(staff.notes.is_empty());
+        let error = staff.error.unwrap();
+        assert_eq!(
+            error.message,
+            "the time signature 256/4 is too large for the staff"
+        );
+    }
+
+    #[test]
+    fn test_staff_data_nested_tuplets() {
+        let source = "| { R { M3 P5 M6 }:3:2 }:3 |:3";
+        let staff = crate::Evaluation::of_source(source).staff_data();
+
+        let times: Vec<_> = staff
+            .notes
+            .iter()
+            .map(|note| ((note.start * 3.0).round(), (note.duration * 3.0).round()))
+            .collect();
+        // The quarter note and a triplet lasting two beats
+        assert_eq!(times, vec![(0.0, 3.0), (3.0, 2.0), (5.0, 2.0), (7.0, 2.0)]);
+    }
+}
@@ -23,6 +23,10 @@ const relaLanguage = {
           name: "comment.line.semicolon.rela",
           match: ";.*",
         },
+        {
+          name: "comment.line.documentation.rela",
+          match: "(---|--\\|).*",
+        },
       ],
     },
     keywords: {
@@ -33,7 +37,7 @@ const relaLanguage = {
         },
         {
           name: "keyword.declaration.rela",
-          match: "\\b(scale|chord|part|section|layer|render|synth)\\b",
+          match: "\\b(scale|chord|part|section|layer|render|context|synth|meta|test)\\b",
         },
         {
           name: "constant.language.boolean.rela",
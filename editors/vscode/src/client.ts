@@ -33,6 +33,12 @@ export async function startClient(
       fileEvents: vscode.workspace.createFileSystemWatcher("**/*.rela"),
     },
     outputChannelName: "Relanote Language Server",
+    initializationOptions: {
+      inlayHints: {
+        types: config.get<boolean>("inlayHints.types", true),
+        beats: config.get<boolean>("inlayHints.beats", true),
+      },
+    },
   };
 
   client = new LanguageClient(
@@ -0,0 +1,120 @@
+import * as vscode from "vscode";
+import { execFile } from "child_process";
+import * as fs from "fs";
+import * as os from "os";
+import * as path from "path";
+import { getClient } from "./client";
+
+/** Result of the server's `relanote/renderSelection` request */
+interface RenderedSelection {
+  /** Bytes of a Standard MIDI File */
+  midi: number[];
+}
+
+/** What a code lens plays or renders, as sent by the language server */
+interface LensTarget {
+  uri: string;
+  /** Name of a render target, or the number of an unnamed one */
+  target?: string;
+  /** Name of a section */
+  section?: string;
+}
+
+export function registerCommands(context: vscode.ExtensionContext): void {
+  context.subscriptions.push(
+    vscode.commands.registerCommand("relanote.play", async (lens: LensTarget) => {
+      const output = path.join(os.tmpdir(), `relanote-${outputName(lens)}`);
+      if (await render(lens, output)) {
+        // Auditioned in the system's MIDI player
+        await vscode.env.openExternal(vscode.Uri.file(output));
+      }
+    }),
+    vscode.commands.registerCommand(
+      "relanote.renderMidi",
+      async (lens: LensTarget) => {
+        const file = vscode.Uri.parse(lens.uri).fsPath;
+        const output = path.join(path.dirname(file), outputName(lens));
+        if (await render(lens, output)) {
+          vscode.window.showInformationMessage(`MIDI file written to ${output}`);
+        }
+      }
+    ),
+    vscode.commands.registerCommand("relanote.playSelection", playSelection)
+  );
+}
+
+/**
+ * Play the selected music, or the whole song without a selection, rendered
+ * by the language server
+ */
+async function playSelection(): Promise<void> {
+  const editor = vscode.window.activeTextEditor;
+  const client = getClient();
+  if (!editor || !client) {
+    vscode.window.showErrorMessage(
+      "Relanote: playing a selection needs the language server"
+    );
+    return;
+  }
+  const selection = editor.selection;
+  try {
+    const result = await client.sendRequest<RenderedSelection>(
+      "relanote/renderSelection",
+      {
+        textDocument: { uri: editor.document.uri.toString() },
+        range: selection.isEmpty
+          ? undefined
+          : client.code2ProtocolConverter.asRange(selection),
+      }
+    );
+    const output = path.join(os.tmpdir(), "relanote-selection.mid");
+    await fs.promises.writeFile(output, Buffer.from(result.midi));
+    await vscode.env.openExternal(vscode.Uri.file(output));
+  } catch (error) {
+    const message = error instanceof Error ? error.message : String(error);
+    vscode.window.showErrorMessage(`Relanote: cannot play the selection: ${message}`);
+  }
+}
+
+/** `song-verse_demo.mid` for target `verse_demo` of `song.rela` */
+function outputName(lens: LensTarget): string {
+  const file = vscode.Uri.parse(lens.uri).fsPath;
+  const stem = path.basename(file, path.extname(file));
+  const label = (lens.target ?? lens.section ?? "").replace(/[^\w-]+/g, "_");
+  return label ? `${stem}-${label}.mid` : `${stem}.mid`;
+}
+
+/** Save the document and run `relanote render` for the lens's song */
+async function render(lens: LensTarget, output: string): Promise<boolean> {
+  const uri = vscode.Uri.parse(lens.uri);
+  const document = vscode.workspace.textDocuments.find(
+    (doc) => doc.uri.toString() === uri.toString()
+  );
+  if (document?.isDirty) {
+    await document.save();
+  }
+
+  const args = ["render", uri.fsPath, "-o", output];
+  if (lens.target !== undefined) {
+    args.push("--target", lens.target);
+  }
+  if (lens.section !== undefined) {
+    args.push("--section", lens.section);
+  }
+
+  const relanote = vscode.workspace
+    .getConfiguration("relanote")
+    .get<string>("lsp.path", "relanote");
+  return new Promise((resolve) => {
+    execFile(relanote, args, (error, _stdout, stderr) => {
+      if (error) {
+        vscode.window.showErrorMessage(
+          `Relanote render failed: ${stderr.trim() || error.message}`
+        );
+        resolve(false);
+      } else {
+        resolve(true);
+      }
+    });
+  });
+}
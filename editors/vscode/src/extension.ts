@@ -1,5 +1,6 @@
 import * as vscode from "vscode";
 import { startClient, stopClient, restartClient, getClient } from "./client";
+import { registerCommands } from "./commands";
 
 export async function activate(
   context: vscode.ExtensionContext
@@ -19,6 +20,8 @@ export async function activate(
     }
   }
 
+  registerCommands(context);
+
   context.subscriptions.push(
     vscode.commands.registerCommand("relanote.restartServer", async () => {
       const client = getClient();
@@ -0,0 +1,29 @@
+//! Evaluate arbitrary programs that parse, and render the music they make
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use relanote_eval::value::SongValue;
+use relanote_eval::{Evaluator, Value};
+use relanote_render::{MidiRenderer, RenderContext};
+
+fuzz_target!(|data: &[u8]| {
+    let (program, diagnostics) = relanote_parser::parse_arbitrary(data);
+    if diagnostics.has_errors() {
+        return;
+    }
+    let mut evaluator = Evaluator::new();
+    if let Some(song) = evaluator
+        .eval_program(&program)
+        .ok()
+        .and_then(Value::into_song)
+    {
+        render(&evaluator, &song);
+    }
+});
+
+fn render(evaluator: &Evaluator, song: &SongValue) {
+    let renderer = MidiRenderer::new(RenderContext::from_evaluator(evaluator).midi_config());
+    let _ = renderer.render(song);
+    let _ = renderer.inspect(song);
+}
@@ -0,0 +1,13 @@
+//! Lex arbitrary text to the end
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use relanote_core::Source;
+use relanote_lexer::Lexer;
+
+fuzz_target!(|content: String| {
+    let source = Source::from_string("<fuzz>", content);
+    let mut lexer = Lexer::new(&source);
+    while lexer.next_token().is_some() {}
+});
@@ -0,0 +1,9 @@
+//! Parse arbitrary bytes
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = relanote_parser::parse_arbitrary(data);
+});
@@ -1,10 +1,35 @@
-import type { AudioNoteEvent, SynthData, ADSRData } from "../types/relanote";
+import type {
+  AudioNoteEvent,
+  SynthData,
+  ADSRData,
+  BusData,
+  EffectData,
+  SendData,
+} from "../types/relanote";
 
 interface Voice {
   oscillators: OscillatorNode[];
-  noiseSource?: AudioBufferSourceNode;
+  noiseSources: AudioBufferSourceNode[];
+  sampleSources: AudioBufferSourceNode[];
+  lfos: OscillatorNode[];
   gainNode: GainNode;
   filterNode?: BiquadFilterNode;
+  tremoloNode?: GainNode;
+  duckNode?: GainNode;
+}
+
+type NoiseColour = "noise" | "pink_noise" | "brown_noise";
+
+// A point of a ducked note's gain, in seconds from the note's start
+interface GainStep {
+  time: number;
+  gain: number;
+}
+
+// Where a part's notes go: its effects, then the buses it sends to
+interface Route {
+  effects: EffectData[];
+  sends: SendData[];
 }
 
 // Create a unique key for each voice (synth name + pitch)
@@ -23,11 +48,18 @@ const DEFAULT_ADSR: ADSRData = {
 export function useAudioSynth() {
   let audioContext: AudioContext | null = null;
   let masterGain: GainNode | null = null;
+  // After the master bus's effects, before the room ambience
+  let masterOut: GainNode | null = null;
   let reverbNode: ConvolverNode | null = null;
   let reverbGain: GainNode | null = null;
   let dryGain: GainNode | null = null;
-  let noiseBuffer: AudioBuffer | null = null;
+  let noiseBuffers: Record<NoiseColour, AudioBuffer> | null = null;
+  const sampleBuffers = new Map<string, Promise<AudioBuffer | null>>();
   const activeVoices = new Map<string, Voice>();
+  // Inputs of the buses and of each part's effect chain, built as songs set them
+  const busInputs = new Map<string, AudioNode>();
+  const routeInputs = new Map<string, AudioNode>();
+  let mixNodes: AudioNode[] = [];
 
   const isInitialized = ref(false);
 
@@ -77,17 +109,24 @@ export function useAudioSynth() {
     dryGain = audioContext.createGain();
     dryGain.gain.value = 0.7; // Dry level
 
-    // Connect: masterGain -> dryGain -> destination
-    //          masterGain -> reverbNode -> reverbGain -> destination
-    masterGain.connect(dryGain);
+    masterOut = audioContext.createGain();
+    masterGain.connect(masterOut);
+
+    // Connect: masterOut -> dryGain -> destination
+    //          masterOut -> reverbNode -> reverbGain -> destination
+    masterOut.connect(dryGain);
     dryGain.connect(audioContext.destination);
 
-    masterGain.connect(reverbNode);
+    masterOut.connect(reverbNode);
     reverbNode.connect(reverbGain);
     reverbGain.connect(audioContext.destination);
 
-    // Create noise buffer for noise oscillators
-    noiseBuffer = createNoiseBuffer(audioContext);
+    // Create noise buffers for noise oscillators
+    noiseBuffers = {
+      noise: createNoiseBuffer(audioContext, "noise"),
+      pink_noise: createNoiseBuffer(audioContext, "pink_noise"),
+      brown_noise: createNoiseBuffer(audioContext, "brown_noise"),
+    };
 
     // Resume context if suspended (required by browsers)
     await ensureContextRunning();
@@ -95,13 +134,31 @@ export function useAudioSynth() {
     isInitialized.value = true;
   };
 
-  // Create white noise buffer
-  const createNoiseBuffer = (ctx: AudioContext): AudioBuffer => {
+  // Create a noise buffer: white, or filtered into pink or brown as the
+  // relanote_synth engine does
+  const createNoiseBuffer = (ctx: AudioContext, colour: NoiseColour): AudioBuffer => {
     const bufferSize = ctx.sampleRate * 2; // 2 seconds of noise
     const buffer = ctx.createBuffer(1, bufferSize, ctx.sampleRate);
     const data = buffer.getChannelData(0);
+    const b = [0, 0, 0, 0, 0, 0, 0];
     for (let i = 0; i < bufferSize; i++) {
-      data[i] = Math.random() * 2 - 1;
+      const white = Math.random() * 2 - 1;
+      if (colour === "pink_noise") {
+        // Paul Kellet's filter
+        b[0] = 0.99886 * b[0] + white * 0.0555179;
+        b[1] = 0.99332 * b[1] + white * 0.0750759;
+        b[2] = 0.969 * b[2] + white * 0.153852;
+        b[3] = 0.8665 * b[3] + white * 0.3104856;
+        b[4] = 0.55 * b[4] + white * 0.5329522;
+        b[5] = -0.7616 * b[5] - white * 0.016898;
+        data[i] = (b[0] + b[1] + b[2] + b[3] + b[4] + b[5] + b[6] + white * 0.5362) * 0.11;
+        b[6] = white * 0.115926;
+      } else if (colour === "brown_noise") {
+        b[0] = (b[0] + 0.02 * white) / 1.02;
+        data[i] = b[0] * 3.5;
+      } else {
+        data[i] = white;
+      }
     }
     return buffer;
   };
@@ -135,12 +192,265 @@ export function useAudioSynth() {
     return oscillator;
   };
 
+  // Shaping curve of a distortion type, driven harder as amount rises
+  const distortionCurve = (curve: string, amount: number): Float32Array => {
+    const size = 1024;
+    const samples = new Float32Array(size);
+    const drive = 1 + amount * 50;
+    const steps = Math.pow(2, Math.max(2, Math.round(16 - amount * 14)));
+    for (let i = 0; i < size; i++) {
+      const x = (i * 2) / size - 1;
+      if (curve === "hard") {
+        samples[i] = Math.max(-1, Math.min(1, x * drive));
+      } else if (curve === "fuzz") {
+        samples[i] = x >= 0 ? Math.tanh(x * drive) : Math.tanh(x * drive * 0.5) * 0.7;
+      } else if (curve === "bitcrush") {
+        samples[i] = Math.round(x * steps) / steps;
+      } else {
+        samples[i] = Math.tanh(x * drive) / Math.tanh(drive);
+      }
+    }
+    return samples;
+  };
+
+  // One effect, between `input` and `output`; the dry sound passes through
+  // alongside the effect as its mix leaves it
+  const createEffect = (ctx: AudioContext, effect: EffectData) => {
+    const input = ctx.createGain();
+    const output = ctx.createGain();
+    const dry = ctx.createGain();
+    const wet = ctx.createGain();
+    const nodes: AudioNode[] = [input, output, dry, wet];
+    input.connect(dry);
+    dry.connect(output);
+    wet.connect(output);
+
+    if (effect.type === "reverb") {
+      const convolver = ctx.createConvolver();
+      convolver.buffer = createReverbImpulse(ctx, 2.5, 2.5);
+      wet.gain.value = effect.level;
+      input.connect(convolver);
+      convolver.connect(wet);
+      nodes.push(convolver);
+    } else if (effect.type === "delay") {
+      const delay = ctx.createDelay(2);
+      delay.delayTime.value = effect.time_ms / 1000;
+      const feedback = ctx.createGain();
+      feedback.gain.value = effect.feedback;
+      wet.gain.value = effect.mix;
+      input.connect(delay);
+      delay.connect(feedback);
+      feedback.connect(delay);
+      delay.connect(wet);
+      nodes.push(delay, feedback);
+    } else if (effect.type === "phaser") {
+      // Four all-pass stages swept by an LFO
+      dry.gain.value = 1 - effect.mix / 2;
+      wet.gain.value = effect.mix / 2;
+      const lfo = ctx.createOscillator();
+      lfo.frequency.value = effect.rate;
+      const sweep = ctx.createGain();
+      sweep.gain.value = effect.depth * 800;
+      lfo.connect(sweep);
+      let previous: AudioNode = input;
+      for (let i = 0; i < 4; i++) {
+        const stage = ctx.createBiquadFilter();
+        stage.type = "allpass";
+        stage.frequency.value = 1000;
+        sweep.connect(stage.frequency);
+        previous.connect(stage);
+        previous = stage;
+        nodes.push(stage);
+      }
+      previous.connect(wet);
+      lfo.start();
+      nodes.push(lfo, sweep);
+    } else if (effect.type === "compressor") {
+      // Compressor and EQ are inserts: all of the sound goes through them
+      dry.gain.value = 0;
+      const compressor = ctx.createDynamicsCompressor();
+      compressor.threshold.value = effect.threshold;
+      compressor.ratio.value = effect.ratio;
+      compressor.attack.value = effect.attack_ms / 1000;
+      compressor.release.value = effect.release_ms / 1000;
+      input.connect(compressor);
+      compressor.connect(wet);
+      nodes.push(compressor);
+    } else if (effect.type === "eq") {
+      dry.gain.value = 0;
+      const bands: Array<[BiquadFilterType, number, number]> = [
+        ["lowshelf", 250, effect.low],
+        ["peaking", 1000, effect.mid],
+        ["highshelf", 4000, effect.high],
+      ];
+      let previous: AudioNode = input;
+      for (const [type, frequency, gain] of bands) {
+        const band = ctx.createBiquadFilter();
+        band.type = type;
+        band.frequency.value = frequency;
+        band.gain.value = gain;
+        previous.connect(band);
+        previous = band;
+        nodes.push(band);
+      }
+      previous.connect(wet);
+    } else if (effect.type === "chorus") {
+      // A short delay on each side, swept in opposite directions
+      dry.gain.value = 1 - effect.mix / 2;
+      wet.gain.value = effect.mix;
+      const lfo = ctx.createOscillator();
+      lfo.frequency.value = effect.rate;
+      const merger = ctx.createChannelMerger(2);
+      nodes.push(lfo, merger);
+      for (const [channel, direction] of [
+        [0, 1],
+        [1, -1],
+      ]) {
+        const delay = ctx.createDelay(0.1);
+        delay.delayTime.value = 0.02;
+        const sweep = ctx.createGain();
+        sweep.gain.value = direction * effect.depth * 0.005;
+        lfo.connect(sweep);
+        sweep.connect(delay.delayTime);
+        input.connect(delay);
+        delay.connect(merger, 0, channel);
+        nodes.push(delay, sweep);
+      }
+      merger.connect(wet);
+      lfo.start();
+    } else if (effect.type === "width") {
+      // Mid/side: the difference between the sides is scaled by the amount
+      dry.gain.value = 0;
+      // Mono voices come in on both sides, so they stay centred
+      input.channelCount = 2;
+      input.channelCountMode = "explicit";
+      input.channelInterpretation = "speakers";
+      const splitter = ctx.createChannelSplitter(2);
+      const merger = ctx.createChannelMerger(2);
+      input.connect(splitter);
+      const same = (1 + effect.amount) / 2;
+      const other = (1 - effect.amount) / 2;
+      for (const [from, to, level] of [
+        [0, 0, same],
+        [1, 0, other],
+        [0, 1, other],
+        [1, 1, same],
+      ]) {
+        const gain = ctx.createGain();
+        gain.gain.value = level;
+        splitter.connect(gain, from);
+        gain.connect(merger, 0, to);
+        nodes.push(gain);
+      }
+      merger.connect(wet);
+      nodes.push(splitter, merger);
+    } else {
+      const shaper = ctx.createWaveShaper();
+      shaper.curve = distortionCurve(effect.curve, effect.amount);
+      shaper.oversample = "4x";
+      dry.gain.value = 1 - effect.mix;
+      wet.gain.value = effect.mix;
+      input.connect(shaper);
+      shaper.connect(wet);
+      nodes.push(shaper);
+    }
+
+    mixNodes.push(...nodes);
+    return { input, output };
+  };
+
+  // A chain of effects into `destination`; returns where sound goes in
+  const createChain = (
+    ctx: AudioContext,
+    effects: EffectData[],
+    destination: AudioNode
+  ): AudioNode => {
+    let next = destination;
+    for (const effect of [...effects].reverse()) {
+      const { input, output } = createEffect(ctx, effect);
+      output.connect(next);
+      next = input;
+    }
+    return next;
+  };
+
+  // Set up the buses and master bus notes play through, replacing the last
+  // song's
+  const setMix = (buses: BusData[], master: EffectData[]) => {
+    if (!audioContext || !masterGain || !masterOut) return;
+    for (const node of mixNodes) {
+      if (node instanceof OscillatorNode) node.stop();
+      node.disconnect();
+    }
+    mixNodes = [];
+    busInputs.clear();
+    routeInputs.clear();
+
+    masterGain.disconnect();
+    masterGain.connect(createChain(audioContext, master, masterOut));
+    for (const bus of buses) {
+      busInputs.set(bus.name, createChain(audioContext, bus.effects, masterGain));
+    }
+  };
+
+  // Where a part's notes go in: through its effects to the master bus, and
+  // from there to its buses; parts with the same effects and sends share one
+  const routeInput = (route?: Route): AudioNode | null => {
+    if (!audioContext || !masterGain) return null;
+    if (!route || (route.effects.length === 0 && route.sends.length === 0)) {
+      return masterGain;
+    }
+    const key = JSON.stringify(route);
+    let input = routeInputs.get(key);
+    if (!input) {
+      const output = audioContext.createGain();
+      output.connect(masterGain);
+      for (const send of route.sends) {
+        const bus = busInputs.get(send.bus);
+        if (!bus) continue;
+        const level = audioContext.createGain();
+        level.gain.value = send.level;
+        output.connect(level);
+        level.connect(bus);
+        mixNodes.push(level);
+      }
+      mixNodes.push(output);
+      input = createChain(audioContext, route.effects, output);
+      routeInputs.set(key, input);
+    }
+    return input;
+  };
+
   const midiToFrequency = (midiNote: number): number => {
     return 440 * Math.pow(2, (midiNote - 69) / 12);
   };
 
-  const noteOn = async (midiNote: number, velocity: number = 100, synth?: SynthData) => {
+  // Decode a sample's WAV file once; files that can't be fetched play silence
+  const loadSample = (path: string): Promise<AudioBuffer | null> => {
+    const ctx = audioContext;
+    if (!ctx) return Promise.resolve(null);
+    let buffer = sampleBuffers.get(path);
+    if (!buffer) {
+      buffer = fetch(path)
+        .then((response) => response.arrayBuffer())
+        .then((data) => ctx.decodeAudioData(data))
+        .catch(() => null);
+      sampleBuffers.set(path, buffer);
+    }
+    return buffer;
+  };
+
+  const noteOn = async (
+    midiNote: number,
+    velocity: number = 100,
+    synth?: SynthData,
+    envelope?: ADSRData,
+    route?: Route,
+    ducking?: GainStep[]
+  ) => {
     if (!audioContext || !masterGain) return;
+    const output = routeInput(route);
+    if (!output) return;
 
     // Ensure context is running (may be suspended after tab switch)
     await ensureContextRunning();
@@ -150,13 +460,14 @@ export function useAudioSynth() {
     // Stop existing voice on same note (same synth + pitch)
     noteOffByKey(voiceKey);
 
-    const adsr = synth?.envelope || DEFAULT_ADSR;
+    const adsr = envelope || synth?.envelope || DEFAULT_ADSR;
     const baseFreq = midiToFrequency(midiNote);
     const volume = (velocity / 127) * 0.5;
 
     const gainNode = audioContext.createGain();
     const oscillators: OscillatorNode[] = [];
-    let noiseSource: AudioBufferSourceNode | undefined;
+    const noiseSources: AudioBufferSourceNode[] = [];
+    const sampleSources: AudioBufferSourceNode[] = [];
     let filterNode: BiquadFilterNode | undefined;
 
     // Create filter if synth has one
@@ -174,11 +485,15 @@ export function useAudioSynth() {
         // Calculate frequency with octave offset
         const freq = baseFreq * Math.pow(2, oscData.octave_offset);
 
-        if (oscData.waveform === "noise") {
+        if (
+          oscData.waveform === "noise" ||
+          oscData.waveform === "pink_noise" ||
+          oscData.waveform === "brown_noise"
+        ) {
           // Create noise source
-          if (noiseBuffer) {
-            noiseSource = audioContext.createBufferSource();
-            noiseSource.buffer = noiseBuffer;
+          if (noiseBuffers) {
+            const noiseSource = audioContext.createBufferSource();
+            noiseSource.buffer = noiseBuffers[oscData.waveform];
             noiseSource.loop = true;
 
             const noiseGain = audioContext.createGain();
@@ -191,6 +506,32 @@ export function useAudioSynth() {
               noiseGain.connect(gainNode);
             }
             noiseSource.start();
+            noiseSources.push(noiseSource);
+          }
+        } else if (oscData.waveform === "sample" && oscData.sample) {
+          // Play the WAV file, faster the further the note is above its root
+          const buffer = await loadSample(oscData.sample.path);
+          if (buffer) {
+            const source = audioContext.createBufferSource();
+            source.buffer = buffer;
+            source.playbackRate.value = freq / midiToFrequency(oscData.sample.root);
+            source.detune.value = oscData.detune_cents + (synth.detune_cents || 0);
+            if (oscData.sample.loop_points) {
+              source.loop = true;
+              [source.loopStart, source.loopEnd] = oscData.sample.loop_points;
+            }
+
+            const sampleGain = audioContext.createGain();
+            sampleGain.gain.value = oscData.mix;
+
+            source.connect(sampleGain);
+            if (filterNode) {
+              sampleGain.connect(filterNode);
+            } else {
+              sampleGain.connect(gainNode);
+            }
+            source.start();
+            sampleSources.push(source);
           }
         } else if (oscData.waveform === "pulse") {
           // Create pulse wave oscillator
@@ -243,6 +584,38 @@ export function useAudioSynth() {
       filterNode.connect(gainNode);
     }
 
+    // LFOs: pitch bends the oscillators and samples (in cents), cutoff
+    // moves the filter (in Hz), amplitude swells a tremolo gain
+    const lfos: OscillatorNode[] = [];
+    let tremoloNode: GainNode | undefined;
+    for (const lfoData of synth?.lfos ?? []) {
+      if (lfoData.target === "cutoff" && !filterNode) continue;
+
+      const lfo = audioContext.createOscillator();
+      lfo.type = lfoData.shape;
+      lfo.frequency.value = lfoData.rate;
+      const depth = audioContext.createGain();
+      lfo.connect(depth);
+
+      if (lfoData.target === "pitch") {
+        depth.gain.value = lfoData.depth * 100;
+        for (const osc of oscillators) depth.connect(osc.detune);
+        for (const source of sampleSources) depth.connect(source.detune);
+      } else if (lfoData.target === "cutoff" && filterNode) {
+        depth.gain.value = lfoData.depth;
+        depth.connect(filterNode.frequency);
+      } else {
+        // Swing between full level and 1 - depth
+        tremoloNode ??= audioContext.createGain();
+        const swing = Math.min(Math.max(lfoData.depth, 0), 1) / 2;
+        tremoloNode.gain.value -= swing;
+        depth.gain.value = swing;
+        depth.connect(tremoloNode.gain);
+      }
+      lfo.start();
+      lfos.push(lfo);
+    }
+
     // ADSR envelope
     const now = audioContext.currentTime;
     gainNode.gain.setValueAtTime(0, now);
@@ -265,9 +638,34 @@ export function useAudioSynth() {
       }
     }
 
-    gainNode.connect(masterGain);
-
-    activeVoices.set(voiceKey, { oscillators, noiseSource, gainNode, filterNode });
+    // Ducking dips the note after its envelope, on a gain of its own
+    let voiceOut: AudioNode = gainNode;
+    if (tremoloNode) {
+      voiceOut.connect(tremoloNode);
+      voiceOut = tremoloNode;
+    }
+    let duckNode: GainNode | undefined;
+    if (ducking && ducking.length > 0) {
+      duckNode = audioContext.createGain();
+      duckNode.gain.setValueAtTime(ducking[0].gain, now);
+      for (const step of ducking.slice(1)) {
+        duckNode.gain.linearRampToValueAtTime(step.gain, now + step.time);
+      }
+      voiceOut.connect(duckNode);
+      voiceOut = duckNode;
+    }
+    voiceOut.connect(output);
+
+    activeVoices.set(voiceKey, {
+      oscillators,
+      noiseSources,
+      sampleSources,
+      lfos,
+      gainNode,
+      filterNode,
+      tremoloNode,
+      duckNode,
+    });
   };
 
   const noteOffByKey = (voiceKey: string) => {
@@ -275,7 +673,16 @@ export function useAudioSynth() {
 
     const voice = activeVoices.get(voiceKey);
     if (voice) {
-      const { oscillators, noiseSource, gainNode, filterNode } = voice;
+      const {
+        oscillators,
+        noiseSources,
+        sampleSources,
+        lfos,
+        gainNode,
+        filterNode,
+        tremoloNode,
+        duckNode,
+      } = voice;
       const now = audioContext.currentTime;
 
       // Get release time from current gain envelope or use default
@@ -291,14 +698,20 @@ export function useAudioSynth() {
           osc.stop();
           osc.disconnect();
         }
-        if (noiseSource) {
-          noiseSource.stop();
-          noiseSource.disconnect();
+        for (const source of [...noiseSources, ...sampleSources, ...lfos]) {
+          source.stop();
+          source.disconnect();
         }
         gainNode.disconnect();
         if (filterNode) {
           filterNode.disconnect();
         }
+        if (tremoloNode) {
+          tremoloNode.disconnect();
+        }
+        if (duckNode) {
+          duckNode.disconnect();
+        }
       }, releaseTime * 1000 + 50);
 
       activeVoices.delete(voiceKey);
@@ -322,6 +735,15 @@ export function useAudioSynth() {
     await init();
     if (!audioContext) return;
 
+    // Load every sample up front, so no note waits for its file
+    const samplePaths = new Set<string>();
+    for (const note of notes) {
+      for (const osc of note.synth?.oscillators ?? []) {
+        if (osc.sample) samplePaths.add(osc.sample.path);
+      }
+    }
+    await Promise.all([...samplePaths].map(loadSample));
+
     const beatsPerSecond = tempo / 60;
     const startTime = audioContext.currentTime;
 
@@ -332,6 +754,9 @@ export function useAudioSynth() {
       pitch: number;
       velocity: number;
       synth?: SynthData;
+      envelope?: ADSRData;
+      route?: Route;
+      ducking?: GainStep[];
     }> = [];
 
     for (const note of notes) {
@@ -344,6 +769,12 @@ export function useAudioSynth() {
         pitch: note.pitch,
         velocity: note.velocity,
         synth: note.synth,
+        envelope: note.envelope,
+        route: { effects: note.effects ?? [], sends: note.sends ?? [] },
+        ducking: (note.gain ?? []).map((point) => ({
+          time: point.beat / beatsPerSecond,
+          gain: point.gain,
+        })),
       });
       scheduledEvents.push({
         time: noteEndTime,
@@ -386,7 +817,14 @@ export function useAudioSynth() {
       }
 
       if (event.type === "on") {
-        await noteOn(event.pitch, event.velocity, event.synth);
+        await noteOn(
+          event.pitch,
+          event.velocity,
+          event.synth,
+          event.envelope,
+          event.route,
+          event.ducking
+        );
       } else {
         noteOff(event.pitch, event.synth?.name);
       }
@@ -409,11 +847,16 @@ export function useAudioSynth() {
       audioContext.close();
       audioContext = null;
       masterGain = null;
+      masterOut = null;
       reverbNode = null;
       reverbGain = null;
       dryGain = null;
-      noiseBuffer = null;
+      noiseBuffers = null;
     }
+    sampleBuffers.clear();
+    busInputs.clear();
+    routeInputs.clear();
+    mixNodes = [];
     isInitialized.value = false;
   };
 
@@ -424,6 +867,7 @@ export function useAudioSynth() {
     noteOff,
     stopAll,
     playNotes,
+    setMix,
     close,
   };
 }
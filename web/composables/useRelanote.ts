@@ -2,15 +2,22 @@ import type {
   AnalysisResult,
   FormatResult,
   RenderResult,
+  RenderTarget,
   StaffData,
   AudioPlaybackData,
+  AudioEventWindow,
   PianoRollNote,
+  QuantizeOptions,
   CompletionItem,
   HoverResult,
 } from "../types/relanote";
 
 let wasmModule: typeof import("../wasm/pkg/relanote_wasm") | null = null;
 let initPromise: Promise<void> | null = null;
+// Parses and evaluates the active document once for all of its views
+let session: InstanceType<
+  typeof import("../wasm/pkg/relanote_wasm").RelanoteSession
+> | null = null;
 
 async function initWasm() {
   if (wasmModule) return;
@@ -47,9 +54,9 @@ export function useRelanote() {
     return wasmModule.analyze(source) as AnalysisResult;
   };
 
-  const format = (source: string): FormatResult | null => {
+  const format = (source: string, config?: string): FormatResult | null => {
     if (!wasmModule) return null;
-    return wasmModule.format_code(source) as FormatResult;
+    return wasmModule.format_code(source, config) as FormatResult;
   };
 
   const renderMidi = (source: string): RenderResult | null => {
@@ -57,6 +64,11 @@ export function useRelanote() {
     return wasmModule.render_midi(source) as RenderResult;
   };
 
+  const listRenderTargets = (source: string): RenderTarget[] | null => {
+    if (!wasmModule) return null;
+    return wasmModule.list_render_targets(source) as RenderTarget[];
+  };
+
   const getStaffData = (source: string): StaffData | null => {
     if (!wasmModule) return null;
     return wasmModule.get_staff_data(source) as StaffData;
@@ -81,7 +93,8 @@ export function useRelanote() {
   const notesToCode = (
     notes: PianoRollNote[],
     synthName?: string,
-    keyPitch?: number
+    keyPitch?: number,
+    options?: Partial<QuantizeOptions>
   ): string | null => {
     if (!wasmModule) return null;
     // Convert notes to JSON for WASM
@@ -92,12 +105,20 @@ export function useRelanote() {
       velocity: n.velocity,
     }));
     const notesJson = JSON.stringify(notesForWasm);
-    return wasmModule.notes_to_code(notesJson, synthName, keyPitch);
+    const optionsJson = options ? JSON.stringify(options) : undefined;
+    return wasmModule.notes_to_code(notesJson, synthName, keyPitch, optionsJson);
   };
 
-  const getCompletions = (): CompletionItem[] | null => {
+  // Inverse of notesToCode: the notes a program plays
+  const codeToNotes = (source: string): Omit<PianoRollNote, "id" | "selected">[] | null => {
     if (!wasmModule) return null;
-    return wasmModule.get_completions() as CompletionItem[];
+    return wasmModule.code_to_notes(source) as Omit<PianoRollNote, "id" | "selected">[];
+  };
+
+  // Builtins plus the scales, chords, synths and lets `source` defines
+  const getCompletions = (source: string): CompletionItem[] | null => {
+    if (!wasmModule) return null;
+    return wasmModule.get_completions(source) as CompletionItem[];
   };
 
   const getHover = (source: string, offset: number): HoverResult | null => {
@@ -105,6 +126,57 @@ export function useRelanote() {
     return wasmModule.get_hover(source, offset) as HoverResult;
   };
 
+  const analyzeDocument = (
+    source: string
+  ): {
+    analysis: AnalysisResult;
+    staff: StaffData;
+    audio: AudioPlaybackData;
+    midi: RenderResult;
+  } | null => {
+    if (!wasmModule) return null;
+    if (!session) {
+      session = new wasmModule.RelanoteSession(source);
+      // Edits patch the evaluation, so a looping song picks them up cheaply
+      session.set_live(true);
+    } else {
+      session.update(source);
+    }
+    return {
+      analysis: session.analyze() as AnalysisResult,
+      staff: session.get_staff_data() as StaffData,
+      audio: session.get_audio_data() as AudioPlaybackData,
+      midi: session.render_midi() as RenderResult,
+    };
+  };
+
+  // Notes of the last analyzed document in a window, for long songs
+  const getAudioEvents = (
+    fromBeat: number,
+    toBeat: number
+  ): AudioEventWindow | null => {
+    if (!session) return null;
+    return session.get_audio_events(fromBeat, toBeat) as AudioEventWindow;
+  };
+
+  // Modules registered with WASM, so `use` can resolve the other open files
+  const registeredModules = new Set<string>();
+
+  const syncModules = (modules: Array<{ name: string; content: string }>) => {
+    if (!wasmModule) return;
+    const names = new Set(modules.map((m) => m.name));
+    for (const name of registeredModules) {
+      if (!names.has(name)) {
+        wasmModule.remove_module(name);
+        registeredModules.delete(name);
+      }
+    }
+    for (const m of modules) {
+      wasmModule.add_module(m.name, m.content);
+      registeredModules.add(m.name);
+    }
+  };
+
   return {
     isReady,
     error,
@@ -112,11 +184,16 @@ export function useRelanote() {
     analyze,
     format,
     renderMidi,
+    listRenderTargets,
     getStaffData,
     getAudioData,
     getTokens,
     notesToCode,
+    codeToNotes,
     getCompletions,
     getHover,
+    syncModules,
+    analyzeDocument,
+    getAudioEvents,
   };
 }
@@ -25,7 +25,7 @@ export default defineNuxtConfig({
 
   vite: {
     optimizeDeps: {
-      exclude: ["relanote-wasm"],
+      exclude: ["relanote_wasm"],
       include: ["monaco-editor"],
     },
   },
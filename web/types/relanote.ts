@@ -2,7 +2,8 @@ export interface WasmDiagnostic {
   message: string;
   start: number;
   end: number;
-  severity: "error" | "warning" | "info";
+  severity: "error" | "warning" | "info" | "hint";
+  code?: string;
 }
 
 export interface AnalysisResult {
@@ -16,10 +17,31 @@ export interface FormatResult {
   error: string | null;
 }
 
+// A runtime error and the source range it was raised at
+export interface WasmError {
+  message: string;
+  start: number;
+  end: number;
+}
+
 export interface RenderResult {
   success: boolean;
   midi_data: number[] | null;
-  error: string | null;
+  error: WasmError | null;
+}
+
+// A `render` item of a program; the editor plays the first one
+export interface RenderTarget {
+  name: string | null; // from render "name" = ...
+  start: number;
+  end: number;
+}
+
+// How a pitch is written in the song's key
+export interface Spelling {
+  step: "C" | "D" | "E" | "F" | "G" | "A" | "B";
+  alter: number; // 1 = sharp, -1 = flat
+  octave: number;
 }
 
 export interface NoteEvent {
@@ -27,23 +49,51 @@ export interface NoteEvent {
   start: number;
   duration: number;
   velocity: number;
+  spelling: Spelling;
+  part: number; // index into StaffData.parts
+  measure: number;
+  beam: number | null; // notes with the same beam group are beamed together
+}
+
+export interface StaffPart {
+  instrument: string;
+  clef: "treble" | "bass";
 }
 
 export interface StaffData {
   notes: NoteEvent[];
+  parts: StaffPart[];
   tempo: number;
   time_signature_num: number;
   time_signature_den: number;
+  key_fifths: number; // sharps when positive, flats when negative
   total_beats: number;
+  error: WasmError | null;
 }
 
 // Synth types for WebAudio playback
 export interface OscillatorData {
-  waveform: "sine" | "square" | "sawtooth" | "triangle" | "noise" | "pulse";
+  waveform:
+    | "sine"
+    | "square"
+    | "sawtooth"
+    | "triangle"
+    | "noise"
+    | "pink_noise"
+    | "brown_noise"
+    | "pulse"
+    | "sample";
   pulse_duty: number;
   mix: number;
   octave_offset: number;
   detune_cents: number;
+  sample?: SampleData;
+}
+
+export interface SampleData {
+  path: string;
+  root: number; // MIDI note the file sounds at as is
+  loop_points?: [number, number]; // seconds
 }
 
 export interface ADSRData {
@@ -65,6 +115,13 @@ export interface PitchEnvelopeData {
   time_seconds: number;
 }
 
+export interface LfoData {
+  target: "pitch" | "cutoff" | "amplitude";
+  shape: "sine" | "triangle" | "square" | "sawtooth";
+  rate: number; // Hz
+  depth: number; // semitones for pitch, Hz for cutoff, 0-1 for amplitude
+}
+
 export interface SynthData {
   name: string;
   oscillators: OscillatorData[];
@@ -72,19 +129,87 @@ export interface SynthData {
   filter?: FilterData;
   detune_cents: number;
   pitch_envelope?: PitchEnvelopeData;
+  lfos: LfoData[];
+}
+
+// An effect in a part's or bus's chain
+export type EffectData =
+  | { type: "reverb"; level: number }
+  | { type: "delay"; time_ms: number; feedback: number; mix: number }
+  | { type: "phaser"; rate: number; depth: number; mix: number }
+  | {
+      type: "distortion";
+      amount: number;
+      curve: "soft" | "hard" | "fuzz" | "bitcrush";
+      mix: number;
+    }
+  | {
+      type: "compressor";
+      threshold: number; // dB
+      ratio: number;
+      attack_ms: number;
+      release_ms: number;
+    }
+  // Gains in dB of a low shelf at 250 Hz, a peak at 1 kHz and a high shelf at 4 kHz
+  | { type: "eq"; low: number; mid: number; high: number }
+  | { type: "chorus"; rate: number; depth: number; mix: number }
+  // 0 is mono, 1 leaves the stereo image as is, 2 is widest
+  | { type: "width"; amount: number };
+
+export interface BusData {
+  name: string;
+  effects: EffectData[];
+}
+
+export interface SendData {
+  bus: string;
+  level: number;
+}
+
+// The gain ramps from point to point and jumps where two share a beat
+export interface GainPoint {
+  beat: number; // beats from the note's start
+  gain: number;
 }
 
 export interface AudioNoteEvent extends NoteEvent {
+  pan: number; // -1.0 (left) to 1.0 (right)
   synth?: SynthData;
+  envelope?: ADSRData; // replaces the synth's envelope (apply_env)
+  effects: EffectData[]; // the part's effects, in order
+  sends: SendData[]; // buses the part sends to, after its effects
+  gain: GainPoint[]; // dips as the part is ducked (duck), empty for full gain
 }
 
 export interface AudioPlaybackData {
   notes: AudioNoteEvent[];
   tempo: number;
   total_beats: number;
+  buses: BusData[];
+  master: EffectData[]; // effects everything plays through
+}
+
+// Notes starting in [from_beat, to_beat) of the whole song
+export interface AudioEventWindow {
+  notes: AudioNoteEvent[];
+  tempo: number;
+  from_beat: number;
+  to_beat: number;
+  total_beats: number;
+  buses: BusData[];
+  master: EffectData[];
 }
 
 // DAW Types
+// How notesToCode fits notes to a rhythm grid
+export interface QuantizeOptions {
+  grid: number; // grid cells per beat (4 = 16th notes)
+  time_signature_num: number;
+  time_signature_den: number;
+  triplets: boolean; // snap beats that fit a triplet grid better to it
+  infer_key: boolean; // guess the key when none is given and write `set key`
+}
+
 export interface PianoRollNote {
   id: string;
   pitch: number; // MIDI note (0-127)
@@ -74,7 +74,7 @@ export const monarchTokensProvider: Monaco.languages.IMonarchLanguage = {
 
   articulations: ["staccato", "accent", "portamento", "legato", "tenuto"],
 
-  operators: ["|>", "|", "->", "=>", "=", ":", ",", ".", "+", "-", "*", "/"],
+  operators: ["|>", "|", "->", "=>", "=", ":", ",", ".", "+", "-", "*", "/", "%", "^"],
 
   symbols: /[=><!~?:&|+\-*\/\^%]+/,
 
@@ -141,6 +141,7 @@ export const monarchTokensProvider: Monaco.languages.IMonarchLanguage = {
 
     whitespace: [
       [/[ \t\r\n]+/, "white"],
+      [/(---|--\|).*$/, "comment.doc"],
       [/;.*$/, "comment"],
     ],
 
@@ -788,12 +789,12 @@ function getHoverContent(word: string): string | null {
 // WASM-based completion provider (uses Rust completions)
 export const createWasmCompletionProvider = (
   monaco: typeof Monaco,
-  getCompletions: () => WasmCompletionItem[] | null
+  getCompletions: (source: string) => WasmCompletionItem[] | null
 ): Monaco.languages.CompletionItemProvider => ({
   triggerCharacters: ["<", "|", ".", " "],
 
   provideCompletionItems: (model, position) => {
-    const wasmCompletions = getCompletions();
+    const wasmCompletions = getCompletions(model.getValue());
     if (!wasmCompletions) {
       // Fall back to static completions if WASM not ready
       return createCompletionProvider(monaco).provideCompletionItems(model, position, {} as Monaco.languages.CompletionContext, {} as Monaco.CancellationToken);
@@ -905,7 +906,7 @@ export function registerRelanoteLanguage(monaco: typeof Monaco) {
 // Register with WASM-based providers
 export function registerRelanoteLanguageWithWasm(
   monaco: typeof Monaco,
-  getCompletions: () => WasmCompletionItem[] | null,
+  getCompletions: (source: string) => WasmCompletionItem[] | null,
   getHover: (source: string, offset: number) => HoverResult | null
 ) {
   // Register language
@@ -1,16 +0,0 @@
-/* tslint:disable */
-/* eslint-disable */
-export const memory: WebAssembly.Memory;
-export const init: () => void;
-export const analyze: (a: number, b: number) => any;
-export const format_code: (a: number, b: number) => any;
-export const evaluate: (a: number, b: number) => any;
-export const render_midi: (a: number, b: number) => any;
-export const get_staff_data: (a: number, b: number) => any;
-export const get_tokens: (a: number, b: number) => any;
-export const get_audio_data: (a: number, b: number) => any;
-export const __wbindgen_malloc: (a: number, b: number) => number;
-export const __wbindgen_realloc: (a: number, b: number, c: number, d: number) => number;
-export const __wbindgen_free: (a: number, b: number, c: number) => void;
-export const __wbindgen_externrefs: WebAssembly.Table;
-export const __wbindgen_start: () => void;